@@ -0,0 +1,209 @@
+//! Benchmarks of the consensus peak algorithms on synthetic peaks.
+//!
+//! The peaks are generated deterministically, so results are comparable between runs.
+//! Run with `cargo bench --bench consensus`.
+
+use std::hint::black_box;
+
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use gipfelkreuzer::peaks::{
+    PeakBin, PeakData,
+    gipfelkreuzer::{AggregationBackend, CoordinateAggregation, consensus_peaks},
+    harmoniser::harmonised_consensus_peaks,
+};
+
+/// The numbers of raw peaks the algorithms are benchmarked with.
+const PEAK_COUNTS: [usize; 3] = [1_000, 10_000, 100_000];
+
+/// The numbers of raw peaks of a single peak bin the Gipfelkreuzer aggregation is benchmarked with.
+const BIN_PEAK_COUNTS: [usize; 3] = [100, 1_000, 5_000];
+
+/// A deterministic pseudo-random number generator (SplitMix64), so the benchmarks do not
+/// require additional dependencies and always use the same peaks.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    /// Returns the next pseudo-random number.
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a pseudo-random number in the range `[0, bound)`.
+    ///
+    /// # Parameters
+    ///
+    /// * `bound` - the exclusive upper bound
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}
+
+/// Generates synthetic raw peaks of a single chromosome clustered around peak sites,
+/// mimicking the peaks of the same regions called on multiple samples.
+///
+/// # Parameters
+///
+/// * `number_of_peaks` - the number of raw peaks to generate
+/// * `peaks_per_site` - the average number of raw peaks per peak site, which defines the density
+///   and thereby the size of the peak bins
+/// * `seed` - the seed of the pseudo-random number generator
+fn synthetic_peaks(number_of_peaks: usize, peaks_per_site: usize, seed: u64) -> Vec<PeakData> {
+    let mut random = SplitMix64(seed);
+    let sites = number_of_peaks.div_ceil(peaks_per_site.max(1)) as u64;
+    // Leaves on average 2 kb between peak sites.
+    let site_distance = 2_000;
+    (0..number_of_peaks)
+        .map(|id| {
+            let site = random.below(sites) * site_distance + 1_000;
+            let summit = site + random.below(100) - 50;
+            let start = summit - 50 - random.below(300);
+            let end = summit + 50 + random.below(300);
+            PeakData::new(id, start, end, summit).expect("The synthetic peaks must be valid.")
+        })
+        .collect()
+}
+
+/// Generates synthetic raw peaks of a single chromosome that overlap their neighbours,
+/// so all peaks form a single long peak bin yielding many consensus peaks.
+///
+/// # Parameters
+///
+/// * `number_of_peaks` - the number of raw peaks to generate
+/// * `seed` - the seed of the pseudo-random number generator
+fn chained_peaks(number_of_peaks: usize, seed: u64) -> Vec<PeakData> {
+    let mut random = SplitMix64(seed);
+    (0..number_of_peaks)
+        .map(|id| {
+            let summit = 1_000 + id as u64 * 100 + random.below(50);
+            let start = summit - 150 - random.below(100);
+            let end = summit + 150 + random.below(100);
+            PeakData::new(id, start, end, summit).expect("The synthetic peaks must be valid.")
+        })
+        .collect()
+}
+
+/// Benchmarks the grouping of raw peaks into peak bins.
+fn bench_bin_peaks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bin_peaks");
+    for number_of_peaks in PEAK_COUNTS {
+        let peaks = synthetic_peaks(number_of_peaks, 10, 42);
+        group.throughput(Throughput::Elements(number_of_peaks as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(number_of_peaks), &peaks, |b, peaks| {
+            b.iter_batched(
+                || peaks.clone(),
+                |peaks| black_box(PeakBin::bin_peaks(peaks)),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// Benchmarks the Gipfelkreuzer algorithm on peaks of moderate density.
+fn bench_gipfelkreuzer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("gipfelkreuzer");
+    for number_of_peaks in PEAK_COUNTS {
+        let peaks = synthetic_peaks(number_of_peaks, 10, 42);
+        group.throughput(Throughput::Elements(number_of_peaks as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(number_of_peaks), &peaks, |b, peaks| {
+            b.iter_batched(
+                || peaks.clone(),
+                |peaks| {
+                    black_box(consensus_peaks(
+                        peaks,
+                        20,
+                        0,
+                        None,
+                        CoordinateAggregation::Median,
+                        None,
+                        AggregationBackend::Linear,
+                    ))
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// Benchmarks the quadratic aggregation of the Gipfelkreuzer algorithm within a single
+/// peak bin with both aggregation backends. The peaks either belong to the same peak site
+/// or form a chain of overlapping peak sites.
+fn bench_gipfelkreuzer_single_bin(c: &mut Criterion) {
+    let mut group = c.benchmark_group("gipfelkreuzer_single_bin");
+    group.sample_size(10);
+    let inputs = BIN_PEAK_COUNTS.into_iter().flat_map(|number_of_peaks| {
+        [
+            ("site", synthetic_peaks(number_of_peaks, number_of_peaks, 42)),
+            ("chain", chained_peaks(number_of_peaks, 42)),
+        ]
+    });
+    for (layout, peaks) in inputs {
+        let number_of_peaks = peaks.len();
+        assert_eq!(
+            PeakBin::bin_peaks(peaks.clone()).len(),
+            1,
+            "The peaks of a single site must form a single peak bin."
+        );
+        group.throughput(Throughput::Elements(number_of_peaks as u64));
+        for backend in [AggregationBackend::Linear, AggregationBackend::Indexed] {
+            group.bench_with_input(
+                BenchmarkId::new(format!("{}/{}", layout, backend), number_of_peaks),
+                &peaks,
+                |b, peaks| {
+                    b.iter_batched(
+                        || peaks.clone(),
+                        |peaks| {
+                            black_box(consensus_peaks(
+                                peaks,
+                                20,
+                                0,
+                                None,
+                                CoordinateAggregation::Median,
+                                None,
+                                backend,
+                            ))
+                        },
+                        BatchSize::LargeInput,
+                    )
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+/// Benchmarks the harmonised algorithm on peaks of moderate density.
+fn bench_harmoniser(c: &mut Criterion) {
+    let mut group = c.benchmark_group("harmoniser");
+    for number_of_peaks in PEAK_COUNTS {
+        let peaks = synthetic_peaks(number_of_peaks, 10, 42);
+        group.throughput(Throughput::Elements(number_of_peaks as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(number_of_peaks), &peaks, |b, peaks| {
+            b.iter_batched(
+                || peaks.clone(),
+                |peaks| {
+                    black_box(
+                        harmonised_consensus_peaks(peaks, 250, 0)
+                            .expect("The harmonised consensus peaks must be created."),
+                    )
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_bin_peaks,
+    bench_gipfelkreuzer,
+    bench_gipfelkreuzer_single_bin,
+    bench_harmoniser
+);
+criterion_main!(benches);
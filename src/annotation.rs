@@ -0,0 +1,357 @@
+//! This module labels peaks with the categories of overlapping regions of a reference
+//! annotation, such as the promoter-like and enhancer-like classes of the
+//! [ENCODE cCREs](https://screen.encodeproject.org/).
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+};
+
+use getset::{CopyGetters, Getters};
+
+use crate::{
+    chromosome::Chromosome,
+    error::{ApplicationError, ApplicationErrorType, ErrorContext},
+    interval_tree::{Interval, IntervalTree},
+    peaks::PeakData,
+    retry::{Retrying, retry},
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "annotation";
+
+/// The default 1-based column of the annotation categories, which is the BED name column.
+pub const DEFAULT_CATEGORY_COLUMN: usize = 4;
+
+/// The label of peaks not overlapping any annotated region.
+pub const UNANNOTATED_LABEL: &str = ".";
+
+/// An annotated reference region.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct AnnotatedRegion {
+    /// The start coordinate.
+    start: u64,
+    /// The end coordinate (inclusive).
+    end: u64,
+    /// The indices of the categories of the region.
+    categories: Vec<usize>,
+}
+
+impl Interval for AnnotatedRegion {
+    fn start(&self) -> u64 {
+        self.start
+    }
+
+    fn end(&self) -> u64 {
+        self.end
+    }
+}
+
+/// A reference annotation of genomic regions with one or more categories each.
+#[derive(Clone, PartialEq, Debug)]
+pub struct RegionAnnotation {
+    /// The names of all categories.
+    categories: Vec<String>,
+    /// The annotated regions per chromosome.
+    regions: HashMap<Chromosome, IntervalTree<AnnotatedRegion>>,
+}
+
+impl RegionAnnotation {
+    /// Parses a BED3+ reference annotation.
+    /// The categories of each region are read from the specified column, where multiple
+    /// categories are separated by commas, as for example in the classification column (6)
+    /// of the ENCODE cCRE files.
+    /// Blank lines as well as comment, track and browser lines are skipped.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - the path of the reference annotation
+    /// * `category_column` - the 1-based column containing the categories
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or a line is invalid.
+    pub fn from_bed_file<T: AsRef<Path>>(
+        path: T,
+        category_column: usize,
+    ) -> Result<Self, ApplicationError> {
+        let path = path.as_ref();
+        log::info!(target: LOG_TARGET, "Parsing the reference annotation {}.", path.display());
+        let description = format!("Reading {}", path.display());
+        let read_error = |err: std::io::Error| {
+            ApplicationError::from(err).chain(format!(
+                "The reference annotation \"{}\" could not be read.",
+                path.display()
+            ))
+        };
+        let file = retry(&description, || File::open(path)).map_err(read_error)?;
+        let reader = BufReader::new(Retrying::new(file, &description));
+        let mut category_indices: HashMap<String, usize> = HashMap::new();
+        let mut categories: Vec<String> = Vec::new();
+        let mut regions: HashMap<Chromosome, Vec<AnnotatedRegion>> = HashMap::new();
+        for (line_index, line) in reader.lines().enumerate() {
+            let line = line.map_err(read_error)?;
+            let line_context = ErrorContext::new()
+                .with_path(path.display())
+                .with_line(line_index as u64 + 1);
+            let fields: Vec<&str> = line.split('\t').collect();
+            if line.trim().is_empty()
+                || ["#", "track", "browser"]
+                    .iter()
+                    .any(|prefix| line.starts_with(prefix))
+            {
+                continue;
+            }
+            let invalid_line = |message: String| {
+                ApplicationError::new(ApplicationErrorType::ParseError, message)
+                    .with_context(line_context.clone())
+                    .with_hint(format!(
+                        "Reference annotations require the tab separated columns chrom, \
+                        chromStart, chromEnd and the categories in column {}.",
+                        category_column
+                    ))
+            };
+            if fields.len() < 3.max(category_column) {
+                return Err(invalid_line(format!(
+                    "The line contains {} instead of at least {} columns.",
+                    fields.len(),
+                    3.max(category_column)
+                )));
+            }
+            let coordinate = |field: &str| {
+                field.trim().parse::<u64>().map_err(|_| {
+                    invalid_line(format!("The coordinate \"{}\" is not a valid position.", field))
+                })
+            };
+            let start = coordinate(fields[1])?;
+            let end = coordinate(fields[2])?;
+            if start > end {
+                return Err(invalid_line(format!(
+                    "The start {} is larger than the end {}.",
+                    start, end
+                )));
+            }
+            let region_categories = fields[category_column - 1]
+                .split(',')
+                .map(str::trim)
+                .filter(|category| !category.is_empty())
+                .map(|category| {
+                    *category_indices
+                        .entry(category.to_string())
+                        .or_insert_with(|| {
+                            categories.push(category.to_string());
+                            categories.len() - 1
+                        })
+                })
+                .collect();
+            regions
+                .entry(Chromosome::from(fields[0]))
+                .or_default()
+                .push(AnnotatedRegion {
+                    start,
+                    end,
+                    categories: region_categories,
+                });
+        }
+        log::debug!(
+            target: LOG_TARGET,
+            "Parsed {} reference regions with {} categories.",
+            regions.values().map(Vec::len).sum::<usize>(),
+            categories.len()
+        );
+        Ok(Self {
+            categories,
+            regions: regions
+                .into_iter()
+                .map(|(chromosome, chromosome_regions)| {
+                    (chromosome, IntervalTree::new(chromosome_regions))
+                })
+                .collect(),
+        })
+    }
+
+    /// Returns the sorted and deduplicated categories of all annotated regions overlapping
+    /// the peak.
+    ///
+    /// # Parameters
+    ///
+    /// * `chromosome` - the chromosome the peak is located on
+    /// * `peak` - the peak
+    pub fn categories(&self, chromosome: Chromosome, peak: &PeakData) -> Vec<&str> {
+        let mut peak_categories: Vec<&str> = self
+            .regions
+            .get(&chromosome)
+            .map(|tree| tree.overlapping(peak.start(), peak.end()))
+            .unwrap_or_default()
+            .into_iter()
+            .flat_map(|region| region.categories.iter())
+            .map(|category| self.categories[*category].as_str())
+            .collect();
+        peak_categories.sort_unstable();
+        peak_categories.dedup();
+        peak_categories
+    }
+}
+
+/// The categories of a set of annotated peaks.
+#[derive(CopyGetters, Getters, Clone, Default, PartialEq, Eq, Debug)]
+pub struct PeakAnnotation {
+    /// The label of each peak by peak ID.
+    /// The label consists of the comma separated categories of the peak
+    /// or [`UNANNOTATED_LABEL`] if the peak does not overlap any annotated region.
+    #[getset(get = "pub")]
+    labels: HashMap<usize, String>,
+    /// The number of peaks per category.
+    #[getset(get = "pub")]
+    category_counts: BTreeMap<String, usize>,
+    /// The number of peaks not overlapping any annotated region.
+    #[getset(get_copy = "pub")]
+    unannotated: usize,
+    /// The total number of peaks.
+    #[getset(get_copy = "pub")]
+    peaks: usize,
+}
+
+impl PeakAnnotation {
+    /// Labels the peaks with the categories of the overlapping annotated regions.
+    ///
+    /// # Parameters
+    ///
+    /// * `annotation` - the reference annotation
+    /// * `peaks` - the peaks grouped by chromosome
+    pub fn new(annotation: &RegionAnnotation, peaks: &HashMap<Chromosome, Vec<PeakData>>) -> Self {
+        let mut peak_annotation = Self::default();
+        for (chromosome, chromosome_peaks) in peaks {
+            for peak in chromosome_peaks {
+                let categories = annotation.categories(*chromosome, peak);
+                for category in &categories {
+                    *peak_annotation
+                        .category_counts
+                        .entry(category.to_string())
+                        .or_default() += 1;
+                }
+                let label = if categories.is_empty() {
+                    peak_annotation.unannotated += 1;
+                    UNANNOTATED_LABEL.to_string()
+                } else {
+                    categories.join(",")
+                };
+                peak_annotation.labels.insert(peak.id(), label);
+                peak_annotation.peaks += 1;
+            }
+        }
+        peak_annotation
+    }
+}
+
+/// Writes the number and fraction of peaks per category as tab separated table.
+/// Peaks overlapping multiple categories are counted for each category.
+/// Peaks without category are reported as [`UNANNOTATED_LABEL`] and all peaks as `*`.
+///
+/// # Parameters
+///
+/// * `writer` - the writer to write the table to
+/// * `annotation` - the peak annotation
+///
+/// # Errors
+///
+/// Returns an error if the table cannot be written.
+pub fn write_annotation_summary<W: Write>(
+    mut writer: W,
+    annotation: &PeakAnnotation,
+) -> Result<(), ApplicationError> {
+    let fraction = |count: usize| {
+        if annotation.peaks() == 0 {
+            0.0
+        } else {
+            count as f64 / annotation.peaks() as f64
+        }
+    };
+    let mut table = String::from("category\tpeaks\tfraction\n");
+    let rows = annotation
+        .category_counts()
+        .iter()
+        .map(|(category, count)| (category.as_str(), *count))
+        .chain([
+            (UNANNOTATED_LABEL, annotation.unannotated()),
+            ("*", annotation.peaks()),
+        ]);
+    for (category, count) in rows {
+        table.push_str(&format!("{}\t{}\t{:.6}\n", category, count, fraction(count)));
+    }
+    writer.write_all(table.as_bytes()).map_err(|err| {
+        ApplicationError::from(err).chain("The annotation summary could not be written.")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::{test_output, test_resources};
+
+    use super::*;
+
+    #[test]
+    fn test_region_annotation_from_bed_file() {
+        let annotation =
+            RegionAnnotation::from_bed_file(test_resources().join("input_test_annotation.bed"), 6)
+                .unwrap();
+        let chromosome = Chromosome::from("chr1");
+        let peak = |start: u64, end: u64| PeakData::new(0, start, end, start).unwrap();
+        assert_eq!(annotation.categories(chromosome, &peak(100, 150)), vec!["PLS"]);
+        assert_eq!(annotation.categories(chromosome, &peak(500, 549)), vec!["CTCF-bound", "dELS"]);
+        assert_eq!(
+            annotation.categories(chromosome, &peak(150, 549)),
+            vec!["CTCF-bound", "PLS", "dELS", "pELS"]
+        );
+        assert!(
+            annotation
+                .categories(chromosome, &peak(900, 999))
+                .is_empty()
+        );
+        assert!(
+            annotation
+                .categories(Chromosome::from("chr2"), &peak(100, 150))
+                .is_empty()
+        );
+
+        std::fs::create_dir_all(test_output()).unwrap();
+        let invalid_path = test_output().join("test_region_annotation_invalid.bed");
+        std::fs::write(&invalid_path, "chr1\t100\t199\tPLS\nchr1\t300\n").unwrap();
+        let error = RegionAnnotation::from_bed_file(&invalid_path, 4).unwrap_err();
+        assert_eq!(error.error_type(), ApplicationErrorType::ParseError);
+        assert_eq!(error.context().and_then(|context| context.line()), Some(2));
+        std::fs::remove_file(&invalid_path).unwrap();
+    }
+
+    #[test]
+    fn test_peak_annotation() {
+        let annotation =
+            RegionAnnotation::from_bed_file(test_resources().join("input_test_annotation.bed"), 6)
+                .unwrap();
+        let peaks = HashMap::from([(
+            Chromosome::from("chr1"),
+            vec![
+                PeakData::new(0, 100u64, 150u64, 125u64).unwrap(),
+                PeakData::new(1, 180u64, 320u64, 250u64).unwrap(),
+                PeakData::new(2, 900u64, 999u64, 950u64).unwrap(),
+            ],
+        )]);
+        let peak_annotation = PeakAnnotation::new(&annotation, &peaks);
+        assert_eq!(peak_annotation.labels()[&0], "PLS");
+        assert_eq!(peak_annotation.labels()[&1], "PLS,pELS");
+        assert_eq!(peak_annotation.labels()[&2], UNANNOTATED_LABEL);
+
+        let mut table = Vec::new();
+        write_annotation_summary(&mut table, &peak_annotation).unwrap();
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            "category\tpeaks\tfraction\n\
+            PLS\t2\t0.666667\n\
+            pELS\t1\t0.333333\n\
+            .\t1\t0.333333\n\
+            *\t3\t1.000000\n"
+        );
+    }
+}
@@ -1,35 +1,485 @@
 //! This module defines command line arguments.
 
-use std::{fmt::Debug, path::PathBuf, time::SystemTime};
+use std::{
+    any::TypeId,
+    collections::BTreeMap,
+    ffi::OsString,
+    fmt::Debug,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
-use clap::Parser;
+use clap::{
+    ArgAction, ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand,
+    parser::ValueSource,
+};
 use getset::{CopyGetters, Getters};
 use log::LevelFilter;
 
-use crate::peaks::ConsensusPeakAlgorithm;
+use gipfelkreuzer::{
+    annotation::DEFAULT_CATEGORY_COLUMN,
+    assembly::AssemblyCheck,
+    blacklist::BlacklistMode,
+    consensus::ConsensusBuilder,
+    convert::PeakFormat,
+    density::DEFAULT_DENSITY_WINDOW,
+    duplicates::{DEFAULT_DUPLICATE_OVERLAP, validate_duplicate_overlap},
+    error::{ApplicationError, ApplicationErrorType, ErrorContext},
+    genes::DEFAULT_PROMOTER_WINDOW,
+    idr::{DEFAULT_IDR_THRESHOLD, RankingMeasure},
+    input::InputFormat,
+    liftover::{DEFAULT_MIN_MATCH, SplitHandling},
+    outliers::{DEFAULT_OUTLIER_THRESHOLD, OutlierThresholds},
+    output::OutputFormat,
+    peaks::{
+        ConsensusPeakAlgorithm,
+        gipfelkreuzer::{AggregationBackend, CoordinateAggregation},
+        mspc::{DEFAULT_COMBINED_STRINGENCY, DEFAULT_WEAK_THRESHOLD},
+        overlap::DEFAULT_MIN_OVERLAP,
+    },
+    pipeline::{ConsensusOutput, ConsensusPipeline, ConsensusRunOptions},
+    scores::{ScoreAggregation, ScoreSource},
+    spill::DEFAULT_COMPRESSION_LEVEL,
+    strand::StrandMode,
+    windows::DEFAULT_SUMMIT_FLANK,
+};
 
 /// A tool for creating consensus peaks from genomic peak data, such as ATAC- or ChIP-Seq data.
 #[derive(Parser, CopyGetters, Getters, Debug, Clone)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 pub struct CommandLineArguments {
+    /// An optional command to run instead of writing the consensus peaks to a file.
+    #[command(subcommand)]
+    #[getset(get = "pub")]
+    command: Option<Command>,
+    /// The arguments of consensus peak generation if no command is specified.
+    #[command(flatten)]
+    consensus_arguments: ConsensusArguments,
+    /// The logging level. Extensive logging might slow down software execution [possible values: TRACE, DEBUG, INFO, WARN, ERROR]
+    #[arg(short, long, default_value_t = LevelFilter::Warn, global = true)]
+    #[getset(get_copy = "pub")]
+    log_level: LevelFilter,
+    /// A chromosome alias file, so that differently named chromosomes of different peak callers
+    /// are treated as the same chromosome. Each line contains the chromosome name used in the
+    /// output followed by its aliases separated by tabs, as in the UCSC chromAlias files.
+    #[arg(long, global = true)]
+    #[getset(get = "pub")]
+    chrom_alias: Option<PathBuf>,
+    /// Treats Ensembl style primary chromosome names such as "1", "X" and "MT" as the
+    /// respective UCSC chromosomes "chr1", "chrX" and "chrM".
+    #[arg(long, global = true)]
+    #[getset(get_copy = "pub")]
+    ucsc_chromosome_names: bool,
+    /// A file to write a JSON diagnostic report to if the application fails or panics.
+    /// The report contains the error type, the message stack, the location of the offending record,
+    /// the exit code, the effective configuration, the application version and features
+    /// and the first lines of each input file. Use "-" to write the report to stderr.
+    #[arg(long, global = true)]
+    #[getset(get = "pub")]
+    error_report: Option<PathBuf>,
+    /// A TOML file declaring run parameters, such as the algorithm, thresholds or input files.
+    /// The parameters are named like the long command line arguments without leading dashes,
+    /// the input files are listed as "input-files". Command line arguments take precedence
+    /// and relative paths are resolved relative to the directory of the configuration file.
+    #[arg(long, global = true)]
+    #[getset(get = "pub")]
+    config: Option<PathBuf>,
+    /// Prints errors to stderr as single line JSON instead of human readable messages,
+    /// so workflow engines can distinguish error types, for example to decide on retries.
+    /// The JSON contains the error type, the message stack, the location of the offending record,
+    /// hints and the exit code.
+    #[arg(long, global = true)]
+    #[getset(get_copy = "pub")]
+    error_json: bool,
+    /// The number of times a file operation failing with a transient error, such as a timeout
+    /// or a stale file handle on network file systems, is retried before giving up.
+    #[arg(long, default_value_t = 3, global = true)]
+    #[getset(get_copy = "pub")]
+    io_retries: u32,
+    /// The delay in milliseconds before the first retry of a failed file operation,
+    /// which doubles with each further retry.
+    #[arg(long, default_value_t = 500, global = true)]
+    #[getset(get_copy = "pub")]
+    io_retry_delay: u64,
+}
+
+/// The arguments of consensus peak generation, which runs if no command or the consensus
+/// command is specified.
+#[derive(Args, CopyGetters, Getters, Debug, Clone)]
+pub struct ConsensusArguments {
     /// The paths to the GA4GH BED v1.0 complient BED3+ input peak files.
     /// The peak summit offset from the start is expected at column 10
     /// as defined in the narrowPeak file format definition.
-    #[arg(required = true)]
+    #[arg(required_unless_present_any = ["watch", "sample_sheet"])]
     #[getset(get = "pub")]
     input_files: Vec<PathBuf>,
+    /// A tab separated sample sheet, or a comma separated one if the file name ends with ".csv",
+    /// listing input peak files in addition to the input files. The header must name the
+    /// columns "path" and "sample" and optionally "condition" and "replicate".
+    /// Relative paths are resolved relative to the directory of the sample sheet.
+    /// The sample names replace the file names in per-sample output.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    sample_sheet: Option<PathBuf>,
+    /// Additionally generates consensus peaks per condition of the "--sample-sheet" from the
+    /// peak files of the respective condition. They are written to the output file path with
+    /// the condition inserted before the extension. Additional columns, reports and the merging
+    /// of near-duplicates only apply to the consensus peaks of all input files.
+    #[arg(long, requires = "sample_sheet")]
+    #[getset(get_copy = "pub")]
+    per_condition: bool,
+    /// A directory to watch for new or changed peak files ("*.bed", "*.narrowPeak", "*.broadPeak").
+    /// If specified, the consensus peaks are regenerated from the input files and all peak files
+    /// in the directory whenever they change and the output file is replaced atomically.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    watch: Option<PathBuf>,
+    /// The interval in seconds to check the watched directory for changes.
+    #[arg(long, default_value_t = 5, requires = "watch")]
+    #[getset(get_copy = "pub")]
+    watch_interval: u64,
     /// The output file path [default: the current system time with the suffix "_consensus_peaks.bed"]
     #[arg(short, long)]
     output_file: Option<PathBuf>,
-    /// The logging level. Extensive logging might slow down software execution [possible values: TRACE, DEBUG, INFO, WARN, ERROR]
-    #[arg(short, long, default_value_t = LevelFilter::Warn)]
-    #[getset(get_copy = "pub")]
-    log_level: LevelFilter,
+    /// The arguments defining the interpretation of the input files.
+    #[command(flatten)]
+    #[getset(get = "pub")]
+    input_format_arguments: InputFormatArguments,
     /// The number of fields / columns to output. If 10 or more columns are specified,
-    /// column 10 is filled with the consensus peak coordinate [minimum to generate a valid BED file: 3]
+    /// column 10 is filled with the summit offset from the start. Columns 5 and 7 to 9 are filled
+    /// with the aggregated narrowPeak values of the raw peaks if present
+    /// [minimum to generate a valid BED file: 3]
     #[arg(short, long, default_value_t = 4)]
     #[getset(get_copy = "pub")]
     bed_output_columns: usize,
+    /// An output format preset defining the number and content of the output columns
+    /// instead of "--bed-output-columns". The narrowPeak and broadPeak presets use "-1" for
+    /// unknown signal values, p-values and q-values as defined by the UCSC specification.
+    #[arg(long, value_enum, conflicts_with = "bed_output_columns")]
+    #[getset(get_copy = "pub")]
+    output_format: Option<OutputFormat>,
+    /// Names the consensus peaks by a short hash of their chromosome and their coordinates
+    /// rounded to 10 bases instead of numbering them, so the names remain comparable if the
+    /// consensus peaks are regenerated, for example after adding a sample.
+    #[arg(long)]
+    #[getset(get_copy = "pub")]
+    stable_ids: bool,
+    /// The function aggregating the score (column 5), signal value (column 7), p-value
+    /// (column 8) and q-value (column 9) of the raw peaks with a summit within a consensus peak,
+    /// which are written to the respective output columns if present.
+    #[arg(long, value_enum, default_value_t = ScoreAggregation::Max)]
+    #[getset(get_copy = "pub")]
+    score_aggregation: ScoreAggregation,
+    /// The source of the score (column 5) of the consensus peaks.
+    /// "aggregated" aggregates the scores of the raw peaks with "--score-aggregation",
+    /// "peaks" scales the number of aggregated raw peaks and "samples" the number of
+    /// supporting input files to 0-1000, which makes the consensus peak confidence visible
+    /// in genome browsers. The score is only written if the output contains column 5.
+    #[arg(long, value_enum, default_value_t = ScoreSource::Aggregated)]
+    #[getset(get_copy = "pub")]
+    score_source: ScoreSource,
+    /// The handling of the strand (column 6) of the raw peaks.
+    /// "separate" generates consensus peaks separately per strand, so peaks on opposite strands
+    /// are never merged, and writes the strand of the consensus peaks to column 6.
+    #[arg(long, value_enum, default_value_t = StrandMode::Ignore)]
+    #[getset(get_copy = "pub")]
+    strand_mode: StrandMode,
+    /// A BED file of regions, such as the ENCODE blacklist, to exclude from the consensus peaks.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    blacklist: Option<PathBuf>,
+    /// The handling of peaks overlapping the "--blacklist" regions.
+    /// "remove" removes raw peaks overlapping a blacklisted region before consensus peak
+    /// generation, "trim" trims the blacklisted regions from the consensus peaks and removes
+    /// completely blacklisted consensus peaks.
+    #[arg(long, value_enum, default_value_t = BlacklistMode::Remove)]
+    #[getset(get_copy = "pub")]
+    blacklist_mode: BlacklistMode,
+    /// The arguments configuring the consensus peak algorithm.
+    #[command(flatten)]
+    #[getset(get = "pub")]
+    algorithm_arguments: AlgorithmArguments,
+    /// The minimum number of distinct input files contributing raw peaks to a consensus peak
+    /// to consider it reproducible. In contrast to "--min-peaks-per-consensus", multiple peaks
+    /// of the same input file only count once.
+    #[arg(long, default_value_t = 0)]
+    #[getset(get_copy = "pub")]
+    min_samples: usize,
+    /// Parses the input files line by line and generates the consensus peaks of each chromosome
+    /// as soon as all of its raw peaks have been read from all input files, so only the raw peaks
+    /// of a single chromosome are kept in memory at a time. The peaks of each chromosome must be
+    /// consecutive within each input file and all input files must list their chromosomes in the
+    /// same order, for example by sorting them with "sort -k1,1 -k2,2n". The mspc algorithm,
+    /// weighted-median coordinate aggregation and outputs comparing the samples still load all
+    /// input files into memory.
+    #[arg(long, conflicts_with_all = [
+        "strand_mode",
+        "collect_errors",
+        "spill_directory",
+        "genome_coverage",
+        "outlier_report",
+        "stats_output",
+    ])]
+    #[getset(get_copy = "pub")]
+    streaming: bool,
+    /// A directory to write nf-core module metadata to.
+    /// If specified, a "versions.yml" file and a "gipfelkreuzer_trace.json" process trace
+    /// are written after successful consensus peak generation.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    nf_core_metadata: Option<PathBuf>,
+    /// Continues parsing after invalid records and reports up to the specified number of problems
+    /// across all input files at once instead of failing on the first problem.
+    #[arg(long, value_name = "MAX_ERRORS", value_parser = clap::value_parser!(u64).range(1..))]
+    #[getset(get_copy = "pub")]
+    collect_errors: Option<u64>,
+    /// Exits with a non-zero exit code if any warnings were logged during the run,
+    /// for example because of approximated peak summits or retried file operations.
+    #[arg(long)]
+    #[getset(get_copy = "pub")]
+    strict_warnings: bool,
+    /// The process name used as key in the nf-core "versions.yml" file.
+    #[arg(long, default_value = "GIPFELKREUZER")]
+    #[getset(get = "pub")]
+    nf_core_process_name: String,
+    /// A MultiQC custom content JSON file to write the raw peaks per input file, the consensus
+    /// peak counts, the filter statistics and, if calculated, the fraction of reads in peaks to,
+    /// so they appear in the MultiQC report. The file name must end with "_mqc.json" to be
+    /// detected by MultiQC automatically.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    multiqc: Option<PathBuf>,
+    /// A JSON manifest file to write the size and SHA-256 checksum of every input and output
+    /// file and the parameters of the run to, so data management systems can register the run
+    /// and detect stale or tampered files.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    manifest: Option<PathBuf>,
+    /// A run manifest previously written with "--manifest" to verify the input files against
+    /// before generating consensus peaks. The run is aborted unless all input files are
+    /// byte-identical to and specified in the same order as the recorded input files.
+    #[arg(long, value_name = "MANIFEST")]
+    #[getset(get = "pub")]
+    verify_inputs: Option<PathBuf>,
+    /// Inserts a short run identifier derived from the tool version, the parameters and the
+    /// input checksums before the extension of the output file name, so outputs of different
+    /// parameterisations of the same inputs can coexist in the same directory.
+    /// The identifier is also recorded in the run manifest.
+    #[arg(long)]
+    #[getset(get_copy = "pub")]
+    run_id_suffix: bool,
+    /// A JSON file to write machine-readable statistics of the run to, including the raw peaks
+    /// per input file, the raw peak bins, the consensus peaks and their mean and median width
+    /// per chromosome and the consensus peaks remaining per minimum number of supporting raw peaks.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    stats_output: Option<PathBuf>,
+    /// A TSV file to write a quality control summary to, including the input peaks, the consensus
+    /// peaks, their mean and median width, the fraction of input peaks contributing to any
+    /// consensus peak and the consensus peaks discarded by "--min-peaks-per-consensus"
+    /// per chromosome.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    stats_file: Option<PathBuf>,
+    /// Prints the quality control summary of "--stats-file" to stderr.
+    #[arg(long)]
+    #[getset(get_copy = "pub")]
+    summary: bool,
+    /// A file to write the number of consensus peaks supported by each combination of
+    /// input files to, which is the data behind an UpSet plot.
+    /// An input file supports a consensus peak if any of its peaks overlaps the consensus peak.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    intersection_summary: Option<PathBuf>,
+    /// A file to write the pairwise Pearson correlations of the number of peaks per genomic window
+    /// between the input files to, per chromosome and genome-wide.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    density_correlation: Option<PathBuf>,
+    /// A file to write the number of consensus peaks per genomic window to as BED-like track
+    /// with the columns chromosome, start, end and number of peaks, for example to visualise the
+    /// genome-wide distribution of the consensus peaks with circos or karyoploteR.
+    /// If chromosome sizes are specified, the windows cover the whole chromosomes.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    density_track: Option<PathBuf>,
+    /// The size in bases of the genomic windows the peaks are counted in for the peak density
+    /// correlation and the peak density track.
+    #[arg(long, default_value_t = DEFAULT_DENSITY_WINDOW, value_parser = clap::value_parser!(u64).range(1..))]
+    #[getset(get_copy = "pub")]
+    density_window: u64,
+    /// A directory to write summit concordance tables to, reporting the spread of the sample
+    /// summits within each consensus peak and the distribution of the offsets of the sample
+    /// summits from the consensus peak summits per input file.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    summit_concordance: Option<PathBuf>,
+    /// A file containing the tab separated columns chromosome name and chromosome size,
+    /// such as a "chrom.sizes" file or a FASTA index.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    chromosome_sizes: Option<PathBuf>,
+    /// A file to write the number and fraction of bases per chromosome and of the whole genome
+    /// covered by raw and consensus peaks to, for example to compare them with the expected
+    /// fraction of open chromatin.
+    #[arg(long, requires = "chromosome_sizes")]
+    #[getset(get = "pub")]
+    genome_coverage: Option<PathBuf>,
+    /// Checks whether the input files appear to originate from different genome assemblies based
+    /// on their chromosome naming and maximum peak coordinates compared to common assemblies and,
+    /// if specified, to the chromosome sizes. Inconsistencies are either logged as warnings
+    /// ("warn") or abort the run ("error").
+    #[arg(long, value_enum)]
+    #[getset(get_copy = "pub")]
+    assembly_check: Option<AssemblyCheck>,
+    /// A BED3+ reference annotation, such as the ENCODE cCREs, to label the consensus peaks with.
+    /// If specified, the categories of all overlapping reference regions are appended to each
+    /// consensus peak as additional column and a category summary table is written alongside
+    /// the consensus peak output.
+    #[arg(long, value_name = "BED")]
+    #[getset(get = "pub")]
+    annotate_with: Option<PathBuf>,
+    /// The column of the reference annotation containing the comma separated categories
+    /// of each region, for example "6" for the ENCODE cCRE classification.
+    #[arg(long, default_value_t = DEFAULT_CATEGORY_COLUMN as u64, value_parser = clap::value_parser!(u64).range(4..), requires = "annotate_with")]
+    #[getset(get_copy = "pub")]
+    annotation_column: u64,
+    /// A GTF gene annotation to annotate the consensus peaks with the nearest gene.
+    /// If specified, the name and strand of the gene with the transcription start site (TSS)
+    /// closest to the summit of each consensus peak and the distance of the summit to the TSS
+    /// in direction of transcription are appended as additional columns.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    gtf: Option<PathBuf>,
+    /// The maximum distance in bases of a consensus peak summit to the nearest TSS to classify
+    /// the peak as promoter-proximal instead of distal. The class is appended as additional column
+    /// after the nearest gene and a class summary table is written alongside the consensus peak
+    /// output.
+    #[arg(long, default_value_t = DEFAULT_PROMOTER_WINDOW, requires = "gtf")]
+    #[getset(get_copy = "pub")]
+    promoter_window: u64,
+    /// The promoter / distal class summary table path [default: the output file path with the suffix "_promoter.tsv"]
+    #[arg(long, requires = "gtf")]
+    promoter_summary: Option<PathBuf>,
+    /// A BED file to write windows of fixed size around the consensus peak summits to,
+    /// for example as input of de-novo motif discovery.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    summit_windows: Option<PathBuf>,
+    /// A FASTA file to write the genome sequences of the windows around the consensus peak
+    /// summits to.
+    #[arg(long, requires = "genome")]
+    #[getset(get = "pub")]
+    summit_sequences: Option<PathBuf>,
+    /// The number of bases on each side of the consensus peak summits included in the summit windows.
+    #[arg(long, default_value_t = DEFAULT_SUMMIT_FLANK)]
+    #[getset(get_copy = "pub")]
+    summit_flank: u64,
+    /// The genome FASTA file to extract the summit window sequences from.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    genome: Option<PathBuf>,
+    /// A file to write the consensus peaks with an extreme length, number of supporting raw peak
+    /// summits or standard deviation of the supporting summits to, which are usually artefacts
+    /// worth reviewing or blacklisting.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    outlier_report: Option<PathBuf>,
+    /// The modified z-score of the consensus peak length above which a peak is reported
+    /// as outlier.
+    #[arg(long, default_value_t = DEFAULT_OUTLIER_THRESHOLD, requires = "outlier_report")]
+    outlier_length_threshold: f64,
+    /// The modified z-score of the number of supporting raw peak summits above which a consensus
+    /// peak is reported as outlier.
+    #[arg(long, default_value_t = DEFAULT_OUTLIER_THRESHOLD, requires = "outlier_report")]
+    outlier_support_threshold: f64,
+    /// The modified z-score of the standard deviation of the supporting raw peak summits above
+    /// which a consensus peak is reported as outlier.
+    #[arg(long, default_value_t = DEFAULT_OUTLIER_THRESHOLD, requires = "outlier_report")]
+    outlier_spread_threshold: f64,
+    /// A file to write the pairs of near-duplicate consensus peaks to, which overlap each other
+    /// by at least the "--duplicate-overlap" fraction. Near-duplicates are a sign of iterative
+    /// merging not converging within the "--max-merge-iterations".
+    #[arg(long)]
+    #[getset(get = "pub")]
+    duplicate_report: Option<PathBuf>,
+    /// Merges each group of near-duplicate consensus peaks into a single consensus peak spanning
+    /// all of them.
+    #[arg(long)]
+    #[getset(get_copy = "pub")]
+    merge_duplicates: bool,
+    /// The minimum fraction of the shorter consensus peak covered by the longer consensus peak
+    /// to consider both peaks near-duplicates.
+    #[arg(long, default_value_t = DEFAULT_DUPLICATE_OVERLAP)]
+    #[getset(get_copy = "pub")]
+    duplicate_overlap: f64,
+    /// A file to write the provenance of each consensus peak to, which lists the input files
+    /// and the IDs of the raw peaks the consensus peak was created from.
+    /// Raw peak IDs are assigned in the order of the records of all input files starting at 0.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    provenance_table: Option<PathBuf>,
+    /// A file to write the mapping of raw peaks to consensus peaks to, which lists the name
+    /// (column 4) of each raw peak and the name of the consensus peak it was merged into.
+    /// Raw peaks without a name are listed by their raw peak ID.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    peak_name_mapping: Option<PathBuf>,
+    /// A file to write the binary occupancy matrix of the consensus peaks to, which lists for
+    /// each consensus peak (rows) and input file (columns) if the input file contributed a raw
+    /// peak (1) or not (0). The matrix can be directly used to create UpSet plots.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    occupancy_matrix: Option<PathBuf>,
+    /// Appends the comma separated names of the input files contributing raw peaks to each
+    /// consensus peak as additional output column.
+    #[arg(long)]
+    #[getset(get_copy = "pub")]
+    provenance_column: bool,
+    /// The annotation summary table path [default: the output file path with the suffix "_annotation.tsv"]
+    #[arg(long, requires = "annotate_with")]
+    annotation_summary: Option<PathBuf>,
+    /// Per-sample BAM files used to calculate the fraction of reads in consensus peaks (FRiP).
+    /// If specified, a QC table is written alongside the consensus peak output.
+    #[cfg(feature = "bam")]
+    #[arg(long, num_args = 1..)]
+    #[getset(get = "pub")]
+    frip_bam: Vec<PathBuf>,
+    /// The FRiP QC table path [default: the output file path with the suffix "_frip.tsv"]
+    #[cfg(feature = "bam")]
+    #[arg(long)]
+    frip_output: Option<PathBuf>,
+    /// BigWig coverage tracks to annotate the consensus peaks with.
+    /// If specified, the score (5) and signal value (7) fields are derived from the mean signal
+    /// and the mean and maximum signal of each track are appended as additional columns.
+    #[cfg(feature = "bigwig")]
+    #[arg(long, num_args = 1..)]
+    #[getset(get = "pub")]
+    bigwig: Vec<PathBuf>,
+    /// Moves the consensus peak summits to the position with the highest BigWig signal.
+    #[cfg(feature = "bigwig")]
+    #[arg(long, requires = "bigwig")]
+    #[getset(get_copy = "pub")]
+    refine_summits: bool,
+}
+
+/// The arguments defining the interpretation of the input files, which are shared by all commands
+/// reading peak files.
+#[derive(Args, CopyGetters, Debug, Clone)]
+pub struct InputFormatArguments {
+    /// The format of the input files.
+    /// "auto" interprets column 10 as the narrowPeak summit offset if present and approximates
+    /// missing summits with a warning. "narrowPeak" requires the summit offset in column 10.
+    /// "broadPeak" and "bed" never interpret column 10 and set the summits to the peak centres.
+    #[arg(long, value_enum, default_value_t = InputFormat::Auto)]
+    #[getset(get_copy = "pub")]
+    input_format: InputFormat,
+}
+
+/// The arguments configuring the consensus peak algorithm, which are shared by all commands
+/// generating consensus peaks.
+#[derive(Args, CopyGetters, Getters, Debug, Clone)]
+pub struct AlgorithmArguments {
     /// The maximum number of consensus peak merging iterations.
     /// A value of "0" means consensus peaks are only called once and not
     /// iteratively merged. This will yield the highest sensitivity, but
@@ -38,10 +488,34 @@ pub struct CommandLineArguments {
     #[arg(short, long, default_value_t = 20)]
     #[getset(get_copy = "pub")]
     max_merge_iterations: usize,
+    /// The maximum distance in base pairs between the summits of peaks merged by the
+    /// Gipfelkreuzer algorithm. If not set, peaks are merged if their summit lies anywhere
+    /// within the consensus defining peak. Smaller values result in more stringent merging
+    /// independent of the peak width.
+    #[arg(long)]
+    #[getset(get_copy = "pub")]
+    summit_tolerance: Option<u64>,
+    /// The way the coordinates of peaks merged by the Gipfelkreuzer algorithm are combined
+    /// into the consensus peak coordinates. The median ("median") and mean ("mean") treat all
+    /// raw peaks equally, while the weighted median ("weighted-median") weights the raw peaks
+    /// by their score (column 5), so high-confidence peaks dominate the consensus geometry.
+    #[arg(long, value_enum, default_value_t = CoordinateAggregation::Median)]
+    #[getset(get_copy = "pub")]
+    coordinate_aggregation: CoordinateAggregation,
+    /// The implementation used by the Gipfelkreuzer algorithm to aggregate the peaks of a peak bin.
+    /// The linear implementation ("linear") scans all remaining peaks of a bin for each consensus
+    /// peak, while the indexed implementation ("indexed") queries them by summit position, which
+    /// is faster on super-dense peak sets, such as pooled single-cell clusters.
+    /// Both create identical consensus peaks.
+    #[arg(long, value_enum, default_value_t = AggregationBackend::Linear)]
+    #[getset(get_copy = "pub")]
+    aggregation_backend: AggregationBackend,
     /// The algorithm to use for creating consensus peaks.
     /// The Gipfelkreuzer algorithm ("gipfelkreuzer") will use summit proximity as a merge criterium.
     /// The simple merge algorithm ("simple") only merges all overlapping and adjacent peaks independent of their summit information or size.
     /// The harmonised algorithm ("harmonised") uses fixed summit distances to merge peaks as proposed by Cherchame et al. in 2025 (https://www.protocols.io/view/atac-seq-methods-for-consensus-peak-generation-to-36wgq326olk5/v1).
+    /// The overlap algorithm ("overlap") only merges peaks overlapping by at least the "--merge-overlap" fraction, analogous to "bedtools intersect -f".
+    /// The MSPC algorithm ("mspc") combines the p-values of overlapping peaks of different input files with Fisher's method and only merges peaks reaching the "--combined-stringency" as proposed by Jalili et al. in 2015 (https://doi.org/10.1093/bioinformatics/btv293).
     #[arg(short, long, default_value_t = ConsensusPeakAlgorithm::Gipfelkreuzer)]
     #[getset(get_copy = "pub")]
     algorithm: ConsensusPeakAlgorithm,
@@ -55,9 +529,775 @@ pub struct CommandLineArguments {
     #[arg(short = 'd', long, default_value_t = 250)]
     #[getset(get_copy = "pub")]
     harmonising_distance: u64,
+    /// The maximum number of bases between peaks merged by the simple algorithm, analogous to
+    /// "bedtools merge -d". By default only overlapping and adjacent peaks are merged.
+    #[arg(long, default_value_t = 0)]
+    #[getset(get_copy = "pub")]
+    max_gap: u64,
+    /// The minimum fraction of the shorter peak that must be overlapped by another peak
+    /// to merge both peaks with the overlap algorithm [possible values: >0-1]
+    #[arg(long, default_value_t = DEFAULT_MIN_OVERLAP)]
+    #[getset(get_copy = "pub")]
+    merge_overlap: f64,
+    /// Requires the "--merge-overlap" fraction to be overlapped of both peaks
+    /// instead of only the shorter one, analogous to "bedtools intersect -r".
+    #[arg(long)]
+    #[getset(get_copy = "pub")]
+    reciprocal_merge_overlap: bool,
+    /// The maximum p-value of the combined evidence of overlapping peaks of different samples
+    /// to confirm a peak with the mspc algorithm [possible values: >0-1]
+    #[arg(long, default_value_t = DEFAULT_COMBINED_STRINGENCY)]
+    #[getset(get_copy = "pub")]
+    combined_stringency: f64,
+    /// The maximum p-value of a peak to not be discarded as background by the mspc algorithm
+    /// [possible values: >0-1]
+    #[arg(long, default_value_t = DEFAULT_WEAK_THRESHOLD)]
+    #[getset(get_copy = "pub")]
+    weak_threshold: f64,
+    /// The minimum number of samples with overlapping peaks, including the sample of the peak
+    /// itself, to confirm a peak with the mspc algorithm.
+    #[arg(long, default_value_t = 1)]
+    #[getset(get_copy = "pub")]
+    min_replicates: usize,
+    /// A directory to spill parsed peaks to before consensus peak generation.
+    /// If specified, all raw peaks are parsed and then stored as compressed temporary files,
+    /// so only the raw peaks of a single chromosome are kept in memory while consensus peaks
+    /// are generated. Use "--streaming" to also bound the memory usage of parsing.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    spill_directory: Option<PathBuf>,
+    /// The zstd compression level of spilled peak data [possible values: 1-22]
+    #[arg(long, default_value_t = DEFAULT_COMPRESSION_LEVEL, value_parser = clap::value_parser!(i32).range(1..=22))]
+    #[getset(get_copy = "pub")]
+    spill_compression_level: i32,
+    /// The number of threads generating consensus peaks of different chromosomes and peak bins
+    /// in parallel. A value of "0" uses one thread per available CPU core.
+    /// The consensus peaks do not depend on the number of threads.
+    #[arg(long, default_value_t = 0)]
+    #[getset(get_copy = "pub")]
+    threads: usize,
+}
+
+/// The commands of the application.
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Generates consensus peaks from the input files, which is done by default if no command is
+    /// specified. Accepts the same arguments as the application without a command, for example
+    /// "Gipfelkreuzer consensus -o consensus_peaks.bed sample_1.narrowPeak sample_2.narrowPeak".
+    Consensus(Box<ConsensusArguments>),
+    /// Merges all overlapping and adjacent peaks of the input files into single peaks,
+    /// similar to "bedtools merge", without generating consensus peaks.
+    /// Merged peaks are written as 10 column BED file with their summits set to their centres.
+    Merge(MergeArguments),
+    /// Validates peak files without generating consensus peaks and writes a report per file
+    /// listing malformed records, invalid coordinates, summits and strands as errors and
+    /// unsorted and duplicate records as warnings. Exits with a non-zero exit code if any
+    /// errors are found.
+    Validate(ValidateArguments),
+    /// Serves consensus peaks via an HTTP API answering overlap ("/overlap?chrom=chr1&start=100&end=200")
+    /// and peak ("/peak/{id}") queries with JSON.
+    Serve(ServeArguments),
+    /// Summarises peak files per file and chromosome as tab separated table, including
+    /// peak counts, covered bases, peak width quantiles and the fraction of peaks with
+    /// summit information.
+    Stats(StatsArguments),
+    /// Compares a query peak file against a reference peak file and reports precision, recall
+    /// and F1 score, for example to benchmark parameter choices against a published consensus.
+    Compare(CompareArguments),
+    /// Generates consensus peaks from random subsets of 1 to all input files and writes the
+    /// number of consensus peaks per subset as saturation curve, so it can be judged whether
+    /// additional replicates would still add consensus peaks.
+    Saturation(SaturationArguments),
+    /// Generates consensus peaks for a range of minimum numbers of raw peaks per consensus peak
+    /// and writes the precision, recall and F1 score against a gold-standard peak file for each
+    /// of them, so the support threshold can be chosen objectively.
+    Sweep(SweepArguments),
+    /// Maps the peaks of a peak file to another genome assembly (e.g. hg19 to hg38) using a
+    /// UCSC chain file, so samples aligned to different assemblies can be combined.
+    /// Lifted peaks are written as 10 column BED file retaining their summits.
+    Liftover(LiftoverArguments),
+    /// Converts a peak file between the BED, narrowPeak, SAF, JSON and bigBed formats without
+    /// generating consensus peaks. The formats are detected from the file extensions unless
+    /// specified explicitly.
+    Convert(ConvertArguments),
+    /// Filters the records of an existing BED3+ / narrowPeak file by blacklisted and whitelisted
+    /// regions, chromosome, length and score without generating consensus peaks.
+    /// Retained records are written unchanged.
+    Filter(FilterArguments),
+    /// Sorts the records of an existing BED3+ / narrowPeak file by chromosome and coordinates,
+    /// so it can be compressed with bgzip and indexed with tabix. Chromosome names can be
+    /// normalised and duplicate records removed.
+    Sort(SortArguments),
+    /// Writes the regions of the peaks of a peak file overlapping the peaks of another peak
+    /// file, similar to "bedtools intersect".
+    Intersect(RegionOperationArguments),
+    /// Writes the regions of the peaks of a peak file not overlapping the peaks of another peak
+    /// file, similar to "bedtools subtract".
+    Subtract(RegionOperationArguments),
+    /// Samples random genomic regions matched to the peaks of a peak file in number, chromosome
+    /// and length, which do not overlap the peaks or blacklisted regions, as null set for
+    /// enrichment analyses. Background regions are written as 10 column BED file.
+    Background(BackgroundArguments),
+    /// Compares two versions of a consensus peak set and reports the peaks gained, lost and
+    /// shifted between them as tab separated table, so changes between releases of a
+    /// consensus peak set can be reviewed.
+    Diff(DiffArguments),
+    /// Scores the reproducibility of the peaks of exactly two replicates with the irreproducible
+    /// discovery rate (IDR) as used by the ENCODE pipelines and writes the overlapping peaks
+    /// passing the IDR threshold in the extended narrowPeak format of the ENCODE IDR output.
+    Idr(IdrArguments),
+}
+
+/// The arguments of the merge command.
+#[derive(Args, Getters, Debug, Clone)]
+pub struct MergeArguments {
+    /// The paths to the BED3+ peak files to merge.
+    #[arg(required = true)]
+    #[getset(get = "pub")]
+    input_files: Vec<PathBuf>,
+    /// The output file path of the merged peaks [default: stdout]
+    #[arg(short, long)]
+    #[getset(get = "pub")]
+    output_file: Option<PathBuf>,
+}
+
+/// The arguments of the validate command.
+#[derive(Args, CopyGetters, Getters, Debug, Clone)]
+pub struct ValidateArguments {
+    /// The paths to the BED3+ peak files to validate.
+    #[arg(required = true)]
+    #[getset(get = "pub")]
+    input_files: Vec<PathBuf>,
+    /// The maximum number of errors and of warnings to report per file.
+    #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u64).range(1..))]
+    #[getset(get_copy = "pub")]
+    max_errors: u64,
+    /// Treats warnings, such as unsorted or duplicate records, as errors.
+    #[arg(long)]
+    #[getset(get_copy = "pub")]
+    strict: bool,
+    /// The output file path of the validation report [default: stdout]
+    #[arg(short, long)]
+    #[getset(get = "pub")]
+    output_file: Option<PathBuf>,
+    /// The arguments defining the interpretation of the input files.
+    #[command(flatten)]
+    #[getset(get = "pub")]
+    input_format_arguments: InputFormatArguments,
+}
+
+/// The arguments of the serve command.
+#[derive(Args, CopyGetters, Getters, Debug, Clone)]
+pub struct ServeArguments {
+    /// The paths to the BED3+ input peak files.
+    #[arg(required = true)]
+    #[getset(get = "pub")]
+    input_files: Vec<PathBuf>,
+    /// The address to listen on.
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    #[getset(get = "pub")]
+    address: String,
+    /// Serves the input peaks as they are instead of generating consensus peaks from them,
+    /// for example to query a previously generated consensus peak file.
+    /// Peak identifiers are assigned in the order of the input peaks.
+    #[arg(long)]
+    #[getset(get_copy = "pub")]
+    precomputed: bool,
+    /// The arguments defining the interpretation of the input files.
+    #[command(flatten)]
+    #[getset(get = "pub")]
+    input_format_arguments: InputFormatArguments,
+    /// The arguments configuring the consensus peak algorithm.
+    #[command(flatten)]
+    #[getset(get = "pub")]
+    algorithm_arguments: AlgorithmArguments,
+}
+
+/// The arguments of the stats command.
+#[derive(Args, Getters, Debug, Clone)]
+pub struct StatsArguments {
+    /// The paths to the BED3+ peak files to summarise.
+    #[arg(required = true)]
+    #[getset(get = "pub")]
+    input_files: Vec<PathBuf>,
+    /// The output file path of the table [default: stdout]
+    #[arg(short, long)]
+    #[getset(get = "pub")]
+    output_file: Option<PathBuf>,
+}
+
+/// The arguments of the compare command.
+#[derive(Args, CopyGetters, Getters, Debug, Clone)]
+pub struct CompareArguments {
+    /// The path to the BED3+ reference peak file.
+    #[getset(get = "pub")]
+    reference: PathBuf,
+    /// The path to the BED3+ query peak file.
+    #[getset(get = "pub")]
+    query: PathBuf,
+    /// The minimum fraction of a reference peak that must be covered by a query peak to match.
+    /// A value of "0" matches peaks overlapping by at least one base.
+    #[arg(long, default_value_t = 0.0)]
+    #[getset(get_copy = "pub")]
+    min_overlap: f64,
+    /// Requires the minimum overlap fraction to be covered of both the reference and the query peak.
+    #[arg(long)]
+    #[getset(get_copy = "pub")]
+    reciprocal: bool,
+    /// The output file path of the comparison table [default: stdout]
+    #[arg(short, long)]
+    #[getset(get = "pub")]
+    output_file: Option<PathBuf>,
+    /// A BED file to write the reference peaks not matched by any query peak to.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    reference_only: Option<PathBuf>,
+    /// A BED file to write the query peaks not matching any reference peak to.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    query_only: Option<PathBuf>,
+}
+
+impl CompareArguments {
+    /// Returns the reference and query peak files.
+    pub fn input_files(&self) -> Vec<PathBuf> {
+        vec![self.reference.clone(), self.query.clone()]
+    }
+}
+
+/// The arguments of the saturation command.
+#[derive(Args, CopyGetters, Getters, Debug, Clone)]
+pub struct SaturationArguments {
+    /// The paths to the BED3+ peak files of the samples.
+    #[arg(required = true)]
+    #[getset(get = "pub")]
+    input_files: Vec<PathBuf>,
+    /// The number of random subsets per number of samples.
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    #[getset(get_copy = "pub")]
+    repetitions: u64,
+    /// The seed of the random subsampling, so the saturation curve can be reproduced.
+    #[arg(long, default_value_t = 0)]
+    #[getset(get_copy = "pub")]
+    seed: u64,
+    /// The output file path of the saturation curve table [default: stdout]
+    #[arg(short, long)]
+    #[getset(get = "pub")]
+    output_file: Option<PathBuf>,
+    /// The arguments defining the interpretation of the input files.
+    #[command(flatten)]
+    #[getset(get = "pub")]
+    input_format_arguments: InputFormatArguments,
+    /// The arguments configuring the consensus peak algorithm.
+    #[command(flatten)]
+    #[getset(get = "pub")]
+    algorithm_arguments: AlgorithmArguments,
+}
+
+/// The arguments of the sweep command.
+#[derive(Args, CopyGetters, Getters, Debug, Clone)]
+pub struct SweepArguments {
+    /// The paths to the BED3+ peak files of the samples.
+    #[arg(required = true)]
+    #[getset(get = "pub")]
+    input_files: Vec<PathBuf>,
+    /// The path to the BED3+ gold-standard peak file.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    gold_standard: PathBuf,
+    /// The largest minimum number of raw peaks per consensus peak to evaluate, starting from 1
+    /// [default: the number of input files]
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    #[getset(get_copy = "pub")]
+    max_min_peaks: Option<u64>,
+    /// The minimum fraction of a gold-standard peak that must be covered by a consensus peak
+    /// to match. A value of "0" matches peaks overlapping by at least one base.
+    #[arg(long, default_value_t = 0.0)]
+    #[getset(get_copy = "pub")]
+    min_overlap: f64,
+    /// Requires the minimum overlap fraction to be covered of both the gold-standard and the
+    /// consensus peak.
+    #[arg(long)]
+    #[getset(get_copy = "pub")]
+    reciprocal: bool,
+    /// The output file path of the threshold sweep table [default: stdout]
+    #[arg(short, long)]
+    #[getset(get = "pub")]
+    output_file: Option<PathBuf>,
+    /// The arguments configuring the consensus peak algorithm.
+    #[command(flatten)]
+    #[getset(get = "pub")]
+    algorithm_arguments: AlgorithmArguments,
+}
+
+/// The arguments of the liftover command.
+#[derive(Args, CopyGetters, Getters, Debug, Clone)]
+pub struct LiftoverArguments {
+    /// The path to the BED3+ peak file to lift.
+    #[getset(get = "pub")]
+    input_file: PathBuf,
+    /// The path to the uncompressed UCSC chain file mapping the source to the target assembly.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    chain: PathBuf,
+    /// The minimum fraction of the bases of a peak that must be mapped by a single chain.
+    #[arg(long, default_value_t = DEFAULT_MIN_MATCH)]
+    #[getset(get_copy = "pub")]
+    min_match: f64,
+    /// The handling of peaks whose bases are mapped by more than one chain, for example to
+    /// different chromosomes. Split peaks are either reported as unmapped ("discard") or mapped
+    /// by the chain mapping the most bases ("largest").
+    #[arg(long, value_enum, default_value_t = SplitHandling::Discard)]
+    #[getset(get_copy = "pub")]
+    split: SplitHandling,
+    /// A BED file to write the peaks that could not be mapped to.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    unmapped: Option<PathBuf>,
+    /// The output file path of the lifted peaks [default: stdout]
+    #[arg(short, long)]
+    #[getset(get = "pub")]
+    output_file: Option<PathBuf>,
+}
+
+/// The arguments of the convert command.
+#[derive(Args, CopyGetters, Getters, Debug, Clone)]
+pub struct ConvertArguments {
+    /// The path to the peak file to convert.
+    #[getset(get = "pub")]
+    input_file: PathBuf,
+    /// The path of the converted peak file.
+    #[getset(get = "pub")]
+    output_file: PathBuf,
+    /// The format of the input file [default: detected from the file extension]
+    #[arg(long, value_enum)]
+    #[getset(get_copy = "pub")]
+    from: Option<PeakFormat>,
+    /// The format of the output file [default: detected from the file extension]
+    #[arg(long, value_enum)]
+    #[getset(get_copy = "pub")]
+    to: Option<PeakFormat>,
+    /// A tab separated file of chromosome names and sizes stored in bigBed output files
+    /// [default: the end of the last peak per chromosome]
+    #[arg(long)]
+    #[getset(get = "pub")]
+    chromosome_sizes: Option<PathBuf>,
+}
+
+/// The arguments of the filter command.
+#[derive(Args, CopyGetters, Getters, Debug, Clone)]
+pub struct FilterArguments {
+    /// The path to the BED3+ peak file to filter.
+    #[getset(get = "pub")]
+    input_file: PathBuf,
+    /// A BED file of regions, such as the ENCODE blacklist, to remove overlapping peaks.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    blacklist: Option<PathBuf>,
+    /// A BED file of regions to retain overlapping peaks only.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    whitelist: Option<PathBuf>,
+    /// The chromosomes to retain peaks on [default: all chromosomes]
+    #[arg(long, num_args = 1..)]
+    #[getset(get = "pub")]
+    chromosomes: Vec<String>,
+    /// The chromosomes to remove peaks from, for example "chrM".
+    #[arg(long, num_args = 1..)]
+    #[getset(get = "pub")]
+    exclude_chromosomes: Vec<String>,
+    /// The minimum peak length in bases.
+    #[arg(long)]
+    #[getset(get_copy = "pub")]
+    min_length: Option<u64>,
+    /// The maximum peak length in bases.
+    #[arg(long)]
+    #[getset(get_copy = "pub")]
+    max_length: Option<u64>,
+    /// The minimum score in column 5. Peaks without a score are removed.
+    #[arg(long)]
+    #[getset(get_copy = "pub")]
+    min_score: Option<f64>,
+    /// The output file path of the retained peaks [default: stdout]
+    #[arg(short, long)]
+    #[getset(get = "pub")]
+    output_file: Option<PathBuf>,
+}
+
+/// The arguments of the intersect and subtract commands.
+#[derive(Args, CopyGetters, Getters, Debug, Clone)]
+pub struct RegionOperationArguments {
+    /// The path to the BED3+ peak file whose peaks are reported.
+    #[getset(get = "pub")]
+    input_file: PathBuf,
+    /// The path to the BED3+ peak file whose peaks are intersected with or subtracted.
+    #[getset(get = "pub")]
+    other_file: PathBuf,
+    /// The minimum fraction of a peak of the input file that must be covered by a peak of the
+    /// other file to overlap. A value of "0" matches peaks overlapping by at least one base.
+    #[arg(long, default_value_t = 0.0)]
+    #[getset(get_copy = "pub")]
+    min_overlap: f64,
+    /// Requires the minimum overlap fraction to be covered of both peaks.
+    #[arg(long)]
+    #[getset(get_copy = "pub")]
+    reciprocal: bool,
+    /// Reports (intersect) or removes (subtract) complete peaks of the input file instead of
+    /// trimming them to the overlapping or non-overlapping regions.
+    #[arg(long)]
+    #[getset(get_copy = "pub")]
+    whole: bool,
+    /// The output file path of the resulting peaks [default: stdout]
+    #[arg(short, long)]
+    #[getset(get = "pub")]
+    output_file: Option<PathBuf>,
+}
+
+impl RegionOperationArguments {
+    /// Returns the input and the other peak file.
+    pub fn input_files(&self) -> Vec<PathBuf> {
+        vec![self.input_file.clone(), self.other_file.clone()]
+    }
+}
+
+/// The arguments of the background command.
+#[derive(Args, CopyGetters, Getters, Debug, Clone)]
+pub struct BackgroundArguments {
+    /// The path to the BED3+ peak file to match background regions to.
+    #[getset(get = "pub")]
+    input_file: PathBuf,
+    /// A tab separated file of chromosome names and sizes to place the background regions on.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    chromosome_sizes: PathBuf,
+    /// A BED3+ file of regions that must not be overlapped by background regions.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    blacklist: Option<PathBuf>,
+    /// The number of background regions per peak.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    #[getset(get_copy = "pub")]
+    ratio: u64,
+    /// The seed of the random placement, so the background regions can be reproduced.
+    #[arg(long, default_value_t = 0)]
+    #[getset(get_copy = "pub")]
+    seed: u64,
+    /// The output file path of the background regions [default: stdout]
+    #[arg(short, long)]
+    #[getset(get = "pub")]
+    output_file: Option<PathBuf>,
+}
+
+impl BackgroundArguments {
+    /// Returns the peak file and the blacklist if specified.
+    pub fn input_files(&self) -> Vec<PathBuf> {
+        std::iter::once(self.input_file.clone())
+            .chain(self.blacklist.clone())
+            .collect()
+    }
+}
+
+/// The arguments of the diff command.
+#[derive(Args, CopyGetters, Getters, Debug, Clone)]
+pub struct DiffArguments {
+    /// The path to the BED3+ file of the old version of the consensus peaks.
+    #[getset(get = "pub")]
+    old: PathBuf,
+    /// The path to the BED3+ file of the new version of the consensus peaks.
+    #[getset(get = "pub")]
+    new: PathBuf,
+    /// The maximum number of bases a peak boundary may move for an overlapping peak to be
+    /// reported as unchanged instead of shifted.
+    #[arg(long, default_value_t = 0)]
+    #[getset(get_copy = "pub")]
+    tolerance: u64,
+    /// The output file path of the difference table [default: stdout]
+    #[arg(short, long)]
+    #[getset(get = "pub")]
+    output_file: Option<PathBuf>,
+    /// A file to write the number of gained, lost, shifted and unchanged peaks to.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    summary: Option<PathBuf>,
+}
+
+impl DiffArguments {
+    /// Returns the old and new peak files.
+    pub fn input_files(&self) -> Vec<PathBuf> {
+        vec![self.old.clone(), self.new.clone()]
+    }
+}
+
+/// The arguments of the idr command.
+#[derive(Args, CopyGetters, Getters, Debug, Clone)]
+pub struct IdrArguments {
+    /// The path to the narrowPeak file of the first replicate.
+    #[getset(get = "pub")]
+    first_replicate: PathBuf,
+    /// The path to the narrowPeak file of the second replicate.
+    #[getset(get = "pub")]
+    second_replicate: PathBuf,
+    /// The narrowPeak value to rank the peaks of each replicate by.
+    #[arg(long, default_value_t = RankingMeasure::Signal)]
+    #[getset(get_copy = "pub")]
+    rank: RankingMeasure,
+    /// The maximum IDR of the written peaks [possible values: 0-1]
+    #[arg(long, default_value_t = DEFAULT_IDR_THRESHOLD)]
+    #[getset(get_copy = "pub")]
+    idr_threshold: f64,
+    /// The output file path of the peaks passing the IDR threshold [default: stdout]
+    #[arg(short, long)]
+    #[getset(get = "pub")]
+    output_file: Option<PathBuf>,
+    /// The arguments defining the interpretation of the input files.
+    #[command(flatten)]
+    #[getset(get = "pub")]
+    input_format_arguments: InputFormatArguments,
+}
+
+impl IdrArguments {
+    /// Returns the peak files of both replicates.
+    pub fn input_files(&self) -> Vec<PathBuf> {
+        vec![self.first_replicate.clone(), self.second_replicate.clone()]
+    }
+}
+
+/// The arguments of the sort command.
+#[derive(Args, CopyGetters, Getters, Debug, Clone)]
+pub struct SortArguments {
+    /// The path to the BED3+ peak file to sort.
+    #[getset(get = "pub")]
+    input_file: PathBuf,
+    /// A tab separated file of chromosome names and sizes whose names are used as canonical
+    /// chromosome names. Names only differing by a "chr" prefix are renamed accordingly.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    chromosome_sizes: Option<PathBuf>,
+    /// A UCSC chromAlias table with the canonical chromosome name in the first column and
+    /// alternative names in the following tab separated columns.
+    #[arg(long)]
+    #[getset(get = "pub")]
+    aliases: Option<PathBuf>,
+    /// Removes all but the first record with identical chromosome, start and end.
+    #[arg(long)]
+    #[getset(get_copy = "pub")]
+    deduplicate: bool,
+    /// The output file path of the sorted peaks [default: stdout]
+    #[arg(short, long)]
+    #[getset(get = "pub")]
+    output_file: Option<PathBuf>,
+}
+
+impl SortArguments {
+    /// Returns the peak file and, if specified, the chromosome sizes and alias files.
+    pub fn input_files(&self) -> Vec<PathBuf> {
+        std::iter::once(&self.input_file)
+            .chain(&self.chromosome_sizes)
+            .chain(&self.aliases)
+            .cloned()
+            .collect()
+    }
+}
+
+impl FilterArguments {
+    /// Returns the peak file and, if specified, the blacklist and whitelist files.
+    pub fn input_files(&self) -> Vec<PathBuf> {
+        std::iter::once(&self.input_file)
+            .chain(&self.blacklist)
+            .chain(&self.whitelist)
+            .cloned()
+            .collect()
+    }
+}
+
+impl ConvertArguments {
+    /// Returns the peak file and, if specified, the chromosome sizes file.
+    pub fn input_files(&self) -> Vec<PathBuf> {
+        std::iter::once(&self.input_file)
+            .chain(&self.chromosome_sizes)
+            .cloned()
+            .collect()
+    }
+}
+
+impl LiftoverArguments {
+    /// Returns the peak and chain files.
+    pub fn input_files(&self) -> Vec<PathBuf> {
+        vec![self.input_file.clone(), self.chain.clone()]
+    }
 }
 
 impl CommandLineArguments {
+    /// Parses the command line arguments complemented by the run parameters of the
+    /// configuration file specified via "--config", if any.
+    /// Parameters specified on the command line override the ones of the configuration file.
+    ///
+    /// # Parameters
+    ///
+    /// * `args` - the command line arguments starting with the binary name
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the command line arguments are invalid or if the configuration file
+    /// cannot be read or contains unknown parameters or invalid values.
+    /// Errors of the configuration file refer to the file and the offending parameter.
+    pub fn try_parse_with_config_from<I, T>(args: I) -> Result<Self, ApplicationError>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+        // Parses leniently first to find the configuration file and the arguments
+        // specified on the command line, as required arguments might be configured.
+        let matches = <Self as CommandFactory>::command()
+            .ignore_errors(true)
+            .try_get_matches_from(&args)?;
+        let args = match matches.get_one::<PathBuf>("config") {
+            Some(config) => {
+                let (options, positionals) = config_arguments(config, &matches)?;
+                merge_config_arguments(args, options, positionals)
+            },
+            None => args,
+        };
+        let matches = <Self as CommandFactory>::command().try_get_matches_from(args)?;
+        // Arguments of consensus peak generation in front of a command would be ignored,
+        // as the command is run instead.
+        if let Some((name, _)) = matches.subcommand() {
+            let mut consensus_command =
+                ConsensusArguments::augment_args(clap::Command::new("consensus"));
+            consensus_command.build();
+            if let Some(arg) = consensus_command.get_arguments().find(|arg| {
+                matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine)
+            }) {
+                return Err(ApplicationError::new(
+                    ApplicationErrorType::ConfigError,
+                    format!(
+                        "The argument \"{}\" must be specified after the command \"{}\".",
+                        arg, name
+                    ),
+                )
+                .with_hint("Only the options applying to all commands, such as \"--log-level\", may precede the command."));
+            }
+        }
+        Ok(Self::from_arg_matches(&matches)?)
+    }
+
+    /// Returns the diagnostic report file specified on the command line. The arguments are
+    /// scanned instead of parsed, so the report can also be written if they are invalid.
+    ///
+    /// # Parameters
+    ///
+    /// * `args` - the command line arguments starting with the binary name
+    pub fn error_report_from<I, T>(args: I) -> Option<PathBuf>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let mut args = args
+            .into_iter()
+            .map(Into::into)
+            .skip(1)
+            .take_while(|arg| arg != "--");
+        let mut error_report = None;
+        while let Some(arg) = args.next() {
+            let Some(arg) = arg.to_str() else {
+                continue;
+            };
+            if arg == "--error-report" {
+                error_report = args.next().map(PathBuf::from);
+            } else if let Some(value) = arg.strip_prefix("--error-report=") {
+                error_report = Some(PathBuf::from(value));
+            }
+        }
+        error_report
+    }
+
+    /// Returns `true` if JSON error output is requested on the command line. The arguments are
+    /// scanned instead of parsed, so errors parsing them can also be printed as JSON.
+    ///
+    /// # Parameters
+    ///
+    /// * `args` - the command line arguments starting with the binary name
+    pub fn error_json_from<I, T>(args: I) -> bool
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        args.into_iter()
+            .map(Into::into)
+            .skip(1)
+            .take_while(|arg| arg != "--")
+            .any(|arg| arg == "--error-json")
+    }
+
+    /// Returns the arguments of consensus peak generation, which are the arguments of the
+    /// consensus command if specified.
+    pub fn consensus_arguments(&self) -> &ConsensusArguments {
+        match &self.command {
+            Some(Command::Consensus(consensus_arguments)) => consensus_arguments,
+            _ => &self.consensus_arguments,
+        }
+    }
+
+    /// Returns the parameters affecting the content of the consensus peak output
+    /// by their argument names.
+    pub fn parameters(&self) -> BTreeMap<String, String> {
+        let consensus = self.consensus_arguments();
+        let algorithm = consensus.algorithm_arguments();
+        BTreeMap::from([
+            ("algorithm".to_string(), algorithm.algorithm.to_string()),
+            ("input-format".to_string(), consensus.input_format_arguments.input_format.to_string()),
+            (
+                "chrom-alias".to_string(),
+                self.chrom_alias
+                    .as_ref()
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_default(),
+            ),
+            ("ucsc-chromosome-names".to_string(), self.ucsc_chromosome_names.to_string()),
+            ("max-merge-iterations".to_string(), algorithm.max_merge_iterations.to_string()),
+            (
+                "summit-tolerance".to_string(),
+                algorithm
+                    .summit_tolerance
+                    .map(|tolerance| tolerance.to_string())
+                    .unwrap_or_default(),
+            ),
+            ("coordinate-aggregation".to_string(), algorithm.coordinate_aggregation.to_string()),
+            ("min-peaks-per-consensus".to_string(), algorithm.min_peaks_per_consensus.to_string()),
+            ("min-samples".to_string(), consensus.min_samples.to_string()),
+            ("harmonising-distance".to_string(), algorithm.harmonising_distance.to_string()),
+            ("max-gap".to_string(), algorithm.max_gap.to_string()),
+            ("merge-overlap".to_string(), algorithm.merge_overlap.to_string()),
+            (
+                "reciprocal-merge-overlap".to_string(),
+                algorithm.reciprocal_merge_overlap.to_string(),
+            ),
+            ("combined-stringency".to_string(), algorithm.combined_stringency.to_string()),
+            ("weak-threshold".to_string(), algorithm.weak_threshold.to_string()),
+            ("min-replicates".to_string(), algorithm.min_replicates.to_string()),
+            ("bed-output-columns".to_string(), consensus.bed_output_columns.to_string()),
+            (
+                "output-format".to_string(),
+                consensus
+                    .output_format
+                    .map(|format| format.to_string())
+                    .unwrap_or_default(),
+            ),
+            ("stable-ids".to_string(), consensus.stable_ids.to_string()),
+            ("score-aggregation".to_string(), consensus.score_aggregation.to_string()),
+            ("score-source".to_string(), consensus.score_source.to_string()),
+            ("strand-mode".to_string(), consensus.strand_mode.to_string()),
+            ("blacklist-mode".to_string(), consensus.blacklist_mode.to_string()),
+            ("per-condition".to_string(), consensus.per_condition.to_string()),
+            ("merge-duplicates".to_string(), consensus.merge_duplicates.to_string()),
+            ("provenance-column".to_string(), consensus.provenance_column.to_string()),
+            ("duplicate-overlap".to_string(), consensus.duplicate_overlap.to_string()),
+        ])
+    }
+}
+
+impl ConsensusArguments {
     /// Returns the output file.
     /// If no file has been specified the current system time and working directory are used
     /// as default output file name and directory, respectively.
@@ -76,4 +1316,341 @@ impl CommandLineArguments {
                 output
             })
     }
+
+    /// Returns the additional input files besides the peak files, such as annotations,
+    /// chromosome sizes, genome sequences and BAM or BigWig files.
+    pub fn auxiliary_input_files(&self) -> Vec<PathBuf> {
+        let files = [
+            &self.sample_sheet,
+            &self.chromosome_sizes,
+            &self.blacklist,
+            &self.annotate_with,
+            &self.gtf,
+            &self.genome,
+        ]
+        .into_iter()
+        .flatten();
+        #[cfg(feature = "bam")]
+        let files = files.chain(&self.frip_bam);
+        #[cfg(feature = "bigwig")]
+        let files = files.chain(&self.bigwig);
+        files.cloned().collect()
+    }
+
+    /// Returns the [`ConsensusRunOptions`] configured by the command line arguments.
+    ///
+    /// # Parameters
+    ///
+    /// * `parameters` - the parameters affecting the content of the consensus peak output
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any outlier threshold is not positive or the near-duplicate overlap
+    /// fraction is invalid.
+    pub fn consensus_run_options(
+        &self,
+        parameters: BTreeMap<String, String>,
+    ) -> Result<ConsensusRunOptions, ApplicationError> {
+        Ok(ConsensusRunOptions {
+            parameters,
+            auxiliary_input_files: self.auxiliary_input_files(),
+            verify_inputs: self.verify_inputs.clone(),
+            manifest: self.manifest.clone(),
+            run_id_suffix: self.run_id_suffix,
+            chromosome_sizes: self.chromosome_sizes.clone(),
+            assembly_check: self.assembly_check,
+            duplicate_report: self.duplicate_report.clone(),
+            merge_duplicates: self.merge_duplicates,
+            duplicate_overlap: validate_duplicate_overlap(self.duplicate_overlap)?,
+            annotate_with: self.annotate_with.clone(),
+            annotation_column: self.annotation_column as usize,
+            annotation_summary: self.annotation_summary.clone(),
+            gtf: self.gtf.clone(),
+            promoter_window: self.promoter_window,
+            promoter_summary: self.promoter_summary.clone(),
+            provenance_column: self.provenance_column,
+            provenance_table: self.provenance_table.clone(),
+            peak_name_mapping: self.peak_name_mapping.clone(),
+            occupancy_matrix: self.occupancy_matrix.clone(),
+            stats_file: self.stats_file.clone(),
+            summary: self.summary,
+            per_condition: self.per_condition,
+            summit_windows: self.summit_windows.clone(),
+            summit_sequences: self.summit_sequences.clone(),
+            summit_flank: self.summit_flank,
+            genome: self.genome.clone(),
+            genome_coverage: self.genome_coverage.clone(),
+            outlier_report: self.outlier_report.clone(),
+            outlier_thresholds: OutlierThresholds::new(
+                self.outlier_length_threshold,
+                self.outlier_support_threshold,
+                self.outlier_spread_threshold,
+            )?,
+            density_track: self.density_track.clone(),
+            density_correlation: self.density_correlation.clone(),
+            density_window: self.density_window,
+            #[cfg(feature = "bam")]
+            frip_bam: self.frip_bam.clone(),
+            #[cfg(feature = "bam")]
+            frip_output: self.frip_output.clone(),
+            intersection_summary: self.intersection_summary.clone(),
+            summit_concordance: self.summit_concordance.clone(),
+            stats_output: self.stats_output.clone(),
+            multiqc: self.multiqc.clone(),
+            nf_core_metadata: self.nf_core_metadata.clone(),
+            nf_core_process_name: self.nf_core_process_name.clone(),
+        })
+    }
+
+    /// Returns the [`ConsensusOutput`] configured by the command line arguments.
+    pub fn consensus_output(&self) -> ConsensusOutput {
+        let output = ConsensusOutput::new()
+            .fields(self.bed_output_columns())
+            .format(self.output_format())
+            .stable_ids(self.stable_ids())
+            .score_aggregation(self.score_aggregation())
+            .score_source(self.score_source());
+        #[cfg(feature = "bigwig")]
+        let output = output.bigwig(self.bigwig().clone(), self.refine_summits());
+        output
+    }
+
+    /// Returns a [`ConsensusPipeline`] configured by the command line arguments.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the blacklist cannot be parsed.
+    pub fn consensus_pipeline(&self) -> Result<ConsensusPipeline, ApplicationError> {
+        ConsensusPipeline::new(self.algorithm_arguments.consensus_builder())
+            .output(self.consensus_output())
+            .input_format(self.input_format_arguments.input_format())
+            .max_errors(self.collect_errors().map(|max_errors| max_errors as usize))
+            .strand_mode(self.strand_mode())
+            .streaming(self.streaming())
+            .min_samples(self.min_samples())
+            .blacklist_file(self.blacklist.as_ref(), self.blacklist_mode())
+    }
+}
+
+impl AlgorithmArguments {
+    /// Returns a [`ConsensusBuilder`] configured by the command line arguments.
+    pub fn consensus_builder(&self) -> ConsensusBuilder {
+        ConsensusBuilder::new()
+            .algorithm(self.algorithm())
+            .max_merge_iterations(self.max_merge_iterations())
+            .summit_tolerance(self.summit_tolerance())
+            .coordinate_aggregation(self.coordinate_aggregation())
+            .aggregation_backend(self.aggregation_backend())
+            .min_peaks_per_consensus(self.min_peaks_per_consensus())
+            .harmonising_distance(self.harmonising_distance())
+            .max_gap(self.max_gap())
+            .min_overlap(self.merge_overlap())
+            .reciprocal_overlap(self.reciprocal_merge_overlap())
+            .combined_stringency(self.combined_stringency())
+            .weak_threshold(self.weak_threshold())
+            .min_replicates(self.min_replicates())
+            .spill_directory(self.spill_directory().as_ref())
+            .spill_compression_level(self.spill_compression_level())
+            .threads(self.threads())
+    }
+}
+
+/// Converts the run parameters of a TOML configuration file into command line arguments
+/// of the command specified on the command line or of consensus peak generation if no command
+/// is specified. Top-level parameters configure consensus peak generation and the options
+/// applying to all commands, tables named like a command, such as "[validate]", configure
+/// the respective command and take precedence over top-level parameters.
+/// Parameters already specified on the command line are skipped.
+/// Returns the options and the positional arguments separately.
+///
+/// # Parameters
+///
+/// * `path` - the path of the configuration file
+/// * `matches` - the arguments specified on the command line
+///
+/// # Errors
+///
+/// Returns an error if the configuration file cannot be read or parsed,
+/// or if it contains unknown commands or parameters or values of an unsupported type.
+fn config_arguments(
+    path: &Path,
+    matches: &ArgMatches,
+) -> Result<(Vec<OsString>, Vec<OsString>), ApplicationError> {
+    let context = || ErrorContext::new().with_path(path.display());
+    let content = std::fs::read_to_string(path).map_err(|err| {
+        ApplicationError::from(err)
+            .chain("The configuration file could not be read.")
+            .with_context(context())
+    })?;
+    let parameters = content.parse::<toml::Table>().map_err(|err| {
+        ApplicationError::new(ApplicationErrorType::ParseError, err.message())
+            .chain("The configuration file is not valid TOML.")
+            .with_context(context())
+    })?;
+    let config_directory = path.parent().unwrap_or(Path::new(""));
+    let command = <CommandLineArguments as CommandFactory>::command();
+    let global_arguments = || command.get_arguments().filter(|arg| arg.is_global_set());
+    // The consensus command accepts the same arguments as the application without a command.
+    let invoked_command = matches
+        .subcommand_name()
+        .filter(|name| *name != "consensus");
+    let unknown_parameter = |key: &str| {
+        ApplicationError::new(
+            ApplicationErrorType::ConfigError,
+            format!("The configuration parameter \"{}\" is unknown.", key),
+        )
+        .with_hint("Parameters are named like the long command line arguments, such as \"algorithm\" for \"--algorithm\", and parameters of commands are declared in a table named like the command, such as \"[validate]\".")
+        .with_context(context().with_value(key))
+    };
+    // Collects the parameters applying to the invoked command, so parameters of the command
+    // table can replace top-level parameters.
+    let mut applicable: Vec<(&clap::Arg, String, toml::Value)> = Vec::new();
+    for (key, value) in parameters.iter() {
+        if let toml::Value::Table(table) = value {
+            let subcommand = command
+                .find_subcommand(key)
+                .filter(|subcommand| subcommand.get_name() != "help")
+                .ok_or_else(|| {
+                    ApplicationError::new(
+                        ApplicationErrorType::ConfigError,
+                        format!("The configuration table \"{}\" does not name a command.", key),
+                    )
+                    .with_context(context().with_value(key))
+                })?;
+            let is_invoked = invoked_command.unwrap_or("consensus") == key;
+            for (table_key, value) in table {
+                let arg = find_config_argument(
+                    subcommand.get_arguments().chain(global_arguments()),
+                    &table_key.replace('_', "-"),
+                )
+                .ok_or_else(|| unknown_parameter(&format!("{}.{}", key, table_key)))?;
+                if is_invoked {
+                    applicable.push((arg, table_key.clone(), value.clone()));
+                }
+            }
+        }
+    }
+    for (key, value) in parameters.iter() {
+        if value.is_table() {
+            continue;
+        }
+        let arg = find_config_argument(command.get_arguments(), &key.replace('_', "-"))
+            .ok_or_else(|| unknown_parameter(key))?;
+        // Top-level parameters of consensus peak generation do not apply to other commands.
+        if invoked_command.is_none() || arg.is_global_set() {
+            let is_replaced = applicable
+                .iter()
+                .any(|(applicable_arg, _, _)| applicable_arg.get_id() == arg.get_id());
+            if !is_replaced {
+                applicable.push((arg, key.clone(), value.clone()));
+            }
+        }
+    }
+    let command_matches = matches
+        .subcommand()
+        .map_or(matches, |(_, subcommand_matches)| subcommand_matches);
+    let mut options = Vec::new();
+    let mut positionals = Vec::new();
+    for (arg, key, value) in applicable {
+        let name = key.replace('_', "-");
+        let id = arg.get_id().as_str();
+        if command_matches.value_source(id) == Some(ValueSource::CommandLine)
+            || matches.value_source(id) == Some(ValueSource::CommandLine)
+        {
+            continue;
+        }
+        let is_flag = matches!(arg.get_action(), ArgAction::SetTrue);
+        let is_path = arg.get_value_parser().type_id() == TypeId::of::<PathBuf>();
+        let values = match value {
+            toml::Value::Array(values) => values,
+            value => vec![value],
+        };
+        for value in values {
+            let value = match value {
+                toml::Value::String(value) => value,
+                toml::Value::Integer(value) => value.to_string(),
+                toml::Value::Float(value) => value.to_string(),
+                toml::Value::Boolean(value) if is_flag => {
+                    if value {
+                        options.push(OsString::from(format!("--{}", name)));
+                    }
+                    continue;
+                },
+                toml::Value::Boolean(value) => value.to_string(),
+                value => {
+                    return Err(ApplicationError::new(
+                        ApplicationErrorType::ConfigError,
+                        format!(
+                            "The {} value of the configuration parameter \"{}\" is not supported.",
+                            value.type_str(),
+                            key
+                        ),
+                    )
+                    .with_context(context().with_value(&key)));
+                },
+            };
+            // Uses "-" for the standard streams.
+            let value = if is_path && value != "-" {
+                config_directory.join(value).into_os_string()
+            } else {
+                OsString::from(value)
+            };
+            if arg.is_positional() {
+                positionals.push(value);
+            } else {
+                let mut argument = OsString::from(format!("--{}=", name));
+                argument.push(value);
+                options.push(argument);
+            }
+        }
+    }
+    Ok((options, positionals))
+}
+
+/// Returns the argument with the specified long name or the positional argument with the
+/// specified identifier, excluding the configuration file itself.
+///
+/// # Parameters
+///
+/// * `arguments` - the arguments to search
+/// * `name` - the name of the configuration parameter with dashes instead of underscores
+fn find_config_argument<'a>(
+    mut arguments: impl Iterator<Item = &'a clap::Arg>,
+    name: &str,
+) -> Option<&'a clap::Arg> {
+    arguments.find(|arg| {
+        arg.get_id() != "config"
+            && (arg.get_long() == Some(name)
+                || (arg.is_positional() && arg.get_id().as_str().replace('_', "-") == name))
+    })
+}
+
+/// Adds configured arguments to the command line arguments, so they belong to the command
+/// specified last. The options are inserted in front of the end of options marker "--", if any,
+/// and the positional arguments are appended after it.
+///
+/// # Parameters
+///
+/// * `args` - the command line arguments starting with the binary name
+/// * `options` - the configured options
+/// * `positionals` - the configured positional arguments
+fn merge_config_arguments(
+    mut args: Vec<OsString>,
+    options: Vec<OsString>,
+    positionals: Vec<OsString>,
+) -> Vec<OsString> {
+    let end_of_options = args
+        .iter()
+        .skip(1)
+        .position(|arg| arg == "--")
+        .map(|index| index + 1);
+    let insert_position = end_of_options.unwrap_or(args.len());
+    args.splice(insert_position..insert_position, options);
+    if !positionals.is_empty() {
+        if end_of_options.is_none() {
+            args.push(OsString::from("--"));
+        }
+        args.extend(positionals);
+    }
+    args
 }
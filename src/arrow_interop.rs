@@ -0,0 +1,308 @@
+//! This module converts peak sets to and from [Arrow](https://arrow.apache.org/) record batches,
+//! which allows exchanging peaks with Arrow based query engines like DataFusion or DuckDB.
+//!
+//! Each row of a record batch represents a single peak with the columns `chromosome`, `id`,
+//! `start`, `end` and `summit`. Coordinates are 0-based and the end coordinate is inclusive
+//! as for [`PeakData`].
+
+use std::sync::Arc;
+
+use arrow_array::{
+    Array, ArrayRef, RecordBatch, StringArray, UInt64Array,
+    cast::AsArray,
+    types::{Int32Type, Int64Type, UInt32Type, UInt64Type},
+};
+use arrow_schema::{DataType, Field, Schema};
+
+use crate::{
+    chromosome::Chromosome,
+    error::{ApplicationError, ApplicationErrorType},
+    peak_set::{CHROMOSOME_COLUMN, END_COLUMN, ID_COLUMN, PeakSet, START_COLUMN, SUMMIT_COLUMN},
+    peaks::PeakData,
+};
+
+/// Returns the schema of record batches created from peak sets.
+pub fn peak_schema() -> Schema {
+    Schema::new(vec![
+        Field::new(CHROMOSOME_COLUMN, DataType::Utf8, false),
+        Field::new(ID_COLUMN, DataType::UInt64, false),
+        Field::new(START_COLUMN, DataType::UInt64, false),
+        Field::new(END_COLUMN, DataType::UInt64, false),
+        Field::new(SUMMIT_COLUMN, DataType::UInt64, false),
+    ])
+}
+
+impl PeakSet {
+    /// Returns a record batch containing all peaks sorted by chromosome and position.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the record batch cannot be created.
+    pub fn to_record_batch(&self) -> Result<RecordBatch, ApplicationError> {
+        let mut chromosomes = Vec::with_capacity(self.len());
+        let mut ids = Vec::with_capacity(self.len());
+        let mut starts = Vec::with_capacity(self.len());
+        let mut ends = Vec::with_capacity(self.len());
+        let mut summits = Vec::with_capacity(self.len());
+        for chromosome in self.chromosomes() {
+            for peak in self.peaks(chromosome) {
+                chromosomes.push(chromosome.name());
+                ids.push(peak.id() as u64);
+                starts.push(peak.start());
+                ends.push(peak.end());
+                summits.push(peak.summit());
+            }
+        }
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from(chromosomes)),
+            Arc::new(UInt64Array::from(ids)),
+            Arc::new(UInt64Array::from(starts)),
+            Arc::new(UInt64Array::from(ends)),
+            Arc::new(UInt64Array::from(summits)),
+        ];
+        RecordBatch::try_new(Arc::new(peak_schema()), columns).map_err(|err| {
+            ApplicationError::from(err).chain("The peak record batch could not be created.")
+        })
+    }
+
+    /// Creates a peak set from a record batch containing the peak columns.
+    /// Additional columns are ignored. Chromosomes may be stored as any Arrow string type
+    /// and coordinates as any 32 or 64 bit integer type.
+    ///
+    /// # Parameters
+    ///
+    /// * `batch` - the record batch containing the peaks
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a column is missing or contains missing values or invalid peaks.
+    pub fn from_record_batch(batch: &RecordBatch) -> Result<Self, ApplicationError> {
+        Self::from_record_batches([batch])
+    }
+
+    /// Creates a peak set from multiple record batches containing the peak columns,
+    /// for example the result batches of a query.
+    ///
+    /// # Parameters
+    ///
+    /// * `batches` - the record batches containing the peaks
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a column is missing or contains missing values or invalid peaks.
+    pub fn from_record_batches<'a, T: IntoIterator<Item = &'a RecordBatch>>(
+        batches: T,
+    ) -> Result<Self, ApplicationError> {
+        let mut peaks = Vec::new();
+        for batch in batches {
+            peaks.extend(record_batch_peaks(batch)?);
+        }
+        Ok(peaks.into_iter().collect())
+    }
+}
+
+/// Parses the peaks contained in a record batch.
+///
+/// # Parameters
+///
+/// * `batch` - the record batch containing the peaks
+fn record_batch_peaks(
+    batch: &RecordBatch,
+) -> Result<Vec<(Chromosome, PeakData)>, ApplicationError> {
+    let chromosomes = string_column(batch, CHROMOSOME_COLUMN)?;
+    let ids = u64_column(batch, ID_COLUMN)?;
+    let starts = u64_column(batch, START_COLUMN)?;
+    let ends = u64_column(batch, END_COLUMN)?;
+    let summits = u64_column(batch, SUMMIT_COLUMN)?;
+    (0..batch.num_rows())
+        .map(|row| {
+            let (Some(chromosome), Some(id), Some(start), Some(end), Some(summit)) =
+                (chromosomes[row], ids[row], starts[row], ends[row], summits[row])
+            else {
+                return Err(ApplicationError::new(
+                    ApplicationErrorType::ValidationError,
+                    format!("Row {} of the peak record batch contains missing values.", row),
+                ));
+            };
+            Ok((Chromosome::intern(chromosome), PeakData::new(id as usize, start, end, summit)?))
+        })
+        .collect()
+}
+
+/// Returns the specified column of a record batch or an error if the column does not exist.
+///
+/// # Parameters
+///
+/// * `batch` - the record batch containing the column
+/// * `name` - the name of the column
+fn column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a ArrayRef, ApplicationError> {
+    batch.column_by_name(name).ok_or_else(|| {
+        ApplicationError::new(
+            ApplicationErrorType::ValidationError,
+            format!("The peak record batch does not contain the column \"{}\".", name),
+        )
+    })
+}
+
+/// Returns the values of the specified string column.
+///
+/// # Parameters
+///
+/// * `batch` - the record batch containing the column
+/// * `name` - the name of the column
+fn string_column<'a>(
+    batch: &'a RecordBatch,
+    name: &str,
+) -> Result<Vec<Option<&'a str>>, ApplicationError> {
+    let array = column(batch, name)?;
+    match array.data_type() {
+        DataType::Utf8 => Ok(array.as_string::<i32>().iter().collect()),
+        DataType::LargeUtf8 => Ok(array.as_string::<i64>().iter().collect()),
+        DataType::Utf8View => Ok(array.as_string_view().iter().collect()),
+        data_type => Err(unsupported_data_type(name, data_type)),
+    }
+}
+
+/// Returns the values of the specified integer column as unsigned 64 bit integers.
+///
+/// # Parameters
+///
+/// * `batch` - the record batch containing the column
+/// * `name` - the name of the column
+fn u64_column(batch: &RecordBatch, name: &str) -> Result<Vec<Option<u64>>, ApplicationError> {
+    let array = column(batch, name)?;
+    let negative_value = |_| {
+        ApplicationError::new(
+            ApplicationErrorType::ValidationError,
+            format!("The peak record batch column \"{}\" contains negative values.", name),
+        )
+    };
+    match array.data_type() {
+        DataType::UInt64 => Ok(array.as_primitive::<UInt64Type>().iter().collect()),
+        DataType::UInt32 => Ok(array
+            .as_primitive::<UInt32Type>()
+            .iter()
+            .map(|value| value.map(u64::from))
+            .collect()),
+        DataType::Int64 => array
+            .as_primitive::<Int64Type>()
+            .iter()
+            .map(|value| value.map(u64::try_from).transpose().map_err(negative_value))
+            .collect(),
+        DataType::Int32 => array
+            .as_primitive::<Int32Type>()
+            .iter()
+            .map(|value| value.map(u64::try_from).transpose().map_err(negative_value))
+            .collect(),
+        data_type => Err(unsupported_data_type(name, data_type)),
+    }
+}
+
+/// Creates the error for a column of an unsupported data type.
+///
+/// # Parameters
+///
+/// * `name` - the name of the column
+/// * `data_type` - the data type of the column
+fn unsupported_data_type(name: &str, data_type: &DataType) -> ApplicationError {
+    ApplicationError::new(
+        ApplicationErrorType::ValidationError,
+        format!(
+            "The peak record batch column \"{}\" has the unsupported data type {}.",
+            name, data_type
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{Int64Array, LargeStringArray};
+
+    use super::*;
+
+    #[test]
+    fn test_peak_set_record_batch() {
+        let peak_set: PeakSet = [
+            (Chromosome::from("chr2"), PeakData::new(2, 50u64, 99u64, 70u64).unwrap()),
+            (Chromosome::from("chr1"), PeakData::new(1, 300u64, 399u64, 350u64).unwrap()),
+            (Chromosome::from("chr1"), PeakData::new(0, 100u64, 199u64, 150u64).unwrap()),
+        ]
+        .into_iter()
+        .collect();
+        let batch = peak_set.to_record_batch().unwrap();
+        assert_eq!(batch.schema().as_ref(), &peak_schema());
+        assert_eq!(batch.num_rows(), 3);
+        assert_eq!(
+            batch
+                .column(0)
+                .as_string::<i32>()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![Some("chr1"), Some("chr1"), Some("chr2")]
+        );
+        assert_eq!(
+            batch
+                .column(2)
+                .as_primitive::<UInt64Type>()
+                .values()
+                .as_ref(),
+            &[100, 300, 50]
+        );
+        assert_eq!(PeakSet::from_record_batch(&batch).unwrap(), peak_set);
+        let empty = PeakSet::new().to_record_batch().unwrap();
+        assert_eq!(empty.num_rows(), 0);
+        assert_eq!(PeakSet::from_record_batches([&empty, &batch, &empty]).unwrap(), peak_set);
+    }
+
+    #[test]
+    fn test_peak_set_from_record_batch_types() {
+        let batch = RecordBatch::try_from_iter(vec![
+            ("summit", Arc::new(Int64Array::from(vec![15])) as ArrayRef),
+            ("chromosome", Arc::new(LargeStringArray::from(vec!["chr1"])) as ArrayRef),
+            ("id", Arc::new(arrow_array::Int32Array::from(vec![7])) as ArrayRef),
+            ("start", Arc::new(arrow_array::UInt32Array::from(vec![10])) as ArrayRef),
+            ("end", Arc::new(Int64Array::from(vec![20])) as ArrayRef),
+            ("score", Arc::new(arrow_array::Float64Array::from(vec![1.5])) as ArrayRef),
+        ])
+        .unwrap();
+        assert_eq!(
+            PeakSet::from_record_batch(&batch)
+                .unwrap()
+                .peaks(Chromosome::from("chr1")),
+            &[PeakData::new(7, 10u64, 20u64, 15u64).unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_peak_set_from_record_batch_invalid() {
+        let valid_columns = || {
+            vec![
+                ("chromosome", Arc::new(StringArray::from(vec!["chr1"])) as ArrayRef),
+                ("id", Arc::new(UInt64Array::from(vec![0])) as ArrayRef),
+                ("start", Arc::new(Int64Array::from(vec![10])) as ArrayRef),
+                ("end", Arc::new(UInt64Array::from(vec![20])) as ArrayRef),
+                ("summit", Arc::new(UInt64Array::from(vec![15])) as ArrayRef),
+            ]
+        };
+        let mut missing_column = valid_columns();
+        missing_column.pop();
+        let mut negative_start = valid_columns();
+        negative_start[2].1 = Arc::new(Int64Array::from(vec![-10]));
+        let mut missing_value = valid_columns();
+        missing_value[3].1 = Arc::new(UInt64Array::from(vec![None]));
+        let mut unsupported_type = valid_columns();
+        unsupported_type[4].1 = Arc::new(arrow_array::Float64Array::from(vec![15.0]));
+        for columns in [
+            missing_column,
+            negative_start,
+            missing_value,
+            unsupported_type,
+        ] {
+            let batch = RecordBatch::try_from_iter(columns).unwrap();
+            assert!(matches!(
+                PeakSet::from_record_batch(&batch).unwrap_err().error_type(),
+                ApplicationErrorType::ValidationError
+            ));
+        }
+    }
+}
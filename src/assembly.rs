@@ -0,0 +1,522 @@
+//! This module detects input files that appear to originate from different genome assemblies,
+//! as combining such files silently produces meaningless consensus peaks.
+//!
+//! The assembly of a file is inferred from its chromosome naming style and the maximum peak
+//! coordinate per chromosome compared to the chromosome sizes of common assemblies and,
+//! optionally, to user-provided chromosome sizes.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+};
+
+use getset::{CopyGetters, Getters};
+
+use crate::{chromosome::Chromosome, peak_set::PeakSet};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "assembly";
+
+/// The handling of detected assembly inconsistencies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum AssemblyCheck {
+    /// Inconsistencies are logged as warnings.
+    Warn,
+    /// Inconsistencies abort the run.
+    Error,
+}
+
+/// The sizes of the primary chromosomes of a common genome assembly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownAssembly {
+    /// The name of the assembly.
+    pub name: &'static str,
+    /// The primary chromosomes without "chr" prefix and their sizes.
+    pub chromosome_sizes: &'static [(&'static str, u64)],
+}
+
+impl KnownAssembly {
+    /// Returns the size of the chromosome or `None` if it is not a primary chromosome
+    /// of the assembly. The "chr" prefix of the chromosome name is ignored.
+    ///
+    /// # Parameters
+    ///
+    /// * `chromosome` - the chromosome name
+    pub fn chromosome_size(&self, chromosome: &str) -> Option<u64> {
+        let chromosome = strip_chromosome_prefix(chromosome);
+        self.chromosome_sizes
+            .iter()
+            .find(|(name, _)| *name == chromosome)
+            .map(|(_, size)| *size)
+    }
+}
+
+/// The common genome assemblies used to infer the assembly of input files.
+pub const KNOWN_ASSEMBLIES: [KnownAssembly; 4] = [
+    KnownAssembly {
+        name: "hg19",
+        chromosome_sizes: &[
+            ("1", 249_250_621),
+            ("2", 243_199_373),
+            ("3", 198_022_430),
+            ("4", 191_154_276),
+            ("5", 180_915_260),
+            ("6", 171_115_067),
+            ("7", 159_138_663),
+            ("8", 146_364_022),
+            ("9", 141_213_431),
+            ("10", 135_534_747),
+            ("11", 135_006_516),
+            ("12", 133_851_895),
+            ("13", 115_169_878),
+            ("14", 107_349_540),
+            ("15", 102_531_392),
+            ("16", 90_354_753),
+            ("17", 81_195_210),
+            ("18", 78_077_248),
+            ("19", 59_128_983),
+            ("20", 63_025_520),
+            ("21", 48_129_895),
+            ("22", 51_304_566),
+            ("X", 155_270_560),
+            ("Y", 59_373_566),
+        ],
+    },
+    KnownAssembly {
+        name: "hg38",
+        chromosome_sizes: &[
+            ("1", 248_956_422),
+            ("2", 242_193_529),
+            ("3", 198_295_559),
+            ("4", 190_214_555),
+            ("5", 181_538_259),
+            ("6", 170_805_979),
+            ("7", 159_345_973),
+            ("8", 145_138_636),
+            ("9", 138_394_717),
+            ("10", 133_797_422),
+            ("11", 135_086_622),
+            ("12", 133_275_309),
+            ("13", 114_364_328),
+            ("14", 107_043_718),
+            ("15", 101_991_189),
+            ("16", 90_338_345),
+            ("17", 83_257_441),
+            ("18", 80_373_285),
+            ("19", 58_617_616),
+            ("20", 64_444_167),
+            ("21", 46_709_983),
+            ("22", 50_818_468),
+            ("X", 156_040_895),
+            ("Y", 57_227_415),
+        ],
+    },
+    KnownAssembly {
+        name: "mm10",
+        chromosome_sizes: &[
+            ("1", 195_471_971),
+            ("2", 182_113_224),
+            ("3", 160_039_680),
+            ("4", 156_508_116),
+            ("5", 151_834_684),
+            ("6", 149_736_546),
+            ("7", 145_441_459),
+            ("8", 129_401_213),
+            ("9", 124_595_110),
+            ("10", 130_694_993),
+            ("11", 122_082_543),
+            ("12", 120_129_022),
+            ("13", 120_421_639),
+            ("14", 124_902_244),
+            ("15", 104_043_685),
+            ("16", 98_207_768),
+            ("17", 94_987_271),
+            ("18", 90_702_639),
+            ("19", 61_431_566),
+            ("X", 171_031_299),
+            ("Y", 91_744_698),
+        ],
+    },
+    KnownAssembly {
+        name: "mm39",
+        chromosome_sizes: &[
+            ("1", 195_154_279),
+            ("2", 181_755_017),
+            ("3", 159_745_316),
+            ("4", 156_860_686),
+            ("5", 151_758_149),
+            ("6", 149_588_044),
+            ("7", 144_995_196),
+            ("8", 130_127_694),
+            ("9", 124_359_700),
+            ("10", 130_530_862),
+            ("11", 121_973_369),
+            ("12", 120_092_757),
+            ("13", 120_883_175),
+            ("14", 125_139_656),
+            ("15", 104_073_951),
+            ("16", 98_008_968),
+            ("17", 95_294_699),
+            ("18", 90_720_763),
+            ("19", 61_420_004),
+            ("X", 169_476_592),
+            ("Y", 91_455_967),
+        ],
+    },
+];
+
+/// Returns the chromosome name without "chr" prefix.
+///
+/// # Parameters
+///
+/// * `chromosome` - the chromosome name
+fn strip_chromosome_prefix(chromosome: &str) -> &str {
+    chromosome.strip_prefix("chr").unwrap_or(chromosome)
+}
+
+/// Returns `true` if the chromosome is a primary chromosome of any known assembly.
+///
+/// # Parameters
+///
+/// * `chromosome` - the chromosome name
+fn is_primary_chromosome(chromosome: &str) -> bool {
+    KNOWN_ASSEMBLIES
+        .iter()
+        .any(|assembly| assembly.chromosome_size(chromosome).is_some())
+}
+
+/// The chromosome naming style of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ChromosomeNaming {
+    /// The primary chromosomes are prefixed with "chr" as in UCSC assemblies.
+    Ucsc,
+    /// The primary chromosomes are not prefixed as in Ensembl assemblies.
+    Ensembl,
+    /// Both naming styles are used.
+    Mixed,
+    /// The file does not contain any primary chromosome.
+    Unknown,
+}
+
+impl Display for ChromosomeNaming {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChromosomeNaming::Ucsc => write!(f, "UCSC (\"chr1\")"),
+            ChromosomeNaming::Ensembl => write!(f, "Ensembl (\"1\")"),
+            ChromosomeNaming::Mixed => write!(f, "mixed"),
+            ChromosomeNaming::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// The assembly characteristics of the peaks of a single file.
+#[derive(CopyGetters, Getters, Clone, PartialEq, Eq, Debug)]
+pub struct AssemblyProfile {
+    /// The chromosome naming style.
+    #[getset(get_copy = "pub")]
+    naming: ChromosomeNaming,
+    /// The maximum peak end per chromosome.
+    #[getset(get = "pub")]
+    max_ends: BTreeMap<Chromosome, u64>,
+    /// The names of the known assemblies containing all primary chromosomes of the file
+    /// with a size of at least the maximum peak end.
+    #[getset(get = "pub")]
+    compatible_assemblies: Vec<&'static str>,
+}
+
+impl AssemblyProfile {
+    /// Creates the assembly profile of the peaks of a single file.
+    ///
+    /// # Parameters
+    ///
+    /// * `peaks` - the peaks of the file
+    pub fn new(peaks: &PeakSet) -> Self {
+        let mut max_ends: BTreeMap<Chromosome, u64> = BTreeMap::new();
+        for (chromosome, peak) in peaks.iter() {
+            let max_end = max_ends.entry(chromosome).or_default();
+            *max_end = (*max_end).max(peak.end());
+        }
+        let primary: Vec<(&str, u64)> = max_ends
+            .iter()
+            .map(|(chromosome, end)| (chromosome.name(), *end))
+            .filter(|(chromosome, _)| is_primary_chromosome(chromosome))
+            .collect();
+        let prefixed = primary
+            .iter()
+            .filter(|(chromosome, _)| chromosome.starts_with("chr"))
+            .count();
+        let naming = match prefixed {
+            _ if primary.is_empty() => ChromosomeNaming::Unknown,
+            0 => ChromosomeNaming::Ensembl,
+            prefixed if prefixed == primary.len() => ChromosomeNaming::Ucsc,
+            _ => ChromosomeNaming::Mixed,
+        };
+        let compatible_assemblies = KNOWN_ASSEMBLIES
+            .iter()
+            .filter(|assembly| {
+                primary.iter().all(|(chromosome, end)| {
+                    assembly
+                        .chromosome_size(chromosome)
+                        .is_some_and(|size| *end <= size)
+                })
+            })
+            .map(|assembly| assembly.name)
+            .collect();
+        Self {
+            naming,
+            max_ends,
+            compatible_assemblies,
+        }
+    }
+}
+
+/// An indication that input files originate from different genome assemblies.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum AssemblyInconsistency {
+    /// The files use different chromosome naming styles.
+    Naming {
+        /// The files and their naming styles.
+        files: Vec<(String, ChromosomeNaming)>,
+    },
+    /// The files are not compatible with any common known assembly.
+    NoCommonAssembly {
+        /// The files and their compatible known assemblies.
+        files: Vec<(String, Vec<&'static str>)>,
+    },
+    /// A file contains peaks beyond the end of a chromosome.
+    BeyondChromosomeEnd {
+        /// The file.
+        file: String,
+        /// The chromosome.
+        chromosome: Chromosome,
+        /// The maximum peak end on the chromosome.
+        max_end: u64,
+        /// The size of the chromosome.
+        size: u64,
+    },
+    /// A file contains peaks on a chromosome missing from the chromosome sizes.
+    UnknownChromosome {
+        /// The file.
+        file: String,
+        /// The chromosome.
+        chromosome: Chromosome,
+    },
+}
+
+impl Display for AssemblyInconsistency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssemblyInconsistency::Naming { files } => {
+                let files: Vec<String> = files
+                    .iter()
+                    .map(|(file, naming)| format!("\"{}\": {}", file, naming))
+                    .collect();
+                write!(
+                    f,
+                    "The input files use different chromosome naming styles: {}.",
+                    files.join(", ")
+                )
+            },
+            AssemblyInconsistency::NoCommonAssembly { files } => {
+                let files: Vec<String> = files
+                    .iter()
+                    .map(|(file, assemblies)| format!("\"{}\": {}", file, assemblies.join("/")))
+                    .collect();
+                write!(
+                    f,
+                    "The peak coordinates of the input files are not compatible with a common \
+                    assembly, which indicates different genome builds: {}.",
+                    files.join(", ")
+                )
+            },
+            AssemblyInconsistency::BeyondChromosomeEnd {
+                file,
+                chromosome,
+                max_end,
+                size,
+            } => write!(
+                f,
+                "The input file \"{}\" contains peaks up to position {} on chromosome {} \
+                exceeding its size of {}.",
+                file, max_end, chromosome, size
+            ),
+            AssemblyInconsistency::UnknownChromosome { file, chromosome } => write!(
+                f,
+                "The input file \"{}\" contains peaks on chromosome {} missing from the \
+                chromosome sizes.",
+                file, chromosome
+            ),
+        }
+    }
+}
+
+/// Detects indications that the input files originate from different genome assemblies
+/// or from an assembly different from the specified chromosome sizes.
+/// Files without primary chromosomes of a known assembly, for example of other species,
+/// are only checked against the chromosome sizes.
+///
+/// # Parameters
+///
+/// * `file_names` - the names of the files
+/// * `profiles` - the assembly profiles of the files in the same order
+/// * `chromosome_sizes` - the optional chromosome sizes of the expected assembly
+pub fn assembly_inconsistencies<S: AsRef<str>>(
+    file_names: &[S],
+    profiles: &[AssemblyProfile],
+    chromosome_sizes: Option<&BTreeMap<Chromosome, u64>>,
+) -> Vec<AssemblyInconsistency> {
+    let mut inconsistencies = Vec::new();
+    let files = || {
+        file_names
+            .iter()
+            .map(|name| name.as_ref().to_string())
+            .zip(profiles)
+    };
+    let namings: BTreeSet<ChromosomeNaming> = profiles
+        .iter()
+        .map(AssemblyProfile::naming)
+        .filter(|naming| *naming != ChromosomeNaming::Unknown)
+        .collect();
+    if namings.len() > 1 {
+        inconsistencies.push(AssemblyInconsistency::Naming {
+            files: files()
+                .filter(|(_, profile)| profile.naming() != ChromosomeNaming::Unknown)
+                .map(|(file, profile)| (file, profile.naming()))
+                .collect(),
+        });
+    }
+    let assigned: Vec<(String, &AssemblyProfile)> = files()
+        .filter(|(_, profile)| {
+            profile.naming() != ChromosomeNaming::Unknown
+                && !profile.compatible_assemblies().is_empty()
+        })
+        .collect();
+    let has_common_assembly = KNOWN_ASSEMBLIES.iter().any(|assembly| {
+        assigned
+            .iter()
+            .all(|(_, profile)| profile.compatible_assemblies().contains(&assembly.name))
+    });
+    if !has_common_assembly {
+        inconsistencies.push(AssemblyInconsistency::NoCommonAssembly {
+            files: assigned
+                .into_iter()
+                .map(|(file, profile)| (file, profile.compatible_assemblies().clone()))
+                .collect(),
+        });
+    }
+    if let Some(chromosome_sizes) = chromosome_sizes {
+        for (file, profile) in files() {
+            for (chromosome, max_end) in profile.max_ends() {
+                match chromosome_sizes.get(chromosome) {
+                    Some(size) if max_end > size => {
+                        inconsistencies.push(AssemblyInconsistency::BeyondChromosomeEnd {
+                            file: file.clone(),
+                            chromosome: *chromosome,
+                            max_end: *max_end,
+                            size: *size,
+                        })
+                    },
+                    Some(_) => {},
+                    None => inconsistencies.push(AssemblyInconsistency::UnknownChromosome {
+                        file: file.clone(),
+                        chromosome: *chromosome,
+                    }),
+                }
+            }
+        }
+    }
+    for (file, profile) in files() {
+        log::debug!(
+            target: LOG_TARGET,
+            "The input file \"{}\" uses {} chromosome names and is compatible with the \
+            assemblies {:?}.",
+            file,
+            profile.naming(),
+            profile.compatible_assemblies()
+        );
+    }
+    inconsistencies
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::peak_set;
+
+    use super::*;
+
+    #[test]
+    fn test_assembly_profile() {
+        let profile = AssemblyProfile::new(&peak_set(&[
+            ("chr1", 248_999_900, 249_000_000, 248_999_950),
+            ("chr21", 900, 1000, 950),
+            ("chrUn_gl000220", 4900, 5000, 4950),
+        ]));
+        assert_eq!(profile.naming(), ChromosomeNaming::Ucsc);
+        assert_eq!(profile.compatible_assemblies(), &vec!["hg19"]);
+        assert_eq!(profile.max_ends()[&Chromosome::from("chrUn_gl000220")], 5000);
+
+        let profile =
+            AssemblyProfile::new(&peak_set(&[("1", 900, 1000, 950), ("X", 900, 1000, 950)]));
+        assert_eq!(profile.naming(), ChromosomeNaming::Ensembl);
+        assert_eq!(profile.compatible_assemblies().len(), 4);
+
+        let profile =
+            AssemblyProfile::new(&peak_set(&[("chr1", 900, 1000, 950), ("2", 900, 1000, 950)]));
+        assert_eq!(profile.naming(), ChromosomeNaming::Mixed);
+
+        let profile = AssemblyProfile::new(&peak_set(&[("scaffold_1", 900, 1000, 950)]));
+        assert_eq!(profile.naming(), ChromosomeNaming::Unknown);
+        assert_eq!(profile.compatible_assemblies().len(), 4);
+    }
+
+    #[test]
+    fn test_assembly_inconsistencies() {
+        let hg19 =
+            AssemblyProfile::new(&peak_set(&[("chr1", 248_999_900, 249_000_000, 248_999_950)]));
+        let hg38 = AssemblyProfile::new(&peak_set(&[
+            ("chr21", 900, 1000, 950),
+            ("chrX", 155_999_900, 156_000_000, 155_999_950),
+        ]));
+        let ensembl = AssemblyProfile::new(&peak_set(&[("1", 900, 1000, 950)]));
+        let unknown = AssemblyProfile::new(&peak_set(&[("scaffold_1", 900, 1000, 950)]));
+        assert!(
+            assembly_inconsistencies(&["a", "b"], &[hg19.clone(), unknown.clone()], None)
+                .is_empty()
+        );
+        assert_eq!(
+            assembly_inconsistencies(&["a", "b", "c"], &[hg19.clone(), hg38, unknown], None),
+            vec![AssemblyInconsistency::NoCommonAssembly {
+                files: vec![
+                    ("a".to_string(), vec!["hg19"]),
+                    ("b".to_string(), vec!["hg38"]),
+                ]
+            }]
+        );
+        assert_eq!(
+            assembly_inconsistencies(&["a", "b"], &[hg19.clone(), ensembl], None),
+            vec![AssemblyInconsistency::Naming {
+                files: vec![
+                    ("a".to_string(), ChromosomeNaming::Ucsc),
+                    ("b".to_string(), ChromosomeNaming::Ensembl),
+                ]
+            }]
+        );
+        let chromosome_sizes = BTreeMap::from([(Chromosome::from("chr1"), 248_956_422)]);
+        let inconsistencies = assembly_inconsistencies(&["a"], &[hg19], Some(&chromosome_sizes));
+        assert_eq!(
+            inconsistencies,
+            vec![AssemblyInconsistency::BeyondChromosomeEnd {
+                file: "a".to_string(),
+                chromosome: Chromosome::from("chr1"),
+                max_end: 249_000_000,
+                size: 248_956_422,
+            }]
+        );
+        assert_eq!(
+            inconsistencies[0].to_string(),
+            "The input file \"a\" contains peaks up to position 249000000 on chromosome chr1 \
+            exceeding its size of 248956422."
+        );
+    }
+}
@@ -0,0 +1,178 @@
+//! This module samples random genomic background regions matched to a set of peaks in number,
+//! chromosome and length, which enrichment analyses use as null set.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    chromosome::Chromosome,
+    error::{ApplicationError, ApplicationErrorType},
+    peak_set::PeakSet,
+    peaks::PeakData,
+    saturation::SplitMix64,
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "background";
+
+/// The default maximum number of random positions tried per background region.
+pub const DEFAULT_MAX_ATTEMPTS: usize = 1000;
+
+/// Samples random background regions matched to the peaks. For each peak the specified number
+/// of regions of the same length are placed uniformly on the same chromosome, so the number,
+/// chromosome and length distribution of the peaks are retained. Background regions neither
+/// overlap the peaks, the excluded regions nor each other. Each background region keeps the
+/// identifier of its matched peak and its summit is set to its centre.
+/// Regions that cannot be placed within the maximum number of attempts are skipped.
+///
+/// # Parameters
+///
+/// * `peaks` - the peaks to match
+/// * `chromosome_sizes` - the sizes of the chromosomes to place the regions on
+/// * `excluded` - regions, such as blacklisted regions, that must not be overlapped
+/// * `regions_per_peak` - the number of background regions per peak
+/// * `seed` - the seed of the random placement, so the regions can be reproduced
+/// * `max_attempts` - the maximum number of random positions tried per background region
+///
+/// # Errors
+///
+/// Returns an error if the size of a chromosome is unknown or
+/// a peak does not fit on its chromosome.
+pub fn background_regions(
+    peaks: &PeakSet,
+    chromosome_sizes: &BTreeMap<Chromosome, u64>,
+    excluded: &PeakSet,
+    regions_per_peak: usize,
+    seed: u64,
+    max_attempts: usize,
+) -> Result<PeakSet, ApplicationError> {
+    let mut random = SplitMix64::new(seed);
+    // The sampled regions by start and end per chromosome, which never overlap each other.
+    let mut sampled: HashMap<Chromosome, BTreeMap<u64, u64>> = HashMap::new();
+    let mut background = Vec::new();
+    let mut skipped = 0;
+    for chromosome in peaks.chromosomes() {
+        let size = chromosome_sizes.get(&chromosome).copied().ok_or_else(|| {
+            ApplicationError::new(
+                ApplicationErrorType::ValidationError,
+                format!("The chromosome sizes do not contain chromosome {}.", chromosome),
+            )
+            .with_hint("Specify a chromosome sizes file matching the assembly of the peaks.")
+        })?;
+        for peak in peaks.peaks(chromosome) {
+            let length = peak.length();
+            if length > size {
+                return Err(ApplicationError::new(
+                    ApplicationErrorType::ValidationError,
+                    format!(
+                        "The peak {} with a length of {} does not fit on chromosome {} of size {}.",
+                        peak.id(),
+                        length,
+                        chromosome,
+                        size
+                    ),
+                ));
+            }
+            let chromosome_sampled = sampled.entry(chromosome).or_default();
+            for _ in 0..regions_per_peak {
+                let placed = (0..max_attempts).find_map(|_| {
+                    let start = random.next_u64() % (size - length + 1);
+                    let end = start + length - 1;
+                    let overlaps_sampled = chromosome_sampled
+                        .range(..=end)
+                        .next_back()
+                        .is_some_and(|(_, sampled_end)| *sampled_end >= start);
+                    let is_free = !overlaps_sampled
+                        && peaks.overlapping(chromosome, start, end).is_empty()
+                        && excluded.overlapping(chromosome, start, end).is_empty();
+                    is_free.then_some((start, end))
+                });
+                match placed {
+                    Some((start, end)) => {
+                        chromosome_sampled.insert(start, end);
+                        background.push((
+                            chromosome,
+                            PeakData::new(peak.id(), start, end, start.midpoint(end))?,
+                        ));
+                    },
+                    None => skipped += 1,
+                }
+            }
+        }
+    }
+    if skipped > 0 {
+        log::warn!(
+            target: LOG_TARGET,
+            "{} background regions could not be placed within {} attempts each and were skipped.",
+            skipped,
+            max_attempts
+        );
+    }
+    Ok(background.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::peak_set;
+
+    use super::*;
+
+    #[test]
+    fn test_background_regions() {
+        let peaks = peak_set(&[("chr1", 100, 199, 100), ("chr1", 500, 549, 500)])
+            .union(&peak_set(&[("chr2", 10, 29, 10)]));
+        let excluded = peak_set(&[("chr1", 0, 99, 0), ("chr1", 800, 999, 800)]);
+        let sizes = BTreeMap::from([
+            (Chromosome::from("chr1"), 1000),
+            (Chromosome::from("chr2"), 100),
+        ]);
+        let background = background_regions(&peaks, &sizes, &excluded, 2, 42, 1000).unwrap();
+        assert_eq!(background.len(), 6);
+        for (chromosome, region) in background.iter() {
+            let peak = peaks
+                .peaks(chromosome)
+                .iter()
+                .find(|peak| peak.id() == region.id())
+                .unwrap();
+            assert_eq!(region.length(), peak.length());
+            assert!(region.end() < sizes[&chromosome]);
+            assert!(
+                peaks
+                    .overlapping(chromosome, region.start(), region.end())
+                    .is_empty()
+            );
+            assert!(
+                excluded
+                    .overlapping(chromosome, region.start(), region.end())
+                    .is_empty()
+            );
+            assert_eq!(
+                background
+                    .overlapping(chromosome, region.start(), region.end())
+                    .len(),
+                1
+            );
+        }
+        assert_eq!(background_regions(&peaks, &sizes, &excluded, 2, 42, 1000).unwrap(), background);
+        assert_ne!(background_regions(&peaks, &sizes, &excluded, 2, 7, 1000).unwrap(), background);
+    }
+
+    #[test]
+    fn test_background_regions_skipped() {
+        let peaks = peak_set(&[("chr1", 0, 49, 0)]);
+        let sizes = BTreeMap::from([(Chromosome::from("chr1"), 120)]);
+        // Only the positions 50 to 119 are free, which fit a single region.
+        let background = background_regions(&peaks, &sizes, &PeakSet::new(), 3, 0, 1000).unwrap();
+        assert_eq!(background.len(), 1);
+    }
+
+    #[test]
+    fn test_background_regions_invalid() {
+        let peaks = peak_set(&[("chr1", 0, 199, 0)]);
+        let sizes = BTreeMap::from([(Chromosome::from("chr1"), 100)]);
+        let error = background_regions(&peaks, &sizes, &PeakSet::new(), 1, 0, 10).unwrap_err();
+        assert_eq!(error.error_type(), ApplicationErrorType::ValidationError);
+        let error =
+            background_regions(&peaks, &BTreeMap::new(), &PeakSet::new(), 1, 0, 10).unwrap_err();
+        assert_eq!(error.error_type(), ApplicationErrorType::ValidationError);
+    }
+}
@@ -0,0 +1,230 @@
+//! This module converts peaks to and from the interval types of the Rust bioinformatics ecosystem.
+//!
+//! The [bio-types](https://crates.io/crates/bio-types) conversions are available with the `bio-types`
+//! feature, while the [rust-bio](https://crates.io/crates/bio) interval tree conversions
+//! additionally require the `bio` feature.
+//! Both libraries use half-open intervals, so the inclusive peak end coordinate
+//! is converted to an exclusive end coordinate and vice versa.
+
+#[cfg(feature = "bio")]
+use std::collections::HashMap;
+
+#[cfg(feature = "bio")]
+use bio::data_structures::interval_tree::IntervalTree;
+use bio_types::genome::{AbstractInterval, Interval, Locus};
+
+use crate::{
+    chromosome::Chromosome,
+    error::{ApplicationError, ApplicationErrorType},
+    peak_set::PeakSet,
+    peaks::{PeakBin, PeakData},
+};
+
+impl PeakData {
+    /// Returns the peak region as genomic interval.
+    ///
+    /// # Parameters
+    ///
+    /// * `chromosome` - the chromosome the peak is located on
+    pub fn to_genome_interval(&self, chromosome: &Chromosome) -> Interval {
+        Interval::new(chromosome.name().to_string(), self.start()..self.end() + 1)
+    }
+
+    /// Returns the peak summit as genomic locus.
+    ///
+    /// # Parameters
+    ///
+    /// * `chromosome` - the chromosome the peak is located on
+    pub fn summit_locus(&self, chromosome: &Chromosome) -> Locus {
+        Locus::new(chromosome.name().to_string(), self.summit())
+    }
+
+    /// Creates a peak from a genomic interval.
+    /// As intervals do not contain summit information, the summit is approximated by the midpoint.
+    ///
+    /// # Parameters
+    ///
+    /// * `id` - the unique identifier of the peak
+    /// * `interval` - the genomic interval
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the interval is empty.
+    pub fn from_genome_interval<T: AbstractInterval>(
+        id: usize,
+        interval: &T,
+    ) -> Result<(Chromosome, Self), ApplicationError> {
+        let range = interval.range();
+        if range.start >= range.end {
+            return Err(ApplicationError::new(
+                ApplicationErrorType::ValidationError,
+                format!(
+                    "The interval {}:{}-{} is empty and cannot be converted to a peak.",
+                    interval.contig(),
+                    range.start,
+                    range.end
+                ),
+            ));
+        }
+        let end = range.end - 1;
+        Ok((
+            Chromosome::intern(interval.contig()),
+            Self::new(id, range.start, end, range.start.midpoint(end))?,
+        ))
+    }
+}
+
+impl PeakBin {
+    /// Returns the region spanned by the bin as genomic interval.
+    ///
+    /// # Parameters
+    ///
+    /// * `chromosome` - the chromosome the bin is located on
+    pub fn to_genome_interval(&self, chromosome: &Chromosome) -> Interval {
+        Interval::new(chromosome.name().to_string(), self.start()..self.end() + 1)
+    }
+}
+
+impl PeakSet {
+    /// Returns all peaks as genomic intervals sorted by chromosome and start coordinate.
+    pub fn to_genome_intervals(&self) -> Vec<Interval> {
+        self.chromosomes()
+            .into_iter()
+            .flat_map(|chromosome| {
+                self.peaks(chromosome)
+                    .iter()
+                    .map(move |peak| peak.to_genome_interval(&chromosome))
+            })
+            .collect()
+    }
+
+    /// Creates a peak set from genomic intervals.
+    /// Peak identifiers are assigned in the order of the intervals.
+    ///
+    /// # Parameters
+    ///
+    /// * `intervals` - the genomic intervals
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any interval is empty.
+    pub fn from_genome_intervals<'a, T: AbstractInterval + 'a, I: IntoIterator<Item = &'a T>>(
+        intervals: I,
+    ) -> Result<Self, ApplicationError> {
+        intervals
+            .into_iter()
+            .enumerate()
+            .map(|(id, interval)| PeakData::from_genome_interval(id, interval))
+            .collect()
+    }
+
+    /// Returns a rust-bio interval tree containing the peaks of each chromosome.
+    #[cfg(feature = "bio")]
+    pub fn to_interval_trees(&self) -> HashMap<Chromosome, IntervalTree<u64, PeakData>> {
+        self.chromosomes()
+            .into_iter()
+            .map(|chromosome| {
+                let tree = self
+                    .peaks(chromosome)
+                    .iter()
+                    .map(|peak| (peak.to_bio_interval(), *peak))
+                    .collect();
+                (chromosome, tree)
+            })
+            .collect()
+    }
+
+    /// Creates a peak set from the peaks stored in rust-bio interval trees.
+    ///
+    /// # Parameters
+    ///
+    /// * `trees` - the interval trees containing the peaks of each chromosome
+    #[cfg(feature = "bio")]
+    pub fn from_interval_trees(trees: &HashMap<Chromosome, IntervalTree<u64, PeakData>>) -> Self {
+        trees
+            .iter()
+            .flat_map(|(chromosome, tree)| {
+                tree.find(0..u64::MAX)
+                    .map(move |entry| (*chromosome, *entry.data()))
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "bio")]
+impl PeakData {
+    /// Returns the peak region as rust-bio interval.
+    pub fn to_bio_interval(&self) -> bio::utils::Interval<u64> {
+        (self.start()..self.end() + 1).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peak_data_genome_interval() {
+        let chromosome = Chromosome::from("chr1");
+        let peak = PeakData::new(3, 100u64, 199u64, 120u64).unwrap();
+        let interval = peak.to_genome_interval(&chromosome);
+        assert_eq!(interval.contig(), "chr1");
+        assert_eq!(interval.range(), 100..200);
+        assert!(interval.contains(peak.summit_locus(&chromosome)));
+        let (converted_chromosome, converted_peak) =
+            PeakData::from_genome_interval(3, &interval).unwrap();
+        assert_eq!(converted_chromosome, chromosome);
+        assert_eq!(converted_peak.start(), peak.start());
+        assert_eq!(converted_peak.end(), peak.end());
+        assert_eq!(converted_peak.summit(), 149);
+        assert!(
+            PeakData::from_genome_interval(0, &Interval::new("chr1".to_string(), 10..10)).is_err()
+        );
+    }
+
+    #[test]
+    fn test_peak_bin_genome_interval() {
+        let mut bin = PeakBin::new(PeakData::new(0, 100u64, 199u64, 120u64).unwrap());
+        assert!(
+            bin.try_insert(PeakData::new(1, 150u64, 299u64, 200u64).unwrap())
+                .is_none()
+        );
+        assert_eq!(bin.to_genome_interval(&Chromosome::from("chr2")).range(), 100..300);
+    }
+
+    #[test]
+    fn test_peak_set_genome_intervals() {
+        let intervals = vec![
+            Interval::new("chr2".to_string(), 50..60),
+            Interval::new("chr1".to_string(), 300..400),
+            Interval::new("chr1".to_string(), 100..200),
+        ];
+        let peak_set = PeakSet::from_genome_intervals(&intervals).unwrap();
+        assert_eq!(peak_set.len(), 3);
+        let mut sorted_intervals = intervals.clone();
+        sorted_intervals.sort();
+        assert_eq!(peak_set.to_genome_intervals(), sorted_intervals);
+    }
+
+    #[cfg(feature = "bio")]
+    #[test]
+    fn test_peak_set_interval_trees() {
+        let chromosome = Chromosome::from("chr1");
+        let peak_set: PeakSet = [
+            (chromosome, PeakData::new(0, 100u64, 199u64, 150u64).unwrap()),
+            (chromosome, PeakData::new(1, 300u64, 399u64, 350u64).unwrap()),
+            (Chromosome::from("chr2"), PeakData::new(2, 100u64, 199u64, 150u64).unwrap()),
+        ]
+        .into_iter()
+        .collect();
+        let trees = peak_set.to_interval_trees();
+        assert_eq!(trees.len(), 2);
+        let overlapping: Vec<usize> = trees[&chromosome]
+            .find(199..300)
+            .map(|entry| entry.data().id())
+            .collect();
+        assert_eq!(overlapping, vec![0]);
+        let converted = PeakSet::from_interval_trees(&trees);
+        assert_eq!(converted.to_genome_intervals(), peak_set.to_genome_intervals());
+    }
+}
@@ -0,0 +1,177 @@
+//! This module removes blacklisted regions, such as the ENCODE blacklist, from peaks
+//! during consensus peak generation.
+
+use std::collections::HashMap;
+
+use crate::{chromosome::Chromosome, peak_set::PeakSet, peaks::PeakData};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "blacklist";
+
+/// The handling of peaks overlapping blacklisted regions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum BlacklistMode {
+    /// Raw peaks overlapping a blacklisted region are removed before consensus peak generation.
+    #[default]
+    Remove,
+    /// Blacklisted regions are trimmed from the consensus peaks after consensus peak generation.
+    Trim,
+}
+
+impl std::fmt::Display for BlacklistMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlacklistMode::Remove => write!(f, "remove"),
+            BlacklistMode::Trim => write!(f, "trim"),
+        }
+    }
+}
+
+/// Removes all peaks overlapping a blacklisted region by at least one base.
+/// Returns the retained peaks.
+///
+/// # Parameters
+///
+/// * `peaks` - the peaks grouped by chromosome
+/// * `blacklist` - the blacklisted regions
+pub fn remove_blacklisted_peaks(
+    peaks: HashMap<Chromosome, Vec<PeakData>>,
+    blacklist: &PeakSet,
+) -> HashMap<Chromosome, Vec<PeakData>> {
+    let mut removed = 0;
+    let retained = peaks
+        .into_iter()
+        .map(|(chromosome, peaks)| {
+            let peaks_before = peaks.len();
+            let peaks: Vec<PeakData> = peaks
+                .into_iter()
+                .filter(|peak| {
+                    blacklist
+                        .overlapping(chromosome, peak.start(), peak.end())
+                        .is_empty()
+                })
+                .collect();
+            removed += peaks_before - peaks.len();
+            (chromosome, peaks)
+        })
+        .filter(|(_, peaks)| !peaks.is_empty())
+        .collect();
+    log::info!(target: LOG_TARGET, "Removed {} peaks overlapping blacklisted regions.", removed);
+    retained
+}
+
+/// Trims the blacklisted regions from the peaks. If blacklisted regions split a peak into
+/// multiple fragments, the longest fragment is retained, so each peak keeps a single region
+/// and its identifier. Peaks completely covered by blacklisted regions are removed.
+/// The summit is retained if it is part of the retained fragment and moved to the centre
+/// of the fragment otherwise.
+///
+/// # Parameters
+///
+/// * `peaks` - the peaks grouped by chromosome
+/// * `blacklist` - the blacklisted regions
+pub fn trim_blacklisted_peaks(
+    peaks: HashMap<Chromosome, Vec<PeakData>>,
+    blacklist: &PeakSet,
+) -> HashMap<Chromosome, Vec<PeakData>> {
+    let peaks_before: usize = peaks.values().map(Vec::len).sum();
+    let fragments: HashMap<Chromosome, Vec<PeakData>> =
+        PeakSet::from(peaks).subtraction(blacklist).into();
+    let trimmed: HashMap<Chromosome, Vec<PeakData>> = fragments
+        .into_iter()
+        .map(|(chromosome, fragments)| {
+            let mut longest_fragments: HashMap<usize, PeakData> = HashMap::new();
+            for fragment in fragments {
+                longest_fragments
+                    .entry(fragment.id())
+                    .and_modify(|longest| {
+                        if (fragment.length(), std::cmp::Reverse(fragment.start()))
+                            > (longest.length(), std::cmp::Reverse(longest.start()))
+                        {
+                            *longest = fragment;
+                        }
+                    })
+                    .or_insert(fragment);
+            }
+            let mut peaks: Vec<PeakData> = longest_fragments.into_values().collect();
+            peaks.sort_unstable_by_key(|peak| (peak.start(), peak.end(), peak.id()));
+            (chromosome, peaks)
+        })
+        .collect();
+    log::info!(
+        target: LOG_TARGET,
+        "Trimmed blacklisted regions from the peaks and removed {} completely blacklisted peaks.",
+        peaks_before - trimmed.values().map(Vec::len).sum::<usize>()
+    );
+    trimmed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peaks(peaks: &[(&str, u64, u64, u64)]) -> HashMap<Chromosome, Vec<PeakData>> {
+        let mut peak_map: HashMap<Chromosome, Vec<PeakData>> = HashMap::new();
+        for (id, (chromosome, start, end, summit)) in peaks.iter().enumerate() {
+            peak_map
+                .entry(Chromosome::from(*chromosome))
+                .or_default()
+                .push(PeakData::new(id, *start, *end, *summit).unwrap());
+        }
+        peak_map
+    }
+
+    fn blacklist() -> PeakSet {
+        PeakSet::from(peaks(&[("chr1", 150, 179, 150), ("chr1", 1000, 1999, 1000)]))
+    }
+
+    #[test]
+    fn test_remove_blacklisted_peaks() {
+        let retained = remove_blacklisted_peaks(
+            peaks(&[
+                ("chr1", 100, 200, 120),
+                ("chr1", 180, 300, 250),
+                ("chr1", 1500, 1600, 1550),
+                ("chr2", 150, 179, 160),
+            ]),
+            &blacklist(),
+        );
+        // The identifiers of the retained peaks are unchanged.
+        assert_eq!(
+            retained,
+            HashMap::from([
+                (Chromosome::from("chr1"), vec![PeakData::new(1, 180u64, 300u64, 250u64).unwrap()]),
+                (Chromosome::from("chr2"), vec![PeakData::new(3, 150u64, 179u64, 160u64).unwrap()]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_trim_blacklisted_peaks() {
+        let trimmed = trim_blacklisted_peaks(
+            peaks(&[
+                ("chr1", 100, 200, 120),
+                ("chr1", 120, 200, 190),
+                ("chr1", 900, 1100, 1050),
+                ("chr1", 1500, 1600, 1550),
+                ("chr2", 150, 179, 160),
+            ]),
+            &blacklist(),
+        );
+        assert_eq!(
+            trimmed[&Chromosome::from("chr1")],
+            vec![
+                // The longest fragment is retained.
+                PeakData::new(0, 100u64, 149u64, 120u64).unwrap(),
+                // The summit is moved to the centre if it is not part of the retained fragment.
+                PeakData::new(1, 120u64, 149u64, 134u64).unwrap(),
+                PeakData::new(2, 900u64, 999u64, 949u64).unwrap(),
+            ]
+        );
+        assert_eq!(
+            trimmed[&Chromosome::from("chr2")],
+            vec![PeakData::new(4, 150u64, 179u64, 160u64).unwrap()]
+        );
+    }
+}
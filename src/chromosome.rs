@@ -0,0 +1,401 @@
+//! This module interns chromosome names.
+//!
+//! Chromosome aliases, such as the Ensembl name "1" for the UCSC name "chr1", can be registered
+//! process wide, so that all aliases of a chromosome are interned as the same chromosome.
+//! Aliases must be registered before the respective names are interned.
+
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{OnceLock, RwLock},
+};
+
+use crate::{
+    error::{ApplicationError, ApplicationErrorType, ErrorContext},
+    input::read_decompressed_to_string,
+};
+
+/// The process wide arena of interned chromosome names.
+static CHROMOSOME_ARENA: OnceLock<RwLock<ChromosomeArena>> = OnceLock::new();
+
+/// The log target of this module.
+const LOG_TARGET: &str = "chromosome";
+
+/// An arena mapping chromosome names to their identifiers and vice versa.
+/// Aliases map to the identifier of their canonical name.
+#[derive(Default)]
+struct ChromosomeArena {
+    names: Vec<&'static str>,
+    ids: HashMap<&'static str, u32>,
+    ucsc_aliases: bool,
+}
+
+impl ChromosomeArena {
+    /// Returns the identifier of the name, interning the name if it is not known yet.
+    /// Names following the Ensembl convention are interned as alias of the UCSC name
+    /// if built-in UCSC aliases are enabled.
+    ///
+    /// # Parameters
+    ///
+    /// * `name` - the chromosome name
+    ///
+    /// # Panics
+    ///
+    /// Panics if more than [`u32::MAX`] distinct chromosome names are interned.
+    fn intern(&mut self, name: &str) -> u32 {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+        let id = match self.ucsc_name(name) {
+            Some(ucsc_name) => self.intern(&ucsc_name),
+            None => {
+                let id = u32::try_from(self.names.len()).expect(
+                    "The number of distinct chromosome names must fit into the identifier type.",
+                );
+                self.names.push(name);
+                id
+            },
+        };
+        self.ids.insert(name, id);
+        id
+    }
+
+    /// Returns the UCSC name of an Ensembl style primary chromosome name if built-in UCSC
+    /// aliases are enabled.
+    ///
+    /// # Parameters
+    ///
+    /// * `name` - the chromosome name
+    fn ucsc_name(&self, name: &str) -> Option<String> {
+        if !self.ucsc_aliases {
+            return None;
+        }
+        if name == "MT" {
+            Some("chrM".to_string())
+        } else if (!name.is_empty() && name.chars().all(|c| c.is_ascii_digit()))
+            || ["X", "Y", "W", "Z"].contains(&name)
+        {
+            Some(format!("chr{}", name))
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns the process wide chromosome arena.
+fn arena() -> &'static RwLock<ChromosomeArena> {
+    CHROMOSOME_ARENA.get_or_init(|| RwLock::new(ChromosomeArena::default()))
+}
+
+/// An interned chromosome name.
+/// Equality and hashing only compare the numeric identifier, while
+/// ordering follows the chromosome name to keep output independent of interning order.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Chromosome(u32);
+
+impl Chromosome {
+    /// Returns the chromosome with the specified name, interning the name if it is not known yet.
+    ///
+    /// # Parameters
+    ///
+    /// * `name` - the name of the chromosome
+    ///
+    /// # Panics
+    ///
+    /// Panics if more than [`u32::MAX`] distinct chromosome names are interned.
+    pub fn intern<T: AsRef<str>>(name: T) -> Self {
+        let name = name.as_ref();
+        if let Some(id) = arena()
+            .read()
+            .expect("The chromosome arena lock must not be poisoned.")
+            .ids
+            .get(name)
+        {
+            return Self(*id);
+        }
+        // Another thread might have interned the name in the meantime,
+        // which is handled by the arena.
+        Self(
+            arena()
+                .write()
+                .expect("The chromosome arena lock must not be poisoned.")
+                .intern(name),
+        )
+    }
+
+    /// Registers an alias of a chromosome, so that the alias is interned as the chromosome
+    /// with the canonical name.
+    ///
+    /// # Parameters
+    ///
+    /// * `alias` - the alternative name of the chromosome
+    /// * `canonical` - the name used for the chromosome
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the alias has already been interned as a different chromosome.
+    pub fn register_alias<A: AsRef<str>, C: AsRef<str>>(
+        alias: A,
+        canonical: C,
+    ) -> Result<Self, ApplicationError> {
+        let (alias, canonical) = (alias.as_ref(), canonical.as_ref());
+        let mut arena = arena()
+            .write()
+            .expect("The chromosome arena lock must not be poisoned.");
+        let id = arena.intern(canonical);
+        match arena.ids.get(alias) {
+            Some(alias_id) if *alias_id != id => Err(ApplicationError::new(
+                ApplicationErrorType::ConfigError,
+                format!(
+                    "The chromosome alias \"{}\" of \"{}\" is already used for chromosome \"{}\".",
+                    alias, canonical, arena.names[*alias_id as usize]
+                ),
+            )
+            .with_hint("Each chromosome alias must refer to a single chromosome.")),
+            Some(_) => Ok(Self(id)),
+            None => {
+                arena
+                    .ids
+                    .insert(Box::leak(alias.to_string().into_boxed_str()), id);
+                Ok(Self(id))
+            },
+        }
+    }
+
+    /// Enables the built-in aliases of Ensembl style primary chromosome names, so that for
+    /// example "1", "X" and "MT" are interned as the UCSC chromosomes "chr1", "chrX" and
+    /// "chrM". Names that have already been interned are not affected.
+    pub fn enable_ucsc_aliases() {
+        arena()
+            .write()
+            .expect("The chromosome arena lock must not be poisoned.")
+            .ucsc_aliases = true;
+    }
+
+    /// Returns the chromosome with the specified name if the name has already been interned.
+    /// In contrast to [`Chromosome::intern`] unknown names are not added to the arena,
+    /// which makes this function suitable for untrusted input like query parameters.
+    ///
+    /// # Parameters
+    ///
+    /// * `name` - the name of the chromosome
+    pub fn lookup<T: AsRef<str>>(name: T) -> Option<Self> {
+        let arena = arena()
+            .read()
+            .expect("The chromosome arena lock must not be poisoned.");
+        let name = name.as_ref();
+        arena
+            .ids
+            .get(name)
+            .or_else(|| {
+                arena
+                    .ucsc_name(name)
+                    .and_then(|ucsc_name| arena.ids.get(ucsc_name.as_str()))
+            })
+            .map(|id| Self(*id))
+    }
+
+    /// Returns the numeric identifier of the chromosome.
+    pub fn id(&self) -> u32 {
+        self.0
+    }
+
+    /// Returns the name of the chromosome.
+    pub fn name(&self) -> &'static str {
+        arena()
+            .read()
+            .expect("The chromosome arena lock must not be poisoned.")
+            .names[self.0 as usize]
+    }
+}
+
+/// Registers the chromosome aliases of an alias file.
+/// Each line contains the canonical chromosome name followed by its aliases separated by
+/// tabs or spaces, as for example in the chromAlias files provided by UCSC.
+/// Blank and comment lines are skipped.
+///
+/// # Parameters
+///
+/// * `path` - the path of the alias file
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or an alias refers to multiple chromosomes.
+pub fn register_aliases_from_file<T: AsRef<Path>>(path: T) -> Result<usize, ApplicationError> {
+    let path = path.as_ref();
+    let content = read_decompressed_to_string(path).map_err(|err| {
+        ApplicationError::from(err)
+            .chain(format!("The chromosome alias file \"{}\" could not be read.", path.display()))
+    })?;
+    let mut registered_aliases = 0;
+    for (line_index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut names = line.split_whitespace();
+        let canonical = names
+            .next()
+            .expect("Non-blank lines must contain at least one name.");
+        for alias in names.filter(|alias| *alias != canonical) {
+            Chromosome::register_alias(alias, canonical).map_err(|err| {
+                err.with_context(
+                    ErrorContext::new()
+                        .with_path(path.display())
+                        .with_line(line_index as u64 + 1),
+                )
+            })?;
+            registered_aliases += 1;
+        }
+    }
+    log::debug!(
+        target: LOG_TARGET,
+        "Registered {} chromosome aliases from {}.",
+        registered_aliases,
+        path.display()
+    );
+    Ok(registered_aliases)
+}
+
+impl From<&str> for Chromosome {
+    fn from(name: &str) -> Self {
+        Self::intern(name)
+    }
+}
+
+impl PartialOrd for Chromosome {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Chromosome {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        if self.0 == other.0 {
+            std::cmp::Ordering::Equal
+        } else {
+            self.name().cmp(other.name())
+        }
+    }
+}
+
+impl serde::Serialize for Chromosome {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Chromosome {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        Ok(Self::intern(name))
+    }
+}
+
+impl std::fmt::Display for Chromosome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl std::fmt::Debug for Chromosome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Chromosome({}: {})", self.0, self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_output;
+
+    #[test]
+    fn test_chromosome_intern() {
+        let chr1 = Chromosome::intern("test_chromosome_intern_1");
+        let chr2 = Chromosome::intern("test_chromosome_intern_2");
+        assert_ne!(chr1, chr2);
+        assert_eq!(chr1, Chromosome::intern("test_chromosome_intern_1"));
+        assert_eq!(chr1.id(), Chromosome::from("test_chromosome_intern_1").id());
+        assert_eq!(chr1.name(), "test_chromosome_intern_1");
+        assert_eq!(chr2.to_string(), "test_chromosome_intern_2");
+    }
+
+    #[test]
+    fn test_chromosome_lookup() {
+        assert_eq!(Chromosome::lookup("test_chromosome_lookup"), None);
+        let chromosome = Chromosome::intern("test_chromosome_lookup");
+        assert_eq!(Chromosome::lookup("test_chromosome_lookup"), Some(chromosome));
+    }
+
+    #[test]
+    fn test_chromosome_intern_threads() {
+        let ids: Vec<u32> = (0..8)
+            .map(|_| std::thread::spawn(|| Chromosome::intern("test_chromosome_intern_threads")))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap().id())
+            .collect();
+        assert!(ids.iter().all(|id| *id == ids[0]));
+    }
+
+    #[test]
+    fn test_chromosome_register_alias() {
+        let chromosome =
+            Chromosome::register_alias("test_register_alias_1", "chr_test_register_alias_1")
+                .unwrap();
+        assert_eq!(chromosome, Chromosome::intern("test_register_alias_1"));
+        assert_eq!(Chromosome::intern("test_register_alias_1").name(), "chr_test_register_alias_1");
+        assert_eq!(Chromosome::lookup("test_register_alias_1"), Some(chromosome));
+        // Registering the same alias again is allowed.
+        assert!(
+            Chromosome::register_alias("test_register_alias_1", "chr_test_register_alias_1")
+                .is_ok()
+        );
+        let error =
+            Chromosome::register_alias("test_register_alias_1", "chr_test_register_alias_2")
+                .unwrap_err();
+        assert_eq!(error.error_type(), ApplicationErrorType::ConfigError);
+    }
+
+    #[test]
+    fn test_chromosome_arena_ucsc_aliases() {
+        // Uses a separate arena to not affect the names interned by other tests.
+        let mut arena = ChromosomeArena {
+            ucsc_aliases: true,
+            ..Default::default()
+        };
+        let chr1 = arena.intern("chr1");
+        assert_eq!(arena.intern("1"), chr1);
+        assert_eq!(arena.intern("MT"), arena.intern("chrM"));
+        let chr_x = arena.intern("X");
+        assert_eq!(arena.names[chr_x as usize], "chrX");
+        let scaffold = arena.intern("scaffold_1");
+        assert_eq!(arena.names[scaffold as usize], "scaffold_1");
+        assert_eq!(arena.names.len(), 4);
+    }
+
+    #[test]
+    fn test_register_aliases_from_file() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let path = test_output().join("test_register_aliases_from_file.txt");
+        std::fs::write(
+            &path,
+            "# ucsc\tensembl\tgenbank\nchr_test_alias_file_1\ttest_alias_file_1\ttest_alias_file_cm1\n\n",
+        )
+        .unwrap();
+        let registered = register_aliases_from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(registered, 2);
+        assert_eq!(Chromosome::intern("test_alias_file_1").name(), "chr_test_alias_file_1");
+        assert_eq!(Chromosome::intern("test_alias_file_cm1").name(), "chr_test_alias_file_1");
+    }
+
+    #[test]
+    fn test_chromosome_ord() {
+        // Interns in reverse order to ensure the ordering does not depend on the identifier.
+        let chr_b = Chromosome::intern("test_chromosome_ord_b");
+        let chr_a = Chromosome::intern("test_chromosome_ord_a");
+        assert!(chr_a < chr_b);
+        assert_eq!(chr_a.cmp(&chr_a), std::cmp::Ordering::Equal);
+    }
+}
@@ -0,0 +1,433 @@
+//! This module compares a query peak set against a reference peak set,
+//! for example to benchmark consensus peaks generated with different parameters
+//! against a published consensus.
+
+use std::{collections::HashMap, io::Write};
+
+use getset::{CopyGetters, Getters};
+
+use crate::{
+    chromosome::Chromosome,
+    consensus::ConsensusBuilder,
+    error::{ApplicationError, ApplicationErrorType},
+    peak_set::PeakSet,
+    peaks::PeakData,
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "compare";
+
+/// The criteria two peaks must fulfill to be considered matching.
+#[derive(CopyGetters, Clone, Copy, PartialEq, Debug)]
+#[getset(get_copy = "pub")]
+pub struct OverlapCriteria {
+    /// The minimum fraction of the reference peak covered by the query peak.
+    min_overlap_fraction: f64,
+    /// If the minimum fraction must also be covered of the query peak.
+    reciprocal: bool,
+}
+
+impl OverlapCriteria {
+    /// Creates new overlap criteria.
+    /// A minimum fraction of `0.0` matches peaks overlapping by at least one base.
+    ///
+    /// # Parameters
+    ///
+    /// * `min_overlap_fraction` - the minimum fraction of the reference peak covered by the query peak
+    /// * `reciprocal` - if the minimum fraction must also be covered of the query peak
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the fraction is not within `[0, 1]`.
+    pub fn new(min_overlap_fraction: f64, reciprocal: bool) -> Result<Self, ApplicationError> {
+        if !(0.0..=1.0).contains(&min_overlap_fraction) {
+            return Err(ApplicationError::new(
+                ApplicationErrorType::ConfigError,
+                format!(
+                    "The minimum overlap fraction {} is not within [0, 1].",
+                    min_overlap_fraction
+                ),
+            ));
+        }
+        Ok(Self {
+            min_overlap_fraction,
+            reciprocal,
+        })
+    }
+
+    /// Returns `true` if the peaks located on the same chromosome match.
+    ///
+    /// # Parameters
+    ///
+    /// * `reference` - the reference peak
+    /// * `query` - the query peak
+    pub fn matches(&self, reference: &PeakData, query: &PeakData) -> bool {
+        let overlap_start = reference.start().max(query.start());
+        let overlap_end = reference.end().min(query.end());
+        if overlap_start > overlap_end {
+            return false;
+        }
+        let overlap = (overlap_end - overlap_start + 1) as f64;
+        let covers = |peak: &PeakData| {
+            overlap >= self.min_overlap_fraction * (peak.end() - peak.start() + 1) as f64
+        };
+        covers(reference) && (!self.reciprocal || covers(query))
+    }
+}
+
+impl Default for OverlapCriteria {
+    fn default() -> Self {
+        Self {
+            min_overlap_fraction: 0.0,
+            reciprocal: false,
+        }
+    }
+}
+
+/// The comparison of a query peak set against a reference peak set.
+#[derive(CopyGetters, Getters, Clone, PartialEq, Debug)]
+pub struct PeakSetComparison {
+    /// The number of reference peaks.
+    #[getset(get_copy = "pub")]
+    reference_peaks: usize,
+    /// The number of query peaks.
+    #[getset(get_copy = "pub")]
+    query_peaks: usize,
+    /// The reference peaks not matched by any query peak.
+    #[getset(get = "pub")]
+    reference_only: PeakSet,
+    /// The query peaks not matching any reference peak.
+    #[getset(get = "pub")]
+    query_only: PeakSet,
+}
+
+impl PeakSetComparison {
+    /// Compares the query peaks against the reference peaks.
+    ///
+    /// # Parameters
+    ///
+    /// * `reference` - the reference peaks
+    /// * `query` - the query peaks
+    /// * `criteria` - the criteria for matching peaks
+    pub fn new(reference: &PeakSet, query: &PeakSet, criteria: &OverlapCriteria) -> Self {
+        log::debug!(
+            target: LOG_TARGET,
+            "Comparing {} query peaks against {} reference peaks.",
+            query.len(),
+            reference.len()
+        );
+        let reference_only = reference
+            .iter()
+            .filter(|(chromosome, reference_peak)| {
+                !query
+                    .overlapping(*chromosome, reference_peak.start(), reference_peak.end())
+                    .into_iter()
+                    .any(|query_peak| criteria.matches(reference_peak, query_peak))
+            })
+            .map(|(chromosome, peak)| (chromosome, *peak))
+            .collect();
+        let query_only = query
+            .iter()
+            .filter(|(chromosome, query_peak)| {
+                !reference
+                    .overlapping(*chromosome, query_peak.start(), query_peak.end())
+                    .into_iter()
+                    .any(|reference_peak| criteria.matches(reference_peak, query_peak))
+            })
+            .map(|(chromosome, peak)| (chromosome, *peak))
+            .collect();
+        Self {
+            reference_peaks: reference.len(),
+            query_peaks: query.len(),
+            reference_only,
+            query_only,
+        }
+    }
+
+    /// Returns the fraction of reference peaks matched by any query peak
+    /// or `0.0` if there are no reference peaks.
+    pub fn recall(&self) -> f64 {
+        fraction(self.reference_peaks - self.reference_only.len(), self.reference_peaks)
+    }
+
+    /// Returns the fraction of query peaks matching any reference peak
+    /// or `0.0` if there are no query peaks.
+    pub fn precision(&self) -> f64 {
+        fraction(self.query_peaks - self.query_only.len(), self.query_peaks)
+    }
+
+    /// Returns the harmonic mean of precision and recall
+    /// or `0.0` if both are `0.0`.
+    pub fn f1(&self) -> f64 {
+        let precision = self.precision();
+        let recall = self.recall();
+        if precision + recall == 0.0 {
+            0.0
+        } else {
+            2.0 * precision * recall / (precision + recall)
+        }
+    }
+}
+
+/// Returns the fraction of the part or `0.0` if the total is `0`.
+///
+/// # Parameters
+///
+/// * `part` - the part of the total
+/// * `total` - the total
+fn fraction(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64
+    }
+}
+
+/// Writes the comparison as tab separated table.
+///
+/// # Parameters
+///
+/// * `writer` - the writer to write the table to
+/// * `comparison` - the comparison to write
+///
+/// # Errors
+///
+/// Returns an error if the table cannot be written.
+pub fn write_comparison_table<W: Write>(
+    mut writer: W,
+    comparison: &PeakSetComparison,
+) -> Result<(), ApplicationError> {
+    let table = format!(
+        "reference_peaks\tquery_peaks\treference_only\tquery_only\tprecision\trecall\tf1\n\
+        {}\t{}\t{}\t{}\t{:.6}\t{:.6}\t{:.6}\n",
+        comparison.reference_peaks(),
+        comparison.query_peaks(),
+        comparison.reference_only().len(),
+        comparison.query_only().len(),
+        comparison.precision(),
+        comparison.recall(),
+        comparison.f1()
+    );
+    writer
+        .write_all(table.as_bytes())
+        .map_err(|err| ApplicationError::from(err).chain("The comparison could not be written."))
+}
+
+/// The accuracy of the consensus peaks generated with a minimum number of raw peaks per
+/// consensus peak compared against a gold-standard peak set.
+#[derive(CopyGetters, Clone, Copy, PartialEq, Debug)]
+#[getset(get_copy = "pub")]
+pub struct ThresholdSweepPoint {
+    /// The minimum number of raw peaks per consensus peak.
+    min_peaks_per_consensus: usize,
+    /// The number of generated consensus peaks.
+    consensus_peaks: usize,
+    /// The fraction of consensus peaks matching any gold-standard peak.
+    precision: f64,
+    /// The fraction of gold-standard peaks matched by any consensus peak.
+    recall: f64,
+    /// The harmonic mean of precision and recall.
+    f1: f64,
+}
+
+/// Generates consensus peaks for each minimum number of raw peaks per consensus peak and
+/// compares them against the gold-standard peaks, so the support threshold can be chosen
+/// based on the resulting precision / recall curve.
+///
+/// # Parameters
+///
+/// * `builder` - the builder to generate consensus peaks with
+/// * `raw_peaks` - the raw peaks of all samples grouped by chromosome
+/// * `gold_standard` - the gold-standard peaks
+/// * `criteria` - the criteria for matching peaks
+/// * `thresholds` - the minimum numbers of raw peaks per consensus peak to evaluate
+///
+/// # Errors
+///
+/// Returns an error if consensus peak generation fails.
+pub fn threshold_sweep<I: IntoIterator<Item = usize>>(
+    builder: &ConsensusBuilder,
+    raw_peaks: &HashMap<Chromosome, Vec<PeakData>>,
+    gold_standard: &PeakSet,
+    criteria: &OverlapCriteria,
+    thresholds: I,
+) -> Result<Vec<ThresholdSweepPoint>, ApplicationError> {
+    thresholds
+        .into_iter()
+        .map(|min_peaks_per_consensus| {
+            log::info!(
+                target: LOG_TARGET,
+                "Evaluating a minimum of {} raw peaks per consensus peak.",
+                min_peaks_per_consensus
+            );
+            let consensus = PeakSet::from(
+                builder
+                    .clone()
+                    .min_peaks_per_consensus(min_peaks_per_consensus)
+                    .run(raw_peaks.clone())?,
+            );
+            let comparison = PeakSetComparison::new(gold_standard, &consensus, criteria);
+            Ok(ThresholdSweepPoint {
+                min_peaks_per_consensus,
+                consensus_peaks: consensus.len(),
+                precision: comparison.precision(),
+                recall: comparison.recall(),
+                f1: comparison.f1(),
+            })
+        })
+        .collect()
+}
+
+/// Writes the threshold sweep as tab separated table.
+///
+/// # Parameters
+///
+/// * `writer` - the writer to write the table to
+/// * `points` - the points of the threshold sweep
+///
+/// # Errors
+///
+/// Returns an error if the table cannot be written.
+pub fn write_sweep_table<W: Write>(
+    mut writer: W,
+    points: &[ThresholdSweepPoint],
+) -> Result<(), ApplicationError> {
+    let mut table =
+        String::from("min_peaks_per_consensus\tconsensus_peaks\tprecision\trecall\tf1\n");
+    for point in points {
+        table.push_str(&format!(
+            "{}\t{}\t{:.6}\t{:.6}\t{:.6}\n",
+            point.min_peaks_per_consensus(),
+            point.consensus_peaks(),
+            point.precision(),
+            point.recall(),
+            point.f1()
+        ));
+    }
+    writer.write_all(table.as_bytes()).map_err(|err| {
+        ApplicationError::from(err).chain("The threshold sweep could not be written.")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::peak_set;
+
+    use super::*;
+
+    #[test]
+    fn test_overlap_criteria() {
+        let reference = PeakData::new(0, 100u64, 199u64, 150u64).unwrap();
+        let query = PeakData::new(1, 150u64, 449u64, 300u64).unwrap();
+        let disjoint = PeakData::new(2, 200u64, 299u64, 250u64).unwrap();
+        assert!(OverlapCriteria::default().matches(&reference, &query));
+        assert!(!OverlapCriteria::default().matches(&reference, &disjoint));
+        assert!(
+            OverlapCriteria::new(0.5, false)
+                .unwrap()
+                .matches(&reference, &query)
+        );
+        assert!(
+            !OverlapCriteria::new(0.5, true)
+                .unwrap()
+                .matches(&reference, &query)
+        );
+        assert!(
+            !OverlapCriteria::new(0.6, false)
+                .unwrap()
+                .matches(&reference, &query)
+        );
+        assert!(OverlapCriteria::new(1.5, false).is_err());
+        assert!(OverlapCriteria::new(-0.1, false).is_err());
+    }
+
+    #[test]
+    fn test_peak_set_comparison() {
+        let reference = peak_set(&[
+            ("chr1", 100, 199, 149),
+            ("chr1", 300, 399, 349),
+            ("chr1", 500, 599, 549),
+            ("chr1", 700, 799, 749),
+        ]);
+        let query = peak_set(&[
+            ("chr1", 150, 249, 199),
+            ("chr1", 390, 409, 399),
+            ("chr1", 900, 999, 949),
+        ]);
+        let comparison = PeakSetComparison::new(&reference, &query, &OverlapCriteria::default());
+        assert_eq!(comparison.reference_peaks(), 4);
+        assert_eq!(comparison.query_peaks(), 3);
+        let reference_only_ids: Vec<usize> = comparison
+            .reference_only()
+            .iter()
+            .map(|(_, peak)| peak.id())
+            .collect();
+        assert_eq!(reference_only_ids, vec![2, 3]);
+        assert_eq!(comparison.query_only().len(), 1);
+        assert_eq!(comparison.recall(), 0.5);
+        assert!((comparison.precision() - 2.0 / 3.0).abs() < 1e-9);
+        assert!((comparison.f1() - 4.0 / 7.0).abs() < 1e-9);
+
+        let strict =
+            PeakSetComparison::new(&reference, &query, &OverlapCriteria::new(0.5, false).unwrap());
+        assert_eq!(strict.reference_only().len(), 3);
+        assert_eq!(strict.query_only().len(), 2);
+
+        let mut table = Vec::new();
+        write_comparison_table(&mut table, &comparison).unwrap();
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            "reference_peaks\tquery_peaks\treference_only\tquery_only\tprecision\trecall\tf1\n\
+            4\t3\t2\t1\t0.666667\t0.500000\t0.571429\n"
+        );
+
+        let empty = PeakSetComparison::new(&PeakSet::new(), &PeakSet::new(), &Default::default());
+        assert_eq!(empty.f1(), 0.0);
+    }
+
+    #[test]
+    fn test_threshold_sweep() {
+        let chromosome = Chromosome::from("chr1");
+        // A peak supported by three samples, one supported by two and one by a single sample.
+        let raw_peaks = HashMap::from([(
+            chromosome,
+            vec![
+                PeakData::new(0, 100u64, 199u64, 150u64).unwrap(),
+                PeakData::new(1, 105u64, 199u64, 150u64).unwrap(),
+                PeakData::new(2, 100u64, 195u64, 150u64).unwrap(),
+                PeakData::new(3, 500u64, 599u64, 550u64).unwrap(),
+                PeakData::new(4, 500u64, 590u64, 550u64).unwrap(),
+                PeakData::new(5, 900u64, 999u64, 950u64).unwrap(),
+            ],
+        )]);
+        let gold_standard = peak_set(&[("chr1", 120, 180, 150), ("chr1", 520, 580, 550)]);
+        let points = threshold_sweep(
+            &ConsensusBuilder::new(),
+            &raw_peaks,
+            &gold_standard,
+            &OverlapCriteria::default(),
+            1..=3,
+        )
+        .unwrap();
+        assert_eq!(
+            points
+                .iter()
+                .map(ThresholdSweepPoint::consensus_peaks)
+                .collect::<Vec<usize>>(),
+            vec![3, 2, 1]
+        );
+        assert!((points[0].precision() - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(points[1].precision(), 1.0);
+        assert_eq!(points[1].recall(), 1.0);
+        assert_eq!(points[2].recall(), 0.5);
+
+        let mut table = Vec::new();
+        write_sweep_table(&mut table, &points[1..]).unwrap();
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            "min_peaks_per_consensus\tconsensus_peaks\tprecision\trecall\tf1\n\
+            2\t2\t1.000000\t1.000000\t1.000000\n\
+            3\t1\t1.000000\t0.500000\t0.666667\n"
+        );
+    }
+}
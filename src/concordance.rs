@@ -0,0 +1,336 @@
+//! This module quantifies how well samples agree on the summit positions of consensus peaks.
+//!
+//! The summits contributing to a consensus peak are the summits of all sample peaks
+//! located within the consensus peak region.
+
+use std::{fs::File, io::Write, path::Path};
+
+use getset::{CopyGetters, Getters};
+
+use crate::{
+    chromosome::Chromosome, error::ApplicationError, peak_set::PeakSet, peaks::PeakData,
+    stats::nearest_rank,
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "concordance";
+
+/// The file name of the per consensus peak summit concordance table.
+pub const PEAK_CONCORDANCE_FILE_NAME: &str = "summit_concordance_peaks.tsv";
+
+/// The file name of the per sample summit offset table.
+pub const SAMPLE_OFFSETS_FILE_NAME: &str = "summit_concordance_samples.tsv";
+
+/// The spread of the summits contributing to a single consensus peak.
+#[derive(CopyGetters, Clone, Copy, PartialEq, Debug)]
+#[getset(get_copy = "pub")]
+pub struct PeakSummitConcordance {
+    /// The chromosome of the consensus peak.
+    chromosome: Chromosome,
+    /// The consensus peak.
+    peak: PeakData,
+    /// The number of contributing summits.
+    summits: usize,
+    /// The standard deviation of the contributing summit positions.
+    standard_deviation: f64,
+    /// The median absolute deviation of the contributing summit positions.
+    median_absolute_deviation: f64,
+}
+
+/// The distribution of the offsets of sample summits from the consensus peak summits.
+#[derive(CopyGetters, Clone, Copy, Default, PartialEq, Debug)]
+#[getset(get_copy = "pub")]
+pub struct SummitOffsets {
+    /// The number of summits.
+    summits: usize,
+    /// The mean absolute offset.
+    mean_absolute_offset: f64,
+    /// The minimum offset.
+    min: i64,
+    /// The first quartile of the offsets.
+    q25: i64,
+    /// The median offset.
+    median: i64,
+    /// The third quartile of the offsets.
+    q75: i64,
+    /// The maximum offset.
+    max: i64,
+}
+
+impl SummitOffsets {
+    /// Summarises the specified offsets.
+    /// All values are `0` if no offsets are specified.
+    ///
+    /// # Parameters
+    ///
+    /// * `offsets` - the offsets of the sample summits from the consensus peak summits
+    fn new(mut offsets: Vec<i64>) -> Self {
+        if offsets.is_empty() {
+            return Self::default();
+        }
+        offsets.sort_unstable();
+        Self {
+            summits: offsets.len(),
+            mean_absolute_offset: offsets
+                .iter()
+                .map(|offset| offset.unsigned_abs() as f64)
+                .sum::<f64>()
+                / offsets.len() as f64,
+            min: offsets[0],
+            q25: nearest_rank(&offsets, 0.25),
+            median: nearest_rank(&offsets, 0.5),
+            q75: nearest_rank(&offsets, 0.75),
+            max: offsets[offsets.len() - 1],
+        }
+    }
+}
+
+/// The summit concordance of all consensus peaks and samples.
+#[derive(CopyGetters, Getters, Clone, PartialEq, Debug)]
+pub struct SummitConcordance {
+    /// The summit spread per consensus peak.
+    #[getset(get = "pub")]
+    peaks: Vec<PeakSummitConcordance>,
+    /// The summit offsets per sample.
+    #[getset(get = "pub")]
+    samples: Vec<SummitOffsets>,
+    /// The summit offsets of all samples.
+    #[getset(get_copy = "pub")]
+    total: SummitOffsets,
+}
+
+impl SummitConcordance {
+    /// Calculates the summit concordance of the consensus peaks.
+    ///
+    /// # Parameters
+    ///
+    /// * `consensus_peaks` - the consensus peaks
+    /// * `samples` - the peaks of each sample
+    pub fn new(consensus_peaks: &PeakSet, samples: &[PeakSet]) -> Self {
+        log::debug!(
+            target: LOG_TARGET,
+            "Calculating the summit concordance of {} consensus peaks.",
+            consensus_peaks.len()
+        );
+        let mut peaks = Vec::with_capacity(consensus_peaks.len());
+        let mut sample_offsets: Vec<Vec<i64>> = vec![Vec::new(); samples.len()];
+        for (chromosome, consensus_peak) in consensus_peaks.iter() {
+            let mut summits = Vec::new();
+            for (sample, offsets) in samples.iter().zip(sample_offsets.iter_mut()) {
+                for summit in sample
+                    .overlapping(chromosome, consensus_peak.start(), consensus_peak.end())
+                    .into_iter()
+                    .map(PeakData::summit)
+                    .filter(|summit| {
+                        (consensus_peak.start()..=consensus_peak.end()).contains(summit)
+                    })
+                {
+                    summits.push(summit as f64);
+                    offsets.push(summit as i64 - consensus_peak.summit() as i64);
+                }
+            }
+            peaks.push(PeakSummitConcordance {
+                chromosome,
+                peak: *consensus_peak,
+                summits: summits.len(),
+                standard_deviation: standard_deviation(&summits),
+                median_absolute_deviation: median_absolute_deviation(summits),
+            });
+        }
+        let total = SummitOffsets::new(sample_offsets.concat());
+        Self {
+            peaks,
+            samples: sample_offsets.into_iter().map(SummitOffsets::new).collect(),
+            total,
+        }
+    }
+}
+
+/// Returns the population standard deviation of the values or `0.0` if there are no values.
+///
+/// # Parameters
+///
+/// * `values` - the values
+fn standard_deviation(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    (values
+        .iter()
+        .map(|value| (value - mean).powi(2))
+        .sum::<f64>()
+        / values.len() as f64)
+        .sqrt()
+}
+
+/// Returns the median of the values or `0.0` if there are no values.
+///
+/// # Parameters
+///
+/// * `values` - the values
+fn median(mut values: Vec<f64>) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+/// Returns the median absolute deviation of the values from their median
+/// or `0.0` if there are no values.
+///
+/// # Parameters
+///
+/// * `values` - the values
+fn median_absolute_deviation(values: Vec<f64>) -> f64 {
+    let center = median(values.clone());
+    median(
+        values
+            .into_iter()
+            .map(|value| (value - center).abs())
+            .collect(),
+    )
+}
+
+/// Writes the summit concordance tables to the specified directory.
+/// The per consensus peak table is written to [`PEAK_CONCORDANCE_FILE_NAME`] and the per sample
+/// table to [`SAMPLE_OFFSETS_FILE_NAME`], where the offsets of all samples are reported as `*`.
+///
+/// # Parameters
+///
+/// * `directory` - the output directory
+/// * `sample_names` - the names of the samples in the order used to calculate the concordance
+/// * `concordance` - the summit concordance
+///
+/// # Errors
+///
+/// Returns an error if the directory or the tables cannot be written.
+pub fn write_summit_concordance<T: AsRef<Path>, S: AsRef<str>>(
+    directory: T,
+    sample_names: &[S],
+    concordance: &SummitConcordance,
+) -> Result<(), ApplicationError> {
+    let directory = directory.as_ref();
+    log::info!(target: LOG_TARGET, "Writing the summit concordance to {}.", directory.display());
+    std::fs::create_dir_all(directory).map_err(|err| {
+        ApplicationError::from(err).chain(format!(
+            "The summit concordance directory \"{}\" could not be created.",
+            directory.display()
+        ))
+    })?;
+
+    let mut peak_table =
+        String::from("chromosome\tstart\tend\tsummit\tsummits\tsummit_sd\tsummit_mad\n");
+    for peak in concordance.peaks() {
+        peak_table.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\t{:.3}\t{:.3}\n",
+            peak.chromosome(),
+            peak.peak().start(),
+            peak.peak().end(),
+            peak.peak().summit(),
+            peak.summits(),
+            peak.standard_deviation(),
+            peak.median_absolute_deviation()
+        ));
+    }
+    let mut sample_table = String::from(
+        "sample\tsummits\tmean_absolute_offset\toffset_min\toffset_q25\toffset_median\t\
+        offset_q75\toffset_max\n",
+    );
+    let rows = sample_names
+        .iter()
+        .map(AsRef::as_ref)
+        .zip(concordance.samples().iter().copied())
+        .chain(std::iter::once(("*", concordance.total())));
+    for (sample, offsets) in rows {
+        sample_table.push_str(&format!(
+            "{}\t{}\t{:.3}\t{}\t{}\t{}\t{}\t{}\n",
+            sample,
+            offsets.summits(),
+            offsets.mean_absolute_offset(),
+            offsets.min(),
+            offsets.q25(),
+            offsets.median(),
+            offsets.q75(),
+            offsets.max()
+        ));
+    }
+
+    for (file_name, table) in [
+        (PEAK_CONCORDANCE_FILE_NAME, peak_table),
+        (SAMPLE_OFFSETS_FILE_NAME, sample_table),
+    ] {
+        let path = directory.join(file_name);
+        File::create(&path)
+            .and_then(|mut file| file.write_all(table.as_bytes()))
+            .map_err(|err| {
+                ApplicationError::from(err).chain(format!(
+                    "The summit concordance table \"{}\" could not be written.",
+                    path.display()
+                ))
+            })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::{peak_set, test_output};
+
+    use super::*;
+
+    #[test]
+    fn test_spread() {
+        assert_eq!(standard_deviation(&[]), 0.0);
+        assert_eq!(standard_deviation(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]), 2.0);
+        assert_eq!(median(vec![3.0, 1.0, 2.0]), 2.0);
+        assert_eq!(median(vec![4.0, 1.0, 2.0, 3.0]), 2.5);
+        assert_eq!(median_absolute_deviation(vec![1.0, 1.0, 2.0, 2.0, 4.0, 6.0, 9.0]), 1.0);
+        assert_eq!(median_absolute_deviation(Vec::new()), 0.0);
+    }
+
+    #[test]
+    fn test_summit_concordance() {
+        let consensus_peaks = peak_set(&[("chr1", 100, 199, 150), ("chr1", 500, 599, 550)]);
+        let samples = [
+            peak_set(&[("chr1", 90, 190, 140), ("chr1", 520, 560, 540)]),
+            peak_set(&[("chr1", 120, 210, 160), ("chr1", 590, 700, 650)]),
+        ];
+        let concordance = SummitConcordance::new(&consensus_peaks, &samples);
+        assert_eq!(concordance.peaks().len(), 2);
+        assert_eq!(concordance.peaks()[0].summits(), 2);
+        assert_eq!(concordance.peaks()[0].standard_deviation(), 10.0);
+        assert_eq!(concordance.peaks()[0].median_absolute_deviation(), 10.0);
+        // The summit of the second sample peak is located outside of the consensus peak.
+        assert_eq!(concordance.peaks()[1].summits(), 1);
+        assert_eq!(concordance.peaks()[1].standard_deviation(), 0.0);
+        assert_eq!(concordance.samples()[0].summits(), 2);
+        assert_eq!(concordance.samples()[0].min(), -10);
+        assert_eq!(concordance.samples()[1].max(), 10);
+        assert_eq!(concordance.total().summits(), 3);
+        assert_eq!(concordance.total().mean_absolute_offset(), 10.0);
+
+        let directory = test_output().join("test_summit_concordance");
+        write_summit_concordance(&directory, &["a", "b"], &concordance).unwrap();
+        let peak_table =
+            std::fs::read_to_string(directory.join(PEAK_CONCORDANCE_FILE_NAME)).unwrap();
+        let sample_table =
+            std::fs::read_to_string(directory.join(SAMPLE_OFFSETS_FILE_NAME)).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(peak_table.lines().nth(1), Some("chr1\t100\t199\t150\t2\t10.000\t10.000"));
+        assert_eq!(
+            sample_table.lines().collect::<Vec<&str>>()[1..],
+            [
+                "a\t2\t10.000\t-10\t-10\t-10\t-10\t-10",
+                "b\t1\t10.000\t10\t10\t10\t10\t10",
+                "*\t3\t10.000\t-10\t-10\t-10\t10\t10",
+            ]
+        );
+    }
+}
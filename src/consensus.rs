@@ -0,0 +1,1029 @@
+//! This module orchestrates consensus peak generation across chromosomes.
+
+use std::{
+    collections::{HashMap, HashSet},
+    iter::Peekable,
+    path::Path,
+    sync::Arc,
+};
+
+use crate::{
+    chromosome::Chromosome,
+    error::{ApplicationError, ApplicationErrorType},
+    input::{InputFormat, bed_to_peaks_with_format},
+    peaks::{
+        ConsensusParameters, ConsensusPeakAlgorithm, PeakData, TrackedConsensusPeak,
+        gipfelkreuzer::{AggregationBackend, CoordinateAggregation, PeakWeights},
+        mspc::PeakEvidence,
+    },
+    provenance::{SampleIndex, filter_min_samples},
+    scores::{PeakScores, raw_peak_weights},
+    strand::{Strand, split_by_strand},
+};
+
+#[cfg(feature = "spill")]
+use std::path::PathBuf;
+
+#[cfg(feature = "spill")]
+use crate::spill::{DEFAULT_COMPRESSION_LEVEL, spill_peaks_by_chromosome};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "consensus";
+
+/// Consensus peaks keeping track of their raw peaks grouped by chromosome.
+pub type TrackedConsensus = HashMap<Chromosome, Vec<TrackedConsensusPeak>>;
+
+/// Consensus peaks keeping track of their raw peaks grouped by chromosome together with their
+/// strands by peak ID.
+pub type StrandedConsensus = (TrackedConsensus, HashMap<usize, Strand>);
+
+/// Discards the raw peaks tracked by the consensus peaks.
+///
+/// # Parameters
+///
+/// * `consensus` - the consensus peaks keeping track of their raw peaks
+pub fn untrack_consensus(consensus: TrackedConsensus) -> HashMap<Chromosome, Vec<PeakData>> {
+    consensus
+        .into_iter()
+        .map(|(chromosome, consensus_peaks)| {
+            (chromosome, consensus_peaks.into_iter().map(PeakData::from).collect())
+        })
+        .collect()
+}
+
+/// A builder configuring and running consensus peak generation.
+/// The available options mirror the command line arguments of the `Gipfelkreuzer` application.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use gipfelkreuzer::{
+///     chromosome::Chromosome,
+///     consensus::ConsensusBuilder,
+///     peaks::{ConsensusPeakAlgorithm, PeakData},
+/// };
+///
+/// let raw_peaks = HashMap::from([(
+///     Chromosome::from("chr1"),
+///     vec![
+///         PeakData::new(0, 100u64, 200u64, 150u64).unwrap(),
+///         PeakData::new(1, 110u64, 190u64, 148u64).unwrap(),
+///     ],
+/// )]);
+/// let consensus_peaks = ConsensusBuilder::new()
+///     .algorithm(ConsensusPeakAlgorithm::Gipfelkreuzer)
+///     .min_peaks_per_consensus(2)
+///     .run(raw_peaks)
+///     .unwrap();
+/// assert_eq!(consensus_peaks[&Chromosome::from("chr1")].len(), 1);
+/// ```
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConsensusBuilder {
+    algorithm: ConsensusPeakAlgorithm,
+    max_merge_iterations: usize,
+    summit_tolerance: Option<u64>,
+    coordinate_aggregation: CoordinateAggregation,
+    peak_weights: Option<Arc<PeakWeights>>,
+    aggregation_backend: AggregationBackend,
+    min_peaks_per_consensus: usize,
+    min_samples: usize,
+    sample_index: Option<Arc<SampleIndex>>,
+    harmonising_distance: u64,
+    max_gap: u64,
+    min_overlap: f64,
+    reciprocal_overlap: bool,
+    combined_stringency: f64,
+    weak_threshold: f64,
+    min_replicates: usize,
+    evidence: Option<Arc<PeakEvidence>>,
+    input_format: InputFormat,
+    #[cfg(feature = "spill")]
+    spill_directory: Option<PathBuf>,
+    #[cfg(feature = "spill")]
+    spill_compression_level: i32,
+    #[cfg(feature = "parallel")]
+    threads: usize,
+}
+
+impl ConsensusBuilder {
+    /// Creates a new builder using the Gipfelkreuzer algorithm and default parameters.
+    pub fn new() -> Self {
+        let parameters = ConsensusParameters::default();
+        Self {
+            algorithm: ConsensusPeakAlgorithm::Gipfelkreuzer,
+            max_merge_iterations: parameters.max_merge_iterations(),
+            summit_tolerance: parameters.summit_tolerance(),
+            coordinate_aggregation: parameters.coordinate_aggregation(),
+            peak_weights: None,
+            aggregation_backend: parameters.aggregation_backend(),
+            min_peaks_per_consensus: parameters.min_peaks_per_consensus(),
+            min_samples: 0,
+            sample_index: None,
+            harmonising_distance: parameters.harmonising_distance(),
+            max_gap: parameters.max_gap(),
+            min_overlap: parameters.min_overlap(),
+            reciprocal_overlap: parameters.reciprocal_overlap(),
+            combined_stringency: parameters.combined_stringency(),
+            weak_threshold: parameters.weak_threshold(),
+            min_replicates: parameters.min_replicates(),
+            evidence: None,
+            input_format: InputFormat::Auto,
+            #[cfg(feature = "spill")]
+            spill_directory: None,
+            #[cfg(feature = "spill")]
+            spill_compression_level: DEFAULT_COMPRESSION_LEVEL,
+            #[cfg(feature = "parallel")]
+            threads: 0,
+        }
+    }
+
+    /// Sets the algorithm used to create consensus peaks.
+    ///
+    /// # Parameters
+    ///
+    /// * `algorithm` - the consensus peak algorithm
+    pub fn algorithm(mut self, algorithm: ConsensusPeakAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Sets the maximum number of consensus peak merging iterations of the Gipfelkreuzer algorithm.
+    ///
+    /// # Parameters
+    ///
+    /// * `max_merge_iterations` - the maximum number of merging iterations
+    pub fn max_merge_iterations(mut self, max_merge_iterations: usize) -> Self {
+        self.max_merge_iterations = max_merge_iterations;
+        self
+    }
+
+    /// Sets the maximum distance between the summits of peaks merged by the Gipfelkreuzer
+    /// algorithm. If not set, peaks are merged if their summit lies within the consensus
+    /// defining peak.
+    ///
+    /// # Parameters
+    ///
+    /// * `summit_tolerance` - the maximum summit distance in base pairs
+    pub fn summit_tolerance(mut self, summit_tolerance: Option<u64>) -> Self {
+        self.summit_tolerance = summit_tolerance;
+        self
+    }
+
+    /// Sets the way the coordinates of peaks merged by the Gipfelkreuzer algorithm are combined.
+    ///
+    /// # Parameters
+    ///
+    /// * `coordinate_aggregation` - the way the coordinates are combined
+    pub fn coordinate_aggregation(mut self, coordinate_aggregation: CoordinateAggregation) -> Self {
+        self.coordinate_aggregation = coordinate_aggregation;
+        self
+    }
+
+    /// Sets the weights of the raw peaks, which are required for weighted coordinate aggregation.
+    /// Raw peaks without a weight do not contribute to weighted consensus coordinates.
+    ///
+    /// # Parameters
+    ///
+    /// * `peak_weights` - the weights of the raw peaks by their identifiers
+    pub fn peak_weights(mut self, peak_weights: PeakWeights) -> Self {
+        self.peak_weights = Some(Arc::new(peak_weights));
+        self
+    }
+
+    /// Sets the implementation used by the Gipfelkreuzer algorithm to aggregate the peaks of
+    /// a bin. All implementations create identical consensus peaks.
+    ///
+    /// # Parameters
+    ///
+    /// * `aggregation_backend` - the aggregation implementation
+    pub fn aggregation_backend(mut self, aggregation_backend: AggregationBackend) -> Self {
+        self.aggregation_backend = aggregation_backend;
+        self
+    }
+
+    /// Sets the minimum number of raw peaks per consensus peak to consider it reproducible.
+    ///
+    /// # Parameters
+    ///
+    /// * `min_peaks_per_consensus` - the minimum number of raw peaks required to form a consensus peak
+    pub fn min_peaks_per_consensus(mut self, min_peaks_per_consensus: usize) -> Self {
+        self.min_peaks_per_consensus = min_peaks_per_consensus;
+        self
+    }
+
+    /// Sets the minimum number of distinct samples contributing raw peaks to a consensus peak
+    /// to consider it reproducible. In contrast to the minimum number of raw peaks, multiple
+    /// raw peaks of the same sample only count once. The samples of the raw peaks are set via
+    /// [`sample_index`](Self::sample_index) or determined from the input files by
+    /// [`run_files`](Self::run_files).
+    ///
+    /// # Parameters
+    ///
+    /// * `min_samples` - the minimum number of contributing samples
+    pub fn min_samples(mut self, min_samples: usize) -> Self {
+        self.min_samples = min_samples;
+        self
+    }
+
+    /// Sets the samples of the raw peaks required to filter consensus peaks by their number of
+    /// contributing samples.
+    ///
+    /// # Parameters
+    ///
+    /// * `sample_index` - the mapping of raw peaks to samples
+    pub fn sample_index(mut self, sample_index: SampleIndex) -> Self {
+        self.sample_index = Some(Arc::new(sample_index));
+        self
+    }
+
+    /// Sets the distance from the summit that is used by the harmonised algorithm.
+    ///
+    /// # Parameters
+    ///
+    /// * `harmonising_distance` - the distance from the summit that is considered a harmonised peak region
+    pub fn harmonising_distance(mut self, harmonising_distance: u64) -> Self {
+        self.harmonising_distance = harmonising_distance;
+        self
+    }
+
+    /// Sets the maximum number of bases between peaks merged by the simple algorithm.
+    /// By default only overlapping and adjacent peaks are merged.
+    ///
+    /// # Parameters
+    ///
+    /// * `max_gap` - the maximum gap in base pairs
+    pub fn max_gap(mut self, max_gap: u64) -> Self {
+        self.max_gap = max_gap;
+        self
+    }
+
+    /// Sets the minimum fraction of the shorter peak that must be overlapped by another peak
+    /// to merge both peaks with the overlap algorithm.
+    ///
+    /// # Parameters
+    ///
+    /// * `min_overlap` - the minimum overlap fraction
+    pub fn min_overlap(mut self, min_overlap: f64) -> Self {
+        self.min_overlap = min_overlap;
+        self
+    }
+
+    /// Sets if the minimum overlap fraction of the overlap algorithm must be reached
+    /// for both peaks instead of only the shorter one.
+    ///
+    /// # Parameters
+    ///
+    /// * `reciprocal_overlap` - if the overlap must be reciprocal
+    pub fn reciprocal_overlap(mut self, reciprocal_overlap: bool) -> Self {
+        self.reciprocal_overlap = reciprocal_overlap;
+        self
+    }
+
+    /// Sets the maximum combined p-value of peaks confirmed by the MSPC algorithm.
+    ///
+    /// # Parameters
+    ///
+    /// * `combined_stringency` - the maximum combined p-value
+    pub fn combined_stringency(mut self, combined_stringency: f64) -> Self {
+        self.combined_stringency = combined_stringency;
+        self
+    }
+
+    /// Sets the maximum p-value of peaks not considered background by the MSPC algorithm.
+    ///
+    /// # Parameters
+    ///
+    /// * `weak_threshold` - the maximum p-value
+    pub fn weak_threshold(mut self, weak_threshold: f64) -> Self {
+        self.weak_threshold = weak_threshold;
+        self
+    }
+
+    /// Sets the minimum number of samples supporting a peak confirmed by the MSPC algorithm.
+    ///
+    /// # Parameters
+    ///
+    /// * `min_replicates` - the minimum number of supporting samples
+    pub fn min_replicates(mut self, min_replicates: usize) -> Self {
+        self.min_replicates = min_replicates;
+        self
+    }
+
+    /// Sets the sample and p-value of the raw peaks, which are required by the MSPC algorithm.
+    ///
+    /// # Parameters
+    ///
+    /// * `evidence` - the sample and p-value of the raw peaks
+    pub fn evidence(mut self, evidence: PeakEvidence) -> Self {
+        self.evidence = Some(Arc::new(evidence));
+        self
+    }
+
+    /// Sets the format defining the interpretation of the columns of the input files
+    /// parsed by [`run_files`](Self::run_files).
+    ///
+    /// # Parameters
+    ///
+    /// * `input_format` - the input file format
+    pub fn input_format(mut self, input_format: InputFormat) -> Self {
+        self.input_format = input_format;
+        self
+    }
+
+    /// Returns if the consensus peak algorithm requires the statistical values of the raw peaks,
+    /// which are the p-values for the MSPC algorithm and the scores for weighted coordinate
+    /// aggregation.
+    pub fn requires_scores(&self) -> bool {
+        self.algorithm == ConsensusPeakAlgorithm::Mspc
+            || (self.algorithm == ConsensusPeakAlgorithm::Gipfelkreuzer
+                && self.coordinate_aggregation == CoordinateAggregation::WeightedMedian)
+    }
+
+    /// Sets the evidence or the weights of the raw peaks derived from their statistical values
+    /// if [required](Self::requires_scores) by the consensus peak algorithm.
+    ///
+    /// # Parameters
+    ///
+    /// * `raw_peak_scores` - the statistical values of the raw peaks by raw peak ID
+    /// * `peaks_per_file` - the number of raw peaks of each input file
+    pub fn raw_peak_scores(
+        self,
+        raw_peak_scores: &HashMap<usize, PeakScores>,
+        peaks_per_file: &[usize],
+    ) -> Self {
+        if !self.requires_scores() {
+            self
+        } else if self.algorithm == ConsensusPeakAlgorithm::Mspc {
+            self.evidence(PeakEvidence::from_scores(
+                raw_peak_scores,
+                &SampleIndex::new(peaks_per_file),
+            ))
+        } else {
+            self.peak_weights(raw_peak_weights(raw_peak_scores))
+        }
+    }
+
+    /// Spills the raw peaks to the specified directory before consensus peak generation,
+    /// so only the raw peaks of a single chromosome are kept in memory while consensus peaks
+    /// are generated. The raw peaks passed to the builder are still held in memory completely
+    /// until they have been spilled.
+    ///
+    /// # Parameters
+    ///
+    /// * `spill_directory` - the directory to store the temporary spill files in or `None` to keep all peaks in memory
+    #[cfg(feature = "spill")]
+    pub fn spill_directory<T: Into<PathBuf>>(mut self, spill_directory: Option<T>) -> Self {
+        self.spill_directory = spill_directory.map(Into::into);
+        self
+    }
+
+    /// Sets the zstd compression level of spilled peak data.
+    ///
+    /// # Parameters
+    ///
+    /// * `spill_compression_level` - the compression level
+    #[cfg(feature = "spill")]
+    pub fn spill_compression_level(mut self, spill_compression_level: i32) -> Self {
+        self.spill_compression_level = spill_compression_level;
+        self
+    }
+
+    /// Sets the number of threads generating the consensus peaks of different chromosomes
+    /// in parallel. Spilled peaks are always processed one chromosome at a time.
+    ///
+    /// # Parameters
+    ///
+    /// * `threads` - the number of threads or `0` to use one thread per available CPU core
+    #[cfg(feature = "parallel")]
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Returns the algorithm used to create consensus peaks.
+    pub fn consensus_algorithm(&self) -> ConsensusPeakAlgorithm {
+        self.algorithm
+    }
+
+    /// Returns the [`ConsensusParameters`] passed to the consensus peak algorithm.
+    pub fn consensus_parameters(&self) -> ConsensusParameters {
+        ConsensusParameters::new(
+            self.max_merge_iterations,
+            self.min_peaks_per_consensus,
+            self.harmonising_distance,
+        )
+        .with_summit_tolerance(self.summit_tolerance)
+        .with_max_gap(self.max_gap)
+        .with_coordinate_aggregation(self.coordinate_aggregation, self.peak_weights.clone())
+        .with_aggregation_backend(self.aggregation_backend)
+        .with_min_overlap(self.min_overlap, self.reciprocal_overlap)
+        .with_mspc(
+            self.combined_stringency,
+            self.weak_threshold,
+            self.min_replicates,
+            self.evidence.clone(),
+        )
+    }
+
+    /// Creates consensus peaks for all chromosomes of the specified raw peaks.
+    ///
+    /// # Parameters
+    ///
+    /// * `peaks` - the raw peaks grouped by chromosome
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if spilling the peaks or creating the consensus peaks fails.
+    pub fn run(
+        &self,
+        peaks: HashMap<Chromosome, Vec<PeakData>>,
+    ) -> Result<HashMap<Chromosome, Vec<PeakData>>, ApplicationError> {
+        Ok(untrack_consensus(self.run_tracked(peaks)?))
+    }
+
+    /// Creates consensus peaks like [`run`](Self::run), but keeps track of the raw peaks
+    /// each consensus peak was created from.
+    ///
+    /// # Parameters
+    ///
+    /// * `peaks` - the raw peaks grouped by chromosome
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if spilling the peaks or creating the consensus peaks fails.
+    pub fn run_tracked(
+        &self,
+        peaks: HashMap<Chromosome, Vec<PeakData>>,
+    ) -> Result<TrackedConsensus, ApplicationError> {
+        #[cfg(feature = "spill")]
+        if let Some(spill_directory) = &self.spill_directory {
+            let mut consensus: TrackedConsensus = HashMap::new();
+            let spill_files =
+                spill_peaks_by_chromosome(spill_directory, peaks, self.spill_compression_level)
+                    .map_err(|err| err.chain("Failed to spill the parsed peaks to disk."))?;
+            for (chromosome, spill_file) in spill_files {
+                let peaks = spill_file
+                    .read_peaks()
+                    .map_err(|err| err.chain("Failed to load spilled peaks."))?;
+                let consensus_peaks = self.chromosome_consensus_peaks(&chromosome, peaks)?;
+                consensus.insert(chromosome, consensus_peaks);
+            }
+            return Ok(consensus);
+        }
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+
+            let thread_pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(self.threads)
+                .build()
+                .map_err(|err| {
+                    ApplicationError::new(ApplicationErrorType::AlgorithmError, err.to_string())
+                        .chain("The consensus peak thread pool could not be created.")
+                })?;
+            log::debug!(
+                target: LOG_TARGET,
+                "Generating consensus peaks using {} threads.",
+                thread_pool.current_num_threads()
+            );
+            thread_pool.install(|| {
+                peaks
+                    .into_par_iter()
+                    .map(|(chromosome, peaks)| {
+                        self.chromosome_consensus_peaks(&chromosome, peaks)
+                            .map(|consensus_peaks| (chromosome, consensus_peaks))
+                    })
+                    .collect()
+            })
+        }
+        #[cfg(not(feature = "parallel"))]
+        {
+            let mut consensus: TrackedConsensus = HashMap::new();
+            for (chromosome, peaks) in peaks {
+                let consensus_peaks = self.chromosome_consensus_peaks(&chromosome, peaks)?;
+                consensus.insert(chromosome, consensus_peaks);
+            }
+            Ok(consensus)
+        }
+    }
+
+    /// Creates consensus peaks like [`run`](Self::run), but separately for each strand,
+    /// so raw peaks located on different strands are never merged into a single consensus peak.
+    /// Raw peaks without a known strand are merged with each other only.
+    /// As the consensus peaks of different strands may overlap, they are renumbered by
+    /// chromosome and coordinates to keep their identifiers unique.
+    /// Returns the consensus peaks, which keep track of their raw peaks, together with their
+    /// strands by peak ID.
+    ///
+    /// # Parameters
+    ///
+    /// * `peaks` - the raw peaks grouped by chromosome
+    /// * `strands` - the strands of the raw peaks by peak ID
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if spilling the peaks or creating the consensus peaks fails.
+    pub fn run_stranded(
+        &self,
+        peaks: HashMap<Chromosome, Vec<PeakData>>,
+        strands: &HashMap<usize, Strand>,
+    ) -> Result<StrandedConsensus, ApplicationError> {
+        let mut stranded_peaks: Vec<(Chromosome, Strand, TrackedConsensusPeak)> = Vec::new();
+        for (strand, peaks) in split_by_strand(peaks, strands) {
+            log::info!(target: LOG_TARGET, "Processing peaks of strand \"{}\".", strand);
+            for (chromosome, consensus_peaks) in self.run_tracked(peaks)? {
+                stranded_peaks.extend(
+                    consensus_peaks
+                        .into_iter()
+                        .map(|peak| (chromosome, strand, peak)),
+                );
+            }
+        }
+        stranded_peaks.sort_unstable_by_key(|(chromosome, strand, peak)| {
+            (chromosome.name(), peak.peak().start(), peak.peak().end(), *strand)
+        });
+        let mut consensus: TrackedConsensus = HashMap::new();
+        let mut consensus_strands = HashMap::new();
+        for (id, (chromosome, strand, peak)) in stranded_peaks.into_iter().enumerate() {
+            let peak = peak.with_id(id);
+            if strand != Strand::Unknown {
+                consensus_strands.insert(id, strand);
+            }
+            consensus.entry(chromosome).or_default().push(peak);
+        }
+        Ok((consensus, consensus_strands))
+    }
+
+    /// Parses the specified BED files and creates consensus peaks for all chromosomes.
+    ///
+    /// # Parameters
+    ///
+    /// * `paths` - the paths to the BED3+ input peak files
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if parsing the input files or creating the consensus peaks fails.
+    pub fn run_files<T: AsRef<Path>>(
+        &self,
+        paths: &[T],
+    ) -> Result<HashMap<Chromosome, Vec<PeakData>>, ApplicationError> {
+        let parsed = bed_to_peaks_with_format(paths, self.input_format, None, false, false)
+            .map_err(|err| {
+                err.chain(format!(
+                    "Failed to parse input files \"{:?}\".",
+                    paths.iter().map(AsRef::as_ref).collect::<Vec<&Path>>()
+                ))
+            })?;
+        if self.sample_index.is_none() {
+            return self
+                .clone()
+                .sample_index(SampleIndex::new(&parsed.peaks_per_file))
+                .run(parsed.peaks);
+        }
+        self.run(parsed.peaks)
+    }
+
+    /// Creates consensus peaks like [`run_tracked`](Self::run_tracked), but consumes the raw peaks
+    /// chromosome by chromosome, for example from a [`BedPeakStream`](crate::input::BedPeakStream).
+    /// The raw peaks of each chromosome are dropped once its consensus peaks have been created,
+    /// so only the raw peaks of the current chunk and the consensus peaks are kept in memory.
+    ///
+    /// # Parameters
+    ///
+    /// * `chunks` - the raw peaks of each chromosome, where each chromosome must only occur once
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a chunk is an error, a chromosome occurs multiple times
+    /// or creating the consensus peaks fails.
+    pub fn run_tracked_chunks<
+        I: IntoIterator<Item = Result<(Chromosome, Vec<PeakData>), ApplicationError>>,
+    >(
+        &self,
+        chunks: I,
+    ) -> Result<TrackedConsensus, ApplicationError> {
+        let mut consensus: TrackedConsensus = HashMap::new();
+        for chunk in chunks {
+            let (chromosome, peaks) = chunk?;
+            if consensus.contains_key(&chromosome) {
+                return Err(ApplicationError::new(
+                    ApplicationErrorType::ValidationError,
+                    format!("The peaks of chromosome {} were provided multiple times.", chromosome),
+                ));
+            }
+            let consensus_peaks = self.chromosome_consensus_peaks(&chromosome, peaks)?;
+            consensus.insert(chromosome, consensus_peaks);
+        }
+        Ok(consensus)
+    }
+
+    /// Creates a [`ConsensusStream`] generating consensus peaks incrementally from the specified raw peaks.
+    ///
+    /// # Parameters
+    ///
+    /// * `peaks` - the raw peaks, where all peaks of a chromosome must be consecutive
+    pub fn stream<I: IntoIterator<Item = (Chromosome, PeakData)>>(
+        &self,
+        peaks: I,
+    ) -> ConsensusStream<I::IntoIter> {
+        ConsensusStream {
+            builder: self.clone(),
+            peaks: peaks.into_iter().peekable(),
+            processed_chromosomes: HashSet::new(),
+            failed: false,
+        }
+    }
+
+    /// Generates the consensus peaks of a single chromosome.
+    ///
+    /// # Parameters
+    ///
+    /// * `chromosome` - the chromosome the peaks are located on
+    /// * `peaks` - the raw peaks of the chromosome
+    fn chromosome_consensus_peaks(
+        &self,
+        chromosome: &Chromosome,
+        peaks: Vec<PeakData>,
+    ) -> Result<Vec<TrackedConsensusPeak>, ApplicationError> {
+        log::info!(
+            target: LOG_TARGET,
+            "Processing {} raw peaks for chromosome {}.",
+            peaks.len(),
+            chromosome
+        );
+        let consensus_peaks = self
+            .algorithm
+            .tracked_consensus_peaks(peaks, &self.consensus_parameters())
+            .map_err(|err| err.chain("Failed to create consensus peaks."))?;
+        log::info!(target: LOG_TARGET, "Generated {} consensus peaks.", consensus_peaks.len());
+        if self.min_samples <= 1 {
+            return Ok(consensus_peaks);
+        }
+        let sample_index = self.sample_index.as_deref().ok_or_else(|| {
+            ApplicationError::new(
+                ApplicationErrorType::ValidationError,
+                format!(
+                    "A minimum of {} samples per consensus peak requires the samples of the raw peaks.",
+                    self.min_samples
+                ),
+            )
+            .with_hint("Set the samples of the raw peaks with \"ConsensusBuilder::sample_index\".")
+        })?;
+        Ok(filter_min_samples(
+            HashMap::from([(*chromosome, consensus_peaks)]),
+            sample_index,
+            self.min_samples,
+        )
+        .remove(chromosome)
+        .unwrap_or_default())
+    }
+}
+
+impl Default for ConsensusBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An iterator consuming raw peaks grouped by chromosome and yielding the consensus peaks
+/// of each chromosome as soon as all of its raw peaks have been consumed.
+/// Only the raw peaks of the current chromosome are collected, so the memory usage is bounded
+/// by the largest chromosome if the raw peaks are read lazily.
+///
+/// The stream is created via [`ConsensusBuilder::stream`].
+/// An error is yielded if the peaks of a chromosome are not consecutive,
+/// after which the stream ends.
+///
+/// # Example
+///
+/// ```
+/// use gipfelkreuzer::{chromosome::Chromosome, consensus::ConsensusBuilder, peaks::PeakData};
+///
+/// let raw_peaks = vec![
+///     (Chromosome::from("chr1"), PeakData::new(0, 100u64, 200u64, 150u64).unwrap()),
+///     (Chromosome::from("chr1"), PeakData::new(1, 110u64, 190u64, 148u64).unwrap()),
+///     (Chromosome::from("chr2"), PeakData::new(2, 10u64, 20u64, 15u64).unwrap()),
+/// ];
+/// for result in ConsensusBuilder::new().stream(raw_peaks) {
+///     let (chromosome, consensus_peaks) = result.unwrap();
+///     println!("{}: {} consensus peaks", chromosome, consensus_peaks.len());
+/// }
+/// ```
+pub struct ConsensusStream<I: Iterator<Item = (Chromosome, PeakData)>> {
+    builder: ConsensusBuilder,
+    peaks: Peekable<I>,
+    processed_chromosomes: HashSet<Chromosome>,
+    failed: bool,
+}
+
+impl<I: Iterator<Item = (Chromosome, PeakData)>> Iterator for ConsensusStream<I> {
+    type Item = Result<(Chromosome, Vec<PeakData>), ApplicationError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let (chromosome, first_peak) = self.peaks.next()?;
+        if !self.processed_chromosomes.insert(chromosome) {
+            // Ends the stream as the remaining input cannot be processed consistently.
+            self.failed = true;
+            return Some(Err(ApplicationError::new(
+                ApplicationErrorType::ValidationError,
+                format!(
+                    "The peaks of chromosome {} are not consecutive in the input stream.",
+                    chromosome
+                ),
+            )));
+        }
+        let mut peaks = vec![first_peak];
+        while let Some((_, peak)) = self
+            .peaks
+            .next_if(|(next_chromosome, _)| *next_chromosome == chromosome)
+        {
+            peaks.push(peak);
+        }
+        Some(
+            self.builder
+                .chromosome_consensus_peaks(&chromosome, peaks)
+                .map(|consensus_peaks| {
+                    (chromosome, consensus_peaks.into_iter().map(PeakData::from).collect())
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "spill")]
+    use crate::test_utils::test_output;
+    use crate::test_utils::test_resources;
+
+    use super::*;
+
+    fn raw_peaks() -> HashMap<Chromosome, Vec<PeakData>> {
+        HashMap::from([
+            (
+                Chromosome::from("chr1"),
+                vec![
+                    PeakData::new(0, 100u64, 200u64, 150u64).unwrap(),
+                    PeakData::new(1, 110u64, 190u64, 148u64).unwrap(),
+                    PeakData::new(2, 5000u64, 5100u64, 5050u64).unwrap(),
+                ],
+            ),
+            (Chromosome::from("chr2"), vec![PeakData::new(3, 10u64, 20u64, 15u64).unwrap()]),
+        ])
+    }
+
+    #[test]
+    fn test_consensus_builder_parameters() {
+        let builder = ConsensusBuilder::new()
+            .algorithm(ConsensusPeakAlgorithm::Harmonised)
+            .max_merge_iterations(3)
+            .summit_tolerance(Some(10))
+            .coordinate_aggregation(CoordinateAggregation::Mean)
+            .aggregation_backend(AggregationBackend::Indexed)
+            .min_peaks_per_consensus(2)
+            .harmonising_distance(50)
+            .max_gap(5)
+            .min_overlap(0.8)
+            .reciprocal_overlap(true);
+        assert_eq!(
+            builder.consensus_parameters(),
+            ConsensusParameters::new(3, 2, 50)
+                .with_summit_tolerance(Some(10))
+                .with_max_gap(5)
+                .with_coordinate_aggregation(CoordinateAggregation::Mean, None)
+                .with_aggregation_backend(AggregationBackend::Indexed)
+                .with_min_overlap(0.8, true)
+        );
+        assert_eq!(
+            ConsensusBuilder::default().consensus_parameters(),
+            ConsensusParameters::default()
+        );
+    }
+
+    #[test]
+    fn test_consensus_builder_run() {
+        let consensus = ConsensusBuilder::new().run(raw_peaks()).unwrap();
+        assert_eq!(consensus[&Chromosome::from("chr1")].len(), 2);
+        assert_eq!(consensus[&Chromosome::from("chr2")].len(), 1);
+        let consensus = ConsensusBuilder::new()
+            .min_peaks_per_consensus(2)
+            .run(raw_peaks())
+            .unwrap();
+        assert_eq!(consensus[&Chromosome::from("chr1")].len(), 1);
+        assert!(consensus[&Chromosome::from("chr2")].is_empty());
+    }
+
+    #[test]
+    fn test_consensus_builder_min_samples() {
+        // Raw peaks 0 and 1 of the same sample and raw peak 2 of another sample.
+        let raw_peaks = HashMap::from([(
+            Chromosome::from("chr1"),
+            vec![
+                PeakData::new(0, 100u64, 200u64, 150u64).unwrap(),
+                PeakData::new(1, 5000u64, 5100u64, 5050u64).unwrap(),
+                PeakData::new(2, 110u64, 190u64, 148u64).unwrap(),
+            ],
+        )]);
+        let builder = ConsensusBuilder::new().min_samples(2);
+        assert_eq!(
+            builder.run(raw_peaks.clone()).unwrap_err().error_type(),
+            ApplicationErrorType::ValidationError
+        );
+        let consensus = builder
+            .sample_index(SampleIndex::new(&[2, 1]))
+            .run_tracked(raw_peaks)
+            .unwrap();
+        let consensus_peaks = &consensus[&Chromosome::from("chr1")];
+        assert_eq!(consensus_peaks.len(), 1);
+        let mut raw_peak_ids = consensus_peaks[0].raw_peak_ids().clone();
+        raw_peak_ids.sort();
+        assert_eq!(raw_peak_ids, vec![0, 2]);
+    }
+
+    #[test]
+    fn test_consensus_builder_run_files_min_samples() {
+        let paths = [
+            test_resources().join("input_test_valid_with_summit_multiple_01.narrowPeak"),
+            test_resources().join("input_test_valid_with_summit_multiple_02.narrowPeak"),
+        ];
+        let all_samples = ConsensusBuilder::new().run_files(&paths).unwrap();
+        let min_samples = ConsensusBuilder::new()
+            .min_samples(2)
+            .run_files(&paths)
+            .unwrap();
+        // The peaks of the second file do not overlap the peaks of the first file.
+        assert_eq!(all_samples.values().map(Vec::len).sum::<usize>(), 4);
+        assert_eq!(min_samples.values().map(Vec::len).sum::<usize>(), 0);
+    }
+
+    #[test]
+    fn test_consensus_builder_run_files_input_format() {
+        let paths = [test_resources().join("input_test_valid_minimal.narrowPeak")];
+        assert!(ConsensusBuilder::new().run_files(&paths).is_ok());
+        // The narrowPeak format requires the summit offset, which the minimal file lacks.
+        assert!(
+            ConsensusBuilder::new()
+                .input_format(InputFormat::NarrowPeak)
+                .run_files(&paths)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_consensus_builder_max_gap() {
+        let raw_peaks = HashMap::from([(
+            Chromosome::from("chr1"),
+            vec![
+                PeakData::new(0, 100u64, 200u64, 150u64).unwrap(),
+                PeakData::new(1, 250u64, 300u64, 275u64).unwrap(),
+            ],
+        )]);
+        let builder = ConsensusBuilder::new().algorithm(ConsensusPeakAlgorithm::Simple);
+        assert_eq!(
+            builder.clone().run(raw_peaks.clone()).unwrap()[&Chromosome::from("chr1")].len(),
+            2
+        );
+        assert_eq!(
+            builder.max_gap(50).run(raw_peaks).unwrap()[&Chromosome::from("chr1")],
+            vec![PeakData::new(0, 100u64, 300u64, 200u64).unwrap()]
+        );
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_consensus_builder_run_threads() {
+        let raw_peaks: HashMap<Chromosome, Vec<PeakData>> = (0..8)
+            .map(|chromosome| {
+                let peaks = (0..200u64)
+                    .map(|index| {
+                        let start = index * 37 % 1000 + index;
+                        PeakData::new(
+                            chromosome * 200 + index as usize,
+                            start,
+                            start + 50 + index % 13,
+                            start + 20,
+                        )
+                        .unwrap()
+                    })
+                    .collect();
+                (Chromosome::from(format!("chr{}", chromosome).as_str()), peaks)
+            })
+            .collect();
+        let serial = ConsensusBuilder::new()
+            .threads(1)
+            .run(raw_peaks.clone())
+            .unwrap();
+        let parallel = ConsensusBuilder::new().threads(4).run(raw_peaks).unwrap();
+        assert_eq!(serial.len(), 8);
+        assert_eq!(serial, parallel);
+    }
+
+    #[test]
+    fn test_consensus_builder_run_stranded() {
+        let strands = HashMap::from([(0, Strand::Forward), (1, Strand::Reverse)]);
+        let builder = ConsensusBuilder::new().algorithm(ConsensusPeakAlgorithm::Simple);
+        assert_eq!(builder.run(raw_peaks()).unwrap()[&Chromosome::from("chr1")].len(), 2);
+        let (consensus, consensus_strands) = builder.run_stranded(raw_peaks(), &strands).unwrap();
+        let chr1 = &consensus[&Chromosome::from("chr1")];
+        assert_eq!(chr1.len(), 3);
+        assert_eq!(
+            chr1.iter()
+                .map(|peak| (peak.peak().id(), peak.raw_peak_ids().clone()))
+                .collect::<Vec<_>>(),
+            vec![(0, vec![0]), (1, vec![1]), (2, vec![2])]
+        );
+        assert_eq!(consensus[&Chromosome::from("chr2")][0].peak().id(), 3);
+        assert_eq!(consensus_strands, HashMap::from([(0, Strand::Forward), (1, Strand::Reverse)]));
+    }
+
+    #[test]
+    fn test_consensus_builder_run_tracked_chunks() {
+        let builder = ConsensusBuilder::new();
+        let consensus = builder
+            .run_tracked_chunks(raw_peaks().into_iter().map(Ok))
+            .unwrap();
+        assert_eq!(consensus, builder.run_tracked(raw_peaks()).unwrap());
+        let chr1 = Chromosome::from("chr1");
+        let peak = PeakData::new(0, 100u64, 200u64, 150u64).unwrap();
+        assert!(
+            builder
+                .run_tracked_chunks([Ok((chr1, vec![peak])), Ok((chr1, vec![peak]))])
+                .is_err()
+        );
+        assert!(
+            builder
+                .run_tracked_chunks([Err(ApplicationError::new(
+                    ApplicationErrorType::ParseError,
+                    "Invalid input."
+                ))])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_consensus_stream() {
+        let builder = ConsensusBuilder::new();
+        let raw_peaks = raw_peaks();
+        let mut chromosomes: Vec<Chromosome> = raw_peaks.keys().copied().collect();
+        chromosomes.sort();
+        let stream_input = chromosomes.iter().flat_map(|chromosome| {
+            raw_peaks[chromosome]
+                .iter()
+                .map(|peak| (*chromosome, *peak))
+        });
+        let streamed: Vec<(Chromosome, Vec<PeakData>)> = builder
+            .stream(stream_input)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            streamed
+                .iter()
+                .map(|(chromosome, _)| *chromosome)
+                .collect::<Vec<_>>(),
+            chromosomes
+        );
+        assert_eq!(
+            streamed.into_iter().collect::<HashMap<_, _>>(),
+            builder.run(raw_peaks).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_consensus_stream_not_consecutive() {
+        let chr1 = Chromosome::from("chr1");
+        let chr2 = Chromosome::from("chr2");
+        let peak = PeakData::new(0, 100u64, 200u64, 150u64).unwrap();
+        let mut stream = ConsensusBuilder::new().stream(vec![
+            (chr1, peak),
+            (chr2, peak),
+            (chr1, peak),
+            (chr2, peak),
+        ]);
+        assert!(stream.next().unwrap().is_ok());
+        assert!(stream.next().unwrap().is_ok());
+        assert!(stream.next().unwrap().is_err());
+        assert!(stream.next().is_none());
+        assert!(ConsensusBuilder::new().stream(Vec::new()).next().is_none());
+    }
+
+    #[cfg(feature = "spill")]
+    #[test]
+    fn test_consensus_builder_run_spill() {
+        let spill_directory = test_output().join("test_consensus_builder_run_spill");
+        std::fs::create_dir_all(&spill_directory).unwrap();
+        let builder = ConsensusBuilder::new().algorithm(ConsensusPeakAlgorithm::Simple);
+        assert_eq!(
+            builder
+                .clone()
+                .spill_directory(Some(&spill_directory))
+                .run(raw_peaks())
+                .unwrap(),
+            builder.run(raw_peaks()).unwrap()
+        );
+        assert_eq!(std::fs::read_dir(&spill_directory).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,590 @@
+//! This module converts peak files between the supported formats without generating consensus
+//! peaks, reusing the parsers and writers of the consensus peak generation.
+//!
+//! Supported formats are BED6, narrowPeak, the
+//! [SAF](https://subread.sourceforge.net/featureCounts.html) format of featureCounts,
+//! the JSON representation of the [`json`](crate::json) module and, if the `bigwig` feature is
+//! enabled, [bigBed](https://genome.ucsc.edu/goldenPath/help/bigBed.html).
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::Write,
+    path::Path,
+};
+
+use crate::{
+    chromosome::Chromosome,
+    error::{ApplicationError, ApplicationErrorType, ErrorContext},
+    input::bed_to_peaks,
+    json::{from_json, to_json},
+    output::{OutputFormat, write_bed_file, write_peaks_to_bed},
+    peaks::PeakData,
+    retry::retry,
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "convert";
+
+/// The header line of SAF files.
+const SAF_HEADER: &str = "GeneID\tChr\tStart\tEnd\tStrand";
+
+/// The autoSql definition of the narrowPeak fields stored in bigBed files.
+#[cfg(feature = "bigwig")]
+const NARROW_PEAK_AUTOSQL: &str = r#"table narrowPeak
+"BED6+4 Peaks of signal enrichment based on pooled, normalized (interpreted) data."
+(
+    string chrom;        "Reference sequence chromosome or scaffold"
+    uint   chromStart;   "Start position in chromosome"
+    uint   chromEnd;     "End position in chromosome"
+    string name;         "Name given to a region (preferably unique). Use . if no name is assigned"
+    uint   score;        "Indicates how dark the peak will be displayed in the browser (0-1000)"
+    char[1]  strand;     "+ or - or . for unknown"
+    float  signalValue;  "Measurement of average enrichment for the region"
+    float  pValue;       "Statistical significance of signal value (-log10). Set to -1 if not used."
+    float  qValue;       "Statistical significance with multiple-test correction applied (FDR -log10). Set to -1 if not used."
+    int   peak;          "Point-source called for this peak; 0-based offset from chromStart. Set to -1 if no point-source called."
+)
+"#;
+
+/// The file formats peaks can be converted between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum PeakFormat {
+    /// BED3+ files when reading and BED6 files when writing.
+    Bed,
+    /// narrowPeak files retaining the peak summits.
+    NarrowPeak,
+    /// The tab separated SAF format of featureCounts with 1-based inclusive coordinates.
+    Saf,
+    /// The JSON representation of peaks grouped by chromosome.
+    Json,
+    /// Binary indexed bigBed files storing the narrowPeak fields.
+    #[cfg(feature = "bigwig")]
+    BigBed,
+}
+
+impl PeakFormat {
+    /// Detects the format of a peak file from the extension of its path.
+    /// Returns `None` if the extension is unknown.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - the path of the peak file
+    pub fn from_path<T: AsRef<Path>>(path: T) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "bed" => Some(Self::Bed),
+            "narrowpeak" => Some(Self::NarrowPeak),
+            "saf" => Some(Self::Saf),
+            "json" => Some(Self::Json),
+            #[cfg(feature = "bigwig")]
+            "bb" | "bigbed" => Some(Self::BigBed),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for PeakFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PeakFormat::Bed => write!(f, "BED"),
+            PeakFormat::NarrowPeak => write!(f, "narrowPeak"),
+            PeakFormat::Saf => write!(f, "SAF"),
+            PeakFormat::Json => write!(f, "JSON"),
+            #[cfg(feature = "bigwig")]
+            PeakFormat::BigBed => write!(f, "bigBed"),
+        }
+    }
+}
+
+/// Reads the peaks of a peak file in the specified format.
+/// Summits of formats without summit information are set to the centre of the peak.
+///
+/// # Parameters
+///
+/// * `path` - the path of the peak file
+/// * `format` - the format of the peak file
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or contains invalid peaks.
+pub fn read_peaks<T: AsRef<Path>>(
+    path: T,
+    format: PeakFormat,
+) -> Result<HashMap<Chromosome, Vec<PeakData>>, ApplicationError> {
+    let path = path.as_ref();
+    log::info!(target: LOG_TARGET, "Reading {} file {}.", format, path.display());
+    match format {
+        PeakFormat::Bed | PeakFormat::NarrowPeak => bed_to_peaks(&[path]),
+        PeakFormat::Saf => saf_to_peaks(&read_to_string(path)?, path),
+        PeakFormat::Json => from_json(read_to_string(path)?).map_err(|err| {
+            err.chain(format!("The JSON file \"{}\" contains invalid peaks.", path.display()))
+        }),
+        #[cfg(feature = "bigwig")]
+        PeakFormat::BigBed => big_bed_to_peaks(path),
+    }
+}
+
+/// Writes the peaks to a peak file in the specified format.
+/// Records are sorted by chromosome name and coordinates.
+///
+/// # Parameters
+///
+/// * `path` - the path of the output file
+/// * `peaks` - the peaks grouped by chromosome
+/// * `format` - the format of the output file
+/// * `chromosome_sizes` - the chromosome sizes stored in bigBed files; if not specified,
+///   the end of the last peak is used as chromosome size
+///
+/// # Errors
+///
+/// Returns an error if the output file cannot be written.
+#[cfg_attr(not(feature = "bigwig"), allow(unused_variables))]
+pub fn write_peaks<T: AsRef<Path>>(
+    path: T,
+    peaks: &HashMap<Chromosome, Vec<PeakData>>,
+    format: PeakFormat,
+    chromosome_sizes: Option<&BTreeMap<Chromosome, u64>>,
+) -> Result<(), ApplicationError> {
+    let path = path.as_ref();
+    match format {
+        PeakFormat::Bed => write_peaks_to_bed(path, peaks, 6),
+        PeakFormat::NarrowPeak => write_bed_file(path, peaks, |peak, chromosome| {
+            narrow_peak_record_line(peak, chromosome)
+        }),
+        PeakFormat::Saf => write_text_file(path, &peaks_to_saf(peaks), format),
+        PeakFormat::Json => {
+            let mut json = to_json(peaks)?;
+            json.push('\n');
+            write_text_file(path, &json, format)
+        },
+        #[cfg(feature = "bigwig")]
+        PeakFormat::BigBed => write_peaks_to_big_bed(path, peaks, chromosome_sizes),
+    }
+}
+
+/// Creates a narrowPeak record line storing the summit as offset from the start coordinate,
+/// so the summit is retained when the file is parsed again.
+///
+/// # Parameters
+///
+/// * `peak` - the peak data
+/// * `chromosome` - the chromosome the peak belongs to
+fn narrow_peak_record_line(peak: &PeakData, chromosome: &Chromosome) -> String {
+    OutputFormat::NarrowPeak.record_line(peak, chromosome.name())
+}
+
+/// Reads the content of a text file.
+///
+/// # Parameters
+///
+/// * `path` - the path of the file
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read.
+fn read_to_string(path: &Path) -> Result<String, ApplicationError> {
+    retry(&format!("Reading {}", path.display()), || std::fs::read_to_string(path)).map_err(|err| {
+        ApplicationError::from(err)
+            .chain(format!("The peak file \"{}\" could not be read.", path.display()))
+    })
+}
+
+/// Writes the content of a text file.
+///
+/// # Parameters
+///
+/// * `path` - the path of the file
+/// * `content` - the content of the file
+/// * `format` - the format of the file
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be written.
+fn write_text_file(path: &Path, content: &str, format: PeakFormat) -> Result<(), ApplicationError> {
+    log::info!(target: LOG_TARGET, "Writing {} file {}.", format, path.display());
+    File::create(path)
+        .and_then(|mut file| file.write_all(content.as_bytes()))
+        .map_err(|err| {
+            ApplicationError::from(err).chain(format!(
+                "The {} file \"{}\" could not be written.",
+                format,
+                path.display()
+            ))
+        })
+}
+
+/// Returns the peaks sorted by chromosome name and coordinates.
+///
+/// # Parameters
+///
+/// * `peaks` - the peaks grouped by chromosome
+fn sorted_peaks(
+    peaks: &HashMap<Chromosome, Vec<PeakData>>,
+) -> BTreeMap<Chromosome, Vec<&PeakData>> {
+    peaks
+        .iter()
+        .map(|(chromosome, chromosome_peaks)| {
+            let mut chromosome_peaks: Vec<&PeakData> = chromosome_peaks.iter().collect();
+            chromosome_peaks
+                .sort_by_key(|peak| (peak.start(), peak.end(), peak.summit(), peak.id()));
+            (*chromosome, chromosome_peaks)
+        })
+        .collect()
+}
+
+/// Parses peaks from the content of a SAF file. The header line is optional.
+///
+/// # Parameters
+///
+/// * `content` - the content of the SAF file
+/// * `path` - the path of the SAF file
+///
+/// # Errors
+///
+/// Returns an error if a line is not a valid SAF record.
+fn saf_to_peaks(
+    content: &str,
+    path: &Path,
+) -> Result<HashMap<Chromosome, Vec<PeakData>>, ApplicationError> {
+    let mut peaks: HashMap<Chromosome, Vec<PeakData>> = HashMap::new();
+    let mut peak_id = 0;
+    for (line_index, line) in content.lines().enumerate() {
+        let line_context = ErrorContext::new()
+            .with_path(path.display())
+            .with_line(line_index as u64 + 1);
+        let fields: Vec<&str> = line.split('\t').collect();
+        if line.trim().is_empty() || line.starts_with('#') || line.starts_with("GeneID\t") {
+            continue;
+        }
+        let coordinates = (fields.len() >= 4)
+            .then(|| Some((fields[2].parse::<u64>().ok()?, fields[3].parse::<u64>().ok()?)))
+            .flatten()
+            .filter(|(start, end)| *start > 0 && start <= end);
+        let Some((start, end)) = coordinates else {
+            return Err(ApplicationError::new(
+                ApplicationErrorType::ParseError,
+                format!("The line \"{}\" is not a valid SAF record.", line),
+            )
+            .with_context(line_context)
+            .with_hint(
+                "SAF files require the tab separated columns GeneID, Chr, Start, End and Strand \
+                with 1-based inclusive coordinates.",
+            ));
+        };
+        // Converts the 1-based inclusive coordinates to the coordinates used by BED files.
+        let peak = PeakData::new(peak_id, start - 1, end, (start - 1).midpoint(end))?;
+        peaks
+            .entry(Chromosome::from(fields[1]))
+            .or_default()
+            .push(peak);
+        peak_id += 1;
+    }
+    Ok(peaks)
+}
+
+/// Creates the content of a SAF file containing all peaks.
+///
+/// # Parameters
+///
+/// * `peaks` - the peaks grouped by chromosome
+fn peaks_to_saf(peaks: &HashMap<Chromosome, Vec<PeakData>>) -> String {
+    let mut saf = format!("{}\n", SAF_HEADER);
+    for (chromosome, chromosome_peaks) in sorted_peaks(peaks) {
+        for peak in chromosome_peaks {
+            saf.push_str(&format!(
+                "consensus_{}\t{}\t{}\t{}\t.\n",
+                peak.id(),
+                chromosome,
+                peak.start() + 1,
+                peak.end()
+            ));
+        }
+    }
+    saf
+}
+
+/// Parses the peaks of a bigBed file. Summits are extracted from the narrowPeak peak field
+/// if present.
+///
+/// # Parameters
+///
+/// * `path` - the path of the bigBed file
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or contains invalid peaks.
+#[cfg(feature = "bigwig")]
+fn big_bed_to_peaks(path: &Path) -> Result<HashMap<Chromosome, Vec<PeakData>>, ApplicationError> {
+    let read_error = |err| {
+        ApplicationError::from_source(ApplicationErrorType::IOError, err)
+            .chain(format!("The bigBed file \"{}\" could not be read.", path.display()))
+    };
+    let mut reader = bigtools::BigBedRead::open_file(path).map_err(|err| {
+        ApplicationError::from_source(ApplicationErrorType::IOError, err)
+            .chain(format!("The bigBed file \"{}\" could not be opened.", path.display()))
+    })?;
+    let chromosomes: Vec<bigtools::ChromInfo> = reader.chroms().to_vec();
+    // Converts the entries to BED records, so they are validated by the BED parser.
+    let mut records = String::new();
+    for chromosome in chromosomes {
+        for entry in reader
+            .get_interval(&chromosome.name, 0, chromosome.length)
+            .map_err(read_error)?
+        {
+            let entry = entry.map_err(read_error)?;
+            records.push_str(&format!("{}\t{}\t{}", chromosome.name, entry.start, entry.end));
+            if !entry.rest.is_empty() {
+                records.push('\t');
+                records.push_str(&entry.rest);
+            }
+            records.push('\n');
+        }
+    }
+    crate::input::bed_buffers_to_peaks(&[records]).map_err(|err| {
+        err.chain(format!("The bigBed file \"{}\" contains invalid peaks.", path.display()))
+    })
+}
+
+/// Writes the peaks to a bigBed file storing the narrowPeak fields.
+///
+/// # Parameters
+///
+/// * `path` - the path of the output file
+/// * `peaks` - the peaks grouped by chromosome
+/// * `chromosome_sizes` - the chromosome sizes; if not specified,
+///   the end of the last peak is used as chromosome size
+///
+/// # Errors
+///
+/// Returns an error if a chromosome size is missing or too small,
+/// or if the output file cannot be written.
+#[cfg(feature = "bigwig")]
+fn write_peaks_to_big_bed(
+    path: &Path,
+    peaks: &HashMap<Chromosome, Vec<PeakData>>,
+    chromosome_sizes: Option<&BTreeMap<Chromosome, u64>>,
+) -> Result<(), ApplicationError> {
+    log::info!(target: LOG_TARGET, "Writing bigBed file {}.", path.display());
+    let write_error = |err| {
+        ApplicationError::from_source(ApplicationErrorType::OutputOperationError, err)
+            .chain(format!("The bigBed file \"{}\" could not be written.", path.display()))
+    };
+    let coordinate_error = |chromosome: &Chromosome| {
+        ApplicationError::new(
+            ApplicationErrorType::ValidationError,
+            format!(
+                "The peaks on chromosome {} exceed the coordinate range of bigBed files.",
+                chromosome
+            ),
+        )
+    };
+    let sorted_peaks = sorted_peaks(peaks);
+    let mut sizes = HashMap::new();
+    let mut entries = Vec::new();
+    for (chromosome, chromosome_peaks) in &sorted_peaks {
+        let max_end = chromosome_peaks
+            .iter()
+            .map(|peak| peak.end())
+            .max()
+            .unwrap_or(0);
+        let size = match chromosome_sizes {
+            Some(chromosome_sizes) => {
+                let size = chromosome_sizes.get(chromosome).copied().ok_or_else(|| {
+                    ApplicationError::new(
+                        ApplicationErrorType::ValidationError,
+                        format!("The chromosome sizes do not contain chromosome {}.", chromosome),
+                    )
+                })?;
+                if size < max_end {
+                    return Err(ApplicationError::new(
+                        ApplicationErrorType::ValidationError,
+                        format!(
+                            "The peaks on chromosome {} end at position {} beyond the chromosome \
+                            size of {}.",
+                            chromosome, max_end, size
+                        ),
+                    ));
+                }
+                size
+            },
+            None => max_end,
+        };
+        sizes.insert(
+            chromosome.name().to_string(),
+            u32::try_from(size).map_err(|_| coordinate_error(chromosome))?,
+        );
+        for peak in chromosome_peaks {
+            let record = narrow_peak_record_line(peak, chromosome);
+            let rest = record
+                .trim_end_matches('\n')
+                .splitn(4, '\t')
+                .nth(3)
+                .unwrap_or_default();
+            entries.push((
+                chromosome.name(),
+                bigtools::BedEntry {
+                    start: u32::try_from(peak.start()).map_err(|_| coordinate_error(chromosome))?,
+                    end: u32::try_from(peak.end()).map_err(|_| coordinate_error(chromosome))?,
+                    rest: rest.to_string(),
+                },
+            ));
+        }
+    }
+    let mut writer = bigtools::BigBedWrite::create_file(path, sizes).map_err(write_error)?;
+    writer.autosql = Some(NARROW_PEAK_AUTOSQL.to_string());
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .map_err(write_error)?;
+    let data = bigtools::beddata::BedParserStreamingIterator::wrap_infallible_iter(
+        entries.into_iter(),
+        false,
+    );
+    writer
+        .write(data, runtime)
+        .map_err(|err| write_error(std::io::Error::other(err.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::test_output;
+
+    use super::*;
+
+    fn test_peaks() -> HashMap<Chromosome, Vec<PeakData>> {
+        HashMap::from([
+            (
+                Chromosome::from("chr1"),
+                vec![
+                    PeakData::new(1, 500u64, 700u64, 650u64).unwrap(),
+                    PeakData::new(0, 100u64, 200u64, 120u64).unwrap(),
+                ],
+            ),
+            (Chromosome::from("chr2"), vec![PeakData::new(2, 50u64, 80u64, 60u64).unwrap()]),
+        ])
+    }
+
+    /// Returns the coordinates of the peaks sorted by chromosome and start.
+    fn coordinates(peaks: &HashMap<Chromosome, Vec<PeakData>>) -> Vec<(String, u64, u64, u64)> {
+        sorted_peaks(peaks)
+            .into_iter()
+            .flat_map(|(chromosome, chromosome_peaks)| {
+                chromosome_peaks.into_iter().map(move |peak| {
+                    (chromosome.to_string(), peak.start(), peak.end(), peak.summit())
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_peak_format_from_path() {
+        assert_eq!(PeakFormat::from_path("peaks.bed"), Some(PeakFormat::Bed));
+        assert_eq!(PeakFormat::from_path("a/peaks.narrowPeak"), Some(PeakFormat::NarrowPeak));
+        assert_eq!(PeakFormat::from_path("peaks.SAF"), Some(PeakFormat::Saf));
+        assert_eq!(PeakFormat::from_path("peaks.json"), Some(PeakFormat::Json));
+        assert_eq!(PeakFormat::from_path("peaks.txt"), None);
+        assert_eq!(PeakFormat::from_path("peaks"), None);
+    }
+
+    #[test]
+    fn test_saf_round_trip() {
+        let saf = peaks_to_saf(&test_peaks());
+        assert_eq!(
+            saf,
+            "GeneID\tChr\tStart\tEnd\tStrand\nconsensus_0\tchr1\t101\t200\t.\n\
+            consensus_1\tchr1\t501\t700\t.\nconsensus_2\tchr2\t51\t80\t.\n"
+        );
+        let peaks = saf_to_peaks(&saf, Path::new("test.saf")).unwrap();
+        assert_eq!(
+            coordinates(&peaks),
+            vec![
+                ("chr1".to_string(), 100, 200, 150),
+                ("chr1".to_string(), 500, 700, 600),
+                ("chr2".to_string(), 50, 80, 65),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_saf_to_peaks_invalid() {
+        let error = saf_to_peaks(
+            "GeneID\tChr\tStart\tEnd\tStrand\np\tchr1\t0\t10\t+\n",
+            Path::new("t.saf"),
+        )
+        .unwrap_err();
+        assert_eq!(error.error_type(), ApplicationErrorType::ParseError);
+        assert_eq!(error.context().unwrap().line(), Some(2));
+        assert!(saf_to_peaks("p\tchr1\t20\t10\t+\n", Path::new("t.saf")).is_err());
+        assert!(saf_to_peaks("p\tchr1\t10\n", Path::new("t.saf")).is_err());
+    }
+
+    #[test]
+    fn test_write_and_read_peaks() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let formats = [
+            (PeakFormat::Bed, "test_write_and_read_peaks.bed"),
+            (PeakFormat::NarrowPeak, "test_write_and_read_peaks.narrowPeak"),
+            (PeakFormat::Saf, "test_write_and_read_peaks.saf"),
+            (PeakFormat::Json, "test_write_and_read_peaks.json"),
+        ];
+        for (format, file_name) in formats {
+            let path = test_output().join(file_name);
+            write_peaks(&path, &test_peaks(), format, None).unwrap();
+            let peaks = read_peaks(&path, format).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            let expected_summits: Vec<u64> = match format {
+                PeakFormat::NarrowPeak | PeakFormat::Json => vec![120, 650, 60],
+                _ => vec![150, 600, 65],
+            };
+            let summits: Vec<u64> = coordinates(&peaks)
+                .into_iter()
+                .map(|(_, _, _, summit)| summit)
+                .collect();
+            assert_eq!(summits, expected_summits, "{}", format);
+            assert_eq!(
+                coordinates(&peaks)
+                    .into_iter()
+                    .map(|(chromosome, start, end, _)| (chromosome, start, end))
+                    .collect::<Vec<_>>(),
+                vec![
+                    ("chr1".to_string(), 100, 200),
+                    ("chr1".to_string(), 500, 700),
+                    ("chr2".to_string(), 50, 80),
+                ],
+                "{}",
+                format
+            );
+        }
+    }
+
+    #[cfg(feature = "bigwig")]
+    #[test]
+    fn test_big_bed_round_trip() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let path = test_output().join("test_big_bed_round_trip.bb");
+        let sizes = BTreeMap::from([
+            (Chromosome::from("chr1"), 1000),
+            (Chromosome::from("chr2"), 100),
+        ]);
+        write_peaks(&path, &test_peaks(), PeakFormat::BigBed, Some(&sizes)).unwrap();
+        let peaks = read_peaks(&path, PeakFormat::BigBed).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            coordinates(&peaks),
+            vec![
+                ("chr1".to_string(), 100, 200, 120),
+                ("chr1".to_string(), 500, 700, 650),
+                ("chr2".to_string(), 50, 80, 60),
+            ]
+        );
+        let too_small = BTreeMap::from([
+            (Chromosome::from("chr1"), 600),
+            (Chromosome::from("chr2"), 100),
+        ]);
+        let error =
+            write_peaks(&path, &test_peaks(), PeakFormat::BigBed, Some(&too_small)).unwrap_err();
+        assert_eq!(error.error_type(), ApplicationErrorType::ValidationError);
+        assert!(!path.exists());
+    }
+}
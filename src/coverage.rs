@@ -0,0 +1,299 @@
+//! This module reports the fraction of the genome covered by peaks, so the consensus peaks
+//! can be sanity-checked against the expected fraction of open chromatin.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+    path::Path,
+};
+
+use getset::{CopyGetters, Getters};
+
+use crate::{
+    chromosome::Chromosome,
+    error::{ApplicationError, ApplicationErrorType, ErrorContext},
+    peaks::PeakData,
+    retry::retry,
+    stats::covered_bases,
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "coverage";
+
+/// Parses a chromosome sizes file containing the tab or space separated columns
+/// chromosome name and chromosome size, such as the files written by `fetchChromSizes`
+/// or the first two columns of a FASTA index.
+/// Blank lines and lines starting with `#` are skipped.
+///
+/// # Parameters
+///
+/// * `path` - the path of the chromosome sizes file
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read, a line is invalid or a chromosome is listed
+/// more than once.
+pub fn chromosome_sizes_from_file<T: AsRef<Path>>(
+    path: T,
+) -> Result<BTreeMap<Chromosome, u64>, ApplicationError> {
+    let path = path.as_ref();
+    log::info!(target: LOG_TARGET, "Parsing chromosome sizes from {}.", path.display());
+    let description = format!("Reading {}", path.display());
+    let content = retry(&description, || std::fs::read_to_string(path)).map_err(|err| {
+        ApplicationError::from(err)
+            .chain(format!("The chromosome sizes file \"{}\" could not be read.", path.display()))
+    })?;
+    let mut sizes = BTreeMap::new();
+    for (line_index, line) in content.lines().enumerate() {
+        let line_context = ErrorContext::new()
+            .with_path(path.display())
+            .with_line(line_index as u64 + 1);
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() || fields[0].starts_with('#') {
+            continue;
+        }
+        let size = fields
+            .get(1)
+            .and_then(|size| size.parse::<u64>().ok())
+            .ok_or_else(|| {
+                ApplicationError::new(
+                    ApplicationErrorType::ParseError,
+                    format!("The line \"{}\" does not contain a valid chromosome size.", line),
+                )
+                .with_context(line_context.clone())
+                .with_hint(
+                    "Chromosome sizes files require the tab or space separated columns \
+                    chromosome name and size.",
+                )
+            })?;
+        if sizes.insert(Chromosome::from(fields[0]), size).is_some() {
+            return Err(ApplicationError::new(
+                ApplicationErrorType::ValidationError,
+                format!("The chromosome \"{}\" is listed more than once.", fields[0]),
+            )
+            .with_context(line_context));
+        }
+    }
+    Ok(sizes)
+}
+
+/// The number of bases of a chromosome or genome covered by peaks.
+#[derive(CopyGetters, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[getset(get_copy = "pub")]
+pub struct CoveredBases {
+    /// The size in bases.
+    size: u64,
+    /// The number of bases covered by at least one raw peak.
+    raw_bases: u64,
+    /// The number of bases covered by at least one consensus peak.
+    consensus_bases: u64,
+}
+
+impl CoveredBases {
+    /// Returns the fraction of bases covered by raw peaks or `0.0` if the size is `0`.
+    pub fn raw_fraction(&self) -> f64 {
+        fraction(self.raw_bases, self.size)
+    }
+
+    /// Returns the fraction of bases covered by consensus peaks or `0.0` if the size is `0`.
+    pub fn consensus_fraction(&self) -> f64 {
+        fraction(self.consensus_bases, self.size)
+    }
+}
+
+/// Returns the fraction of the part or `0.0` if the total is `0`.
+///
+/// # Parameters
+///
+/// * `part` - the part of the total
+/// * `total` - the total
+fn fraction(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64
+    }
+}
+
+/// The coverage of the genome by raw and consensus peaks.
+#[derive(CopyGetters, Getters, Clone, PartialEq, Eq, Debug)]
+pub struct GenomeCoverage {
+    /// The coverage per chromosome.
+    #[getset(get = "pub")]
+    chromosomes: BTreeMap<Chromosome, CoveredBases>,
+    /// The coverage of the whole genome.
+    #[getset(get_copy = "pub")]
+    total: CoveredBases,
+}
+
+impl GenomeCoverage {
+    /// Calculates the coverage of all chromosomes with a known size.
+    /// Peaks located on chromosomes of unknown size are ignored with a warning.
+    ///
+    /// # Parameters
+    ///
+    /// * `chromosome_sizes` - the sizes of all chromosomes of the genome
+    /// * `raw_peaks` - the raw peaks grouped by chromosome
+    /// * `consensus_peaks` - the consensus peaks grouped by chromosome
+    pub fn new(
+        chromosome_sizes: &BTreeMap<Chromosome, u64>,
+        raw_peaks: &HashMap<Chromosome, Vec<PeakData>>,
+        consensus_peaks: &HashMap<Chromosome, Vec<PeakData>>,
+    ) -> Self {
+        let mut unknown_chromosomes: Vec<Chromosome> = raw_peaks
+            .keys()
+            .chain(consensus_peaks.keys())
+            .filter(|chromosome| !chromosome_sizes.contains_key(chromosome))
+            .copied()
+            .collect();
+        unknown_chromosomes.sort_unstable();
+        unknown_chromosomes.dedup();
+        if !unknown_chromosomes.is_empty() {
+            log::warn!(
+                target: LOG_TARGET,
+                "The peaks on the chromosomes {:?} were ignored for the genome coverage, \
+                as the chromosome sizes are unknown.",
+                unknown_chromosomes
+            );
+        }
+        let covered = |peaks: &HashMap<Chromosome, Vec<PeakData>>, chromosome: &Chromosome| {
+            peaks
+                .get(chromosome)
+                .map(|peaks| covered_bases(peaks))
+                .unwrap_or_default()
+        };
+        let chromosomes: BTreeMap<Chromosome, CoveredBases> = chromosome_sizes
+            .iter()
+            .map(|(chromosome, size)| {
+                (
+                    *chromosome,
+                    CoveredBases {
+                        size: *size,
+                        raw_bases: covered(raw_peaks, chromosome),
+                        consensus_bases: covered(consensus_peaks, chromosome),
+                    },
+                )
+            })
+            .collect();
+        let total = chromosomes
+            .values()
+            .fold(CoveredBases::default(), |total, coverage| CoveredBases {
+                size: total.size + coverage.size,
+                raw_bases: total.raw_bases + coverage.raw_bases,
+                consensus_bases: total.consensus_bases + coverage.consensus_bases,
+            });
+        Self { chromosomes, total }
+    }
+}
+
+/// Writes the genome coverage as tab separated table.
+/// The coverage of the whole genome is reported as chromosome `*`.
+///
+/// # Parameters
+///
+/// * `writer` - the writer to write the table to
+/// * `coverage` - the genome coverage
+///
+/// # Errors
+///
+/// Returns an error if the table cannot be written.
+pub fn write_coverage_table<W: Write>(
+    mut writer: W,
+    coverage: &GenomeCoverage,
+) -> Result<(), ApplicationError> {
+    let mut table = String::from(
+        "chromosome\tsize\traw_bases\traw_fraction\tconsensus_bases\tconsensus_fraction\n",
+    );
+    let rows = coverage
+        .chromosomes()
+        .iter()
+        .map(|(chromosome, covered)| (chromosome.name(), covered))
+        .chain(std::iter::once(("*", &coverage.total)));
+    for (chromosome, covered) in rows {
+        table.push_str(&format!(
+            "{}\t{}\t{}\t{:.6}\t{}\t{:.6}\n",
+            chromosome,
+            covered.size(),
+            covered.raw_bases(),
+            covered.raw_fraction(),
+            covered.consensus_bases(),
+            covered.consensus_fraction()
+        ));
+    }
+    writer.write_all(table.as_bytes()).map_err(|err| {
+        ApplicationError::from(err).chain("The genome coverage could not be written.")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::{test_output, test_resources};
+
+    use super::*;
+
+    #[test]
+    fn test_chromosome_sizes_from_file() {
+        let sizes =
+            chromosome_sizes_from_file(test_resources().join("input_test_chromosome_sizes.tsv"))
+                .unwrap();
+        assert_eq!(
+            sizes,
+            BTreeMap::from([
+                (Chromosome::from("chr1"), 2000),
+                (Chromosome::from("chr2"), 1000)
+            ])
+        );
+
+        std::fs::create_dir_all(test_output()).unwrap();
+        let invalid_path = test_output().join("test_chromosome_sizes_from_file_invalid.tsv");
+        std::fs::write(&invalid_path, "chr1\t2000\nchr2\tlarge\n").unwrap();
+        let error = chromosome_sizes_from_file(&invalid_path).unwrap_err();
+        assert_eq!(error.error_type(), ApplicationErrorType::ParseError);
+        assert_eq!(error.context().and_then(|context| context.line()), Some(2));
+        std::fs::write(&invalid_path, "chr1\t2000\nchr1\t1000\n").unwrap();
+        let error = chromosome_sizes_from_file(&invalid_path).unwrap_err();
+        assert_eq!(error.error_type(), ApplicationErrorType::ValidationError);
+        std::fs::remove_file(&invalid_path).unwrap();
+    }
+
+    #[test]
+    fn test_genome_coverage() {
+        let chr1 = Chromosome::from("chr1");
+        let chr2 = Chromosome::from("chr2");
+        let sizes = BTreeMap::from([(chr1, 1000), (chr2, 500)]);
+        let raw_peaks = HashMap::from([
+            (
+                chr1,
+                vec![
+                    PeakData::new(0, 100u64, 199u64, 150u64).unwrap(),
+                    PeakData::new(1, 150u64, 249u64, 200u64).unwrap(),
+                ],
+            ),
+            (chr2, vec![PeakData::new(2, 0u64, 99u64, 50u64).unwrap()]),
+            (
+                Chromosome::from("chrUn_test_genome_coverage"),
+                vec![PeakData::new(3, 0u64, 99u64, 50u64).unwrap()],
+            ),
+        ]);
+        let consensus_peaks =
+            HashMap::from([(chr1, vec![PeakData::new(0, 125u64, 224u64, 175u64).unwrap()])]);
+        let coverage = GenomeCoverage::new(&sizes, &raw_peaks, &consensus_peaks);
+        assert_eq!(coverage.chromosomes()[&chr1].raw_bases(), 150);
+        assert_eq!(coverage.chromosomes()[&chr1].consensus_bases(), 100);
+        assert_eq!(coverage.chromosomes()[&chr2].consensus_bases(), 0);
+        assert_eq!(coverage.total().size(), 1500);
+        assert_eq!(coverage.total().raw_bases(), 250);
+        assert_eq!(coverage.total().consensus_bases(), 100);
+        assert_eq!(CoveredBases::default().raw_fraction(), 0.0);
+
+        let mut table = Vec::new();
+        write_coverage_table(&mut table, &coverage).unwrap();
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            "chromosome\tsize\traw_bases\traw_fraction\tconsensus_bases\tconsensus_fraction\n\
+            chr1\t1000\t150\t0.150000\t100\t0.100000\n\
+            chr2\t500\t100\t0.200000\t0\t0.000000\n\
+            *\t1500\t250\t0.166667\t100\t0.066667\n"
+        );
+    }
+}
@@ -0,0 +1,392 @@
+//! This module counts peaks per genomic window, either to correlate the peak densities between
+//! samples, which is a coarse replicate similarity measure not requiring any signal tracks,
+//! or to export a density track for genome-wide visualisation, for example with circos or
+//! karyoploteR.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io::Write,
+};
+
+use getset::{CopyGetters, Getters};
+
+use crate::{chromosome::Chromosome, error::ApplicationError, peak_set::PeakSet};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "density";
+
+/// The default size of the genomic windows in bases.
+pub const DEFAULT_DENSITY_WINDOW: u64 = 1_000_000;
+
+/// The correlation of the peak densities of two samples.
+#[derive(CopyGetters, Clone, Copy, PartialEq, Debug)]
+#[getset(get_copy = "pub")]
+pub struct DensityCorrelation {
+    /// The index of the first sample.
+    first: usize,
+    /// The index of the second sample.
+    second: usize,
+    /// The number of windows containing a peak summit of any sample.
+    windows: usize,
+    /// The Pearson correlation of the peak counts per window
+    /// or `None` if the counts of either sample do not vary.
+    pearson: Option<f64>,
+}
+
+/// The pairwise correlations of the peak densities of samples per chromosome and genome-wide.
+/// Peaks are assigned to the window containing their summit and only windows containing
+/// a peak of any sample are considered, as the chromosome sizes are unknown.
+#[derive(CopyGetters, Getters, Clone, PartialEq, Debug)]
+pub struct DensityCorrelations {
+    /// The size of the genomic windows in bases.
+    #[getset(get_copy = "pub")]
+    window_size: u64,
+    /// The correlations of all sample pairs per chromosome.
+    #[getset(get = "pub")]
+    chromosomes: BTreeMap<Chromosome, Vec<DensityCorrelation>>,
+    /// The correlations of all sample pairs across the whole genome.
+    #[getset(get = "pub")]
+    total: Vec<DensityCorrelation>,
+}
+
+impl DensityCorrelations {
+    /// Correlates the number of peaks per window between all pairs of samples.
+    ///
+    /// # Parameters
+    ///
+    /// * `samples` - the peaks of each sample
+    /// * `window_size` - the size of the genomic windows in bases
+    ///
+    /// # Panics
+    ///
+    /// Panics if the window size is `0`.
+    pub fn new(samples: &[PeakSet], window_size: u64) -> Self {
+        assert!(window_size > 0, "The window size must be positive.");
+        let counts: Vec<HashMap<(Chromosome, u64), usize>> = samples
+            .iter()
+            .map(|sample| {
+                let mut sample_counts = HashMap::new();
+                for (chromosome, peak) in sample.iter() {
+                    *sample_counts
+                        .entry((chromosome, peak.summit() / window_size))
+                        .or_default() += 1;
+                }
+                sample_counts
+            })
+            .collect();
+        let mut windows: BTreeMap<Chromosome, BTreeSet<u64>> = BTreeMap::new();
+        for (chromosome, window) in counts.iter().flat_map(HashMap::keys) {
+            windows.entry(*chromosome).or_default().insert(*window);
+        }
+        let correlate = |windows: &[(Chromosome, u64)]| {
+            let densities: Vec<Vec<f64>> = counts
+                .iter()
+                .map(|sample_counts| {
+                    windows
+                        .iter()
+                        .map(|window| sample_counts.get(window).copied().unwrap_or_default() as f64)
+                        .collect()
+                })
+                .collect();
+            let mut correlations = Vec::new();
+            for first in 0..samples.len() {
+                for second in first + 1..samples.len() {
+                    correlations.push(DensityCorrelation {
+                        first,
+                        second,
+                        windows: windows.len(),
+                        pearson: pearson(&densities[first], &densities[second]),
+                    });
+                }
+            }
+            correlations
+        };
+        let chromosome_windows = |chromosome: Chromosome, windows: &BTreeSet<u64>| {
+            windows
+                .iter()
+                .map(|window| (chromosome, *window))
+                .collect::<Vec<(Chromosome, u64)>>()
+        };
+        let chromosomes = windows
+            .iter()
+            .map(|(chromosome, windows)| {
+                (*chromosome, correlate(&chromosome_windows(*chromosome, windows)))
+            })
+            .collect();
+        let all_windows: Vec<(Chromosome, u64)> = windows
+            .iter()
+            .flat_map(|(chromosome, windows)| chromosome_windows(*chromosome, windows))
+            .collect();
+        log::debug!(
+            target: LOG_TARGET,
+            "Correlating the peak densities of {} samples across {} windows.",
+            samples.len(),
+            all_windows.len()
+        );
+        Self {
+            window_size,
+            chromosomes,
+            total: correlate(&all_windows),
+        }
+    }
+}
+
+/// Returns the Pearson correlation of the values
+/// or `None` if the values of either series do not vary.
+///
+/// # Parameters
+///
+/// * `x` - the first series of values
+/// * `y` - the second series of values of the same length
+fn pearson(x: &[f64], y: &[f64]) -> Option<f64> {
+    if x.is_empty() {
+        return None;
+    }
+    let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+    let (mean_x, mean_y) = (mean(x), mean(y));
+    let mut covariance = 0.0;
+    let mut variance_x = 0.0;
+    let mut variance_y = 0.0;
+    for (value_x, value_y) in x.iter().zip(y) {
+        covariance += (value_x - mean_x) * (value_y - mean_y);
+        variance_x += (value_x - mean_x).powi(2);
+        variance_y += (value_y - mean_y).powi(2);
+    }
+    (variance_x > 0.0 && variance_y > 0.0)
+        .then(|| covariance / (variance_x.sqrt() * variance_y.sqrt()))
+}
+
+/// The number of peaks in a genomic window.
+#[derive(CopyGetters, Clone, Copy, PartialEq, Eq, Debug)]
+#[getset(get_copy = "pub")]
+pub struct DensityWindow {
+    /// The 0-based start of the window.
+    start: u64,
+    /// The exclusive end of the window.
+    end: u64,
+    /// The number of peaks with a summit within the window.
+    peaks: usize,
+}
+
+/// Counts the peaks per genomic window of each chromosome, assigning each peak to the window
+/// containing its summit. The windows of a chromosome with known size cover the whole
+/// chromosome and the last window ends at the chromosome end. Otherwise, the windows end with
+/// the last window containing a peak. Chromosomes with known size but without peaks are
+/// included with empty windows.
+///
+/// # Parameters
+///
+/// * `peaks` - the peaks
+/// * `window_size` - the size of the genomic windows in bases
+/// * `chromosome_sizes` - the known chromosome sizes
+///
+/// # Panics
+///
+/// Panics if the window size is `0`.
+pub fn peak_density_track(
+    peaks: &PeakSet,
+    window_size: u64,
+    chromosome_sizes: &BTreeMap<Chromosome, u64>,
+) -> BTreeMap<Chromosome, Vec<DensityWindow>> {
+    assert!(window_size > 0, "The window size must be positive.");
+    let mut counts: BTreeMap<Chromosome, Vec<usize>> = chromosome_sizes
+        .iter()
+        .map(|(chromosome, size)| (*chromosome, vec![0; size.div_ceil(window_size) as usize]))
+        .collect();
+    for (chromosome, peak) in peaks.iter() {
+        let window = (peak.summit() / window_size) as usize;
+        let chromosome_counts = counts.entry(chromosome).or_default();
+        if chromosome_counts.len() <= window {
+            chromosome_counts.resize(window + 1, 0);
+        }
+        chromosome_counts[window] += 1;
+    }
+    log::debug!(
+        target: LOG_TARGET,
+        "Counted {} peaks in {} windows of {} bases.",
+        peaks.len(),
+        counts.values().map(Vec::len).sum::<usize>(),
+        window_size
+    );
+    counts
+        .into_iter()
+        .map(|(chromosome, chromosome_counts)| {
+            let chromosome_end = chromosome_sizes.get(&chromosome).copied();
+            let windows = chromosome_counts
+                .into_iter()
+                .enumerate()
+                .map(|(index, peaks)| {
+                    let start = index as u64 * window_size;
+                    let end = start + window_size;
+                    DensityWindow {
+                        start,
+                        end: chromosome_end
+                            .map_or(end, |chromosome_end| end.min(chromosome_end).max(start + 1)),
+                        peaks,
+                    }
+                })
+                .collect();
+            (chromosome, windows)
+        })
+        .collect()
+}
+
+/// Writes the peak density track as tab separated BED-like table without header with the
+/// columns chromosome, window start, window end and number of peaks, which can be used as
+/// circos histogram or karyoploteR track.
+///
+/// # Parameters
+///
+/// * `writer` - the writer to write the track to
+/// * `track` - the peak density windows per chromosome
+///
+/// # Errors
+///
+/// Returns an error if the track cannot be written.
+pub fn write_density_track<W: Write>(
+    mut writer: W,
+    track: &BTreeMap<Chromosome, Vec<DensityWindow>>,
+) -> Result<(), ApplicationError> {
+    let mut table = String::new();
+    for (chromosome, windows) in track {
+        for window in windows {
+            table.push_str(&format!(
+                "{}\t{}\t{}\t{}\n",
+                chromosome,
+                window.start(),
+                window.end(),
+                window.peaks()
+            ));
+        }
+    }
+    writer.write_all(table.as_bytes()).map_err(|err| {
+        ApplicationError::from(err).chain("The peak density track could not be written.")
+    })
+}
+
+/// Writes the pairwise peak density correlations as tab separated table.
+/// The genome-wide correlations are reported as chromosome `*` and undefined correlations
+/// as `NA`.
+///
+/// # Parameters
+///
+/// * `writer` - the writer to write the table to
+/// * `sample_names` - the names of the samples in the order used to create the correlations
+/// * `correlations` - the peak density correlations
+///
+/// # Errors
+///
+/// Returns an error if the table cannot be written.
+pub fn write_density_correlations<W: Write, S: AsRef<str>>(
+    mut writer: W,
+    sample_names: &[S],
+    correlations: &DensityCorrelations,
+) -> Result<(), ApplicationError> {
+    let mut table = String::from("chromosome\tfirst_sample\tsecond_sample\twindows\tpearson\n");
+    let rows = correlations
+        .chromosomes()
+        .iter()
+        .map(|(chromosome, correlations)| (chromosome.name(), correlations))
+        .chain(std::iter::once(("*", correlations.total())));
+    for (chromosome, pairs) in rows {
+        for pair in pairs {
+            table.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                chromosome,
+                sample_names[pair.first()].as_ref(),
+                sample_names[pair.second()].as_ref(),
+                pair.windows(),
+                pair.pearson()
+                    .map(|pearson| format!("{:.6}", pearson))
+                    .unwrap_or_else(|| "NA".to_string())
+            ));
+        }
+    }
+    writer.write_all(table.as_bytes()).map_err(|err| {
+        ApplicationError::from(err).chain("The peak density correlations could not be written.")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::peaks::PeakData;
+
+    use super::*;
+
+    #[test]
+    fn test_pearson() {
+        assert!((pearson(&[1.0, 2.0, 3.0], &[2.0, 4.0, 6.0]).unwrap() - 1.0).abs() < 1e-9);
+        assert!((pearson(&[1.0, 2.0, 3.0], &[3.0, 2.0, 1.0]).unwrap() + 1.0).abs() < 1e-9);
+        assert_eq!(pearson(&[1.0, 2.0], &[1.0, 1.0]), None);
+        assert_eq!(pearson(&[], &[]), None);
+    }
+
+    #[test]
+    fn test_density_correlations() {
+        let chr1 = Chromosome::from("chr1");
+        let chr2 = Chromosome::from("chr2");
+        let sample = |summits: &[(Chromosome, u64)]| {
+            let mut peaks = PeakSet::new();
+            for (id, (chromosome, summit)) in summits.iter().enumerate() {
+                peaks
+                    .insert(*chromosome, PeakData::new(id, *summit, summit + 10, *summit).unwrap());
+            }
+            peaks
+        };
+        let samples = [
+            sample(&[(chr1, 10), (chr1, 20), (chr1, 150), (chr2, 50)]),
+            sample(&[
+                (chr1, 30),
+                (chr1, 40),
+                (chr1, 60),
+                (chr1, 120),
+                (chr2, 70),
+                (chr2, 80),
+            ]),
+        ];
+        let correlations = DensityCorrelations::new(&samples, 100);
+        assert_eq!(correlations.window_size(), 100);
+        let chr1_correlation = correlations.chromosomes()[&chr1][0];
+        assert_eq!(chr1_correlation.windows(), 2);
+        assert!((chr1_correlation.pearson().unwrap() - 1.0).abs() < 1e-9);
+        assert_eq!(correlations.chromosomes()[&chr2][0].pearson(), None);
+
+        let mut table = Vec::new();
+        write_density_correlations(&mut table, &["a", "b"], &correlations).unwrap();
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            "chromosome\tfirst_sample\tsecond_sample\twindows\tpearson\n\
+            chr1\ta\tb\t2\t1.000000\n\
+            chr2\ta\tb\t1\tNA\n\
+            *\ta\tb\t3\t0.866025\n"
+        );
+    }
+
+    #[test]
+    fn test_peak_density_track() {
+        let chr1 = Chromosome::from("chr1");
+        let chr2 = Chromosome::from("chr2");
+        let chr3 = Chromosome::from("chr3");
+        let mut peaks = PeakSet::new();
+        for (id, (chromosome, summit)) in [(chr1, 10u64), (chr1, 20), (chr1, 250), (chr2, 150)]
+            .iter()
+            .enumerate()
+        {
+            peaks.insert(*chromosome, PeakData::new(id, *summit, summit + 10, *summit).unwrap());
+        }
+        let chromosome_sizes = BTreeMap::from([(chr1, 320), (chr3, 100)]);
+        let track = peak_density_track(&peaks, 100, &chromosome_sizes);
+        let mut table = Vec::new();
+        write_density_track(&mut table, &track).unwrap();
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            "chr1\t0\t100\t2\n\
+            chr1\t100\t200\t0\n\
+            chr1\t200\t300\t1\n\
+            chr1\t300\t320\t0\n\
+            chr2\t0\t100\t0\n\
+            chr2\t100\t200\t1\n\
+            chr3\t0\t100\t0\n"
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! This module creates diagnostic bundles of fatal errors for remote debugging.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use gipfelkreuzer::error::{ApplicationError, ErrorReport};
+use serde::{Deserialize, Serialize};
+
+use crate::arguments::{Command, CommandLineArguments};
+
+/// The number of lines included per input file.
+const INPUT_HEADER_LINES: usize = 5;
+
+/// The features the application was built with.
+const FEATURES: [(&str, bool); 11] = [
+    ("cli", cfg!(feature = "cli")),
+    ("spill", cfg!(feature = "spill")),
+    ("wasm", cfg!(feature = "wasm")),
+    ("bam", cfg!(feature = "bam")),
+    ("bigwig", cfg!(feature = "bigwig")),
+    ("bio-types", cfg!(feature = "bio-types")),
+    ("bio", cfg!(feature = "bio")),
+    ("polars", cfg!(feature = "polars")),
+    ("arrow", cfg!(feature = "arrow")),
+    ("mimalloc", cfg!(feature = "mimalloc")),
+    ("jemalloc", cfg!(feature = "jemalloc")),
+];
+
+/// A diagnostic bundle describing a fatal error and the environment it occurred in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiagnosticReport {
+    /// The report of the error.
+    pub error: ErrorReport,
+    /// The version of the application.
+    pub version: String,
+    /// The enabled features of the application.
+    pub features: Vec<String>,
+    /// The operating system and architecture.
+    pub platform: String,
+    /// The raw command line arguments.
+    pub arguments: Vec<String>,
+    /// The effective configuration after parsing the command line arguments,
+    /// if they could be parsed.
+    pub configuration: Option<String>,
+    /// The first lines of each input file.
+    pub input_headers: Vec<InputHeader>,
+}
+
+/// The first lines of an input file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputHeader {
+    /// The path of the input file.
+    pub path: PathBuf,
+    /// The first lines of the input file.
+    pub lines: Vec<String>,
+    /// The reason the file could not be read, if any.
+    pub error: Option<String>,
+}
+
+impl DiagnosticReport {
+    /// Creates a diagnostic report of the specified error.
+    ///
+    /// # Parameters
+    ///
+    /// * `error` - the fatal error
+    /// * `command_line_arguments` - the parsed command line arguments, if they could be parsed
+    pub fn new(
+        error: &ApplicationError,
+        command_line_arguments: Option<&CommandLineArguments>,
+    ) -> Self {
+        let input_headers = command_line_arguments
+            .map(|cl_args| {
+                let input_files = match cl_args.command() {
+                    Some(Command::Consensus(_)) | None => {
+                        cl_args.consensus_arguments().input_files().clone()
+                    },
+                    Some(Command::Merge(merge_arguments)) => merge_arguments.input_files().clone(),
+                    Some(Command::Validate(validate_arguments)) => {
+                        validate_arguments.input_files().clone()
+                    },
+                    Some(Command::Serve(serve_arguments)) => serve_arguments.input_files().clone(),
+                    Some(Command::Stats(stats_arguments)) => stats_arguments.input_files().clone(),
+                    Some(Command::Compare(compare_arguments)) => compare_arguments.input_files(),
+                    Some(Command::Saturation(saturation_arguments)) => {
+                        saturation_arguments.input_files().clone()
+                    },
+                    Some(Command::Sweep(sweep_arguments)) => sweep_arguments
+                        .input_files()
+                        .iter()
+                        .chain(std::iter::once(sweep_arguments.gold_standard()))
+                        .cloned()
+                        .collect(),
+                    Some(Command::Liftover(liftover_arguments)) => liftover_arguments.input_files(),
+                    Some(Command::Convert(convert_arguments)) => convert_arguments.input_files(),
+                    Some(Command::Filter(filter_arguments)) => filter_arguments.input_files(),
+                    Some(Command::Sort(sort_arguments)) => sort_arguments.input_files(),
+                    Some(
+                        Command::Intersect(region_arguments) | Command::Subtract(region_arguments),
+                    ) => region_arguments.input_files(),
+                    Some(Command::Background(background_arguments)) => {
+                        background_arguments.input_files()
+                    },
+                    Some(Command::Diff(diff_arguments)) => diff_arguments.input_files(),
+                    Some(Command::Idr(idr_arguments)) => idr_arguments.input_files(),
+                };
+                input_files
+                    .iter()
+                    .map(|path| InputHeader::read(path))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            error: error.report(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            features: FEATURES
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(feature, _)| feature.to_string())
+                .collect(),
+            platform: format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH),
+            arguments: std::env::args().collect(),
+            configuration: command_line_arguments.map(|cl_args| format!("{:#?}", cl_args)),
+            input_headers,
+        }
+    }
+
+    /// Writes the report as JSON to the specified file or to stderr if the path is "-".
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - the report file
+    pub fn write<T: AsRef<Path>>(&self, path: T) -> Result<(), ApplicationError> {
+        let path = path.as_ref();
+        let report = serde_json::to_string_pretty(self)?;
+        if path == Path::new("-") {
+            writeln!(std::io::stderr(), "{}", report)?;
+            Ok(())
+        } else {
+            std::fs::write(path, report).map_err(|err| {
+                ApplicationError::from(err)
+                    .chain(format!("The error report \"{}\" could not be written.", path.display()))
+            })
+        }
+    }
+}
+
+impl InputHeader {
+    /// Reads the first lines of the specified input file.
+    /// Invalid UTF-8 is replaced, so malformed input files can be inspected.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - the input file
+    fn read(path: &Path) -> Self {
+        let lines = File::open(path).and_then(|file| {
+            let mut reader = BufReader::new(file);
+            let mut lines = Vec::new();
+            let mut buffer = Vec::new();
+            while lines.len() < INPUT_HEADER_LINES {
+                buffer.clear();
+                if reader.read_until(b'\n', &mut buffer)? == 0 {
+                    break;
+                }
+                lines.push(
+                    String::from_utf8_lossy(&buffer)
+                        .trim_end_matches(['\n', '\r'])
+                        .to_string(),
+                );
+            }
+            Ok(lines)
+        });
+        match lines {
+            Ok(lines) => Self {
+                path: path.to_path_buf(),
+                lines,
+                error: None,
+            },
+            Err(err) => Self {
+                path: path.to_path_buf(),
+                lines: Vec::new(),
+                error: Some(err.to_string()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use crate::test_utils::{test_output, test_resources};
+
+    use super::*;
+
+    #[test]
+    fn test_diagnostic_report() {
+        let input = test_resources().join("input_test_invalid_start.narrowPeak");
+        let missing_input = test_resources().join("file_does_not_exist.error");
+        let cla = CommandLineArguments::try_parse_from(vec![
+            "Gipfelkreuzer".to_string(),
+            input.display().to_string(),
+            missing_input.display().to_string(),
+        ])
+        .unwrap();
+        let error = gipfelkreuzer::input::bed_to_peaks(cla.consensus_arguments().input_files())
+            .unwrap_err();
+        let report = DiagnosticReport::new(&error, Some(&cla));
+        assert_eq!(report.error, error.report());
+        assert_eq!(report.version, env!("CARGO_PKG_VERSION"));
+        assert!(report.features.contains(&"cli".to_string()));
+        assert!(report.configuration.is_some());
+        assert_eq!(report.input_headers.len(), 2);
+        assert_eq!(report.input_headers[0].lines.len(), INPUT_HEADER_LINES);
+        assert!(report.input_headers[0].lines[1].starts_with("chr1\t-4496298"));
+        assert!(report.input_headers[1].error.is_some());
+        assert!(DiagnosticReport::new(&error, None).input_headers.is_empty());
+
+        std::fs::create_dir_all(test_output()).unwrap();
+        let report_path = test_output().join("test_diagnostic_report.json");
+        report.write(&report_path).unwrap();
+        let written: DiagnosticReport =
+            serde_json::from_str(&std::fs::read_to_string(&report_path).unwrap()).unwrap();
+        std::fs::remove_file(&report_path).unwrap();
+        assert_eq!(written, report);
+    }
+}
@@ -0,0 +1,311 @@
+//! This module compares two versions of a consensus peak set and reports the peaks that were
+//! gained, lost or shifted between them, so changes between releases of a consensus peak set
+//! can be reviewed.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::Write,
+};
+
+use getset::{CopyGetters, Getters};
+
+use crate::{chromosome::Chromosome, error::ApplicationError, peak_set::PeakSet, peaks::PeakData};
+
+/// The change of a peak between two versions of a peak set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PeakChange {
+    /// The peak is only present in the new version.
+    Gained,
+    /// The peak is only present in the old version.
+    Lost,
+    /// The peak is present in both versions, but at least one boundary moved by more than
+    /// the tolerance.
+    Shifted,
+    /// The peak is present in both versions with both boundaries within the tolerance.
+    Unchanged,
+}
+
+impl std::fmt::Display for PeakChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PeakChange::Gained => write!(f, "gained"),
+            PeakChange::Lost => write!(f, "lost"),
+            PeakChange::Shifted => write!(f, "shifted"),
+            PeakChange::Unchanged => write!(f, "unchanged"),
+        }
+    }
+}
+
+/// A peak of either version of a peak set and its change.
+#[derive(Getters, CopyGetters, Debug, Clone, PartialEq)]
+pub struct PeakDifference {
+    /// The change of the peak.
+    #[getset(get_copy = "pub")]
+    change: PeakChange,
+    /// The chromosome of the peak.
+    #[getset(get_copy = "pub")]
+    chromosome: Chromosome,
+    /// The peak of the old version if present.
+    #[getset(get = "pub")]
+    old: Option<PeakData>,
+    /// The matching peak of the new version if present.
+    #[getset(get = "pub")]
+    new: Option<PeakData>,
+}
+
+impl PeakDifference {
+    /// Returns the start coordinate of the peak, preferring the old version.
+    fn start(&self) -> u64 {
+        self.old
+            .or(self.new)
+            .map(|peak| peak.start())
+            .unwrap_or_default()
+    }
+}
+
+/// Compares two versions of a peak set. Each old peak is matched to the overlapping new peak
+/// whose boundaries moved least, with every new peak being matched at most once.
+/// Matched peaks are unchanged if neither boundary moved by more than the tolerance and
+/// shifted otherwise. Unmatched old peaks are lost and unmatched new peaks are gained.
+/// The differences are sorted by chromosome and coordinates.
+///
+/// # Parameters
+///
+/// * `old` - the old version of the peak set
+/// * `new` - the new version of the peak set
+/// * `tolerance` - the maximum number of bases a boundary may move for a peak to be unchanged
+pub fn peak_set_differences(old: &PeakSet, new: &PeakSet, tolerance: u64) -> Vec<PeakDifference> {
+    let mut chromosomes = old.chromosomes();
+    chromosomes.extend(new.chromosomes());
+    chromosomes.sort();
+    chromosomes.dedup();
+    let mut differences = Vec::new();
+    for chromosome in chromosomes {
+        let mut matched = HashSet::new();
+        let mut chromosome_differences = Vec::new();
+        for old_peak in old.peaks(chromosome) {
+            let shift = |new_peak: &PeakData| {
+                (
+                    old_peak.start().abs_diff(new_peak.start()),
+                    old_peak.end().abs_diff(new_peak.end()),
+                )
+            };
+            let new_peak = new
+                .overlapping(chromosome, old_peak.start(), old_peak.end())
+                .into_iter()
+                .filter(|new_peak| !matched.contains(&new_peak.id()))
+                .min_by_key(|new_peak| {
+                    let (start_shift, end_shift) = shift(new_peak);
+                    (start_shift + end_shift, new_peak.start(), new_peak.id())
+                })
+                .copied();
+            let change = match new_peak {
+                Some(new_peak) => {
+                    matched.insert(new_peak.id());
+                    let (start_shift, end_shift) = shift(&new_peak);
+                    if start_shift <= tolerance && end_shift <= tolerance {
+                        PeakChange::Unchanged
+                    } else {
+                        PeakChange::Shifted
+                    }
+                },
+                None => PeakChange::Lost,
+            };
+            chromosome_differences.push(PeakDifference {
+                change,
+                chromosome,
+                old: Some(*old_peak),
+                new: new_peak,
+            });
+        }
+        chromosome_differences.extend(
+            new.peaks(chromosome)
+                .iter()
+                .filter(|new_peak| !matched.contains(&new_peak.id()))
+                .map(|new_peak| PeakDifference {
+                    change: PeakChange::Gained,
+                    chromosome,
+                    old: None,
+                    new: Some(*new_peak),
+                }),
+        );
+        chromosome_differences.sort_by_key(|difference| (difference.start(), difference.change));
+        differences.extend(chromosome_differences);
+    }
+    differences
+}
+
+/// Returns the number of peaks per change, including changes without any peaks.
+///
+/// # Parameters
+///
+/// * `differences` - the differences between the peak set versions
+pub fn difference_counts(differences: &[PeakDifference]) -> BTreeMap<PeakChange, usize> {
+    let mut counts = BTreeMap::from([
+        (PeakChange::Gained, 0),
+        (PeakChange::Lost, 0),
+        (PeakChange::Shifted, 0),
+        (PeakChange::Unchanged, 0),
+    ]);
+    for difference in differences {
+        *counts.entry(difference.change()).or_default() += 1;
+    }
+    counts
+}
+
+/// Writes the differences as tab separated table with the columns change, chromosome, the
+/// start and end of the old and the new peak and the shift of the start and end.
+/// Missing values are written as ".".
+///
+/// # Parameters
+///
+/// * `writer` - the writer to write the table to
+/// * `differences` - the differences between the peak set versions
+///
+/// # Errors
+///
+/// Returns an error if the table could not be written.
+pub fn write_difference_table<W: Write>(
+    mut writer: W,
+    differences: &[PeakDifference],
+) -> Result<(), ApplicationError> {
+    let coordinates = |peak: &Option<PeakData>| match peak {
+        Some(peak) => format!("{}\t{}", peak.start(), peak.end()),
+        None => ".\t.".to_string(),
+    };
+    let mut table = String::from(
+        "change\tchromosome\told_start\told_end\tnew_start\tnew_end\tstart_shift\tend_shift\n",
+    );
+    for difference in differences {
+        let shifts = match (difference.old(), difference.new()) {
+            (Some(old), Some(new)) => format!(
+                "{}\t{}",
+                new.start() as i128 - old.start() as i128,
+                new.end() as i128 - old.end() as i128
+            ),
+            _ => ".\t.".to_string(),
+        };
+        table.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\n",
+            difference.change(),
+            difference.chromosome(),
+            coordinates(difference.old()),
+            coordinates(difference.new()),
+            shifts
+        ));
+    }
+    writer.write_all(table.as_bytes()).map_err(|err| {
+        ApplicationError::from(err).chain("The peak set differences could not be written.")
+    })
+}
+
+/// Writes the number of peaks per change as tab separated table.
+///
+/// # Parameters
+///
+/// * `writer` - the writer to write the table to
+/// * `differences` - the differences between the peak set versions
+///
+/// # Errors
+///
+/// Returns an error if the table could not be written.
+pub fn write_difference_summary<W: Write>(
+    mut writer: W,
+    differences: &[PeakDifference],
+) -> Result<(), ApplicationError> {
+    let mut table = String::from("change\tpeaks\n");
+    for (change, count) in difference_counts(differences) {
+        table.push_str(&format!("{}\t{}\n", change, count));
+    }
+    writer.write_all(table.as_bytes()).map_err(|err| {
+        ApplicationError::from(err).chain("The peak set difference summary could not be written.")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::peak_set;
+
+    use super::*;
+
+    #[test]
+    fn test_peak_set_differences() {
+        let old = peak_set(&[
+            ("chr1", 100, 199, 100),
+            ("chr1", 500, 599, 500),
+            ("chr1", 1000, 1099, 1000),
+            ("chr2", 100, 199, 100),
+        ]);
+        let new = peak_set(&[
+            ("chr1", 102, 198, 102),
+            ("chr1", 520, 640, 520),
+            ("chr1", 2000, 2099, 2000),
+            ("chr3", 100, 199, 100),
+        ]);
+        let changes = |tolerance: u64| -> Vec<(PeakChange, &str, u64)> {
+            peak_set_differences(&old, &new, tolerance)
+                .iter()
+                .map(|difference| {
+                    (difference.change(), difference.chromosome().name(), difference.start())
+                })
+                .collect()
+        };
+        assert_eq!(
+            changes(5),
+            vec![
+                (PeakChange::Unchanged, "chr1", 100),
+                (PeakChange::Shifted, "chr1", 500),
+                (PeakChange::Lost, "chr1", 1000),
+                (PeakChange::Gained, "chr1", 2000),
+                (PeakChange::Lost, "chr2", 100),
+                (PeakChange::Gained, "chr3", 100),
+            ]
+        );
+        assert_eq!(changes(1)[0].0, PeakChange::Shifted);
+        assert_eq!(changes(41)[1].0, PeakChange::Unchanged);
+        let counts = difference_counts(&peak_set_differences(&old, &new, 5));
+        assert_eq!(
+            counts,
+            BTreeMap::from([
+                (PeakChange::Gained, 2),
+                (PeakChange::Lost, 2),
+                (PeakChange::Shifted, 1),
+                (PeakChange::Unchanged, 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_peak_set_differences_split() {
+        // An old peak split into two new peaks is matched to the closer one.
+        let old = peak_set(&[("chr1", 100, 299, 100)]);
+        let new = peak_set(&[("chr1", 100, 180, 100), ("chr1", 200, 299, 200)]);
+        let differences = peak_set_differences(&old, &new, 0);
+        assert_eq!(differences.len(), 2);
+        assert_eq!(differences[0].change(), PeakChange::Gained);
+        assert_eq!(differences[0].new().unwrap().start(), 100);
+        assert_eq!(differences[1].change(), PeakChange::Shifted);
+        assert_eq!(differences[1].new().unwrap().start(), 200);
+    }
+
+    #[test]
+    fn test_write_difference_tables() {
+        let old = peak_set(&[("chr1", 100, 199, 100), ("chr1", 500, 599, 500)]);
+        let new = peak_set(&[("chr1", 90, 210, 90)]);
+        let differences = peak_set_differences(&old, &new, 0);
+        let mut table = Vec::new();
+        write_difference_table(&mut table, &differences).unwrap();
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            "change\tchromosome\told_start\told_end\tnew_start\tnew_end\tstart_shift\tend_shift\n\
+            shifted\tchr1\t100\t199\t90\t210\t-10\t11\n\
+            lost\tchr1\t500\t599\t.\t.\t.\t.\n"
+        );
+        let mut summary = Vec::new();
+        write_difference_summary(&mut summary, &differences).unwrap();
+        assert_eq!(
+            String::from_utf8(summary).unwrap(),
+            "change\tpeaks\ngained\t0\nlost\t1\nshifted\t1\nunchanged\t0\n"
+        );
+    }
+}
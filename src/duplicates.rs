@@ -0,0 +1,267 @@
+//! This module detects near-duplicate consensus peaks, which largely overlap each other.
+//! Such pairs are a known failure mode if iterative merging does not converge
+//! within the maximum number of merge iterations.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+};
+
+use getset::CopyGetters;
+
+use crate::{
+    chromosome::Chromosome,
+    error::{ApplicationError, ApplicationErrorType},
+    peaks::PeakData,
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "duplicates";
+
+/// The default minimum fraction of the shorter peak covered by the longer peak
+/// to consider two peaks near-duplicates.
+pub const DEFAULT_DUPLICATE_OVERLAP: f64 = 0.5;
+
+/// A pair of near-duplicate peaks.
+#[derive(CopyGetters, Clone, Copy, PartialEq, Debug)]
+#[getset(get_copy = "pub")]
+pub struct NearDuplicate {
+    /// The chromosome of the peaks.
+    chromosome: Chromosome,
+    /// The peak starting first.
+    first: PeakData,
+    /// The peak starting second.
+    second: PeakData,
+    /// The fraction of the shorter peak covered by the longer peak.
+    overlap_fraction: f64,
+}
+
+/// Validates the minimum overlap fraction of near-duplicate peaks.
+///
+/// # Parameters
+///
+/// * `min_overlap_fraction` - the minimum fraction of the shorter peak covered by the longer peak
+///
+/// # Errors
+///
+/// Returns an error if the fraction is not within `(0, 1]`.
+pub fn validate_duplicate_overlap(min_overlap_fraction: f64) -> Result<f64, ApplicationError> {
+    if min_overlap_fraction > 0.0 && min_overlap_fraction <= 1.0 {
+        Ok(min_overlap_fraction)
+    } else {
+        Err(ApplicationError::new(
+            ApplicationErrorType::ConfigError,
+            format!(
+                "The minimum near-duplicate overlap fraction {} is not within (0, 1].",
+                min_overlap_fraction
+            ),
+        ))
+    }
+}
+
+/// Returns the fraction of the shorter peak covered by the longer peak.
+///
+/// # Parameters
+///
+/// * `first` - the first peak
+/// * `second` - the second peak
+fn overlap_fraction(first: &PeakData, second: &PeakData) -> f64 {
+    let overlap_start = first.start().max(second.start());
+    let overlap_end = first.end().min(second.end());
+    if overlap_start > overlap_end {
+        return 0.0;
+    }
+    (overlap_end - overlap_start + 1) as f64 / first.length().min(second.length()) as f64
+}
+
+/// Returns the pairs of peaks, in which the shorter peak is covered by the longer peak
+/// by at least the specified fraction, sorted by chromosome and coordinates.
+///
+/// # Parameters
+///
+/// * `peaks` - the peaks grouped by chromosome
+/// * `min_overlap_fraction` - the minimum fraction of the shorter peak covered by the longer peak
+pub fn near_duplicates(
+    peaks: &HashMap<Chromosome, Vec<PeakData>>,
+    min_overlap_fraction: f64,
+) -> Vec<NearDuplicate> {
+    let mut chromosomes: Vec<&Chromosome> = peaks.keys().collect();
+    chromosomes.sort_unstable();
+    let mut duplicates = Vec::new();
+    for chromosome in chromosomes {
+        let mut sorted_peaks: Vec<&PeakData> = peaks[chromosome].iter().collect();
+        sorted_peaks.sort_by_key(|peak| (peak.start(), peak.end(), peak.id()));
+        for (index, first) in sorted_peaks.iter().enumerate() {
+            for second in sorted_peaks[index + 1..]
+                .iter()
+                .take_while(|second| second.start() <= first.end())
+            {
+                let fraction = overlap_fraction(first, second);
+                if fraction >= min_overlap_fraction {
+                    duplicates.push(NearDuplicate {
+                        chromosome: *chromosome,
+                        first: **first,
+                        second: **second,
+                        overlap_fraction: fraction,
+                    });
+                }
+            }
+        }
+    }
+    duplicates
+}
+
+/// Merges each group of transitively near-duplicate peaks into a single peak spanning
+/// all merged peaks. A merged peak keeps the lowest identifier of the group and its summit
+/// is set to its centre, while all other peaks are retained unchanged.
+///
+/// # Parameters
+///
+/// * `peaks` - the peaks grouped by chromosome
+/// * `duplicates` - the near-duplicate peaks to merge as returned by [`near_duplicates`]
+pub fn merge_near_duplicates(
+    peaks: HashMap<Chromosome, Vec<PeakData>>,
+    duplicates: &[NearDuplicate],
+) -> HashMap<Chromosome, Vec<PeakData>> {
+    // Each group of near-duplicates is represented by the smallest identifier of the group.
+    let mut representatives: HashMap<(Chromosome, usize), usize> = HashMap::new();
+    let representative = |representatives: &HashMap<(Chromosome, usize), usize>,
+                          chromosome: Chromosome,
+                          mut id: usize| {
+        while let Some(parent) = representatives.get(&(chromosome, id)) {
+            id = *parent;
+        }
+        id
+    };
+    let mut grouped: HashSet<(Chromosome, usize)> = HashSet::new();
+    for duplicate in duplicates {
+        let chromosome = duplicate.chromosome();
+        let first = representative(&representatives, chromosome, duplicate.first().id());
+        let second = representative(&representatives, chromosome, duplicate.second().id());
+        if first != second {
+            representatives.insert((chromosome, first.max(second)), first.min(second));
+        }
+        grouped.insert((chromosome, duplicate.first().id()));
+        grouped.insert((chromosome, duplicate.second().id()));
+    }
+    let merged: HashMap<Chromosome, Vec<PeakData>> = peaks
+        .into_iter()
+        .map(|(chromosome, chromosome_peaks)| {
+            let mut groups: HashMap<usize, (u64, u64)> = HashMap::new();
+            let mut retained = Vec::with_capacity(chromosome_peaks.len());
+            for peak in chromosome_peaks {
+                if !grouped.contains(&(chromosome, peak.id())) {
+                    retained.push(peak);
+                    continue;
+                }
+                let id = representative(&representatives, chromosome, peak.id());
+                let group = groups.entry(id).or_insert((peak.start(), peak.end()));
+                group.0 = group.0.min(peak.start());
+                group.1 = group.1.max(peak.end());
+            }
+            retained.extend(groups.into_iter().map(|(id, (start, end))| {
+                PeakData::new(id, start, end, start + (end - start) / 2)
+                    .expect("The merged peak must be valid as its summit is its centre.")
+            }));
+            (chromosome, retained)
+        })
+        .collect();
+    log::info!(
+        target: LOG_TARGET,
+        "Merged {} near-duplicate peaks.",
+        grouped.len()
+    );
+    merged
+}
+
+/// Writes the near-duplicate peaks as tab separated table.
+///
+/// # Parameters
+///
+/// * `writer` - the writer to write the table to
+/// * `duplicates` - the near-duplicate peaks
+///
+/// # Errors
+///
+/// Returns an error if the table cannot be written.
+pub fn write_duplicate_table<W: Write>(
+    mut writer: W,
+    duplicates: &[NearDuplicate],
+) -> Result<(), ApplicationError> {
+    let mut table = String::from(
+        "chromosome\tfirst_start\tfirst_end\tsecond_start\tsecond_end\toverlap_fraction\n",
+    );
+    for duplicate in duplicates {
+        table.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\t{:.6}\n",
+            duplicate.chromosome(),
+            duplicate.first().start(),
+            duplicate.first().end(),
+            duplicate.second().start(),
+            duplicate.second().end(),
+            duplicate.overlap_fraction()
+        ));
+    }
+    writer.write_all(table.as_bytes()).map_err(|err| {
+        ApplicationError::from(err).chain("The near-duplicate report could not be written.")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_duplicate_overlap() {
+        assert_eq!(validate_duplicate_overlap(1.0).unwrap(), 1.0);
+        assert!(validate_duplicate_overlap(0.0).is_err());
+        assert!(validate_duplicate_overlap(1.5).is_err());
+        assert!(validate_duplicate_overlap(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_near_duplicates() {
+        let chromosome = Chromosome::from("chr1");
+        let peaks = HashMap::from([(
+            chromosome,
+            vec![
+                PeakData::new(3, 1000u64, 1099u64, 1050u64).unwrap(),
+                PeakData::new(0, 100u64, 199u64, 150u64).unwrap(),
+                PeakData::new(1, 150u64, 299u64, 200u64).unwrap(),
+                PeakData::new(2, 260u64, 359u64, 300u64).unwrap(),
+                PeakData::new(4, 1090u64, 1199u64, 1150u64).unwrap(),
+            ],
+        )]);
+        let duplicates = near_duplicates(&peaks, 0.4);
+        assert_eq!(duplicates.len(), 2);
+        assert_eq!(duplicates[0].first().id(), 0);
+        assert_eq!(duplicates[0].second().id(), 1);
+        assert!((duplicates[0].overlap_fraction() - 0.5).abs() < 1e-9);
+        assert_eq!(duplicates[1].first().id(), 1);
+        assert_eq!(duplicates[1].second().id(), 2);
+        assert!((duplicates[1].overlap_fraction() - 0.4).abs() < 1e-9);
+        assert_eq!(near_duplicates(&peaks, 0.45).len(), 1);
+
+        let mut table = Vec::new();
+        write_duplicate_table(&mut table, &duplicates).unwrap();
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            "chromosome\tfirst_start\tfirst_end\tsecond_start\tsecond_end\toverlap_fraction\n\
+            chr1\t100\t199\t150\t299\t0.500000\n\
+            chr1\t150\t299\t260\t359\t0.400000\n"
+        );
+
+        let mut merged = merge_near_duplicates(peaks, &duplicates)
+            .remove(&chromosome)
+            .unwrap();
+        merged.sort_by_key(PeakData::id);
+        assert_eq!(
+            merged,
+            vec![
+                PeakData::new(0, 100u64, 359u64, 229u64).unwrap(),
+                PeakData::new(3, 1000u64, 1099u64, 1050u64).unwrap(),
+                PeakData::new(4, 1090u64, 1199u64, 1150u64).unwrap(),
+            ]
+        );
+    }
+}
@@ -1,7 +1,10 @@
 //! This module defines specific error types.
 
+use std::{backtrace::Backtrace, collections::HashMap, error::Error, sync::Arc};
+
 use getset::{CopyGetters, Getters};
-use log::error;
+use log::Level;
+use serde::{Deserialize, Serialize};
 
 /// An application wide error.
 #[derive(Debug, Clone, Getters, CopyGetters)]
@@ -12,19 +15,92 @@ pub struct ApplicationError {
     /// The message stack for logging or display.
     #[getset(get = "pub")]
     internal_messages: Vec<String>,
+    /// The location of the offending record, if the error refers to specific input data.
+    context: Option<Box<ErrorContext>>,
+    /// Actionable hints on how to resolve the error.
+    #[getset(get = "pub")]
+    hints: Vec<String>,
+    /// Further errors reported together with this error, such as all problems found in the input data.
+    #[getset(get = "pub")]
+    related: Vec<ApplicationError>,
+    /// The underlying error that caused this error.
+    source: Option<Arc<dyn Error + Send + Sync + 'static>>,
+    /// The backtrace captured when the error was created.
+    backtrace: Arc<Backtrace>,
 }
 
-/// An application wide error type.
-#[derive(Debug, Copy, Clone)]
+/// An application wide error type classifying the cause of an [`ApplicationError`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ApplicationErrorType {
     /// An generic error implying an internal problem.
     InternalError,
     /// An input or output related error.
     IOError,
-    /// Input data error.
-    InputDataError,
-    /// Input operation error.
+    /// Malformed input data that could not be parsed, such as invalid BED records or JSON.
+    ParseError,
+    /// Well-formed data violating a constraint, such as a peak summit outside of the peak region.
+    ValidationError,
+    /// An invalid configuration, such as unknown command line arguments or algorithm names.
+    ConfigError,
+    /// A failure during consensus peak generation.
+    AlgorithmError,
+    /// A failure preparing the output, such as an invalid output path.
     OutputOperationError,
+    /// An unexpected internal failure caused by a panic, which indicates a bug.
+    PanicError,
+}
+
+impl ApplicationErrorType {
+    /// Returns the process exit code for errors of this type
+    /// following the conventions of the BSD `sysexits.h` header.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ApplicationErrorType::InternalError | ApplicationErrorType::AlgorithmError => 70,
+            ApplicationErrorType::IOError => 74,
+            ApplicationErrorType::ParseError | ApplicationErrorType::ValidationError => 65,
+            ApplicationErrorType::ConfigError => 64,
+            ApplicationErrorType::OutputOperationError => 73,
+            ApplicationErrorType::PanicError => 101,
+        }
+    }
+
+    /// Returns `true` if errors of this type are caused by the environment, such as
+    /// invalid input data, an invalid configuration or failing file operations,
+    /// so the operation might succeed with corrected inputs or if it is retried.
+    /// Returns `false` if errors of this type indicate a bug.
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            ApplicationErrorType::IOError
+            | ApplicationErrorType::ParseError
+            | ApplicationErrorType::ValidationError
+            | ApplicationErrorType::ConfigError
+            | ApplicationErrorType::OutputOperationError => true,
+            ApplicationErrorType::InternalError
+            | ApplicationErrorType::AlgorithmError
+            | ApplicationErrorType::PanicError => false,
+        }
+    }
+
+    /// Returns the error type of an IO error of the specified kind.
+    /// Undecodable or truncated data is classified as malformed input data
+    /// and a lack of storage space or write permissions as output error.
+    ///
+    /// # Parameters
+    ///
+    /// * `kind` - the kind of the IO error
+    pub fn from_io_error_kind(kind: std::io::ErrorKind) -> Self {
+        match kind {
+            std::io::ErrorKind::InvalidData | std::io::ErrorKind::UnexpectedEof => {
+                ApplicationErrorType::ParseError
+            },
+            std::io::ErrorKind::StorageFull
+            | std::io::ErrorKind::QuotaExceeded
+            | std::io::ErrorKind::FileTooLarge
+            | std::io::ErrorKind::ReadOnlyFilesystem
+            | std::io::ErrorKind::WriteZero => ApplicationErrorType::OutputOperationError,
+            _ => ApplicationErrorType::IOError,
+        }
+    }
 }
 
 impl std::fmt::Display for ApplicationErrorType {
@@ -32,22 +108,183 @@ impl std::fmt::Display for ApplicationErrorType {
         let name = match self {
             ApplicationErrorType::InternalError => "Generic internal error",
             ApplicationErrorType::IOError => "IO error",
-            ApplicationErrorType::InputDataError => "Invalid input data",
+            ApplicationErrorType::ParseError => "Malformed input data",
+            ApplicationErrorType::ValidationError => "Invalid data",
+            ApplicationErrorType::ConfigError => "Invalid configuration",
+            ApplicationErrorType::AlgorithmError => "Consensus peak generation error",
             ApplicationErrorType::OutputOperationError => "Output operation error",
+            ApplicationErrorType::PanicError => "Unexpected internal failure",
         };
         write!(f, "{}", name)
     }
 }
 
 impl ApplicationError {
-    /// Creates a new error and automatically logs the error.
+    /// Creates a new error.
+    /// A backtrace is captured if enabled via the `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE`
+    /// environment variables.
+    ///
+    /// # Parameters
+    ///
+    /// * `error_type` - the type of the error
+    /// * `message` - the initial message of the message stack
     pub fn new<T: ToString>(error_type: ApplicationErrorType, message: T) -> Self {
         Self {
             error_type,
             internal_messages: vec![message.to_string()],
+            context: None,
+            hints: Vec::new(),
+            related: Vec::new(),
+            source: None,
+            backtrace: Arc::new(Backtrace::capture()),
         }
     }
 
+    /// Creates a new error caused by another error, which is preserved as the
+    /// [`source`](Error::source) of the created error.
+    ///
+    /// # Parameters
+    ///
+    /// * `error_type` - the type of the error
+    /// * `source` - the error causing the created error
+    pub fn from_source<E: Error + Send + Sync + 'static>(
+        error_type: ApplicationErrorType,
+        source: E,
+    ) -> Self {
+        let mut error = Self::new(error_type, &source);
+        error.source = Some(Arc::new(source));
+        error
+    }
+
+    /// Attaches the location of the offending record to the error.
+    /// An already attached context is replaced.
+    ///
+    /// # Parameters
+    ///
+    /// * `context` - the location of the offending record
+    pub fn with_context(mut self, context: ErrorContext) -> Self {
+        self.context = Some(Box::new(context));
+        self
+    }
+
+    /// Adds an actionable hint on how to resolve the error.
+    ///
+    /// # Parameters
+    ///
+    /// * `hint` - the hint to add
+    pub fn with_hint<T: ToString>(mut self, hint: T) -> Self {
+        self.hints.push(hint.to_string());
+        self
+    }
+
+    /// Renders the error for display to a user with the message stack starting at the outermost
+    /// context, the location of the offending record and hints on how to resolve the error.
+    ///
+    /// # Parameters
+    ///
+    /// * `colour` - highlights the output with ANSI colour codes, for example if printed to a terminal
+    pub fn render(&self, colour: bool) -> String {
+        let mut rendered = String::new();
+        self.render_internal(colour, "", &mut rendered);
+        rendered
+    }
+
+    /// Renders the error with the specified indentation into the output.
+    ///
+    /// # Parameters
+    ///
+    /// * `colour` - highlights the output with ANSI colour codes
+    /// * `indentation` - the indentation of all rendered lines
+    /// * `output` - the output to append the rendered error to
+    fn render_internal(&self, colour: bool, indentation: &str, output: &mut String) {
+        let paint = |text: &str, code: &str| {
+            if colour {
+                format!("\x1b[{}m{}\x1b[0m", code, text)
+            } else {
+                text.to_string()
+            }
+        };
+        output.push_str(&format!(
+            "{}{} {}\n",
+            indentation,
+            paint("error:", "1;31"),
+            paint(&self.error_type().to_string(), "1")
+        ));
+        if let Some(context) = self.context() {
+            output.push_str(&format!(
+                "{}  {} {}\n",
+                indentation,
+                paint("-->", "1;34"),
+                paint(&context.to_string(), "36")
+            ));
+        }
+        for message in self.internal_messages().iter().rev() {
+            output.push_str(&format!("{}  {}\n", indentation, message));
+        }
+        for hint in self.hints() {
+            output.push_str(&format!("{}  {} {}\n", indentation, paint("hint:", "1;32"), hint));
+        }
+        let related_indentation = format!("{}    ", indentation);
+        for (index, related) in self.related().iter().enumerate() {
+            output.push_str(&format!(
+                "{}  {}\n",
+                indentation,
+                paint(&format!("Problem {} of {}:", index + 1, self.related().len()), "1")
+            ));
+            related.render_internal(colour, &related_indentation, output);
+        }
+    }
+
+    /// Attaches further errors that are reported together with this error.
+    ///
+    /// # Parameters
+    ///
+    /// * `related` - the related errors
+    pub fn with_related(mut self, related: Vec<ApplicationError>) -> Self {
+        self.related = related;
+        self
+    }
+
+    /// Returns the location of the offending record, if the error refers to specific input data.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        self.context.as_deref()
+    }
+
+    /// Returns the backtrace captured when the error was created
+    /// or `None` if backtraces are disabled.
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        match self.backtrace.status() {
+            std::backtrace::BacktraceStatus::Captured => Some(&self.backtrace),
+            _ => None,
+        }
+    }
+
+    /// Creates an error from the payload of a panic.
+    ///
+    /// # Parameters
+    ///
+    /// * `payload` - the panic payload
+    /// * `location` - the source code location of the panic, if known
+    pub fn from_panic_payload(
+        payload: &(dyn std::any::Any + Send),
+        location: Option<String>,
+    ) -> Self {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic payload".to_string());
+        let message = match location {
+            Some(location) => format!("Panicked at {}: {}", location, message),
+            None => format!("Panicked: {}", message),
+        };
+        Self::new(ApplicationErrorType::PanicError, message).with_hint(concat!(
+            "This is a bug. Please report it at ",
+            env!("CARGO_PKG_REPOSITORY"),
+            "/issues including the command line and this message."
+        ))
+    }
+
     /// Returns the formated internal message stack.
     fn format_internal_messages(&self) -> String {
         self.internal_messages().iter().rev().enumerate().fold(
@@ -70,19 +307,93 @@ impl ApplicationError {
         self
     }
 
+    /// Changes the type of the error, for example if the context of an error
+    /// determines its classification.
+    ///
+    /// # Parameters
+    ///
+    /// * `error_type` - the new error type
+    pub fn with_error_type(mut self, error_type: ApplicationErrorType) -> Self {
+        self.error_type = error_type;
+        self
+    }
+
+    /// Returns `true` if the error is caused by the environment rather than a bug,
+    /// see [`ApplicationErrorType::is_recoverable`].
+    pub fn is_recoverable(&self) -> bool {
+        self.error_type().is_recoverable()
+    }
+
+    /// Returns a machine-readable report of the error.
+    pub fn report(&self) -> ErrorReport {
+        ErrorReport {
+            error_type: self.error_type(),
+            description: self.error_type().to_string(),
+            messages: self.internal_messages().iter().rev().cloned().collect(),
+            context: self.context().cloned(),
+            hints: self.hints().clone(),
+            related: self
+                .related()
+                .iter()
+                .map(ApplicationError::report)
+                .collect(),
+            exit_code: self.error_type().exit_code(),
+            recoverable: self.is_recoverable(),
+        }
+    }
+
+    /// Returns the machine-readable report of the error serialised as single line JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.report())
+            .expect("Serialising an error report to JSON must not fail.")
+    }
+
     /// Logs the error on its default level.
     pub fn log_default(&self) {
         ApplicationErrorLogger::new(self).log_default();
     }
+
+    /// Logs the error on the level configured for its type.
+    ///
+    /// # Parameters
+    ///
+    /// * `levels` - the log levels of the different error types
+    pub fn log_with_levels(&self, levels: &ErrorLogLevels) {
+        ApplicationErrorLogger::new(self)
+            .with_levels(levels.clone())
+            .log_default();
+    }
 }
 
 impl std::fmt::Display for ApplicationError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}\n{}", self.error_type(), self.format_internal_messages())
+        match self.context() {
+            Some(context) => write!(
+                f,
+                "{} at {}\n{}",
+                self.error_type(),
+                context,
+                self.format_internal_messages()
+            )?,
+            None => write!(f, "{}\n{}", self.error_type(), self.format_internal_messages())?,
+        }
+        for hint in self.hints() {
+            writeln!(f, "hint: {}", hint)?;
+        }
+        for (index, related) in self.related().iter().enumerate() {
+            write!(f, "Problem {}: {}", index + 1, related)?;
+        }
+        Ok(())
     }
 }
 
-impl std::error::Error for ApplicationError {}
+impl Error for ApplicationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|source| source as &(dyn Error + 'static))
+    }
+}
 
 impl AsRef<ApplicationError> for ApplicationError {
     fn as_ref(&self) -> &ApplicationError {
@@ -92,7 +403,7 @@ impl AsRef<ApplicationError> for ApplicationError {
 
 impl From<std::io::Error> for ApplicationError {
     fn from(error: std::io::Error) -> Self {
-        Self::new(ApplicationErrorType::IOError, error)
+        Self::from_source(ApplicationErrorType::from_io_error_kind(error.kind()), error)
     }
 }
 
@@ -102,26 +413,231 @@ impl From<log::SetLoggerError> for ApplicationError {
     }
 }
 
+#[cfg(feature = "cli")]
 impl From<clap::Error> for ApplicationError {
     fn from(error: clap::Error) -> Self {
-        Self::new(ApplicationErrorType::InternalError, error)
+        Self::from_source(ApplicationErrorType::ConfigError, error)
     }
 }
 
 impl From<core::num::ParseIntError> for ApplicationError {
     fn from(error: core::num::ParseIntError) -> Self {
-        Self::new(ApplicationErrorType::InternalError, error)
+        Self::from_source(ApplicationErrorType::ParseError, error)
+    }
+}
+
+impl From<serde_json::Error> for ApplicationError {
+    fn from(error: serde_json::Error) -> Self {
+        if error.is_io() {
+            Self::from_source(ApplicationErrorType::IOError, error)
+        } else if error.is_data() {
+            Self::from_source(ApplicationErrorType::ValidationError, error)
+        } else {
+            Self::from_source(ApplicationErrorType::ParseError, error)
+        }
     }
 }
 
-/// A logger for a specific [`SeqError`].
+#[cfg(feature = "polars")]
+impl From<polars::prelude::PolarsError> for ApplicationError {
+    fn from(error: polars::prelude::PolarsError) -> Self {
+        if matches!(error, polars::prelude::PolarsError::IO { .. }) {
+            Self::from_source(ApplicationErrorType::IOError, error)
+        } else {
+            Self::from_source(ApplicationErrorType::ValidationError, error)
+        }
+    }
+}
+
+#[cfg(feature = "arrow")]
+impl From<arrow_schema::ArrowError> for ApplicationError {
+    fn from(error: arrow_schema::ArrowError) -> Self {
+        if matches!(error, arrow_schema::ArrowError::IoError(..)) {
+            Self::from_source(ApplicationErrorType::IOError, error)
+        } else {
+            Self::from_source(ApplicationErrorType::InternalError, error)
+        }
+    }
+}
+
+/// The location of the record an [`ApplicationError`] refers to.
+/// Line and column numbers are 1-based, where the column refers to the
+/// tab or space separated field of a record.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Getters, CopyGetters, Serialize, Deserialize)]
+pub struct ErrorContext {
+    /// The path of the input file or a description of an in-memory source.
+    #[getset(get = "pub")]
+    path: Option<String>,
+    /// The line number of the record.
+    #[getset(get_copy = "pub")]
+    line: Option<u64>,
+    /// The column number of the offending field.
+    #[getset(get_copy = "pub")]
+    column: Option<usize>,
+    /// The name of the offending field.
+    #[getset(get = "pub")]
+    field: Option<String>,
+    /// The offending value.
+    #[getset(get = "pub")]
+    value: Option<String>,
+}
+
+impl ErrorContext {
+    /// Creates a new empty context.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the path of the input file or a description of an in-memory source.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - the path or source description
+    pub fn with_path<T: ToString>(mut self, path: T) -> Self {
+        self.path = Some(path.to_string());
+        self
+    }
+
+    /// Sets the 1-based line number of the record.
+    ///
+    /// # Parameters
+    ///
+    /// * `line` - the line number
+    pub fn with_line(mut self, line: u64) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    /// Sets the offending field.
+    ///
+    /// # Parameters
+    ///
+    /// * `column` - the 1-based column number of the field
+    /// * `field` - the name of the field
+    pub fn with_field<T: ToString>(mut self, column: usize, field: T) -> Self {
+        self.column = Some(column);
+        self.field = Some(field.to_string());
+        self
+    }
+
+    /// Sets the offending value.
+    ///
+    /// # Parameters
+    ///
+    /// * `value` - the value
+    pub fn with_value<T: ToString>(mut self, value: T) -> Self {
+        self.value = Some(value.to_string());
+        self
+    }
+}
+
+impl std::fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let location: Vec<String> = [
+            self.path.clone(),
+            self.line.map(|line| line.to_string()),
+            self.column.map(|column| column.to_string()),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        write!(f, "{}", location.join(":"))?;
+        if let Some(field) = self.field() {
+            write!(f, " (field \"{}\"", field)?;
+            if let Some(value) = self.value() {
+                write!(f, ", value \"{}\"", value)?;
+            }
+            write!(f, ")")?;
+        } else if let Some(value) = self.value() {
+            write!(f, " (value \"{}\")", value)?;
+        }
+        Ok(())
+    }
+}
+
+/// A machine-readable report of an [`ApplicationError`], for example for workflow engines
+/// that display failure reasons.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorReport {
+    /// The error type.
+    pub error_type: ApplicationErrorType,
+    /// The human readable description of the error type.
+    pub description: String,
+    /// The message stack starting with the outermost context and ending with the root cause.
+    pub messages: Vec<String>,
+    /// The location of the offending record, if the error refers to specific input data.
+    pub context: Option<ErrorContext>,
+    /// Actionable hints on how to resolve the error.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hints: Vec<String>,
+    /// The reports of further errors reported together with this error.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub related: Vec<ErrorReport>,
+    /// The exit code of the process.
+    pub exit_code: u8,
+    /// If the error is caused by the environment rather than a bug.
+    #[serde(default)]
+    pub recoverable: bool,
+}
+
+/// The log levels used for the different [`ApplicationErrorType`]s.
+/// By default all errors are logged as errors.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorLogLevels {
+    /// The log levels deviating from the default level.
+    levels: HashMap<ApplicationErrorType, Level>,
+}
+
+impl ErrorLogLevels {
+    /// Creates a mapping logging all errors as errors.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a mapping logging malformed and invalid input data as warnings,
+    /// for example if processing continues despite the error.
+    pub fn lenient() -> Self {
+        Self::new()
+            .with_level(ApplicationErrorType::ParseError, Level::Warn)
+            .with_level(ApplicationErrorType::ValidationError, Level::Warn)
+    }
+
+    /// Sets the log level of an error type.
+    /// Internal errors are always logged as errors, so their level cannot be changed.
+    ///
+    /// # Parameters
+    ///
+    /// * `error_type` - the error type
+    /// * `level` - the log level of the error type
+    pub fn with_level(mut self, error_type: ApplicationErrorType, level: Level) -> Self {
+        if error_type != ApplicationErrorType::InternalError {
+            self.levels.insert(error_type, level);
+        }
+        self
+    }
+
+    /// Returns the log level of an error type.
+    ///
+    /// # Parameters
+    ///
+    /// * `error_type` - the error type
+    pub fn level(&self, error_type: ApplicationErrorType) -> Level {
+        self.levels
+            .get(&error_type)
+            .copied()
+            .unwrap_or(Level::Error)
+    }
+}
+
+/// A logger for a specific [`ApplicationError`].
 pub struct ApplicationErrorLogger {
     message: String,
     error_type: ApplicationErrorType,
+    levels: ErrorLogLevels,
 }
 
 impl ApplicationErrorLogger {
-    /// Creates a new [`SeqErrorLogger`] from an [`SeqError`].
+    /// Creates a new [`ApplicationErrorLogger`] from an [`ApplicationError`].
     ///
     /// # Parameters
     ///
@@ -130,13 +646,209 @@ impl ApplicationErrorLogger {
         ApplicationErrorLogger {
             message: error.as_ref().to_string(),
             error_type: error.as_ref().error_type(),
+            levels: ErrorLogLevels::default(),
         }
     }
 
-    /// Logs the error on its default level.
+    /// Sets the log levels of the different error types.
+    ///
+    /// # Parameters
+    ///
+    /// * `levels` - the log levels of the different error types
+    pub fn with_levels(mut self, levels: ErrorLogLevels) -> Self {
+        self.levels = levels;
+        self
+    }
+
+    /// Returns the level the error is logged on.
+    pub fn level(&self) -> Level {
+        self.levels.level(self.error_type)
+    }
+
+    /// Logs the error on the level configured for its type.
     pub fn log_default(&self) {
-        match self.error_type {
-            _ => error!("{}", self.message),
-        }
+        log::log!(self.level(), "{}", self.message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_application_error_type_display() {
+        assert_eq!(ApplicationErrorType::ParseError.to_string(), "Malformed input data");
+        assert_eq!(ApplicationErrorType::ValidationError.to_string(), "Invalid data");
+        assert_eq!(ApplicationErrorType::ConfigError.to_string(), "Invalid configuration");
+        assert_eq!(
+            ApplicationErrorType::AlgorithmError.to_string(),
+            "Consensus peak generation error"
+        );
+    }
+
+    #[test]
+    fn test_application_error_with_error_type() {
+        let error = ApplicationError::new(ApplicationErrorType::ValidationError, "cause")
+            .with_error_type(ApplicationErrorType::AlgorithmError)
+            .chain("context");
+        assert_eq!(error.error_type(), ApplicationErrorType::AlgorithmError);
+        assert_eq!(error.internal_messages(), &vec!["cause".to_string(), "context".to_string()]);
+    }
+
+    #[test]
+    fn test_application_error_from() {
+        assert_eq!(
+            ApplicationError::from("a".parse::<u64>().unwrap_err()).error_type(),
+            ApplicationErrorType::ParseError
+        );
+        assert_eq!(
+            ApplicationError::from(std::io::Error::other("io")).error_type(),
+            ApplicationErrorType::IOError
+        );
+        assert_eq!(
+            ApplicationError::from(std::io::Error::from(std::io::ErrorKind::NotFound)).error_type(),
+            ApplicationErrorType::IOError
+        );
+        assert_eq!(
+            ApplicationError::from(std::io::Error::from(std::io::ErrorKind::InvalidData))
+                .error_type(),
+            ApplicationErrorType::ParseError
+        );
+        assert_eq!(
+            ApplicationError::from(std::io::Error::from(std::io::ErrorKind::StorageFull))
+                .error_type(),
+            ApplicationErrorType::OutputOperationError
+        );
+    }
+
+    #[test]
+    fn test_application_error_is_recoverable() {
+        assert!(
+            ApplicationError::new(ApplicationErrorType::ParseError, "invalid").is_recoverable()
+        );
+        assert!(ApplicationError::new(ApplicationErrorType::IOError, "io").is_recoverable());
+        assert!(ApplicationErrorType::ConfigError.is_recoverable());
+        assert!(
+            !ApplicationError::new(ApplicationErrorType::InternalError, "bug").is_recoverable()
+        );
+        assert!(!ApplicationErrorType::AlgorithmError.is_recoverable());
+        assert!(!ApplicationErrorType::PanicError.is_recoverable());
+    }
+
+    #[test]
+    fn test_application_error_context() {
+        let context = ErrorContext::new()
+            .with_path("peaks.bed")
+            .with_line(42)
+            .with_field(2, "chromStart")
+            .with_value("abc");
+        assert_eq!(context.to_string(), "peaks.bed:42:2 (field \"chromStart\", value \"abc\")");
+        assert_eq!(ErrorContext::new().with_path("buffer 0").to_string(), "buffer 0");
+        let error = ApplicationError::new(ApplicationErrorType::ParseError, "invalid")
+            .with_context(context.clone());
+        assert_eq!(error.context(), Some(&context));
+        assert!(
+            error
+                .to_string()
+                .starts_with("Malformed input data at peaks.bed:42:2")
+        );
+    }
+
+    #[test]
+    fn test_application_error_report() {
+        let error = ApplicationError::new(ApplicationErrorType::ParseError, "cause")
+            .chain("context")
+            .with_context(ErrorContext::new().with_path("peaks.bed").with_line(3));
+        let report = error.report();
+        assert_eq!(report.error_type, ApplicationErrorType::ParseError);
+        assert_eq!(report.messages, vec!["context".to_string(), "cause".to_string()]);
+        assert_eq!(report.exit_code, 65);
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            r#"{"error_type":"ParseError","description":"Malformed input data","messages":["context","cause"],"context":{"path":"peaks.bed","line":3,"column":null,"field":null,"value":null},"exit_code":65,"recoverable":true}"#
+        );
+    }
+
+    #[test]
+    fn test_application_error_to_json() {
+        let error = ApplicationError::new(ApplicationErrorType::InternalError, "bug")
+            .with_hint("Please report this.");
+        assert_eq!(
+            error.to_json(),
+            r#"{"error_type":"InternalError","description":"Generic internal error","messages":["bug"],"context":null,"hints":["Please report this."],"exit_code":70,"recoverable":false}"#
+        );
+    }
+
+    #[test]
+    fn test_error_log_levels() {
+        let error = ApplicationError::new(ApplicationErrorType::ValidationError, "invalid");
+        assert_eq!(ApplicationErrorLogger::new(&error).level(), Level::Error);
+        let lenient = ErrorLogLevels::lenient();
+        assert_eq!(
+            ApplicationErrorLogger::new(&error)
+                .with_levels(lenient.clone())
+                .level(),
+            Level::Warn
+        );
+        assert_eq!(lenient.level(ApplicationErrorType::IOError), Level::Error);
+        let levels = lenient
+            .with_level(ApplicationErrorType::IOError, Level::Info)
+            .with_level(ApplicationErrorType::InternalError, Level::Debug);
+        assert_eq!(levels.level(ApplicationErrorType::IOError), Level::Info);
+        assert_eq!(levels.level(ApplicationErrorType::InternalError), Level::Error);
+    }
+
+    #[test]
+    fn test_application_error_render() {
+        let error = ApplicationError::new(ApplicationErrorType::ParseError, "cause")
+            .chain("context")
+            .with_context(ErrorContext::new().with_path("peaks.bed").with_line(3))
+            .with_hint("Fix the file.");
+        assert_eq!(
+            error.render(false),
+            "error: Malformed input data\n  --> peaks.bed:3\n  context\n  cause\n  hint: Fix the file.\n"
+        );
+        assert!(
+            error
+                .render(true)
+                .contains("\x1b[1;32mhint:\x1b[0m Fix the file.")
+        );
+        let collected = ApplicationError::new(ApplicationErrorType::ParseError, "2 problems")
+            .with_related(vec![error.clone(), error]);
+        let rendered = collected.render(false);
+        assert!(rendered.contains("  Problem 2 of 2:\n    error: Malformed input data\n"));
+        assert!(rendered.contains("\n      hint: Fix the file.\n"));
+    }
+
+    #[test]
+    fn test_application_error_from_panic_payload() {
+        let error = ApplicationError::from_panic_payload(
+            &"The collection must not be empty.",
+            Some("src/peaks.rs:1:1".to_string()),
+        );
+        assert_eq!(error.error_type(), ApplicationErrorType::PanicError);
+        assert_eq!(error.error_type().exit_code(), 101);
+        assert_eq!(
+            error.internal_messages(),
+            &vec!["Panicked at src/peaks.rs:1:1: The collection must not be empty.".to_string()]
+        );
+        assert!(error.hints()[0].contains("/issues"));
+        let error = ApplicationError::from_panic_payload(&"formatted".to_string(), None);
+        assert_eq!(error.internal_messages(), &vec!["Panicked: formatted".to_string()]);
+    }
+
+    #[test]
+    fn test_application_error_source() {
+        let error = ApplicationError::from(std::io::Error::other("disk full"))
+            .chain("The output file could not be written.");
+        let source = error.source().unwrap();
+        assert_eq!(source.to_string(), "disk full");
+        assert!(source.downcast_ref::<std::io::Error>().is_some());
+        assert_eq!(error.internal_messages()[0], "disk full");
+        assert!(
+            ApplicationError::new(ApplicationErrorType::InternalError, "cause")
+                .source()
+                .is_none()
+        );
     }
 }
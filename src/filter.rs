@@ -0,0 +1,351 @@
+//! This module filters the records of existing peak files by blacklisted and whitelisted
+//! regions, chromosome, length and score without generating consensus peaks.
+//! Records passing all filters are retained unchanged, including all additional columns.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::Path,
+};
+
+use getset::{CopyGetters, Getters};
+
+use crate::{
+    chromosome::Chromosome,
+    error::ApplicationError,
+    input::{bed_to_peaks, read_decompressed_to_string},
+    peak_set::PeakSet,
+    peaks::PeakData,
+    retry::retry,
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "filter";
+
+/// The reason a peak was removed by a [`PeakFilter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FilterReason {
+    /// The peak overlaps a blacklisted region.
+    Blacklisted,
+    /// The peak does not overlap any whitelisted region.
+    NotWhitelisted,
+    /// The peak is located on an excluded chromosome.
+    Chromosome,
+    /// The peak is shorter or longer than allowed.
+    Length,
+    /// The score of the peak is missing or below the minimum score.
+    Score,
+}
+
+impl std::fmt::Display for FilterReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FilterReason::Blacklisted => write!(f, "blacklisted"),
+            FilterReason::NotWhitelisted => write!(f, "not whitelisted"),
+            FilterReason::Chromosome => write!(f, "chromosome"),
+            FilterReason::Length => write!(f, "length"),
+            FilterReason::Score => write!(f, "score"),
+        }
+    }
+}
+
+/// A set of filters peaks must pass to be retained.
+/// Filters are checked in the order of the [`FilterReason`] variants and
+/// a peak is attributed to the first filter it fails.
+#[derive(Debug, Clone, Default)]
+pub struct PeakFilter {
+    blacklist: Option<PeakSet>,
+    whitelist: Option<PeakSet>,
+    chromosomes: Option<HashSet<Chromosome>>,
+    excluded_chromosomes: HashSet<Chromosome>,
+    min_length: Option<u64>,
+    max_length: Option<u64>,
+    min_score: Option<f64>,
+}
+
+impl PeakFilter {
+    /// Creates a filter retaining all peaks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Removes peaks overlapping any of the specified regions.
+    ///
+    /// # Parameters
+    ///
+    /// * `blacklist` - the blacklisted regions
+    pub fn blacklist(mut self, blacklist: PeakSet) -> Self {
+        self.blacklist = Some(blacklist);
+        self
+    }
+
+    /// Removes peaks not overlapping any of the specified regions.
+    ///
+    /// # Parameters
+    ///
+    /// * `whitelist` - the whitelisted regions
+    pub fn whitelist(mut self, whitelist: PeakSet) -> Self {
+        self.whitelist = Some(whitelist);
+        self
+    }
+
+    /// Removes peaks on chromosomes other than the specified ones.
+    ///
+    /// # Parameters
+    ///
+    /// * `chromosomes` - the chromosomes to retain peaks on
+    pub fn chromosomes<I: IntoIterator<Item = Chromosome>>(mut self, chromosomes: I) -> Self {
+        self.chromosomes = Some(chromosomes.into_iter().collect());
+        self
+    }
+
+    /// Removes peaks on the specified chromosomes.
+    ///
+    /// # Parameters
+    ///
+    /// * `chromosomes` - the chromosomes to remove peaks from
+    pub fn exclude_chromosomes<I: IntoIterator<Item = Chromosome>>(
+        mut self,
+        chromosomes: I,
+    ) -> Self {
+        self.excluded_chromosomes = chromosomes.into_iter().collect();
+        self
+    }
+
+    /// Removes peaks shorter than the specified length.
+    ///
+    /// # Parameters
+    ///
+    /// * `min_length` - the minimum peak length in bases
+    pub fn min_length(mut self, min_length: u64) -> Self {
+        self.min_length = Some(min_length);
+        self
+    }
+
+    /// Removes peaks longer than the specified length.
+    ///
+    /// # Parameters
+    ///
+    /// * `max_length` - the maximum peak length in bases
+    pub fn max_length(mut self, max_length: u64) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    /// Removes peaks without a score or with a score below the specified value.
+    ///
+    /// # Parameters
+    ///
+    /// * `min_score` - the minimum score in column 5
+    pub fn min_score(mut self, min_score: f64) -> Self {
+        self.min_score = Some(min_score);
+        self
+    }
+
+    /// Returns the first filter the peak fails or `None` if the peak is retained.
+    ///
+    /// # Parameters
+    ///
+    /// * `chromosome` - the chromosome of the peak
+    /// * `peak` - the peak
+    /// * `score` - the score of the peak if present
+    pub fn rejection(
+        &self,
+        chromosome: Chromosome,
+        peak: &PeakData,
+        score: Option<f64>,
+    ) -> Option<FilterReason> {
+        let overlaps = |regions: &PeakSet| {
+            !regions
+                .overlapping(chromosome, peak.start(), peak.end())
+                .is_empty()
+        };
+        if self.blacklist.as_ref().is_some_and(overlaps) {
+            Some(FilterReason::Blacklisted)
+        } else if self
+            .whitelist
+            .as_ref()
+            .is_some_and(|whitelist| !overlaps(whitelist))
+        {
+            Some(FilterReason::NotWhitelisted)
+        } else if self.excluded_chromosomes.contains(&chromosome)
+            || self
+                .chromosomes
+                .as_ref()
+                .is_some_and(|chromosomes| !chromosomes.contains(&chromosome))
+        {
+            Some(FilterReason::Chromosome)
+        } else if self
+            .min_length
+            .is_some_and(|min_length| peak.length() < min_length)
+            || self
+                .max_length
+                .is_some_and(|max_length| peak.length() > max_length)
+        {
+            Some(FilterReason::Length)
+        } else if self
+            .min_score
+            .is_some_and(|min_score| score.is_none_or(|score| score < min_score))
+        {
+            Some(FilterReason::Score)
+        } else {
+            None
+        }
+    }
+}
+
+/// The records retained by filtering a peak file and the number of removed peaks.
+#[derive(Getters, CopyGetters, Debug, Clone, PartialEq)]
+pub struct FilteredPeakFile {
+    /// The retained lines including comment and header lines.
+    #[getset(get = "pub")]
+    content: String,
+    /// The number of retained peaks.
+    #[getset(get_copy = "pub")]
+    retained: usize,
+    /// The number of removed peaks per filter.
+    #[getset(get = "pub")]
+    removed: BTreeMap<FilterReason, usize>,
+}
+
+/// Filters the records of a BED3+ / narrowPeak file.
+/// Records are parsed and validated like input files of the consensus peak generation and
+/// retained unchanged if they pass all filters, so additional columns are preserved.
+/// Blank, comment and header lines are retained as well.
+///
+/// # Parameters
+///
+/// * `path` - the path of the peak file
+/// * `filter` - the filters to apply
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or contains invalid records.
+pub fn filter_peak_file<T: AsRef<Path>>(
+    path: T,
+    filter: &PeakFilter,
+) -> Result<FilteredPeakFile, ApplicationError> {
+    let path = path.as_ref();
+    let mut peaks: Vec<(Chromosome, PeakData)> = bed_to_peaks(&[path])?
+        .into_iter()
+        .flat_map(|(chromosome, peaks)| peaks.into_iter().map(move |peak| (chromosome, peak)))
+        .collect();
+    // Peak identifiers are assigned in the order of the records.
+    peaks.sort_unstable_by_key(|(_, peak)| peak.id());
+    let content =
+        retry(&format!("Reading {}", path.display()), || read_decompressed_to_string(path))
+            .map_err(|err| {
+                ApplicationError::from(err)
+                    .chain(format!("The peak file \"{}\" could not be read.", path.display()))
+            })?;
+    let mut filtered = FilteredPeakFile {
+        content: String::new(),
+        retained: 0,
+        removed: BTreeMap::new(),
+    };
+    let mut records = peaks.iter();
+    for line in content.split_inclusive('\n') {
+        let fields: Vec<&str> = line
+            .trim_end_matches(['\n', '\r'])
+            .split(&[' ', '\t'])
+            .filter(|split| !split.is_empty())
+            .collect();
+        if fields.is_empty() || fields[0].starts_with('#') {
+            filtered.content.push_str(line);
+            continue;
+        }
+        let (chromosome, peak) = records
+            .next()
+            .expect("Each record line must have been parsed as peak.");
+        let score = fields.get(4).and_then(|score| score.parse::<f64>().ok());
+        match filter.rejection(*chromosome, peak, score) {
+            Some(reason) => *filtered.removed.entry(reason).or_default() += 1,
+            None => {
+                filtered.retained += 1;
+                filtered.content.push_str(line);
+            },
+        }
+    }
+    log::info!(
+        target: LOG_TARGET,
+        "Retained {} and removed {} peaks of {}.",
+        filtered.retained,
+        filtered.removed.values().sum::<usize>(),
+        path.display()
+    );
+    Ok(filtered)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::test_resources;
+
+    use super::*;
+
+    fn regions(regions: &[(&str, u64, u64)]) -> PeakSet {
+        regions
+            .iter()
+            .enumerate()
+            .map(|(id, (chromosome, start, end))| {
+                (Chromosome::from(*chromosome), PeakData::new(id, *start, *end, *start).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_peak_filter_rejection() {
+        let chr1 = Chromosome::from("chr1");
+        let chr2 = Chromosome::from("chr2");
+        let peak = PeakData::new(0, 100u64, 199u64, 150u64).unwrap();
+        assert_eq!(PeakFilter::new().rejection(chr1, &peak, None), None);
+        let blacklist = PeakFilter::new().blacklist(regions(&[("chr1", 190, 300)]));
+        assert_eq!(blacklist.rejection(chr1, &peak, None), Some(FilterReason::Blacklisted));
+        assert_eq!(blacklist.rejection(chr2, &peak, None), None);
+        let whitelist = PeakFilter::new().whitelist(regions(&[("chr1", 0, 100)]));
+        assert_eq!(whitelist.rejection(chr1, &peak, None), None);
+        assert_eq!(whitelist.rejection(chr2, &peak, None), Some(FilterReason::NotWhitelisted));
+        let chromosomes = PeakFilter::new().chromosomes([chr1]);
+        assert_eq!(chromosomes.rejection(chr2, &peak, None), Some(FilterReason::Chromosome));
+        let excluded = PeakFilter::new().exclude_chromosomes([chr1]);
+        assert_eq!(excluded.rejection(chr1, &peak, None), Some(FilterReason::Chromosome));
+        assert_eq!(excluded.rejection(chr2, &peak, None), None);
+        assert_eq!(
+            PeakFilter::new()
+                .min_length(100)
+                .rejection(chr1, &peak, None),
+            None
+        );
+        assert_eq!(
+            PeakFilter::new()
+                .min_length(101)
+                .rejection(chr1, &peak, None),
+            Some(FilterReason::Length)
+        );
+        assert_eq!(
+            PeakFilter::new()
+                .max_length(99)
+                .rejection(chr1, &peak, None),
+            Some(FilterReason::Length)
+        );
+        let score = PeakFilter::new().min_score(10.0);
+        assert_eq!(score.rejection(chr1, &peak, Some(10.0)), None);
+        assert_eq!(score.rejection(chr1, &peak, Some(9.5)), Some(FilterReason::Score));
+        assert_eq!(score.rejection(chr1, &peak, None), Some(FilterReason::Score));
+    }
+
+    #[test]
+    fn test_filter_peak_file() {
+        let path = test_resources().join("input_test_main_internal_input_01.narrowPeak");
+        let filtered = filter_peak_file(&path, &PeakFilter::new()).unwrap();
+        assert_eq!(filtered.content(), &std::fs::read_to_string(&path).unwrap());
+        assert_eq!(filtered.retained(), 4);
+        assert!(filtered.removed().is_empty());
+        let filtered = filter_peak_file(&path, &PeakFilter::new().max_length(300)).unwrap();
+        assert_eq!(filtered.retained(), 3);
+        assert_eq!(filtered.removed(), &BTreeMap::from([(FilterReason::Length, 1)]));
+        assert!(filtered.content().starts_with("# Broad fragment"));
+        assert!(!filtered.content().contains("chr1\t500\t1000"));
+        let filtered = filter_peak_file(&path, &PeakFilter::new().min_score(400.0)).unwrap();
+        assert_eq!(filtered.retained(), 0);
+        assert_eq!(filtered.removed(), &BTreeMap::from([(FilterReason::Score, 4)]));
+    }
+}
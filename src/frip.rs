@@ -0,0 +1,252 @@
+//! This module calculates the fraction of reads in peaks (FRiP) from BAM files.
+
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use getset::{CopyGetters, Getters};
+use noodles_bam as bam;
+use noodles_sam as sam;
+
+use crate::{chromosome::Chromosome, error::ApplicationError, peak_set::PeakSet};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "frip";
+
+/// The read statistics of a single sample.
+#[derive(CopyGetters, Getters, Clone, PartialEq, Eq, Debug)]
+pub struct FripStatistics {
+    /// The BAM file of the sample.
+    #[getset(get = "pub")]
+    sample: PathBuf,
+    /// The number of counted reads.
+    #[getset(get_copy = "pub")]
+    total_reads: u64,
+    /// The number of counted reads overlapping at least one peak.
+    #[getset(get_copy = "pub")]
+    reads_in_peaks: u64,
+    /// The total length of all reference sequences of the sample.
+    #[getset(get_copy = "pub")]
+    genome_size: u64,
+    /// The number of reference bases covered by at least one peak.
+    #[getset(get_copy = "pub")]
+    peak_bases: u64,
+}
+
+impl FripStatistics {
+    /// Returns the fraction of reads in peaks or `0.0` if no reads were counted.
+    pub fn frip(&self) -> f64 {
+        if self.total_reads == 0 {
+            0.0
+        } else {
+            self.reads_in_peaks as f64 / self.total_reads as f64
+        }
+    }
+
+    /// Returns the enrichment of reads in peaks, which is the fraction of reads in peaks
+    /// relative to the fraction of the genome covered by peaks.
+    /// A value of `1.0` means reads are not enriched in peaks at all.
+    /// Returns `0.0` if no peak bases or reference sequences are present.
+    pub fn enrichment(&self) -> f64 {
+        if self.peak_bases == 0 || self.genome_size == 0 {
+            0.0
+        } else {
+            self.frip() / (self.peak_bases as f64 / self.genome_size as f64)
+        }
+    }
+}
+
+/// Calculates the fraction of reads overlapping the specified peaks.
+/// Only mapped primary alignments are counted, so unmapped, secondary and
+/// supplementary records are skipped.
+///
+/// # Parameters
+///
+/// * `bam_path` - the path to the BAM file of the sample
+/// * `peaks` - the peaks to count the reads in
+///
+/// # Errors
+///
+/// Returns an error if the BAM file cannot be read.
+pub fn fraction_of_reads_in_peaks<T: AsRef<Path>>(
+    bam_path: T,
+    peaks: &PeakSet,
+) -> Result<FripStatistics, ApplicationError> {
+    log::info!(target: LOG_TARGET, "Counting reads in peaks for {}.", bam_path.as_ref().display());
+    let read_error = |err: std::io::Error| {
+        ApplicationError::from(err)
+            .chain(format!("The BAM file \"{}\" could not be read.", bam_path.as_ref().display()))
+    };
+    let mut reader = File::open(&bam_path)
+        .map(bam::io::Reader::new)
+        .map_err(read_error)?;
+    let header = reader.read_header().map_err(read_error)?;
+    // Resolves the chromosome of each reference sequence once instead of per record.
+    let chromosomes: Vec<Chromosome> = header
+        .reference_sequences()
+        .keys()
+        .map(|name| Chromosome::intern(String::from_utf8_lossy(name)))
+        .collect();
+    let genome_size = header
+        .reference_sequences()
+        .values()
+        .map(|reference_sequence| usize::from(reference_sequence.length()) as u64)
+        .sum();
+    let peak_bases = peaks
+        .merge()
+        .iter()
+        .map(|(_, peak)| peak.end() - peak.start() + 1)
+        .sum();
+    let mut total_reads = 0;
+    let mut reads_in_peaks = 0;
+    let mut record = bam::Record::default();
+    while reader.read_record(&mut record).map_err(read_error)? != 0 {
+        let flags = record.flags();
+        if flags.is_unmapped() || flags.is_secondary() || flags.is_supplementary() {
+            continue;
+        }
+        let (Some(reference_sequence_id), Some(start), Some(end)) = (
+            record.reference_sequence_id(),
+            record.alignment_start(),
+            sam::alignment::Record::alignment_end(&record),
+        ) else {
+            continue;
+        };
+        total_reads += 1;
+        let chromosome = chromosomes.get(reference_sequence_id.map_err(read_error)?);
+        // Converts the 1-based alignment coordinates to the 0-based peak coordinates.
+        let start = usize::from(start.map_err(read_error)?) as u64 - 1;
+        let end = usize::from(end.map_err(read_error)?) as u64 - 1;
+        if chromosome
+            .is_some_and(|chromosome| !peaks.overlapping(*chromosome, start, end).is_empty())
+        {
+            reads_in_peaks += 1;
+        }
+    }
+    Ok(FripStatistics {
+        sample: bam_path.as_ref().to_path_buf(),
+        total_reads,
+        reads_in_peaks,
+        genome_size,
+        peak_bases,
+    })
+}
+
+/// Writes the FRiP statistics of all samples as tab separated QC table.
+///
+/// # Parameters
+///
+/// * `path` - the path of the QC table
+/// * `statistics` - the statistics of all samples
+///
+/// # Errors
+///
+/// Returns an error if the QC table cannot be written.
+pub fn write_frip_table<T: AsRef<Path>>(
+    path: T,
+    statistics: &[FripStatistics],
+) -> Result<(), ApplicationError> {
+    log::info!(target: LOG_TARGET, "Writing FRiP statistics to {}.", path.as_ref().display());
+    let mut table = String::from(
+        "sample\ttotal_reads\treads_in_peaks\tfrip\tgenome_size\tpeak_bases\tenrichment\n",
+    );
+    for sample_statistics in statistics {
+        table.push_str(&format!(
+            "{}\t{}\t{}\t{:.6}\t{}\t{}\t{:.6}\n",
+            sample_statistics.sample().display(),
+            sample_statistics.total_reads(),
+            sample_statistics.reads_in_peaks(),
+            sample_statistics.frip(),
+            sample_statistics.genome_size(),
+            sample_statistics.peak_bases(),
+            sample_statistics.enrichment()
+        ));
+    }
+    File::create(&path)
+        .and_then(|mut file| file.write_all(table.as_bytes()))
+        .map_err(|err| {
+            ApplicationError::from(err).chain(format!(
+                "The FRiP table \"{}\" could not be written.",
+                path.as_ref().display()
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use sam::alignment::io::Write;
+
+    use crate::{peaks::PeakData, test_utils::test_output};
+
+    use super::*;
+
+    /// Writes a BAM file containing the records of the specified SAM text.
+    fn write_test_bam(path: &Path, sam_text: &str) {
+        let mut sam_reader = sam::io::Reader::new(sam_text.as_bytes());
+        let header = sam_reader.read_header().unwrap();
+        let mut writer = bam::io::Writer::new(File::create(path).unwrap());
+        writer.write_header(&header).unwrap();
+        for record in sam_reader.record_bufs(&header) {
+            writer
+                .write_alignment_record(&header, &record.unwrap())
+                .unwrap();
+        }
+        writer.try_finish().unwrap();
+    }
+
+    #[test]
+    fn test_fraction_of_reads_in_peaks() {
+        let bam_path = test_output().join("test_fraction_of_reads_in_peaks.bam");
+        write_test_bam(
+            &bam_path,
+            "@HD\tVN:1.6\n\
+            @SQ\tSN:chr1\tLN:10000\n\
+            @SQ\tSN:test_frip_chr2\tLN:10000\n\
+            read_1\t0\tchr1\t101\t60\t60M\t*\t0\t0\t*\t*\n\
+            read_2\t0\tchr1\t181\t60\t20M\t*\t0\t0\t*\t*\n\
+            read_3\t0\tchr1\t1001\t60\t20M\t*\t0\t0\t*\t*\n\
+            read_4\t0\ttest_frip_chr2\t101\t60\t20M\t*\t0\t0\t*\t*\n\
+            read_5\t4\t*\t0\t0\t*\t*\t0\t0\t*\t*\n\
+            read_6\t256\tchr1\t101\t60\t50M\t*\t0\t0\t*\t*\n",
+        );
+        let peaks: PeakSet = [
+            (Chromosome::from("chr1"), PeakData::new(0, 150u64, 200u64, 175u64).unwrap()),
+            (Chromosome::from("chr1"), PeakData::new(1, 2000u64, 2100u64, 2050u64).unwrap()),
+        ]
+        .into_iter()
+        .collect();
+        let statistics = fraction_of_reads_in_peaks(&bam_path, &peaks).unwrap();
+        assert_eq!(statistics.total_reads(), 4);
+        assert_eq!(statistics.reads_in_peaks(), 2);
+        assert_eq!(statistics.frip(), 0.5);
+        assert_eq!(statistics.genome_size(), 20000);
+        assert_eq!(statistics.peak_bases(), 152);
+        assert!((statistics.enrichment() - 0.5 / (152.0 / 20000.0)).abs() < 1e-9);
+
+        let table_path = test_output().join("test_fraction_of_reads_in_peaks.tsv");
+        write_frip_table(&table_path, &[statistics]).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&table_path).unwrap(),
+            format!(
+                "sample\ttotal_reads\treads_in_peaks\tfrip\tgenome_size\tpeak_bases\t\
+                enrichment\n{}\t4\t2\t0.500000\t20000\t152\t65.789474\n",
+                bam_path.display()
+            )
+        );
+        std::fs::remove_file(bam_path).unwrap();
+        std::fs::remove_file(table_path).unwrap();
+    }
+
+    #[test]
+    fn test_fraction_of_reads_in_peaks_missing_file() {
+        assert!(
+            fraction_of_reads_in_peaks(
+                test_output().join("test_fraction_of_reads_in_peaks_missing_file.bam"),
+                &PeakSet::new()
+            )
+            .is_err()
+        );
+    }
+}
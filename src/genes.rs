@@ -0,0 +1,419 @@
+//! This module annotates peaks with the nearest gene based on the transcription start sites
+//! (TSS) of a [GTF](https://www.ensembl.org/info/website/upload/gff.html) gene annotation.
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+};
+
+use getset::{CopyGetters, Getters};
+
+use crate::{
+    chromosome::Chromosome,
+    error::{ApplicationError, ApplicationErrorType, ErrorContext},
+    peaks::PeakData,
+    retry::{Retrying, retry},
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "genes";
+
+/// The GTF feature type of genes.
+const GENE_FEATURE: &str = "gene";
+
+/// The default maximum distance of a promoter-proximal peak summit to the TSS.
+pub const DEFAULT_PROMOTER_WINDOW: u64 = 2000;
+
+/// The DNA strand a gene is located on.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Strand {
+    /// The forward strand.
+    Forward,
+    /// The reverse strand.
+    Reverse,
+}
+
+impl Display for Strand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Strand::Forward => write!(f, "+"),
+            Strand::Reverse => write!(f, "-"),
+        }
+    }
+}
+
+/// The transcription start site of a gene.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct TranscriptionStartSite {
+    /// The 0-based position of the TSS.
+    position: u64,
+    /// The strand of the gene.
+    strand: Strand,
+    /// The name of the gene.
+    gene: String,
+}
+
+/// The nearest gene of a peak.
+#[derive(CopyGetters, Getters, Clone, PartialEq, Eq, Debug)]
+pub struct NearestGene<'a> {
+    /// The name of the gene.
+    #[getset(get_copy = "pub")]
+    gene: &'a str,
+    /// The strand of the gene.
+    #[getset(get_copy = "pub")]
+    strand: Strand,
+    /// The distance of the peak summit to the TSS in direction of transcription.
+    /// Negative distances are located upstream of the TSS.
+    #[getset(get_copy = "pub")]
+    distance: i64,
+}
+
+/// The transcription start sites of all genes of a gene annotation.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct GeneAnnotation {
+    /// The transcription start sites per chromosome sorted by position.
+    transcription_start_sites: HashMap<Chromosome, Vec<TranscriptionStartSite>>,
+}
+
+impl GeneAnnotation {
+    /// Parses the genes of a GTF file.
+    /// The gene name is taken from the `gene_name` attribute or the `gene_id` attribute
+    /// if no name is present.
+    /// Comment lines and features other than genes are skipped.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - the path of the GTF file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or a gene record is invalid.
+    pub fn from_gtf_file<T: AsRef<Path>>(path: T) -> Result<Self, ApplicationError> {
+        let path = path.as_ref();
+        log::info!(target: LOG_TARGET, "Parsing the gene annotation {}.", path.display());
+        let description = format!("Reading {}", path.display());
+        let read_error = |err: std::io::Error| {
+            ApplicationError::from(err)
+                .chain(format!("The gene annotation \"{}\" could not be read.", path.display()))
+        };
+        let file = retry(&description, || File::open(path)).map_err(read_error)?;
+        let reader = BufReader::new(Retrying::new(file, &description));
+        let mut transcription_start_sites: HashMap<Chromosome, Vec<TranscriptionStartSite>> =
+            HashMap::new();
+        for (line_index, line) in reader.lines().enumerate() {
+            let line = line.map_err(read_error)?;
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line_context = ErrorContext::new()
+                .with_path(path.display())
+                .with_line(line_index as u64 + 1);
+            let invalid_line = |message: String| {
+                ApplicationError::new(ApplicationErrorType::ParseError, message)
+                    .with_context(line_context.clone())
+                    .with_hint(
+                        "GTF files require the 9 tab separated columns seqname, source, feature, \
+                        start, end, score, strand, frame and attribute.",
+                    )
+            };
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 9 {
+                return Err(invalid_line(format!(
+                    "The line contains {} instead of 9 columns.",
+                    fields.len()
+                )));
+            }
+            if fields[2] != GENE_FEATURE {
+                continue;
+            }
+            // GTF coordinates are 1-based and inclusive.
+            let coordinate = |field: &str| {
+                field
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|coordinate| *coordinate > 0)
+                    .ok_or_else(|| {
+                        invalid_line(format!(
+                            "The coordinate \"{}\" is not a valid position.",
+                            field
+                        ))
+                    })
+            };
+            let start = coordinate(fields[3])? - 1;
+            let end = coordinate(fields[4])? - 1;
+            let (strand, position) = match fields[6] {
+                "+" => (Strand::Forward, start),
+                "-" => (Strand::Reverse, end),
+                strand => {
+                    return Err(invalid_line(format!(
+                        "The gene strand \"{}\" is neither \"+\" nor \"-\".",
+                        strand
+                    )));
+                },
+            };
+            let gene = attribute(fields[8], "gene_name")
+                .or_else(|| attribute(fields[8], "gene_id"))
+                .ok_or_else(|| {
+                    invalid_line("The gene has neither a gene_name nor a gene_id.".to_string())
+                })?;
+            transcription_start_sites
+                .entry(Chromosome::from(fields[0]))
+                .or_default()
+                .push(TranscriptionStartSite {
+                    position,
+                    strand,
+                    gene: gene.to_string(),
+                });
+        }
+        for chromosome_sites in transcription_start_sites.values_mut() {
+            chromosome_sites.sort_by_key(|site| site.position);
+        }
+        log::debug!(
+            target: LOG_TARGET,
+            "Parsed {} genes.",
+            transcription_start_sites
+                .values()
+                .map(Vec::len)
+                .sum::<usize>()
+        );
+        Ok(Self {
+            transcription_start_sites,
+        })
+    }
+
+    /// Returns the number of genes.
+    pub fn len(&self) -> usize {
+        self.transcription_start_sites.values().map(Vec::len).sum()
+    }
+
+    /// Returns `true` if the annotation does not contain any genes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the gene with the TSS closest to the peak summit
+    /// or `None` if there are no genes on the chromosome.
+    /// If two genes are equally close, the gene with the lower TSS coordinate is returned.
+    ///
+    /// # Parameters
+    ///
+    /// * `chromosome` - the chromosome the peak is located on
+    /// * `peak` - the peak
+    pub fn nearest_gene(&self, chromosome: Chromosome, peak: &PeakData) -> Option<NearestGene<'_>> {
+        let sites = self.transcription_start_sites.get(&chromosome)?;
+        let next_index = sites.partition_point(|site| site.position < peak.summit());
+        let nearest_site = [next_index.checked_sub(1), Some(next_index)]
+            .into_iter()
+            .flatten()
+            .filter_map(|index| sites.get(index))
+            .min_by_key(|site| site.position.abs_diff(peak.summit()))?;
+        let offset = peak.summit() as i64 - nearest_site.position as i64;
+        Some(NearestGene {
+            gene: &nearest_site.gene,
+            strand: nearest_site.strand,
+            distance: match nearest_site.strand {
+                Strand::Forward => offset,
+                Strand::Reverse => -offset,
+            },
+        })
+    }
+}
+
+/// The location class of a peak relative to the nearest TSS.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PeakClass {
+    /// The peak summit is located within the promoter window around the nearest TSS.
+    Promoter,
+    /// The peak summit is located outside the promoter window of any TSS.
+    Distal,
+}
+
+impl PeakClass {
+    /// Classifies a peak as promoter-proximal or distal.
+    /// Peaks without any gene on their chromosome are distal.
+    ///
+    /// # Parameters
+    ///
+    /// * `nearest_gene` - the nearest gene of the peak if any
+    /// * `promoter_window` - the maximum distance of a promoter-proximal peak summit to the TSS
+    pub fn new(nearest_gene: Option<&NearestGene>, promoter_window: u64) -> Self {
+        match nearest_gene {
+            Some(gene) if gene.distance().unsigned_abs() <= promoter_window => PeakClass::Promoter,
+            _ => PeakClass::Distal,
+        }
+    }
+}
+
+impl Display for PeakClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PeakClass::Promoter => write!(f, "promoter"),
+            PeakClass::Distal => write!(f, "distal"),
+        }
+    }
+}
+
+/// The number of peaks per [`PeakClass`].
+#[derive(CopyGetters, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[getset(get_copy = "pub")]
+pub struct PeakClassCounts {
+    /// The number of promoter-proximal peaks.
+    promoter: usize,
+    /// The number of distal peaks.
+    distal: usize,
+}
+
+impl PeakClassCounts {
+    /// Counts a peak of the specified class.
+    ///
+    /// # Parameters
+    ///
+    /// * `class` - the class of the peak
+    pub fn add(&mut self, class: PeakClass) {
+        match class {
+            PeakClass::Promoter => self.promoter += 1,
+            PeakClass::Distal => self.distal += 1,
+        }
+    }
+
+    /// Returns the total number of peaks.
+    pub fn total(&self) -> usize {
+        self.promoter + self.distal
+    }
+}
+
+/// Writes the number and fraction of peaks per class as tab separated table.
+/// All peaks are reported as `*`.
+///
+/// # Parameters
+///
+/// * `writer` - the writer to write the table to
+/// * `counts` - the number of peaks per class
+///
+/// # Errors
+///
+/// Returns an error if the table cannot be written.
+pub fn write_class_summary<W: Write>(
+    mut writer: W,
+    counts: &PeakClassCounts,
+) -> Result<(), ApplicationError> {
+    let fraction = |count: usize| {
+        if counts.total() == 0 {
+            0.0
+        } else {
+            count as f64 / counts.total() as f64
+        }
+    };
+    let mut table = String::from("class\tpeaks\tfraction\n");
+    for (class, count) in [
+        (PeakClass::Promoter.to_string(), counts.promoter()),
+        (PeakClass::Distal.to_string(), counts.distal()),
+        ("*".to_string(), counts.total()),
+    ] {
+        table.push_str(&format!("{}\t{}\t{:.6}\n", class, count, fraction(count)));
+    }
+    writer.write_all(table.as_bytes()).map_err(|err| {
+        ApplicationError::from(err).chain("The peak class summary could not be written.")
+    })
+}
+
+/// Returns the value of the GTF attribute with the specified key if present.
+///
+/// # Parameters
+///
+/// * `attributes` - the attribute column of a GTF record, such as `gene_id "A"; gene_name "B";`
+/// * `key` - the key of the attribute
+fn attribute<'a>(attributes: &'a str, key: &str) -> Option<&'a str> {
+    attributes
+        .split(';')
+        .filter_map(|attribute| attribute.trim().split_once(' '))
+        .find(|(attribute_key, _)| *attribute_key == key)
+        .map(|(_, value)| value.trim().trim_matches('"'))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::{test_output, test_resources};
+
+    use super::*;
+
+    #[test]
+    fn test_attribute() {
+        let attributes =
+            "gene_id \"ENSG01\"; gene_name \"GENE1\"; gene_biotype \"protein_coding\";";
+        assert_eq!(attribute(attributes, "gene_id"), Some("ENSG01"));
+        assert_eq!(attribute(attributes, "gene_name"), Some("GENE1"));
+        assert_eq!(attribute(attributes, "gene"), None);
+    }
+
+    #[test]
+    fn test_peak_class() {
+        let nearest_gene = |distance: i64| NearestGene {
+            gene: "GENE1",
+            strand: Strand::Forward,
+            distance,
+        };
+        assert_eq!(PeakClass::new(Some(&nearest_gene(-2000)), 2000), PeakClass::Promoter);
+        assert_eq!(PeakClass::new(Some(&nearest_gene(150)), 2000), PeakClass::Promoter);
+        assert_eq!(PeakClass::new(Some(&nearest_gene(2001)), 2000), PeakClass::Distal);
+        assert_eq!(PeakClass::new(None, 2000), PeakClass::Distal);
+
+        let mut counts = PeakClassCounts::default();
+        for class in [PeakClass::Promoter, PeakClass::Distal, PeakClass::Distal] {
+            counts.add(class);
+        }
+        assert_eq!(counts.total(), 3);
+        let mut table = Vec::new();
+        write_class_summary(&mut table, &counts).unwrap();
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            "class\tpeaks\tfraction\n\
+            promoter\t1\t0.333333\n\
+            distal\t2\t0.666667\n\
+            *\t3\t1.000000\n"
+        );
+    }
+
+    #[test]
+    fn test_gene_annotation_from_gtf_file() {
+        let annotation =
+            GeneAnnotation::from_gtf_file(test_resources().join("input_test_genes.gtf")).unwrap();
+        assert_eq!(annotation.len(), 3);
+        let chromosome = Chromosome::from("chr1");
+        let peak = |summit: u64| PeakData::new(0, summit, summit, summit).unwrap();
+        // The forward strand gene GENE1 starts at 1000 (0-based 999).
+        let nearest = annotation.nearest_gene(chromosome, &peak(949)).unwrap();
+        assert_eq!(nearest.gene(), "GENE1");
+        assert_eq!(nearest.strand(), Strand::Forward);
+        assert_eq!(nearest.distance(), -50);
+        // The reverse strand gene ENSG02 without name ends at 5000 (0-based 4999).
+        let nearest = annotation.nearest_gene(chromosome, &peak(4899)).unwrap();
+        assert_eq!(nearest.gene(), "ENSG02");
+        assert_eq!(nearest.strand(), Strand::Reverse);
+        assert_eq!(nearest.distance(), 100);
+        assert_eq!(
+            annotation
+                .nearest_gene(chromosome, &peak(20000))
+                .unwrap()
+                .gene(),
+            "ENSG02"
+        );
+        assert!(
+            annotation
+                .nearest_gene(Chromosome::from("chr3"), &peak(100))
+                .is_none()
+        );
+
+        std::fs::create_dir_all(test_output()).unwrap();
+        let invalid_path = test_output().join("test_gene_annotation_invalid.gtf");
+        std::fs::write(&invalid_path, "chr1\ttest\tgene\t100\t200\t.\t*\t.\tgene_id \"A\";\n")
+            .unwrap();
+        let error = GeneAnnotation::from_gtf_file(&invalid_path).unwrap_err();
+        assert_eq!(error.error_type(), ApplicationErrorType::ParseError);
+        assert_eq!(error.context().and_then(|context| context.line()), Some(1));
+        std::fs::remove_file(&invalid_path).unwrap();
+    }
+}
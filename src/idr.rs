@@ -0,0 +1,592 @@
+//! This module scores the reproducibility of peaks called on exactly two replicates with the
+//! irreproducible discovery rate (IDR) as proposed by
+//! [Li et al. in 2011](https://doi.org/10.1214/11-AOAS466) and used by the ENCODE pipelines.
+//!
+//! Overlapping peaks of both replicates are merged and ranked by a statistical value in each
+//! replicate. A copula mixture model of a reproducible component, whose ranks are correlated
+//! between the replicates, and an irreproducible component, whose ranks are independent,
+//! is fitted to the ranks with the expectation-maximisation algorithm. The local IDR of a
+//! peak is the posterior probability of belonging to the irreproducible component and the
+//! (global) IDR of a peak is the mean local IDR of all peaks that are at least as reproducible.
+
+use std::{collections::HashMap, io::Write};
+
+use getset::{CopyGetters, Getters};
+
+use crate::{
+    chromosome::Chromosome,
+    error::{ApplicationError, ApplicationErrorType},
+    peaks::PeakData,
+    scores::{PeakScores, ScoredPeaks},
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "idr";
+
+/// The default maximum IDR of the reported peaks as used by the ENCODE pipelines.
+pub const DEFAULT_IDR_THRESHOLD: f64 = 0.05;
+
+/// The minimum number of peaks present in both replicates required to fit the model.
+const MIN_PEAKS: usize = 10;
+/// The maximum number of expectation-maximisation iterations.
+const MAX_ITERATIONS: usize = 3000;
+/// The maximum change of any model parameter between two iterations to consider the
+/// model converged.
+const CONVERGENCE_TOLERANCE: f64 = 1e-5;
+/// The number of grid points used to invert the marginal distribution function.
+const GRID_POINTS: usize = 2000;
+/// The bounds of the mean of the reproducible component.
+const MU_BOUNDS: (f64, f64) = (0.0, 20.0);
+/// The bounds of the standard deviation of the reproducible component, which prevent the
+/// reproducible component from collapsing onto a few peaks.
+const SIGMA_BOUNDS: (f64, f64) = (0.2, 20.0);
+/// The bounds of the correlation of the reproducible component.
+const RHO_BOUNDS: (f64, f64) = (0.1, 0.99);
+/// The bounds of the proportion of reproducible peaks.
+const MIXING_PROPORTION_BOUNDS: (f64, f64) = (0.01, 0.99);
+
+/// The statistical value of the narrowPeak format used to rank the peaks of each replicate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum RankingMeasure {
+    /// The signal value of column 7.
+    #[default]
+    Signal,
+    /// The -log10 p-value of column 8.
+    PValue,
+    /// The -log10 q-value of column 9.
+    QValue,
+    /// The score of column 5.
+    Score,
+}
+
+impl RankingMeasure {
+    /// Returns the ranking value of a peak if present.
+    ///
+    /// # Parameters
+    ///
+    /// * `scores` - the statistical values of the peak
+    pub fn value(&self, scores: &PeakScores) -> Option<f64> {
+        match self {
+            RankingMeasure::Signal => scores.signal_value(),
+            RankingMeasure::PValue => scores.p_value(),
+            RankingMeasure::QValue => scores.q_value(),
+            RankingMeasure::Score => scores.score(),
+        }
+    }
+}
+
+impl std::fmt::Display for RankingMeasure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RankingMeasure::Signal => write!(f, "signal"),
+            RankingMeasure::PValue => write!(f, "p-value"),
+            RankingMeasure::QValue => write!(f, "q-value"),
+            RankingMeasure::Score => write!(f, "score"),
+        }
+    }
+}
+
+/// The standard normal cumulative distribution function.
+///
+/// # Parameters
+///
+/// * `x` - the quantile
+fn standard_normal_cdf(x: f64) -> f64 {
+    0.5 * erfc(-x / std::f64::consts::SQRT_2)
+}
+
+/// The complementary error function with a relative error below `1.2e-7`
+/// as given by Press et al. in Numerical Recipes.
+///
+/// # Parameters
+///
+/// * `x` - the argument
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let result = t
+        * (-z * z - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+            .exp();
+    if x >= 0.0 { result } else { 2.0 - result }
+}
+
+/// Returns the ranks of the values scaled to `(0, 1)` with ties receiving their average rank.
+///
+/// # Parameters
+///
+/// * `values` - the values to rank
+fn scaled_ranks(values: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|a, b| values[*a].total_cmp(&values[*b]));
+    let mut ranks = vec![0.0; values.len()];
+    let mut first = 0;
+    while first < order.len() {
+        let mut last = first;
+        while last + 1 < order.len() && values[order[last + 1]] == values[order[first]] {
+            last += 1;
+        }
+        let average_rank = (first + last) as f64 / 2.0 + 1.0;
+        for index in &order[first..=last] {
+            ranks[*index] = average_rank / (values.len() + 1) as f64;
+        }
+        first = last + 1;
+    }
+    ranks
+}
+
+/// The fitted copula mixture model of reproducible and irreproducible peaks.
+#[derive(CopyGetters, Clone, Copy, PartialEq, Debug)]
+#[getset(get_copy = "pub")]
+pub struct IdrModel {
+    /// The mean of the reproducible component.
+    mu: f64,
+    /// The standard deviation of the reproducible component.
+    sigma: f64,
+    /// The correlation of the replicates in the reproducible component.
+    rho: f64,
+    /// The proportion of reproducible peaks.
+    mixing_proportion: f64,
+    /// The number of performed iterations.
+    iterations: usize,
+    /// If the parameters converged before the maximum number of iterations was reached.
+    converged: bool,
+}
+
+impl Default for IdrModel {
+    /// The initial parameters of the expectation-maximisation algorithm as suggested by
+    /// the reference implementation. The parameters are bounded during fitting as by the
+    /// ENCODE IDR implementation.
+    fn default() -> Self {
+        Self {
+            mu: 2.6,
+            sigma: 1.3,
+            rho: 0.8,
+            mixing_proportion: 0.7,
+            iterations: 0,
+            converged: false,
+        }
+    }
+}
+
+impl IdrModel {
+    /// Fits the model to the paired ranking values of the peaks of both replicates.
+    ///
+    /// # Parameters
+    ///
+    /// * `values` - the ranking values of each peak in the first and second replicate
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if less than 10 peaks are specified.
+    pub fn fit(values: &[(f64, f64)]) -> Result<Self, ApplicationError> {
+        if values.len() < MIN_PEAKS {
+            return Err(ApplicationError::new(
+                ApplicationErrorType::ValidationError,
+                format!(
+                    "Only {} peaks are present in both replicates, but at least {} peaks are \
+                     required to estimate the irreproducible discovery rate.",
+                    values.len(),
+                    MIN_PEAKS
+                ),
+            )
+            .with_hint(
+                "Check that both replicates contain the ranking measure and overlapping peaks.",
+            ));
+        }
+        let first_values: Vec<f64> = values.iter().map(|(first, _)| *first).collect();
+        let second_values: Vec<f64> = values.iter().map(|(_, second)| *second).collect();
+        let first_ranks = scaled_ranks(&first_values);
+        let second_ranks = scaled_ranks(&second_values);
+        let mut model = Self::default();
+        while model.iterations < MAX_ITERATIONS && !model.converged {
+            let first_z = model.pseudo_values(&first_ranks);
+            let second_z = model.pseudo_values(&second_ranks);
+            let weights: Vec<f64> = first_z
+                .iter()
+                .zip(&second_z)
+                .map(|(z_1, z_2)| 1.0 - model.local_idr_of_pseudo_values(*z_1, *z_2))
+                .collect();
+            let weight_sum: f64 = weights.iter().sum::<f64>().max(f64::MIN_POSITIVE);
+            let weighted_sum = |term: &dyn Fn(f64, f64) -> f64| {
+                weights
+                    .iter()
+                    .zip(first_z.iter().zip(&second_z))
+                    .map(|(weight, (z_1, z_2))| weight * term(*z_1, *z_2))
+                    .sum::<f64>()
+            };
+            let mu = (weighted_sum(&|z_1, z_2| z_1 + z_2) / (2.0 * weight_sum))
+                .clamp(MU_BOUNDS.0, MU_BOUNDS.1);
+            let variance = weighted_sum(&|z_1, z_2| (z_1 - mu).powi(2) + (z_2 - mu).powi(2))
+                / (2.0 * weight_sum);
+            let sigma = variance.sqrt().clamp(SIGMA_BOUNDS.0, SIGMA_BOUNDS.1);
+            let rho = (weighted_sum(&|z_1, z_2| (z_1 - mu) * (z_2 - mu))
+                / (sigma.powi(2) * weight_sum))
+                .clamp(RHO_BOUNDS.0, RHO_BOUNDS.1);
+            let mixing_proportion = (weight_sum / values.len() as f64)
+                .clamp(MIXING_PROPORTION_BOUNDS.0, MIXING_PROPORTION_BOUNDS.1);
+            let change = (mu - model.mu)
+                .abs()
+                .max((sigma - model.sigma).abs())
+                .max((rho - model.rho).abs())
+                .max((mixing_proportion - model.mixing_proportion).abs());
+            model = Self {
+                mu,
+                sigma,
+                rho,
+                mixing_proportion,
+                iterations: model.iterations + 1,
+                converged: change < CONVERGENCE_TOLERANCE,
+            };
+        }
+        if model.converged {
+            log::info!(
+                target: LOG_TARGET,
+                "Fitted the IDR model after {} iterations: mu = {:.4}, sigma = {:.4}, rho = {:.4}, \
+                 proportion of reproducible peaks = {:.4}.",
+                model.iterations,
+                model.mu,
+                model.sigma,
+                model.rho,
+                model.mixing_proportion
+            );
+        } else {
+            log::warn!(
+                target: LOG_TARGET,
+                "The IDR model did not converge within {} iterations.",
+                MAX_ITERATIONS
+            );
+        }
+        Ok(model)
+    }
+
+    /// Returns the local IDR of each peak given the ranking values used to fit the model.
+    ///
+    /// # Parameters
+    ///
+    /// * `values` - the ranking values of each peak in the first and second replicate
+    pub fn local_idr(&self, values: &[(f64, f64)]) -> Vec<f64> {
+        let first_values: Vec<f64> = values.iter().map(|(first, _)| *first).collect();
+        let second_values: Vec<f64> = values.iter().map(|(_, second)| *second).collect();
+        let first_z = self.pseudo_values(&scaled_ranks(&first_values));
+        let second_z = self.pseudo_values(&scaled_ranks(&second_values));
+        first_z
+            .iter()
+            .zip(&second_z)
+            .map(|(z_1, z_2)| self.local_idr_of_pseudo_values(*z_1, *z_2))
+            .collect()
+    }
+
+    /// The marginal cumulative distribution function of the mixture model.
+    ///
+    /// # Parameters
+    ///
+    /// * `z` - the pseudo-value
+    fn marginal_cdf(&self, z: f64) -> f64 {
+        self.mixing_proportion * standard_normal_cdf((z - self.mu) / self.sigma)
+            + (1.0 - self.mixing_proportion) * standard_normal_cdf(z)
+    }
+
+    /// Transforms the scaled ranks into pseudo-values by inverting the marginal cumulative
+    /// distribution function, which is linearly interpolated on a grid.
+    ///
+    /// # Parameters
+    ///
+    /// * `ranks` - the ranks scaled to `(0, 1)`
+    fn pseudo_values(&self, ranks: &[f64]) -> Vec<f64> {
+        let lower = (-8.0f64).min(self.mu - 8.0 * self.sigma);
+        let upper = 8.0f64.max(self.mu + 8.0 * self.sigma);
+        let step = (upper - lower) / (GRID_POINTS - 1) as f64;
+        let grid: Vec<(f64, f64)> = (0..GRID_POINTS)
+            .map(|index| {
+                let z = lower + index as f64 * step;
+                (z, self.marginal_cdf(z))
+            })
+            .collect();
+        ranks
+            .iter()
+            .map(|rank| {
+                let upper_index = grid
+                    .partition_point(|(_, cdf)| cdf < rank)
+                    .clamp(1, GRID_POINTS - 1);
+                let (z_lower, cdf_lower) = grid[upper_index - 1];
+                let (z_upper, cdf_upper) = grid[upper_index];
+                if cdf_upper > cdf_lower {
+                    z_lower
+                        + (z_upper - z_lower)
+                            * ((rank - cdf_lower) / (cdf_upper - cdf_lower)).clamp(0.0, 1.0)
+                } else {
+                    z_lower
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the posterior probability of a pair of pseudo-values to belong to the
+    /// irreproducible component.
+    ///
+    /// # Parameters
+    ///
+    /// * `z_1` - the pseudo-value of the first replicate
+    /// * `z_2` - the pseudo-value of the second replicate
+    fn local_idr_of_pseudo_values(&self, z_1: f64, z_2: f64) -> f64 {
+        let a = (z_1 - self.mu) / self.sigma;
+        let b = (z_2 - self.mu) / self.sigma;
+        let one_minus_rho_squared = 1.0 - self.rho.powi(2);
+        let log_reproducible = -(a * a - 2.0 * self.rho * a * b + b * b)
+            / (2.0 * one_minus_rho_squared)
+            - 2.0 * self.sigma.ln()
+            - 0.5 * one_minus_rho_squared.ln();
+        let log_irreproducible = -(z_1 * z_1 + z_2 * z_2) / 2.0;
+        let odds = (1.0 - self.mixing_proportion) / self.mixing_proportion
+            * (log_irreproducible - log_reproducible).exp();
+        if odds.is_infinite() {
+            1.0
+        } else {
+            odds / (1.0 + odds)
+        }
+    }
+}
+
+/// A merged peak of both replicates annotated with its reproducibility.
+#[derive(Getters, CopyGetters, Clone, PartialEq, Debug)]
+pub struct IdrPeak {
+    /// The chromosome of the peak.
+    #[getset(get_copy = "pub")]
+    chromosome: Chromosome,
+    /// The region spanning the overlapping peaks of both replicates.
+    #[getset(get = "pub")]
+    peak: PeakData,
+    /// The ranking values of the peak in the first and second replicate.
+    #[getset(get_copy = "pub")]
+    values: (f64, f64),
+    /// The local IDR of the peak.
+    #[getset(get_copy = "pub")]
+    local_idr: f64,
+    /// The global IDR of the peak.
+    #[getset(get_copy = "pub")]
+    idr: f64,
+}
+
+impl IdrPeak {
+    /// Returns the score of the peak as defined by the ENCODE pipelines,
+    /// which is `min(int(-125 * log2(IDR)), 1000)`.
+    pub fn scaled_score(&self) -> u64 {
+        (-125.0 * self.idr.max(f64::MIN_POSITIVE).log2()).clamp(0.0, 1000.0) as u64
+    }
+}
+
+/// Merges the overlapping peaks of both replicates and scores their reproducibility.
+/// Merged peaks without a peak or a ranking value in either replicate are discarded.
+/// The ranking value of a merged peak in a replicate is the maximum value of its
+/// overlapping peaks and its summit is the midpoint of the summits of the highest ranked
+/// peaks of both replicates. The peaks are sorted by chromosome and coordinates.
+///
+/// # Parameters
+///
+/// * `first_replicate` - the peaks of the first replicate
+/// * `second_replicate` - the peaks of the second replicate
+/// * `measure` - the statistical value to rank the peaks by
+///
+/// # Errors
+///
+/// Returns an error if the model cannot be fitted.
+pub fn idr_peaks(
+    first_replicate: &ScoredPeaks,
+    second_replicate: &ScoredPeaks,
+    measure: RankingMeasure,
+) -> Result<(IdrModel, Vec<IdrPeak>), ApplicationError> {
+    let merged = first_replicate
+        .peaks()
+        .union(second_replicate.peaks())
+        .merge();
+    let mut chromosomes = merged.chromosomes();
+    chromosomes.sort();
+    let best_peak = |replicate: &ScoredPeaks, chromosome: Chromosome, region: &PeakData| {
+        replicate
+            .peaks()
+            .overlapping(chromosome, region.start(), region.end())
+            .into_iter()
+            .filter_map(|peak| {
+                replicate
+                    .scores()
+                    .get(&peak.id())
+                    .and_then(|scores| measure.value(scores))
+                    .map(|value| (*peak, value))
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+    };
+    let mut regions: Vec<(Chromosome, PeakData, (f64, f64))> = Vec::new();
+    for chromosome in chromosomes {
+        for region in merged.peaks(chromosome) {
+            if let (Some((first_peak, first_value)), Some((second_peak, second_value))) = (
+                best_peak(first_replicate, chromosome, region),
+                best_peak(second_replicate, chromosome, region),
+            ) {
+                let peak = PeakData::new(
+                    regions.len(),
+                    region.start(),
+                    region.end(),
+                    first_peak.summit().midpoint(second_peak.summit()),
+                )?;
+                regions.push((chromosome, peak, (first_value, second_value)));
+            }
+        }
+    }
+    log::info!(
+        target: LOG_TARGET,
+        "{} of {} merged peaks are present in both replicates.",
+        regions.len(),
+        merged.len()
+    );
+    let values: Vec<(f64, f64)> = regions.iter().map(|(_, _, values)| *values).collect();
+    let model = IdrModel::fit(&values)?;
+    let local_idr = model.local_idr(&values);
+    // The global IDR is the mean local IDR of all peaks with a lower or equal local IDR.
+    let mut order: Vec<usize> = (0..local_idr.len()).collect();
+    order.sort_by(|a, b| local_idr[*a].total_cmp(&local_idr[*b]));
+    let mut idr: HashMap<usize, f64> = HashMap::new();
+    let mut cumulative_local_idr = 0.0;
+    for (count, index) in order.into_iter().enumerate() {
+        cumulative_local_idr += local_idr[index];
+        idr.insert(index, cumulative_local_idr / (count + 1) as f64);
+    }
+    let peaks = regions
+        .into_iter()
+        .enumerate()
+        .map(|(index, (chromosome, peak, values))| IdrPeak {
+            chromosome,
+            peak,
+            values,
+            local_idr: local_idr[index],
+            idr: idr[&index],
+        })
+        .collect();
+    Ok((model, peaks))
+}
+
+/// Writes the peaks with an IDR of at most the threshold in the narrowPeak format extended
+/// by the columns of the ENCODE IDR output. Column 5 contains the scaled IDR score, column 7
+/// the mean ranking value of both replicates, columns 11 and 12 the -log10 local and global
+/// IDR and columns 13 and 14 the ranking values of the first and second replicate.
+///
+/// # Parameters
+///
+/// * `writer` - the writer to write the peaks to
+/// * `peaks` - the peaks annotated with their reproducibility
+/// * `threshold` - the maximum IDR of the written peaks
+///
+/// # Errors
+///
+/// Returns an error if the threshold is not within `[0, 1]` or writing fails.
+pub fn write_idr_peaks<W: Write>(
+    mut writer: W,
+    peaks: &[IdrPeak],
+    threshold: f64,
+) -> Result<(), ApplicationError> {
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err(ApplicationError::new(
+            ApplicationErrorType::ConfigError,
+            format!("The IDR threshold {} is not within [0, 1].", threshold),
+        ));
+    }
+    let neg_log10 = |value: f64| -value.max(f64::MIN_POSITIVE).log10();
+    let mut records = String::new();
+    let mut written = 0;
+    for peak in peaks.iter().filter(|peak| peak.idr() <= threshold) {
+        let (first_value, second_value) = peak.values();
+        records.push_str(&format!(
+            "{}\t{}\t{}\tidr_{}\t{}\t.\t{:.5}\t-1\t-1\t{}\t{:.5}\t{:.5}\t{:.5}\t{:.5}\n",
+            peak.chromosome(),
+            peak.peak().start(),
+            peak.peak().end(),
+            peak.peak().id(),
+            peak.scaled_score(),
+            (first_value + second_value) / 2.0,
+            peak.peak().summit() - peak.peak().start(),
+            neg_log10(peak.local_idr()),
+            neg_log10(peak.idr()),
+            first_value,
+            second_value
+        ));
+        written += 1;
+    }
+    log::info!(
+        target: LOG_TARGET,
+        "{} of {} peaks pass the IDR threshold of {}.",
+        written,
+        peaks.len(),
+        threshold
+    );
+    writer
+        .write_all(records.as_bytes())
+        .map_err(|err| ApplicationError::from(err).chain("The IDR peaks could not be written."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns 100 peaks with strongly correlated high values followed by 100 peaks with
+    /// uncorrelated low values.
+    fn replicate_values() -> Vec<(f64, f64)> {
+        (0..100)
+            .map(|index| (200.0 - index as f64, 200.0 - index as f64 + ((index * 7) % 10) as f64))
+            .chain((0..100).map(|index| (((index * 37) % 100) as f64, ((index * 61) % 100) as f64)))
+            .collect()
+    }
+
+    #[test]
+    fn test_standard_normal_cdf() {
+        assert!((standard_normal_cdf(0.0) - 0.5).abs() < 1e-7);
+        assert!((standard_normal_cdf(1.96) - 0.9750021).abs() < 1e-6);
+        assert!((standard_normal_cdf(-1.0) - 0.1586553).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_scaled_ranks() {
+        assert_eq!(scaled_ranks(&[3.0, 1.0, 3.0, 2.0]), vec![0.7, 0.2, 0.7, 0.4]);
+    }
+
+    #[test]
+    fn test_idr_model_fit() {
+        let values = replicate_values();
+        let model = IdrModel::fit(&values).unwrap();
+        assert!(model.mixing_proportion() > 0.3 && model.mixing_proportion() < 0.7, "{:?}", model);
+        assert!(model.converged() && model.rho() > 0.5, "{:?}", model);
+        let local_idr = model.local_idr(&values);
+        let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+        assert!(mean(&local_idr[..100]) < 0.1, "{:?}", local_idr);
+        assert!(mean(&local_idr[100..]) > 0.5, "{:?}", local_idr);
+        assert!(IdrModel::fit(&values[..5]).is_err());
+    }
+
+    #[test]
+    fn test_write_idr_peaks() {
+        let peak = IdrPeak {
+            chromosome: Chromosome::from("chr1"),
+            peak: PeakData::new(3, 100u64, 199u64, 150u64).unwrap(),
+            values: (10.0, 20.0),
+            local_idr: 0.01,
+            idr: 0.001,
+        };
+        assert_eq!(peak.scaled_score(), 1000);
+        let mut output = Vec::new();
+        write_idr_peaks(&mut output, std::slice::from_ref(&peak), 0.05).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "chr1\t100\t199\tidr_3\t1000\t.\t15.00000\t-1\t-1\t50\t2.00000\t3.00000\t10.00000\t20.00000\n"
+        );
+        let mut output = Vec::new();
+        write_idr_peaks(&mut output, std::slice::from_ref(&peak), 0.0001).unwrap();
+        assert!(output.is_empty());
+        assert!(write_idr_peaks(Vec::new(), &[peak], 1.5).is_err());
+    }
+}
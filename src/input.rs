@@ -1,150 +1,1551 @@
 //! This module parses input data.
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet, VecDeque},
     fs::File,
-    io::{BufRead, BufReader},
-    path::Path,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
 };
 
-use crate::{error::ApplicationError, peaks::PeakData};
+use flate2::bufread::MultiGzDecoder;
+
+use crate::{
+    chromosome::Chromosome,
+    error::{ApplicationError, ApplicationErrorType, ErrorContext},
+    peaks::PeakData,
+    retry::{Retrying, retry},
+    scores::PeakScores,
+    strand::Strand,
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "input";
+
+/// The hint shown if the summit offset in column 10 is invalid.
+const SUMMIT_COLUMN_HINT: &str = "Column 10 is interpreted as the narrowPeak summit offset \
+    relative to chromStart. Files of other formats with 10 or more columns, such as BED12, \
+    must be parsed in the BED input format.";
+
+/// The number of lines at the start of a source used to detect its format.
+const FORMAT_DETECTION_LINES: usize = 10;
+
+/// The maximum number of line numbers listed in an aggregated warning.
+const MAX_WARNING_LINES: usize = 5;
+
+/// The default maximum number of input files a [`BedPeakStream`] keeps open at a time,
+/// which is well below the common open file limit of 1024.
+pub const DEFAULT_MAX_OPEN_FILES: usize = 256;
+
+/// The magic bytes at the start of gzip- and BGZF-compressed files.
+const GZIP_MAGIC_BYTES: [u8; 2] = [0x1f, 0x8b];
 
 /// Parses BED3+ files according to the [GA4GH BED v1.0](https://github.com/samtools/hts-specs/blob/master/BEDv1.pdf) definition.
 /// Peak summit information will be extracted from field 10 according to the
 /// [narrowPeak](https://genome.ucsc.edu/FAQ/FAQformat.html#format12) fromat definition if present and possible.
+/// Gzip- and BGZF-compressed files are decompressed transparently.
 ///
 /// # Parameters
 ///
 /// * `paths` - the input file paths
 pub fn bed_to_peaks<T: AsRef<Path>>(
     paths: &[T],
-) -> Result<HashMap<String, Vec<PeakData>>, ApplicationError> {
-    let mut peak_map: HashMap<String, Vec<PeakData>> = HashMap::new();
-    let mut current_peak_id = 0;
-    for path in paths {
-        log::info!("Parsing {}.", path.as_ref().display());
-        let file = File::open(&path).map_err(|err| {
-            ApplicationError::from(err).chain(format!(
-                "The input file \"{}\" could not be opened.",
-                path.as_ref().display()
-            ))
-        })?;
-        for (line_index, line_result) in BufReader::new(file).lines().enumerate() {
-            let line_number = line_index + 1;
-            let line = line_result.map_err(|err| {
-                ApplicationError::from(err).chain(format!(
-                    "Failed to parse line {} of input file \"{}\".",
-                    line_number,
-                    path.as_ref().display()
-                ))
-            })?;
-            let fields: Vec<&str> = line
-                .split(&[' ', '\t'])
-                .filter(|split| !split.is_empty())
+) -> Result<HashMap<Chromosome, Vec<PeakData>>, ApplicationError> {
+    BedParser::default().parse_files(paths)
+}
+
+/// Parses BED3+ files like [`bed_to_peaks`], but continues parsing after invalid records
+/// or files that cannot be read.
+/// If any problems are found, an error containing all collected problems as
+/// [`related`](ApplicationError::related) errors is returned, so all input files
+/// can be fixed at once.
+///
+/// # Parameters
+///
+/// * `paths` - the input file paths
+/// * `max_errors` - the maximum number of problems to collect before parsing is stopped
+pub fn bed_to_peaks_collecting_errors<T: AsRef<Path>>(
+    paths: &[T],
+    max_errors: usize,
+) -> Result<HashMap<Chromosome, Vec<PeakData>>, ApplicationError> {
+    BedParser {
+        max_errors: Some(max_errors.max(1)),
+        ..Default::default()
+    }
+    .parse_files(paths)
+}
+
+/// Parsed peaks grouped by chromosome together with the identifiers of all peaks
+/// whose summit was approximated.
+pub type PeaksWithApproximatedSummits = (HashMap<Chromosome, Vec<PeakData>>, HashSet<usize>);
+
+/// Parses a single BED3+ file like [`bed_to_peaks`] and additionally returns the identifiers
+/// of all peaks whose summit was approximated, as the file did not contain summit information.
+///
+/// # Parameters
+///
+/// * `path` - the input file path
+pub fn bed_to_peaks_with_approximated_summits<T: AsRef<Path>>(
+    path: T,
+) -> Result<PeaksWithApproximatedSummits, ApplicationError> {
+    let mut parser = BedParser {
+        approximated_summit_ids: Some(HashSet::new()),
+        ..Default::default()
+    };
+    let peaks = parser.parse_files(&[path])?;
+    Ok((peaks, parser.approximated_summit_ids.unwrap_or_default()))
+}
+
+/// Parsed peaks grouped by chromosome together with the strands of all peaks by peak ID.
+pub type PeaksWithStrands = (HashMap<Chromosome, Vec<PeakData>>, HashMap<usize, Strand>);
+
+/// Parses BED3+ files like [`bed_to_peaks`] and additionally parses the strand of all peaks
+/// from column 6. Peaks of records without a strand column are not stranded.
+///
+/// # Parameters
+///
+/// * `paths` - the input file paths
+/// * `max_errors` - the maximum number of problems to collect before parsing is stopped
+///   or `None` to fail on the first problem
+pub fn bed_to_peaks_with_strands<T: AsRef<Path>>(
+    paths: &[T],
+    max_errors: Option<usize>,
+) -> Result<PeaksWithStrands, ApplicationError> {
+    let mut parser = BedParser {
+        max_errors: max_errors.map(|max_errors| max_errors.max(1)),
+        strands: Some(HashMap::new()),
+        ..Default::default()
+    };
+    let peaks = parser.parse_files(paths)?;
+    Ok((peaks, parser.strands.unwrap_or_default()))
+}
+
+/// Raw peaks parsed from BED3+ files together with additional information about the peaks.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedPeaks {
+    /// The peaks grouped by chromosome.
+    pub peaks: HashMap<Chromosome, Vec<PeakData>>,
+    /// The strands of all stranded peaks by peak ID if parsed.
+    pub strands: HashMap<usize, Strand>,
+    /// The statistical values of all peaks with any statistical value by peak ID if parsed.
+    pub scores: HashMap<usize, PeakScores>,
+    /// The number of peaks of each input file in input order. As peak IDs are assigned
+    /// consecutively, they map each peak to the file it was parsed from.
+    pub peaks_per_file: Vec<usize>,
+}
+
+impl ParsedPeaks {
+    /// Returns the peaks of each input file in input order.
+    pub fn peaks_by_file(&self) -> Vec<HashMap<Chromosome, Vec<PeakData>>> {
+        let first_ids: Vec<usize> = self
+            .peaks_per_file
+            .iter()
+            .scan(0, |first_id, peaks| {
+                let file_first_id = *first_id;
+                *first_id += peaks;
+                Some(file_first_id)
+            })
+            .collect();
+        let mut files: Vec<HashMap<Chromosome, Vec<PeakData>>> =
+            vec![HashMap::new(); self.peaks_per_file.len()];
+        for (chromosome, peaks) in &self.peaks {
+            for peak in peaks {
+                let file = first_ids
+                    .partition_point(|first_id| *first_id <= peak.id())
+                    .saturating_sub(1);
+                if let Some(file_peaks) = files.get_mut(file) {
+                    file_peaks.entry(*chromosome).or_default().push(*peak);
+                }
+            }
+        }
+        files
+    }
+}
+
+/// Parses BED3+ files of the specified format like [`bed_to_peaks`] and additionally counts
+/// the peaks of each file.
+/// The strands of all peaks are only parsed from column 6 and the statistical values from
+/// columns 5, 7, 8 and 9 if requested and are empty otherwise.
+///
+/// # Parameters
+///
+/// * `paths` - the input file paths
+/// * `input_format` - the format of the input files
+/// * `max_errors` - the maximum number of problems to collect before parsing is stopped
+///   or `None` to fail on the first problem
+/// * `parse_strands` - if the strands of the peaks should be parsed
+/// * `parse_scores` - if the statistical values of the peaks should be parsed
+pub fn bed_to_peaks_with_format<T: AsRef<Path>>(
+    paths: &[T],
+    input_format: InputFormat,
+    max_errors: Option<usize>,
+    parse_strands: bool,
+    parse_scores: bool,
+) -> Result<ParsedPeaks, ApplicationError> {
+    let mut parser = BedParser {
+        max_errors: max_errors.map(|max_errors| max_errors.max(1)),
+        strands: parse_strands.then(HashMap::new),
+        scores: parse_scores.then(HashMap::new),
+        input_format,
+        ..Default::default()
+    };
+    let peaks = parser.parse_files(paths)?;
+    Ok(ParsedPeaks {
+        peaks,
+        strands: parser.strands.unwrap_or_default(),
+        scores: parser.scores.unwrap_or_default(),
+        peaks_per_file: parser.peaks_per_source,
+    })
+}
+
+/// Parses the names (column 4) of all peaks of BED3+ files with peak identifiers assigned like
+/// [`bed_to_peaks`] does. Peaks without a name column or with the name "." are not named.
+///
+/// # Parameters
+///
+/// * `paths` - the input file paths
+/// * `input_format` - the format of the input files
+pub fn bed_to_peak_names<T: AsRef<Path>>(
+    paths: &[T],
+    input_format: InputFormat,
+) -> Result<HashMap<usize, String>, ApplicationError> {
+    let mut parser = BedParser {
+        names: Some(HashMap::new()),
+        input_format,
+        ..Default::default()
+    };
+    parser.parse_files(paths)?;
+    Ok(parser.names.unwrap_or_default())
+}
+
+/// Parses in-memory BED3+ data according to the [GA4GH BED v1.0](https://github.com/samtools/hts-specs/blob/master/BEDv1.pdf) definition.
+/// Each buffer is treated like the content of a single input file, so this function
+/// does not require any file system access.
+///
+/// # Parameters
+///
+/// * `buffers` - the contents of the input files
+pub fn bed_buffers_to_peaks<T: AsRef<[u8]>>(
+    buffers: &[T],
+) -> Result<HashMap<Chromosome, Vec<PeakData>>, ApplicationError> {
+    let mut parser = BedParser::default();
+    for (index, buffer) in buffers.iter().enumerate() {
+        log::info!(target: LOG_TARGET, "Parsing buffer {}.", index);
+        parser.parse(buffer.as_ref(), &format!("buffer {}", index))?;
+    }
+    parser.finish()
+}
+
+/// Creates a [`BedPeakStream`] parsing BED3+ files like [`bed_to_peaks`], but yielding the peaks
+/// chromosome by chromosome, so only the raw peaks of a single chromosome are kept in memory
+/// at a time.
+/// The peaks of each chromosome must be consecutive within each file and all files must list
+/// their shared chromosomes in the same order, which is the case for files sorted the same
+/// way, such as per-sample peak files sorted with `sort -k1,1 -k2,2n`.
+///
+/// # Parameters
+///
+/// * `paths` - the input file paths
+pub fn bed_to_peak_stream<T: AsRef<Path>>(paths: &[T]) -> BedPeakStream {
+    BedPeakStream {
+        parser: BedParser::default(),
+        paths: paths
+            .iter()
+            .map(|path| path.as_ref().to_path_buf())
+            .collect(),
+        files: None,
+        peaks_per_file: Vec::new(),
+        max_open_files: DEFAULT_MAX_OPEN_FILES,
+        failed: false,
+    }
+}
+
+/// Opens a BED file for reading and decompresses its content if required.
+///
+/// # Parameters
+///
+/// * `path` - the input file path
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be opened or read.
+pub(crate) fn open_bed_file(path: &Path) -> Result<Box<dyn BufRead>, ApplicationError> {
+    decompressing_reader(open_buffered_file(path)?).map_err(|err| {
+        ApplicationError::from(err)
+            .chain(format!("The input file \"{}\" could not be read.", path.display()))
+    })
+}
+
+/// Opens a file for buffered reading without decompressing its content.
+///
+/// # Parameters
+///
+/// * `path` - the input file path
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be opened.
+fn open_buffered_file(path: &Path) -> Result<BufReader<Retrying<File>>, ApplicationError> {
+    let source = path.display().to_string();
+    let file = retry(&format!("Opening {}", source), || File::open(path)).map_err(|err| {
+        ApplicationError::from(err)
+            .chain(format!("The input file \"{}\" could not be opened.", source))
+    })?;
+    Ok(BufReader::new(Retrying::new(file, format!("Reading {}", source))))
+}
+
+/// Returns a reader of the decompressed content if the content of the specified reader is
+/// gzip- or BGZF-compressed and a reader of the unchanged content otherwise.
+/// Compression is detected from the content instead of the file extension.
+///
+/// # Parameters
+///
+/// * `reader` - the reader of the possibly compressed content
+///
+/// # Errors
+///
+/// Returns an error if the start of the content cannot be read.
+pub fn decompressing_reader<'a, R: BufRead + 'a>(
+    mut reader: R,
+) -> std::io::Result<Box<dyn BufRead + 'a>> {
+    if is_compressed(&mut reader)? {
+        // BGZF files consist of multiple concatenated gzip members.
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Returns if the content of the specified reader is gzip- or BGZF-compressed.
+///
+/// # Parameters
+///
+/// * `reader` - the reader of the possibly compressed content
+///
+/// # Errors
+///
+/// Returns an error if the start of the content cannot be read.
+fn is_compressed<R: BufRead>(reader: &mut R) -> std::io::Result<bool> {
+    Ok(reader.fill_buf()?.starts_with(&GZIP_MAGIC_BYTES))
+}
+
+/// Reads the complete content of a possibly gzip- or BGZF-compressed text file.
+///
+/// # Parameters
+///
+/// * `path` - the path of the file
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or decompressed or is not valid UTF-8.
+pub fn read_decompressed_to_string<T: AsRef<Path>>(path: T) -> std::io::Result<String> {
+    let mut content = String::new();
+    decompressing_reader(BufReader::new(File::open(path)?))?.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+/// Returns a hint on how to fix a coordinate value that could not be parsed.
+///
+/// # Parameters
+///
+/// * `value` - the invalid coordinate value
+fn coordinate_hint(value: &str) -> &'static str {
+    if value.starts_with('-') {
+        "Genomic coordinates must not be negative."
+    } else if value.chars().any(char::is_alphabetic) {
+        "Header lines must start with \"#\" to be skipped."
+    } else {
+        "Genomic coordinates must be unsigned integers."
+    }
+}
+
+/// The format of BED3+ input files, which determines how their columns are interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum InputFormat {
+    /// Column 10 is interpreted as summit offset if present and summits are approximated
+    /// with a warning otherwise.
+    #[default]
+    Auto,
+    /// narrowPeak files, which must contain the summit offset in column 10.
+    #[cfg_attr(feature = "cli", value(name = "narrowPeak"))]
+    NarrowPeak,
+    /// broadPeak files without summit information.
+    #[cfg_attr(feature = "cli", value(name = "broadPeak"))]
+    BroadPeak,
+    /// BED files without summit information, whose additional columns are ignored.
+    Bed,
+}
+
+impl InputFormat {
+    /// Returns `true` if files of this format are expected to contain summit information.
+    pub fn has_summits(&self) -> bool {
+        matches!(self, Self::Auto | Self::NarrowPeak)
+    }
+}
+
+impl std::fmt::Display for InputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputFormat::Auto => write!(f, "auto"),
+            InputFormat::NarrowPeak => write!(f, "narrowPeak"),
+            InputFormat::BroadPeak => write!(f, "broadPeak"),
+            InputFormat::Bed => write!(f, "bed"),
+        }
+    }
+}
+
+/// Input formats that are commonly confused with the supported BED3+ formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfusedFormat {
+    /// A broadPeak file without summit information.
+    BroadPeak,
+    /// A BED12 file, whose column 10 contains the block count.
+    Bed12,
+    /// A GFF or GTF file with 1-based coordinates in columns 4 and 5.
+    Gff,
+    /// A VCF file with 1-based positions.
+    Vcf,
+    /// A comma separated file.
+    Csv,
+}
+
+impl ConfusedFormat {
+    /// Guesses the format of a source from its first lines.
+    /// Returns `None` if the lines do not indicate any of the known formats.
+    ///
+    /// # Parameters
+    ///
+    /// * `lines` - the first lines of the source
+    fn detect(lines: &[&str]) -> Option<Self> {
+        let lines: Vec<&str> = lines
+            .iter()
+            .map(|line| line.trim_end_matches(['\n', '\r']))
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+        if lines.iter().any(|line| line.starts_with("##gff-version")) {
+            return Some(Self::Gff);
+        }
+        if lines
+            .iter()
+            .any(|line| line.starts_with("##fileformat=VCF"))
+        {
+            return Some(Self::Vcf);
+        }
+        let records: Vec<Vec<&str>> = lines
+            .iter()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| line.split('\t').collect())
+            .collect();
+        if records.is_empty() {
+            return None;
+        }
+        let is_integer =
+            |value: &str| !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit());
+        if records
+            .iter()
+            .all(|fields| fields.len() < 3 && fields[0].split(',').count() >= 3)
+        {
+            Some(Self::Csv)
+        } else if records.iter().all(|fields| {
+            (fields.len() == 8 || fields.len() == 9)
+                && is_integer(fields[3])
+                && is_integer(fields[4])
+                && ["+", "-", "."].contains(&fields[6])
+        }) {
+            Some(Self::Gff)
+        } else if records
+            .iter()
+            .all(|fields| fields.len() == 12 && is_integer(fields[9]))
+        {
+            Some(Self::Bed12)
+        } else if records.iter().all(|fields| fields.len() == 9) {
+            Some(Self::BroadPeak)
+        } else {
+            None
+        }
+    }
+
+    /// Returns a suggestion on how to use a source of this format as input.
+    fn suggestion(&self) -> &'static str {
+        match self {
+            Self::BroadPeak => {
+                "The file appears to be a broadPeak file. broadPeak files do not contain summit \
+                information, so check that the coordinates in columns 2 and 3 are valid."
+            },
+            Self::Bed12 => {
+                "The file appears to be a BED12 file, whose column 10 contains the block count \
+                instead of the summit offset. Parse the file in the BED input format, remove \
+                columns 10 to 12 or convert the file to narrowPeak."
+            },
+            Self::Gff => {
+                "The file appears to be a GFF or GTF file with 1-based coordinates in columns 4 \
+                and 5. Convert it to BED with 0-based start coordinates, for example with \
+                \"awk -v OFS='\\t' '{print $1, $4 - 1, $5}'\"."
+            },
+            Self::Vcf => {
+                "The file appears to be a VCF file with 1-based positions. Convert the variants \
+                to BED regions with 0-based start coordinates."
+            },
+            Self::Csv => {
+                "The file appears to be comma separated. Columns of BED files must be separated \
+                by tabs or spaces."
+            },
+        }
+    }
+}
+
+/// A parser collecting peaks from multiple BED sources.
+#[derive(Default)]
+struct BedParser {
+    /// The peaks parsed so far.
+    peak_map: HashMap<Chromosome, Vec<PeakData>>,
+    /// The identifier of the next parsed peak.
+    current_peak_id: usize,
+    /// The line buffer recycled for all sources.
+    line_buffer: String,
+    /// The maximum number of problems to collect or `None` to fail on the first problem.
+    max_errors: Option<usize>,
+    /// The problems collected so far.
+    errors: Vec<ApplicationError>,
+    /// The lines of the current source whose summit was approximated.
+    approximated_summits: LineSummary,
+    /// The identifiers of all peaks whose summit was approximated or `None` if they are not tracked.
+    approximated_summit_ids: Option<HashSet<usize>>,
+    /// The strands of all stranded peaks or `None` if they are not tracked.
+    strands: Option<HashMap<usize, Strand>>,
+    /// The names of all named peaks or `None` if they are not tracked.
+    names: Option<HashMap<usize, String>>,
+    /// The statistical values of all peaks with any statistical value or `None` if they are
+    /// not tracked.
+    scores: Option<HashMap<usize, PeakScores>>,
+    /// The format of the parsed sources.
+    input_format: InputFormat,
+    /// The number of peaks whose summit was set to the centre, as the input format
+    /// does not contain summit information.
+    centred_summits: usize,
+    /// The first lines of the current source used to detect its format if parsing fails.
+    first_lines: Vec<String>,
+    /// If a format suggestion has already been added to a problem of the current source.
+    format_suggested: bool,
+    /// The identifier of the first peak of the current source.
+    source_first_id: usize,
+    /// The number of peaks of each completely parsed source.
+    peaks_per_source: Vec<usize>,
+}
+
+/// A summary of the lines a repetitive warning applies to.
+#[derive(Default)]
+struct LineSummary {
+    /// The number of affected lines.
+    count: usize,
+    /// The first affected line numbers.
+    first_lines: Vec<u64>,
+}
+
+impl LineSummary {
+    /// Adds an affected line.
+    ///
+    /// # Parameters
+    ///
+    /// * `line_number` - the number of the affected line
+    fn add(&mut self, line_number: u64) {
+        self.count += 1;
+        if self.first_lines.len() < MAX_WARNING_LINES {
+            self.first_lines.push(line_number);
+        }
+    }
+
+    /// Returns the affected line numbers for display, such as `1, 2, 3, ...`.
+    fn format_lines(&self) -> String {
+        let mut lines: Vec<String> = self
+            .first_lines
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        if self.count > self.first_lines.len() {
+            lines.push("...".to_string());
+        }
+        lines.join(", ")
+    }
+}
+
+impl BedParser {
+    /// Parses all peaks of the specified BED files and returns them.
+    ///
+    /// # Parameters
+    ///
+    /// * `paths` - the input file paths
+    fn parse_files<T: AsRef<Path>>(
+        &mut self,
+        paths: &[T],
+    ) -> Result<HashMap<Chromosome, Vec<PeakData>>, ApplicationError> {
+        #[cfg(feature = "parallel")]
+        if paths.len() > 1 {
+            use rayon::prelude::*;
+
+            // Each file is parsed concurrently by its own parser. The parsers are merged
+            // in input order, so the peak identifiers do not depend on scheduling.
+            let paths: Vec<&Path> = paths.iter().map(AsRef::as_ref).collect();
+            let file_parsers: Vec<(BedParser, Result<(), ApplicationError>)> = paths
+                .par_iter()
+                .map(|path| {
+                    let mut file_parser = self.file_parser();
+                    let result = file_parser.parse_file(path);
+                    (file_parser, result)
+                })
                 .collect();
-            if fields.is_empty() {
-                log::debug!(
-                    "Skipping blank line {} in file \"{}\".",
-                    line_number,
-                    path.as_ref().display()
-                );
-            } else if fields[0].starts_with('#') {
-                log::debug!(
-                    "Skipping comment line {} in file \"{}\".",
-                    line_number,
-                    path.as_ref().display()
-                );
-            } else if fields.len() < 3 {
-                return Err(ApplicationError::new(
-                    crate::error::ApplicationErrorType::InputDataError,
-                    format!(
-                        "Line {} of file \"{}\" does not contain the minimally required records.",
-                        line_number,
-                        path.as_ref().display()
-                    ),
-                ));
-            } else {
-                // Tries to parse the actual values from the file.
-                let chromosome = fields[0].to_string();
-                let start: u64 = fields[1].parse().map_err(|err| {
-                    ApplicationError::from(err).chain(format!(
-                        "Value \"{}\" at line {} of file \"{}\" could \
-                        not be parsed as genomic start coordinates.",
-                        fields[1],
-                        line_number,
-                        path.as_ref().display()
-                    ))
-                })?;
-                let end: u64 = fields[2].parse().map_err(|err| {
+            for (file_parser, result) in file_parsers {
+                self.merge(file_parser)?;
+                result?;
+            }
+            return self.finish();
+        }
+        for path in paths {
+            self.parse_file(path)?;
+        }
+        self.finish()
+    }
+
+    /// Parses all peaks of a single BED file.
+    /// If problems are collected, a file that cannot be read is skipped.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - the input file path
+    fn parse_file<T: AsRef<Path>>(&mut self, path: T) -> Result<(), ApplicationError> {
+        // The parser recycles its line buffer and each file is closed after parsing,
+        // so the number of open file descriptors does not grow with the number of input files.
+        let path = path.as_ref();
+        log::info!(target: LOG_TARGET, "Parsing {}.", path.display());
+        let source = path.display().to_string();
+        match retry(&format!("Opening {}", source), || File::open(path)) {
+            Ok(file) => {
+                let reader = Retrying::new(file, format!("Reading {}", source));
+                match decompressing_reader(BufReader::new(reader)) {
+                    Ok(reader) => self.parse(reader, &source)?,
+                    Err(err) => {
+                        self.peaks_per_source.push(0);
+                        self.record_error(
+                            ApplicationError::from(err)
+                                .chain(format!("The input file \"{}\" could not be read.", source)),
+                        )?
+                    },
+                }
+            },
+            Err(err) => {
+                self.peaks_per_source.push(0);
+                self.record_error(
                     ApplicationError::from(err).chain(format!(
-                        "Value \"{}\" at line {} of file \"{}\" could \
-                        not be parsed as genomic end coordinates.",
-                        fields[2],
-                        line_number,
-                        path.as_ref().display()
-                    ))
-                })?;
-                let summit = if let Some(summit_field) = fields.get(9).and_then(|field_value| {
-                    // "-1" indicates missing peak summit information according to the narrowPeak format definition,
-                    // so parsing should be skipped.
-                    if *field_value == "-1" {
-                        None
-                    } else {
-                        Some(field_value)
+                        "The input file \"{}\" could not be opened.",
+                        path.display()
+                    )),
+                )?
+            },
+        }
+        log::debug!(target: LOG_TARGET, "Closing {}.", path.display());
+        Ok(())
+    }
+
+    /// Returns an empty parser with the configuration of this parser for parsing a single file
+    /// concurrently. Problems are collected without limit, as the limit is applied when the
+    /// parser is merged.
+    #[cfg(feature = "parallel")]
+    fn file_parser(&self) -> Self {
+        Self {
+            max_errors: self.max_errors.map(|_| usize::MAX),
+            approximated_summit_ids: self
+                .approximated_summit_ids
+                .as_ref()
+                .map(|_| HashSet::new()),
+            strands: self.strands.as_ref().map(|_| HashMap::new()),
+            names: self.names.as_ref().map(|_| HashMap::new()),
+            scores: self.scores.as_ref().map(|_| HashMap::new()),
+            input_format: self.input_format,
+            ..Default::default()
+        }
+    }
+
+    /// Appends the peaks and problems of a parser created by [`BedParser::file_parser`].
+    /// The peak identifiers of the merged parser are shifted to follow the peaks parsed so far.
+    ///
+    /// # Parameters
+    ///
+    /// * `other` - the parser to merge
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the maximum number of problems is reached.
+    #[cfg(feature = "parallel")]
+    fn merge(&mut self, other: BedParser) -> Result<(), ApplicationError> {
+        let first_id = self.current_peak_id;
+        for (chromosome, peaks) in other.peak_map {
+            self.peak_map
+                .entry(chromosome)
+                .or_default()
+                .extend(peaks.into_iter().map(|peak| {
+                    PeakData::new(first_id + peak.id(), peak.start(), peak.end(), peak.summit())
+                        .expect("The peak must be valid as only its identifier was changed.")
+                }));
+        }
+        self.current_peak_id += other.current_peak_id;
+        self.peaks_per_source.extend(other.peaks_per_source);
+        if let (Some(ids), Some(other_ids)) =
+            (&mut self.approximated_summit_ids, other.approximated_summit_ids)
+        {
+            ids.extend(other_ids.into_iter().map(|id| first_id + id));
+        }
+        if let (Some(strands), Some(other_strands)) = (&mut self.strands, other.strands) {
+            strands.extend(
+                other_strands
+                    .into_iter()
+                    .map(|(id, strand)| (first_id + id, strand)),
+            );
+        }
+        if let (Some(names), Some(other_names)) = (&mut self.names, other.names) {
+            names.extend(
+                other_names
+                    .into_iter()
+                    .map(|(id, name)| (first_id + id, name)),
+            );
+        }
+        if let (Some(scores), Some(other_scores)) = (&mut self.scores, other.scores) {
+            scores.extend(
+                other_scores
+                    .into_iter()
+                    .map(|(id, peak_scores)| (first_id + id, peak_scores)),
+            );
+        }
+        self.centred_summits += other.centred_summits;
+        for error in other.errors {
+            self.record_error(error)?;
+        }
+        Ok(())
+    }
+
+    /// Parses all peaks of a single BED source.
+    /// If problems are collected, a source that cannot be read is skipped.
+    ///
+    /// # Parameters
+    ///
+    /// * `reader` - the reader of the BED source
+    /// * `source` - the path or a description of the source used in error messages
+    fn parse<R: BufRead>(&mut self, mut reader: R, source: &str) -> Result<(), ApplicationError> {
+        let mut line_number = 0;
+        self.start_source();
+        let result = loop {
+            line_number += 1;
+            self.line_buffer.clear();
+            let line_context = ErrorContext::new().with_path(source).with_line(line_number);
+            match reader.read_line(&mut self.line_buffer) {
+                Ok(0) => break Ok(()),
+                Ok(_) => {
+                    if self.first_lines.len() < FORMAT_DETECTION_LINES {
+                        self.first_lines.push(self.line_buffer.clone());
                     }
-                }) {
-                    let summit_offset: u64 = summit_field.parse().map_err(|err| {
-                        ApplicationError::from(err).chain(format!(
-                            "Value \"{}\" at line {} of file \"{}\" could \
-                            not be parsed as peak summit coordinates.",
-                            summit_field,
-                            line_number,
-                            path.as_ref().display()
-                        ))
-                    })?;
-                    start + summit_offset
+                    match self.parse_line(line_context) {
+                        Ok(Some((chromosome, peak))) => {
+                            self.peak_map.entry(chromosome).or_default().push(peak)
+                        },
+                        Ok(None) => {},
+                        Err(err) => {
+                            let err = self.suggest_format(err);
+                            if let Err(err) = self.record_error(err) {
+                                break Err(err);
+                            }
+                        },
+                    }
+                },
+                Err(err) => {
+                    break self.record_error(
+                        ApplicationError::from(err)
+                            .chain("Failed to parse line.")
+                            .with_context(line_context),
+                    );
+                },
+            }
+        };
+        self.finish_source(source);
+        result
+    }
+
+    /// Reads the lines of a source until the next record and returns the parsed peak together
+    /// with its chromosome or `None` if the source has been read completely.
+    ///
+    /// # Parameters
+    ///
+    /// * `reader` - the reader of the source
+    /// * `source` - the path or a description of the source used in error messages
+    /// * `line_number` - the number of the last read line, which is updated
+    fn read_record<R: BufRead>(
+        &mut self,
+        reader: &mut R,
+        source: &str,
+        line_number: &mut u64,
+    ) -> Result<Option<(Chromosome, PeakData)>, ApplicationError> {
+        loop {
+            *line_number += 1;
+            self.line_buffer.clear();
+            let line_context = ErrorContext::new()
+                .with_path(source)
+                .with_line(*line_number);
+            let bytes = reader.read_line(&mut self.line_buffer).map_err(|err| {
+                ApplicationError::from(err)
+                    .chain("Failed to parse line.")
+                    .with_context(line_context.clone())
+            })?;
+            if bytes == 0 {
+                return Ok(None);
+            }
+            if self.first_lines.len() < FORMAT_DETECTION_LINES {
+                self.first_lines.push(self.line_buffer.clone());
+            }
+            match self.parse_line(line_context) {
+                Ok(Some(peak)) => return Ok(Some(peak)),
+                Ok(None) => {},
+                Err(err) => return Err(self.suggest_format(err)),
+            }
+        }
+    }
+
+    /// Resets the state tracked per source before a new source is parsed.
+    fn start_source(&mut self) {
+        self.source_first_id = self.current_peak_id;
+        self.approximated_summits = LineSummary::default();
+        self.first_lines.clear();
+        self.format_suggested = false;
+    }
+
+    /// Logs the warnings aggregated for a completely parsed source.
+    ///
+    /// # Parameters
+    ///
+    /// * `source` - the path or a description of the source used in log messages
+    fn finish_source(&mut self, source: &str) {
+        self.peaks_per_source
+            .push(self.current_peak_id - self.source_first_id);
+        if !self.input_format.has_summits() {
+            self.centred_summits += self.approximated_summits.count;
+        } else if self.approximated_summits.count > 0 {
+            log::warn!(
+                target: LOG_TARGET,
+                "{} lines of {} did not contain peak summit information, \
+                so their summits were approximated (lines {}).",
+                self.approximated_summits.count,
+                source,
+                self.approximated_summits.format_lines()
+            );
+        }
+    }
+
+    /// Parses the line currently stored in the line buffer and returns the parsed peak
+    /// together with its chromosome or `None` if the line does not contain a record.
+    ///
+    /// # Parameters
+    ///
+    /// * `line_context` - the location of the line used in error messages
+    fn parse_line(
+        &mut self,
+        line_context: ErrorContext,
+    ) -> Result<Option<(Chromosome, PeakData)>, ApplicationError> {
+        let source = line_context.path().as_deref().unwrap_or_default();
+        let line_number = line_context.line().unwrap_or_default();
+        let line = self.line_buffer.trim_end_matches(['\n', '\r']);
+        let fields: Vec<&str> = line
+            .split(&[' ', '\t'])
+            .filter(|split| !split.is_empty())
+            .collect();
+        if fields.is_empty() {
+            log::debug!(target: LOG_TARGET, "Skipping blank line {} in {}.", line_number, source);
+        } else if fields[0].starts_with('#') {
+            log::debug!(target: LOG_TARGET, "Skipping comment line {} in {}.", line_number, source);
+        } else if fields.len() < 3 {
+            return Err(ApplicationError::new(
+                ApplicationErrorType::ParseError,
+                "The line does not contain the minimally required records.",
+            )
+            .with_context(line_context)
+            .with_hint(
+                "BED files require at least the tab or space separated columns chrom, \
+                chromStart and chromEnd.",
+            ));
+        } else {
+            // Tries to parse the actual values from the file.
+            let chromosome = Chromosome::intern(fields[0]);
+            let start: u64 = fields[1].parse().map_err(|err| {
+                ApplicationError::from(err)
+                    .chain("The value could not be parsed as genomic start coordinates.")
+                    .with_context(
+                        line_context
+                            .clone()
+                            .with_field(2, "chromStart")
+                            .with_value(fields[1]),
+                    )
+                    .with_hint(coordinate_hint(fields[1]))
+            })?;
+            let end: u64 = fields[2].parse().map_err(|err| {
+                ApplicationError::from(err)
+                    .chain("The value could not be parsed as genomic end coordinates.")
+                    .with_context(
+                        line_context
+                            .clone()
+                            .with_field(3, "chromEnd")
+                            .with_value(fields[2]),
+                    )
+                    .with_hint(coordinate_hint(fields[2]))
+            })?;
+            if self.input_format == InputFormat::NarrowPeak && fields.len() < 10 {
+                return Err(ApplicationError::new(
+                    ApplicationErrorType::ParseError,
+                    "The line does not contain the narrowPeak summit column.",
+                )
+                .with_context(line_context)
+                .with_hint(
+                    "narrowPeak files require 10 columns. Parse files without summit \
+                    information in the broadPeak or BED input format.",
+                ));
+            }
+            // Column 10 is only interpreted as summit by formats containing summit information.
+            let summit_column = fields.get(9).filter(|_| self.input_format.has_summits());
+            let summit_from_field = summit_column.is_some_and(|field_value| *field_value != "-1");
+            let summit = if let Some(summit_field) = summit_column.and_then(|field_value| {
+                // "-1" indicates missing peak summit information according to the narrowPeak format definition,
+                // so parsing should be skipped.
+                if *field_value == "-1" {
+                    None
                 } else {
-                    log::warn!(
-                        "Line {} of file \"{}\" did not contain \
+                    Some(field_value)
+                }
+            }) {
+                let summit_offset: u64 = summit_field.parse().map_err(|err| {
+                    ApplicationError::from(err)
+                        .chain("The value could not be parsed as peak summit coordinates.")
+                        .with_context(
+                            line_context
+                                .clone()
+                                .with_field(10, "peak")
+                                .with_value(summit_field),
+                        )
+                        .with_hint(SUMMIT_COLUMN_HINT)
+                })?;
+                start.checked_add(summit_offset).ok_or_else(|| {
+                    ApplicationError::new(
+                        ApplicationErrorType::ValidationError,
+                        "The peak summit exceeds the maximum genomic coordinate.",
+                    )
+                    .with_context(
+                        line_context
+                            .clone()
+                            .with_field(10, "peak")
+                            .with_value(summit_field),
+                    )
+                    .with_hint(SUMMIT_COLUMN_HINT)
+                })?
+            } else {
+                if self.input_format.has_summits() {
+                    log::trace!(
+                        target: LOG_TARGET,
+                        "Line {} of {} did not contain \
                         peak summit information. Summit is approximated.",
                         line_number,
-                        path.as_ref().display()
+                        source
                     );
-                    start.midpoint(end)
-                };
-                let peak = PeakData::new(current_peak_id, start, end, summit).map_err(|err| {
+                }
+                self.approximated_summits.add(line_number);
+                start.midpoint(end)
+            };
+            let peak = PeakData::new(self.current_peak_id, start, end, summit).map_err(|err| {
+                let err = err
+                    .chain("The line contains invalid data.")
+                    .with_context(line_context.clone());
+                if summit_from_field && start <= end {
+                    err.with_hint(SUMMIT_COLUMN_HINT)
+                } else {
+                    err
+                }
+            })?;
+            if !summit_from_field
+                && let Some(approximated_summit_ids) = &mut self.approximated_summit_ids
+            {
+                approximated_summit_ids.insert(peak.id());
+            }
+            if let (Some(strands), Some(strand_field)) = (&mut self.strands, fields.get(5)) {
+                let strand: Strand = strand_field.parse().map_err(|err: ApplicationError| {
+                    err.chain("The value could not be parsed as strand.")
+                        .with_context(
+                            line_context
+                                .clone()
+                                .with_field(6, "strand")
+                                .with_value(strand_field),
+                        )
+                })?;
+                if strand != Strand::Unknown {
+                    strands.insert(peak.id(), strand);
+                }
+            }
+            if let (Some(names), Some(name)) = (&mut self.names, fields.get(3))
+                && *name != "."
+            {
+                names.insert(peak.id(), name.to_string());
+            }
+            if let Some(scores) = &mut self.scores {
+                let peak_scores = PeakScores::from_fields(&fields);
+                if !peak_scores.is_empty() {
+                    scores.insert(peak.id(), peak_scores);
+                }
+            }
+            self.current_peak_id += 1;
+            return Ok(Some((chromosome, peak)));
+        }
+        Ok(None)
+    }
+
+    /// Adds a suggestion to the first problem of the current source if the first lines of the
+    /// source indicate an unsupported format.
+    ///
+    /// # Parameters
+    ///
+    /// * `error` - the problem to add the suggestion to
+    fn suggest_format(&mut self, error: ApplicationError) -> ApplicationError {
+        if self.format_suggested {
+            return error;
+        }
+        let lines: Vec<&str> = self.first_lines.iter().map(String::as_str).collect();
+        match ConfusedFormat::detect(&lines) {
+            Some(format) => {
+                self.format_suggested = true;
+                error.with_hint(format.suggestion())
+            },
+            None => error,
+        }
+    }
+
+    /// Records a problem if problems are collected or returns it otherwise.
+    /// If the maximum number of problems is reached, an error containing all
+    /// collected problems is returned.
+    ///
+    /// # Parameters
+    ///
+    /// * `error` - the problem to record
+    fn record_error(&mut self, error: ApplicationError) -> Result<(), ApplicationError> {
+        let Some(max_errors) = self.max_errors else {
+            return Err(error);
+        };
+        self.errors.push(error);
+        if self.errors.len() >= max_errors {
+            Err(self.collected_error(format!(
+                "Parsing was stopped after {} problems were found in the input data.",
+                self.errors.len()
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Logs a single message for all peaks whose summit was set to the centre,
+    /// as the input format does not contain summit information.
+    fn log_centred_summits(&mut self) {
+        if self.centred_summits > 0 {
+            log::info!(
+                target: LOG_TARGET,
+                "The summits of {} peaks were set to the peak centres, \
+                as the {} format does not contain summit information.",
+                self.centred_summits,
+                self.input_format
+            );
+            self.centred_summits = 0;
+        }
+    }
+
+    /// Returns the parsed peaks or an error if any problems were collected.
+    fn finish(&mut self) -> Result<HashMap<Chromosome, Vec<PeakData>>, ApplicationError> {
+        self.log_centred_summits();
+        if self.errors.is_empty() {
+            Ok(std::mem::take(&mut self.peak_map))
+        } else {
+            Err(self.collected_error(format!(
+                "{} problems were found in the input data.",
+                self.errors.len()
+            )))
+        }
+    }
+
+    /// Creates an error containing all collected problems.
+    /// The type of the error is the type of the first problem.
+    ///
+    /// # Parameters
+    ///
+    /// * `message` - the summary message of the error
+    fn collected_error(&mut self, message: String) -> ApplicationError {
+        let errors = std::mem::take(&mut self.errors);
+        let error_type = errors
+            .first()
+            .map(ApplicationError::error_type)
+            .unwrap_or(ApplicationErrorType::InternalError);
+        ApplicationError::new(error_type, message).with_related(errors)
+    }
+}
+
+/// A parser of the records of a single BED3+ source, whose lines are read by the caller,
+/// so each record can be inspected together with its line in a single pass over the source,
+/// for example to validate peak files. The parsed peaks are not retained.
+pub struct BedLineParser {
+    /// The parser of the records.
+    parser: BedParser,
+    /// The path or a description of the source used in error messages.
+    source: String,
+    /// The number of the last parsed line.
+    line_number: u64,
+}
+
+impl BedLineParser {
+    /// Creates a parser of the specified source.
+    ///
+    /// # Parameters
+    ///
+    /// * `source` - the path or a description of the source used in error messages
+    /// * `input_format` - the format of the source
+    pub fn new<T: ToString>(source: T, input_format: InputFormat) -> Self {
+        let mut parser = BedParser {
+            strands: Some(HashMap::new()),
+            input_format,
+            ..Default::default()
+        };
+        parser.start_source();
+        Self {
+            parser,
+            source: source.to_string(),
+            line_number: 0,
+        }
+    }
+
+    /// Parses the next line of the source and returns the parsed peak together with its
+    /// chromosome or `None` if the line does not contain a record.
+    ///
+    /// # Parameters
+    ///
+    /// * `line` - the line including or excluding the line break
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the line does not contain a valid record.
+    /// Parsing can be continued with the next line.
+    pub fn parse_line(
+        &mut self,
+        line: &str,
+    ) -> Result<Option<(Chromosome, PeakData)>, ApplicationError> {
+        self.line_number += 1;
+        self.parser.line_buffer.clear();
+        self.parser.line_buffer.push_str(line);
+        if self.parser.first_lines.len() < FORMAT_DETECTION_LINES {
+            self.parser.first_lines.push(line.to_string());
+        }
+        let line_context = ErrorContext::new()
+            .with_path(&self.source)
+            .with_line(self.line_number);
+        let result = self
+            .parser
+            .parse_line(line_context)
+            .map_err(|err| self.parser.suggest_format(err));
+        // Strands are only parsed to validate them, so they are not retained.
+        if let Some(strands) = &mut self.parser.strands {
+            strands.clear();
+        }
+        result
+    }
+}
+
+/// An iterator parsing BED3+ files line by line and yielding the peaks of each chromosome
+/// as soon as all of its records have been read from all files.
+/// The files are merged by chromosome, so each file may contain the peaks of a single sample.
+/// Before the first chromosome is yielded, each file is read once to count its peaks and
+/// determine the order of its chromosomes, so that peak identifiers are assigned like
+/// [`bed_to_peaks`] does.
+///
+/// The stream is created via [`bed_to_peak_stream`].
+/// An error is yielded if a file cannot be parsed, the peaks of a chromosome are not
+/// consecutive within a file or the files list their chromosomes in a different order,
+/// after which the stream ends.
+///
+/// # Example
+///
+/// ```no_run
+/// use gipfelkreuzer::input::bed_to_peak_stream;
+///
+/// for result in bed_to_peak_stream(&["sample_1.narrowPeak", "sample_2.narrowPeak"]) {
+///     let (chromosome, peaks) = result.unwrap();
+///     println!("{}: {} peaks", chromosome, peaks.len());
+/// }
+/// ```
+pub struct BedPeakStream {
+    /// The parser defining the configuration of the file parsers and summarising their
+    /// warnings.
+    parser: BedParser,
+    /// The input files.
+    paths: Vec<PathBuf>,
+    /// The input files in input order or `None` if the files have not been indexed yet.
+    files: Option<Vec<StreamedFile>>,
+    /// The number of peaks of each indexed input file.
+    peaks_per_file: Vec<usize>,
+    /// The maximum number of input files kept open at a time.
+    max_open_files: usize,
+    /// If an error has been yielded.
+    failed: bool,
+}
+
+/// An input file of a [`BedPeakStream`].
+struct StreamedFile {
+    /// The parser of the file, which assigns the peak identifiers following the peaks of the
+    /// previous files.
+    parser: BedParser,
+    /// The path of the file.
+    path: PathBuf,
+    /// The reader of the file or `None` if the file is currently closed.
+    reader: Option<StreamedReader>,
+    /// The number of lines read so far.
+    line_number: u64,
+    /// The number of bytes read before the file was closed, where reading resumes once the
+    /// file is reopened.
+    byte_offset: u64,
+    /// The peak read ahead of the current chromosome.
+    next_peak: Option<(Chromosome, PeakData)>,
+    /// The chromosomes of the file whose peaks have not been read yet in file order.
+    chromosomes: VecDeque<Chromosome>,
+}
+
+/// The reader of a [`StreamedFile`].
+enum StreamedReader {
+    /// A reader of uncompressed content, whose position can be restored after reopening the
+    /// file.
+    Uncompressed(BufReader<Retrying<File>>),
+    /// A reader of decompressed content, which is kept open until the file has been read
+    /// completely, as restoring its position requires decompressing the file again.
+    Compressed(Box<dyn BufRead>),
+}
+
+impl StreamedReader {
+    /// Returns the reader of the uncompressed content.
+    fn as_buf_read(&mut self) -> &mut dyn BufRead {
+        match self {
+            StreamedReader::Uncompressed(reader) => reader,
+            StreamedReader::Compressed(reader) => reader,
+        }
+    }
+}
+
+impl StreamedFile {
+    /// Reads all peaks of the specified chromosome, which must be the next chromosome of the
+    /// file. The file is opened if it is closed and closed once it has been read completely.
+    ///
+    /// # Parameters
+    ///
+    /// * `chromosome` - the chromosome to read
+    /// * `peaks` - the peaks to add the read peaks to
+    fn read_chromosome(
+        &mut self,
+        chromosome: Chromosome,
+        peaks: &mut Vec<PeakData>,
+    ) -> Result<(), ApplicationError> {
+        let source = self.path.display().to_string();
+        if let Some((_, peak)) = self.next_peak.take() {
+            peaks.push(peak);
+        }
+        if self.reader.is_none() {
+            self.open(&source)?;
+        }
+        while let Some(reader) = &mut self.reader {
+            match self.parser.read_record(
+                &mut reader.as_buf_read(),
+                &source,
+                &mut self.line_number,
+            )? {
+                Some((next_chromosome, peak)) if next_chromosome == chromosome => peaks.push(peak),
+                Some(next_peak) => {
+                    self.next_peak = Some(next_peak);
+                    break;
+                },
+                None => {
+                    log::debug!(target: LOG_TARGET, "Closing {}.", source);
+                    self.parser.finish_source(&source);
+                    self.reader = None;
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Opens the file and resumes reading at the position it was closed at.
+    ///
+    /// # Parameters
+    ///
+    /// * `source` - the path of the file used in error messages
+    fn open(&mut self, source: &str) -> Result<(), ApplicationError> {
+        let mut reader = open_buffered_file(&self.path)?;
+        if self.line_number == 0 {
+            log::info!(target: LOG_TARGET, "Streaming {}.", source);
+            self.parser.start_source();
+            let compressed = is_compressed(&mut reader).map_err(|err| {
+                ApplicationError::from(err)
+                    .chain(format!("The input file \"{}\" could not be read.", source))
+            })?;
+            self.reader = Some(if compressed {
+                StreamedReader::Compressed(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+            } else {
+                StreamedReader::Uncompressed(reader)
+            });
+            return Ok(());
+        }
+        log::debug!(
+            target: LOG_TARGET,
+            "Reopening {} after line {}.",
+            source,
+            self.line_number
+        );
+        reader
+            .seek(SeekFrom::Start(self.byte_offset))
+            .map_err(|err| {
+                ApplicationError::from(err)
+                    .chain("Failed to resume reading the reopened file.")
+                    .with_context(
+                        ErrorContext::new()
+                            .with_path(source)
+                            .with_line(self.line_number),
+                    )
+            })?;
+        self.reader = Some(StreamedReader::Uncompressed(reader));
+        Ok(())
+    }
+
+    /// Closes the file to stay within the limit of open files and records the position to
+    /// resume reading at. Compressed files are kept open.
+    /// Returns if the file has been closed.
+    fn close(&mut self) -> Result<bool, ApplicationError> {
+        match self.reader.take() {
+            Some(StreamedReader::Uncompressed(mut reader)) => {
+                self.byte_offset = reader.stream_position().map_err(|err| {
                     ApplicationError::from(err).chain(format!(
-                        "Line {} of file \"{}\" contains invalid data.",
-                        line_number,
-                        path.as_ref().display()
+                        "Failed to determine the read position of \"{}\".",
+                        self.path.display()
                     ))
                 })?;
-                if let Some(peaks) = peak_map.get_mut(&chromosome) {
-                    peaks.push(peak);
-                } else {
-                    peak_map.insert(chromosome, vec![peak]);
+                Ok(true)
+            },
+            reader => {
+                self.reader = reader;
+                Ok(false)
+            },
+        }
+    }
+}
+
+impl BedPeakStream {
+    /// Sets the format of the input files.
+    ///
+    /// # Parameters
+    ///
+    /// * `input_format` - the format of the input files
+    pub fn input_format(mut self, input_format: InputFormat) -> Self {
+        self.parser.input_format = input_format;
+        self
+    }
+
+    /// Parses the statistical values of the peaks from columns 5, 7, 8 and 9, which are
+    /// available via [`BedPeakStream::take_scores`].
+    pub fn parse_scores(mut self) -> Self {
+        self.parser.scores = Some(HashMap::new());
+        self
+    }
+
+    /// Returns the statistical values of all peaks read so far with any statistical value
+    /// by peak ID and removes them from the stream.
+    pub fn take_scores(&mut self) -> HashMap<usize, PeakScores> {
+        self.files
+            .iter_mut()
+            .flatten()
+            .filter_map(|file| file.parser.scores.as_mut())
+            .flat_map(std::mem::take)
+            .collect()
+    }
+
+    /// Sets the maximum number of input files kept open at a time, so that streaming
+    /// thousands of input files does not exceed the open file limit.
+    /// Files closed to stay within the limit are reopened and resume reading at the position
+    /// they were closed at when their next chromosome is read. Compressed files are kept open
+    /// until they have been read completely, as resuming them would require decompressing
+    /// them again. Defaults to [`DEFAULT_MAX_OPEN_FILES`].
+    ///
+    /// # Parameters
+    ///
+    /// * `max_open_files` - the maximum number of open input files
+    pub fn max_open_files(mut self, max_open_files: usize) -> Self {
+        self.max_open_files = max_open_files.max(1);
+        self
+    }
+
+    /// Returns the number of peaks of each input file in input order, which is known once
+    /// the first chromosome has been read.
+    pub fn peaks_per_file(&self) -> &[usize] {
+        &self.peaks_per_file
+    }
+
+    /// Reads all input files once to count their peaks and determine the order of their
+    /// chromosomes and returns the files prepared for merging.
+    fn index_files(&mut self) -> Result<Vec<StreamedFile>, ApplicationError> {
+        let mut files = Vec::with_capacity(self.paths.len());
+        let mut first_id = 0;
+        for path in &self.paths {
+            log::info!(target: LOG_TARGET, "Indexing {}.", path.display());
+            let source = path.display().to_string();
+            let mut reader = open_bed_file(path)?;
+            let mut parser = BedParser {
+                input_format: self.parser.input_format,
+                ..Default::default()
+            };
+            let mut chromosomes: VecDeque<Chromosome> = VecDeque::new();
+            let mut line_number = 0;
+            while let Some((chromosome, _)) =
+                parser.read_record(&mut reader, &source, &mut line_number)?
+            {
+                if chromosomes.back() == Some(&chromosome) {
+                    continue;
                 }
-                current_peak_id += 1;
+                if chromosomes.contains(&chromosome) {
+                    return Err(ApplicationError::new(
+                        ApplicationErrorType::ValidationError,
+                        format!(
+                            "The peaks of chromosome {} are not consecutive in the input file.",
+                            chromosome
+                        ),
+                    )
+                    .with_context(
+                        ErrorContext::new()
+                            .with_path(&*source)
+                            .with_line(line_number),
+                    )
+                    .with_hint(
+                        "Streamed input files must be sorted by chromosome, for example \
+                        with \"sort -k1,1 -k2,2n\".",
+                    ));
+                }
+                chromosomes.push_back(chromosome);
             }
+            self.peaks_per_file.push(parser.current_peak_id);
+            files.push(StreamedFile {
+                parser: BedParser {
+                    input_format: self.parser.input_format,
+                    current_peak_id: first_id,
+                    scores: self.parser.scores.as_ref().map(|_| HashMap::new()),
+                    ..Default::default()
+                },
+                path: path.clone(),
+                reader: None,
+                line_number: 0,
+                byte_offset: 0,
+                next_peak: None,
+                chromosomes,
+            });
+            first_id += parser.current_peak_id;
         }
+        Ok(files)
+    }
+
+    /// Reads the peaks of the next chromosome from all input files.
+    /// The next chromosome is the first chromosome of any file, which is not preceded by
+    /// another chromosome in any other file.
+    fn read_chromosome(&mut self) -> Result<Option<(Chromosome, Vec<PeakData>)>, ApplicationError> {
+        if self.files.is_none() {
+            self.files = Some(self.index_files()?);
+        }
+        let files = self
+            .files
+            .as_mut()
+            .expect("The input files must have been indexed.");
+        let mut next_chromosomes = files
+            .iter()
+            .filter_map(|file| file.chromosomes.front())
+            .peekable();
+        if next_chromosomes.peek().is_none() {
+            self.parser.log_centred_summits();
+            return Ok(None);
+        }
+        let chromosome = next_chromosomes
+            .find(|chromosome| {
+                files.iter().all(|file| {
+                    !file
+                        .chromosomes
+                        .iter()
+                        .skip(1)
+                        .any(|other| other == *chromosome)
+                })
+            })
+            .copied()
+            .ok_or_else(|| {
+                ApplicationError::new(
+                    ApplicationErrorType::ValidationError,
+                    "The input files list their chromosomes in a different order.",
+                )
+                .with_hint(
+                    "Streamed input files must be sorted the same way, for example \
+                    with \"sort -k1,1 -k2,2n\".",
+                )
+            })?;
+        let mut peaks = Vec::new();
+        // Uncompressed files opened to read the chromosome are closed again if the maximum
+        // number of open files is reached, so at most one additional file is open while reading.
+        let mut open_files = files.iter().filter(|file| file.reader.is_some()).count();
+        for file in files
+            .iter_mut()
+            .filter(|file| file.chromosomes.front() == Some(&chromosome))
+        {
+            let was_open = file.reader.is_some();
+            file.chromosomes.pop_front();
+            file.read_chromosome(chromosome, &mut peaks)?;
+            if file.chromosomes.is_empty() {
+                self.parser.centred_summits += std::mem::take(&mut file.parser.centred_summits);
+                if was_open {
+                    open_files -= 1;
+                }
+            } else if !was_open && (open_files < self.max_open_files || !file.close()?) {
+                open_files += 1;
+            }
+        }
+        log::debug!(
+            target: LOG_TARGET,
+            "Read {} peaks of chromosome {}.",
+            peaks.len(),
+            chromosome
+        );
+        Ok(Some((chromosome, peaks)))
+    }
+}
+
+impl Iterator for BedPeakStream {
+    type Item = Result<(Chromosome, Vec<PeakData>), ApplicationError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = self.read_chromosome().transpose();
+        // Ends the stream as the remaining input cannot be processed consistently.
+        self.failed = matches!(result, Some(Err(_)));
+        result
     }
-    Ok(peak_map)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::test_utils::test_resources;
+    use crate::test_utils::{test_output, test_resources};
 
     use super::*;
 
     #[test]
     fn test_bed_to_peaks() {
         let peaks =
-            bed_to_peaks(&vec![test_resources().join("input_test_valid_with_summit.narrowPeak")])
+            bed_to_peaks(&[test_resources().join("input_test_valid_with_summit.narrowPeak")])
                 .unwrap();
         assert_eq!(peaks.len(), 2);
-        assert_eq!(peaks["chr1"].len(), 4);
-        assert_eq!(peaks["chr2"].len(), 2);
+        assert_eq!(peaks[&Chromosome::from("chr1")].len(), 4);
+        assert_eq!(peaks[&Chromosome::from("chr2")].len(), 2);
         let expected_peaks_1 = vec![
             PeakData::new(0, 4470246u64, 4470509u64, 4470246u64 + 107u64).unwrap(),
             PeakData::new(1, 4496298u64, 4496749u64, 4496298u64 + 278u64).unwrap(),
@@ -153,10 +1554,10 @@ mod tests {
         ];
         for expected_peak in expected_peaks_1 {
             assert!(
-                peaks["chr1"].contains(&expected_peak),
+                peaks[&Chromosome::from("chr1")].contains(&expected_peak),
                 "Expected peak {:?} in {:?}..",
                 expected_peak,
-                peaks["chr1"]
+                peaks[&Chromosome::from("chr1")]
             );
         }
 
@@ -166,25 +1567,181 @@ mod tests {
         ];
         for expected_peak in expected_peaks_2 {
             assert!(
-                peaks["chr2"].contains(&expected_peak),
+                peaks[&Chromosome::from("chr2")].contains(&expected_peak),
                 "Expected peak {:?} in {:?}..",
                 expected_peak,
-                peaks["chr2"]
+                peaks[&Chromosome::from("chr2")]
             );
         }
     }
 
+    #[test]
+    fn test_bed_to_peaks_gzip() {
+        use std::io::Write;
+
+        use flate2::{Compression, write::GzEncoder};
+
+        std::fs::create_dir_all(test_output()).unwrap();
+        let path = test_resources().join("input_test_valid_with_summit.narrowPeak");
+        let compressed_path = test_output().join("test_bed_to_peaks_gzip.narrowPeak.bgz");
+        let content = std::fs::read(&path).unwrap();
+        // Writes the content as multiple concatenated gzip members like BGZF.
+        let (first, second) = content.split_at(content.len() / 2);
+        let mut compressed = Vec::new();
+        for member in [first, second] {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(member).unwrap();
+            compressed.extend(encoder.finish().unwrap());
+        }
+        std::fs::write(&compressed_path, compressed).unwrap();
+        assert_eq!(bed_to_peaks(&[&compressed_path]).unwrap(), bed_to_peaks(&[&path]).unwrap());
+        assert_eq!(
+            read_decompressed_to_string(&compressed_path)
+                .unwrap()
+                .as_bytes(),
+            content.as_slice()
+        );
+        assert_eq!(read_decompressed_to_string(&path).unwrap().as_bytes(), content.as_slice());
+        std::fs::remove_file(compressed_path).unwrap();
+    }
+
+    #[test]
+    fn test_bed_to_peaks_with_approximated_summits() {
+        let (peaks, approximated_summit_ids) = bed_to_peaks_with_approximated_summits(
+            test_resources().join("input_test_valid_minimal.narrowPeak"),
+        )
+        .unwrap();
+        assert_eq!(approximated_summit_ids, (0..6).collect());
+        assert_eq!(peaks.values().map(Vec::len).sum::<usize>(), 6);
+        let (_, approximated_summit_ids) = bed_to_peaks_with_approximated_summits(
+            test_resources().join("input_test_valid_with_summit.narrowPeak"),
+        )
+        .unwrap();
+        assert!(approximated_summit_ids.is_empty());
+    }
+
+    #[test]
+    fn test_bed_to_peaks_with_format() {
+        let path = test_resources().join("input_test_valid_with_summit.narrowPeak");
+        let auto =
+            bed_to_peaks_with_format(&[&path], InputFormat::Auto, None, false, false).unwrap();
+        assert!(auto.strands.is_empty());
+        assert_eq!(auto.peaks_per_file, vec![6]);
+        let narrow =
+            bed_to_peaks_with_format(&[&path], InputFormat::NarrowPeak, None, false, false)
+                .unwrap();
+        assert_eq!(narrow.peaks, auto.peaks);
+        for input_format in [InputFormat::BroadPeak, InputFormat::Bed] {
+            let peaks = bed_to_peaks_with_format(&[&path], input_format, None, false, false)
+                .unwrap()
+                .peaks;
+            assert_eq!(peaks.values().map(Vec::len).sum::<usize>(), 6);
+            for peak in peaks.values().flatten() {
+                assert_eq!(peak.summit(), peak.start().midpoint(peak.end()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_bed_parser_input_format() {
+        let record = "chr1\t100\t200\tpeak\t0\t.\t1.5\t2.5\t3.5\t20\n";
+        let summit = |input_format: InputFormat| {
+            let mut parser = BedParser {
+                input_format,
+                ..Default::default()
+            };
+            parser.parse(record.as_bytes(), "buffer").unwrap();
+            parser.finish().unwrap()[&Chromosome::from("chr1")][0].summit()
+        };
+        assert_eq!(summit(InputFormat::Auto), 120);
+        assert_eq!(summit(InputFormat::NarrowPeak), 120);
+        assert_eq!(summit(InputFormat::BroadPeak), 150);
+        assert_eq!(summit(InputFormat::Bed), 150);
+
+        // Column 10 is not interpreted, so BED12 records can be parsed in the BED format.
+        let bed12 = "chr1\t100\t200\tname\t0\t+\t100\t200\t0\t2\t3,4,\t0,6,\n";
+        let mut parser = BedParser {
+            input_format: InputFormat::Bed,
+            ..Default::default()
+        };
+        parser.parse(bed12.as_bytes(), "buffer").unwrap();
+        assert_eq!(parser.centred_summits, 1);
+        assert_eq!(parser.finish().unwrap()[&Chromosome::from("chr1")][0].summit(), 150);
+        assert_eq!(parser.centred_summits, 0);
+
+        // narrowPeak records require the summit column.
+        let mut parser = BedParser {
+            input_format: InputFormat::NarrowPeak,
+            ..Default::default()
+        };
+        let error = parser
+            .parse("chr1\t100\t200\n".as_bytes(), "buffer")
+            .unwrap_err();
+        assert_eq!(error.error_type(), ApplicationErrorType::ParseError);
+    }
+
+    #[test]
+    fn test_bed_to_peak_names() {
+        let names = bed_to_peak_names(
+            &[
+                test_resources().join("input_test_valid_minimal.narrowPeak"),
+                test_resources().join("input_test_stranded.narrowPeak"),
+            ],
+            InputFormat::Auto,
+        )
+        .unwrap();
+        // The minimal input file of the first 6 peaks does not contain names.
+        let expected: HashMap<usize, String> = (1..=5)
+            .map(|index| (index + 5, format!("peak_0{}", index)))
+            .collect();
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn test_bed_to_peaks_with_strands() {
+        let (peaks, strands) = bed_to_peaks_with_strands(
+            &[test_resources().join("input_test_stranded.narrowPeak")],
+            None,
+        )
+        .unwrap();
+        assert_eq!(peaks.values().map(Vec::len).sum::<usize>(), 5);
+        assert_eq!(
+            strands,
+            HashMap::from([
+                (0, Strand::Forward),
+                (1, Strand::Forward),
+                (2, Strand::Reverse),
+                (3, Strand::Reverse),
+            ])
+        );
+        let (_, strands) = bed_to_peaks_with_strands(
+            &[test_resources().join("input_test_valid_minimal.narrowPeak")],
+            None,
+        )
+        .unwrap();
+        assert!(strands.is_empty());
+        let mut parser = BedParser {
+            strands: Some(HashMap::new()),
+            ..Default::default()
+        };
+        assert!(
+            parser
+                .parse("chr1\t0\t10\tpeak\t0\tx\n".as_bytes(), "buffer")
+                .is_err()
+        );
+    }
+
     #[test]
     fn test_bed_to_peaks_multiple() {
-        let peaks = bed_to_peaks(&vec![
+        let peaks = bed_to_peaks(&[
             test_resources().join("input_test_valid_with_summit_multiple_01.narrowPeak"),
             test_resources().join("input_test_valid_with_summit_multiple_02.narrowPeak"),
             test_resources().join("input_test_valid_with_summit_multiple_03.narrowPeak"),
         ])
         .unwrap();
         assert_eq!(peaks.len(), 2);
-        assert_eq!(peaks["chr1"].len(), 4);
-        assert_eq!(peaks["chr2"].len(), 2);
+        assert_eq!(peaks[&Chromosome::from("chr1")].len(), 4);
+        assert_eq!(peaks[&Chromosome::from("chr2")].len(), 2);
         let expected_peaks_1 = vec![
             PeakData::new(0, 4470246u64, 4470509u64, 4470246u64 + 107u64).unwrap(),
             PeakData::new(2, 4496298u64, 4496749u64, 4496298u64 + 278u64).unwrap(),
@@ -193,10 +1750,10 @@ mod tests {
         ];
         for expected_peak in expected_peaks_1 {
             assert!(
-                peaks["chr1"].contains(&expected_peak),
+                peaks[&Chromosome::from("chr1")].contains(&expected_peak),
                 "Expected peak {:?} in {:?}...",
                 expected_peak,
-                peaks["chr1"]
+                peaks[&Chromosome::from("chr1")]
             );
         }
 
@@ -206,22 +1763,326 @@ mod tests {
         ];
         for expected_peak in expected_peaks_2 {
             assert!(
-                peaks["chr2"].contains(&expected_peak),
+                peaks[&Chromosome::from("chr2")].contains(&expected_peak),
                 "Expected peak {:?} in {:?}...",
                 expected_peak,
-                peaks["chr2"]
+                peaks[&Chromosome::from("chr2")]
             );
         }
     }
 
+    #[test]
+    fn test_bed_to_peaks_with_format_peaks_per_file() {
+        let paths = [
+            test_resources().join("input_test_valid_with_summit_multiple_01.narrowPeak"),
+            test_resources().join("input_test_valid_with_summit_multiple_02.narrowPeak"),
+            test_resources().join("input_test_valid_with_summit_multiple_03.narrowPeak"),
+        ];
+        let parsed =
+            bed_to_peaks_with_format(&paths, InputFormat::Auto, None, false, false).unwrap();
+        assert_eq!(parsed.peaks_per_file, vec![2, 2, 2]);
+        let ids_by_file: Vec<Vec<usize>> = parsed
+            .peaks_by_file()
+            .iter()
+            .map(|file| {
+                let mut ids: Vec<usize> = file.values().flatten().map(PeakData::id).collect();
+                ids.sort_unstable();
+                ids
+            })
+            .collect();
+        assert_eq!(ids_by_file, vec![vec![0, 1], vec![2, 3], vec![4, 5]]);
+        assert!(parsed.scores.is_empty());
+        // The statistical values are parsed together with the peaks.
+        let parsed =
+            bed_to_peaks_with_format(&paths, InputFormat::Auto, None, false, true).unwrap();
+        assert_eq!(parsed.scores.len(), 6);
+        assert_eq!(
+            parsed.scores[&2],
+            PeakScores::new(Some(1339.0), Some(22.3927), Some(136.971), Some(133.925))
+        );
+        // Files that cannot be read are counted without peaks if problems are collected.
+        let mut parser = BedParser {
+            max_errors: Some(10),
+            ..Default::default()
+        };
+        let _ = parser.parse_files(&[
+            &paths[0],
+            &test_resources().join("file_does_not_exist.error"),
+            &paths[1],
+        ]);
+        assert_eq!(parser.peaks_per_source, vec![2, 0, 2]);
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn test_bed_parser_parse_files_concurrently() {
+        let paths = [
+            test_resources().join("input_test_valid_minimal.narrowPeak"),
+            test_resources().join("input_test_stranded.narrowPeak"),
+            test_resources().join("input_test_valid_with_summit.narrowPeak"),
+        ];
+        let mut sequential_parser = BedParser {
+            approximated_summit_ids: Some(HashSet::new()),
+            strands: Some(HashMap::new()),
+            ..Default::default()
+        };
+        for path in &paths {
+            sequential_parser.parse_file(path).unwrap();
+        }
+        let sequential_peaks = sequential_parser.finish().unwrap();
+        let mut concurrent_parser = BedParser {
+            approximated_summit_ids: Some(HashSet::new()),
+            strands: Some(HashMap::new()),
+            ..Default::default()
+        };
+        let concurrent_peaks = concurrent_parser.parse_files(&paths).unwrap();
+        assert_eq!(concurrent_peaks, sequential_peaks);
+        assert_eq!(concurrent_parser.current_peak_id, sequential_parser.current_peak_id);
+        assert_eq!(
+            concurrent_parser.approximated_summit_ids,
+            sequential_parser.approximated_summit_ids
+        );
+        assert_eq!(concurrent_parser.strands, sequential_parser.strands);
+        assert_eq!(concurrent_parser.peaks_per_source, sequential_parser.peaks_per_source);
+    }
+
+    #[test]
+    fn test_bed_to_peaks_many_files() {
+        // More input files than the usual default limit of open file descriptors.
+        let number_of_files = 5000;
+        let paths =
+            vec![test_resources().join("input_test_valid_minimal.narrowPeak"); number_of_files];
+        let peaks = bed_to_peaks(&paths).unwrap();
+        assert_eq!(peaks.len(), 2);
+        assert_eq!(peaks[&Chromosome::from("chr1")].len(), 4 * number_of_files);
+        assert_eq!(peaks[&Chromosome::from("chr2")].len(), 2 * number_of_files);
+    }
+
+    #[test]
+    fn test_bed_to_peak_stream() {
+        let paths = [
+            test_resources().join("input_test_valid_with_summit_additional_features.narrowPeak")
+        ];
+        let streamed: Vec<(Chromosome, Vec<PeakData>)> = bed_to_peak_stream(&paths)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            streamed
+                .iter()
+                .map(|(chromosome, _)| *chromosome)
+                .collect::<Vec<_>>(),
+            vec![Chromosome::from("chr1"), Chromosome::from("chr2")]
+        );
+        assert_eq!(streamed.into_iter().collect::<HashMap<_, _>>(), bed_to_peaks(&paths).unwrap());
+    }
+
+    #[test]
+    fn test_bed_to_peak_stream_chunked_files() {
+        // The first file only contains peaks of chr1, which are continued by the second file.
+        let paths = [
+            test_resources().join("input_test_valid_with_summit_multiple_02.narrowPeak"),
+            test_resources().join("input_test_valid_minimal.narrowPeak"),
+        ];
+        let streamed: HashMap<Chromosome, Vec<PeakData>> = bed_to_peak_stream(&paths)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(streamed, bed_to_peaks(&paths).unwrap());
+    }
+
+    #[test]
+    fn test_bed_to_peak_stream_per_sample_files() {
+        let paths = [
+            test_resources().join("input_test_valid_with_summit_multiple_01.narrowPeak"),
+            test_resources().join("input_test_valid_with_summit_multiple_02.narrowPeak"),
+            test_resources().join("input_test_valid_with_summit_multiple_03.narrowPeak"),
+        ];
+        let mut stream = bed_to_peak_stream(&paths).parse_scores();
+        let streamed: Vec<(Chromosome, Vec<PeakData>)> =
+            stream.by_ref().collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            streamed
+                .iter()
+                .map(|(chromosome, _)| *chromosome)
+                .collect::<Vec<_>>(),
+            vec![Chromosome::from("chr1"), Chromosome::from("chr2")]
+        );
+        let parsed =
+            bed_to_peaks_with_format(&paths, InputFormat::Auto, None, false, true).unwrap();
+        assert_eq!(streamed.into_iter().collect::<HashMap<_, _>>(), parsed.peaks);
+        assert_eq!(stream.peaks_per_file(), &[2, 2, 2]);
+        assert_eq!(stream.take_scores(), parsed.scores);
+    }
+
+    #[test]
+    fn test_bed_to_peak_stream_many_files() {
+        // More input files than the usual default limit of open file descriptors.
+        let number_of_files = 5000;
+        let paths =
+            vec![test_resources().join("input_test_valid_minimal.narrowPeak"); number_of_files];
+        let mut stream = bed_to_peak_stream(&paths);
+        let streamed: HashMap<Chromosome, Vec<PeakData>> =
+            stream.by_ref().collect::<Result<_, _>>().unwrap();
+        assert_eq!(streamed, bed_to_peaks(&paths).unwrap());
+        assert_eq!(stream.peaks_per_file(), vec![6; number_of_files]);
+        // Files are reopened and resumed at the current line.
+        let paths = [
+            test_resources().join("input_test_valid_with_summit_multiple_01.narrowPeak"),
+            test_resources().join("input_test_valid_with_summit_multiple_02.narrowPeak"),
+            test_resources().join("input_test_valid_with_summit_multiple_03.narrowPeak"),
+        ];
+        let streamed: HashMap<Chromosome, Vec<PeakData>> = bed_to_peak_stream(&paths)
+            .max_open_files(1)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(streamed, bed_to_peaks(&paths).unwrap());
+    }
+
+    #[test]
+    fn test_bed_to_peak_stream_reopen() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let path_a = test_output().join("test_bed_to_peak_stream_reopen_a.bed");
+        let path_b = test_output().join("test_bed_to_peak_stream_reopen_b.bed");
+        std::fs::write(&path_a, "chr1\t10\t20\nchr2\t10\t20\n").unwrap();
+        std::fs::write(&path_b, "chr1\t30\t40\nchr1\t50\t60\nchr2\t30\t40\n").unwrap();
+        let expected = bed_to_peaks(&[&path_a, &path_b]).unwrap();
+        let mut stream = bed_to_peak_stream(&[&path_a, &path_b]).max_open_files(1);
+        let chr1 = stream.next().unwrap().unwrap();
+        // Replaces the lines already read from the closed file without changing its size,
+        // so the file only resumes at the correct record if the read lines are not reread.
+        std::fs::write(&path_b, format!("{}\nchr2\t30\t40\n", "x".repeat(21))).unwrap();
+        let chr2 = stream.next().unwrap().unwrap();
+        assert!(stream.next().is_none());
+        std::fs::remove_file(&path_a).unwrap();
+        std::fs::remove_file(&path_b).unwrap();
+        assert_eq!(chr1, (Chromosome::from("chr1"), expected[&Chromosome::from("chr1")].clone()));
+        assert_eq!(chr2, (Chromosome::from("chr2"), expected[&Chromosome::from("chr2")].clone()));
+    }
+
+    #[test]
+    fn test_bed_to_peak_stream_compressed_kept_open() {
+        use std::io::Write;
+
+        use flate2::{Compression, write::GzEncoder};
+
+        std::fs::create_dir_all(test_output()).unwrap();
+        let path_a = test_output().join("test_bed_to_peak_stream_compressed_kept_open_a.bed.gz");
+        let path_b = test_output().join("test_bed_to_peak_stream_compressed_kept_open_b.bed.gz");
+        for (path, content) in [
+            (&path_a, "chr1\t10\t20\nchr2\t10\t20\n"),
+            (&path_b, "chr1\t30\t40\nchr1\t50\t60\nchr2\t30\t40\n"),
+        ] {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(content.as_bytes()).unwrap();
+            std::fs::write(path, encoder.finish().unwrap()).unwrap();
+        }
+        let mut stream = bed_to_peak_stream(&[&path_a, &path_b]).max_open_files(1);
+        stream.next().unwrap().unwrap();
+        // Compressed files are not closed to stay within the limit of open files.
+        assert!(
+            stream
+                .files
+                .iter()
+                .flatten()
+                .all(|file| file.reader.is_some())
+        );
+        let chr2 = stream.next().unwrap().unwrap();
+        assert!(stream.next().is_none());
+        let expected = bed_to_peaks(&[&path_a, &path_b]).unwrap();
+        std::fs::remove_file(&path_a).unwrap();
+        std::fs::remove_file(&path_b).unwrap();
+        assert_eq!(chr2, (Chromosome::from("chr2"), expected[&Chromosome::from("chr2")].clone()));
+    }
+
+    #[test]
+    fn test_bed_to_peak_stream_missing_chromosome() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let path_a = test_output().join("test_bed_to_peak_stream_missing_chromosome_a.bed");
+        let path_b = test_output().join("test_bed_to_peak_stream_missing_chromosome_b.bed");
+        std::fs::write(&path_a, "chr2\t10\t20\n").unwrap();
+        std::fs::write(&path_b, "chr1\t10\t20\nchr2\t15\t25\nchr3\t10\t20\n").unwrap();
+        let paths = [&path_a, &path_b];
+        let streamed: Vec<(Chromosome, Vec<PeakData>)> = bed_to_peak_stream(&paths)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let peaks = bed_to_peaks(&paths).unwrap();
+        std::fs::remove_file(&path_a).unwrap();
+        std::fs::remove_file(&path_b).unwrap();
+        assert_eq!(
+            streamed
+                .iter()
+                .map(|(chromosome, _)| *chromosome)
+                .collect::<Vec<_>>(),
+            vec![
+                Chromosome::from("chr1"),
+                Chromosome::from("chr2"),
+                Chromosome::from("chr3")
+            ]
+        );
+        assert_eq!(streamed.into_iter().collect::<HashMap<_, _>>(), peaks);
+    }
+
+    #[test]
+    fn test_bed_to_peak_stream_not_consecutive() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let path = test_output().join("test_bed_to_peak_stream_not_consecutive.bed");
+        std::fs::write(&path, "chr1\t10\t20\nchr2\t10\t20\nchr1\t30\t40\n").unwrap();
+        let mut stream = bed_to_peak_stream(&[&path]);
+        let err = stream.next().unwrap().unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(err.error_type(), ApplicationErrorType::ValidationError);
+        assert_eq!(err.context().and_then(|context| context.line()), Some(3));
+        assert!(stream.next().is_none());
+    }
+
+    #[test]
+    fn test_bed_to_peak_stream_different_order() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let path_a = test_output().join("test_bed_to_peak_stream_different_order_a.bed");
+        let path_b = test_output().join("test_bed_to_peak_stream_different_order_b.bed");
+        std::fs::write(&path_a, "chr1\t10\t20\nchr2\t10\t20\n").unwrap();
+        std::fs::write(&path_b, "chr2\t10\t20\nchr1\t10\t20\n").unwrap();
+        let mut stream = bed_to_peak_stream(&[&path_a, &path_b]);
+        let err = stream.next().unwrap().unwrap_err();
+        std::fs::remove_file(&path_a).unwrap();
+        std::fs::remove_file(&path_b).unwrap();
+        assert_eq!(err.error_type(), ApplicationErrorType::ValidationError);
+        assert!(stream.next().is_none());
+    }
+
+    #[test]
+    fn test_bed_to_peak_stream_invalid() {
+        let mut stream =
+            bed_to_peak_stream(&[test_resources().join("input_test_invalid_start.narrowPeak")]);
+        let err = stream.next().unwrap().unwrap_err();
+        assert_eq!(err.error_type(), ApplicationErrorType::ParseError);
+        assert!(stream.next().is_none());
+        let mut stream = bed_to_peak_stream(&[test_resources().join("does_not_exist.narrowPeak")]);
+        assert!(stream.next().unwrap().is_err());
+        assert!(stream.next().is_none());
+    }
+
+    #[test]
+    fn test_bed_buffers_to_peaks() {
+        let paths = [
+            test_resources().join("input_test_valid_with_summit_multiple_01.narrowPeak"),
+            test_resources().join("input_test_valid_with_summit_multiple_02.narrowPeak"),
+            test_resources().join("input_test_valid_with_summit_multiple_03.narrowPeak"),
+        ];
+        let buffers: Vec<Vec<u8>> = paths
+            .iter()
+            .map(|path| std::fs::read(path).unwrap())
+            .collect();
+        assert_eq!(bed_buffers_to_peaks(&buffers).unwrap(), bed_to_peaks(&paths).unwrap());
+        assert!(bed_buffers_to_peaks(&["chr1\t10\n"]).is_err());
+    }
+
     #[test]
     fn test_bed_to_peaks_minimal() {
         let peaks =
-            bed_to_peaks(&vec![test_resources().join("input_test_valid_minimal.narrowPeak")])
-                .unwrap();
+            bed_to_peaks(&[test_resources().join("input_test_valid_minimal.narrowPeak")]).unwrap();
         assert_eq!(peaks.len(), 2);
-        assert_eq!(peaks["chr1"].len(), 4);
-        assert_eq!(peaks["chr2"].len(), 2);
+        assert_eq!(peaks[&Chromosome::from("chr1")].len(), 4);
+        assert_eq!(peaks[&Chromosome::from("chr2")].len(), 2);
         let expected_peaks_1 = vec![
             PeakData::new(0, 4470246u64, 4470509u64, (4470246u64 + 4470509u64) / 2).unwrap(),
             PeakData::new(1, 4496298u64, 4496749u64, (4496298u64 + 4496749u64) / 2).unwrap(),
@@ -230,10 +2091,10 @@ mod tests {
         ];
         for expected_peak in expected_peaks_1 {
             assert!(
-                peaks["chr1"].contains(&expected_peak),
+                peaks[&Chromosome::from("chr1")].contains(&expected_peak),
                 "Expected peak {:?} in {:?}..",
                 expected_peak,
-                peaks["chr1"]
+                peaks[&Chromosome::from("chr1")]
             );
         }
 
@@ -243,23 +2104,23 @@ mod tests {
         ];
         for expected_peak in expected_peaks_2 {
             assert!(
-                peaks["chr2"].contains(&expected_peak),
+                peaks[&Chromosome::from("chr2")].contains(&expected_peak),
                 "Expected peak {:?} in {:?}..",
                 expected_peak,
-                peaks["chr2"]
+                peaks[&Chromosome::from("chr2")]
             );
         }
     }
 
     #[test]
     fn test_bed_to_peaks_additional_format_specifications() {
-        let peaks = bed_to_peaks(&vec![
-            test_resources().join("input_test_valid_with_summit_additional_features.narrowPeak"),
-        ])
-        .unwrap();
+        let peaks =
+            bed_to_peaks(&[test_resources()
+                .join("input_test_valid_with_summit_additional_features.narrowPeak")])
+            .unwrap();
         assert_eq!(peaks.len(), 2);
-        assert_eq!(peaks["chr1"].len(), 4);
-        assert_eq!(peaks["chr2"].len(), 2);
+        assert_eq!(peaks[&Chromosome::from("chr1")].len(), 4);
+        assert_eq!(peaks[&Chromosome::from("chr2")].len(), 2);
 
         let expected_peaks_1 = vec![
             PeakData::new(0, 4470246u64, 4470509u64, 4470246u64 + 107u64).unwrap(),
@@ -269,10 +2130,10 @@ mod tests {
         ];
         for expected_peak in expected_peaks_1 {
             assert!(
-                peaks["chr1"].contains(&expected_peak),
+                peaks[&Chromosome::from("chr1")].contains(&expected_peak),
                 "Expected peak {:?} in {:?}..",
                 expected_peak,
-                peaks["chr1"]
+                peaks[&Chromosome::from("chr1")]
             );
         }
 
@@ -282,10 +2143,10 @@ mod tests {
         ];
         for expected_peak in expected_peaks_2 {
             assert!(
-                peaks["chr2"].contains(&expected_peak),
+                peaks[&Chromosome::from("chr2")].contains(&expected_peak),
                 "Expected peak {:?} in {:?}..",
                 expected_peak,
-                peaks["chr2"]
+                peaks[&Chromosome::from("chr2")]
             );
         }
     }
@@ -294,7 +2155,7 @@ mod tests {
     fn test_bed_to_peaks_file_does_not_exist() {
         let expected_error_message_content = "could not be opened.";
         let error =
-            bed_to_peaks(&vec![test_resources().join("file_does_not_exist.error")]).unwrap_err();
+            bed_to_peaks(&[test_resources().join("file_does_not_exist.error")]).unwrap_err();
         assert!(
             error
                 .internal_messages()
@@ -310,9 +2171,8 @@ mod tests {
     #[test]
     fn test_bed_to_peaks_invalid_encoding() {
         let expected_error_message_content = "Failed to parse line";
-        let error =
-            bed_to_peaks(&vec![test_resources().join("input_test_invalid_utf8.narrowPeak")])
-                .unwrap_err();
+        let error = bed_to_peaks(&[test_resources().join("input_test_invalid_utf8.narrowPeak")])
+            .unwrap_err();
         assert!(
             error
                 .internal_messages()
@@ -328,9 +2188,8 @@ mod tests {
     #[test]
     fn test_bed_to_peaks_invalid_start() {
         let expected_error_message_content = "could not be parsed as genomic start coordinates.";
-        let error =
-            bed_to_peaks(&vec![test_resources().join("input_test_invalid_start.narrowPeak")])
-                .unwrap_err();
+        let error = bed_to_peaks(&[test_resources().join("input_test_invalid_start.narrowPeak")])
+            .unwrap_err();
         assert!(
             error
                 .internal_messages()
@@ -343,10 +2202,143 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_bed_to_peaks_invalid_start_context() {
+        let path = test_resources().join("input_test_invalid_start.narrowPeak");
+        let error = bed_to_peaks(&[&path]).unwrap_err();
+        let context = error.context().unwrap();
+        assert_eq!(context.path(), &Some(path.display().to_string()));
+        assert!(context.line().is_some());
+        assert_eq!(context.column(), Some(2));
+        assert_eq!(context.field().as_deref(), Some("chromStart"));
+        assert!(context.value().is_some());
+        assert_eq!(error.hints(), &vec!["Genomic coordinates must not be negative.".to_string()]);
+    }
+
+    #[test]
+    fn test_coordinate_hint() {
+        assert_eq!(coordinate_hint("-10"), "Genomic coordinates must not be negative.");
+        assert_eq!(
+            coordinate_hint("chromStart"),
+            "Header lines must start with \"#\" to be skipped."
+        );
+        assert_eq!(coordinate_hint("1.5"), "Genomic coordinates must be unsigned integers.");
+    }
+
+    #[test]
+    fn test_input_format_detect() {
+        assert_eq!(
+            ConfusedFormat::detect(&[
+                "##gff-version 3",
+                "chr1\tsource\tpeak\t10\t20\t.\t+\t.\tID=1"
+            ]),
+            Some(ConfusedFormat::Gff)
+        );
+        assert_eq!(
+            ConfusedFormat::detect(&["chr1\tsource\tpeak\t10\t20\t.\t+\t.\tID=1\n"]),
+            Some(ConfusedFormat::Gff)
+        );
+        assert_eq!(
+            ConfusedFormat::detect(&["##fileformat=VCFv4.2", "chr1\t10\t.\tA\tT\t.\t.\t."]),
+            Some(ConfusedFormat::Vcf)
+        );
+        assert_eq!(
+            ConfusedFormat::detect(&["chrom,start,end", "chr1,10,20"]),
+            Some(ConfusedFormat::Csv)
+        );
+        assert_eq!(
+            ConfusedFormat::detect(&["chr1\t10\t20\tname\t0\t+\t10\t20\t0\t2\t3,4,\t0,6,"]),
+            Some(ConfusedFormat::Bed12)
+        );
+        assert_eq!(
+            ConfusedFormat::detect(&["chr1\t10\t20\tname\t0\t.\t1.5\t2.5\t3.5"]),
+            Some(ConfusedFormat::BroadPeak)
+        );
+        assert_eq!(ConfusedFormat::detect(&["chr1\t10\t20"]), None);
+        assert_eq!(ConfusedFormat::detect(&[]), None);
+    }
+
+    #[test]
+    fn test_bed_buffers_to_peaks_format_suggestion() {
+        let error = bed_buffers_to_peaks(&["chrom,start,end\nchr1,10,20\n"]).unwrap_err();
+        assert_eq!(
+            error.hints().last().map(String::as_str),
+            Some(ConfusedFormat::Csv.suggestion())
+        );
+    }
+
+    #[test]
+    fn test_bed_buffers_to_peaks_summit_overflow() {
+        let error = bed_buffers_to_peaks(&[
+            "chr1\t18446744073709551610\t18446744073709551615\t.\t0\t.\t0\t0\t0\t10\n",
+        ])
+        .unwrap_err();
+        assert_eq!(error.error_type(), ApplicationErrorType::ValidationError);
+        assert_eq!(error.context().and_then(|context| context.column()), Some(10));
+    }
+
+    #[test]
+    fn test_bed_line_parser() {
+        let mut parser = BedLineParser::new("buffer", InputFormat::Auto);
+        assert_eq!(parser.parse_line("# comment\n").unwrap(), None);
+        let (chromosome, peak) = parser
+            .parse_line("chr1\t10\t20\t.\t0\t+\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(chromosome, Chromosome::intern("chr1"));
+        assert_eq!((peak.id(), peak.start(), peak.end()), (0, 10, 20));
+        let error = parser.parse_line("chr1\t10\t20\t.\t0\t*\n").unwrap_err();
+        assert_eq!(error.context().and_then(|context| context.line()), Some(3));
+        // Parsing continues after an invalid line.
+        let (_, peak) = parser.parse_line("chr1\t30\t40").unwrap().unwrap();
+        assert_eq!((peak.id(), peak.summit()), (1, 35));
+    }
+
+    #[test]
+    fn test_line_summary() {
+        let mut summary = LineSummary::default();
+        assert_eq!(summary.format_lines(), "");
+        for line in 1..=MAX_WARNING_LINES as u64 {
+            summary.add(line);
+        }
+        assert_eq!(summary.format_lines(), "1, 2, 3, 4, 5");
+        summary.add(10);
+        assert_eq!(summary.count, MAX_WARNING_LINES + 1);
+        assert_eq!(summary.format_lines(), "1, 2, 3, 4, 5, ...");
+    }
+
+    #[test]
+    fn test_bed_to_peaks_collecting_errors() {
+        let paths = [
+            test_resources().join("input_test_invalid_start.narrowPeak"),
+            test_resources().join("file_does_not_exist.error"),
+            test_resources().join("input_test_invalid_end.narrowPeak"),
+            test_resources().join("input_test_valid_with_summit.narrowPeak"),
+        ];
+        let error = bed_to_peaks_collecting_errors(&paths, 10).unwrap_err();
+        assert_eq!(error.error_type(), ApplicationErrorType::ParseError);
+        let lines: Vec<Option<u64>> = error
+            .related()
+            .iter()
+            .map(|related| related.context().and_then(|context| context.line()))
+            .collect();
+        assert_eq!(lines, vec![Some(2), None, Some(4)]);
+        assert_eq!(error.related()[1].error_type(), ApplicationErrorType::IOError);
+        let capped_error = bed_to_peaks_collecting_errors(&paths, 2).unwrap_err();
+        assert_eq!(capped_error.related().len(), 2);
+        assert!(
+            bed_to_peaks_collecting_errors(
+                &[test_resources().join("input_test_valid_with_summit.narrowPeak")],
+                10
+            )
+            .is_ok()
+        );
+    }
+
     #[test]
     fn test_bed_to_peaks_invalid_end() {
         let expected_error_message_content = "could not be parsed as genomic end coordinates.";
-        let error = bed_to_peaks(&vec![test_resources().join("input_test_invalid_end.narrowPeak")])
+        let error = bed_to_peaks(&[test_resources().join("input_test_invalid_end.narrowPeak")])
             .unwrap_err();
         assert!(
             error
@@ -363,9 +2355,8 @@ mod tests {
     #[test]
     fn test_bed_to_peaks_invalid_summit() {
         let expected_error_message_content = "could not be parsed as peak summit coordinates.";
-        let error =
-            bed_to_peaks(&vec![test_resources().join("input_test_invalid_summit.narrowPeak")])
-                .unwrap_err();
+        let error = bed_to_peaks(&[test_resources().join("input_test_invalid_summit.narrowPeak")])
+            .unwrap_err();
         assert!(
             error
                 .internal_messages()
@@ -381,8 +2372,8 @@ mod tests {
     #[test]
     fn test_bed_to_peaks_invalid_fields() {
         let expected_error_message_content = "does not contain the minimally required records.";
-        let error = bed_to_peaks(&vec![
-            test_resources().join("input_test_invalid_not_enough_fields.narrowPeak"),
+        let error = bed_to_peaks(&[
+            test_resources().join("input_test_invalid_not_enough_fields.narrowPeak")
         ])
         .unwrap_err();
         assert!(
@@ -400,10 +2391,9 @@ mod tests {
     #[test]
     fn test_bed_to_peaks_invalid_data_start_end() {
         let expected_error_message_content = "contains invalid data.";
-        let error = bed_to_peaks(&vec![
-            test_resources().join("input_test_invalid_data_start_end.narrowPeak"),
-        ])
-        .unwrap_err();
+        let error =
+            bed_to_peaks(&[test_resources().join("input_test_invalid_data_start_end.narrowPeak")])
+                .unwrap_err();
         assert!(
             error
                 .internal_messages()
@@ -420,7 +2410,7 @@ mod tests {
     fn test_bed_to_peaks_invalid_data_summit() {
         let expected_error_message_content = "contains invalid data.";
         let error =
-            bed_to_peaks(&vec![test_resources().join("input_test_invalid_data_summit.narrowPeak")])
+            bed_to_peaks(&[test_resources().join("input_test_invalid_data_summit.narrowPeak")])
                 .unwrap_err();
         assert!(
             error
@@ -0,0 +1,298 @@
+//! This module provides a static interval tree for fast overlap queries.
+//! The tree is stored implicitly in an array sorted by start coordinate as described for
+//! [cgranges](https://github.com/lh3/cgranges), so it does not require any additional
+//! allocations besides the maximum end coordinate of each subtree.
+
+use crate::peaks::PeakData;
+
+/// Below this number of intervals queries are answered by a linear scan.
+const LINEAR_SCAN_THRESHOLD: usize = 16;
+
+/// A genomic interval with inclusive start and end coordinates.
+pub trait Interval {
+    /// The start coordinate of the interval.
+    fn start(&self) -> u64;
+    /// The end coordinate of the interval (inclusive).
+    fn end(&self) -> u64;
+}
+
+impl Interval for PeakData {
+    fn start(&self) -> u64 {
+        PeakData::start(self)
+    }
+
+    fn end(&self) -> u64 {
+        PeakData::end(self)
+    }
+}
+
+/// An implicit interval tree over a collection of intervals.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntervalTree<T> {
+    /// The intervals sorted by start coordinate.
+    items: Vec<T>,
+    /// The maximum end coordinate of the subtree rooted at the respective index.
+    max_ends: Vec<u64>,
+    /// The level of the root node.
+    root_level: usize,
+}
+
+impl<T: Interval> IntervalTree<T> {
+    /// Creates a new interval tree from the specified intervals.
+    /// Intervals with equal start coordinates keep their relative order.
+    ///
+    /// # Parameters
+    ///
+    /// * `items` - the intervals to index
+    pub fn new(mut items: Vec<T>) -> Self {
+        items.sort_by_key(|item| item.start());
+        Self::from_sorted(items)
+    }
+
+    /// Creates a new interval tree from intervals that are already sorted by start coordinate.
+    ///
+    /// # Parameters
+    ///
+    /// * `items` - the intervals sorted by start coordinate
+    ///
+    /// # Panics
+    ///
+    /// Panics if the intervals are not sorted by start coordinate.
+    pub fn from_sorted(items: Vec<T>) -> Self {
+        assert!(
+            items.is_sorted_by_key(|item| item.start()),
+            "The intervals must be sorted by start coordinate."
+        );
+        let mut tree = Self {
+            items,
+            max_ends: Vec::new(),
+            root_level: 0,
+        };
+        tree.index();
+        tree
+    }
+
+    /// Builds the implicit tree by calculating the maximum end coordinate of each subtree.
+    fn index(&mut self) {
+        let n = self.items.len();
+        self.max_ends = self.items.iter().map(Interval::end).collect();
+        self.root_level = 0;
+        if n == 0 {
+            return;
+        }
+        // Leaves are located at even indices.
+        let mut last_index = (n - 1) & !1;
+        let mut last_max_end = self.max_ends[last_index];
+        let mut level = 1;
+        while 1usize << level <= n {
+            let offset = 1usize << (level - 1);
+            let mut index = (offset << 1) - 1;
+            while index < n {
+                let left_max_end = self.max_ends[index - offset];
+                let right_max_end = if index + offset < n {
+                    self.max_ends[index + offset]
+                } else {
+                    last_max_end
+                };
+                self.max_ends[index] = self.max_ends[index].max(left_max_end).max(right_max_end);
+                index += offset << 2;
+            }
+            last_index = if (last_index >> level) & 1 == 1 {
+                last_index - offset
+            } else {
+                last_index + offset
+            };
+            if last_index < n && self.max_ends[last_index] > last_max_end {
+                last_max_end = self.max_ends[last_index];
+            }
+            level += 1;
+        }
+        self.root_level = level - 1;
+    }
+
+    /// Returns all indexed intervals sorted by start coordinate.
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Returns the number of indexed intervals.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns `true` if the tree does not contain any intervals.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Inserts an interval and re-indexes the tree.
+    ///
+    /// # Parameters
+    ///
+    /// * `item` - the interval to insert
+    pub fn insert(&mut self, item: T) {
+        let index = self
+            .items
+            .partition_point(|existing| existing.start() <= item.start());
+        self.items.insert(index, item);
+        self.index();
+    }
+
+    /// Returns the indices of all intervals overlapping the query range in ascending order.
+    ///
+    /// # Parameters
+    ///
+    /// * `start` - the start of the query range
+    /// * `end` - the end of the query range (inclusive)
+    pub fn overlapping_indices(&self, start: u64, end: u64) -> Vec<usize> {
+        let n = self.items.len();
+        let overlaps =
+            |index: usize| self.items[index].start() <= end && start <= self.items[index].end();
+        let mut indices = Vec::new();
+        if n < LINEAR_SCAN_THRESHOLD {
+            indices.extend(
+                (0..n)
+                    .take_while(|index| self.items[*index].start() <= end)
+                    .filter(|index| overlaps(*index)),
+            );
+            return indices;
+        }
+        // Stack of (level, node index, left child processed) for a top down traversal.
+        let mut stack = vec![(self.root_level, (1usize << self.root_level) - 1, false)];
+        while let Some((level, node, left_processed)) = stack.pop() {
+            if level <= 3 {
+                // Small subtrees are scanned linearly.
+                let first = node >> level << level;
+                let last = (first + (1usize << (level + 1)) - 1).min(n);
+                indices.extend(
+                    (first..last)
+                        .take_while(|index| self.items[*index].start() <= end)
+                        .filter(|index| overlaps(*index)),
+                );
+            } else if !left_processed {
+                let left_child = node - (1usize << (level - 1));
+                stack.push((level, node, true));
+                // The left child might be out of range if the tree is not complete.
+                if left_child >= n || self.max_ends[left_child] >= start {
+                    stack.push((level - 1, left_child, false));
+                }
+            } else if node < n && self.items[node].start() <= end {
+                if overlaps(node) {
+                    indices.push(node);
+                }
+                stack.push((level - 1, node + (1usize << (level - 1)), false));
+            }
+        }
+        indices
+    }
+
+    /// Returns all intervals overlapping the query range sorted by start coordinate.
+    ///
+    /// # Parameters
+    ///
+    /// * `start` - the start of the query range
+    /// * `end` - the end of the query range (inclusive)
+    pub fn overlapping(&self, start: u64, end: u64) -> Vec<&T> {
+        self.overlapping_indices(start, end)
+            .into_iter()
+            .map(|index| &self.items[index])
+            .collect()
+    }
+}
+
+impl<T: Interval> Default for IntervalTree<T> {
+    fn default() -> Self {
+        Self::from_sorted(Vec::new())
+    }
+}
+
+impl<T> From<IntervalTree<T>> for Vec<T> {
+    fn from(value: IntervalTree<T>) -> Self {
+        value.items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A simple linear congruential generator for reproducible test data.
+    fn pseudo_random(state: &mut u64) -> u64 {
+        *state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        *state >> 33
+    }
+
+    /// Creates reproducible pseudo random peaks.
+    fn random_peaks(number: usize, seed: u64) -> Vec<PeakData> {
+        let mut state = seed;
+        (0..number)
+            .map(|id| {
+                let start = pseudo_random(&mut state) % 100_000;
+                let length = pseudo_random(&mut state) % 2_000;
+                PeakData::new(id, start, start + length, start).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_interval_tree_overlapping_matches_linear_scan() {
+        for number in [0, 1, 2, 7, 15, 16, 17, 100, 1023, 1024, 1025, 5000] {
+            let peaks = random_peaks(number, number as u64 + 42);
+            let tree = IntervalTree::new(peaks.clone());
+            assert_eq!(tree.len(), number);
+            let mut state = 7;
+            for _ in 0..200 {
+                let start = pseudo_random(&mut state) % 105_000;
+                let end = start + pseudo_random(&mut state) % 3_000;
+                let mut expected: Vec<usize> = peaks
+                    .iter()
+                    .filter(|peak| peak.start() <= end && start <= peak.end())
+                    .map(PeakData::id)
+                    .collect();
+                expected.sort();
+                let mut actual: Vec<usize> = tree
+                    .overlapping(start, end)
+                    .into_iter()
+                    .map(PeakData::id)
+                    .collect();
+                actual.sort();
+                assert_eq!(
+                    actual, expected,
+                    "Query [{}, {}] with {} intervals.",
+                    start, end, number
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_interval_tree_overlapping_indices_sorted() {
+        let tree = IntervalTree::new(random_peaks(3000, 3));
+        let indices = tree.overlapping_indices(40_000, 60_000);
+        assert!(!indices.is_empty());
+        assert!(indices.is_sorted());
+    }
+
+    #[test]
+    fn test_interval_tree_insert() {
+        let mut tree = IntervalTree::default();
+        assert!(tree.is_empty());
+        for peak in random_peaks(100, 11) {
+            tree.insert(peak);
+        }
+        assert_eq!(tree.len(), 100);
+        assert!(tree.items().is_sorted_by_key(|peak| peak.start()));
+        assert_eq!(tree, IntervalTree::new(Vec::from(tree.clone())));
+    }
+
+    #[test]
+    fn test_interval_tree_inclusive_bounds() {
+        let tree = IntervalTree::new(vec![PeakData::new(0, 10u64, 20u64, 15u64).unwrap()]);
+        assert_eq!(tree.overlapping(20, 30).len(), 1);
+        assert_eq!(tree.overlapping(0, 10).len(), 1);
+        assert!(tree.overlapping(21, 30).is_empty());
+        assert!(tree.overlapping(0, 9).is_empty());
+    }
+}
@@ -0,0 +1,83 @@
+//! This module converts peak maps to and from JSON.
+//!
+//! Peak maps are represented as a JSON object mapping chromosome names to arrays of peaks.
+//! Each peak is an object with the fields `id`, `start`, `end` (inclusive) and `summit`.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{chromosome::Chromosome, error::ApplicationError, peaks::PeakData};
+
+/// Serialises the specified peaks grouped by chromosome to JSON.
+/// Chromosomes are written in sorted order, while the peaks keep their order.
+///
+/// # Parameters
+///
+/// * `peaks` - the peaks grouped by chromosome
+///
+/// # Errors
+///
+/// Returns an error if the serialisation fails.
+pub fn to_json(peaks: &HashMap<Chromosome, Vec<PeakData>>) -> Result<String, ApplicationError> {
+    let sorted_peaks: BTreeMap<&Chromosome, &Vec<PeakData>> = peaks.iter().collect();
+    Ok(serde_json::to_string(&sorted_peaks)?)
+}
+
+/// Deserialises peaks grouped by chromosome from JSON.
+///
+/// # Parameters
+///
+/// * `json` - the JSON representation of the peaks
+///
+/// # Errors
+///
+/// Returns an error if the JSON is malformed or contains invalid peaks.
+pub fn from_json<T: AsRef<str>>(
+    json: T,
+) -> Result<HashMap<Chromosome, Vec<PeakData>>, ApplicationError> {
+    Ok(serde_json::from_str(json.as_ref())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ApplicationErrorType;
+
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let peaks: HashMap<Chromosome, Vec<PeakData>> = HashMap::from([
+            (Chromosome::from("chr2"), vec![PeakData::new(2, 50u64, 80u64, 60u64).unwrap()]),
+            (
+                Chromosome::from("chr1"),
+                vec![
+                    PeakData::new(0, 100u64, 200u64, 150u64).unwrap(),
+                    PeakData::new(1, 10u64, 20u64, 10u64).unwrap(),
+                ],
+            ),
+        ]);
+        let json = to_json(&peaks).unwrap();
+        assert_eq!(
+            json,
+            r#"{"chr1":[{"id":0,"start":100,"end":200,"summit":150},{"id":1,"start":10,"end":20,"summit":10}],"chr2":[{"id":2,"start":50,"end":80,"summit":60}]}"#
+        );
+        assert_eq!(from_json(&json).unwrap(), peaks);
+    }
+
+    #[test]
+    fn test_from_json_invalid_peak() {
+        let error =
+            from_json(r#"{"chr1":[{"id":0,"start":100,"end":200,"summit":250}]}"#).unwrap_err();
+        assert_eq!(error.error_type(), ApplicationErrorType::ValidationError);
+        let error =
+            from_json(r#"{"chr1":[{"id":0,"start":300,"end":200,"summit":250}]}"#).unwrap_err();
+        assert_eq!(error.error_type(), ApplicationErrorType::ValidationError);
+    }
+
+    #[test]
+    fn test_from_json_malformed() {
+        let error = from_json(r#"{"chr1":[{"id":0,"start":100}]}"#).unwrap_err();
+        assert_eq!(error.error_type(), ApplicationErrorType::ValidationError);
+        let error = from_json("not json").unwrap_err();
+        assert_eq!(error.error_type(), ApplicationErrorType::ParseError);
+    }
+}
@@ -0,0 +1,147 @@
+//! Creates consensus peaks from raw peaks called on ChIP- or ATAC-Seq data.
+//!
+//! The library exposes the building blocks used by the `Gipfelkreuzer` command line application,
+//! so consensus peaks can be generated programmatically without calling the executable:
+//!
+//! * [`input`] parses BED3+ / narrowPeak files into [`peaks::PeakData`] grouped by [`chromosome::Chromosome`]
+//! * [`consensus`] provides the [`consensus::ConsensusBuilder`] running consensus peak generation across chromosomes
+//!   and the streaming [`consensus::ConsensusStream`]
+//! * [`peaks`] contains the peak data types and the consensus peak algorithms
+//! * [`pipeline`] runs consensus peak generation from peak files to the consensus peak output file
+//! * [`peak_set`] provides the [`peak_set::PeakSet`] collection supporting region set operations and overlap queries
+//! * [`output`] writes consensus peaks as BED files or in-memory BED buffers
+//! * [`chromosome`] interns chromosome names, so they can be copied and compared cheaply
+//! * [`interval_tree`] provides the static interval tree backing overlap queries
+//! * [`annotation`] labels peaks with the categories of overlapping reference regions
+//! * [`assembly`] detects input files originating from different genome assemblies
+//! * [`background`] samples random background regions matched to peaks as null set
+//! * [`blacklist`] removes or trims peaks overlapping blacklisted regions during consensus peak generation
+//! * [`compare`] benchmarks peak sets against a reference peak set
+//! * [`concordance`] quantifies the agreement of samples on consensus peak summits
+//! * [`convert`] converts peak files between BED, narrowPeak, SAF, JSON and bigBed
+//! * [`coverage`] reports the fraction of the genome covered by raw and consensus peaks
+//! * [`density`] correlates the number of peaks per genomic window between samples
+//! * [`diff`] reports the peaks gained, lost and shifted between two versions of a peak set
+//! * [`duplicates`] detects and merges near-duplicate consensus peaks
+//! * [`filter`] filters existing peak files by regions, chromosome, length and score
+//! * [`genes`] annotates peaks with the nearest gene of a GTF gene annotation
+//! * [`idr`] scores the reproducibility of the peaks of two replicates with the irreproducible discovery rate
+//! * [`liftover`] maps peaks between genome assemblies using UCSC chain files
+//! * [`manifest`] records the checksums of the input and output files of a run
+//! * [`outliers`] flags consensus peaks with an extreme length, support or summit spread
+//! * [`metadata`] writes nf-core module metadata
+//! * [`json`] converts peak maps to and from JSON
+//! * [`provenance`] records the samples and raw peaks each consensus peak was created from
+//! * [`retry`] retries file operations failing with transient I/O errors
+//! * [`samples`] parses sample sheets mapping peak files to sample names, conditions and replicates
+//! * [`saturation`] analyses the growth of consensus peaks with the number of samples
+//! * [`scores`] aggregates the narrowPeak score, signal value, p-value and q-value of raw peaks per consensus peak
+//! * [`server`] answers overlap and peak queries over HTTP
+//! * [`sort`] coordinate-sorts peak files and normalises their chromosome names
+//! * [`stats`] summarises peak files per chromosome
+//! * [`strand`] handles the strand of stranded peaks
+//! * [`validation`] checks peak files for compliance with the BED and narrowPeak specifications
+//! * [`windows`] exports fixed-size windows around peak summits for motif analysis
+//! * `arrow_interop` converts peak sets to and from Arrow record batches (requires the `arrow` feature)
+//! * `bio_interop` converts peaks to bio-types intervals and rust-bio interval trees (requires the `bio-types` / `bio` feature)
+//! * `frip` calculates the fraction of reads in peaks from BAM files (requires the `bam` feature)
+//! * `polars_interop` converts peak sets to and from polars DataFrames (requires the `polars` feature)
+//! * `spill` spills raw peaks to compressed temporary files (requires the `spill` feature)
+//! * `signal` annotates peaks with the signal of BigWig coverage tracks (requires the `bigwig` feature)
+//! * `wasm` provides JavaScript bindings for WebAssembly builds (requires the `wasm` feature)
+//! * [`error`] defines the [`error::ApplicationError`] returned by all fallible operations
+//!
+//! # Example
+//!
+//! ```
+//! use gipfelkreuzer::peaks::{ConsensusParameters, ConsensusPeakAlgorithm, PeakData};
+//!
+//! let raw_peaks = vec![
+//!     PeakData::new(0, 100u64, 200u64, 150u64).unwrap(),
+//!     PeakData::new(1, 110u64, 190u64, 148u64).unwrap(),
+//!     PeakData::new(2, 5000u64, 5100u64, 5050u64).unwrap(),
+//! ];
+//! let consensus_peaks = ConsensusPeakAlgorithm::Gipfelkreuzer
+//!     .consensus_peaks(raw_peaks, &ConsensusParameters::default())
+//!     .unwrap();
+//! assert_eq!(consensus_peaks.len(), 2);
+//! ```
+//!
+//! BED data can be read, merged into consensus peaks and written again without any
+//! file system access, for example to embed consensus peak generation in another pipeline:
+//!
+//! ```
+//! use gipfelkreuzer::{
+//!     consensus::ConsensusBuilder, input::bed_buffers_to_peaks, output::peaks_to_bed_buffer,
+//! };
+//!
+//! let sample_a = "chr1\t100\t200\tpeak_a\t0\t.\t0\t0\t0\t50\n";
+//! let sample_b = "chr1\t110\t190\tpeak_b\t0\t.\t0\t0\t0\t38\n";
+//! let raw_peaks = bed_buffers_to_peaks(&[sample_a, sample_b]).unwrap();
+//! let consensus_peaks = ConsensusBuilder::new()
+//!     .min_peaks_per_consensus(2)
+//!     .run(raw_peaks)
+//!     .unwrap();
+//! let bed = String::from_utf8(peaks_to_bed_buffer(&consensus_peaks, 4)).unwrap();
+//! assert_eq!(bed.lines().count(), 1);
+//! assert!(bed.starts_with("chr1\t"));
+//! ```
+
+#![warn(missing_docs)]
+
+pub mod annotation;
+#[cfg(feature = "arrow")]
+pub mod arrow_interop;
+pub mod assembly;
+pub mod background;
+#[cfg(feature = "bio-types")]
+pub mod bio_interop;
+pub mod blacklist;
+pub mod chromosome;
+pub mod compare;
+pub mod concordance;
+pub mod consensus;
+pub mod convert;
+pub mod coverage;
+pub mod density;
+pub mod diff;
+pub mod duplicates;
+pub mod error;
+pub mod filter;
+#[cfg(feature = "bam")]
+pub mod frip;
+pub mod genes;
+pub mod idr;
+pub mod input;
+pub mod interval_tree;
+pub mod json;
+pub mod liftover;
+pub mod manifest;
+pub mod metadata;
+pub mod outliers;
+pub mod output;
+pub mod peak_set;
+pub mod peaks;
+pub mod pipeline;
+#[cfg(feature = "polars")]
+pub mod polars_interop;
+pub mod provenance;
+pub mod retry;
+pub mod samples;
+pub mod saturation;
+pub mod scores;
+pub mod server;
+#[cfg(feature = "bigwig")]
+pub mod signal;
+pub mod sort;
+#[cfg(feature = "spill")]
+pub mod spill;
+pub mod stats;
+pub mod strand;
+pub mod validation;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod windows;
+
+#[cfg(test)]
+mod test_utils;
@@ -0,0 +1,449 @@
+//! This module maps peaks between genome assemblies using
+//! [UCSC chain files](https://genome.ucsc.edu/goldenPath/help/chain.html), for example from
+//! hg19 to hg38, so samples aligned to different assemblies can be combined.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use crate::{
+    chromosome::Chromosome,
+    error::{ApplicationError, ApplicationErrorType, ErrorContext},
+    interval_tree::{Interval, IntervalTree},
+    peaks::PeakData,
+    retry::{Retrying, retry},
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "liftover";
+
+/// The default minimum fraction of the bases of a peak that must be mapped by a single chain.
+pub const DEFAULT_MIN_MATCH: f64 = 0.95;
+
+/// The handling of peaks whose bases are mapped by more than one chain,
+/// for example to different chromosomes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum SplitHandling {
+    /// Split peaks are reported as unmapped.
+    Discard,
+    /// Split peaks are mapped by the chain mapping the most bases.
+    Largest,
+}
+
+/// The reason a peak could not be mapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnmappedReason {
+    /// Too few bases of the peak are mapped by a single chain.
+    Deleted,
+    /// The bases of the peak are mapped by more than one chain.
+    Split,
+}
+
+impl std::fmt::Display for UnmappedReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnmappedReason::Deleted => write!(f, "deleted"),
+            UnmappedReason::Split => write!(f, "split"),
+        }
+    }
+}
+
+/// The target chromosome and orientation of a chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ChainTarget {
+    /// The chromosome of the target assembly.
+    chromosome: Chromosome,
+    /// The size of the target chromosome.
+    size: u64,
+    /// If the chain maps to the reverse strand of the target chromosome.
+    reverse: bool,
+}
+
+/// An ungapped block of a chain aligning source to target positions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AlignedBlock {
+    /// The first source position of the block.
+    start: u64,
+    /// The last source position of the block (inclusive).
+    end: u64,
+    /// The index of the chain the block belongs to.
+    chain: usize,
+    /// The target position aligned to the first source position in chain coordinates,
+    /// which are relative to the reverse strand for reverse chains.
+    target_start: u64,
+}
+
+impl Interval for AlignedBlock {
+    fn start(&self) -> u64 {
+        self.start
+    }
+
+    fn end(&self) -> u64 {
+        self.end
+    }
+}
+
+/// The alignment of a source to a target assembly as defined by a chain file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainMap {
+    /// The targets of all chains.
+    chains: Vec<ChainTarget>,
+    /// The aligned blocks per source chromosome.
+    blocks: HashMap<Chromosome, IntervalTree<AlignedBlock>>,
+}
+
+impl ChainMap {
+    /// Parses an uncompressed UCSC chain file.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - the path of the chain file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is invalid.
+    pub fn from_chain_file<T: AsRef<Path>>(path: T) -> Result<Self, ApplicationError> {
+        let path = path.as_ref();
+        log::info!(target: LOG_TARGET, "Parsing the chain file {}.", path.display());
+        let description = format!("Reading {}", path.display());
+        let read_error = |err: std::io::Error| {
+            ApplicationError::from(err)
+                .chain(format!("The chain file \"{}\" could not be read.", path.display()))
+        };
+        let file = retry(&description, || File::open(path)).map_err(read_error)?;
+        let reader = BufReader::new(Retrying::new(file, &description));
+        let mut chains: Vec<ChainTarget> = Vec::new();
+        let mut blocks: HashMap<Chromosome, Vec<AlignedBlock>> = HashMap::new();
+        // The source chromosome and the current source and target positions of the open chain.
+        let mut current: Option<(Chromosome, u64, u64)> = None;
+        for (line_index, line) in reader.lines().enumerate() {
+            let line = line.map_err(read_error)?;
+            let line_context = ErrorContext::new()
+                .with_path(path.display())
+                .with_line(line_index as u64 + 1);
+            let invalid_line = |message: String| {
+                ApplicationError::new(ApplicationErrorType::ParseError, message)
+                    .with_context(line_context.clone())
+                    .with_hint(
+                        "Chain files consist of \"chain\" header lines followed by alignment data \
+                        lines as described at https://genome.ucsc.edu/goldenPath/help/chain.html. \
+                        Compressed chain files must be decompressed first.",
+                    )
+            };
+            let number = |field: &str| {
+                field.parse::<u64>().map_err(|_| {
+                    invalid_line(format!("The value \"{}\" is not a valid number.", field))
+                })
+            };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.is_empty() || fields[0].starts_with('#') {
+                continue;
+            }
+            if fields[0] == "chain" {
+                if fields.len() < 12 {
+                    return Err(invalid_line(format!(
+                        "The chain header contains {} instead of at least 12 fields.",
+                        fields.len()
+                    )));
+                }
+                chains.push(ChainTarget {
+                    chromosome: Chromosome::from(fields[7]),
+                    size: number(fields[8])?,
+                    reverse: fields[9] == "-",
+                });
+                current =
+                    Some((Chromosome::from(fields[2]), number(fields[5])?, number(fields[10])?));
+                continue;
+            }
+            let Some((source_chromosome, source_position, target_position)) = current.as_mut()
+            else {
+                return Err(invalid_line(
+                    "The alignment data line is not preceded by a chain header.".to_string(),
+                ));
+            };
+            let size = number(fields[0])?;
+            if size > 0 {
+                blocks
+                    .entry(*source_chromosome)
+                    .or_default()
+                    .push(AlignedBlock {
+                        start: *source_position,
+                        end: *source_position + size - 1,
+                        chain: chains.len() - 1,
+                        target_start: *target_position,
+                    });
+            }
+            *source_position += size;
+            *target_position += size;
+            match fields.len() {
+                1 => current = None,
+                3 => {
+                    *source_position += number(fields[1])?;
+                    *target_position += number(fields[2])?;
+                },
+                _ => {
+                    return Err(invalid_line(format!(
+                        "The alignment data line contains {} instead of 1 or 3 fields.",
+                        fields.len()
+                    )));
+                },
+            }
+        }
+        log::debug!(
+            target: LOG_TARGET,
+            "Parsed {} chains with {} aligned blocks.",
+            chains.len(),
+            blocks.values().map(Vec::len).sum::<usize>()
+        );
+        Ok(Self {
+            chains,
+            blocks: blocks
+                .into_iter()
+                .map(|(chromosome, chromosome_blocks)| {
+                    (chromosome, IntervalTree::new(chromosome_blocks))
+                })
+                .collect(),
+        })
+    }
+
+    /// Returns the target coordinate of a source position within an aligned block
+    /// on the forward strand of the target chromosome.
+    ///
+    /// # Parameters
+    ///
+    /// * `block` - the aligned block containing the position
+    /// * `position` - the source position
+    fn target_position(&self, block: &AlignedBlock, position: u64) -> u64 {
+        let chain = &self.chains[block.chain];
+        let position = block.target_start + position - block.start;
+        if chain.reverse {
+            chain.size - 1 - position
+        } else {
+            position
+        }
+    }
+
+    /// Maps a peak to the target assembly. The lifted peak spans the target positions of all
+    /// mapped bases of the chain mapping the most bases and keeps the identifier of the peak.
+    /// The summit is mapped if it is aligned by that chain and set to the centre of the lifted
+    /// peak otherwise.
+    ///
+    /// # Parameters
+    ///
+    /// * `chromosome` - the source chromosome of the peak
+    /// * `peak` - the peak
+    /// * `min_match` - the minimum fraction of the bases of the peak that must be mapped
+    ///   by a single chain
+    /// * `split_handling` - the handling of peaks mapped by more than one chain
+    ///
+    /// # Errors
+    ///
+    /// Returns the reason the peak could not be mapped.
+    pub fn lift(
+        &self,
+        chromosome: Chromosome,
+        peak: &PeakData,
+        min_match: f64,
+        split_handling: SplitHandling,
+    ) -> Result<(Chromosome, PeakData), UnmappedReason> {
+        let overlapping = self
+            .blocks
+            .get(&chromosome)
+            .map(|tree| tree.overlapping(peak.start(), peak.end()))
+            .unwrap_or_default();
+        // The number of mapped bases and the lifted region per chain.
+        let mut mapped: HashMap<usize, (u64, u64, u64)> = HashMap::new();
+        for block in &overlapping {
+            let start = block.start.max(peak.start());
+            let end = block.end.min(peak.end());
+            let (first, last) =
+                (self.target_position(block, start), self.target_position(block, end));
+            let region = mapped
+                .entry(block.chain)
+                .or_insert((0, first.min(last), first.max(last)));
+            region.0 += end - start + 1;
+            region.1 = region.1.min(first.min(last));
+            region.2 = region.2.max(first.max(last));
+        }
+        let Some((chain, (mapped_bases, start, end))) = mapped
+            .iter()
+            .max_by_key(|(chain, (mapped_bases, _, _))| (*mapped_bases, std::cmp::Reverse(**chain)))
+            .map(|(chain, region)| (*chain, *region))
+        else {
+            return Err(UnmappedReason::Deleted);
+        };
+        if mapped.len() > 1 && split_handling == SplitHandling::Discard {
+            return Err(UnmappedReason::Split);
+        }
+        if (mapped_bases as f64) < min_match * peak.length() as f64 {
+            return Err(UnmappedReason::Deleted);
+        }
+        let summit = overlapping
+            .iter()
+            .find(|block| {
+                block.chain == chain && block.start <= peak.summit() && peak.summit() <= block.end
+            })
+            .map(|block| self.target_position(block, peak.summit()))
+            .unwrap_or_else(|| start + (end - start) / 2);
+        let lifted = PeakData::new(peak.id(), start, end, summit)
+            .expect("The lifted peak must be valid as its summit is within the lifted region.");
+        Ok((self.chains[chain].chromosome, lifted))
+    }
+}
+
+/// The peaks mapped to the target assembly and the peaks that could not be mapped.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LiftoverResult {
+    /// The lifted peaks grouped by target chromosome.
+    pub lifted: HashMap<Chromosome, Vec<PeakData>>,
+    /// The peaks that could not be mapped grouped by source chromosome.
+    pub unmapped: HashMap<Chromosome, Vec<PeakData>>,
+    /// The number of unmapped peaks per reason.
+    pub unmapped_reasons: HashMap<String, usize>,
+}
+
+/// Maps all peaks to the target assembly.
+///
+/// # Parameters
+///
+/// * `chain_map` - the alignment of the source to the target assembly
+/// * `peaks` - the peaks grouped by source chromosome
+/// * `min_match` - the minimum fraction of the bases of a peak that must be mapped
+///   by a single chain
+/// * `split_handling` - the handling of peaks mapped by more than one chain
+///
+/// # Errors
+///
+/// Returns an error if the minimum match is not within `(0, 1]`.
+pub fn lift_peaks(
+    chain_map: &ChainMap,
+    peaks: &HashMap<Chromosome, Vec<PeakData>>,
+    min_match: f64,
+    split_handling: SplitHandling,
+) -> Result<LiftoverResult, ApplicationError> {
+    if min_match.is_nan() || min_match <= 0.0 || min_match > 1.0 {
+        return Err(ApplicationError::new(
+            ApplicationErrorType::ConfigError,
+            format!("The minimum match {} is not within (0, 1].", min_match),
+        ));
+    }
+    let mut result = LiftoverResult::default();
+    for (chromosome, chromosome_peaks) in peaks {
+        for peak in chromosome_peaks {
+            match chain_map.lift(*chromosome, peak, min_match, split_handling) {
+                Ok((target_chromosome, lifted)) => result
+                    .lifted
+                    .entry(target_chromosome)
+                    .or_default()
+                    .push(lifted),
+                Err(reason) => {
+                    result.unmapped.entry(*chromosome).or_default().push(*peak);
+                    *result
+                        .unmapped_reasons
+                        .entry(reason.to_string())
+                        .or_default() += 1;
+                },
+            }
+        }
+    }
+    let unmapped: usize = result.unmapped_reasons.values().sum();
+    if unmapped > 0 {
+        log::warn!(
+            target: LOG_TARGET,
+            "{} peaks could not be mapped to the target assembly: {:?}",
+            unmapped,
+            result.unmapped_reasons
+        );
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::{test_output, test_resources};
+
+    use super::*;
+
+    #[test]
+    fn test_chain_map_lift() {
+        let chain_map =
+            ChainMap::from_chain_file(test_resources().join("input_test_liftover.chain")).unwrap();
+        let chr1 = Chromosome::from("chr1");
+        let peak =
+            |start: u64, end: u64, summit: u64| PeakData::new(0, start, end, summit).unwrap();
+        // The blocks of the forward chain are shifted by 1000 and 1005 bases, respectively.
+        assert_eq!(
+            chain_map.lift(chr1, &peak(100, 199, 150), 0.95, SplitHandling::Discard),
+            Ok((Chromosome::from("chr1"), peak(1100, 1199, 1150)))
+        );
+        assert_eq!(
+            chain_map.lift(chr1, &peak(480, 529, 490), 0.8, SplitHandling::Discard),
+            Ok((Chromosome::from("chr1"), peak(1480, 1534, 1490)))
+        );
+        // The gap between the blocks is not mapped.
+        assert_eq!(
+            chain_map.lift(chr1, &peak(480, 529, 490), 0.95, SplitHandling::Discard),
+            Err(UnmappedReason::Deleted)
+        );
+        // The reverse chain maps to the end of chr5.
+        assert_eq!(
+            chain_map.lift(chr1, &peak(2000, 2099, 2010), 0.95, SplitHandling::Discard),
+            Ok((Chromosome::from("chr5"), peak(9850, 9949, 9939)))
+        );
+        // A peak spanning both chains.
+        assert_eq!(
+            chain_map.lift(chr1, &peak(1940, 2059, 2000), 0.1, SplitHandling::Discard),
+            Err(UnmappedReason::Split)
+        );
+        assert_eq!(
+            chain_map.lift(chr1, &peak(1940, 2059, 1945), 0.1, SplitHandling::Largest),
+            Ok((Chromosome::from("chr5"), peak(9890, 9999, 9944)))
+        );
+        assert_eq!(
+            chain_map.lift(
+                Chromosome::from("chr2"),
+                &peak(100, 199, 150),
+                0.95,
+                SplitHandling::Discard
+            ),
+            Err(UnmappedReason::Deleted)
+        );
+    }
+
+    #[test]
+    fn test_lift_peaks() {
+        let chain_map =
+            ChainMap::from_chain_file(test_resources().join("input_test_liftover.chain")).unwrap();
+        let chr1 = Chromosome::from("chr1");
+        let peaks = HashMap::from([(
+            chr1,
+            vec![
+                PeakData::new(0, 100u64, 199u64, 150u64).unwrap(),
+                PeakData::new(1, 5000u64, 5099u64, 5050u64).unwrap(),
+            ],
+        )]);
+        let result = lift_peaks(&chain_map, &peaks, 0.95, SplitHandling::Discard).unwrap();
+        assert_eq!(
+            result.lifted[&chr1],
+            vec![PeakData::new(0, 1100u64, 1199u64, 1150u64).unwrap()]
+        );
+        assert_eq!(
+            result.unmapped[&chr1],
+            vec![PeakData::new(1, 5000u64, 5099u64, 5050u64).unwrap()]
+        );
+        assert_eq!(result.unmapped_reasons["deleted"], 1);
+        assert!(lift_peaks(&chain_map, &peaks, 0.0, SplitHandling::Discard).is_err());
+
+        std::fs::create_dir_all(test_output()).unwrap();
+        let invalid_path = test_output().join("test_lift_peaks_invalid.chain");
+        std::fs::write(&invalid_path, "100 0 0\n").unwrap();
+        let error = ChainMap::from_chain_file(&invalid_path).unwrap_err();
+        assert_eq!(error.error_type(), ApplicationErrorType::ParseError);
+        assert_eq!(error.context().and_then(|context| context.line()), Some(1));
+        std::fs::remove_file(&invalid_path).unwrap();
+    }
+}
@@ -1,19 +1,134 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    fs::File,
+    io::{BufWriter, IsTerminal, Write},
+    panic::AssertUnwindSafe,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Mutex,
+    time::Duration,
+};
 
-use clap::Parser;
+use gipfelkreuzer::{
+    background::{DEFAULT_MAX_ATTEMPTS, background_regions},
+    chromosome::{Chromosome, register_aliases_from_file},
+    compare::{
+        OverlapCriteria, PeakSetComparison, threshold_sweep, write_comparison_table,
+        write_sweep_table,
+    },
+    convert::{PeakFormat, read_peaks, write_peaks},
+    coverage::chromosome_sizes_from_file,
+    diff::{peak_set_differences, write_difference_summary, write_difference_table},
+    error::{ApplicationError, ApplicationErrorType, ErrorLogLevels},
+    filter::{PeakFilter, filter_peak_file},
+    idr::{idr_peaks, write_idr_peaks},
+    input::{bed_to_peaks, bed_to_peaks_with_format},
+    liftover::{ChainMap, lift_peaks},
+    output::{peaks_to_bed_buffer, write_peaks_to_bed},
+    peak_set::PeakSet,
+    peaks::PeakData,
+    pipeline::{ConsensusPipeline, ConsensusRunOptions},
+    retry::RetryPolicy,
+    samples::SampleSheet,
+    saturation::{saturation_curve, write_saturation_table},
+    scores::ScoredPeaks,
+    server::PeakServer,
+    sort::{ChromosomeNormaliser, chromosome_aliases_from_file, sort_peak_file},
+    stats::{FileStatistics, write_statistics_table},
+    validation::{FileValidation, write_validation_report},
+};
 
 use crate::{
-    arguments::CommandLineArguments, error::ApplicationError, input::bed_to_peaks,
-    output::write_peaks_to_bed, peaks::PeakData,
+    arguments::{
+        BackgroundArguments, Command, CommandLineArguments, CompareArguments, ConvertArguments,
+        DiffArguments, FilterArguments, IdrArguments, LiftoverArguments, MergeArguments,
+        RegionOperationArguments, SaturationArguments, ServeArguments, SortArguments,
+        StatsArguments, SweepArguments, ValidateArguments,
+    },
+    diagnostics::DiagnosticReport,
+    warnings::{WarningCountingLogger, format_warning_counts, warning_counts},
+    watch::DirectoryWatcher,
 };
 
+#[cfg(feature = "mimalloc")]
+#[global_allocator]
+static GLOBAL_ALLOCATOR: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
+#[cfg(all(feature = "jemalloc", not(feature = "mimalloc")))]
+#[global_allocator]
+static GLOBAL_ALLOCATOR: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+/// The error created from the last panic by the panic hook.
+static PANIC_ERROR: Mutex<Option<ApplicationError>> = Mutex::new(None);
+
 /// Runs the application.
-fn main() -> Result<(), ApplicationError> {
-    // Logs any uncatched errors.
-    main_internal(CommandLineArguments::try_parse(), false).map_err(|err| {
-        err.log_default();
-        err
-    })
+fn main() -> ExitCode {
+    install_panic_hook();
+    let args: Vec<OsString> = std::env::args_os().collect();
+    let command_line_arguments_result =
+        CommandLineArguments::try_parse_with_config_from(args.clone());
+    let command_line_arguments = command_line_arguments_result.as_ref().ok().cloned();
+    // Converts panics into errors, so they are reported like any other error.
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        main_internal(command_line_arguments_result, false)
+    }))
+    .unwrap_or_else(|payload| {
+        Err(PANIC_ERROR
+            .lock()
+            .ok()
+            .and_then(|mut panic_error| panic_error.take())
+            .unwrap_or_else(|| ApplicationError::from_panic_payload(payload.as_ref(), None)))
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            // Renders uncatched errors for the user if running in a terminal
+            // and logs them otherwise.
+            // Invalid command line arguments are reported in the requested format as well.
+            let error_json = match &command_line_arguments {
+                Some(cl_args) => cl_args.error_json(),
+                None => CommandLineArguments::error_json_from(&args),
+            };
+            if error_json {
+                eprintln!("{}", err.to_json());
+            } else if std::io::stderr().is_terminal() {
+                let colour = std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty());
+                eprint!("{}", err.render(colour));
+            } else {
+                err.log_default();
+            }
+            let error_report = match &command_line_arguments {
+                Some(cl_args) => cl_args.error_report().clone(),
+                None => CommandLineArguments::error_report_from(&args),
+            };
+            if let Some(error_report) = error_report
+                && let Err(report_err) =
+                    DiagnosticReport::new(&err, command_line_arguments.as_ref()).write(error_report)
+            {
+                report_err.log_default();
+            }
+            ExitCode::from(err.error_type().exit_code())
+        },
+    }
+}
+
+/// Replaces the default panic hook printing a raw message and backtrace with a hook
+/// storing the panic as [`ApplicationError`] for reporting.
+/// The backtrace is captured at the panic location if enabled via `RUST_BACKTRACE`.
+fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let error = ApplicationError::from_panic_payload(
+            info.payload(),
+            info.location().map(|location| location.to_string()),
+        );
+        if let Some(backtrace) = error.backtrace() {
+            log::debug!("Panic backtrace:\n{}", backtrace);
+        }
+        if let Ok(mut panic_error) = PANIC_ERROR.lock() {
+            *panic_error = Some(error);
+        }
+    }));
 }
 
 /// An internal helper function to allow easier testing and error logging.
@@ -22,19 +137,27 @@ fn main() -> Result<(), ApplicationError> {
 ///
 /// * `command_line_arguments_result` - the results of parsing the command line arguments. This parameter mainly exists to allow testing.
 /// * `disable_logging` - disables starting of the logger. This parameter mainly exists to allow testing.
-fn main_internal(
-    command_line_arguments_result: Result<CommandLineArguments, clap::Error>,
+fn main_internal<E: Into<ApplicationError>>(
+    command_line_arguments_result: Result<CommandLineArguments, E>,
     disable_logging: bool,
 ) -> Result<(), ApplicationError> {
     // Tries to parse the command line arguments.
-    let cl_args_result = match command_line_arguments_result {
+    let cl_args_result = match command_line_arguments_result.map_err(Into::into) {
         Ok(cl_args) => Ok(cl_args),
         Err(err) => {
-            match err.kind() {
+            match std::error::Error::source(&err)
+                .and_then(|source| source.downcast_ref::<clap::Error>())
+            {
                 // Returns successful after the help message has been printed
                 // or an error if the printing failed.
-                clap::error::ErrorKind::DisplayHelp | clap::error::ErrorKind::DisplayVersion => {
-                    if let Err(err) = err.print() {
+                Some(clap_err)
+                    if matches!(
+                        clap_err.kind(),
+                        clap::error::ErrorKind::DisplayHelp
+                            | clap::error::ErrorKind::DisplayVersion
+                    ) =>
+                {
+                    if let Err(err) = clap_err.print() {
                         Err(ApplicationError::from(err)
                             .chain("The command line arguments could not be parsed."))
                     } else {
@@ -42,8 +165,7 @@ fn main_internal(
                     }
                 },
                 // On an actual error, returns the error.
-                _ => Err(ApplicationError::from(err)
-                    .chain("The command line arguments could not be parsed.")),
+                _ => Err(err.chain("The command line arguments could not be parsed.")),
             }
         },
     };
@@ -55,55 +177,748 @@ fn main_internal(
 
     // Initialises the logger.
     if !disable_logging {
-        env_logger::builder()
-            .filter_level(log_level)
-            .try_init()
-            .map_err(|err| {
-                ApplicationError::from(err).chain("The logger could not be initialised.")
-            })?;
+        // Includes the log target of the subsystem, so messages can be attributed to
+        // a processing stage and filtered via the "RUST_LOG" environment variable.
+        // Counts warnings independent of the log level, so they can be summarised.
+        WarningCountingLogger::new(
+            env_logger::builder()
+                .filter_level(log_level)
+                .parse_default_env()
+                .format_target(true)
+                .build(),
+        )
+        .init()
+        .map_err(|err| ApplicationError::from(err).chain("The logger could not be initialised."))?;
     }
 
     let command_line_arguments = cl_args_result?;
+    RetryPolicy::new(
+        command_line_arguments.io_retries(),
+        Duration::from_millis(command_line_arguments.io_retry_delay()),
+    )
+    .set_global();
     log::debug!("Running with arguments: {:?}", command_line_arguments);
-    let peaks_by_chromosome =
-        bed_to_peaks(command_line_arguments.input_files()).map_err(|err| {
+    // Aliases must be registered before any chromosome name is parsed.
+    if command_line_arguments.ucsc_chromosome_names() {
+        Chromosome::enable_ucsc_aliases();
+    }
+    if let Some(chrom_alias) = command_line_arguments.chrom_alias() {
+        register_aliases_from_file(chrom_alias)?;
+    }
+    match command_line_arguments.command() {
+        Some(Command::Merge(merge_arguments)) => merge(merge_arguments),
+        Some(Command::Validate(validate_arguments)) => validate(validate_arguments),
+        Some(Command::Serve(serve_arguments)) => serve(serve_arguments),
+        Some(Command::Stats(stats_arguments)) => stats(stats_arguments),
+        Some(Command::Compare(compare_arguments)) => compare(compare_arguments),
+        Some(Command::Saturation(saturation_arguments)) => saturation(saturation_arguments),
+        Some(Command::Sweep(sweep_arguments)) => sweep(sweep_arguments),
+        Some(Command::Liftover(liftover_arguments)) => liftover(liftover_arguments),
+        Some(Command::Convert(convert_arguments)) => convert(convert_arguments),
+        Some(Command::Filter(filter_arguments)) => filter(filter_arguments),
+        Some(Command::Sort(sort_arguments)) => sort(sort_arguments),
+        Some(Command::Intersect(region_arguments)) => region_operation(region_arguments, true),
+        Some(Command::Subtract(region_arguments)) => region_operation(region_arguments, false),
+        Some(Command::Background(background_arguments)) => background(background_arguments),
+        Some(Command::Diff(diff_arguments)) => diff(diff_arguments),
+        Some(Command::Idr(idr_arguments)) => idr(idr_arguments),
+        Some(Command::Consensus(_)) | None => consensus(&command_line_arguments),
+    }
+}
+
+/// Generates consensus peaks from the input files, either once or whenever the watched
+/// directory changes.
+///
+/// # Parameters
+///
+/// * `command_line_arguments` - the arguments to customise consensus peak generation and output
+fn consensus(command_line_arguments: &CommandLineArguments) -> Result<(), ApplicationError> {
+    let consensus_arguments = command_line_arguments.consensus_arguments();
+    // Resolves the output file once, as the default name depends on the current time.
+    let output_file = consensus_arguments.output_file();
+    let sample_sheet = match consensus_arguments.sample_sheet() {
+        Some(sample_sheet_path) => SampleSheet::from_file(sample_sheet_path)
+            .map_err(|err| err.chain("Failed to parse the sample sheet."))?,
+        None => SampleSheet::default(),
+    };
+    let mut input_files = consensus_arguments.input_files().clone();
+    input_files.extend(sample_sheet.paths());
+    let pipeline = consensus_arguments.consensus_pipeline()?;
+    let options = consensus_arguments.consensus_run_options(command_line_arguments.parameters())?;
+    if let Some(watch_directory) = consensus_arguments.watch() {
+        return watch(
+            &pipeline,
+            &options,
+            &input_files,
+            &sample_sheet,
+            watch_directory,
+            &output_file,
+            Duration::from_secs(consensus_arguments.watch_interval()),
+        );
+    }
+    pipeline.run_and_write(&input_files, &sample_sheet, output_file, &options)?;
+    let warning_counts = warning_counts();
+    log::info!("Run summary: {}.", format_warning_counts(&warning_counts));
+    if consensus_arguments.strict_warnings() && !warning_counts.is_empty() {
+        return Err(ApplicationError::new(
+            ApplicationErrorType::ValidationError,
+            format!(
+                "The run produced {}, which are treated as failure.",
+                format_warning_counts(&warning_counts)
+            ),
+        )
+        .with_hint(
+            "Inspect the logged warnings or run without --strict-warnings to accept them.",
+        ));
+    }
+    log::info!("Finished successfully.");
+    Ok(())
+}
+
+/// Regenerates the consensus peaks whenever the peak files in the watched directory change
+/// until the process is stopped.
+/// Errors during consensus peak generation are logged, so a single invalid sample
+/// does not stop watching. Invalid input data is logged as warning.
+///
+/// # Parameters
+///
+/// * `pipeline` - the pipeline generating the consensus peaks
+/// * `options` - the checks and additional output of each run
+/// * `input_files` - the peak files to generate consensus peaks from in addition to the watched ones
+/// * `sample_sheet` - the sample sheet naming the input files
+/// * `watch_directory` - the directory to watch for peak files
+/// * `output_file` - the consensus peak output file
+/// * `watch_interval` - the interval to check the watched directory for changes
+fn watch(
+    pipeline: &ConsensusPipeline,
+    options: &ConsensusRunOptions,
+    input_files: &[PathBuf],
+    sample_sheet: &SampleSheet,
+    watch_directory: &Path,
+    output_file: &Path,
+    watch_interval: Duration,
+) -> Result<(), ApplicationError> {
+    log::info!("Watching {} for peak files.", watch_directory.display());
+    let mut watcher = DirectoryWatcher::new(watch_directory, vec![output_file.to_path_buf()]);
+    loop {
+        if let Some(watched_files) = watcher.poll()? {
+            let mut input_files = input_files.to_vec();
+            input_files.extend(watched_files);
+            log::info!("Regenerating consensus peaks from {} input files.", input_files.len());
+            if let Err(err) = pipeline.run_and_write(
+                &input_files,
+                sample_sheet,
+                output_file.to_path_buf(),
+                options,
+            ) {
+                // Invalid input files are expected while samples are added, so they are only
+                // logged as warnings.
+                err.log_with_levels(&ErrorLogLevels::lenient());
+            }
+        }
+        std::thread::sleep(watch_interval);
+    }
+}
+
+/// Loads the peaks to serve and answers HTTP queries until the process is stopped.
+///
+/// # Parameters
+///
+/// * `serve_arguments` - the arguments of the serve command
+fn serve(serve_arguments: &ServeArguments) -> Result<(), ApplicationError> {
+    let peaks = if serve_arguments.precomputed() {
+        bed_to_peaks_with_format(
+            serve_arguments.input_files(),
+            serve_arguments.input_format_arguments().input_format(),
+            None,
+            false,
+            false,
+        )
+        .map_err(|err| {
             err.chain(format!(
                 "Failed to parse input files \"{:?}\".",
-                command_line_arguments.input_files()
+                serve_arguments.input_files()
+            ))
+        })?
+        .peaks
+    } else {
+        ConsensusPipeline::new(serve_arguments.algorithm_arguments().consensus_builder())
+            .input_format(serve_arguments.input_format_arguments().input_format())
+            .run_files(serve_arguments.input_files())?
+            .consensus
+    };
+    PeakServer::new(PeakSet::from(peaks))
+        .serve(serve_arguments.address())
+        .map_err(|err| err.chain(format!("Failed to serve on \"{}\".", serve_arguments.address())))
+}
+
+/// Summarises the input files and writes the statistics table.
+///
+/// # Parameters
+///
+/// * `stats_arguments` - the arguments of the stats command
+fn stats(stats_arguments: &StatsArguments) -> Result<(), ApplicationError> {
+    let statistics = stats_arguments
+        .input_files()
+        .iter()
+        .map(FileStatistics::from_bed_file)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| err.chain("Failed to summarise the input files."))?;
+    match stats_arguments.output_file() {
+        Some(output_file) => {
+            let file = File::create(output_file).map_err(|err| {
+                ApplicationError::from(err).chain(format!(
+                    "The statistics file \"{}\" could not be created.",
+                    output_file.display()
+                ))
+            })?;
+            write_statistics_table(BufWriter::new(file), &statistics)
+        },
+        None => write_statistics_table(std::io::stdout().lock(), &statistics),
+    }
+}
+
+/// Merges all overlapping and adjacent peaks of the input files and writes the merged peaks.
+///
+/// # Parameters
+///
+/// * `merge_arguments` - the arguments of the merge command
+fn merge(merge_arguments: &MergeArguments) -> Result<(), ApplicationError> {
+    let peaks = bed_to_peaks(merge_arguments.input_files())
+        .map(PeakSet::from)
+        .map_err(|err| err.chain("Failed to parse the input files."))?;
+    let merged: HashMap<Chromosome, Vec<PeakData>> = peaks.merge().into();
+    match merge_arguments.output_file() {
+        Some(output_file) => write_peaks_to_bed(output_file, &merged, 10),
+        None => std::io::stdout()
+            .lock()
+            .write_all(&peaks_to_bed_buffer(&merged, 10))
+            .map_err(|err| {
+                ApplicationError::from(err).chain("The merged peaks could not be written.")
+            }),
+    }
+}
+
+/// Validates the input files and writes the validation report.
+///
+/// # Parameters
+///
+/// * `validate_arguments` - the arguments of the validate command
+///
+/// # Errors
+///
+/// Returns an error if the report cannot be written or any input file is invalid.
+fn validate(validate_arguments: &ValidateArguments) -> Result<(), ApplicationError> {
+    let validations: Vec<FileValidation> = validate_arguments
+        .input_files()
+        .iter()
+        .map(|path| {
+            FileValidation::from_bed_file(
+                path,
+                validate_arguments.input_format_arguments().input_format(),
+                validate_arguments.max_errors() as usize,
+            )
+        })
+        .collect();
+    match validate_arguments.output_file() {
+        Some(output_file) => {
+            let file = File::create(output_file).map_err(|err| {
+                ApplicationError::from(err).chain(format!(
+                    "The validation report \"{}\" could not be created.",
+                    output_file.display()
+                ))
+            })?;
+            write_validation_report(BufWriter::new(file), &validations, validate_arguments.strict())
+        },
+        None => write_validation_report(
+            std::io::stdout().lock(),
+            &validations,
+            validate_arguments.strict(),
+        ),
+    }?;
+    let invalid = validations
+        .iter()
+        .filter(|validation| !validation.is_valid(validate_arguments.strict()))
+        .count();
+    if invalid > 0 {
+        return Err(ApplicationError::new(
+            ApplicationErrorType::ValidationError,
+            format!("{} of {} input files are invalid.", invalid, validations.len()),
+        )
+        .with_hint("The problems of each file are listed in the validation report."));
+    }
+    Ok(())
+}
+
+/// Compares the query peaks against the reference peaks and writes the comparison table
+/// and the unmatched peaks if requested.
+///
+/// # Parameters
+///
+/// * `compare_arguments` - the arguments of the compare command
+fn compare(compare_arguments: &CompareArguments) -> Result<(), ApplicationError> {
+    let criteria =
+        OverlapCriteria::new(compare_arguments.min_overlap(), compare_arguments.reciprocal())?;
+    let parse = |path: &PathBuf| {
+        bed_to_peaks(&[path])
+            .map(PeakSet::from)
+            .map_err(|err| err.chain(format!("Failed to parse input file \"{}\".", path.display())))
+    };
+    let comparison = PeakSetComparison::new(
+        &parse(compare_arguments.reference())?,
+        &parse(compare_arguments.query())?,
+        &criteria,
+    );
+    for (path, peaks) in [
+        (compare_arguments.reference_only(), comparison.reference_only()),
+        (compare_arguments.query_only(), comparison.query_only()),
+    ] {
+        if let Some(path) = path {
+            write_peaks_to_bed(path, &peaks.clone().into(), 3)?;
+        }
+    }
+    match compare_arguments.output_file() {
+        Some(output_file) => {
+            let file = File::create(output_file).map_err(|err| {
+                ApplicationError::from(err).chain(format!(
+                    "The comparison file \"{}\" could not be created.",
+                    output_file.display()
+                ))
+            })?;
+            write_comparison_table(BufWriter::new(file), &comparison)
+        },
+        None => write_comparison_table(std::io::stdout().lock(), &comparison),
+    }
+}
+
+/// Generates consensus peaks from random subsets of the input files and writes
+/// the saturation curve.
+///
+/// # Parameters
+///
+/// * `saturation_arguments` - the arguments of the saturation command
+fn saturation(saturation_arguments: &SaturationArguments) -> Result<(), ApplicationError> {
+    let samples = saturation_arguments
+        .input_files()
+        .iter()
+        .map(|input_file| {
+            bed_to_peaks_with_format(
+                &[input_file],
+                saturation_arguments.input_format_arguments().input_format(),
+                None,
+                false,
+                false,
+            )
+            .map(|parsed| parsed.peaks)
+            .map_err(|err| {
+                err.chain(format!("Failed to parse input file \"{}\".", input_file.display()))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let points = saturation_curve(
+        &saturation_arguments
+            .algorithm_arguments()
+            .consensus_builder(),
+        &samples,
+        saturation_arguments.repetitions() as usize,
+        saturation_arguments.seed(),
+    )
+    .map_err(|err| err.chain("Failed to calculate the saturation curve."))?;
+    match saturation_arguments.output_file() {
+        Some(output_file) => {
+            let file = File::create(output_file).map_err(|err| {
+                ApplicationError::from(err).chain(format!(
+                    "The saturation curve file \"{}\" could not be created.",
+                    output_file.display()
+                ))
+            })?;
+            write_saturation_table(BufWriter::new(file), &points)
+        },
+        None => write_saturation_table(std::io::stdout().lock(), &points),
+    }
+}
+
+/// Generates consensus peaks for a range of support thresholds and writes their accuracy
+/// against the gold-standard peaks.
+///
+/// # Parameters
+///
+/// * `sweep_arguments` - the arguments of the sweep command
+fn sweep(sweep_arguments: &SweepArguments) -> Result<(), ApplicationError> {
+    let criteria =
+        OverlapCriteria::new(sweep_arguments.min_overlap(), sweep_arguments.reciprocal())?;
+    let raw_peaks = bed_to_peaks(sweep_arguments.input_files()).map_err(|err| {
+        err.chain(format!("Failed to parse input files \"{:?}\".", sweep_arguments.input_files()))
+    })?;
+    let gold_standard = bed_to_peaks(&[sweep_arguments.gold_standard()])
+        .map(PeakSet::from)
+        .map_err(|err| {
+            err.chain(format!(
+                "Failed to parse the gold-standard file \"{}\".",
+                sweep_arguments.gold_standard().display()
             ))
         })?;
-    let mut consenus: HashMap<String, Vec<PeakData>> = HashMap::new();
-    for (chromosome, peaks) in peaks_by_chromosome {
-        log::info!("Processing {} raw peaks for chromosome {}.", peaks.len(), chromosome);
-        let consensus_peaks = command_line_arguments
-            .algorithm()
-            .consensus_peaks(peaks, &command_line_arguments)
-            .map_err(|err| err.chain("Failed to create consensus peaks."))?;
-        log::info!("Generated {} consensus peaks.", consensus_peaks.len());
-        consenus.insert(chromosome, consensus_peaks);
-    }
-    write_peaks_to_bed(
-        command_line_arguments.output_file(),
-        &consenus,
-        command_line_arguments.bed_output_columns(),
+    let max_min_peaks = sweep_arguments
+        .max_min_peaks()
+        .map_or(sweep_arguments.input_files().len(), |max_min_peaks| max_min_peaks as usize);
+    let points = threshold_sweep(
+        &sweep_arguments.algorithm_arguments().consensus_builder(),
+        &raw_peaks,
+        &gold_standard,
+        &criteria,
+        1..=max_min_peaks,
     )
-    .map_err(|err| {
+    .map_err(|err| err.chain("Failed to sweep the support thresholds."))?;
+    match sweep_arguments.output_file() {
+        Some(output_file) => {
+            let file = File::create(output_file).map_err(|err| {
+                ApplicationError::from(err).chain(format!(
+                    "The threshold sweep file \"{}\" could not be created.",
+                    output_file.display()
+                ))
+            })?;
+            write_sweep_table(BufWriter::new(file), &points)
+        },
+        None => write_sweep_table(std::io::stdout().lock(), &points),
+    }
+}
+
+/// Maps the peaks of the input file to the target assembly and writes the lifted peaks.
+///
+/// # Parameters
+///
+/// * `liftover_arguments` - the arguments of the liftover command
+fn liftover(liftover_arguments: &LiftoverArguments) -> Result<(), ApplicationError> {
+    let peaks = bed_to_peaks(&[liftover_arguments.input_file()]).map_err(|err| {
         err.chain(format!(
-            "Failed to write the consensus peaks to output file \"{}\".",
-            command_line_arguments.output_file().display(),
+            "Failed to parse the input file \"{}\".",
+            liftover_arguments.input_file().display()
         ))
     })?;
-    log::info!("Finished successfully.");
-    Ok(())
+    let chain_map = ChainMap::from_chain_file(liftover_arguments.chain())?;
+    let result =
+        lift_peaks(&chain_map, &peaks, liftover_arguments.min_match(), liftover_arguments.split())?;
+    if let Some(unmapped_path) = liftover_arguments.unmapped() {
+        write_peaks_to_bed(unmapped_path, &result.unmapped, 10)?;
+    }
+    match liftover_arguments.output_file() {
+        Some(output_file) => write_peaks_to_bed(output_file, &result.lifted, 10),
+        None => std::io::stdout()
+            .lock()
+            .write_all(&peaks_to_bed_buffer(&result.lifted, 10))
+            .map_err(|err| {
+                ApplicationError::from(err).chain("The lifted peaks could not be written.")
+            }),
+    }
+}
+
+/// Converts a peak file to another format.
+///
+/// # Parameters
+///
+/// * `convert_arguments` - the arguments of the convert command
+fn convert(convert_arguments: &ConvertArguments) -> Result<(), ApplicationError> {
+    let format = |path: &Path, format: Option<PeakFormat>| {
+        format
+            .or_else(|| PeakFormat::from_path(path))
+            .ok_or_else(|| {
+                ApplicationError::new(
+                    ApplicationErrorType::ConfigError,
+                    format!(
+                        "The format of the peak file \"{}\" could not be detected.",
+                        path.display()
+                    ),
+                )
+                .with_hint(
+                    "Use a known file extension (\".bed\", \".narrowPeak\", \".saf\", \".json\", \
+                \".bb\") or specify the format with \"--from\" or \"--to\".",
+                )
+            })
+    };
+    let input_format = format(convert_arguments.input_file(), convert_arguments.from())?;
+    let output_format = format(convert_arguments.output_file(), convert_arguments.to())?;
+    let chromosome_sizes = convert_arguments
+        .chromosome_sizes()
+        .as_ref()
+        .map(chromosome_sizes_from_file)
+        .transpose()?;
+    let peaks = read_peaks(convert_arguments.input_file(), input_format).map_err(|err| {
+        err.chain(format!(
+            "Failed to parse the input file \"{}\".",
+            convert_arguments.input_file().display()
+        ))
+    })?;
+    write_peaks(convert_arguments.output_file(), &peaks, output_format, chromosome_sizes.as_ref())
+}
+
+/// Filters the records of a peak file.
+///
+/// # Parameters
+///
+/// * `filter_arguments` - the arguments of the filter command
+fn filter(filter_arguments: &FilterArguments) -> Result<(), ApplicationError> {
+    let regions = |path: &PathBuf| {
+        bed_to_peaks(&[path]).map(PeakSet::from).map_err(|err| {
+            err.chain(format!("Failed to parse the regions \"{}\".", path.display()))
+        })
+    };
+    let mut peak_filter = PeakFilter::new();
+    if let Some(blacklist) = filter_arguments.blacklist() {
+        peak_filter = peak_filter.blacklist(regions(blacklist)?);
+    }
+    if let Some(whitelist) = filter_arguments.whitelist() {
+        peak_filter = peak_filter.whitelist(regions(whitelist)?);
+    }
+    if !filter_arguments.chromosomes().is_empty() {
+        peak_filter = peak_filter.chromosomes(
+            filter_arguments
+                .chromosomes()
+                .iter()
+                .map(|chromosome| Chromosome::from(chromosome.as_str())),
+        );
+    }
+    peak_filter = peak_filter.exclude_chromosomes(
+        filter_arguments
+            .exclude_chromosomes()
+            .iter()
+            .map(|chromosome| Chromosome::from(chromosome.as_str())),
+    );
+    if let Some(min_length) = filter_arguments.min_length() {
+        peak_filter = peak_filter.min_length(min_length);
+    }
+    if let Some(max_length) = filter_arguments.max_length() {
+        peak_filter = peak_filter.max_length(max_length);
+    }
+    if let Some(min_score) = filter_arguments.min_score() {
+        peak_filter = peak_filter.min_score(min_score);
+    }
+    let filtered =
+        filter_peak_file(filter_arguments.input_file(), &peak_filter).map_err(|err| {
+            err.chain(format!(
+                "Failed to filter the input file \"{}\".",
+                filter_arguments.input_file().display()
+            ))
+        })?;
+    for (reason, count) in filtered.removed() {
+        log::info!("Removed {} peaks by the {} filter.", count, reason);
+    }
+    match filter_arguments.output_file() {
+        Some(output_file) => std::fs::write(output_file, filtered.content()).map_err(|err| {
+            ApplicationError::from(err).chain(format!(
+                "The filtered peaks could not be written to \"{}\".",
+                output_file.display()
+            ))
+        }),
+        None => std::io::stdout()
+            .lock()
+            .write_all(filtered.content().as_bytes())
+            .map_err(|err| {
+                ApplicationError::from(err).chain("The filtered peaks could not be written.")
+            }),
+    }
+}
+
+/// Sorts the records of a peak file.
+///
+/// # Parameters
+///
+/// * `sort_arguments` - the arguments of the sort command
+fn sort(sort_arguments: &SortArguments) -> Result<(), ApplicationError> {
+    let mut normaliser = ChromosomeNormaliser::new();
+    if let Some(chromosome_sizes) = sort_arguments.chromosome_sizes() {
+        normaliser = normaliser.chromosome_sizes(&chromosome_sizes_from_file(chromosome_sizes)?);
+    }
+    if let Some(aliases) = sort_arguments.aliases() {
+        normaliser = normaliser.aliases(chromosome_aliases_from_file(aliases)?);
+    }
+    let sorted =
+        sort_peak_file(sort_arguments.input_file(), &normaliser, sort_arguments.deduplicate())
+            .map_err(|err| {
+                err.chain(format!(
+                    "Failed to sort the input file \"{}\".",
+                    sort_arguments.input_file().display()
+                ))
+            })?;
+    if sorted.duplicates() > 0 {
+        log::info!("Removed {} duplicate records.", sorted.duplicates());
+    }
+    match sort_arguments.output_file() {
+        Some(output_file) => std::fs::write(output_file, sorted.content()).map_err(|err| {
+            ApplicationError::from(err).chain(format!(
+                "The sorted peaks could not be written to \"{}\".",
+                output_file.display()
+            ))
+        }),
+        None => std::io::stdout()
+            .lock()
+            .write_all(sorted.content().as_bytes())
+            .map_err(|err| {
+                ApplicationError::from(err).chain("The sorted peaks could not be written.")
+            }),
+    }
+}
+
+/// Intersects the peaks of a peak file with or subtracts the peaks of another peak file.
+/// Only peaks of the input file overlapping the other file according to the overlap criteria
+/// are trimmed.
+///
+/// # Parameters
+///
+/// * `region_arguments` - the arguments of the intersect or subtract command
+/// * `intersect` - `true` for an intersection, `false` for a subtraction
+fn region_operation(
+    region_arguments: &RegionOperationArguments,
+    intersect: bool,
+) -> Result<(), ApplicationError> {
+    let parse = |path: &PathBuf| {
+        bed_to_peaks(&[path]).map(PeakSet::from).map_err(|err| {
+            err.chain(format!("Failed to parse the input file \"{}\".", path.display()))
+        })
+    };
+    let peaks = parse(region_arguments.input_file())?;
+    let other_peaks = parse(region_arguments.other_file())?;
+    let criteria =
+        OverlapCriteria::new(region_arguments.min_overlap(), region_arguments.reciprocal())?;
+    let overlapped = peaks.overlapped_by(&other_peaks, &criteria);
+    let result = match (intersect, region_arguments.whole()) {
+        (true, true) => overlapped,
+        (true, false) => overlapped.intersection(&other_peaks),
+        (false, true) => peaks.not_overlapped_by(&other_peaks, &criteria),
+        (false, false) => overlapped
+            .subtraction(&other_peaks)
+            .union(&peaks.not_overlapped_by(&other_peaks, &criteria)),
+    };
+    let result: HashMap<Chromosome, Vec<PeakData>> = result.into();
+    match region_arguments.output_file() {
+        Some(output_file) => write_peaks_to_bed(output_file, &result, 10),
+        None => std::io::stdout()
+            .lock()
+            .write_all(&peaks_to_bed_buffer(&result, 10))
+            .map_err(|err| {
+                ApplicationError::from(err).chain("The resulting peaks could not be written.")
+            }),
+    }
+}
+
+/// Compares two versions of a consensus peak set and writes the differences.
+///
+/// # Parameters
+///
+/// * `diff_arguments` - the arguments of the diff command
+fn diff(diff_arguments: &DiffArguments) -> Result<(), ApplicationError> {
+    let parse = |path: &PathBuf| {
+        bed_to_peaks(&[path])
+            .map(PeakSet::from)
+            .map_err(|err| err.chain(format!("Failed to parse input file \"{}\".", path.display())))
+    };
+    let differences = peak_set_differences(
+        &parse(diff_arguments.old())?,
+        &parse(diff_arguments.new())?,
+        diff_arguments.tolerance(),
+    );
+    if let Some(summary) = diff_arguments.summary() {
+        let file = File::create(summary).map_err(|err| {
+            ApplicationError::from(err).chain(format!(
+                "The difference summary file \"{}\" could not be created.",
+                summary.display()
+            ))
+        })?;
+        write_difference_summary(BufWriter::new(file), &differences)?;
+    }
+    match diff_arguments.output_file() {
+        Some(output_file) => {
+            let file = File::create(output_file).map_err(|err| {
+                ApplicationError::from(err).chain(format!(
+                    "The difference file \"{}\" could not be created.",
+                    output_file.display()
+                ))
+            })?;
+            write_difference_table(BufWriter::new(file), &differences)
+        },
+        None => write_difference_table(std::io::stdout().lock(), &differences),
+    }
+}
+
+/// Scores the reproducibility of the peaks of two replicates and writes the peaks passing
+/// the IDR threshold.
+///
+/// # Parameters
+///
+/// * `idr_arguments` - the arguments of the idr command
+fn idr(idr_arguments: &IdrArguments) -> Result<(), ApplicationError> {
+    let parse = |path: &PathBuf| {
+        ScoredPeaks::from_file(path, idr_arguments.input_format_arguments().input_format())
+            .map_err(|err| err.chain(format!("Failed to parse input file \"{}\".", path.display())))
+    };
+    let (_, peaks) = idr_peaks(
+        &parse(idr_arguments.first_replicate())?,
+        &parse(idr_arguments.second_replicate())?,
+        idr_arguments.rank(),
+    )
+    .map_err(|err| err.chain("Failed to estimate the irreproducible discovery rate."))?;
+    match idr_arguments.output_file() {
+        Some(output_file) => {
+            let file = File::create(output_file).map_err(|err| {
+                ApplicationError::from(err).chain(format!(
+                    "The IDR peak file \"{}\" could not be created.",
+                    output_file.display()
+                ))
+            })?;
+            write_idr_peaks(BufWriter::new(file), &peaks, idr_arguments.idr_threshold())
+        },
+        None => write_idr_peaks(std::io::stdout().lock(), &peaks, idr_arguments.idr_threshold()),
+    }
+}
+
+/// Samples background regions matched to the peaks of a peak file and writes them.
+///
+/// # Parameters
+///
+/// * `background_arguments` - the arguments of the background command
+fn background(background_arguments: &BackgroundArguments) -> Result<(), ApplicationError> {
+    let parse = |path: &PathBuf| {
+        bed_to_peaks(&[path]).map(PeakSet::from).map_err(|err| {
+            err.chain(format!("Failed to parse the input file \"{}\".", path.display()))
+        })
+    };
+    let peaks = parse(background_arguments.input_file())?;
+    let blacklist = match background_arguments.blacklist() {
+        Some(blacklist) => parse(blacklist)?,
+        None => PeakSet::new(),
+    };
+    let chromosome_sizes = chromosome_sizes_from_file(background_arguments.chromosome_sizes())?;
+    let regions = background_regions(
+        &peaks,
+        &chromosome_sizes,
+        &blacklist,
+        background_arguments.ratio() as usize,
+        background_arguments.seed(),
+        DEFAULT_MAX_ATTEMPTS,
+    )
+    .map_err(|err| err.chain("Failed to sample the background regions."))?;
+    let regions: HashMap<Chromosome, Vec<PeakData>> = regions.into();
+    match background_arguments.output_file() {
+        Some(output_file) => write_peaks_to_bed(output_file, &regions, 10),
+        None => std::io::stdout()
+            .lock()
+            .write_all(&peaks_to_bed_buffer(&regions, 10))
+            .map_err(|err| {
+                ApplicationError::from(err).chain("The background regions could not be written.")
+            }),
+    }
 }
 
 mod arguments;
-mod error;
-mod input;
-mod output;
-mod peaks;
+mod diagnostics;
+mod warnings;
+mod watch;
 
+// The shared test utilities refer to the library modules through the crate root
+// and contain helpers only used by the library tests.
+#[cfg(test)]
+use gipfelkreuzer::{chromosome, peak_set, peaks};
 #[cfg(test)]
+#[allow(dead_code)]
 mod test_utils;
 #[cfg(test)]
 mod tests {
@@ -113,22 +928,27 @@ mod tests {
         path::PathBuf,
     };
 
-    use crate::{
-        output::peak_to_bed_record_line,
-        test_utils::{test_output, test_resources},
+    use clap::Parser;
+    use gipfelkreuzer::{
+        manifest::RUN_ID_LENGTH,
+        output::{peak_to_bed_record_line, stable_peak_name},
+        peaks::ConsensusPeakAlgorithm,
+        scores::PeakScores,
     };
 
+    use crate::test_utils::{test_output, test_resources};
+
     use super::*;
 
     #[test]
     fn test_main_internal_default_with_summit_4_fields() {
         test_main_internal(
-            &vec![
+            &[
                 "input_test_main_internal_input_01.narrowPeak",
                 "input_test_main_internal_input_02.narrowPeak",
             ],
             "test_main_internal_default_with_summit_4_fields.bed",
-            &vec![
+            &[
                 "-a".to_string(),
                 "gipfelkreuzer".to_string(),
                 "-m".to_string(),
@@ -146,12 +966,12 @@ mod tests {
     #[test]
     fn test_main_internal_default_with_summit_14_fields() {
         test_main_internal(
-            &vec![
+            &[
                 "input_test_main_internal_input_01.narrowPeak",
                 "input_test_main_internal_input_02.narrowPeak",
             ],
             "test_main_internal_default_with_summit_14_fields.bed",
-            &vec![
+            &[
                 "-a".to_string(),
                 "gipfelkreuzer".to_string(),
                 "-m".to_string(),
@@ -169,12 +989,12 @@ mod tests {
     #[test]
     fn test_main_internal_default_with_summit_14_fields_min() {
         test_main_internal(
-            &vec![
+            &[
                 "input_test_main_internal_input_01.narrowPeak",
                 "input_test_main_internal_input_02.narrowPeak",
             ],
             "test_main_internal_default_with_summit_14_fields_min.bed",
-            &vec![
+            &[
                 "-a".to_string(),
                 "gipfelkreuzer".to_string(),
                 "-m".to_string(),
@@ -191,12 +1011,12 @@ mod tests {
     #[test]
     fn test_main_internal_default_with_summit_4_fields_simple() {
         test_main_internal(
-            &vec![
+            &[
                 "input_test_main_internal_input_01.narrowPeak",
                 "input_test_main_internal_input_02.narrowPeak",
             ],
             "test_main_internal_default_with_summit_4_fields_simple.bed",
-            &vec![
+            &[
                 "-a".to_string(),
                 "simple".to_string(),
                 "-b".to_string(),
@@ -209,12 +1029,12 @@ mod tests {
     #[test]
     fn test_main_internal_with_summit_14_fields_simple_min() {
         test_main_internal(
-            &vec![
+            &[
                 "input_test_main_internal_input_01_simple_min.narrowPeak",
                 "input_test_main_internal_input_02.narrowPeak",
             ],
             "test_main_internal_default_with_summit_14_fields_simple_min.bed",
-            &vec![
+            &[
                 "-a".to_string(),
                 "simple".to_string(),
                 "-n".to_string(),
@@ -229,12 +1049,12 @@ mod tests {
     #[test]
     fn test_main_internal_default_with_summit_14_fields_simple() {
         test_main_internal(
-            &vec![
+            &[
                 "input_test_main_internal_input_01.narrowPeak",
                 "input_test_main_internal_input_02.narrowPeak",
             ],
             "test_main_internal_default_with_summit_14_fields_simple.bed",
-            &vec![
+            &[
                 "-a".to_string(),
                 "simple".to_string(),
                 "-b".to_string(),
@@ -247,9 +1067,9 @@ mod tests {
     #[test]
     fn test_main_internal_default_with_summit_14_fields_iter_20() {
         test_main_internal(
-            &vec!["input_test_main_internal_input_iter_01.narrowPeak"],
+            &["input_test_main_internal_input_iter_01.narrowPeak"],
             "test_main_internal_default_with_summit_14_fields_iter_20.bed",
-            &vec![
+            &[
                 "-a".to_string(),
                 "gipfelkreuzer".to_string(),
                 "-m".to_string(),
@@ -264,9 +1084,9 @@ mod tests {
     #[test]
     fn test_main_internal_default_with_summit_14_fields_iter_0() {
         test_main_internal(
-            &vec!["input_test_main_internal_input_iter_01.narrowPeak"],
+            &["input_test_main_internal_input_iter_01.narrowPeak"],
             "test_main_internal_default_with_summit_14_fields_iter_0.bed",
-            &vec![
+            &[
                 "-a".to_string(),
                 "gipfelkreuzer".to_string(),
                 "-m".to_string(),
@@ -284,12 +1104,12 @@ mod tests {
     #[test]
     fn test_main_internal_with_summit_14_fields_harmoniser() {
         test_main_internal(
-            &vec![
+            &[
                 "input_test_main_internal_input_01.narrowPeak",
                 "input_test_main_internal_input_02.narrowPeak",
             ],
             "test_main_internal_with_summit_14_fields_harmoniser.bed",
-            &vec![
+            &[
                 "-a".to_string(),
                 "harmonised".to_string(),
                 "-b".to_string(),
@@ -302,12 +1122,12 @@ mod tests {
     #[test]
     fn test_main_internal_with_summit_14_fields_harmoniser_distance() {
         test_main_internal(
-            &vec![
+            &[
                 "input_test_main_internal_input_01.narrowPeak",
                 "input_test_main_internal_input_02.narrowPeak",
             ],
             "test_main_internal_with_summit_14_fields_harmoniser_distance.bed",
-            &vec![
+            &[
                 "-a".to_string(),
                 "harmonised".to_string(),
                 "-d".to_string(),
@@ -325,12 +1145,12 @@ mod tests {
     #[test]
     fn test_main_internal_with_summit_14_fields_harmoniser_min() {
         test_main_internal(
-            &vec![
+            &[
                 "input_test_main_internal_input_01.narrowPeak",
                 "input_test_main_internal_input_02.narrowPeak",
             ],
             "test_main_internal_with_summit_14_fields_harmoniser_min.bed",
-            &vec![
+            &[
                 "-a".to_string(),
                 "harmonised".to_string(),
                 "-d".to_string(),
@@ -345,15 +1165,2490 @@ mod tests {
     }
 
     #[test]
-    fn test_main_internal_help() {
-        let cla_short = CommandLineArguments::try_parse_from(vec!["Gipfelkreuzer", "-h"]);
-        assert!(main_internal(cla_short, true).is_ok());
+    fn test_main_internal_default_with_summit_14_fields_spill() {
+        let spill_directory = test_output().join("test_main_internal_spill");
+        test_main_internal(
+            &[
+                "input_test_main_internal_input_01.narrowPeak",
+                "input_test_main_internal_input_02.narrowPeak",
+            ],
+            "test_main_internal_default_with_summit_14_fields_spill.bed",
+            &[
+                "-a".to_string(),
+                "gipfelkreuzer".to_string(),
+                "-m".to_string(),
+                "20".to_string(),
+                "-b".to_string(),
+                "14".to_string(),
+                "--spill-directory".to_string(),
+                spill_directory.display().to_string(),
+                "--spill-compression-level".to_string(),
+                "19".to_string(),
+            ],
+            vec![
+                ("chr1".to_string(), PeakData::new(1, 629u64, 769u64, 698u64).unwrap()),
+                ("chr1".to_string(), PeakData::new(4, 864u64, 918u64, 904u64).unwrap()),
+            ],
+        );
+        // All spill files must have been cleaned up.
+        assert_eq!(std::fs::read_dir(&spill_directory).unwrap().count(), 0);
+        std::fs::remove_dir(spill_directory).unwrap();
     }
 
     #[test]
-    fn test_main_internal_version() {
-        let cla_short = CommandLineArguments::try_parse_from(vec!["Gipfelkreuzer", "-V"]);
-        assert!(main_internal(cla_short, true).is_ok());
+    fn test_main_internal_nf_core_metadata() {
+        let metadata_directory = test_output().join("test_main_internal_nf_core_metadata");
+        test_main_internal(
+            &[
+                "input_test_main_internal_input_01.narrowPeak",
+                "input_test_main_internal_input_02.narrowPeak",
+            ],
+            "test_main_internal_nf_core_metadata.bed",
+            &[
+                "--nf-core-metadata".to_string(),
+                metadata_directory.display().to_string(),
+                "--nf-core-process-name".to_string(),
+                "NFCORE_ATACSEQ:GIPFELKREUZER".to_string(),
+            ],
+            vec![
+                ("chr1".to_string(), PeakData::new(1, 629u64, 769u64, 698u64).unwrap()),
+                ("chr1".to_string(), PeakData::new(4, 864u64, 918u64, 904u64).unwrap()),
+            ],
+        );
+        let versions = std::fs::read_to_string(metadata_directory.join("versions.yml")).unwrap();
+        assert!(versions.starts_with("\"NFCORE_ATACSEQ:GIPFELKREUZER\":\n    gipfelkreuzer: "));
+        let trace: serde_json::Value = serde_json::from_slice(
+            &std::fs::read(metadata_directory.join("gipfelkreuzer_trace.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(trace["algorithm"], "gipfelkreuzer");
+        assert_eq!(trace["consensus_peaks"], 2);
+        assert_eq!(trace["input_files"].as_array().unwrap().len(), 2);
+        std::fs::remove_dir_all(metadata_directory).unwrap();
+    }
+
+    #[test]
+    fn test_main_internal_intersection_summary() {
+        let summary_path = test_output().join("test_main_internal_intersection_summary.tsv");
+        test_main_internal(
+            &[
+                "input_test_main_internal_input_01.narrowPeak",
+                "input_test_main_internal_input_02.narrowPeak",
+            ],
+            "test_main_internal_intersection_summary.bed",
+            &[
+                "--intersection-summary".to_string(),
+                summary_path.display().to_string(),
+            ],
+            vec![
+                ("chr1".to_string(), PeakData::new(1, 629u64, 769u64, 698u64).unwrap()),
+                ("chr1".to_string(), PeakData::new(4, 864u64, 918u64, 904u64).unwrap()),
+            ],
+        );
+        let summary = std::fs::read_to_string(&summary_path).unwrap();
+        std::fs::remove_file(&summary_path).unwrap();
+        let lines: Vec<&str> = summary.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "samples\tsample_count\tconsensus_peaks");
+        assert!(lines[1].ends_with("input_test_main_internal_input_01.narrowPeak\t1\t1"));
+        assert!(lines[2].contains("input_test_main_internal_input_01.narrowPeak&"));
+        assert!(lines[2].ends_with("input_test_main_internal_input_02.narrowPeak\t2\t1"));
+    }
+
+    #[test]
+    fn test_main_internal_annotate_with() {
+        let output_path = test_output().join("test_main_internal_annotate_with.bed");
+        let summary_path = test_output().join("test_main_internal_annotate_with_annotation.tsv");
+        let input_paths = [
+            "input_test_main_internal_input_01.narrowPeak",
+            "input_test_main_internal_input_02.narrowPeak",
+        ]
+        .map(|input| test_resources().join(input).display().to_string());
+        let cla = CommandLineArguments::try_parse_from(
+            [
+                "Gipfelkreuzer".to_string(),
+                "-b".to_string(),
+                "3".to_string(),
+                "--annotate-with".to_string(),
+                test_resources()
+                    .join("input_test_annotation.bed")
+                    .display()
+                    .to_string(),
+                "--annotation-column".to_string(),
+                "6".to_string(),
+                "-o".to_string(),
+                output_path.display().to_string(),
+            ]
+            .into_iter()
+            .chain(input_paths),
+        );
+        assert!(main_internal(cla, true).is_ok());
+        assert_eq!(
+            std::fs::read_to_string(&output_path).unwrap(),
+            "chr1\t629\t769\tdELS\nchr1\t864\t918\t.\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&summary_path).unwrap(),
+            "category\tpeaks\tfraction\ndELS\t1\t0.500000\n.\t1\t0.500000\n*\t2\t1.000000\n"
+        );
+        std::fs::remove_file(output_path).unwrap();
+        std::fs::remove_file(summary_path).unwrap();
+    }
+
+    #[test]
+    fn test_main_internal_gtf() {
+        let output_path = test_output().join("test_main_internal_gtf.bed");
+        let summary_path = test_output().join("test_main_internal_gtf_promoter.tsv");
+        let input_paths = [
+            "input_test_main_internal_input_01.narrowPeak",
+            "input_test_main_internal_input_02.narrowPeak",
+        ]
+        .map(|input| test_resources().join(input).display().to_string());
+        let cla = CommandLineArguments::try_parse_from(
+            [
+                "Gipfelkreuzer".to_string(),
+                "-b".to_string(),
+                "3".to_string(),
+                "--gtf".to_string(),
+                test_resources()
+                    .join("input_test_genes.gtf")
+                    .display()
+                    .to_string(),
+                "--promoter-window".to_string(),
+                "100".to_string(),
+                "-o".to_string(),
+                output_path.display().to_string(),
+            ]
+            .into_iter()
+            .chain(input_paths),
+        );
+        assert!(main_internal(cla, true).is_ok());
+        assert_eq!(
+            std::fs::read_to_string(&output_path).unwrap(),
+            "chr1\t629\t769\tGENE1\t+\t-301\tdistal\nchr1\t864\t918\tGENE1\t+\t-95\tpromoter\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&summary_path).unwrap(),
+            "class\tpeaks\tfraction\npromoter\t1\t0.500000\ndistal\t1\t0.500000\n*\t2\t1.000000\n"
+        );
+        std::fs::remove_file(output_path).unwrap();
+        std::fs::remove_file(summary_path).unwrap();
+    }
+
+    #[test]
+    fn test_main_internal_summit_windows() {
+        let windows_path = test_output().join("test_main_internal_summit_windows_windows.bed");
+        let sequences_path = test_output().join("test_main_internal_summit_windows.fa");
+        test_main_internal(
+            &[
+                "input_test_main_internal_input_01.narrowPeak",
+                "input_test_main_internal_input_02.narrowPeak",
+            ],
+            "test_main_internal_summit_windows.bed",
+            &[
+                "--summit-windows".to_string(),
+                windows_path.display().to_string(),
+                "--summit-sequences".to_string(),
+                sequences_path.display().to_string(),
+                "--summit-flank".to_string(),
+                "10".to_string(),
+                "--genome".to_string(),
+                test_resources()
+                    .join("input_test_genome.fa")
+                    .display()
+                    .to_string(),
+            ],
+            vec![
+                ("chr1".to_string(), PeakData::new(1, 629u64, 769u64, 698u64).unwrap()),
+                ("chr1".to_string(), PeakData::new(4, 864u64, 918u64, 904u64).unwrap()),
+            ],
+        );
+        let windows = std::fs::read_to_string(&windows_path).unwrap();
+        let sequences = std::fs::read_to_string(&sequences_path).unwrap();
+        std::fs::remove_file(&windows_path).unwrap();
+        std::fs::remove_file(&sequences_path).unwrap();
+        assert_eq!(windows, "chr1\t688\t708\tconsensus_1\nchr1\t894\t914\tconsensus_4\n");
+        // The test genome is shorter than the windows, so the sequences are empty.
+        assert_eq!(sequences, ">consensus_1::chr1:688-708\n>consensus_4::chr1:894-914\n");
+    }
+
+    #[test]
+    fn test_main_internal_genome_coverage() {
+        let coverage_path = test_output().join("test_main_internal_genome_coverage.tsv");
+        test_main_internal(
+            &[
+                "input_test_main_internal_input_01.narrowPeak",
+                "input_test_main_internal_input_02.narrowPeak",
+            ],
+            "test_main_internal_genome_coverage.bed",
+            &[
+                "--chromosome-sizes".to_string(),
+                test_resources()
+                    .join("input_test_chromosome_sizes.tsv")
+                    .display()
+                    .to_string(),
+                "--genome-coverage".to_string(),
+                coverage_path.display().to_string(),
+            ],
+            vec![
+                ("chr1".to_string(), PeakData::new(1, 629u64, 769u64, 698u64).unwrap()),
+                ("chr1".to_string(), PeakData::new(4, 864u64, 918u64, 904u64).unwrap()),
+            ],
+        );
+        let coverage = std::fs::read_to_string(&coverage_path).unwrap();
+        std::fs::remove_file(&coverage_path).unwrap();
+        let lines: Vec<&str> = coverage.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("chr1\t2000\t"));
+        assert!(lines[1].ends_with("\t196\t0.098000"));
+        assert_eq!(lines[2], "chr2\t1000\t0\t0.000000\t0\t0.000000");
+        assert!(lines[3].starts_with("*\t3000\t"));
+    }
+
+    #[test]
+    fn test_main_internal_outlier_report() {
+        let outlier_path = test_output().join("test_main_internal_outlier_report.tsv");
+        test_main_internal(
+            &[
+                "input_test_main_internal_input_01.narrowPeak",
+                "input_test_main_internal_input_02.narrowPeak",
+            ],
+            "test_main_internal_outlier_report.bed",
+            &[
+                "--outlier-report".to_string(),
+                outlier_path.display().to_string(),
+                "--outlier-length-threshold".to_string(),
+                "0.5".to_string(),
+            ],
+            vec![
+                ("chr1".to_string(), PeakData::new(1, 629u64, 769u64, 698u64).unwrap()),
+                ("chr1".to_string(), PeakData::new(4, 864u64, 918u64, 904u64).unwrap()),
+            ],
+        );
+        let report = std::fs::read_to_string(&outlier_path).unwrap();
+        std::fs::remove_file(&outlier_path).unwrap();
+        assert_eq!(
+            report,
+            "chromosome\tstart\tend\tsummit\tlength\tsupport\tsummit_sd\treasons\n\
+            chr1\t629\t769\t698\t141\t4\t14.872\tlong\n\
+            chr1\t864\t918\t904\t55\t3\t0.471\tshort\n"
+        );
+    }
+
+    #[test]
+    fn test_main_internal_duplicate_report() {
+        let report_path = test_output().join("test_main_internal_duplicate_report.tsv");
+        test_main_internal(
+            &[
+                "input_test_main_internal_input_01.narrowPeak",
+                "input_test_main_internal_input_02.narrowPeak",
+            ],
+            "test_main_internal_duplicate_report.bed",
+            &[
+                "--duplicate-report".to_string(),
+                report_path.display().to_string(),
+                "--merge-duplicates".to_string(),
+            ],
+            vec![
+                ("chr1".to_string(), PeakData::new(1, 629u64, 769u64, 698u64).unwrap()),
+                ("chr1".to_string(), PeakData::new(4, 864u64, 918u64, 904u64).unwrap()),
+            ],
+        );
+        let report = std::fs::read_to_string(&report_path).unwrap();
+        std::fs::remove_file(&report_path).unwrap();
+        assert_eq!(
+            report,
+            "chromosome\tfirst_start\tfirst_end\tsecond_start\tsecond_end\toverlap_fraction\n"
+        );
+    }
+
+    #[test]
+    fn test_main_internal_density_correlation() {
+        let correlation_path = test_output().join("test_main_internal_density_correlation.tsv");
+        test_main_internal(
+            &[
+                "input_test_main_internal_input_01.narrowPeak",
+                "input_test_main_internal_input_02.narrowPeak",
+            ],
+            "test_main_internal_density_correlation.bed",
+            &[
+                "--density-correlation".to_string(),
+                correlation_path.display().to_string(),
+                "--density-window".to_string(),
+                "100".to_string(),
+            ],
+            vec![
+                ("chr1".to_string(), PeakData::new(1, 629u64, 769u64, 698u64).unwrap()),
+                ("chr1".to_string(), PeakData::new(4, 864u64, 918u64, 904u64).unwrap()),
+            ],
+        );
+        let correlation = std::fs::read_to_string(&correlation_path).unwrap();
+        std::fs::remove_file(&correlation_path).unwrap();
+        let lines: Vec<&str> = correlation.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("chr1\t"));
+        assert!(lines[1].ends_with("input_test_main_internal_input_02.narrowPeak\t3\t-1.000000"));
+        assert!(lines[2].starts_with("*\t"));
+    }
+
+    #[test]
+    fn test_main_internal_density_track() {
+        let track_path = test_output().join("test_main_internal_density_track_windows.bed");
+        test_main_internal(
+            &[
+                "input_test_main_internal_input_01.narrowPeak",
+                "input_test_main_internal_input_02.narrowPeak",
+            ],
+            "test_main_internal_density_track.bed",
+            &[
+                "--density-track".to_string(),
+                track_path.display().to_string(),
+                "--density-window".to_string(),
+                "500".to_string(),
+            ],
+            vec![
+                ("chr1".to_string(), PeakData::new(1, 629u64, 769u64, 698u64).unwrap()),
+                ("chr1".to_string(), PeakData::new(4, 864u64, 918u64, 904u64).unwrap()),
+            ],
+        );
+        let track = std::fs::read_to_string(&track_path).unwrap();
+        std::fs::remove_file(&track_path).unwrap();
+        assert_eq!(track, "chr1\t0\t500\t0\nchr1\t500\t1000\t2\n");
+    }
+
+    #[test]
+    fn test_main_internal_manifest() {
+        let manifest_path = test_output().join("test_main_internal_manifest.json");
+        let multiqc_path = test_output().join("test_main_internal_manifest_mqc.json");
+        test_main_internal(
+            &[
+                "input_test_main_internal_input_01.narrowPeak",
+                "input_test_main_internal_input_02.narrowPeak",
+            ],
+            "test_main_internal_manifest.bed",
+            &[
+                "--manifest".to_string(),
+                manifest_path.display().to_string(),
+                "--multiqc".to_string(),
+                multiqc_path.display().to_string(),
+                "-n".to_string(),
+                "2".to_string(),
+            ],
+            vec![
+                ("chr1".to_string(), PeakData::new(1, 629u64, 769u64, 698u64).unwrap()),
+                ("chr1".to_string(), PeakData::new(4, 864u64, 918u64, 904u64).unwrap()),
+            ],
+        );
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+        std::fs::remove_file(&manifest_path).unwrap();
+        std::fs::remove_file(&multiqc_path).unwrap();
+        assert_eq!(manifest["parameters"]["min-peaks-per-consensus"], "2");
+        assert_eq!(manifest["inputs"].as_array().unwrap().len(), 2);
+        assert_eq!(manifest["inputs"][1]["size"], 222);
+        let outputs = manifest["outputs"].as_array().unwrap();
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(outputs[1]["path"], multiqc_path.display().to_string());
+        assert_eq!(outputs[0]["sha256"].as_str().unwrap().len(), 64);
+    }
+
+    #[test]
+    fn test_main_internal_verify_inputs() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let manifest_path = test_output().join("test_main_internal_verify_inputs.json");
+        let output_path = test_output().join("test_main_internal_verify_inputs.bed");
+        let input_01 = test_resources()
+            .join("input_test_main_internal_input_01.narrowPeak")
+            .display()
+            .to_string();
+        let input_02 = test_resources()
+            .join("input_test_main_internal_input_02.narrowPeak")
+            .display()
+            .to_string();
+        let arguments = |manifest_argument: &str, inputs: [&String; 2]| {
+            vec![
+                "Gipfelkreuzer".to_string(),
+                manifest_argument.to_string(),
+                manifest_path.display().to_string(),
+                "-o".to_string(),
+                output_path.display().to_string(),
+                inputs[0].clone(),
+                inputs[1].clone(),
+            ]
+        };
+        let cla =
+            CommandLineArguments::try_parse_from(arguments("--manifest", [&input_01, &input_02]));
+        main_internal(cla, true).unwrap();
+        let cla = CommandLineArguments::try_parse_from(arguments(
+            "--verify-inputs",
+            [&input_01, &input_02],
+        ));
+        main_internal(cla, true).unwrap();
+        let cla = CommandLineArguments::try_parse_from(arguments(
+            "--verify-inputs",
+            [&input_02, &input_01],
+        ));
+        let error = main_internal(cla, true).unwrap_err();
+        std::fs::remove_file(&manifest_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        assert_eq!(error.error_type(), ApplicationErrorType::ValidationError);
+        assert_eq!(error.related().len(), 2);
+    }
+
+    #[test]
+    fn test_main_internal_stable_ids() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_stable_ids.bed");
+        let input_01 = test_resources().join("input_test_main_internal_input_01.narrowPeak");
+        let input_02 = test_resources().join("input_test_main_internal_input_02.narrowPeak");
+        let run = |inputs: &[&PathBuf]| {
+            let mut arguments = vec![
+                "Gipfelkreuzer".to_string(),
+                "--stable-ids".to_string(),
+                "--min-peaks-per-consensus".to_string(),
+                "1".to_string(),
+                "-o".to_string(),
+                output_path.display().to_string(),
+            ];
+            arguments.extend(inputs.iter().map(|input| input.display().to_string()));
+            main_internal(CommandLineArguments::try_parse_from(arguments), true).unwrap();
+            let output = std::fs::read_to_string(&output_path).unwrap();
+            std::fs::remove_file(&output_path).unwrap();
+            output
+        };
+        let output = run(&[&input_01, &input_02]);
+        assert!(!output.is_empty());
+        for line in output.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let peak = PeakData::new(
+                0,
+                fields[1].parse::<u64>().unwrap(),
+                fields[2].parse::<u64>().unwrap(),
+                fields[1].parse::<u64>().unwrap(),
+            )
+            .unwrap();
+            assert_eq!(fields[3], stable_peak_name(&peak, fields[0]));
+        }
+        // The names do not depend on the order of the input files.
+        assert_eq!(run(&[&input_02, &input_01]), output);
+    }
+
+    #[test]
+    fn test_main_internal_provenance() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_provenance.bed");
+        let provenance_path = test_output().join("test_main_internal_provenance.tsv");
+        let input_01 = test_resources().join("input_test_main_internal_input_01.narrowPeak");
+        let input_02 = test_resources().join("input_test_main_internal_input_iter_01.narrowPeak");
+        main_internal(
+            CommandLineArguments::try_parse_from([
+                "Gipfelkreuzer".to_string(),
+                "--provenance-column".to_string(),
+                "--provenance-table".to_string(),
+                provenance_path.display().to_string(),
+                "--min-peaks-per-consensus".to_string(),
+                "1".to_string(),
+                "-o".to_string(),
+                output_path.display().to_string(),
+                input_01.display().to_string(),
+                input_02.display().to_string(),
+            ]),
+            true,
+        )
+        .unwrap();
+        let output = std::fs::read_to_string(&output_path).unwrap();
+        let provenance = std::fs::read_to_string(&provenance_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        std::fs::remove_file(&provenance_path).unwrap();
+        let mut lines = provenance.lines();
+        assert_eq!(lines.next(), Some("name\tchromosome\tstart\tend\tsamples\traw_peak_ids"));
+        let rows: Vec<Vec<&str>> = lines.map(|line| line.split('\t').collect()).collect();
+        assert_eq!(rows.len(), output.lines().count());
+        let both_samples =
+            "input_test_main_internal_input_01,input_test_main_internal_input_iter_01";
+        assert!(rows.iter().any(|row| row[4] == both_samples));
+        // Each raw peak contributes to exactly one consensus peak.
+        let mut raw_peak_ids: Vec<usize> = rows
+            .iter()
+            .flat_map(|row| row[5].split(',').map(|id| id.parse::<usize>().unwrap()))
+            .collect();
+        raw_peak_ids.sort_unstable();
+        let raw_peaks = bed_to_peaks(&[&input_01, &input_02]).unwrap();
+        assert_eq!(
+            raw_peak_ids,
+            (0..raw_peaks.values().map(Vec::len).sum()).collect::<Vec<usize>>()
+        );
+        for line in output.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            assert_eq!(fields.len(), 5);
+            let row = rows.iter().find(|row| row[0] == fields[3]).unwrap();
+            assert_eq!(fields[4], row[4]);
+        }
+    }
+
+    #[test]
+    fn test_main_internal_sample_sheet() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_sample_sheet.bed");
+        main_internal(
+            CommandLineArguments::try_parse_from([
+                "Gipfelkreuzer".to_string(),
+                "--sample-sheet".to_string(),
+                test_resources()
+                    .join("input_test_sample_sheet.tsv")
+                    .display()
+                    .to_string(),
+                "--per-condition".to_string(),
+                "-a".to_string(),
+                "simple".to_string(),
+                "--provenance-column".to_string(),
+                "-o".to_string(),
+                output_path.display().to_string(),
+            ]),
+            true,
+        )
+        .unwrap();
+        let read_output = |path: PathBuf| {
+            let output = std::fs::read_to_string(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            output
+        };
+        let output = read_output(output_path);
+        let treated =
+            read_output(test_output().join("test_main_internal_sample_sheet_treated.bed"));
+        let control =
+            read_output(test_output().join("test_main_internal_sample_sheet_control.bed"));
+        // The provenance column names the samples as specified in the sample sheet.
+        assert_eq!(output, "chr1\t500\t1000\tconsensus_0\ttreated_a,control_a,unassigned\n");
+        assert_eq!(treated, "chr1\t500\t1000\tconsensus_0\n");
+        assert_eq!(control, "chr1\t800\t999\tconsensus_0\n");
+        // The peak files of the sample sheet replace the required input files.
+        assert!(
+            CommandLineArguments::try_parse_from(["Gipfelkreuzer", "--per-condition"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_main_internal_min_samples() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_min_samples.bed");
+        let input_a = test_output().join("test_main_internal_min_samples_a.bed");
+        let input_b = test_output().join("test_main_internal_min_samples_b.bed");
+        std::fs::write(
+            &input_a,
+            "chr1\t100\t200\nchr1\t150\t250\nchr1\t1000\t1100\nchr1\t1050\t1150\n",
+        )
+        .unwrap();
+        std::fs::write(&input_b, "chr1\t180\t220\nchr1\t500\t600\n").unwrap();
+        let run = |threshold: &str| {
+            main_internal(
+                CommandLineArguments::try_parse_from([
+                    "Gipfelkreuzer".to_string(),
+                    "-a".to_string(),
+                    "simple".to_string(),
+                    threshold.to_string(),
+                    "2".to_string(),
+                    "-o".to_string(),
+                    output_path.display().to_string(),
+                    input_a.display().to_string(),
+                    input_b.display().to_string(),
+                ]),
+                true,
+            )
+            .unwrap();
+            let output = std::fs::read_to_string(&output_path).unwrap();
+            std::fs::remove_file(&output_path).unwrap();
+            output
+        };
+        let min_peaks = run("--min-peaks-per-consensus");
+        let min_samples = run("--min-samples");
+        std::fs::remove_file(&input_a).unwrap();
+        std::fs::remove_file(&input_b).unwrap();
+        assert_eq!(min_peaks, "chr1\t100\t250\tconsensus_0\nchr1\t1000\t1150\tconsensus_1\n");
+        // Both peaks at 1000 originate from the same input file.
+        assert_eq!(min_samples, "chr1\t100\t250\tconsensus_0\n");
+    }
+
+    #[test]
+    fn test_main_internal_input_format() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_input_format.bed");
+        let input = test_resources().join("input_test_valid_minimal.narrowPeak");
+        let run = |input_format: &str| {
+            let result = main_internal(
+                CommandLineArguments::try_parse_from([
+                    "Gipfelkreuzer",
+                    "--input-format",
+                    input_format,
+                    "--output-format",
+                    "narrowPeak",
+                    "-o",
+                    &output_path.display().to_string(),
+                    &input.display().to_string(),
+                ]),
+                true,
+            );
+            let output = std::fs::read_to_string(&output_path).ok();
+            let _ = std::fs::remove_file(&output_path);
+            result.map(|_| output.unwrap())
+        };
+        // The input does not contain summits, so the output only depends on their presence.
+        assert_eq!(run("broadPeak").unwrap(), run("auto").unwrap());
+        assert_eq!(run("bed").unwrap(), run("auto").unwrap());
+        assert!(run("narrowPeak").is_err());
+        assert!(run("bed12").is_err());
+    }
+
+    #[test]
+    fn test_main_internal_input_format_per_sample() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_input_format_per_sample.bed");
+        let input_a = test_output().join("test_main_internal_input_format_per_sample_a.bed");
+        let input_b = test_output().join("test_main_internal_input_format_per_sample_b.bed");
+        // Column 10 is not a summit offset and lies outside of the peaks.
+        std::fs::write(
+            &input_a,
+            "chr1\t100\t200\tpeak_a\t900\t.\t0\t0\t0\t500\n\
+            chr1\t1000\t1100\tpeak_c\t900\t.\t0\t0\t0\t500\n",
+        )
+        .unwrap();
+        std::fs::write(&input_b, "chr1\t120\t180\tpeak_b\t10\t.\t0\t0\t0\t500\n").unwrap();
+        let run = |input_format: &str| {
+            let result = main_internal(
+                CommandLineArguments::try_parse_from([
+                    "Gipfelkreuzer".to_string(),
+                    "--input-format".to_string(),
+                    input_format.to_string(),
+                    "--min-samples".to_string(),
+                    "2".to_string(),
+                    "--coordinate-aggregation".to_string(),
+                    "weighted-median".to_string(),
+                    "-o".to_string(),
+                    output_path.display().to_string(),
+                    input_a.display().to_string(),
+                    input_b.display().to_string(),
+                ]),
+                true,
+            );
+            let output = std::fs::read_to_string(&output_path).ok();
+            let _ = std::fs::remove_file(&output_path);
+            result.map(|_| output.unwrap())
+        };
+        let bed = run("bed");
+        let auto = run("auto");
+        std::fs::remove_file(&input_a).unwrap();
+        std::fs::remove_file(&input_b).unwrap();
+        // The high-scoring peak dominates the consensus geometry and the peak of a single
+        // sample is removed.
+        assert_eq!(bed.unwrap(), "chr1\t100\t200\tconsensus_2\n");
+        assert!(auto.is_err());
+    }
+
+    #[test]
+    fn test_main_internal_streaming() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_streaming.bed");
+        let run = |streaming: bool, input_files: &[PathBuf]| {
+            let mut arguments = vec![
+                "Gipfelkreuzer".to_string(),
+                "-o".to_string(),
+                output_path.display().to_string(),
+            ];
+            if streaming {
+                arguments.push("--streaming".to_string());
+            }
+            arguments.extend(
+                input_files
+                    .iter()
+                    .map(|input_file| input_file.display().to_string()),
+            );
+            let result = main_internal(CommandLineArguments::try_parse_from(arguments), true);
+            let output = std::fs::read_to_string(&output_path).ok();
+            let _ = std::fs::remove_file(&output_path);
+            result.map(|_| output.unwrap())
+        };
+        let sorted = [
+            test_resources().join("input_test_valid_with_summit_additional_features.narrowPeak")
+        ];
+        assert_eq!(run(true, &sorted).unwrap(), run(false, &sorted).unwrap());
+        // The per-sample files are merged by chromosome.
+        let per_sample = [
+            test_resources().join("input_test_valid_with_summit_multiple_01.narrowPeak"),
+            test_resources().join("input_test_valid_with_summit_multiple_02.narrowPeak"),
+            test_resources().join("input_test_valid_with_summit_multiple_03.narrowPeak"),
+        ];
+        assert_eq!(run(true, &per_sample).unwrap(), run(false, &per_sample).unwrap());
+        let unsorted = [test_output().join("test_main_internal_streaming_unsorted.bed")];
+        std::fs::write(&unsorted[0], "chr1\t10\t20\nchr2\t10\t20\nchr1\t30\t40\n").unwrap();
+        assert!(run(false, &unsorted).is_ok());
+        assert!(run(true, &unsorted).is_err());
+        std::fs::remove_file(&unsorted[0]).unwrap();
+        assert!(
+            CommandLineArguments::try_parse_from([
+                "Gipfelkreuzer",
+                "--streaming",
+                "--strand-mode",
+                "separate",
+                "input.bed",
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_main_internal_stats_file() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_stats_file.bed");
+        let stats_path = test_output().join("test_main_internal_stats_file.tsv");
+        let input_a = test_output().join("test_main_internal_stats_file_a.bed");
+        let input_b = test_output().join("test_main_internal_stats_file_b.bed");
+        std::fs::write(
+            &input_a,
+            "chr1\t100\t200\nchr1\t150\t250\nchr1\t1000\t1100\nchr1\t1050\t1150\n",
+        )
+        .unwrap();
+        std::fs::write(&input_b, "chr1\t180\t220\nchr1\t500\t600\nchr2\t100\t200\n").unwrap();
+        let run = |streaming: bool| {
+            let mut arguments = vec![
+                "Gipfelkreuzer".to_string(),
+                "-a".to_string(),
+                "simple".to_string(),
+                "-n".to_string(),
+                "2".to_string(),
+                "--stats-file".to_string(),
+                stats_path.display().to_string(),
+                "-o".to_string(),
+                output_path.display().to_string(),
+                input_a.display().to_string(),
+                input_b.display().to_string(),
+            ];
+            if streaming {
+                arguments.push("--streaming".to_string());
+            }
+            main_internal(CommandLineArguments::try_parse_from(arguments), true).unwrap();
+            let stats = std::fs::read_to_string(&stats_path).unwrap();
+            std::fs::remove_file(&stats_path).unwrap();
+            std::fs::remove_file(&output_path).unwrap();
+            stats
+        };
+        let stats = run(false);
+        let streamed_stats = run(true);
+        std::fs::remove_file(&input_a).unwrap();
+        std::fs::remove_file(&input_b).unwrap();
+        assert_eq!(
+            stats,
+            "chromosome\tinput_peaks\tconsensus_peaks\tmean_width\tmedian_width\t\
+            retained_input_fraction\tfiltered_by_min_peaks\n\
+            *\t7\t2\t151.0\t151\t0.714286\t2\n\
+            chr1\t6\t2\t151.0\t151\t0.833333\t1\n\
+            chr2\t1\t0\t0.0\t0\t0.000000\t1\n"
+        );
+        assert_eq!(streamed_stats, stats);
+    }
+
+    #[test]
+    fn test_main_internal_json_output() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_json_output.jsonl");
+        let input_a = test_output().join("test_main_internal_json_output_a.bed");
+        let input_b = test_output().join("test_main_internal_json_output_b.bed");
+        std::fs::write(&input_a, "chr1\t100\t200\nchr1\t150\t250\n").unwrap();
+        std::fs::write(&input_b, "chr1\t180\t220\nchr2\t500\t600\n").unwrap();
+        main_internal(
+            CommandLineArguments::try_parse_from([
+                "Gipfelkreuzer".to_string(),
+                "-a".to_string(),
+                "simple".to_string(),
+                "--output-format".to_string(),
+                "json".to_string(),
+                "-o".to_string(),
+                output_path.display().to_string(),
+                input_a.display().to_string(),
+                input_b.display().to_string(),
+            ]),
+            true,
+        )
+        .unwrap();
+        let output = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        std::fs::remove_file(&input_a).unwrap();
+        std::fs::remove_file(&input_b).unwrap();
+        let records: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["chromosome"], "chr1");
+        assert_eq!(records[0]["start"], 100);
+        assert_eq!(records[0]["end"], 250);
+        assert_eq!(records[0]["contributing_peaks"], 3);
+        assert_eq!(
+            records[0]["samples"],
+            serde_json::json!([
+                "test_main_internal_json_output_a",
+                "test_main_internal_json_output_b"
+            ])
+        );
+        assert_eq!(records[1]["contributing_peaks"], 1);
+        assert_eq!(records[1]["samples"], serde_json::json!(["test_main_internal_json_output_b"]));
+    }
+
+    #[test]
+    fn test_main_internal_gff3_output() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_gff3_output.gff3");
+        let input_a = test_output().join("test_main_internal_gff3_output_a.bed");
+        let input_b = test_output().join("test_main_internal_gff3_output_b.bed");
+        std::fs::write(&input_a, "chr1\t100\t200\nchr1\t150\t250\n").unwrap();
+        std::fs::write(&input_b, "chr1\t180\t220\nchr2\t500\t600\n").unwrap();
+        main_internal(
+            CommandLineArguments::try_parse_from([
+                "Gipfelkreuzer".to_string(),
+                "-a".to_string(),
+                "simple".to_string(),
+                "--output-format".to_string(),
+                "gff3".to_string(),
+                "-o".to_string(),
+                output_path.display().to_string(),
+                input_a.display().to_string(),
+                input_b.display().to_string(),
+            ]),
+            true,
+        )
+        .unwrap();
+        let output = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        std::fs::remove_file(&input_a).unwrap();
+        std::fs::remove_file(&input_b).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "##gff-version 3");
+        let fields: Vec<&str> = lines[1].split('\t').collect();
+        assert_eq!(
+            fields[..8],
+            [
+                "chr1",
+                "Gipfelkreuzer",
+                "region",
+                "101",
+                "250",
+                ".",
+                ".",
+                "."
+            ]
+        );
+        assert!(fields[8].ends_with(
+            ";aggregated_peaks=3;samples=test_main_internal_gff3_output_a,\
+            test_main_internal_gff3_output_b"
+        ));
+        assert!(lines[2].starts_with("chr2\tGipfelkreuzer\tregion\t501\t600\t"));
+    }
+
+    #[test]
+    fn test_main_internal_occupancy_matrix() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_occupancy_matrix.bed");
+        let matrix_path = test_output().join("test_main_internal_occupancy_matrix.tsv");
+        let input_a = test_output().join("test_main_internal_occupancy_matrix_a.bed");
+        let input_b = test_output().join("test_main_internal_occupancy_matrix_b.bed");
+        std::fs::write(&input_a, "chr1\t100\t200\n").unwrap();
+        std::fs::write(&input_b, "chr1\t110\t190\nchr2\t500\t600\n").unwrap();
+        main_internal(
+            CommandLineArguments::try_parse_from([
+                "Gipfelkreuzer".to_string(),
+                "--occupancy-matrix".to_string(),
+                matrix_path.display().to_string(),
+                "-o".to_string(),
+                output_path.display().to_string(),
+                input_a.display().to_string(),
+                input_b.display().to_string(),
+            ]),
+            true,
+        )
+        .unwrap();
+        let matrix = std::fs::read_to_string(&matrix_path).unwrap();
+        for path in [&output_path, &matrix_path, &input_a, &input_b] {
+            std::fs::remove_file(path).unwrap();
+        }
+        assert_eq!(
+            matrix,
+            "name\ttest_main_internal_occupancy_matrix_a\ttest_main_internal_occupancy_matrix_b\n\
+            consensus_1\t1\t1\n\
+            consensus_2\t0\t1\n"
+        );
+    }
+
+    #[test]
+    fn test_main_internal_peak_name_mapping() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_peak_name_mapping.bed");
+        let mapping_path = test_output().join("test_main_internal_peak_name_mapping.tsv");
+        let input_a = test_output().join("test_main_internal_peak_name_mapping_a.bed");
+        let input_b = test_output().join("test_main_internal_peak_name_mapping_b.bed");
+        std::fs::write(&input_a, "chr1\t100\t200\ta_1\nchr1\t150\t250\ta_2\n").unwrap();
+        std::fs::write(&input_b, "chr1\t180\t220\nchr2\t500\t600\tb_2\n").unwrap();
+        main_internal(
+            CommandLineArguments::try_parse_from([
+                "Gipfelkreuzer".to_string(),
+                "-a".to_string(),
+                "simple".to_string(),
+                "--peak-name-mapping".to_string(),
+                mapping_path.display().to_string(),
+                "-o".to_string(),
+                output_path.display().to_string(),
+                input_a.display().to_string(),
+                input_b.display().to_string(),
+            ]),
+            true,
+        )
+        .unwrap();
+        let output = std::fs::read_to_string(&output_path).unwrap();
+        let mapping = std::fs::read_to_string(&mapping_path).unwrap();
+        for path in [&output_path, &mapping_path, &input_a, &input_b] {
+            std::fs::remove_file(path).unwrap();
+        }
+        let consensus_names: Vec<&str> = output
+            .lines()
+            .map(|line| line.split('\t').nth(3).unwrap())
+            .collect();
+        // The unnamed raw peak is listed by its raw peak ID.
+        assert_eq!(
+            mapping,
+            format!(
+                "raw_peak_name\tconsensus_name\na_1\t{0}\na_2\t{0}\n2\t{0}\nb_2\t{1}\n",
+                consensus_names[0], consensus_names[1]
+            )
+        );
+    }
+
+    #[test]
+    fn test_main_internal_score_aggregation() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_score_aggregation.bed");
+        let input_a = test_output().join("test_main_internal_score_aggregation_a.bed");
+        let input_b = test_output().join("test_main_internal_score_aggregation_b.bed");
+        std::fs::write(&input_a, "chr1\t200\t300\ta\t900\t.\t1\t2\t3\t90\n").unwrap();
+        std::fs::write(&input_b, "chr1\t0\t260\tb\t10\t.\t1\t2\t3\t250\n").unwrap();
+        main_internal(
+            CommandLineArguments::try_parse_from([
+                "Gipfelkreuzer".to_string(),
+                "--coordinate-aggregation".to_string(),
+                "mean".to_string(),
+                "-b".to_string(),
+                "6".to_string(),
+                "-o".to_string(),
+                output_path.display().to_string(),
+                input_a.display().to_string(),
+                input_b.display().to_string(),
+            ]),
+            true,
+        )
+        .unwrap();
+        let output = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        std::fs::remove_file(&input_a).unwrap();
+        std::fs::remove_file(&input_b).unwrap();
+        // The score of the raw peak is aggregated although its summit lies outside of the
+        // consensus peak.
+        assert_eq!(output, "chr1\t100\t280\tconsensus_0\t900\t.\n");
+    }
+
+    #[test]
+    fn test_main_internal_score_source() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_score_source.bed");
+        let input_a = test_output().join("test_main_internal_score_source_a.bed");
+        let input_b = test_output().join("test_main_internal_score_source_b.bed");
+        std::fs::write(&input_a, "chr1\t100\t200\nchr1\t1000\t1100\n").unwrap();
+        std::fs::write(&input_b, "chr1\t110\t190\nchr2\t500\t600\n").unwrap();
+        let run = |score_source: &str| {
+            main_internal(
+                CommandLineArguments::try_parse_from([
+                    "Gipfelkreuzer".to_string(),
+                    "-b".to_string(),
+                    "6".to_string(),
+                    "--score-source".to_string(),
+                    score_source.to_string(),
+                    "-o".to_string(),
+                    output_path.display().to_string(),
+                    input_a.display().to_string(),
+                    input_b.display().to_string(),
+                ]),
+                true,
+            )
+            .unwrap();
+            let output = std::fs::read_to_string(&output_path).unwrap();
+            std::fs::remove_file(&output_path).unwrap();
+            output
+                .lines()
+                .map(|line| line.split('\t').nth(4).unwrap().to_string())
+                .collect::<Vec<String>>()
+        };
+        let aggregated = run("aggregated");
+        let peaks = run("peaks");
+        let samples = run("samples");
+        std::fs::remove_file(&input_a).unwrap();
+        std::fs::remove_file(&input_b).unwrap();
+        assert_eq!(aggregated, vec!["0", "0", "0"]);
+        assert_eq!(peaks, vec!["1000", "500", "500"]);
+        assert_eq!(samples, vec!["1000", "500", "500"]);
+    }
+
+    #[test]
+    fn test_main_internal_coordinate_aggregation() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_coordinate_aggregation.bed");
+        let input_a = test_output().join("test_main_internal_coordinate_aggregation_a.bed");
+        let input_b = test_output().join("test_main_internal_coordinate_aggregation_b.bed");
+        std::fs::write(&input_a, "chr1\t100\t200\tpeak_a\t900\n").unwrap();
+        std::fs::write(&input_b, "chr1\t120\t180\tpeak_b\t10\n").unwrap();
+        let run = |coordinate_aggregation: &str| {
+            main_internal(
+                CommandLineArguments::try_parse_from([
+                    "Gipfelkreuzer".to_string(),
+                    "--coordinate-aggregation".to_string(),
+                    coordinate_aggregation.to_string(),
+                    "-o".to_string(),
+                    output_path.display().to_string(),
+                    input_a.display().to_string(),
+                    input_b.display().to_string(),
+                ]),
+                true,
+            )
+            .unwrap();
+            let output = std::fs::read_to_string(&output_path).unwrap();
+            std::fs::remove_file(&output_path).unwrap();
+            output
+                .lines()
+                .map(|line| line.split('\t').take(3).collect::<Vec<&str>>().join("\t"))
+                .collect::<Vec<String>>()
+        };
+        let median = run("median");
+        let weighted_median = run("weighted-median");
+        std::fs::remove_file(&input_a).unwrap();
+        std::fs::remove_file(&input_b).unwrap();
+        assert_eq!(median, vec!["chr1\t110\t190"]);
+        // The high-scoring peak dominates the consensus geometry.
+        assert_eq!(weighted_median, vec!["chr1\t100\t200"]);
+    }
+
+    #[test]
+    fn test_main_internal_chrom_alias() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_chrom_alias.bed");
+        let alias_path = test_output().join("test_main_internal_chrom_alias.txt");
+        let input_a = test_output().join("test_main_internal_chrom_alias_a.bed");
+        let input_b = test_output().join("test_main_internal_chrom_alias_b.bed");
+        std::fs::write(&alias_path, "chr_main_alias\tmain_alias\n").unwrap();
+        std::fs::write(&input_a, "chr_main_alias\t100\t200\n").unwrap();
+        std::fs::write(&input_b, "main_alias\t110\t190\n").unwrap();
+        main_internal(
+            CommandLineArguments::try_parse_from([
+                "Gipfelkreuzer".to_string(),
+                "--chrom-alias".to_string(),
+                alias_path.display().to_string(),
+                "-n".to_string(),
+                "2".to_string(),
+                "-o".to_string(),
+                output_path.display().to_string(),
+                input_a.display().to_string(),
+                input_b.display().to_string(),
+            ]),
+            true,
+        )
+        .unwrap();
+        let output = std::fs::read_to_string(&output_path).unwrap();
+        for path in [&output_path, &alias_path, &input_a, &input_b] {
+            std::fs::remove_file(path).unwrap();
+        }
+        // Both peaks are merged on the canonical chromosome.
+        assert_eq!(output, "chr_main_alias\t105\t195\tconsensus_1\n");
+    }
+
+    #[test]
+    fn test_main_internal_config() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_config.bed");
+        let config_path = test_output().join("test_main_internal_config.toml");
+        let input_a = test_output().join("test_main_internal_config_a.bed");
+        let input_b = test_output().join("test_main_internal_config_b.bed");
+        std::fs::write(
+            &config_path,
+            "input-files = [\"test_main_internal_config_a.bed\", \"test_main_internal_config_b.bed\"]\n\
+             min-peaks-per-consensus = 2\n\
+             bed_output_columns = 3\n\
+             provenance-column = false\n",
+        )
+        .unwrap();
+        std::fs::write(&input_a, "chr1\t100\t200\nchr2\t100\t200\n").unwrap();
+        std::fs::write(&input_b, "chr1\t110\t190\n").unwrap();
+        let cla = CommandLineArguments::try_parse_with_config_from([
+            "Gipfelkreuzer".to_string(),
+            "--config".to_string(),
+            config_path.display().to_string(),
+            "-b".to_string(),
+            "4".to_string(),
+            "-o".to_string(),
+            output_path.display().to_string(),
+        ]);
+        // Relative paths are resolved relative to the configuration file.
+        assert_eq!(
+            cla.as_ref().unwrap().consensus_arguments().input_files(),
+            &vec![input_a.clone(), input_b.clone()]
+        );
+        assert_eq!(
+            cla.as_ref()
+                .unwrap()
+                .consensus_arguments()
+                .algorithm_arguments()
+                .min_peaks_per_consensus(),
+            2
+        );
+        // Command line arguments override the configuration.
+        assert_eq!(
+            cla.as_ref()
+                .unwrap()
+                .consensus_arguments()
+                .bed_output_columns(),
+            4
+        );
+        main_internal(cla, true).unwrap();
+        let output = std::fs::read_to_string(&output_path).unwrap();
+        // Input files on the command line replace the configured ones.
+        let cla = CommandLineArguments::try_parse_with_config_from([
+            "Gipfelkreuzer".to_string(),
+            "--config".to_string(),
+            config_path.display().to_string(),
+            input_b.display().to_string(),
+        ]);
+        assert_eq!(cla.unwrap().consensus_arguments().input_files(), &vec![input_b.clone()]);
+        std::fs::write(&config_path, "unknown-parameter = 1\n").unwrap();
+        let cla = CommandLineArguments::try_parse_with_config_from([
+            "Gipfelkreuzer".to_string(),
+            "--config".to_string(),
+            config_path.display().to_string(),
+            input_b.display().to_string(),
+        ]);
+        for path in [&output_path, &config_path, &input_a, &input_b] {
+            std::fs::remove_file(path).unwrap();
+        }
+        // Configuration errors refer to the file and the offending parameter.
+        let error = cla.unwrap_err();
+        assert_eq!(error.error_type(), ApplicationErrorType::ConfigError);
+        let context = error.context().unwrap();
+        assert_eq!(context.path(), &Some(config_path.display().to_string()));
+        assert_eq!(context.value(), &Some("unknown-parameter".to_string()));
+        assert_eq!(output, "chr1\t105\t195\tconsensus_2\n");
+    }
+
+    #[test]
+    fn test_main_internal_mspc() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_mspc.bed");
+        let run = |min_replicates: &str| {
+            main_internal(
+                CommandLineArguments::try_parse_from([
+                    "Gipfelkreuzer".to_string(),
+                    "-a".to_string(),
+                    "mspc".to_string(),
+                    "--min-replicates".to_string(),
+                    min_replicates.to_string(),
+                    "-o".to_string(),
+                    output_path.display().to_string(),
+                    test_resources()
+                        .join("input_test_main_internal_input_01.narrowPeak")
+                        .display()
+                        .to_string(),
+                    test_resources()
+                        .join("input_test_main_internal_input_02.narrowPeak")
+                        .display()
+                        .to_string(),
+                ]),
+                true,
+            )
+            .unwrap();
+            let output = std::fs::read_to_string(&output_path).unwrap();
+            std::fs::remove_file(&output_path).unwrap();
+            output
+        };
+        assert_eq!(run("2"), "chr1\t500\t1000\tconsensus_0\n");
+        // No peak is supported by three input files.
+        assert_eq!(run("3"), "");
+    }
+
+    #[test]
+    fn test_main_internal_strand_mode() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_strand_mode.bed");
+        let input = test_resources().join("input_test_stranded.narrowPeak");
+        let run = |strand_mode: &str| {
+            main_internal(
+                CommandLineArguments::try_parse_from([
+                    "Gipfelkreuzer".to_string(),
+                    "--strand-mode".to_string(),
+                    strand_mode.to_string(),
+                    "--min-peaks-per-consensus".to_string(),
+                    "2".to_string(),
+                    "-b".to_string(),
+                    "6".to_string(),
+                    "-o".to_string(),
+                    output_path.display().to_string(),
+                    input.display().to_string(),
+                ]),
+                true,
+            )
+            .unwrap();
+            let output = std::fs::read_to_string(&output_path).unwrap();
+            std::fs::remove_file(&output_path).unwrap();
+            output
+                .lines()
+                .map(|line| line.split('\t').nth(5).unwrap().to_string())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(run("ignore"), vec!["."]);
+        let mut strands = run("separate");
+        strands.sort();
+        assert_eq!(strands, vec!["+", "-"]);
+    }
+
+    #[test]
+    fn test_main_internal_output_format() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_output_format.bed");
+        let input = test_resources().join("input_test_main_internal_input_01.narrowPeak");
+        let arguments = |extra_arguments: &[&str]| {
+            let mut arguments = vec![
+                "Gipfelkreuzer".to_string(),
+                "-a".to_string(),
+                "simple".to_string(),
+            ];
+            arguments.extend(extra_arguments.iter().map(|argument| argument.to_string()));
+            arguments.extend([
+                "-o".to_string(),
+                output_path.display().to_string(),
+                input.display().to_string(),
+            ]);
+            CommandLineArguments::try_parse_from(arguments)
+        };
+        main_internal(arguments(&["--output-format", "narrowPeak"]), true).unwrap();
+        let output = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        // The statistical values are aggregated from the raw peaks and the summit at the centre
+        // of the merged peak is written as offset from the start.
+        assert_eq!(
+            output,
+            "chr1\t500\t1000\tconsensus_0\t358\t.\t16.7471\t38.4652\t35.8377\t250\n"
+        );
+        assert!(arguments(&["--output-format", "bed6", "-b", "6"]).is_err());
+    }
+
+    #[test]
+    fn test_main_internal_blacklist() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_blacklist.bed");
+        let input = test_resources().join("input_test_main_internal_input_01.narrowPeak");
+        let blacklist = test_resources().join("input_test_blacklist.bed");
+        let run = |blacklist_mode: &str| {
+            main_internal(
+                CommandLineArguments::try_parse_from([
+                    "Gipfelkreuzer".to_string(),
+                    "-a".to_string(),
+                    "simple".to_string(),
+                    "--blacklist".to_string(),
+                    blacklist.display().to_string(),
+                    "--blacklist-mode".to_string(),
+                    blacklist_mode.to_string(),
+                    "-o".to_string(),
+                    output_path.display().to_string(),
+                    input.display().to_string(),
+                ]),
+                true,
+            )
+            .unwrap();
+            let output = std::fs::read_to_string(&output_path).unwrap();
+            std::fs::remove_file(&output_path).unwrap();
+            output
+        };
+        // The broad raw peak overlapping the blacklist is removed before merging.
+        assert_eq!(run("remove"), "chr1\t600\t800\tconsensus_0\n");
+        // The blacklisted end of the merged consensus peak is trimmed.
+        assert_eq!(run("trim"), "chr1\t500\t899\tconsensus_0\n");
+    }
+
+    #[test]
+    fn test_main_internal_run_id_suffix() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_run_id_suffix.bed");
+        let input_01 = test_resources().join("input_test_main_internal_input_01.narrowPeak");
+        let input_02 = test_resources().join("input_test_main_internal_input_02.narrowPeak");
+        let arguments = |min_peaks: &str| {
+            CommandLineArguments::try_parse_from([
+                "Gipfelkreuzer".to_string(),
+                "--run-id-suffix".to_string(),
+                "--min-peaks-per-consensus".to_string(),
+                min_peaks.to_string(),
+                "-o".to_string(),
+                output_path.display().to_string(),
+                input_01.display().to_string(),
+                input_02.display().to_string(),
+            ])
+        };
+        main_internal(arguments("1"), true).unwrap();
+        main_internal(arguments("2"), true).unwrap();
+        let mut outputs: Vec<PathBuf> = std::fs::read_dir(test_output())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .starts_with("test_main_internal_run_id_suffix.")
+            })
+            .collect();
+        outputs.sort_unstable();
+        for output in &outputs {
+            std::fs::remove_file(output).unwrap();
+        }
+        assert!(!output_path.exists());
+        assert_eq!(outputs.len(), 2);
+        for output in &outputs {
+            let file_name = output.file_name().unwrap().to_string_lossy().to_string();
+            let run_id = file_name
+                .strip_prefix("test_main_internal_run_id_suffix.")
+                .and_then(|name| name.strip_suffix(".bed"))
+                .unwrap();
+            assert_eq!(run_id.len(), RUN_ID_LENGTH);
+        }
+    }
+
+    #[test]
+    fn test_main_internal_multiqc() {
+        let metrics_path = test_output().join("test_main_internal_multiqc_mqc.json");
+        test_main_internal(
+            &[
+                "input_test_main_internal_input_01.narrowPeak",
+                "input_test_main_internal_input_02.narrowPeak",
+            ],
+            "test_main_internal_multiqc.bed",
+            &["--multiqc".to_string(), metrics_path.display().to_string()],
+            vec![
+                ("chr1".to_string(), PeakData::new(1, 629u64, 769u64, 698u64).unwrap()),
+                ("chr1".to_string(), PeakData::new(4, 864u64, 918u64, 904u64).unwrap()),
+            ],
+        );
+        let metrics: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&metrics_path).unwrap()).unwrap();
+        std::fs::remove_file(&metrics_path).unwrap();
+        assert_eq!(metrics["data"]["input_test_main_internal_input_01"]["raw_peaks"], 4);
+        assert_eq!(metrics["data"]["input_test_main_internal_input_02"]["raw_peaks"], 3);
+        assert_eq!(metrics["data"]["consensus"]["consensus_peaks"], 2);
+        assert_eq!(metrics["data"]["consensus"]["merged_duplicates"], 0);
+    }
+
+    #[test]
+    fn test_main_internal_stats_output() {
+        let statistics_path = test_output().join("test_main_internal_stats_output.json");
+        test_main_internal(
+            &[
+                "input_test_main_internal_input_01.narrowPeak",
+                "input_test_main_internal_input_02.narrowPeak",
+            ],
+            "test_main_internal_stats_output.bed",
+            &[
+                "--stats-output".to_string(),
+                statistics_path.display().to_string(),
+            ],
+            vec![
+                ("chr1".to_string(), PeakData::new(1, 629u64, 769u64, 698u64).unwrap()),
+                ("chr1".to_string(), PeakData::new(4, 864u64, 918u64, 904u64).unwrap()),
+            ],
+        );
+        let statistics: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&statistics_path).unwrap()).unwrap();
+        std::fs::remove_file(&statistics_path).unwrap();
+        assert_eq!(statistics["inputs"][0]["raw_peaks"], 4);
+        assert_eq!(statistics["inputs"][1]["raw_peaks"], 3);
+        assert_eq!(statistics["total"]["raw_peaks"], 7);
+        assert_eq!(statistics["total"]["bins"], 1);
+        assert_eq!(statistics["chromosomes"]["chr1"]["consensus_peaks"], 2);
+        assert_eq!(statistics["total"]["mean_width"], 98.0);
+        assert_eq!(statistics["support_thresholds"][1]["consensus_peaks"], 2);
+    }
+
+    #[test]
+    fn test_main_internal_summit_concordance() {
+        let concordance_directory = test_output().join("test_main_internal_summit_concordance");
+        test_main_internal(
+            &[
+                "input_test_main_internal_input_01.narrowPeak",
+                "input_test_main_internal_input_02.narrowPeak",
+            ],
+            "test_main_internal_summit_concordance.bed",
+            &[
+                "--summit-concordance".to_string(),
+                concordance_directory.display().to_string(),
+            ],
+            vec![
+                ("chr1".to_string(), PeakData::new(1, 629u64, 769u64, 698u64).unwrap()),
+                ("chr1".to_string(), PeakData::new(4, 864u64, 918u64, 904u64).unwrap()),
+            ],
+        );
+        let peaks = std::fs::read_to_string(
+            concordance_directory.join(gipfelkreuzer::concordance::PEAK_CONCORDANCE_FILE_NAME),
+        )
+        .unwrap();
+        let samples = std::fs::read_to_string(
+            concordance_directory.join(gipfelkreuzer::concordance::SAMPLE_OFFSETS_FILE_NAME),
+        )
+        .unwrap();
+        std::fs::remove_dir_all(&concordance_directory).unwrap();
+        let peak_lines: Vec<&str> = peaks.lines().collect();
+        assert_eq!(peak_lines.len(), 3);
+        assert!(peak_lines[1].starts_with("chr1\t629\t769\t698\t"));
+        assert!(peak_lines[2].starts_with("chr1\t864\t918\t904\t"));
+        let sample_lines: Vec<&str> = samples.lines().collect();
+        assert_eq!(sample_lines.len(), 4);
+        assert!(sample_lines[3].starts_with("*\t"));
+    }
+
+    #[cfg(feature = "bigwig")]
+    #[test]
+    fn test_main_internal_bigwig() {
+        let output_path = test_output().join("test_main_internal_bigwig.bed");
+        let bigwig_path = test_resources().join("input_test_signal.bw");
+        let cla = CommandLineArguments::try_parse_from([
+            "Gipfelkreuzer".to_string(),
+            "-b".to_string(),
+            "3".to_string(),
+            "--bigwig".to_string(),
+            bigwig_path.display().to_string(),
+            bigwig_path.display().to_string(),
+            "--refine-summits".to_string(),
+            "-o".to_string(),
+            output_path.display().to_string(),
+            test_resources()
+                .join("input_test_main_internal_input_01.narrowPeak")
+                .display()
+                .to_string(),
+        ]);
+        assert!(main_internal(cla, true).is_ok());
+        let output = std::fs::read_to_string(&output_path).unwrap();
+        assert!(!output.is_empty());
+        // Three standard columns followed by the mean and maximum signal of both tracks.
+        assert!(output.lines().all(|line| line.split('\t').count() == 7));
+        std::fs::remove_file(output_path).unwrap();
+    }
+
+    #[test]
+    fn test_main_internal_deterministic_output() {
+        let input_paths: Vec<String> = [
+            "input_test_valid_with_summit_multiple_01.narrowPeak",
+            "input_test_valid_with_summit_multiple_02.narrowPeak",
+            "input_test_valid_with_summit_multiple_03.narrowPeak",
+        ]
+        .iter()
+        .map(|input| test_resources().join(input).display().to_string())
+        .collect();
+        let mut outputs = Vec::new();
+        for (run, spill) in [false, true, false, true].into_iter().enumerate() {
+            let output_path =
+                test_output().join(format!("test_main_internal_deterministic_output_{}.bed", run));
+            let mut cla = vec![
+                "Gipfelkreuzer".to_string(),
+                "-b".to_string(),
+                "14".to_string(),
+                "-o".to_string(),
+                output_path.display().to_string(),
+            ];
+            if spill {
+                cla.push("--spill-directory".to_string());
+                cla.push(
+                    test_output()
+                        .join("test_main_internal_deterministic_output")
+                        .display()
+                        .to_string(),
+                );
+            }
+            cla.extend(input_paths.iter().cloned());
+            assert!(main_internal(CommandLineArguments::try_parse_from(cla), true).is_ok());
+            outputs.push(std::fs::read(&output_path).unwrap());
+            std::fs::remove_file(output_path).unwrap();
+        }
+        std::fs::remove_dir(test_output().join("test_main_internal_deterministic_output")).unwrap();
+        assert!(outputs.iter().all(|output| output == &outputs[0]));
+        let output = String::from_utf8(outputs[0].clone()).unwrap();
+        let chromosomes: Vec<&str> = output
+            .lines()
+            .map(|line| line.split('\t').next().unwrap())
+            .collect();
+        assert_eq!(chromosomes, vec!["chr1", "chr1", "chr1", "chr1", "chr2", "chr2"]);
+        let starts: Vec<u64> = output
+            .lines()
+            .map(|line| line.split('\t').nth(1).unwrap().parse().unwrap())
+            .collect();
+        assert!(starts[..4].is_sorted());
+        assert!(starts[4..].is_sorted());
+    }
+
+    #[test]
+    fn test_main_internal_deterministic_output_threads() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        // Overlapping peaks of several chromosomes with pseudo-random coordinates and scores,
+        // so that chromosomes and peak bins are processed in parallel.
+        let mut state: u64 = 42;
+        let mut next = |modulus: u64| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) % modulus
+        };
+        let input_paths: Vec<PathBuf> = (0..3)
+            .map(|sample| {
+                let path = test_output().join(format!(
+                    "test_main_internal_deterministic_output_threads_{}.narrowPeak",
+                    sample
+                ));
+                let mut content = String::new();
+                for chromosome in 1..=8 {
+                    for _ in 0..200 {
+                        let start = next(100_000);
+                        let length = 50 + next(500);
+                        content.push_str(&format!(
+                            "chr{}\t{}\t{}\tpeak\t{}\t.\t{}\t{}\t{}\t{}\n",
+                            chromosome,
+                            start,
+                            start + length,
+                            next(1000),
+                            next(100),
+                            next(20),
+                            next(20),
+                            next(length)
+                        ));
+                    }
+                }
+                std::fs::write(&path, content).unwrap();
+                path
+            })
+            .collect();
+        let output_path = test_output().join("test_main_internal_deterministic_output_threads.bed");
+        let run = |threads: usize| {
+            let mut cla = vec![
+                "Gipfelkreuzer".to_string(),
+                "-b".to_string(),
+                "10".to_string(),
+                "--threads".to_string(),
+                threads.to_string(),
+                "-o".to_string(),
+                output_path.display().to_string(),
+            ];
+            cla.extend(input_paths.iter().map(|path| path.display().to_string()));
+            main_internal(CommandLineArguments::try_parse_from(cla), true).unwrap();
+            let output = std::fs::read(&output_path).unwrap();
+            std::fs::remove_file(&output_path).unwrap();
+            output
+        };
+        let single_threaded = run(1);
+        let multi_threaded = [run(4), run(8), run(0)];
+        for path in input_paths {
+            std::fs::remove_file(path).unwrap();
+        }
+        assert!(!single_threaded.is_empty());
+        assert!(
+            multi_threaded
+                .iter()
+                .all(|output| output == &single_threaded)
+        );
+    }
+
+    #[test]
+    fn test_main_internal_help() {
+        let cla_short = CommandLineArguments::try_parse_from(vec!["Gipfelkreuzer", "-h"]);
+        assert!(main_internal(cla_short, true).is_ok());
+    }
+
+    #[test]
+    fn test_main_internal_version() {
+        let cla_short = CommandLineArguments::try_parse_from(vec!["Gipfelkreuzer", "-V"]);
+        assert!(main_internal(cla_short, true).is_ok());
+    }
+
+    #[test]
+    fn test_main_internal_serve_arguments() {
+        let input = test_resources().join("input_test_main_internal_input_01.narrowPeak");
+        let cla = CommandLineArguments::try_parse_from(vec![
+            "Gipfelkreuzer".to_string(),
+            "serve".to_string(),
+            "-a".to_string(),
+            "simple".to_string(),
+            "--precomputed".to_string(),
+            "--address".to_string(),
+            "127.0.0.1:9000".to_string(),
+            input.display().to_string(),
+        ])
+        .unwrap();
+        assert!(cla.consensus_arguments().input_files().is_empty());
+        let Some(Command::Serve(serve_arguments)) = cla.command() else {
+            panic!("The serve command must be parsed.");
+        };
+        assert_eq!(
+            serve_arguments.algorithm_arguments().algorithm(),
+            ConsensusPeakAlgorithm::Simple
+        );
+        assert!(serve_arguments.precomputed());
+        assert_eq!(serve_arguments.address(), "127.0.0.1:9000");
+        assert_eq!(serve_arguments.input_files(), &vec![input]);
+        assert!(CommandLineArguments::try_parse_from(vec!["Gipfelkreuzer", "serve"]).is_err());
+    }
+
+    #[test]
+    fn test_main_internal_stats() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_stats.tsv");
+        let cla = CommandLineArguments::try_parse_from(vec![
+            "Gipfelkreuzer".to_string(),
+            "stats".to_string(),
+            "--output-file".to_string(),
+            output_path.display().to_string(),
+            test_resources()
+                .join("input_test_valid_with_summit.narrowPeak")
+                .display()
+                .to_string(),
+            test_resources()
+                .join("input_test_valid_minimal.narrowPeak")
+                .display()
+                .to_string(),
+        ]);
+        main_internal(cla, true).unwrap();
+        let table = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        assert_eq!(table.lines().count(), 7);
+        assert!(table.starts_with("file\tchromosome\tpeaks\tcovered_bases"));
+        assert!(CommandLineArguments::try_parse_from(vec!["Gipfelkreuzer", "stats"]).is_err());
+    }
+
+    #[test]
+    fn test_main_internal_compare() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_compare.tsv");
+        let reference_only_path = test_output().join("test_main_internal_compare_reference.bed");
+        let cla = CommandLineArguments::try_parse_from(vec![
+            "Gipfelkreuzer".to_string(),
+            "compare".to_string(),
+            "-o".to_string(),
+            output_path.display().to_string(),
+            "--reference-only".to_string(),
+            reference_only_path.display().to_string(),
+            test_resources()
+                .join("input_test_main_internal_input_01.narrowPeak")
+                .display()
+                .to_string(),
+            test_resources()
+                .join("input_test_main_internal_input_02.narrowPeak")
+                .display()
+                .to_string(),
+        ]);
+        main_internal(cla, true).unwrap();
+        let table = std::fs::read_to_string(&output_path).unwrap();
+        let reference_only = std::fs::read_to_string(&reference_only_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        std::fs::remove_file(&reference_only_path).unwrap();
+        assert_eq!(table.lines().nth(1), Some("4\t3\t2\t0\t1.000000\t0.500000\t0.666667"));
+        assert_eq!(reference_only, "chr1\t658\t739\nchr1\t678\t719\n");
+        let invalid_overlap = CommandLineArguments::try_parse_from(vec![
+            "Gipfelkreuzer",
+            "compare",
+            "--min-overlap",
+            "2",
+            "reference.bed",
+            "query.bed",
+        ]);
+        assert_eq!(
+            main_internal(invalid_overlap, true)
+                .unwrap_err()
+                .error_type(),
+            ApplicationErrorType::ConfigError
+        );
+    }
+
+    #[test]
+    fn test_main_internal_saturation() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_saturation.tsv");
+        let cla = CommandLineArguments::try_parse_from(vec![
+            "Gipfelkreuzer".to_string(),
+            "saturation".to_string(),
+            "--repetitions".to_string(),
+            "3".to_string(),
+            "-o".to_string(),
+            output_path.display().to_string(),
+            test_resources()
+                .join("input_test_main_internal_input_01.narrowPeak")
+                .display()
+                .to_string(),
+            test_resources()
+                .join("input_test_main_internal_input_02.narrowPeak")
+                .display()
+                .to_string(),
+        ]);
+        main_internal(cla, true).unwrap();
+        let table = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(lines[0], "samples\trepetition\tconsensus_peaks");
+        assert_eq!(lines[6], "2\t3\t2");
+    }
+
+    #[test]
+    fn test_main_internal_sweep() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_sweep.tsv");
+        let cla = CommandLineArguments::try_parse_from(vec![
+            "Gipfelkreuzer".to_string(),
+            "sweep".to_string(),
+            "--gold-standard".to_string(),
+            test_resources()
+                .join("input_test_main_internal_input_01.narrowPeak")
+                .display()
+                .to_string(),
+            "--max-min-peaks".to_string(),
+            "5".to_string(),
+            "-o".to_string(),
+            output_path.display().to_string(),
+            test_resources()
+                .join("input_test_main_internal_input_01.narrowPeak")
+                .display()
+                .to_string(),
+            test_resources()
+                .join("input_test_main_internal_input_02.narrowPeak")
+                .display()
+                .to_string(),
+        ]);
+        main_internal(cla, true).unwrap();
+        let table = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0], "min_peaks_per_consensus\tconsensus_peaks\tprecision\trecall\tf1");
+        assert_eq!(lines[3], "3\t2\t1.000000\t1.000000\t1.000000");
+        assert_eq!(lines[4], "4\t1\t1.000000\t1.000000\t1.000000");
+        assert_eq!(lines[5], "5\t0\t0.000000\t0.000000\t0.000000");
+    }
+
+    #[test]
+    fn test_main_internal_convert() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let saf_path = test_output().join("test_main_internal_convert.saf");
+        let output_path = test_output().join("test_main_internal_convert.out");
+        let convert_arguments = |input: &Path, output: &Path, format: Option<&str>| {
+            let mut arguments = vec![
+                "Gipfelkreuzer".to_string(),
+                "convert".to_string(),
+                input.display().to_string(),
+                output.display().to_string(),
+            ];
+            if let Some(format) = format {
+                arguments.extend(["--to".to_string(), format.to_string()]);
+            }
+            CommandLineArguments::try_parse_from(arguments)
+        };
+        let input_path = test_resources().join("input_test_main_internal_input_01.narrowPeak");
+        main_internal(convert_arguments(&input_path, &saf_path, None), true).unwrap();
+        main_internal(convert_arguments(&saf_path, &output_path, Some("narrow-peak")), true)
+            .unwrap();
+        let error =
+            main_internal(convert_arguments(&saf_path, &output_path, None), true).unwrap_err();
+        let saf = std::fs::read_to_string(&saf_path).unwrap();
+        let narrow_peaks = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&saf_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        assert_eq!(
+            saf,
+            "GeneID\tChr\tStart\tEnd\tStrand\n\
+            consensus_0\tchr1\t501\t1000\t.\n\
+            consensus_3\tchr1\t601\t800\t.\n\
+            consensus_2\tchr1\t659\t739\t.\n\
+            consensus_1\tchr1\t679\t719\t.\n"
+        );
+        // SAF files do not contain summits, so the summits are set to the centre of the peaks.
+        assert_eq!(
+            narrow_peaks,
+            "chr1\t500\t1000\tconsensus_0\t0\t.\t-1\t-1\t-1\t250\n\
+            chr1\t600\t800\tconsensus_1\t0\t.\t-1\t-1\t-1\t100\n\
+            chr1\t658\t739\tconsensus_2\t0\t.\t-1\t-1\t-1\t40\n\
+            chr1\t678\t719\tconsensus_3\t0\t.\t-1\t-1\t-1\t20\n"
+        );
+        assert_eq!(error.error_type(), ApplicationErrorType::ConfigError);
+    }
+
+    #[test]
+    fn test_main_internal_filter() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_filter.narrowPeak");
+        let cla = CommandLineArguments::try_parse_from(vec![
+            "Gipfelkreuzer".to_string(),
+            "filter".to_string(),
+            "--whitelist".to_string(),
+            test_resources()
+                .join("input_test_main_internal_input_02.narrowPeak")
+                .display()
+                .to_string(),
+            "--min-length".to_string(),
+            "50".to_string(),
+            "--exclude-chromosomes".to_string(),
+            "chrM".to_string(),
+            "-o".to_string(),
+            output_path.display().to_string(),
+            test_resources()
+                .join("input_test_main_internal_input_01.narrowPeak")
+                .display()
+                .to_string(),
+        ]);
+        main_internal(cla, true).unwrap();
+        let filtered = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        assert_eq!(
+            filtered,
+            "# Broad fragment covering both peaks and slightly shifted summit.\n\
+            chr1\t500\t1000\tnarrow_peak_01\t358\t.\t16.7471\t38.4652\t35.8377 230\n\
+            # Peak at approximately coordinate 700.\n\
+            chr1\t600\t800\tnarrow_peak_01\t358\t.\t16.7471\t38.4652\t35.8377 101"
+        );
+    }
+
+    #[test]
+    fn test_main_internal_sort() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let input_path = test_output().join("test_main_internal_sort_input.bed");
+        let aliases_path = test_output().join("test_main_internal_sort_aliases.txt");
+        let output_path = test_output().join("test_main_internal_sort.bed");
+        std::fs::write(&input_path, "2\t30\t40\tb\n1\t10\t20\ta\nchr1\t10\t20\tc\n").unwrap();
+        std::fs::write(&aliases_path, "chr1\t1\nchr2\t2\n").unwrap();
+        let cla = CommandLineArguments::try_parse_from(vec![
+            "Gipfelkreuzer".to_string(),
+            "sort".to_string(),
+            "--aliases".to_string(),
+            aliases_path.display().to_string(),
+            "--deduplicate".to_string(),
+            "-o".to_string(),
+            output_path.display().to_string(),
+            input_path.display().to_string(),
+        ]);
+        main_internal(cla, true).unwrap();
+        let sorted = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&input_path).unwrap();
+        std::fs::remove_file(&aliases_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        assert_eq!(sorted, "chr1\t10\t20\ta\nchr2\t30\t40\tb\n");
+    }
+
+    #[test]
+    fn test_main_internal_intersect_subtract() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_intersect_subtract.bed");
+        let run = |command: &str, options: &[&str]| {
+            let mut arguments = vec!["Gipfelkreuzer".to_string(), command.to_string()];
+            arguments.extend(options.iter().map(|option| option.to_string()));
+            arguments.extend([
+                "-o".to_string(),
+                output_path.display().to_string(),
+                test_resources()
+                    .join("input_test_main_internal_input_01.narrowPeak")
+                    .display()
+                    .to_string(),
+                test_resources()
+                    .join("input_test_main_internal_input_02.narrowPeak")
+                    .display()
+                    .to_string(),
+            ]);
+            main_internal(CommandLineArguments::try_parse_from(arguments), true).unwrap();
+            let output = std::fs::read_to_string(&output_path).unwrap();
+            std::fs::remove_file(&output_path).unwrap();
+            output
+        };
+        assert_eq!(
+            run("intersect", &[]),
+            "chr1\t800\t800\tconsensus_3\t0\t.\t0\t0\t0\t0\n\
+            chr1\t800\t999\tconsensus_0\t0\t.\t0\t0\t0\t99\n"
+        );
+        assert_eq!(
+            run("intersect", &["--whole", "--min-overlap", "0.1"]),
+            "chr1\t500\t1000\tconsensus_0\t0\t.\t0\t0\t0\t230\n"
+        );
+        assert_eq!(
+            run("subtract", &["--min-overlap", "0.1"]),
+            "chr1\t500\t799\tconsensus_0\t0\t.\t0\t0\t0\t230\n\
+            chr1\t600\t800\tconsensus_3\t0\t.\t0\t0\t0\t101\n\
+            chr1\t658\t739\tconsensus_2\t0\t.\t0\t0\t0\t37\n\
+            chr1\t678\t719\tconsensus_1\t0\t.\t0\t0\t0\t15\n\
+            chr1\t1000\t1000\tconsensus_0\t0\t.\t0\t0\t0\t0\n"
+        );
+        assert_eq!(
+            run("subtract", &["--whole"]),
+            "chr1\t658\t739\tconsensus_2\t0\t.\t0\t0\t0\t37\n\
+            chr1\t678\t719\tconsensus_1\t0\t.\t0\t0\t0\t15\n"
+        );
+    }
+
+    #[test]
+    fn test_main_internal_merge() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_merge.bed");
+        let input_a = test_output().join("test_main_internal_merge_a.bed");
+        let input_b = test_output().join("test_main_internal_merge_b.bed");
+        std::fs::write(&input_a, "chr1\t100\t200\nchr1\t150\t300\nchr2\t10\t20\n").unwrap();
+        std::fs::write(&input_b, "chr1\t290\t400\n").unwrap();
+        main_internal(
+            CommandLineArguments::try_parse_from([
+                "Gipfelkreuzer".to_string(),
+                "merge".to_string(),
+                "-o".to_string(),
+                output_path.display().to_string(),
+                input_a.display().to_string(),
+                input_b.display().to_string(),
+            ]),
+            true,
+        )
+        .unwrap();
+        let output = std::fs::read_to_string(&output_path).unwrap();
+        for path in [&output_path, &input_a, &input_b] {
+            std::fs::remove_file(path).unwrap();
+        }
+        assert_eq!(
+            output,
+            "chr1\t100\t400\tconsensus_0\t0\t.\t0\t0\t0\t150\n\
+            chr2\t10\t20\tconsensus_2\t0\t.\t0\t0\t0\t5\n"
+        );
+    }
+
+    #[test]
+    fn test_main_internal_validate() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let valid_path = test_output().join("test_main_internal_validate_valid.bed");
+        let invalid_path = test_output().join("test_main_internal_validate_invalid.bed");
+        let report_path = test_output().join("test_main_internal_validate_report.txt");
+        std::fs::write(&valid_path, "chr1\t100\t200\nchr1\t100\t200\n").unwrap();
+        std::fs::write(&invalid_path, "chr1\t100\tx\nchr1\t5\t1\n").unwrap();
+        let run = |path: &PathBuf, strict: bool| {
+            let mut arguments = vec![
+                "Gipfelkreuzer".to_string(),
+                "validate".to_string(),
+                "-o".to_string(),
+                report_path.display().to_string(),
+                valid_path.display().to_string(),
+                path.display().to_string(),
+            ];
+            if strict {
+                arguments.push("--strict".to_string());
+            }
+            let result = main_internal(CommandLineArguments::try_parse_from(arguments), true);
+            let report = std::fs::read_to_string(&report_path).unwrap();
+            (result, report)
+        };
+        let (valid, valid_report) = run(&valid_path, false);
+        let (strict, _) = run(&valid_path, true);
+        let (invalid, invalid_report) = run(&invalid_path, false);
+        for path in [&valid_path, &invalid_path, &report_path] {
+            std::fs::remove_file(path).unwrap();
+        }
+        // Duplicate records are only warnings.
+        assert!(valid.is_ok());
+        assert!(valid_report.contains("valid, 2 records, 0 errors, 1 warnings, sorted\n"));
+        assert!(valid_report.contains("  line 2: warning: The record duplicates"));
+        assert!(strict.is_err());
+        // All problems are reported at once.
+        let invalid = invalid.unwrap_err();
+        assert_eq!(invalid.error_type().exit_code(), 65);
+        assert!(invalid_report.contains("invalid, 2 records, 2 errors, 0 warnings, sorted\n"));
+        assert!(invalid_report.contains("  line 1: error: "));
+        assert!(invalid_report.contains("  line 2: error: "));
+    }
+
+    #[test]
+    fn test_consensus_command_arguments() {
+        let cla = CommandLineArguments::try_parse_with_config_from([
+            "Gipfelkreuzer",
+            "-l",
+            "debug",
+            "consensus",
+            "-b",
+            "3",
+            "a.bed",
+            "b.bed",
+        ])
+        .unwrap();
+        assert!(matches!(cla.command(), Some(Command::Consensus(_))));
+        assert_eq!(cla.log_level(), log::LevelFilter::Debug);
+        assert_eq!(cla.consensus_arguments().bed_output_columns(), 3);
+        assert_eq!(
+            cla.consensus_arguments().input_files(),
+            &vec![PathBuf::from("a.bed"), PathBuf::from("b.bed")]
+        );
+        // Arguments of consensus peak generation must follow the command.
+        assert!(
+            CommandLineArguments::try_parse_with_config_from([
+                "Gipfelkreuzer",
+                "-b",
+                "3",
+                "consensus",
+                "a.bed",
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_consensus_command_config() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let config_path = test_output().join("test_consensus_command_config.toml");
+        std::fs::write(
+            &config_path,
+            "input-files = [\"a.bed\", \"b.bed\"]\n\
+             min-peaks-per-consensus = 2\n\
+             log-level = \"debug\"\n\
+             [stats]\n\
+             input-files = [\"c.bed\"]\n\
+             [consensus]\n\
+             min-peaks-per-consensus = 3\n",
+        )
+        .unwrap();
+        let cla = CommandLineArguments::try_parse_with_config_from([
+            "Gipfelkreuzer".to_string(),
+            "--config".to_string(),
+            config_path.display().to_string(),
+            "consensus".to_string(),
+            "-b".to_string(),
+            "3".to_string(),
+        ])
+        .unwrap();
+        assert!(matches!(cla.command(), Some(Command::Consensus(_))));
+        assert_eq!(cla.log_level(), log::LevelFilter::Debug);
+        let consensus_arguments = cla.consensus_arguments();
+        assert_eq!(consensus_arguments.bed_output_columns(), 3);
+        // Parameters of the command table take precedence.
+        assert_eq!(
+            consensus_arguments
+                .algorithm_arguments()
+                .min_peaks_per_consensus(),
+            3
+        );
+        assert_eq!(
+            consensus_arguments.input_files(),
+            &vec![test_output().join("a.bed"), test_output().join("b.bed")]
+        );
+        // Other commands are configured by their table and the options applying to all commands.
+        let cla = CommandLineArguments::try_parse_with_config_from([
+            "Gipfelkreuzer".to_string(),
+            "--config".to_string(),
+            config_path.display().to_string(),
+            "stats".to_string(),
+        ])
+        .unwrap();
+        let Some(Command::Stats(stats_arguments)) = cla.command() else {
+            panic!("The stats command must be parsed.");
+        };
+        assert_eq!(stats_arguments.input_files(), &vec![test_output().join("c.bed")]);
+        assert_eq!(cla.log_level(), log::LevelFilter::Debug);
+        // Unknown tables and parameters of command tables are rejected.
+        for config in [
+            "[unknown]\nsummary = true\n",
+            "[stats]\nalgorithm = \"simple\"\n",
+        ] {
+            std::fs::write(&config_path, config).unwrap();
+            let error = CommandLineArguments::try_parse_with_config_from([
+                "Gipfelkreuzer".to_string(),
+                "--config".to_string(),
+                config_path.display().to_string(),
+                "stats".to_string(),
+                "c.bed".to_string(),
+            ])
+            .unwrap_err();
+            assert_eq!(error.error_type(), ApplicationErrorType::ConfigError);
+        }
+        std::fs::remove_file(&config_path).unwrap();
+    }
+
+    #[test]
+    fn test_main_internal_diff() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_diff.tsv");
+        let summary_path = test_output().join("test_main_internal_diff_summary.tsv");
+        let cla = CommandLineArguments::try_parse_from(vec![
+            "Gipfelkreuzer".to_string(),
+            "diff".to_string(),
+            "--tolerance".to_string(),
+            "5".to_string(),
+            "--summary".to_string(),
+            summary_path.display().to_string(),
+            "-o".to_string(),
+            output_path.display().to_string(),
+            test_resources()
+                .join("input_test_main_internal_input_01.narrowPeak")
+                .display()
+                .to_string(),
+            test_resources()
+                .join("input_test_main_internal_input_02.narrowPeak")
+                .display()
+                .to_string(),
+        ]);
+        main_internal(cla, true).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output_path).unwrap(),
+            "change\tchromosome\told_start\told_end\tnew_start\tnew_end\tstart_shift\tend_shift\n\
+            shifted\tchr1\t500\t1000\t800\t999\t300\t-1\n\
+            lost\tchr1\t600\t800\t.\t.\t.\t.\n\
+            lost\tchr1\t658\t739\t.\t.\t.\t.\n\
+            lost\tchr1\t678\t719\t.\t.\t.\t.\n\
+            gained\tchr1\t.\t.\t864\t918\t.\t.\n\
+            gained\tchr1\t.\t.\t898\t913\t.\t.\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&summary_path).unwrap(),
+            "change\tpeaks\ngained\t2\nlost\t3\nshifted\t1\nunchanged\t0\n"
+        );
+        std::fs::remove_file(output_path).unwrap();
+        std::fs::remove_file(summary_path).unwrap();
+    }
+
+    #[test]
+    fn test_main_internal_idr() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_idr.narrowPeak");
+        let replicate_paths = [
+            test_output().join("test_main_internal_idr_rep1.narrowPeak"),
+            test_output().join("test_main_internal_idr_rep2.narrowPeak"),
+        ];
+        // 40 peaks with concordant high signal followed by 40 peaks with discordant low signal.
+        let signal = |replicate: usize, index: usize| {
+            if index < 40 {
+                100.0 - index as f64 + ((index * (replicate + 3)) % 5) as f64
+            } else {
+                ((index * [37, 61][replicate]) % 40) as f64
+            }
+        };
+        for (replicate, path) in replicate_paths.iter().enumerate() {
+            let mut records: String = (0..80)
+                .map(|index| {
+                    let start = index * 1000 + replicate * 10;
+                    format!(
+                        "chr1\t{}\t{}\tpeak_{}\t0\t.\t{}\t-1\t-1\t50\n",
+                        start,
+                        start + 100,
+                        index,
+                        signal(replicate, index)
+                    )
+                })
+                .collect();
+            // Peaks only present in a single replicate are discarded.
+            let start = 100 + replicate * 1000;
+            records.push_str(&format!(
+                "chr2\t{}\t{}\tsingle\t0\t.\t500\t-1\t-1\t50\n",
+                start,
+                start + 100
+            ));
+            std::fs::write(path, records).unwrap();
+        }
+        let cla = CommandLineArguments::try_parse_from(vec![
+            "Gipfelkreuzer".to_string(),
+            "idr".to_string(),
+            "--idr-threshold".to_string(),
+            "0.05".to_string(),
+            "-o".to_string(),
+            output_path.display().to_string(),
+            replicate_paths[0].display().to_string(),
+            replicate_paths[1].display().to_string(),
+        ]);
+        main_internal(cla, true).unwrap();
+        let output = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(output_path).unwrap();
+        for path in replicate_paths {
+            std::fs::remove_file(path).unwrap();
+        }
+        let records: Vec<Vec<&str>> = output
+            .lines()
+            .map(|line| line.split('\t').collect())
+            .collect();
+        assert!(records.len() > 20 && records.len() < 50, "{}", output);
+        assert!(
+            records
+                .iter()
+                .all(|record| record.len() == 14 && record[0] == "chr1")
+        );
+        // The most reproducible peak spans the peaks of both replicates.
+        assert!(records.iter().any(|record| record[1..3] == ["0", "110"]));
+        // All concordant peaks pass the IDR threshold.
+        assert_eq!(
+            records
+                .iter()
+                .filter(|record| record[1].parse::<u64>().unwrap() < 40000)
+                .count(),
+            40
+        );
+    }
+
+    #[test]
+    fn test_main_internal_background() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_background.bed");
+        let run = |seed: &str| {
+            let cla = CommandLineArguments::try_parse_from(vec![
+                "Gipfelkreuzer".to_string(),
+                "background".to_string(),
+                "--chromosome-sizes".to_string(),
+                test_resources()
+                    .join("input_test_chromosome_sizes.tsv")
+                    .display()
+                    .to_string(),
+                "--blacklist".to_string(),
+                test_resources()
+                    .join("input_test_main_internal_input_02.narrowPeak")
+                    .display()
+                    .to_string(),
+                "--seed".to_string(),
+                seed.to_string(),
+                "-o".to_string(),
+                output_path.display().to_string(),
+                test_resources()
+                    .join("input_test_main_internal_input_01.narrowPeak")
+                    .display()
+                    .to_string(),
+            ]);
+            main_internal(cla, true).unwrap();
+            let output = std::fs::read_to_string(&output_path).unwrap();
+            std::fs::remove_file(&output_path).unwrap();
+            output
+        };
+        let output = run("3");
+        let mut lengths: Vec<u64> = output
+            .lines()
+            .map(|line| {
+                let fields: Vec<&str> = line.split('\t').collect();
+                assert_eq!(fields[0], "chr1");
+                let start: u64 = fields[1].parse().unwrap();
+                let end: u64 = fields[2].parse().unwrap();
+                assert!(end < 2000);
+                assert!(end < 500 || start > 1000);
+                end + 1 - start
+            })
+            .collect();
+        lengths.sort_unstable();
+        assert_eq!(lengths, vec![42, 82, 201, 501]);
+        assert_eq!(run("3"), output);
+        assert_ne!(run("4"), output);
+    }
+
+    #[test]
+    fn test_main_internal_liftover() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_liftover.bed");
+        let unmapped_path = test_output().join("test_main_internal_liftover_unmapped.bed");
+        let cla = CommandLineArguments::try_parse_from(vec![
+            "Gipfelkreuzer".to_string(),
+            "liftover".to_string(),
+            "--chain".to_string(),
+            test_resources()
+                .join("input_test_main_internal_liftover.chain")
+                .display()
+                .to_string(),
+            "--unmapped".to_string(),
+            unmapped_path.display().to_string(),
+            "-o".to_string(),
+            output_path.display().to_string(),
+            test_resources()
+                .join("input_test_main_internal_input_01.narrowPeak")
+                .display()
+                .to_string(),
+        ]);
+        main_internal(cla, true).unwrap();
+        let lifted = std::fs::read_to_string(&output_path).unwrap();
+        let unmapped = std::fs::read_to_string(&unmapped_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        std::fs::remove_file(&unmapped_path).unwrap();
+        assert_eq!(
+            lifted,
+            "chr2\t10600\t10800\tconsensus_3\t0\t.\t0\t0\t0\t101\n\
+            chr2\t10658\t10739\tconsensus_2\t0\t.\t0\t0\t0\t37\n\
+            chr2\t10678\t10719\tconsensus_1\t0\t.\t0\t0\t0\t15\n"
+        );
+        // The broad peak is only partially aligned.
+        assert_eq!(unmapped, "chr1\t500\t1000\tconsensus_0\t0\t.\t0\t0\t0\t230\n");
+    }
+
+    #[test]
+    fn test_main_internal_serve_invalid_address() {
+        let cla = CommandLineArguments::try_parse_from(vec![
+            "Gipfelkreuzer".to_string(),
+            "serve".to_string(),
+            "--address".to_string(),
+            "invalid address".to_string(),
+            test_resources()
+                .join("input_test_main_internal_input_01.narrowPeak")
+                .display()
+                .to_string(),
+        ]);
+        assert!(main_internal(cla, true).is_err());
+    }
+
+    #[test]
+    fn test_main_internal_watch_arguments() {
+        let cla = CommandLineArguments::try_parse_from(vec![
+            "Gipfelkreuzer",
+            "--watch",
+            "peaks",
+            "--watch-interval",
+            "1",
+        ])
+        .unwrap();
+        assert_eq!(cla.consensus_arguments().watch(), &Some(PathBuf::from("peaks")));
+        assert_eq!(cla.consensus_arguments().watch_interval(), 1);
+        assert!(
+            CommandLineArguments::try_parse_from(vec![
+                "Gipfelkreuzer",
+                "--watch-interval",
+                "1",
+                "input.bed"
+            ])
+            .is_err()
+        );
+        assert!(CommandLineArguments::try_parse_from(vec!["Gipfelkreuzer"]).is_err());
+    }
+
+    #[test]
+    fn test_main_internal_watch_missing_directory() {
+        let cla = CommandLineArguments::try_parse_from(vec![
+            "Gipfelkreuzer".to_string(),
+            "--watch".to_string(),
+            test_output()
+                .join("test_main_internal_watch_missing_directory")
+                .display()
+                .to_string(),
+        ]);
+        assert!(main_internal(cla, true).is_err());
+    }
+
+    #[test]
+    fn test_error_report_arguments() {
+        let cla = CommandLineArguments::try_parse_from(vec![
+            "Gipfelkreuzer",
+            "--error-report",
+            "report.json",
+            "input.bed",
+        ])
+        .unwrap();
+        assert_eq!(cla.error_report(), &Some(PathBuf::from("report.json")));
+        assert!(!cla.consensus_arguments().strict_warnings());
+        assert!(!cla.error_json());
+        let cla = CommandLineArguments::try_parse_from(vec![
+            "Gipfelkreuzer",
+            "--error-json",
+            "input.bed",
+        ])
+        .unwrap();
+        assert!(cla.error_json());
+        let cla = CommandLineArguments::try_parse_from(vec![
+            "Gipfelkreuzer",
+            "--strict-warnings",
+            "input.bed",
+        ])
+        .unwrap();
+        assert!(cla.consensus_arguments().strict_warnings());
+        // The report file is determined even if the arguments are invalid.
+        assert_eq!(
+            CommandLineArguments::error_report_from([
+                "Gipfelkreuzer",
+                "--bogus",
+                "stats",
+                "--error-report",
+                "report.json",
+            ]),
+            Some(PathBuf::from("report.json"))
+        );
+        assert!(CommandLineArguments::error_json_from([
+            "Gipfelkreuzer",
+            "--error-json",
+            "--bogus",
+            "a.bed"
+        ]));
+        assert!(!CommandLineArguments::error_json_from(["Gipfelkreuzer", "--", "--error-json"]));
+    }
+
+    #[test]
+    fn test_io_retry_arguments() {
+        let cla = CommandLineArguments::try_parse_from(vec!["Gipfelkreuzer", "input.bed"]).unwrap();
+        assert_eq!(cla.io_retries(), 3);
+        assert_eq!(cla.io_retry_delay(), 500);
+        let cla = CommandLineArguments::try_parse_from(vec![
+            "Gipfelkreuzer",
+            "--io-retries",
+            "0",
+            "--io-retry-delay",
+            "20",
+            "input.bed",
+        ])
+        .unwrap();
+        assert_eq!(cla.io_retries(), 0);
+        assert_eq!(cla.io_retry_delay(), 20);
+    }
+
+    #[test]
+    fn test_main_internal_collect_errors() {
+        let input_files = [
+            "input_test_invalid_start.narrowPeak",
+            "input_test_invalid_end.narrowPeak",
+            "input_test_main_internal_input_01.narrowPeak",
+        ];
+        let mut arguments = vec!["Gipfelkreuzer".to_string(), "--collect-errors".to_string()];
+        arguments.push("10".to_string());
+        arguments.extend(
+            input_files
+                .iter()
+                .map(|file| test_resources().join(file).display().to_string()),
+        );
+        let cla = CommandLineArguments::try_parse_from(arguments).unwrap();
+        assert_eq!(cla.consensus_arguments().collect_errors(), Some(10));
+        let error = main_internal(Ok::<_, ApplicationError>(cla), true).unwrap_err();
+        assert_eq!(error.related().len(), 2);
+        assert!(
+            CommandLineArguments::try_parse_from(vec![
+                "Gipfelkreuzer",
+                "--collect-errors",
+                "0",
+                "input.bed"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_main_internal_assembly_check() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_main_internal_assembly_check.bed");
+        let arguments = |assembly_check: &str| {
+            vec![
+                "Gipfelkreuzer".to_string(),
+                "--assembly-check".to_string(),
+                assembly_check.to_string(),
+                "-o".to_string(),
+                output_path.display().to_string(),
+                test_resources()
+                    .join("input_test_assembly_hg19.narrowPeak")
+                    .display()
+                    .to_string(),
+                test_resources()
+                    .join("input_test_assembly_hg38.narrowPeak")
+                    .display()
+                    .to_string(),
+            ]
+        };
+        let cla = CommandLineArguments::try_parse_from(arguments("error")).unwrap();
+        let error = main_internal(Ok::<_, ApplicationError>(cla), true).unwrap_err();
+        assert_eq!(error.error_type(), ApplicationErrorType::ValidationError);
+        assert_eq!(error.related().len(), 1);
+        assert!(!output_path.exists());
+        let cla = CommandLineArguments::try_parse_from(arguments("warn")).unwrap();
+        main_internal(Ok::<_, ApplicationError>(cla), true).unwrap();
+        assert!(output_path.exists());
+        std::fs::remove_file(&output_path).unwrap();
     }
 
     /// Runs a standardised test for the internal ```main``` function.
@@ -386,15 +3681,21 @@ mod tests {
         let cla = CommandLineArguments::try_parse_from(final_cla);
         let bed_fields = cla
             .as_ref()
-            .map(|arguments| arguments.bed_output_columns())
+            .map(|arguments| arguments.consensus_arguments().bed_output_columns())
             .unwrap();
         assert!(main_internal(cla, true).is_ok());
         assert!(output_path.exists());
 
         let output_file = BufReader::new(File::open(&output_path).unwrap());
+        // All peaks of the narrowPeak test input files share the same statistical values.
+        let input_scores =
+            PeakScores::new(Some(358.0), Some(16.7471), Some(38.4652), Some(35.8377));
         let expected_output_lines: Vec<String> = expected_output_peaks
             .iter()
-            .map(|(chromosome, peak)| peak_to_bed_record_line(peak, chromosome, bed_fields))
+            .map(|(chromosome, peak)| {
+                input_scores
+                    .apply_to_record_line(&peak_to_bed_record_line(peak, chromosome, bed_fields))
+            })
             .collect();
         let mut number_lines_in_output = 0;
         for line in output_file.lines() {
@@ -0,0 +1,458 @@
+//! This module writes a run manifest recording the size and SHA-256 checksum of every input
+//! and output file together with the parameters of the run, so data management systems can
+//! register the run and detect stale or tampered artifacts. A recorded manifest can be used to
+//! verify that a re-run uses byte-identical input files. A short run identifier derived from
+//! the tool version, the parameters and the input checksums allows outputs of different
+//! parameterisations of the same inputs to coexist on disk.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use getset::{CopyGetters, Getters};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::{ApplicationError, ApplicationErrorType},
+    retry::{Retrying, retry},
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "manifest";
+
+/// The size of the chunks files are read in to calculate their checksums.
+const CHECKSUM_BUFFER_SIZE: usize = 64 * 1024;
+/// The number of hexadecimal digits of a run identifier.
+pub const RUN_ID_LENGTH: usize = 12;
+
+/// The size and checksum of a file.
+#[derive(Serialize, Deserialize, CopyGetters, Getters, Clone, PartialEq, Eq, Debug)]
+pub struct FileEntry {
+    /// The path of the file.
+    #[getset(get = "pub")]
+    path: PathBuf,
+    /// The size of the file in bytes.
+    #[getset(get_copy = "pub")]
+    size: u64,
+    /// The hexadecimal SHA-256 checksum of the file.
+    #[getset(get = "pub")]
+    sha256: String,
+}
+
+impl FileEntry {
+    /// Reads a file and calculates its size and checksum.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - the path of the file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read.
+    pub fn from_file<T: AsRef<Path>>(path: T) -> Result<Self, ApplicationError> {
+        let path = path.as_ref();
+        let description = format!("Reading {}", path.display());
+        let read_error = |err: std::io::Error| {
+            ApplicationError::from(err).chain(format!(
+                "The checksum of the file \"{}\" could not be calculated.",
+                path.display()
+            ))
+        };
+        let file = retry(&description, || File::open(path)).map_err(read_error)?;
+        let mut reader = Retrying::new(file, &description);
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0; CHECKSUM_BUFFER_SIZE];
+        let mut size = 0;
+        loop {
+            let read_bytes = reader.read(&mut buffer).map_err(read_error)?;
+            if read_bytes == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read_bytes]);
+            size += read_bytes as u64;
+        }
+        let sha256 = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        Ok(Self {
+            path: path.to_path_buf(),
+            size,
+            sha256,
+        })
+    }
+}
+
+/// Creates the entries of the specified files. Directories are replaced by all files they
+/// contain recursively in lexicographical order.
+///
+/// # Parameters
+///
+/// * `paths` - the paths of the files and directories
+///
+/// # Errors
+///
+/// Returns an error if a file or directory cannot be read.
+pub fn file_entries<T: AsRef<Path>>(paths: &[T]) -> Result<Vec<FileEntry>, ApplicationError> {
+    let mut entries = Vec::new();
+    for path in paths {
+        let path = path.as_ref();
+        if path.is_dir() {
+            let mut children = std::fs::read_dir(path)
+                .and_then(|directory| {
+                    directory
+                        .map(|entry| entry.map(|entry| entry.path()))
+                        .collect::<Result<Vec<PathBuf>, _>>()
+                })
+                .map_err(|err| {
+                    ApplicationError::from(err)
+                        .chain(format!("The directory \"{}\" could not be read.", path.display()))
+                })?;
+            children.sort_unstable();
+            entries.extend(file_entries(&children)?);
+        } else {
+            entries.push(FileEntry::from_file(path)?);
+        }
+    }
+    Ok(entries)
+}
+
+/// The inventory of a single run.
+#[derive(Serialize, Deserialize, Getters, Clone, PartialEq, Eq, Debug)]
+#[getset(get = "pub")]
+pub struct RunManifest {
+    /// The version of the tool.
+    version: String,
+    /// The identifier of the run as derived by [`run_identifier`].
+    #[serde(default)]
+    run_id: String,
+    /// The parameters of the run.
+    parameters: BTreeMap<String, String>,
+    /// The input files.
+    inputs: Vec<FileEntry>,
+    /// The output files.
+    outputs: Vec<FileEntry>,
+}
+
+impl RunManifest {
+    /// Creates the manifest of a run by calculating the checksums of all output files.
+    ///
+    /// # Parameters
+    ///
+    /// * `parameters` - the parameters of the run
+    /// * `inputs` - the entries of the input files as created by [`file_entries`]
+    /// * `outputs` - the output files and directories
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any output file cannot be read.
+    pub fn new<T: AsRef<Path>>(
+        parameters: BTreeMap<String, String>,
+        inputs: Vec<FileEntry>,
+        outputs: &[T],
+    ) -> Result<Self, ApplicationError> {
+        let manifest = Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            run_id: run_identifier(&parameters, &inputs),
+            parameters,
+            inputs,
+            outputs: file_entries(outputs)?,
+        };
+        log::debug!(
+            target: LOG_TARGET,
+            "Created the manifest of {} input and {} output files.",
+            manifest.inputs.len(),
+            manifest.outputs.len()
+        );
+        Ok(manifest)
+    }
+
+    /// Reads a run manifest previously written by [`write_manifest`].
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - the path of the JSON file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a valid manifest.
+    pub fn from_file<T: AsRef<Path>>(path: T) -> Result<Self, ApplicationError> {
+        let path = path.as_ref();
+        let content = std::fs::read(path).map_err(|err| {
+            ApplicationError::from(err)
+                .chain(format!("The run manifest \"{}\" could not be read.", path.display()))
+        })?;
+        serde_json::from_slice(&content).map_err(|err| {
+            ApplicationError::from(err)
+                .chain(format!("The run manifest \"{}\" is invalid.", path.display()))
+        })
+    }
+
+    /// Verifies that the specified input files are byte-identical to the input files recorded
+    /// in the manifest. The files are compared by their order and checksum, so verification
+    /// succeeds if the inputs have been moved or renamed.
+    ///
+    /// # Parameters
+    ///
+    /// * `actual` - the entries of the input files in the order of the original run
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any file differs from the recorded input.
+    pub fn verify_inputs(&self, actual: &[FileEntry]) -> Result<(), ApplicationError> {
+        let mut mismatches: Vec<ApplicationError> = actual
+            .iter()
+            .zip(&self.inputs)
+            .filter(|(actual, expected)| actual.sha256() != expected.sha256())
+            .map(|(actual, expected)| {
+                ApplicationError::new(
+                    ApplicationErrorType::ValidationError,
+                    format!(
+                        "The input file \"{}\" with checksum {} differs from the recorded \
+                        input file \"{}\" with checksum {}.",
+                        actual.path().display(),
+                        actual.sha256(),
+                        expected.path().display(),
+                        expected.sha256()
+                    ),
+                )
+            })
+            .collect();
+        if actual.len() != self.inputs.len() {
+            mismatches.push(ApplicationError::new(
+                ApplicationErrorType::ValidationError,
+                format!(
+                    "{} input files were specified, but {} input files are recorded.",
+                    actual.len(),
+                    self.inputs.len()
+                ),
+            ));
+        }
+        if mismatches.is_empty() {
+            log::info!(
+                target: LOG_TARGET,
+                "Verified the checksums of {} input files.",
+                actual.len()
+            );
+            return Ok(());
+        }
+        Err(ApplicationError::new(
+            ApplicationErrorType::ValidationError,
+            "The input files are not identical to the input files recorded in the run manifest.",
+        )
+        .with_related(mismatches)
+        .with_hint(
+            "Specify the same input files in the same order as in the recorded run or create \
+            a new manifest with \"--manifest\".",
+        ))
+    }
+}
+
+/// Derives a short identifier of a run from the tool version, the parameters and the
+/// checksums of the input files. Runs with identical inputs and parameters share the same
+/// identifier, while the paths of the input files do not affect it.
+///
+/// # Parameters
+///
+/// * `parameters` - the parameters of the run
+/// * `inputs` - the entries of the input files
+pub fn run_identifier(parameters: &BTreeMap<String, String>, inputs: &[FileEntry]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION"));
+    hasher.update("\n");
+    for (key, value) in parameters {
+        hasher.update(format!("{}={}\n", key, value));
+    }
+    for input in inputs {
+        hasher.update(input.sha256());
+        hasher.update("\n");
+    }
+    let mut run_id = format!("{:x}", hasher.finalize());
+    run_id.truncate(RUN_ID_LENGTH);
+    run_id
+}
+
+/// Inserts the run identifier before the extension of the file name,
+/// e.g. `consensus.bed` becomes `consensus.<run_id>.bed`.
+///
+/// # Parameters
+///
+/// * `path` - the path of the file
+/// * `run_id` - the run identifier
+pub fn with_run_identifier<T: AsRef<Path>>(path: T, run_id: &str) -> PathBuf {
+    let path = path.as_ref();
+    let mut file_name = path
+        .file_stem()
+        .map(|stem| stem.to_os_string())
+        .unwrap_or_default();
+    file_name.push(".");
+    file_name.push(run_id);
+    if let Some(extension) = path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+    path.with_file_name(file_name)
+}
+
+/// Writes the run manifest as JSON file.
+///
+/// # Parameters
+///
+/// * `path` - the path of the JSON file
+/// * `manifest` - the run manifest
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be written.
+pub fn write_manifest<T: AsRef<Path>>(
+    path: T,
+    manifest: &RunManifest,
+) -> Result<(), ApplicationError> {
+    log::info!(target: LOG_TARGET, "Writing the run manifest to {}.", path.as_ref().display());
+    let mut manifest_json = serde_json::to_vec_pretty(manifest)?;
+    manifest_json.push(b'\n');
+    File::create(&path)
+        .and_then(|mut file| file.write_all(&manifest_json))
+        .map_err(|err| {
+            ApplicationError::from(err).chain(format!(
+                "The run manifest \"{}\" could not be written.",
+                path.as_ref().display()
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::test_output;
+
+    use super::*;
+
+    #[test]
+    fn test_file_entry_from_file() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let path = test_output().join("test_file_entry_from_file.txt");
+        std::fs::write(&path, "abc").unwrap();
+        let entry = FileEntry::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(entry.size(), 3);
+        assert_eq!(
+            entry.sha256(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert!(FileEntry::from_file(&path).is_err());
+    }
+
+    #[test]
+    fn test_run_manifest() {
+        let directory = test_output().join("test_run_manifest");
+        std::fs::create_dir_all(directory.join("nested")).unwrap();
+        std::fs::write(directory.join("b.txt"), "").unwrap();
+        std::fs::write(directory.join("nested").join("a.txt"), "abc").unwrap();
+        let input = test_output().join("test_run_manifest_input.txt");
+        std::fs::write(&input, "abc").unwrap();
+        let parameters = BTreeMap::from([("algorithm".to_string(), "simple".to_string())]);
+        let manifest =
+            RunManifest::new(parameters.clone(), file_entries(&[&input]).unwrap(), &[&directory])
+                .unwrap();
+        let manifest_path = test_output().join("test_run_manifest.json");
+        write_manifest(&manifest_path, &manifest).unwrap();
+        let manifest_json: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&manifest_path).unwrap();
+        assert_eq!(manifest.parameters(), &parameters);
+        assert_eq!(manifest.run_id(), &run_identifier(&parameters, manifest.inputs()));
+        assert_eq!(manifest.inputs().len(), 1);
+        let outputs: Vec<&PathBuf> = manifest.outputs().iter().map(FileEntry::path).collect();
+        assert_eq!(
+            outputs,
+            vec![
+                &directory.join("b.txt"),
+                &directory.join("nested").join("a.txt")
+            ]
+        );
+        assert_eq!(manifest_json["parameters"]["algorithm"], "simple");
+        assert_eq!(manifest_json["inputs"][0]["size"], 3);
+        assert_eq!(manifest_json["outputs"][0]["sha256"], manifest.outputs()[0].sha256().as_str());
+    }
+
+    #[test]
+    fn test_run_manifest_verify_inputs() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let first = test_output().join("test_run_manifest_verify_inputs_1.txt");
+        let second = test_output().join("test_run_manifest_verify_inputs_2.txt");
+        std::fs::write(&first, "abc").unwrap();
+        std::fs::write(&second, "def").unwrap();
+        let entries = |paths: &[&PathBuf]| file_entries(paths).unwrap();
+        let manifest =
+            RunManifest::new(BTreeMap::new(), entries(&[&first, &second]), &Vec::<PathBuf>::new())
+                .unwrap();
+        let manifest_path = test_output().join("test_run_manifest_verify_inputs.json");
+        write_manifest(&manifest_path, &manifest).unwrap();
+        let manifest = RunManifest::from_file(&manifest_path).unwrap();
+        assert!(manifest.verify_inputs(&entries(&[&first, &second])).is_ok());
+        let error = manifest
+            .verify_inputs(&entries(&[&second, &first]))
+            .unwrap_err();
+        assert_eq!(error.error_type(), ApplicationErrorType::ValidationError);
+        assert_eq!(error.related().len(), 2);
+        assert_eq!(
+            manifest
+                .verify_inputs(&entries(&[&first]))
+                .unwrap_err()
+                .related()
+                .len(),
+            1
+        );
+        std::fs::write(&second, "changed").unwrap();
+        assert_eq!(
+            manifest
+                .verify_inputs(&entries(&[&first, &second]))
+                .unwrap_err()
+                .related()
+                .len(),
+            1
+        );
+        std::fs::remove_file(&first).unwrap();
+        std::fs::remove_file(&second).unwrap();
+        std::fs::remove_file(&manifest_path).unwrap();
+    }
+
+    #[test]
+    fn test_run_identifier() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let first = test_output().join("test_run_identifier_1.txt");
+        let second = test_output().join("test_run_identifier_2.txt");
+        std::fs::write(&first, "abc").unwrap();
+        std::fs::write(&second, "abc").unwrap();
+        let first_entries = file_entries(&[&first]).unwrap();
+        let second_entries = file_entries(&[&second]).unwrap();
+        std::fs::remove_file(&first).unwrap();
+        std::fs::remove_file(&second).unwrap();
+        let parameters = BTreeMap::from([("algorithm".to_string(), "simple".to_string())]);
+        let run_id = run_identifier(&parameters, &first_entries);
+        assert_eq!(run_id.len(), RUN_ID_LENGTH);
+        assert!(run_id.chars().all(|digit| digit.is_ascii_hexdigit()));
+        assert_eq!(run_identifier(&parameters, &second_entries), run_id);
+        assert_ne!(run_identifier(&BTreeMap::new(), &first_entries), run_id);
+        assert_ne!(run_identifier(&parameters, &[]), run_id);
+    }
+
+    #[test]
+    fn test_with_run_identifier() {
+        assert_eq!(
+            with_run_identifier("results/consensus.bed", "0123456789ab"),
+            PathBuf::from("results/consensus.0123456789ab.bed")
+        );
+        assert_eq!(
+            with_run_identifier("consensus", "0123456789ab"),
+            PathBuf::from("consensus.0123456789ab")
+        );
+    }
+}
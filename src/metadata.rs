@@ -0,0 +1,317 @@
+//! This module writes run metadata in the formats expected by
+//! [nf-core](https://nf-co.re/docs/guidelines/components/modules) modules and
+//! [MultiQC](https://docs.seqera.io/multiqc/custom_content) reports.
+
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use serde_json::json;
+
+use crate::error::ApplicationError;
+
+/// The log target of this module.
+const LOG_TARGET: &str = "metadata";
+
+/// The name of the tool as reported in the metadata.
+pub const TOOL_NAME: &str = "gipfelkreuzer";
+
+/// The file name of the software versions file.
+pub const VERSIONS_FILE_NAME: &str = "versions.yml";
+
+/// The file name of the process trace file.
+pub const PROCESS_TRACE_FILE_NAME: &str = "gipfelkreuzer_trace.json";
+
+/// A summary of a single consensus peak generation run.
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct ProcessTrace {
+    /// The name of the process the tool is run by.
+    pub process: String,
+    /// The name of the tool.
+    pub tool: String,
+    /// The version of the tool.
+    pub version: String,
+    /// The consensus peak algorithm.
+    pub algorithm: String,
+    /// The input files.
+    pub input_files: Vec<PathBuf>,
+    /// The output file.
+    pub output_file: PathBuf,
+    /// The number of parsed raw peaks.
+    pub raw_peaks: usize,
+    /// The number of generated consensus peaks.
+    pub consensus_peaks: usize,
+    /// The duration of the run in seconds.
+    pub duration_seconds: f64,
+}
+
+/// The file name suffix MultiQC detects custom content files by.
+pub const MULTIQC_FILE_SUFFIX: &str = "_mqc.json";
+
+/// The name of the MultiQC table row summarising the consensus peaks.
+pub const MULTIQC_CONSENSUS_ROW: &str = "consensus";
+
+/// The metrics of a single consensus peak generation run reported to MultiQC.
+#[derive(Clone, PartialEq, Debug)]
+pub struct RunMetrics {
+    /// The sample names and numbers of raw peaks of the input files.
+    pub inputs: Vec<(String, usize)>,
+    /// The number of generated consensus peaks.
+    pub consensus_peaks: usize,
+    /// The minimum number of raw peaks required to form a consensus peak.
+    pub min_peaks_per_consensus: usize,
+    /// The number of consensus peaks removed by merging near-duplicates.
+    pub merged_duplicates: usize,
+    /// The sample names and fractions of reads in consensus peaks of the BAM files, if calculated.
+    pub frip: Vec<(String, f64)>,
+}
+
+/// Returns the sample name of a file as the file name without extension,
+/// so MultiQC can match the metrics to the samples of other tools.
+///
+/// # Parameters
+///
+/// * `path` - the path of the file
+pub fn multiqc_sample_name<T: AsRef<Path>>(path: T) -> String {
+    path.as_ref()
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.as_ref().display().to_string())
+}
+
+/// Creates a MultiQC custom content table of the run metrics. Each input file and each BAM file
+/// is reported as a sample and the consensus peaks are summarised in the row
+/// [`MULTIQC_CONSENSUS_ROW`].
+///
+/// # Parameters
+///
+/// * `metrics` - the metrics of the run
+pub fn multiqc_custom_content(metrics: &RunMetrics) -> serde_json::Value {
+    let mut data = serde_json::Map::new();
+    for (sample, raw_peaks) in &metrics.inputs {
+        data.insert(sample.clone(), json!({ "raw_peaks": raw_peaks }));
+    }
+    for (sample, frip) in &metrics.frip {
+        let row = data.entry(sample.clone()).or_insert_with(|| json!({}));
+        row["frip"] = json!(frip);
+    }
+    data.insert(
+        MULTIQC_CONSENSUS_ROW.to_string(),
+        json!({
+            "raw_peaks": metrics.inputs.iter().map(|(_, raw_peaks)| raw_peaks).sum::<usize>(),
+            "consensus_peaks": metrics.consensus_peaks,
+            "min_peaks_per_consensus": metrics.min_peaks_per_consensus,
+            "merged_duplicates": metrics.merged_duplicates,
+        }),
+    );
+    json!({
+        "id": TOOL_NAME,
+        "section_name": "Gipfelkreuzer",
+        "description": "Raw peaks per input file and the resulting consensus peaks.",
+        "plot_type": "table",
+        "pconfig": {
+            "id": format!("{}_table", TOOL_NAME),
+            "title": "Gipfelkreuzer: consensus peaks",
+        },
+        "headers": {
+            "raw_peaks": { "title": "Raw peaks", "format": "{:,.0f}" },
+            "consensus_peaks": { "title": "Consensus peaks", "format": "{:,.0f}" },
+            "min_peaks_per_consensus": {
+                "title": "Min. support",
+                "description": "The minimum number of raw peaks required to form a consensus peak",
+                "format": "{:,.0f}",
+            },
+            "merged_duplicates": {
+                "title": "Merged duplicates",
+                "description": "The number of consensus peaks removed by merging near-duplicates",
+                "format": "{:,.0f}",
+            },
+            "frip": {
+                "title": "FRiP",
+                "description": "The fraction of reads in consensus peaks",
+                "min": 0,
+                "max": 1,
+                "format": "{:,.3f}",
+            },
+        },
+        "data": data,
+    })
+}
+
+/// Writes the run metrics as MultiQC custom content JSON file.
+/// MultiQC only detects the file automatically if its name ends with [`MULTIQC_FILE_SUFFIX`].
+///
+/// # Parameters
+///
+/// * `path` - the path of the JSON file
+/// * `metrics` - the metrics of the run
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be written.
+pub fn write_multiqc_metrics<T: AsRef<Path>>(
+    path: T,
+    metrics: &RunMetrics,
+) -> Result<(), ApplicationError> {
+    if !path
+        .as_ref()
+        .to_string_lossy()
+        .ends_with(MULTIQC_FILE_SUFFIX)
+    {
+        log::warn!(
+            target: LOG_TARGET,
+            "The MultiQC metrics file \"{}\" does not end with \"{}\" and will not be detected \
+            by MultiQC automatically.",
+            path.as_ref().display(),
+            MULTIQC_FILE_SUFFIX
+        );
+    }
+    let mut metrics_json = serde_json::to_vec_pretty(&multiqc_custom_content(metrics))?;
+    metrics_json.push(b'\n');
+    write_metadata_file(path.as_ref().to_path_buf(), &metrics_json)
+}
+
+/// Creates the content of an nf-core `versions.yml` file for the specified process.
+///
+/// # Parameters
+///
+/// * `process_name` - the name of the process the tool is run by
+pub fn versions_yml(process_name: &str) -> String {
+    format!(
+        "\"{}\":\n    {}: {}\n",
+        process_name.replace('\\', "\\\\").replace('"', "\\\""),
+        TOOL_NAME,
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+/// Writes the nf-core `versions.yml` and the process trace JSON to the specified directory.
+///
+/// # Parameters
+///
+/// * `directory` - the directory to write the metadata files to
+/// * `trace` - the process trace of the run
+///
+/// # Errors
+///
+/// Returns an error if the directory or files cannot be created.
+pub fn write_nf_core_metadata<T: AsRef<Path>>(
+    directory: T,
+    trace: &ProcessTrace,
+) -> Result<(), ApplicationError> {
+    std::fs::create_dir_all(&directory).map_err(|err| {
+        ApplicationError::from(err).chain(format!(
+            "The metadata directory \"{}\" could not be created.",
+            directory.as_ref().display()
+        ))
+    })?;
+    write_metadata_file(
+        directory.as_ref().join(VERSIONS_FILE_NAME),
+        versions_yml(&trace.process).as_bytes(),
+    )?;
+    let mut trace_json = serde_json::to_vec_pretty(trace)?;
+    trace_json.push(b'\n');
+    write_metadata_file(directory.as_ref().join(PROCESS_TRACE_FILE_NAME), &trace_json)
+}
+
+/// Writes the content of a single metadata file.
+///
+/// # Parameters
+///
+/// * `path` - the path of the metadata file
+/// * `content` - the content to write
+fn write_metadata_file(path: PathBuf, content: &[u8]) -> Result<(), ApplicationError> {
+    File::create(&path)
+        .and_then(|mut file| file.write_all(content))
+        .map_err(|err| {
+            ApplicationError::from(err)
+                .chain(format!("The metadata file \"{}\" could not be written.", path.display()))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::test_output;
+
+    use super::*;
+
+    #[test]
+    fn test_versions_yml() {
+        assert_eq!(
+            versions_yml("NFCORE_ATACSEQ:GIPFELKREUZER"),
+            format!(
+                "\"NFCORE_ATACSEQ:GIPFELKREUZER\":\n    gipfelkreuzer: {}\n",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+
+    #[test]
+    fn test_write_nf_core_metadata() {
+        let directory = test_output().join("test_write_nf_core_metadata");
+        let trace = ProcessTrace {
+            process: "GIPFELKREUZER".to_string(),
+            tool: TOOL_NAME.to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            algorithm: "gipfelkreuzer".to_string(),
+            input_files: vec!["a.narrowPeak".into(), "b.narrowPeak".into()],
+            output_file: "consensus.bed".into(),
+            raw_peaks: 10,
+            consensus_peaks: 4,
+            duration_seconds: 0.5,
+        };
+        write_nf_core_metadata(&directory, &trace).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(directory.join(VERSIONS_FILE_NAME)).unwrap(),
+            versions_yml("GIPFELKREUZER")
+        );
+        let trace_json: serde_json::Value = serde_json::from_slice(
+            &std::fs::read(directory.join(PROCESS_TRACE_FILE_NAME)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(trace_json["process"], "GIPFELKREUZER");
+        assert_eq!(trace_json["raw_peaks"], 10);
+        assert_eq!(trace_json["consensus_peaks"], 4);
+        assert_eq!(trace_json["input_files"][1], "b.narrowPeak");
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_multiqc_sample_name() {
+        assert_eq!(multiqc_sample_name("data/sample_1.narrowPeak"), "sample_1");
+        assert_eq!(multiqc_sample_name("sample_2"), "sample_2");
+    }
+
+    #[test]
+    fn test_write_multiqc_metrics() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let path = test_output().join("test_write_multiqc_metrics_mqc.json");
+        let metrics = RunMetrics {
+            inputs: vec![("a".to_string(), 10), ("b".to_string(), 5)],
+            consensus_peaks: 4,
+            min_peaks_per_consensus: 2,
+            merged_duplicates: 1,
+            frip: vec![("a".to_string(), 0.25)],
+        };
+        write_multiqc_metrics(&path, &metrics).unwrap();
+        let metrics_json: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(metrics_json["id"], TOOL_NAME);
+        assert_eq!(metrics_json["plot_type"], "table");
+        assert_eq!(metrics_json["data"]["a"], json!({ "raw_peaks": 10, "frip": 0.25 }));
+        assert_eq!(metrics_json["data"]["b"], json!({ "raw_peaks": 5 }));
+        assert_eq!(
+            metrics_json["data"][MULTIQC_CONSENSUS_ROW],
+            json!({
+                "raw_peaks": 15,
+                "consensus_peaks": 4,
+                "min_peaks_per_consensus": 2,
+                "merged_duplicates": 1,
+            })
+        );
+    }
+}
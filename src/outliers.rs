@@ -0,0 +1,368 @@
+//! This module flags consensus peaks whose length, support or summit spread are extreme
+//! relative to all consensus peaks, as such regions are usually artefacts worth reviewing
+//! or blacklisting.
+//!
+//! Extreme values are detected with the modified z-score of
+//! [Iglewicz and Hoaglin](https://doi.org/10.1080/00401706.1993.10485036), which is based on the
+//! median and the median absolute deviation and thus robust against the outliers themselves.
+
+use std::{fmt::Display, io::Write};
+
+use getset::{CopyGetters, Getters};
+
+use crate::{
+    chromosome::Chromosome,
+    concordance::{PeakSummitConcordance, SummitConcordance},
+    error::{ApplicationError, ApplicationErrorType},
+    peaks::PeakData,
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "outliers";
+
+/// The default modified z-score above which a value is considered extreme.
+pub const DEFAULT_OUTLIER_THRESHOLD: f64 = 3.5;
+
+/// The factor relating the median absolute deviation to the standard deviation
+/// of normally distributed values.
+const MAD_SCALE: f64 = 1.4826;
+
+/// The factor relating the mean absolute deviation to the standard deviation
+/// of normally distributed values.
+const MEAN_ABSOLUTE_DEVIATION_SCALE: f64 = 1.2533;
+
+/// The reason for flagging a consensus peak as outlier.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub enum OutlierReason {
+    /// The peak is extremely short.
+    Short,
+    /// The peak is extremely long.
+    Long,
+    /// The peak is supported by extremely few raw peaks.
+    LowSupport,
+    /// The peak is supported by extremely many raw peaks.
+    HighSupport,
+    /// The summits of the supporting raw peaks are extremely dispersed.
+    HighSummitSpread,
+}
+
+impl Display for OutlierReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutlierReason::Short => write!(f, "short"),
+            OutlierReason::Long => write!(f, "long"),
+            OutlierReason::LowSupport => write!(f, "low_support"),
+            OutlierReason::HighSupport => write!(f, "high_support"),
+            OutlierReason::HighSummitSpread => write!(f, "high_summit_spread"),
+        }
+    }
+}
+
+/// The modified z-scores above which the respective property of a consensus peak is
+/// considered extreme.
+#[derive(CopyGetters, Clone, Copy, PartialEq, Debug)]
+#[getset(get_copy = "pub")]
+pub struct OutlierThresholds {
+    /// The threshold of the peak length.
+    length: f64,
+    /// The threshold of the number of supporting raw peaks.
+    support: f64,
+    /// The threshold of the standard deviation of the supporting raw peak summits.
+    summit_spread: f64,
+}
+
+impl OutlierThresholds {
+    /// Creates new outlier thresholds.
+    ///
+    /// # Parameters
+    ///
+    /// * `length` - the threshold of the peak length
+    /// * `support` - the threshold of the number of supporting raw peaks
+    /// * `summit_spread` - the threshold of the standard deviation of the supporting summits
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any threshold is not positive.
+    pub fn new(length: f64, support: f64, summit_spread: f64) -> Result<Self, ApplicationError> {
+        for threshold in [length, support, summit_spread] {
+            if threshold.is_nan() || threshold <= 0.0 {
+                return Err(ApplicationError::new(
+                    ApplicationErrorType::ConfigError,
+                    format!("The outlier threshold {} is not positive.", threshold),
+                ));
+            }
+        }
+        Ok(Self {
+            length,
+            support,
+            summit_spread,
+        })
+    }
+}
+
+impl Default for OutlierThresholds {
+    fn default() -> Self {
+        Self {
+            length: DEFAULT_OUTLIER_THRESHOLD,
+            support: DEFAULT_OUTLIER_THRESHOLD,
+            summit_spread: DEFAULT_OUTLIER_THRESHOLD,
+        }
+    }
+}
+
+/// A consensus peak flagged as outlier.
+#[derive(CopyGetters, Getters, Clone, PartialEq, Debug)]
+pub struct OutlierPeak {
+    /// The chromosome of the consensus peak.
+    #[getset(get_copy = "pub")]
+    chromosome: Chromosome,
+    /// The consensus peak.
+    #[getset(get_copy = "pub")]
+    peak: PeakData,
+    /// The number of raw peaks with a summit within the consensus peak.
+    #[getset(get_copy = "pub")]
+    support: usize,
+    /// The standard deviation of the supporting raw peak summits.
+    #[getset(get_copy = "pub")]
+    summit_spread: f64,
+    /// The reasons for flagging the peak.
+    #[getset(get = "pub")]
+    reasons: Vec<OutlierReason>,
+}
+
+/// The modified z-score relative to a set of values.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct ModifiedZScore {
+    /// The median of the values.
+    median: f64,
+    /// The robust estimate of the standard deviation of the values.
+    scale: f64,
+}
+
+impl ModifiedZScore {
+    /// Estimates the location and scale of the values or returns `None` if the values
+    /// do not vary.
+    ///
+    /// # Parameters
+    ///
+    /// * `values` - the values
+    fn new(values: &[f64]) -> Option<Self> {
+        let location = median(values.to_vec())?;
+        let deviations: Vec<f64> = values
+            .iter()
+            .map(|value| (value - location).abs())
+            .collect();
+        let median_absolute_deviation = median(deviations.clone())?;
+        // Falls back to the mean absolute deviation if more than half of the values are equal.
+        let scale = if median_absolute_deviation > 0.0 {
+            MAD_SCALE * median_absolute_deviation
+        } else {
+            MEAN_ABSOLUTE_DEVIATION_SCALE * deviations.iter().sum::<f64>() / deviations.len() as f64
+        };
+        (scale > 0.0).then_some(Self {
+            median: location,
+            scale,
+        })
+    }
+
+    /// Returns the modified z-score of the value.
+    ///
+    /// # Parameters
+    ///
+    /// * `value` - the value
+    fn score(&self, value: f64) -> f64 {
+        (value - self.median) / self.scale
+    }
+}
+
+/// Returns the median of the values or `None` if there are no values.
+///
+/// # Parameters
+///
+/// * `values` - the values
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        Some((values[middle - 1] + values[middle]) / 2.0)
+    } else {
+        Some(values[middle])
+    }
+}
+
+/// Flags the consensus peaks whose length, support or summit spread are extreme.
+/// The support of a consensus peak is the number of raw peaks with a summit within
+/// the consensus peak as reported by the [`SummitConcordance`] of the consensus peaks
+/// with the raw peaks.
+///
+/// # Parameters
+///
+/// * `concordance` - the summit concordance of the consensus peaks with the raw peaks
+/// * `thresholds` - the modified z-scores above which a value is extreme
+pub fn outlier_peaks(
+    concordance: &SummitConcordance,
+    thresholds: &OutlierThresholds,
+) -> Vec<OutlierPeak> {
+    let peaks = concordance.peaks();
+    let length =
+        |peak: &PeakSummitConcordance| (peak.peak().end() - peak.peak().start() + 1) as f64;
+    let support = |peak: &PeakSummitConcordance| peak.summits() as f64;
+    let spread = |peak: &PeakSummitConcordance| peak.standard_deviation();
+    let length_score = ModifiedZScore::new(&peaks.iter().map(length).collect::<Vec<f64>>());
+    let support_score = ModifiedZScore::new(&peaks.iter().map(support).collect::<Vec<f64>>());
+    let spread_score = ModifiedZScore::new(&peaks.iter().map(spread).collect::<Vec<f64>>());
+    let outliers: Vec<OutlierPeak> = peaks
+        .iter()
+        .filter_map(|peak| {
+            let mut reasons = Vec::new();
+            if let Some(score) = length_score.map(|score| score.score(length(peak))) {
+                if score < -thresholds.length() {
+                    reasons.push(OutlierReason::Short);
+                } else if score > thresholds.length() {
+                    reasons.push(OutlierReason::Long);
+                }
+            }
+            if let Some(score) = support_score.map(|score| score.score(support(peak))) {
+                if score < -thresholds.support() {
+                    reasons.push(OutlierReason::LowSupport);
+                } else if score > thresholds.support() {
+                    reasons.push(OutlierReason::HighSupport);
+                }
+            }
+            if spread_score
+                .is_some_and(|score| score.score(spread(peak)) > thresholds.summit_spread())
+            {
+                reasons.push(OutlierReason::HighSummitSpread);
+            }
+            (!reasons.is_empty()).then(|| OutlierPeak {
+                chromosome: peak.chromosome(),
+                peak: peak.peak(),
+                support: peak.summits(),
+                summit_spread: peak.standard_deviation(),
+                reasons,
+            })
+        })
+        .collect();
+    log::info!(
+        target: LOG_TARGET,
+        "Flagged {} of {} consensus peaks as outliers.",
+        outliers.len(),
+        peaks.len()
+    );
+    outliers
+}
+
+/// Writes the outlier peaks as tab separated table sorted by chromosome and coordinates.
+///
+/// # Parameters
+///
+/// * `writer` - the writer to write the table to
+/// * `outliers` - the outlier peaks
+///
+/// # Errors
+///
+/// Returns an error if the table cannot be written.
+pub fn write_outlier_table<W: Write>(
+    mut writer: W,
+    outliers: &[OutlierPeak],
+) -> Result<(), ApplicationError> {
+    let mut sorted_outliers: Vec<&OutlierPeak> = outliers.iter().collect();
+    sorted_outliers.sort_by_key(|outlier| {
+        (outlier.chromosome(), outlier.peak().start(), outlier.peak().end())
+    });
+    let mut table =
+        String::from("chromosome\tstart\tend\tsummit\tlength\tsupport\tsummit_sd\treasons\n");
+    for outlier in sorted_outliers {
+        let reasons: Vec<String> = outlier.reasons().iter().map(ToString::to_string).collect();
+        table.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{:.3}\t{}\n",
+            outlier.chromosome(),
+            outlier.peak().start(),
+            outlier.peak().end(),
+            outlier.peak().summit(),
+            outlier.peak().end() - outlier.peak().start() + 1,
+            outlier.support(),
+            outlier.summit_spread(),
+            reasons.join(",")
+        ));
+    }
+    writer.write_all(table.as_bytes()).map_err(|err| {
+        ApplicationError::from(err).chain("The outlier report could not be written.")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::peak_set::PeakSet;
+
+    use super::*;
+
+    #[test]
+    fn test_modified_z_score() {
+        let z_score = ModifiedZScore::new(&[1.0, 2.0, 3.0, 4.0, 100.0]).unwrap();
+        assert!(z_score.score(3.0).abs() < 1e-9);
+        assert!((z_score.score(100.0) - 97.0 / MAD_SCALE).abs() < 1e-9);
+        // Falls back to the mean absolute deviation.
+        let z_score = ModifiedZScore::new(&[1.0, 1.0, 1.0, 5.0]).unwrap();
+        assert!((z_score.score(5.0) - 4.0 / MEAN_ABSOLUTE_DEVIATION_SCALE).abs() < 1e-9);
+        assert!(ModifiedZScore::new(&[2.0, 2.0]).is_none());
+        assert!(ModifiedZScore::new(&[]).is_none());
+    }
+
+    #[test]
+    fn test_outlier_thresholds() {
+        assert!(OutlierThresholds::new(3.5, 2.0, 1.0).is_ok());
+        assert!(OutlierThresholds::new(0.0, 2.0, 1.0).is_err());
+        assert!(OutlierThresholds::new(3.5, f64::NAN, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_outlier_peaks() {
+        let chromosome = Chromosome::from("chr1");
+        // Ten regular consensus peaks of similar length supported by two summits each
+        // and a very long peak supported by many dispersed summits.
+        let mut consensus = PeakSet::new();
+        let mut raw = PeakSet::new();
+        let mut raw_id = 0;
+        for index in 0..10u64 {
+            let start = index * 1000;
+            consensus.insert(
+                chromosome,
+                PeakData::new(index as usize, start, start + 199 + index, start + 100).unwrap(),
+            );
+            for summit in [start + 95, start + 105] {
+                raw.insert(chromosome, PeakData::new(raw_id, start, start + 199, summit).unwrap());
+                raw_id += 1;
+            }
+        }
+        consensus.insert(chromosome, PeakData::new(10, 20000u64, 29999u64, 25000u64).unwrap());
+        for summit in (20000..30000u64).step_by(1000) {
+            raw.insert(chromosome, PeakData::new(raw_id, summit, summit + 199, summit).unwrap());
+            raw_id += 1;
+        }
+        let concordance = SummitConcordance::new(&consensus, &[raw]);
+        let outliers = outlier_peaks(&concordance, &OutlierThresholds::default());
+        assert_eq!(outliers.len(), 1);
+        assert_eq!(outliers[0].peak().id(), 10);
+        assert_eq!(outliers[0].support(), 10);
+        assert_eq!(
+            outliers[0].reasons(),
+            &vec![
+                OutlierReason::Long,
+                OutlierReason::HighSupport,
+                OutlierReason::HighSummitSpread
+            ]
+        );
+
+        let mut table = Vec::new();
+        write_outlier_table(&mut table, &outliers).unwrap();
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            "chromosome\tstart\tend\tsummit\tlength\tsupport\tsummit_sd\treasons\n\
+            chr1\t20000\t29999\t25000\t10000\t10\t2872.281\tlong,high_support,high_summit_spread\n"
+        );
+    }
+}
@@ -1,12 +1,43 @@
 //! This module writes output data.
 
-use std::{collections::HashMap, fs::File, io::Write, path::Path};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Read, Write},
+    path::Path,
+};
+
+use getset::{CopyGetters, Getters};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 
 use crate::{
+    chromosome::Chromosome,
     error::{ApplicationError, ApplicationErrorType},
     peaks::PeakData,
+    retry::{Retrying, retry},
+    scores::PeakScores,
+    strand::Strand,
 };
 
+/// The log target of this module.
+const LOG_TARGET: &str = "output";
+
+/// The resolution in bases the peak coordinates are rounded to for stable peak names.
+pub const STABLE_NAME_RESOLUTION: u64 = 10;
+
+/// The number of hexadecimal digits of the hash of stable peak names.
+pub const STABLE_NAME_LENGTH: usize = 10;
+
+/// The header line of [GFF3](https://github.com/The-Sequence-Ontology/Specifications/blob/master/gff3.md) files.
+pub const GFF3_HEADER: &str = "##gff-version 3";
+
+/// The source of the features of GFF3 records.
+const GFF3_SOURCE: &str = "Gipfelkreuzer";
+
+/// The Sequence Ontology type of the features of GFF3 records.
+const GFF3_FEATURE_TYPE: &str = "region";
+
 /// Creates a BED record line according to the
 /// [GA4GH BED v1.0](https://github.com/samtools/hts-specs/blob/master/BEDv1.pdf) standard
 /// from [`PeakData`] and the respective chromosome name.
@@ -30,7 +61,8 @@ pub fn peak_to_bed_record_line(peak: &PeakData, chromosome: &str, fields: usize)
             3 => bed_record.push_str(&format!("consensus_{}", peak.id())),
             // Strand
             5 => bed_record.push('.'),
-            9 => bed_record.push_str(&peak.summit().to_string()),
+            // Summit offset from the start as defined by the narrowPeak format
+            9 => bed_record.push_str(&(peak.summit() - peak.start()).to_string()),
             _ => bed_record.push('0'),
         };
         if field_index < fields - 1 {
@@ -42,9 +74,466 @@ pub fn peak_to_bed_record_line(peak: &PeakData, chromosome: &str, fields: usize)
     bed_record
 }
 
+/// The output file format presets defining the number and content of the fields / columns
+/// of the output records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum OutputFormat {
+    /// BED3 records containing only the coordinates.
+    #[cfg_attr(feature = "cli", value(name = "bed3"))]
+    Bed3,
+    /// BED6 records containing the coordinates, name, score and strand.
+    #[cfg_attr(feature = "cli", value(name = "bed6"))]
+    Bed6,
+    /// BED12 records describing each peak as a single block.
+    #[cfg_attr(feature = "cli", value(name = "bed12"))]
+    Bed12,
+    /// [narrowPeak](https://genome.ucsc.edu/FAQ/FAQformat.html#format12) records
+    /// including the summit offset from the start.
+    #[cfg_attr(feature = "cli", value(name = "narrowPeak"))]
+    NarrowPeak,
+    /// [broadPeak](https://genome.ucsc.edu/FAQ/FAQformat.html#format13) records
+    /// without summit information.
+    #[cfg_attr(feature = "cli", value(name = "broadPeak"))]
+    BroadPeak,
+    /// [JSON Lines](https://jsonlines.org/) records with one JSON object per peak
+    /// including the contributing raw peaks and samples if known.
+    #[cfg_attr(feature = "cli", value(name = "json"))]
+    Json,
+    /// [GFF3](https://github.com/The-Sequence-Ontology/Specifications/blob/master/gff3.md)
+    /// features with 1-based coordinates including the summit and the contributing raw peaks
+    /// and samples if known as attributes.
+    #[cfg_attr(feature = "cli", value(name = "gff3"))]
+    Gff3,
+}
+
+impl OutputFormat {
+    /// Returns the number of fields / columns of the records.
+    pub fn fields(&self) -> usize {
+        match self {
+            OutputFormat::Bed3 => 3,
+            OutputFormat::Bed6 => 6,
+            OutputFormat::Bed12 => 12,
+            OutputFormat::NarrowPeak => 10,
+            OutputFormat::BroadPeak => 9,
+            // JSON records are not split into fields.
+            OutputFormat::Json => 0,
+            OutputFormat::Gff3 => 9,
+        }
+    }
+
+    /// Creates a record line of the format from [`PeakData`] and the respective chromosome name.
+    /// Unknown signal values, p-values and q-values are set to `-1` as defined by the UCSC
+    /// narrowPeak and broadPeak specifications.
+    ///
+    /// # Parameters
+    ///
+    /// * `peak` - the peak data
+    /// * `chromosome`- the name of the chromosome the peak belongs to
+    pub fn record_line(&self, peak: &PeakData, chromosome: &str) -> String {
+        match self {
+            OutputFormat::Bed3 | OutputFormat::Bed6 => {
+                peak_to_bed_record_line(peak, chromosome, self.fields())
+            },
+            OutputFormat::Bed12 => format!(
+                "{}\t{}\t{}\t0\t1\t{}\t0\n",
+                peak_to_bed_record_line(peak, chromosome, 6).trim_end_matches('\n'),
+                peak.start(),
+                peak.end(),
+                peak.end() - peak.start()
+            ),
+            OutputFormat::NarrowPeak | OutputFormat::BroadPeak => {
+                let record_line = peak_to_bed_record_line(peak, chromosome, self.fields());
+                let mut fields: Vec<&str> =
+                    record_line.trim_end_matches('\n').split('\t').collect();
+                for field in &mut fields[6..9] {
+                    *field = "-1";
+                }
+                format!("{}\n", fields.join("\t"))
+            },
+            OutputFormat::Json => RecordFields::new().json_record_line(peak, chromosome, None),
+            OutputFormat::Gff3 => RecordFields::new().gff3_record_line(peak, chromosome, None),
+        }
+    }
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputFormat::Bed3 => write!(f, "bed3"),
+            OutputFormat::Bed6 => write!(f, "bed6"),
+            OutputFormat::Bed12 => write!(f, "bed12"),
+            OutputFormat::NarrowPeak => write!(f, "narrowPeak"),
+            OutputFormat::BroadPeak => write!(f, "broadPeak"),
+            OutputFormat::Json => write!(f, "json"),
+            OutputFormat::Gff3 => write!(f, "gff3"),
+        }
+    }
+}
+
+/// Escapes the characters with a reserved meaning in the attribute column of GFF3 records.
+///
+/// # Parameters
+///
+/// * `value` - the attribute value
+fn gff3_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for character in value.chars() {
+        if matches!(character, ';' | '=' | '&' | ',' | '%') || character.is_control() {
+            let mut buffer = [0; 4];
+            for byte in character.encode_utf8(&mut buffer).bytes() {
+                escaped.push_str(&format!("%{:02X}", byte));
+            }
+        } else {
+            escaped.push(character);
+        }
+    }
+    escaped
+}
+
+/// Returns the stable name of a peak, which is derived from a hash of its chromosome and its
+/// coordinates rounded to [`STABLE_NAME_RESOLUTION`] bases instead of its identifier.
+/// Stable names therefore remain the same if the consensus peaks are regenerated, for example
+/// after adding a sample, as long as the coordinates of a peak do not change noticeably.
+///
+/// # Parameters
+///
+/// * `peak` - the peak data
+/// * `chromosome`- the name of the chromosome the peak belongs to
+pub fn stable_peak_name(peak: &PeakData, chromosome: &str) -> String {
+    let round = |coordinate: u64| {
+        (coordinate + STABLE_NAME_RESOLUTION / 2) / STABLE_NAME_RESOLUTION * STABLE_NAME_RESOLUTION
+    };
+    let hash: String =
+        Sha256::digest(format!("{}:{}-{}", chromosome, round(peak.start()), round(peak.end())))
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+    format!("consensus_{}", &hash[..STABLE_NAME_LENGTH])
+}
+
+/// Returns the [stable names](stable_peak_name) of all peaks by peak ID.
+/// Peaks sharing a stable name are distinguished by a numeric suffix
+/// in the order of their coordinates.
+///
+/// # Parameters
+///
+/// * `peaks` - all peaks sorted by chromosome
+pub fn stable_peak_names(peaks: &HashMap<Chromosome, Vec<PeakData>>) -> HashMap<usize, String> {
+    let mut sorted_peaks: Vec<(&Chromosome, &PeakData)> = peaks
+        .iter()
+        .flat_map(|(chromosome, peaks)| peaks.iter().map(move |peak| (chromosome, peak)))
+        .collect();
+    sorted_peaks.sort_unstable_by_key(|(chromosome, peak)| {
+        (chromosome.name(), peak.start(), peak.end(), peak.id())
+    });
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+    sorted_peaks
+        .into_iter()
+        .map(|(chromosome, peak)| {
+            let name = stable_peak_name(peak, chromosome.name());
+            let occurrence = occurrences.entry(name.clone()).or_default();
+            *occurrence += 1;
+            if *occurrence > 1 {
+                (peak.id(), format!("{}_{}", name, occurrence))
+            } else {
+                (peak.id(), name)
+            }
+        })
+        .collect()
+}
+
+/// The raw peaks and samples a consensus peak was created from.
+#[derive(Serialize, CopyGetters, Getters, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PeakContribution {
+    /// The number of contributing raw peaks.
+    #[getset(get_copy = "pub")]
+    contributing_peaks: usize,
+    /// The names of the contributing samples.
+    #[getset(get = "pub")]
+    samples: Vec<String>,
+}
+
+impl PeakContribution {
+    /// Creates the contribution of raw peaks and samples to a consensus peak.
+    ///
+    /// # Parameters
+    ///
+    /// * `contributing_peaks` - the number of contributing raw peaks
+    /// * `samples` - the names of the contributing samples
+    pub fn new(contributing_peaks: usize, samples: Vec<String>) -> Self {
+        Self {
+            contributing_peaks,
+            samples,
+        }
+    }
+}
+
+/// A peak serialised as JSON object.
+#[derive(Serialize)]
+struct JsonRecord<'a> {
+    /// The chromosome of the peak.
+    chromosome: &'a str,
+    /// The start coordinate of the peak.
+    start: u64,
+    /// The end coordinate of the peak.
+    end: u64,
+    /// The summit coordinate of the peak.
+    summit: u64,
+    /// The name of the peak.
+    name: String,
+    /// The strand of the peak if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    strand: Option<String>,
+    /// The raw peaks and samples the peak was created from if known.
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    contribution: Option<&'a PeakContribution>,
+    /// The additional output columns of the peak.
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    columns: &'a [String],
+}
+
+/// Values replacing the default standard fields of the BED records of individual peaks.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecordFields {
+    /// The names of the peaks by peak ID.
+    names: HashMap<usize, String>,
+    /// The statistical values of the peaks by peak ID.
+    scores: HashMap<usize, PeakScores>,
+    /// The strands of the peaks by peak ID.
+    strands: HashMap<usize, Strand>,
+    /// The output format preset replacing the default record layout.
+    format: Option<OutputFormat>,
+    /// The raw peaks and samples contributing to the peaks by chromosome and peak ID.
+    contributions: HashMap<(Chromosome, usize), PeakContribution>,
+}
+
+impl RecordFields {
+    /// Creates record fields keeping the default values of all peaks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the default names of the peaks.
+    ///
+    /// # Parameters
+    ///
+    /// * `names` - the names of the peaks by peak ID
+    pub fn names(mut self, names: HashMap<usize, String>) -> Self {
+        self.names = names;
+        self
+    }
+
+    /// Replaces the default score, signal value, p-value and q-value of the peaks.
+    ///
+    /// # Parameters
+    ///
+    /// * `scores` - the statistical values of the peaks by peak ID
+    pub fn scores(mut self, scores: HashMap<usize, PeakScores>) -> Self {
+        self.scores = scores;
+        self
+    }
+
+    /// Replaces the default unknown strand of the peaks.
+    ///
+    /// # Parameters
+    ///
+    /// * `strands` - the strands of the peaks by peak ID
+    pub fn strands(mut self, strands: HashMap<usize, Strand>) -> Self {
+        self.strands = strands;
+        self
+    }
+
+    /// Uses the record layout of the specified output format preset instead of the
+    /// default layout. The number of fields / columns is then defined by the preset.
+    ///
+    /// # Parameters
+    ///
+    /// * `format` - the output format preset
+    pub fn format(mut self, format: OutputFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Adds the contributing raw peaks and samples of the peaks to JSON and GFF3 records.
+    ///
+    /// # Parameters
+    ///
+    /// * `contributions` - the raw peaks and samples contributing to the peaks by chromosome
+    ///   and peak ID
+    pub fn contributions(
+        mut self,
+        contributions: HashMap<(Chromosome, usize), PeakContribution>,
+    ) -> Self {
+        self.contributions = contributions;
+        self
+    }
+
+    /// Returns the output format preset replacing the default record layout if specified.
+    pub fn output_format(&self) -> Option<OutputFormat> {
+        self.format
+    }
+
+    /// Creates a BED record line like [`peak_to_bed_record_line`], but uses the specified
+    /// values of the peak if present.
+    ///
+    /// # Parameters
+    ///
+    /// * `peak` - the peak data
+    /// * `chromosome`- the name of the chromosome the peak belongs to
+    /// * `fields`- the number of fields / columns to generate
+    pub fn record_line(&self, peak: &PeakData, chromosome: &str, fields: usize) -> String {
+        match self.format {
+            Some(OutputFormat::Json) => return self.json_record_line(peak, chromosome, None),
+            Some(OutputFormat::Gff3) => return self.gff3_record_line(peak, chromosome, None),
+            _ => {},
+        }
+        let (record_line, fields) = match self.format {
+            Some(format) => (format.record_line(peak, chromosome), format.fields()),
+            None => (peak_to_bed_record_line(peak, chromosome, fields), fields),
+        };
+        let name = self.names.get(&peak.id()).filter(|_| fields > 3);
+        let strand = self
+            .strands
+            .get(&peak.id())
+            .filter(|_| fields > 5)
+            .map(Strand::to_string);
+        let record_line = if name.is_some() || strand.is_some() {
+            let mut record_fields: Vec<&str> =
+                record_line.trim_end_matches('\n').split('\t').collect();
+            if let Some(name) = name {
+                record_fields[3] = name;
+            }
+            if let Some(strand) = &strand {
+                record_fields[5] = strand;
+            }
+            format!("{}\n", record_fields.join("\t"))
+        } else {
+            record_line
+        };
+        match self.scores.get(&peak.id()) {
+            // The fields following the score have a different meaning in BED12 records.
+            Some(scores) if self.format == Some(OutputFormat::Bed12) => {
+                PeakScores::new(scores.score(), None, None, None).apply_to_record_line(&record_line)
+            },
+            Some(scores) => scores.apply_to_record_line(&record_line),
+            None => record_line,
+        }
+    }
+
+    /// Creates a [JSON Lines](https://jsonlines.org/) record of the peak containing its
+    /// coordinates, name and the specified values of the peak if present.
+    ///
+    /// # Parameters
+    ///
+    /// * `peak` - the peak data
+    /// * `chromosome`- the name of the chromosome the peak belongs to
+    /// * `columns` - the additional output columns of the peak
+    pub fn json_record_line(
+        &self,
+        peak: &PeakData,
+        chromosome: &str,
+        columns: Option<&Vec<String>>,
+    ) -> String {
+        let record = JsonRecord {
+            chromosome,
+            start: peak.start(),
+            end: peak.end(),
+            summit: peak.summit(),
+            name: self
+                .names
+                .get(&peak.id())
+                .cloned()
+                .unwrap_or_else(|| format!("consensus_{}", peak.id())),
+            strand: self.strands.get(&peak.id()).map(Strand::to_string),
+            contribution: self
+                .contributions
+                .get(&(Chromosome::from(chromosome), peak.id())),
+            columns: columns.map_or(&[], Vec::as_slice),
+        };
+        // Serialising a record of strings and numbers cannot fail.
+        let mut record_line = serde_json::to_string(&record).unwrap_or_default();
+        record_line.push('\n');
+        record_line
+    }
+
+    /// Creates a [GFF3](https://github.com/The-Sequence-Ontology/Specifications/blob/master/gff3.md)
+    /// record of the peak with 1-based inclusive coordinates. The name, 1-based summit position
+    /// and the specified values of the peak if present are added as attributes.
+    ///
+    /// # Parameters
+    ///
+    /// * `peak` - the peak data
+    /// * `chromosome`- the name of the chromosome the peak belongs to
+    /// * `columns` - the additional output columns of the peak
+    pub fn gff3_record_line(
+        &self,
+        peak: &PeakData,
+        chromosome: &str,
+        columns: Option<&Vec<String>>,
+    ) -> String {
+        let name = self
+            .names
+            .get(&peak.id())
+            .cloned()
+            .unwrap_or_else(|| format!("consensus_{}", peak.id()));
+        let mut attributes = vec![
+            format!("ID={}", gff3_escape(&name)),
+            format!("summit={}", peak.summit() + 1),
+        ];
+        if let Some(contribution) = self
+            .contributions
+            .get(&(Chromosome::from(chromosome), peak.id()))
+        {
+            attributes.push(format!("aggregated_peaks={}", contribution.contributing_peaks()));
+            if !contribution.samples().is_empty() {
+                attributes.push(format!(
+                    "samples={}",
+                    contribution
+                        .samples()
+                        .iter()
+                        .map(|sample| gff3_escape(sample))
+                        .collect::<Vec<String>>()
+                        .join(",")
+                ));
+            }
+        }
+        if let Some(columns) = columns.filter(|columns| !columns.is_empty()) {
+            attributes.push(format!(
+                "columns={}",
+                columns
+                    .iter()
+                    .map(|column| gff3_escape(column))
+                    .collect::<Vec<String>>()
+                    .join(",")
+            ));
+        }
+        let score = self
+            .scores
+            .get(&peak.id())
+            .and_then(PeakScores::score)
+            .map_or_else(|| ".".to_string(), |score| format!("{:.0}", score));
+        let strand = self
+            .strands
+            .get(&peak.id())
+            .map_or_else(|| ".".to_string(), Strand::to_string);
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t.\t{}\n",
+            gff3_escape(chromosome),
+            GFF3_SOURCE,
+            GFF3_FEATURE_TYPE,
+            peak.start() + 1,
+            peak.end(),
+            score,
+            strand,
+            attributes.join(";")
+        )
+    }
+}
+
 ///  Writes all peaks to the specified file using the
 /// [GA4GH BED v1.0](https://github.com/samtools/hts-specs/blob/master/BEDv1.pdf)
-/// standard.
+/// standard. Records are sorted by chromosome name and coordinates.
 ///
 /// # Parameters
 /// * `path`- the path of the output file
@@ -56,10 +545,108 @@ pub fn peak_to_bed_record_line(peak: &PeakData, chromosome: &str, fields: usize)
 /// creation of the output file failed.
 pub fn write_peaks_to_bed<T: AsRef<Path>>(
     path: T,
-    peaks: &HashMap<String, Vec<PeakData>>,
+    peaks: &HashMap<Chromosome, Vec<PeakData>>,
+    fields: usize,
+) -> Result<(), ApplicationError> {
+    write_bed_file(path, peaks, |peak, chromosome| {
+        peak_to_bed_record_line(peak, chromosome.name(), fields)
+    })
+}
+
+/// Writes all peaks to the specified file using the
+/// [GA4GH BED v1.0](https://github.com/samtools/hts-specs/blob/master/BEDv1.pdf)
+/// standard and appends the additional columns of each peak.
+/// Records are sorted by chromosome name and coordinates.
+///
+/// # Parameters
+/// * `path`- the path of the output file
+/// * `peaks` - all peaks sorted by chromosome
+/// * `fields`- the number of standard fields / columns to generate
+/// * `columns` - the additional columns by peak ID
+/// * `record_fields` - the values replacing the default standard fields
+///
+/// # Errors
+/// Returns an error if the output file path is invalid or if
+/// creation of the output file failed.
+pub fn write_peaks_to_bed_with_columns<T: AsRef<Path>>(
+    path: T,
+    peaks: &HashMap<Chromosome, Vec<PeakData>>,
     fields: usize,
+    columns: &HashMap<usize, Vec<String>>,
+    record_fields: &RecordFields,
+) -> Result<(), ApplicationError> {
+    let header = match record_fields.output_format() {
+        Some(OutputFormat::Gff3) => Some(GFF3_HEADER),
+        _ => None,
+    };
+    write_bed_file_with_header(path, header, peaks, |peak, chromosome| {
+        match record_fields.output_format() {
+            Some(OutputFormat::Json) => {
+                return record_fields.json_record_line(
+                    peak,
+                    chromosome.name(),
+                    columns.get(&peak.id()),
+                );
+            },
+            Some(OutputFormat::Gff3) => {
+                return record_fields.gff3_record_line(
+                    peak,
+                    chromosome.name(),
+                    columns.get(&peak.id()),
+                );
+            },
+            _ => {},
+        }
+        let record_line = record_fields.record_line(peak, chromosome.name(), fields);
+        match columns.get(&peak.id()) {
+            Some(peak_columns) if !record_line.is_empty() && !peak_columns.is_empty() => {
+                format!("{}\t{}\n", record_line.trim_end_matches('\n'), peak_columns.join("\t"))
+            },
+            _ => record_line,
+        }
+    })
+}
+
+/// Writes all peaks to the specified file, creating the parent directories if required.
+/// Records are sorted by chromosome name and coordinates.
+/// The records are written to a hidden temporary file first, which then replaces the output file,
+/// so readers never observe a partially written output file.
+///
+/// # Parameters
+/// * `path`- the path of the output file
+/// * `peaks` - all peaks sorted by chromosome
+/// * `record_line`- creates the record line of a peak on the respective chromosome
+///
+/// # Errors
+/// Returns an error if the output file path is invalid or if
+/// creation of the output file failed.
+pub(crate) fn write_bed_file<T: AsRef<Path>, F: Fn(&PeakData, &Chromosome) -> String>(
+    path: T,
+    peaks: &HashMap<Chromosome, Vec<PeakData>>,
+    record_line: F,
+) -> Result<(), ApplicationError> {
+    write_bed_file_with_header(path, None, peaks, record_line)
+}
+
+/// Writes all peaks to the specified file like [`write_bed_file`], but writes the specified
+/// header line before the records.
+///
+/// # Parameters
+/// * `path`- the path of the output file
+/// * `header` - the header line without line break if any
+/// * `peaks` - all peaks sorted by chromosome
+/// * `record_line`- creates the record line of a peak on the respective chromosome
+///
+/// # Errors
+/// Returns an error if the output file path is invalid or if
+/// creation of the output file failed.
+fn write_bed_file_with_header<T: AsRef<Path>, F: Fn(&PeakData, &Chromosome) -> String>(
+    path: T,
+    header: Option<&str>,
+    peaks: &HashMap<Chromosome, Vec<PeakData>>,
+    record_line: F,
 ) -> Result<(), ApplicationError> {
-    log::info!("Writing consensus peaks to {}.", path.as_ref().display());
+    log::info!(target: LOG_TARGET, "Writing consensus peaks to {}.", path.as_ref().display());
     // Creates the specified output path.
     let parent_directory = path.as_ref().parent().ok_or(ApplicationError::new(
         ApplicationErrorType::OutputOperationError,
@@ -72,28 +659,189 @@ pub fn write_peaks_to_bed<T: AsRef<Path>>(
         ))
     })?;
 
-    // Creates the output file.
-    let mut file = File::create(&path).map_err(|err| {
-        ApplicationError::from(err)
-            .chain(format!("The output file \"{}\" could not created.", path.as_ref().display()))
-    })?;
+    // Creates the temporary output file next to the output file,
+    // as renaming is only atomic within the same file system.
+    let file_name = path.as_ref().file_name().ok_or(ApplicationError::new(
+        ApplicationErrorType::OutputOperationError,
+        format!("The output file path \"{}\" is invalid.", path.as_ref().display()),
+    ))?;
+    let mut temporary_file_name = std::ffi::OsString::from(".");
+    temporary_file_name.push(file_name);
+    temporary_file_name.push(".tmp");
+    let temporary_path = path.as_ref().with_file_name(temporary_file_name);
+    let temporary_display = temporary_path.display();
+    let file = retry(&format!("Creating {}", temporary_display), || File::create(&temporary_path))
+        .map_err(|err| {
+            ApplicationError::from(err)
+                .chain(format!("The output file \"{}\" could not created.", temporary_display))
+        })?;
+
+    let mut writer = Retrying::new(&file, format!("Writing {}", temporary_display));
+    write_bed_records(&mut writer, header, peaks, record_line)
+        .and_then(|digest| {
+            // Flushes the data to disk, so write failures like an exceeded quota surface here.
+            retry(&format!("Syncing {}", temporary_display), || file.sync_all())?;
+            verify_output(&temporary_path, digest)
+        })
+        .and_then(|_| {
+            Ok(retry(&format!("Renaming {}", temporary_display), || {
+                std::fs::rename(&temporary_path, &path)
+            })?)
+        })
+        .map_err(|err| {
+            // Removes the incomplete temporary file on a best effort basis.
+            let _ = std::fs::remove_file(&temporary_path);
+            err.chain(format!("Writing to output file \"{}\" failed.", path.as_ref().display()))
+        })
+}
+
+/// Creates the content of a BED file containing all peaks using the
+/// [GA4GH BED v1.0](https://github.com/samtools/hts-specs/blob/master/BEDv1.pdf)
+/// standard without requiring any file system access.
+/// Records are sorted by chromosome name and coordinates.
+///
+/// # Parameters
+/// * `peaks` - all peaks sorted by chromosome
+/// * `fields`- the number of fields / columns to generate
+pub fn peaks_to_bed_buffer(peaks: &HashMap<Chromosome, Vec<PeakData>>, fields: usize) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    write_bed_records(&mut buffer, None, peaks, |peak, chromosome| {
+        peak_to_bed_record_line(peak, chromosome.name(), fields)
+    })
+    .expect("Writing to an in-memory buffer must not fail.");
+    buffer
+}
 
-    // Writes the records to the file.
-    for (chromosome, chromosome_peaks) in peaks {
+/// Writes all peaks as BED records to the specified writer
+/// and returns a digest of the written data.
+///
+/// # Parameters
+/// * `writer`- the writer to write the records to
+/// * `header` - the header line without line break written before the records if any
+/// * `peaks` - all peaks sorted by chromosome
+/// * `record_line`- creates the record line of a peak on the respective chromosome
+fn write_bed_records<W: Write, F: Fn(&PeakData, &Chromosome) -> String>(
+    writer: &mut W,
+    header: Option<&str>,
+    peaks: &HashMap<Chromosome, Vec<PeakData>>,
+    record_line: F,
+) -> Result<OutputDigest, ApplicationError> {
+    let mut digest = OutputDigest::default();
+    if let Some(header) = header {
+        let header = format!("{}\n", header);
+        writer.write_all(header.as_bytes()).map_err(|err| {
+            ApplicationError::from(err).chain(format!("Writing header \"{}\" failed.", header))
+        })?;
+        digest.update(header.as_bytes());
+    }
+    // Writes the records sorted by chromosome and coordinates
+    // to keep the output independent of the processing order.
+    let mut chromosomes: Vec<&Chromosome> = peaks.keys().collect();
+    chromosomes.sort();
+    for chromosome in chromosomes {
+        let mut chromosome_peaks: Vec<&PeakData> = peaks[chromosome].iter().collect();
+        chromosome_peaks.sort_by_key(|peak| (peak.start(), peak.end(), peak.summit(), peak.id()));
         for peak in chromosome_peaks {
-            let peak_record = peak_to_bed_record_line(peak, chromosome, fields);
-            file.write_all(peak_record.as_bytes()).map_err(|err| {
-                ApplicationError::from(err).chain(format!(
-                    "Writing record \"{}\" to output file \"{}\" failed.",
-                    peak_record,
-                    path.as_ref().display()
-                ))
+            let peak_record = record_line(peak, chromosome);
+            writer.write_all(peak_record.as_bytes()).map_err(|err| {
+                ApplicationError::from(err)
+                    .chain(format!("Writing record \"{}\" failed.", peak_record))
             })?;
+            digest.update(peak_record.as_bytes());
+        }
+    }
+    Ok(digest)
+}
+
+/// Re-reads a written output file and verifies that its content matches the generated data,
+/// so incomplete output, for example on full file systems, is detected.
+///
+/// # Parameters
+///
+/// * `path` - the written output file
+/// * `expected` - the digest of the generated data
+fn verify_output<T: AsRef<Path>>(path: T, expected: OutputDigest) -> Result<(), ApplicationError> {
+    let read_error = |err: std::io::Error| {
+        ApplicationError::from(err).chain(format!(
+            "The output file \"{}\" could not be read for verification.",
+            path.as_ref().display()
+        ))
+    };
+    let description = format!("Reading {}", path.as_ref().display());
+    let mut file =
+        Retrying::new(retry(&description, || File::open(&path)).map_err(read_error)?, &description);
+    let mut actual = OutputDigest::default();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let bytes_read = file.read(&mut buffer).map_err(read_error)?;
+        if bytes_read == 0 {
+            break;
         }
+        actual.update(&buffer[..bytes_read]);
+    }
+    if actual != expected {
+        return Err(ApplicationError::new(
+            ApplicationErrorType::OutputOperationError,
+            format!(
+                "The output file \"{}\" does not match the generated data. \
+                Expected {} records with {} bytes and checksum {:016x}, \
+                but found {} records with {} bytes and checksum {:016x}.",
+                path.as_ref().display(),
+                expected.records,
+                expected.bytes,
+                expected.checksum,
+                actual.records,
+                actual.bytes,
+                actual.checksum
+            ),
+        )
+        .with_hint("Check that the file system of the output file is not full or quota-limited."));
     }
     Ok(())
 }
 
+/// A digest of written BED data consisting of the number of records, the number of bytes
+/// and an [FNV-1a](https://en.wikipedia.org/wiki/Fowler%E2%80%93Noll%E2%80%93Vo_hash_function)
+/// checksum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct OutputDigest {
+    /// The number of newline terminated records.
+    records: usize,
+    /// The number of bytes.
+    bytes: u64,
+    /// The checksum of the bytes.
+    checksum: u64,
+}
+
+impl Default for OutputDigest {
+    fn default() -> Self {
+        Self {
+            records: 0,
+            bytes: 0,
+            checksum: 0xcbf29ce484222325,
+        }
+    }
+}
+
+impl OutputDigest {
+    /// Adds a chunk of data to the digest.
+    /// Records are counted by their terminating newline character.
+    ///
+    /// # Parameters
+    ///
+    /// * `chunk` - the data
+    fn update(&mut self, chunk: &[u8]) {
+        for byte in chunk {
+            self.checksum ^= u64::from(*byte);
+            self.checksum = self.checksum.wrapping_mul(0x100000001b3);
+            if *byte == b'\n' {
+                self.records += 1;
+            }
+        }
+        self.bytes += chunk.len() as u64;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::{
@@ -151,28 +899,44 @@ mod tests {
             peak_to_bed_record_line(&peak, chromosome, 10),
             format!(
                 "{}\t{}\t{}\tconsensus_{}\t0\t.\t0\t0\t0\t{}\n",
-                chromosome, start, end, id, summit
+                chromosome,
+                start,
+                end,
+                id,
+                summit - start
             )
         );
         assert_eq!(
             peak_to_bed_record_line(&peak, chromosome, 11),
             format!(
                 "{}\t{}\t{}\tconsensus_{}\t0\t.\t0\t0\t0\t{}\t0\n",
-                chromosome, start, end, id, summit
+                chromosome,
+                start,
+                end,
+                id,
+                summit - start
             )
         );
         assert_eq!(
             peak_to_bed_record_line(&peak, chromosome, 12),
             format!(
                 "{}\t{}\t{}\tconsensus_{}\t0\t.\t0\t0\t0\t{}\t0\t0\n",
-                chromosome, start, end, id, summit
+                chromosome,
+                start,
+                end,
+                id,
+                summit - start
             )
         );
         assert_eq!(
             peak_to_bed_record_line(&peak, chromosome, 13),
             format!(
                 "{}\t{}\t{}\tconsensus_{}\t0\t.\t0\t0\t0\t{}\t0\t0\t0\n",
-                chromosome, start, end, id, summit
+                chromosome,
+                start,
+                end,
+                id,
+                summit - start
             )
         );
     }
@@ -185,11 +949,18 @@ mod tests {
         assert!(!output_path.exists());
         output_path.push("test_write_peaks_to_bed_4_fields.bed");
         let mut peaks = HashMap::new();
-        peaks.insert("chr1".to_string(), vec![PeakData::new(0, 45u64, 98u64, 55u64).unwrap()]);
-        peaks.insert("chr1".to_string(), vec![PeakData::new(10, 455u64, 983u64, 554u64).unwrap()]);
-        peaks.insert("chr1".to_string(), vec![PeakData::new(11, 456u64, 986u64, 553u64).unwrap()]);
+        peaks
+            .insert(Chromosome::from("chr1"), vec![PeakData::new(0, 45u64, 98u64, 55u64).unwrap()]);
+        peaks.insert(
+            Chromosome::from("chr1"),
+            vec![PeakData::new(10, 455u64, 983u64, 554u64).unwrap()],
+        );
+        peaks.insert(
+            Chromosome::from("chr1"),
+            vec![PeakData::new(11, 456u64, 986u64, 553u64).unwrap()],
+        );
         peaks.insert(
-            "chr4".to_string(),
+            Chromosome::from("chr4"),
             vec![PeakData::new(109, 4568u64, 9786u64, 5573u64).unwrap()],
         );
         write_peaks_to_bed(&output_path, &peaks, n_fields).unwrap();
@@ -200,7 +971,7 @@ mod tests {
             .flat_map(|(chromosome, chromosome_peaks)| {
                 chromosome_peaks.iter().map(move |peak| (chromosome, peak))
             })
-            .map(|(chromosome, peak)| peak_to_bed_record_line(peak, chromosome, n_fields))
+            .map(|(chromosome, peak)| peak_to_bed_record_line(peak, chromosome.name(), n_fields))
             .collect();
         for line in output_file.lines() {
             // Adds the new line character that was stripped during the read process.
@@ -221,11 +992,18 @@ mod tests {
         let mut output_path = test_output();
         output_path.push("test_write_peaks_to_bed_42_fields.bed");
         let mut peaks = HashMap::new();
-        peaks.insert("chr1".to_string(), vec![PeakData::new(0, 45u64, 98u64, 55u64).unwrap()]);
-        peaks.insert("chr1".to_string(), vec![PeakData::new(10, 455u64, 983u64, 554u64).unwrap()]);
-        peaks.insert("chr1".to_string(), vec![PeakData::new(11, 456u64, 986u64, 553u64).unwrap()]);
+        peaks
+            .insert(Chromosome::from("chr1"), vec![PeakData::new(0, 45u64, 98u64, 55u64).unwrap()]);
         peaks.insert(
-            "chr4".to_string(),
+            Chromosome::from("chr1"),
+            vec![PeakData::new(10, 455u64, 983u64, 554u64).unwrap()],
+        );
+        peaks.insert(
+            Chromosome::from("chr1"),
+            vec![PeakData::new(11, 456u64, 986u64, 553u64).unwrap()],
+        );
+        peaks.insert(
+            Chromosome::from("chr4"),
             vec![PeakData::new(109, 4568u64, 9786u64, 5573u64).unwrap()],
         );
         write_peaks_to_bed(&output_path, &peaks, n_fields).unwrap();
@@ -235,7 +1013,7 @@ mod tests {
             .flat_map(|(chromosome, chromosome_peaks)| {
                 chromosome_peaks.iter().map(move |peak| (chromosome, peak))
             })
-            .map(|(chromosome, peak)| peak_to_bed_record_line(peak, chromosome, n_fields))
+            .map(|(chromosome, peak)| peak_to_bed_record_line(peak, chromosome.name(), n_fields))
             .collect();
         for line in output_file.lines() {
             // Adds the new line character that was stripped during the read process.
@@ -256,11 +1034,18 @@ mod tests {
         let mut output_path = test_output();
         output_path.push("test_write_peaks_to_bed_0_fields.bed");
         let mut peaks = HashMap::new();
-        peaks.insert("chr1".to_string(), vec![PeakData::new(0, 45u64, 98u64, 55u64).unwrap()]);
-        peaks.insert("chr1".to_string(), vec![PeakData::new(10, 455u64, 983u64, 554u64).unwrap()]);
-        peaks.insert("chr1".to_string(), vec![PeakData::new(11, 456u64, 986u64, 553u64).unwrap()]);
+        peaks
+            .insert(Chromosome::from("chr1"), vec![PeakData::new(0, 45u64, 98u64, 55u64).unwrap()]);
+        peaks.insert(
+            Chromosome::from("chr1"),
+            vec![PeakData::new(10, 455u64, 983u64, 554u64).unwrap()],
+        );
+        peaks.insert(
+            Chromosome::from("chr1"),
+            vec![PeakData::new(11, 456u64, 986u64, 553u64).unwrap()],
+        );
         peaks.insert(
-            "chr4".to_string(),
+            Chromosome::from("chr4"),
             vec![PeakData::new(109, 4568u64, 9786u64, 5573u64).unwrap()],
         );
         write_peaks_to_bed(&output_path, &peaks, n_fields).unwrap();
@@ -272,4 +1057,281 @@ mod tests {
         );
         std::fs::remove_file(output_path).unwrap();
     }
+
+    #[test]
+    fn test_write_peaks_to_bed_with_columns() {
+        let output_path = test_output().join("test_write_peaks_to_bed_with_columns.bed");
+        let peaks = HashMap::from([(
+            Chromosome::from("chr1"),
+            vec![
+                PeakData::new(0, 45u64, 98u64, 55u64).unwrap(),
+                PeakData::new(1, 455u64, 983u64, 554u64).unwrap(),
+            ],
+        )]);
+        let columns = HashMap::from([(1, vec!["PLS".to_string(), "gene".to_string()])]);
+        write_peaks_to_bed_with_columns(&output_path, &peaks, 3, &columns, &RecordFields::new())
+            .unwrap();
+        assert_eq!(
+            read_to_string(&output_path).unwrap(),
+            "chr1\t45\t98\nchr1\t455\t983\tPLS\tgene\n"
+        );
+        std::fs::remove_file(output_path).unwrap();
+    }
+
+    #[test]
+    fn test_output_format_record_line() {
+        let peak = PeakData::new(7, 1003u64, 1998u64, 1500u64).unwrap();
+        assert_eq!(OutputFormat::Bed3.record_line(&peak, "chr1"), "chr1\t1003\t1998\n");
+        assert_eq!(
+            OutputFormat::Bed6.record_line(&peak, "chr1"),
+            "chr1\t1003\t1998\tconsensus_7\t0\t.\n"
+        );
+        assert_eq!(
+            OutputFormat::Bed12.record_line(&peak, "chr1"),
+            "chr1\t1003\t1998\tconsensus_7\t0\t.\t1003\t1998\t0\t1\t995\t0\n"
+        );
+        assert_eq!(
+            OutputFormat::NarrowPeak.record_line(&peak, "chr1"),
+            "chr1\t1003\t1998\tconsensus_7\t0\t.\t-1\t-1\t-1\t497\n"
+        );
+        assert_eq!(
+            OutputFormat::BroadPeak.record_line(&peak, "chr1"),
+            "chr1\t1003\t1998\tconsensus_7\t0\t.\t-1\t-1\t-1\n"
+        );
+        for format in [
+            OutputFormat::Bed3,
+            OutputFormat::Bed6,
+            OutputFormat::Bed12,
+            OutputFormat::NarrowPeak,
+            OutputFormat::BroadPeak,
+        ] {
+            assert_eq!(format.record_line(&peak, "chr1").split('\t').count(), format.fields());
+        }
+    }
+
+    #[test]
+    fn test_record_fields_json() {
+        let peak = PeakData::new(7, 1003u64, 1998u64, 1500u64).unwrap();
+        assert_eq!(
+            OutputFormat::Json.record_line(&peak, "chr1"),
+            "{\"chromosome\":\"chr1\",\"start\":1003,\"end\":1998,\"summit\":1500,\
+            \"name\":\"consensus_7\"}\n"
+        );
+        let record_fields = RecordFields::new()
+            .format(OutputFormat::Json)
+            .strands(HashMap::from([(7, Strand::Reverse)]))
+            .contributions(HashMap::from([(
+                (Chromosome::from("chr1"), 7),
+                PeakContribution::new(3, vec!["a".to_string(), "b".to_string()]),
+            )]));
+        let record_line = record_fields.record_line(&peak, "chr1", 4);
+        assert_eq!(
+            record_line,
+            "{\"chromosome\":\"chr1\",\"start\":1003,\"end\":1998,\"summit\":1500,\
+            \"name\":\"consensus_7\",\"strand\":\"-\",\"contributing_peaks\":3,\
+            \"samples\":[\"a\",\"b\"]}\n"
+        );
+        let output_path = test_output().join("test_record_fields_json.jsonl");
+        let peaks = HashMap::from([(Chromosome::from("chr1"), vec![peak])]);
+        let columns = HashMap::from([(7, vec!["PLS".to_string()])]);
+        write_peaks_to_bed_with_columns(&output_path, &peaks, 0, &columns, &record_fields).unwrap();
+        let record: serde_json::Value =
+            serde_json::from_str(&read_to_string(&output_path).unwrap()).unwrap();
+        std::fs::remove_file(output_path).unwrap();
+        assert_eq!(record["columns"][0], "PLS");
+        assert_eq!(record["samples"][1], "b");
+    }
+
+    #[test]
+    fn test_record_fields_gff3() {
+        let peak = PeakData::new(7, 1003u64, 1998u64, 1500u64).unwrap();
+        assert_eq!(
+            OutputFormat::Gff3.record_line(&peak, "chr1"),
+            "chr1\tGipfelkreuzer\tregion\t1004\t1998\t.\t.\t.\tID=consensus_7;summit=1501\n"
+        );
+        let record_fields = RecordFields::new()
+            .format(OutputFormat::Gff3)
+            .names(HashMap::from([(7, "peak;7".to_string())]))
+            .scores(HashMap::from([(7, PeakScores::new(Some(500.0), None, None, None))]))
+            .strands(HashMap::from([(7, Strand::Reverse)]))
+            .contributions(HashMap::from([(
+                (Chromosome::from("chr1"), 7),
+                PeakContribution::new(3, vec!["a,1".to_string(), "b".to_string()]),
+            )]));
+        assert_eq!(
+            record_fields.record_line(&peak, "chr1", 4),
+            "chr1\tGipfelkreuzer\tregion\t1004\t1998\t500\t-\t.\t\
+            ID=peak%3B7;summit=1501;aggregated_peaks=3;samples=a%2C1,b\n"
+        );
+        let output_path = test_output().join("test_record_fields_gff3.gff3");
+        let peaks = HashMap::from([(Chromosome::from("chr1"), vec![peak])]);
+        let columns = HashMap::from([(7, vec!["PLS".to_string()])]);
+        write_peaks_to_bed_with_columns(&output_path, &peaks, 9, &columns, &record_fields).unwrap();
+        let output = read_to_string(&output_path).unwrap();
+        std::fs::remove_file(output_path).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], GFF3_HEADER);
+        assert!(lines[1].ends_with(";samples=a%2C1,b;columns=PLS"));
+    }
+
+    #[test]
+    fn test_gff3_escape() {
+        assert_eq!(gff3_escape("consensus_1"), "consensus_1");
+        assert_eq!(gff3_escape("a=b;c&d,e%f\tg"), "a%3Db%3Bc%26d%2Ce%25f%09g");
+    }
+
+    #[test]
+    fn test_record_fields_output_format() {
+        let peak = PeakData::new(7, 1003u64, 1998u64, 1500u64).unwrap();
+        let scores = HashMap::from([(7, PeakScores::new(Some(12.0), Some(3.5), None, None))]);
+        assert_eq!(
+            RecordFields::new()
+                .scores(scores.clone())
+                .format(OutputFormat::NarrowPeak)
+                .record_line(&peak, "chr1", 4),
+            "chr1\t1003\t1998\tconsensus_7\t12\t.\t3.5\t-1\t-1\t497\n"
+        );
+        // The fields following the score are not replaced in BED12 records.
+        assert_eq!(
+            RecordFields::new()
+                .scores(scores)
+                .format(OutputFormat::Bed12)
+                .record_line(&peak, "chr1", 4),
+            "chr1\t1003\t1998\tconsensus_7\t12\t.\t1003\t1998\t0\t1\t995\t0\n"
+        );
+    }
+
+    #[test]
+    fn test_stable_peak_names() {
+        let peak = PeakData::new(0, 1003u64, 1998u64, 1500u64).unwrap();
+        let name = stable_peak_name(&peak, "chr1");
+        assert_eq!(name.len(), "consensus_".len() + STABLE_NAME_LENGTH);
+        // The identifier and small coordinate shifts do not change the name.
+        assert_eq!(
+            stable_peak_name(&PeakData::new(42, 998u64, 2004u64, 1200u64).unwrap(), "chr1"),
+            name
+        );
+        assert_ne!(stable_peak_name(&peak, "chr2"), name);
+        assert_ne!(
+            stable_peak_name(&PeakData::new(0, 1003u64, 2020u64, 1500u64).unwrap(), "chr1"),
+            name
+        );
+        let peaks = HashMap::from([(
+            Chromosome::from("chr1"),
+            vec![
+                PeakData::new(1, 1001u64, 2000u64, 1500u64).unwrap(),
+                PeakData::new(2, 3000u64, 3999u64, 3500u64).unwrap(),
+                PeakData::new(3, 1003u64, 1998u64, 1500u64).unwrap(),
+            ],
+        )]);
+        let names = stable_peak_names(&peaks);
+        assert_eq!(names[&1], name);
+        assert_eq!(names[&3], format!("{}_2", name));
+        assert_ne!(names[&2], name);
+        assert_eq!(
+            RecordFields::new().names(names).record_line(
+                &peaks[&Chromosome::from("chr1")][0],
+                "chr1",
+                5
+            ),
+            format!("chr1\t1001\t2000\t{}\t0\n", name)
+        );
+    }
+
+    #[test]
+    fn test_record_fields() {
+        let peak = PeakData::new(0, 1003u64, 1998u64, 1500u64).unwrap();
+        assert_eq!(
+            RecordFields::new().record_line(&peak, "chr1", 4),
+            "chr1\t1003\t1998\tconsensus_0\n"
+        );
+        let record_fields = RecordFields::new()
+            .names(HashMap::from([(0, "peak_a".to_string())]))
+            .scores(HashMap::from([(0, PeakScores::new(Some(12.4), Some(3.5), Some(7.25), None))]));
+        assert_eq!(
+            record_fields.record_line(&peak, "chr1", 10),
+            "chr1\t1003\t1998\tpeak_a\t12\t.\t3.5\t7.25\t0\t497\n"
+        );
+        assert_eq!(record_fields.record_line(&peak, "chr1", 3), "chr1\t1003\t1998\n");
+        let record_fields = record_fields.strands(HashMap::from([(0, Strand::Reverse)]));
+        assert_eq!(
+            record_fields.record_line(&peak, "chr1", 6),
+            "chr1\t1003\t1998\tpeak_a\t12\t-\n"
+        );
+        assert_eq!(record_fields.record_line(&peak, "chr1", 5), "chr1\t1003\t1998\tpeak_a\t12\n");
+    }
+
+    #[test]
+    fn test_write_peaks_to_bed_replaces_output() {
+        let output_path = test_output().join("test_write_peaks_to_bed_replaces_output.bed");
+        std::fs::write(&output_path, "outdated content that is longer than the new content\n")
+            .unwrap();
+        let peaks = HashMap::from([(
+            Chromosome::from("chr1"),
+            vec![PeakData::new(0, 45u64, 98u64, 55u64).unwrap()],
+        )]);
+        write_peaks_to_bed(&output_path, &peaks, 3).unwrap();
+        assert_eq!(read_to_string(&output_path).unwrap(), "chr1\t45\t98\n");
+        assert!(
+            !test_output()
+                .join(".test_write_peaks_to_bed_replaces_output.bed.tmp")
+                .exists()
+        );
+        std::fs::remove_file(output_path).unwrap();
+    }
+
+    #[test]
+    fn test_output_digest() {
+        let mut chunked = OutputDigest::default();
+        chunked.update(b"chr1\t45");
+        chunked.update(b"\t98\nchr2\t1\t5\n");
+        let mut records = OutputDigest::default();
+        records.update(b"chr1\t45\t98\n");
+        records.update(b"chr2\t1\t5\n");
+        assert_eq!(chunked, records);
+        assert_eq!(records.records, 2);
+        assert_eq!(records.bytes, 20);
+        let mut modified = OutputDigest::default();
+        modified.update(b"chr1\t45\t98\nchr2\t1\t6\n");
+        assert_ne!(modified.checksum, records.checksum);
+    }
+
+    #[test]
+    fn test_verify_output() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_verify_output.bed");
+        let content = b"chr1\t45\t98\nchr2\t1\t5\n";
+        let mut expected = OutputDigest::default();
+        expected.update(content);
+        std::fs::write(&output_path, content).unwrap();
+        assert!(verify_output(&output_path, expected).is_ok());
+        std::fs::write(&output_path, &content[..12]).unwrap();
+        let truncated = verify_output(&output_path, expected).unwrap_err();
+        assert_eq!(truncated.error_type(), ApplicationErrorType::OutputOperationError);
+        std::fs::write(&output_path, b"chr1\t45\t98\nchr2\t1\t6\n").unwrap();
+        assert!(verify_output(&output_path, expected).is_err());
+        std::fs::remove_file(output_path).unwrap();
+    }
+
+    #[test]
+    fn test_peaks_to_bed_buffer() {
+        let n_fields = 10;
+        let output_path = test_output().join("test_peaks_to_bed_buffer.bed");
+        let peaks = HashMap::from([
+            (
+                Chromosome::from("chr1"),
+                vec![
+                    PeakData::new(10, 455u64, 983u64, 554u64).unwrap(),
+                    PeakData::new(0, 45u64, 98u64, 55u64).unwrap(),
+                ],
+            ),
+            (
+                Chromosome::from("chr4"),
+                vec![PeakData::new(109, 4568u64, 9786u64, 5573u64).unwrap()],
+            ),
+        ]);
+        write_peaks_to_bed(&output_path, &peaks, n_fields).unwrap();
+        assert_eq!(peaks_to_bed_buffer(&peaks, n_fields), std::fs::read(&output_path).unwrap());
+        std::fs::remove_file(output_path).unwrap();
+    }
 }
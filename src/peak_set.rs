@@ -0,0 +1,538 @@
+//! This module defines a collection of genomic peaks supporting region set operations.
+
+use std::collections::HashMap;
+
+use crate::{
+    chromosome::Chromosome,
+    compare::OverlapCriteria,
+    interval_tree::IntervalTree,
+    peaks::{PeakBin, PeakData, sort_peaks_by_position},
+};
+
+/// The name of the chromosome column in tabular representations of a peak set.
+pub const CHROMOSOME_COLUMN: &str = "chromosome";
+/// The name of the peak identifier column in tabular representations of a peak set.
+pub const ID_COLUMN: &str = "id";
+/// The name of the start coordinate column in tabular representations of a peak set.
+pub const START_COLUMN: &str = "start";
+/// The name of the end coordinate column in tabular representations of a peak set.
+pub const END_COLUMN: &str = "end";
+/// The name of the summit coordinate column in tabular representations of a peak set.
+pub const SUMMIT_COLUMN: &str = "summit";
+
+/// A set of genomic peaks on multiple chromosomes.
+/// The peaks of each chromosome are kept sorted by their coordinates
+/// and indexed in an [`IntervalTree`] for fast overlap queries.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct PeakSet {
+    peaks: HashMap<Chromosome, IntervalTree<PeakData>>,
+}
+
+impl PeakSet {
+    /// Creates an empty peak set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts a peak into the set.
+    ///
+    /// # Parameters
+    ///
+    /// * `chromosome` - the chromosome the peak is located on
+    /// * `peak` - the peak to insert
+    pub fn insert(&mut self, chromosome: Chromosome, peak: PeakData) {
+        self.peaks.entry(chromosome).or_default().insert(peak);
+    }
+
+    /// Returns the total number of peaks in the set.
+    pub fn len(&self) -> usize {
+        self.peaks.values().map(IntervalTree::len).sum()
+    }
+
+    /// Returns `true` if the set does not contain any peaks.
+    pub fn is_empty(&self) -> bool {
+        self.peaks.values().all(IntervalTree::is_empty)
+    }
+
+    /// Returns all chromosomes containing peaks sorted by name.
+    pub fn chromosomes(&self) -> Vec<Chromosome> {
+        let mut chromosomes: Vec<Chromosome> = self
+            .peaks
+            .iter()
+            .filter(|(_, peaks)| !peaks.is_empty())
+            .map(|(chromosome, _)| *chromosome)
+            .collect();
+        chromosomes.sort();
+        chromosomes
+    }
+
+    /// Returns the peaks of the specified chromosome sorted by their coordinates.
+    ///
+    /// # Parameters
+    ///
+    /// * `chromosome` - the chromosome to return the peaks for
+    pub fn peaks(&self, chromosome: Chromosome) -> &[PeakData] {
+        self.peaks
+            .get(&chromosome)
+            .map(IntervalTree::items)
+            .unwrap_or_default()
+    }
+
+    /// Returns all peaks overlapping the specified region sorted by their coordinates.
+    ///
+    /// # Parameters
+    ///
+    /// * `chromosome` - the chromosome of the query region
+    /// * `start` - the start of the query region
+    /// * `end` - the end of the query region (inclusive)
+    pub fn overlapping(&self, chromosome: Chromosome, start: u64, end: u64) -> Vec<&PeakData> {
+        self.peaks
+            .get(&chromosome)
+            .map(|tree| tree.overlapping(start, end))
+            .unwrap_or_default()
+    }
+
+    /// Returns the peaks overlapping each of the specified regions.
+    /// The results are returned in the same order as the queries.
+    ///
+    /// # Parameters
+    ///
+    /// * `queries` - the query regions as chromosome, start and (inclusive) end
+    pub fn overlapping_batch(&self, queries: &[(Chromosome, u64, u64)]) -> Vec<Vec<&PeakData>> {
+        queries
+            .iter()
+            .map(|(chromosome, start, end)| self.overlapping(*chromosome, *start, *end))
+            .collect()
+    }
+
+    /// Returns an iterator over all peaks sorted by chromosome and coordinates.
+    pub fn iter(&self) -> impl Iterator<Item = (Chromosome, &PeakData)> {
+        self.chromosomes().into_iter().flat_map(move |chromosome| {
+            self.peaks(chromosome)
+                .iter()
+                .map(move |peak| (chromosome, peak))
+        })
+    }
+
+    /// Returns the union of both sets containing all peaks of either set.
+    ///
+    /// # Parameters
+    ///
+    /// * `other` - the set to unite with
+    pub fn union(&self, other: &PeakSet) -> PeakSet {
+        let mut union: HashMap<Chromosome, Vec<PeakData>> = self.clone().into();
+        for (chromosome, peaks) in &other.peaks {
+            union
+                .entry(*chromosome)
+                .or_default()
+                .extend_from_slice(peaks.items());
+        }
+        union.into()
+    }
+
+    /// Returns a set in which all overlapping and adjacent peaks are merged into a single peak
+    /// spanning all merged peaks. The summit of a merged peak is set to its centre if multiple
+    /// peaks were merged.
+    pub fn merge(&self) -> PeakSet {
+        self.peaks
+            .iter()
+            .map(|(chromosome, peaks)| (*chromosome, merged_regions(peaks.items())))
+            .collect::<HashMap<Chromosome, Vec<PeakData>>>()
+            .into()
+    }
+
+    /// Returns the regions of the peaks of this set that are covered by peaks of the other set.
+    /// Peaks partially covered by the other set are trimmed to the covered fragments, which keep
+    /// the identifier of the original peak.
+    ///
+    /// # Parameters
+    ///
+    /// * `other` - the set to intersect with
+    pub fn intersection(&self, other: &PeakSet) -> PeakSet {
+        self.fragment(other, true)
+    }
+
+    /// Returns the regions of the peaks of this set that are not covered by peaks of the other set.
+    /// Peaks partially covered by the other set are trimmed to the uncovered fragments, which keep
+    /// the identifier of the original peak.
+    ///
+    /// # Parameters
+    ///
+    /// * `other` - the set to subtract
+    pub fn subtraction(&self, other: &PeakSet) -> PeakSet {
+        self.fragment(other, false)
+    }
+
+    /// Returns the complete peaks of this set that overlap at least one peak of the other set
+    /// according to the criteria. The peaks of this set are the reference peaks of the criteria,
+    /// so the minimum overlap fraction refers to them unless the criteria are reciprocal.
+    ///
+    /// # Parameters
+    ///
+    /// * `other` - the set to check for overlaps
+    /// * `criteria` - the criteria for overlapping peaks
+    pub fn overlapped_by(&self, other: &PeakSet, criteria: &OverlapCriteria) -> PeakSet {
+        self.select_overlapped(other, criteria, true)
+    }
+
+    /// Returns the complete peaks of this set that do not overlap any peak of the other set
+    /// according to the criteria. This is the complement of [`PeakSet::overlapped_by`].
+    ///
+    /// # Parameters
+    ///
+    /// * `other` - the set to check for overlaps
+    /// * `criteria` - the criteria for overlapping peaks
+    pub fn not_overlapped_by(&self, other: &PeakSet, criteria: &OverlapCriteria) -> PeakSet {
+        self.select_overlapped(other, criteria, false)
+    }
+
+    /// Selects the peaks of this set that either overlap or do not overlap the other set.
+    ///
+    /// # Parameters
+    ///
+    /// * `other` - the set to check for overlaps
+    /// * `criteria` - the criteria for overlapping peaks
+    /// * `overlapped` - `true` if the overlapping peaks should be retained, `false` if the non-overlapping ones should be retained
+    fn select_overlapped(
+        &self,
+        other: &PeakSet,
+        criteria: &OverlapCriteria,
+        overlapped: bool,
+    ) -> PeakSet {
+        self.iter()
+            .filter(|(chromosome, peak)| {
+                other
+                    .overlapping(*chromosome, peak.start(), peak.end())
+                    .into_iter()
+                    .any(|other_peak| criteria.matches(peak, other_peak))
+                    == overlapped
+            })
+            .map(|(chromosome, peak)| (chromosome, *peak))
+            .collect()
+    }
+
+    /// Splits the peaks of this set into the fragments that are either covered or
+    /// not covered by the other set.
+    ///
+    /// # Parameters
+    ///
+    /// * `other` - the set defining the covered regions
+    /// * `covered` - `true` if the covered fragments should be retained, `false` if the uncovered ones should be retained
+    fn fragment(&self, other: &PeakSet, covered: bool) -> PeakSet {
+        let mut fragments = HashMap::new();
+        for (chromosome, peaks) in &self.peaks {
+            let regions = merged_regions(other.peaks(*chromosome));
+            let chromosome_fragments: Vec<PeakData> = peaks
+                .items()
+                .iter()
+                .flat_map(|peak| {
+                    let covered_ranges = covered_ranges(peak, &regions);
+                    let ranges = if covered {
+                        covered_ranges
+                    } else {
+                        uncovered_ranges(peak, &covered_ranges)
+                    };
+                    ranges
+                        .into_iter()
+                        .map(|(start, end)| fragment_peak(peak, start, end))
+                })
+                .collect();
+            if !chromosome_fragments.is_empty() {
+                fragments.insert(*chromosome, chromosome_fragments);
+            }
+        }
+        fragments.into()
+    }
+}
+
+impl From<HashMap<Chromosome, Vec<PeakData>>> for PeakSet {
+    fn from(peaks: HashMap<Chromosome, Vec<PeakData>>) -> Self {
+        Self {
+            peaks: peaks
+                .into_iter()
+                .map(|(chromosome, peaks)| {
+                    (chromosome, IntervalTree::from_sorted(sort_peaks_by_position(peaks)))
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<PeakSet> for HashMap<Chromosome, Vec<PeakData>> {
+    fn from(value: PeakSet) -> Self {
+        value
+            .peaks
+            .into_iter()
+            .map(|(chromosome, peaks)| (chromosome, peaks.into()))
+            .collect()
+    }
+}
+
+impl FromIterator<(Chromosome, PeakData)> for PeakSet {
+    fn from_iter<T: IntoIterator<Item = (Chromosome, PeakData)>>(iter: T) -> Self {
+        let mut peaks: HashMap<Chromosome, Vec<PeakData>> = HashMap::new();
+        for (chromosome, peak) in iter {
+            peaks.entry(chromosome).or_default().push(peak);
+        }
+        peaks.into()
+    }
+}
+
+/// Merges overlapping and adjacent peaks into disjoint regions sorted by position.
+///
+/// # Parameters
+///
+/// * `peaks` - the peaks to merge
+fn merged_regions(peaks: &[PeakData]) -> Vec<PeakData> {
+    PeakBin::bin_peaks(peaks.to_vec())
+        .into_iter()
+        .map(|bin| {
+            if bin.peaks().len() == 1 {
+                bin.peaks()[0]
+            } else {
+                PeakData::new(
+                    bin.peaks()[0].id(),
+                    bin.start(),
+                    bin.end(),
+                    bin.start().midpoint(bin.end()),
+                )
+                .expect("The merged peak must be valid as it was derived from a valid bin.")
+            }
+        })
+        .collect()
+}
+
+/// Returns the ranges of the peak covered by the specified disjoint and sorted regions.
+///
+/// # Parameters
+///
+/// * `peak` - the peak to check for coverage
+/// * `regions` - the disjoint regions sorted by position
+fn covered_ranges(peak: &PeakData, regions: &[PeakData]) -> Vec<(u64, u64)> {
+    let first_region = regions.partition_point(|region| region.end() < peak.start());
+    regions[first_region..]
+        .iter()
+        .take_while(|region| region.start() <= peak.end())
+        .map(|region| (region.start().max(peak.start()), region.end().min(peak.end())))
+        .collect()
+}
+
+/// Returns the ranges of the peak not covered by the specified disjoint and sorted ranges.
+///
+/// # Parameters
+///
+/// * `peak` - the peak to fragment
+/// * `covered_ranges` - the disjoint and sorted ranges within the peak that are covered
+fn uncovered_ranges(peak: &PeakData, covered_ranges: &[(u64, u64)]) -> Vec<(u64, u64)> {
+    let mut ranges = Vec::new();
+    let mut next_start = Some(peak.start());
+    for (covered_start, covered_end) in covered_ranges {
+        if let Some(start) = next_start
+            && start < *covered_start
+        {
+            ranges.push((start, covered_start - 1));
+        }
+        next_start = covered_end.checked_add(1);
+    }
+    if let Some(start) = next_start
+        && start <= peak.end()
+    {
+        ranges.push((start, peak.end()));
+    }
+    ranges
+}
+
+/// Creates a fragment of the specified peak. The summit is retained if it is located
+/// within the fragment and otherwise set to the fragment centre.
+///
+/// # Parameters
+///
+/// * `peak` - the peak to create a fragment of
+/// * `start` - the start of the fragment
+/// * `end` - the end of the fragment (inclusive)
+fn fragment_peak(peak: &PeakData, start: u64, end: u64) -> PeakData {
+    let summit = if (start..=end).contains(&peak.summit()) {
+        peak.summit()
+    } else {
+        start.midpoint(end)
+    };
+    PeakData::new(peak.id(), start, end, summit)
+        .expect("The fragment must be valid as it was derived from a valid peak.")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::peak_set;
+
+    use super::*;
+
+    /// Returns the coordinates of all peaks of the set.
+    fn coordinates(set: &PeakSet) -> Vec<(&'static str, u64, u64, u64)> {
+        set.iter()
+            .map(|(chromosome, peak)| (chromosome.name(), peak.start(), peak.end(), peak.summit()))
+            .collect()
+    }
+
+    #[test]
+    fn test_peak_set_insert() {
+        let mut set = PeakSet::new();
+        assert!(set.is_empty());
+        let chromosome = Chromosome::from("chr1");
+        set.insert(chromosome, PeakData::new(0, 50u64, 60u64, 55u64).unwrap());
+        set.insert(chromosome, PeakData::new(1, 10u64, 20u64, 15u64).unwrap());
+        set.insert(Chromosome::from("chr2"), PeakData::new(2, 1u64, 2u64, 1u64).unwrap());
+        set.insert(chromosome, PeakData::new(3, 30u64, 40u64, 35u64).unwrap());
+        assert_eq!(set.len(), 4);
+        assert!(!set.is_empty());
+        assert_eq!(
+            coordinates(&set),
+            vec![
+                ("chr1", 10, 20, 15),
+                ("chr1", 30, 40, 35),
+                ("chr1", 50, 60, 55),
+                ("chr2", 1, 2, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_peak_set_union() {
+        let set_a = peak_set(&[("chr1", 10, 20, 15), ("chr1", 50, 60, 55)]);
+        let set_b = peak_set(&[("chr1", 15, 30, 20)]).union(&peak_set(&[("chr2", 1, 5, 3)]));
+        let union = set_a.union(&set_b);
+        assert_eq!(union.len(), 4);
+        assert_eq!(
+            coordinates(&union),
+            vec![
+                ("chr1", 10, 20, 15),
+                ("chr1", 15, 30, 20),
+                ("chr1", 50, 60, 55),
+                ("chr2", 1, 5, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_peak_set_merge() {
+        let set = peak_set(&[
+            ("chr1", 10, 20, 15),
+            ("chr1", 21, 30, 25),
+            ("chr1", 50, 60, 55),
+            ("chr1", 55, 58, 57),
+        ]);
+        assert_eq!(coordinates(&set.merge()), vec![("chr1", 10, 30, 20), ("chr1", 50, 60, 55)]);
+    }
+
+    #[test]
+    fn test_peak_set_intersection() {
+        let set_a = peak_set(&[
+            ("chr1", 10, 100, 20),
+            ("chr1", 200, 300, 250),
+            ("chr1", 400, 500, 450),
+        ]);
+        let set_b = peak_set(&[
+            ("chr1", 50, 60, 55),
+            ("chr1", 70, 80, 75),
+            ("chr1", 250, 350, 300),
+        ])
+        .union(&peak_set(&[("chr2", 400, 500, 450)]));
+        assert_eq!(
+            coordinates(&set_a.intersection(&set_b)),
+            vec![
+                ("chr1", 50, 60, 55),
+                ("chr1", 70, 80, 75),
+                ("chr1", 250, 300, 250)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_peak_set_subtraction() {
+        let set_a = peak_set(&[
+            ("chr1", 10, 100, 20),
+            ("chr1", 200, 300, 250),
+            ("chr1", 400, 500, 450),
+        ])
+        .union(&peak_set(&[("chr2", 1, 5, 3)]));
+        let set_b = peak_set(&[
+            ("chr1", 50, 60, 55),
+            ("chr1", 70, 80, 75),
+            ("chr1", 150, 350, 300),
+        ]);
+        assert_eq!(
+            coordinates(&set_a.subtraction(&set_b)),
+            vec![
+                ("chr1", 10, 49, 20),
+                ("chr1", 61, 69, 65),
+                ("chr1", 81, 100, 90),
+                ("chr1", 400, 500, 450),
+                ("chr2", 1, 5, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_peak_set_overlapped_by() {
+        let set_a = peak_set(&[
+            ("chr1", 10, 100, 20),
+            ("chr1", 200, 300, 250),
+            ("chr1", 400, 500, 450),
+        ])
+        .union(&peak_set(&[("chr2", 1, 5, 3)]));
+        let set_b = peak_set(&[("chr1", 50, 60, 55), ("chr1", 150, 350, 300)]);
+        let any_overlap = OverlapCriteria::default();
+        assert_eq!(
+            coordinates(&set_a.overlapped_by(&set_b, &any_overlap)),
+            vec![("chr1", 10, 100, 20), ("chr1", 200, 300, 250)]
+        );
+        assert_eq!(
+            coordinates(&set_a.not_overlapped_by(&set_b, &any_overlap)),
+            vec![("chr1", 400, 500, 450), ("chr2", 1, 5, 3)]
+        );
+        let half_overlap = OverlapCriteria::new(0.5, false).unwrap();
+        assert_eq!(
+            coordinates(&set_a.overlapped_by(&set_b, &half_overlap)),
+            vec![("chr1", 200, 300, 250)]
+        );
+        let reciprocal = OverlapCriteria::new(0.6, true).unwrap();
+        assert_eq!(
+            coordinates(&set_a.overlapped_by(&set_b, &reciprocal)),
+            Vec::<(&str, u64, u64, u64)>::new()
+        );
+    }
+
+    #[test]
+    fn test_peak_set_overlapping() {
+        let set = peak_set(&[
+            ("chr1", 10, 100, 20),
+            ("chr1", 200, 300, 250),
+            ("chr1", 90, 210, 150),
+        ])
+        .union(&peak_set(&[("chr2", 10, 100, 20)]));
+        let chr1 = Chromosome::from("chr1");
+        let starts = |peaks: Vec<&PeakData>| -> Vec<u64> {
+            peaks.into_iter().map(PeakData::start).collect()
+        };
+        assert_eq!(starts(set.overlapping(chr1, 95, 95)), vec![10, 90]);
+        assert_eq!(starts(set.overlapping(chr1, 0, 9)), Vec::<u64>::new());
+        assert_eq!(starts(set.overlapping(chr1, 210, 1000)), vec![90, 200]);
+        assert_eq!(starts(set.overlapping(Chromosome::from("chr3"), 0, 1000)), Vec::<u64>::new());
+        let batch = set.overlapping_batch(&[
+            (chr1, 0, 1000),
+            (Chromosome::from("chr2"), 100, 100),
+            (chr1, 101, 199),
+        ]);
+        assert_eq!(batch.len(), 3);
+        assert_eq!(starts(batch[0].clone()), vec![10, 90, 200]);
+        assert_eq!(starts(batch[1].clone()), vec![10]);
+        assert_eq!(starts(batch[2].clone()), vec![90]);
+    }
+
+    #[test]
+    fn test_uncovered_ranges_bounds() {
+        let peak = PeakData::new(0, 0u64, u64::MAX, 5u64).unwrap();
+        assert_eq!(uncovered_ranges(&peak, &[(0, 10)]), vec![(11, u64::MAX)]);
+        assert_eq!(uncovered_ranges(&peak, &[(10, u64::MAX)]), vec![(0, 9)]);
+        assert!(uncovered_ranges(&peak, &[(0, u64::MAX)]).is_empty());
+    }
+}
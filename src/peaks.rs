@@ -1,28 +1,60 @@
 //! This module defines operations on genomic peak data.
 
-use crate::{
-    arguments::CommandLineArguments,
-    error::{ApplicationError, ApplicationErrorType},
-};
+use crate::error::{ApplicationError, ApplicationErrorType};
 use getset::{CopyGetters, Getters};
+use serde::{Deserialize, Serialize};
 
-#[derive(CopyGetters, Clone, Copy, PartialEq, Eq, Debug)]
+/// The log target of this module.
+const LOG_TARGET: &str = "peaks";
+
+/// An unsigned integer type usable as genomic coordinate of [`PeakData`].
+/// Narrower coordinate types reduce the memory footprint of large peak collections,
+/// while [`u64`] is used by default and by the consensus peak algorithms.
+pub trait Coordinate:
+    Copy
+    + Ord
+    + std::hash::Hash
+    + std::fmt::Debug
+    + std::fmt::Display
+    + Into<u64>
+    + TryFrom<u64>
+    + Serialize
+    + for<'de> Deserialize<'de>
+    + Send
+    + Sync
+    + 'static
+{
+}
+
+impl Coordinate for u32 {}
+
+impl Coordinate for u64 {}
+
+#[derive(CopyGetters, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(try_from = "PeakDataRecord<C>", bound(deserialize = "C: Coordinate"))]
 /// Data representing a peak region on genomic data.
-pub struct PeakData {
+/// The coordinate type defaults to [`u64`], see [`Coordinate`] for narrower alternatives.
+pub struct PeakData<C: Coordinate = u64> {
     /// The unique identifier of the peak.
     #[getset(get_copy = "pub")]
     id: usize,
     /// The genomic start coordinate of the peak region.
     #[getset(get_copy = "pub")]
-    start: u64,
+    start: C,
     /// The genomic end coordinate of the peak region (inclusive).
     #[getset(get_copy = "pub")]
-    end: u64,
+    end: C,
     /// The genomic coordinate of the peak summit.
     #[getset(get_copy = "pub")]
-    summit: u64,
+    summit: C,
 }
 
+/// Peak data using 32 bit coordinates, which suffice for all chromosomes of common reference genomes.
+pub type PeakData32 = PeakData<u32>;
+
+/// Peak data using 64 bit coordinates.
+pub type PeakData64 = PeakData<u64>;
+
 impl PeakData {
     /// Creates a new genomic peak region.
     ///
@@ -43,28 +75,50 @@ impl PeakData {
         end: EndType,
         summit: SummitType,
     ) -> Result<Self, ApplicationError> {
-        let start = start.into();
-        let end = end.into();
-        let summit = summit.into();
+        Self::from_coordinates(id, start.into(), end.into(), summit.into())
+    }
+}
 
+impl<C: Coordinate> PeakData<C> {
+    /// Creates a new genomic peak region using the coordinate type `C`.
+    ///
+    /// # Parameters
+    ///
+    /// * `id`: the unique identifier of the peak
+    /// * `start`: the start location of the peak region
+    /// * `end`: the end location of the peak region (inclusive)
+    /// * `summit`: the peak summit within the peak region
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the summit is without the defined region or
+    /// if the start coordinate is greater than the end coordinate.
+    pub fn from_coordinates(
+        id: usize,
+        start: C,
+        end: C,
+        summit: C,
+    ) -> Result<Self, ApplicationError> {
         if start > end {
             return Err(ApplicationError::new(
-                ApplicationErrorType::InputDataError,
+                ApplicationErrorType::ValidationError,
                 format!(
                     "The end coordinate {} of peak {} is smaller than the start coordinate {}.",
                     end, id, start
                 ),
-            ));
+            )
+            .with_hint("Check that the start and end coordinates are not swapped."));
         }
 
         if summit < start || summit > end {
             return Err(ApplicationError::new(
-                ApplicationErrorType::InputDataError,
+                ApplicationErrorType::ValidationError,
                 format!(
                     "The summit {} of peak {} is not within the peak region [{}, {}].",
                     summit, id, start, end
                 ),
-            ));
+            )
+            .with_hint("The summit must lie between the start and end coordinate of the peak."));
         }
 
         Ok(Self {
@@ -77,17 +131,82 @@ impl PeakData {
 
     /// Returns the length of the genomic peak region.
     pub fn length(&self) -> u64 {
-        self.end() + 1 - self.start()
+        self.end().into() + 1 - self.start().into()
+    }
+
+    /// Converts the peak to a different coordinate type.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a coordinate cannot be represented by the target coordinate type.
+    pub fn try_into_coordinates<T: Coordinate>(self) -> Result<PeakData<T>, ApplicationError> {
+        let convert = |coordinate: C| {
+            T::try_from(coordinate.into()).map_err(|_| {
+                ApplicationError::new(
+                    ApplicationErrorType::ValidationError,
+                    format!(
+                        "The coordinate {} of peak {} exceeds the range of the target coordinate type.",
+                        coordinate, self.id
+                    ),
+                )
+            })
+        };
+        Ok(PeakData {
+            id: self.id,
+            start: convert(self.start)?,
+            end: convert(self.end)?,
+            summit: convert(self.summit)?,
+        })
+    }
+}
+
+impl From<PeakData32> for PeakData64 {
+    fn from(value: PeakData32) -> Self {
+        Self {
+            id: value.id,
+            start: value.start.into(),
+            end: value.end.into(),
+            summit: value.summit.into(),
+        }
+    }
+}
+
+impl TryFrom<PeakData64> for PeakData32 {
+    type Error = ApplicationError;
+
+    fn try_from(value: PeakData64) -> Result<Self, Self::Error> {
+        value.try_into_coordinates()
+    }
+}
+
+/// The unvalidated representation of [`PeakData`] used for deserialisation.
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "C: Coordinate"))]
+struct PeakDataRecord<C: Coordinate> {
+    id: usize,
+    start: C,
+    end: C,
+    summit: C,
+}
+
+impl<C: Coordinate> TryFrom<PeakDataRecord<C>> for PeakData<C> {
+    type Error = ApplicationError;
+
+    fn try_from(value: PeakDataRecord<C>) -> Result<Self, Self::Error> {
+        PeakData::from_coordinates(value.id, value.start, value.end, value.summit)
     }
 }
 
 #[derive(CopyGetters, Getters, PartialEq, Debug)]
 /// A bin containing overlapping or adjacent peaks.
 pub struct PeakBin {
+    /// The smallest start coordinate of all peaks in the bin.
     #[getset(get_copy = "pub")]
     start: u64,
+    /// The largest end coordinate of all peaks in the bin (inclusive).
     #[getset(get_copy = "pub")]
     end: u64,
+    /// The peaks contained in the bin.
     #[getset(get = "pub")]
     peaks: Vec<PeakData>,
 }
@@ -142,22 +261,28 @@ impl PeakBin {
     /// # Parameters
     ///
     /// * `peaks` - the peaks to group into bins
-    pub fn bin_peaks(mut peaks: Vec<PeakData>) -> Vec<Self> {
-        log::info!("Creating peak bins from {} peaks.", peaks.len());
-        log::debug!("Sorting peaks by start position.");
-        peaks.sort_by(|a, b| a.start().cmp(&b.start()));
+    pub fn bin_peaks(peaks: Vec<PeakData>) -> Vec<Self> {
+        log::info!(target: LOG_TARGET, "Creating peak bins from {} peaks.", peaks.len());
+        log::debug!(target: LOG_TARGET, "Sorting peaks by start position.");
+        let peaks = sort_peaks_by_position(peaks);
         let mut bins: Vec<PeakBin> = Vec::new();
-        log::debug!("Inserting peaks...");
+        log::debug!(target: LOG_TARGET, "Inserting peaks...");
         for peak in peaks {
-            log::debug!("Inserting peak {:?}...", peak);
+            log::debug!(target: LOG_TARGET, "Inserting peak {:?}...", peak);
             if let Some(current_bin) = bins.last_mut() {
-                log::debug!("Checking bin [{}, {}]...", current_bin.start(), current_bin.end());
+                log::debug!(
+                    target: LOG_TARGET,
+                    "Checking bin [{}, {}]...",
+                    current_bin.start(),
+                    current_bin.end()
+                );
                 if let Some(peak) = current_bin.try_insert(peak) {
                     // Creates a new bin if the insertion failed into the old one.
-                    log::debug!("Creating new peak bin for peak {:?}.", peak);
+                    log::debug!(target: LOG_TARGET, "Creating new peak bin for peak {:?}.", peak);
                     bins.push(PeakBin::new(peak));
                 } else {
                     log::debug!(
+                        target: LOG_TARGET,
                         "Inserted peak into bin [{}, {}]",
                         current_bin.start(),
                         current_bin.end()
@@ -165,7 +290,7 @@ impl PeakBin {
                 }
             } else {
                 // Creates an initial bin if there are none yet.
-                log::debug!("Creating initial peak bin...");
+                log::debug!(target: LOG_TARGET, "Creating initial peak bin...");
                 bins.push(PeakBin::new(peak));
             }
         }
@@ -179,7 +304,47 @@ impl From<PeakBin> for Vec<PeakData> {
     }
 }
 
-#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+#[derive(Getters, CopyGetters, Clone, PartialEq, Eq, Debug)]
+/// A consensus peak together with the identifiers of the raw peaks it was created from.
+pub struct TrackedConsensusPeak {
+    /// The consensus peak.
+    #[getset(get_copy = "pub")]
+    peak: PeakData,
+    /// The identifiers of the raw peaks the consensus peak was created from.
+    #[getset(get = "pub")]
+    raw_peak_ids: Vec<usize>,
+}
+
+impl TrackedConsensusPeak {
+    /// Creates a new consensus peak tracking its raw peaks.
+    ///
+    /// # Parameters
+    ///
+    /// * `peak` - the consensus peak
+    /// * `raw_peak_ids` - the identifiers of the raw peaks the consensus peak was created from
+    pub fn new(peak: PeakData, raw_peak_ids: Vec<usize>) -> Self {
+        Self { peak, raw_peak_ids }
+    }
+
+    /// Returns the tracked consensus peak with the specified identifier.
+    ///
+    /// # Parameters
+    ///
+    /// * `id` - the new identifier of the consensus peak
+    pub fn with_id(mut self, id: usize) -> Self {
+        self.peak.id = id;
+        self
+    }
+}
+
+impl From<TrackedConsensusPeak> for PeakData {
+    fn from(value: TrackedConsensusPeak) -> Self {
+        value.peak
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
 /// A general definition of an algorithm that generates a set of consensus
 /// [`PeakData`] from raw input peaks.
 pub enum ConsensusPeakAlgorithm {
@@ -195,6 +360,15 @@ pub enum ConsensusPeakAlgorithm {
     /// Creates consensus peaks by harmonising peaks based on ther summit
     /// and merging overlapping and adjacent ones.
     Harmonised,
+    /// A minimum overlap algorithm analogous to `bedtools intersect -f -r`.
+    /// Only peaks that overlap by at least a minimum fraction of their lengths
+    /// are merged to create consensus peaks.
+    Overlap,
+    /// A multiple sample peak calling (MSPC) like algorithm proposed by
+    /// [Jalili 2015](https://doi.org/10.1093/bioinformatics/btv293).
+    /// Only peaks whose p-values combined with the p-values of overlapping peaks of other
+    /// samples reach a combined stringency are merged to create consensus peaks.
+    Mspc,
 }
 
 impl ConsensusPeakAlgorithm {
@@ -202,42 +376,304 @@ impl ConsensusPeakAlgorithm {
     /// Returns and error if the consensus finding failed.
     ///
     /// `peaks` - the raw input peaks to create consensus peaks from
-    /// `algorithm_arguments` - the passed [`CommandLineArguments`] to customise the algorithm
+    /// `algorithm_arguments` - the [`ConsensusParameters`] to customise the algorithm
     pub fn consensus_peaks(
         &self,
         peaks: Vec<PeakData>,
-        algorithm_arguments: &CommandLineArguments,
+        algorithm_arguments: &ConsensusParameters,
     ) -> Result<Vec<PeakData>, ApplicationError> {
-        log::debug!("Generating consensus peaks using algorithm: {}", self);
+        Ok(self
+            .tracked_consensus_peaks(peaks, algorithm_arguments)?
+            .into_iter()
+            .map(PeakData::from)
+            .collect())
+    }
+
+    /// Creates consensus peaks like [`consensus_peaks`](Self::consensus_peaks), but keeps
+    /// track of the raw peaks each consensus peak was created from.
+    /// Returns and error if the consensus finding failed.
+    ///
+    /// `peaks` - the raw input peaks to create consensus peaks from
+    /// `algorithm_arguments` - the [`ConsensusParameters`] to customise the algorithm
+    pub fn tracked_consensus_peaks(
+        &self,
+        peaks: Vec<PeakData>,
+        algorithm_arguments: &ConsensusParameters,
+    ) -> Result<Vec<TrackedConsensusPeak>, ApplicationError> {
+        log::debug!(target: LOG_TARGET, "Generating consensus peaks using algorithm: {}", self);
         match self {
-            ConsensusPeakAlgorithm::Gipfelkreuzer => Ok(gipfelkreuzer::consensus_peaks(
+            ConsensusPeakAlgorithm::Gipfelkreuzer => Ok(gipfelkreuzer::tracked_consensus_peaks(
                 peaks,
                 algorithm_arguments.max_merge_iterations(),
                 algorithm_arguments.min_peaks_per_consensus(),
+                algorithm_arguments.summit_tolerance(),
+                algorithm_arguments.coordinate_aggregation(),
+                algorithm_arguments.peak_weights().as_deref(),
+                algorithm_arguments.aggregation_backend(),
             )),
-            ConsensusPeakAlgorithm::Simple => {
-                simple::merge_peaks(peaks, algorithm_arguments.min_peaks_per_consensus())
-            },
-            ConsensusPeakAlgorithm::Harmonised => harmoniser::harmonised_consensus_peaks(
+            ConsensusPeakAlgorithm::Simple => simple::tracked_merge_peaks(
+                peaks,
+                algorithm_arguments.min_peaks_per_consensus(),
+                algorithm_arguments.max_gap(),
+            ),
+            ConsensusPeakAlgorithm::Harmonised => harmoniser::tracked_harmonised_consensus_peaks(
                 peaks,
                 algorithm_arguments.harmonising_distance(),
                 algorithm_arguments.min_peaks_per_consensus(),
             ),
+            ConsensusPeakAlgorithm::Overlap => overlap::tracked_overlap_consensus_peaks(
+                peaks,
+                algorithm_arguments.min_overlap(),
+                algorithm_arguments.reciprocal_overlap(),
+                algorithm_arguments.min_peaks_per_consensus(),
+            ),
+            ConsensusPeakAlgorithm::Mspc => mspc::tracked_mspc_consensus_peaks(
+                peaks,
+                algorithm_arguments
+                    .evidence()
+                    .as_deref()
+                    .unwrap_or(&mspc::PeakEvidence::new()),
+                algorithm_arguments.combined_stringency(),
+                algorithm_arguments.weak_threshold(),
+                algorithm_arguments.min_replicates(),
+                algorithm_arguments.min_peaks_per_consensus(),
+            ),
         }
     }
 }
 
+#[derive(CopyGetters, Getters, Clone, PartialEq, Debug)]
+/// The parameters customising the consensus peak algorithms.
+pub struct ConsensusParameters {
+    /// The maximum number of consensus peak merging iterations of the Gipfelkreuzer algorithm.
+    #[getset(get_copy = "pub")]
+    max_merge_iterations: usize,
+    /// The maximum distance between the summits of peaks merged by the Gipfelkreuzer algorithm.
+    /// If not set, peaks are merged if their summit lies within the consensus defining peak.
+    #[getset(get_copy = "pub")]
+    summit_tolerance: Option<u64>,
+    /// The way the coordinates of peaks merged by the Gipfelkreuzer algorithm are combined.
+    #[getset(get_copy = "pub")]
+    coordinate_aggregation: gipfelkreuzer::CoordinateAggregation,
+    /// The raw peak weights used for weighted coordinate aggregation by the Gipfelkreuzer algorithm.
+    #[getset(get = "pub")]
+    peak_weights: Option<std::sync::Arc<gipfelkreuzer::PeakWeights>>,
+    /// The implementation used by the Gipfelkreuzer algorithm to aggregate the peaks of a bin.
+    #[getset(get_copy = "pub")]
+    aggregation_backend: gipfelkreuzer::AggregationBackend,
+    /// The minimum number of raw peaks per consensus peak to consider it reproducible.
+    #[getset(get_copy = "pub")]
+    min_peaks_per_consensus: usize,
+    /// The distance from the summit that is used to generate peak start and end coordinates
+    /// by the harmonised algorithm.
+    #[getset(get_copy = "pub")]
+    harmonising_distance: u64,
+    /// The maximum number of bases between peaks merged by the simple algorithm.
+    #[getset(get_copy = "pub")]
+    max_gap: u64,
+    /// The minimum fraction of a peak that must be overlapped by another peak
+    /// to merge both peaks with the overlap algorithm.
+    #[getset(get_copy = "pub")]
+    min_overlap: f64,
+    /// If the minimum overlap fraction must be reached for both peaks
+    /// instead of only the shorter one.
+    #[getset(get_copy = "pub")]
+    reciprocal_overlap: bool,
+    /// The maximum combined p-value of peaks confirmed by the MSPC algorithm.
+    #[getset(get_copy = "pub")]
+    combined_stringency: f64,
+    /// The maximum p-value of peaks not considered background by the MSPC algorithm.
+    #[getset(get_copy = "pub")]
+    weak_threshold: f64,
+    /// The minimum number of samples supporting a peak confirmed by the MSPC algorithm.
+    #[getset(get_copy = "pub")]
+    min_replicates: usize,
+    /// The sample and p-value of the raw peaks required by the MSPC algorithm.
+    #[getset(get = "pub")]
+    evidence: Option<std::sync::Arc<mspc::PeakEvidence>>,
+}
+
+impl ConsensusParameters {
+    /// Creates a new set of consensus peak algorithm parameters.
+    ///
+    /// # Parameters
+    ///
+    /// * `max_merge_iterations` - the maximum number of merging iterations of the Gipfelkreuzer algorithm
+    /// * `min_peaks_per_consensus` - the minimum number of raw peaks required to form a consensus peak
+    /// * `harmonising_distance` - the distance from the summit that is considered a harmonised peak region
+    pub fn new(
+        max_merge_iterations: usize,
+        min_peaks_per_consensus: usize,
+        harmonising_distance: u64,
+    ) -> Self {
+        Self {
+            max_merge_iterations,
+            summit_tolerance: None,
+            coordinate_aggregation: gipfelkreuzer::CoordinateAggregation::default(),
+            peak_weights: None,
+            aggregation_backend: gipfelkreuzer::AggregationBackend::default(),
+            min_peaks_per_consensus,
+            harmonising_distance,
+            max_gap: 0,
+            min_overlap: overlap::DEFAULT_MIN_OVERLAP,
+            reciprocal_overlap: false,
+            combined_stringency: mspc::DEFAULT_COMBINED_STRINGENCY,
+            weak_threshold: mspc::DEFAULT_WEAK_THRESHOLD,
+            min_replicates: 1,
+            evidence: None,
+        }
+    }
+
+    /// Sets the maximum distance between the summits of peaks merged by the Gipfelkreuzer algorithm.
+    ///
+    /// # Parameters
+    ///
+    /// * `summit_tolerance` - the maximum summit distance in base pairs, if any
+    pub fn with_summit_tolerance(mut self, summit_tolerance: Option<u64>) -> Self {
+        self.summit_tolerance = summit_tolerance;
+        self
+    }
+
+    /// Sets the way the coordinates of peaks merged by the Gipfelkreuzer algorithm are combined.
+    ///
+    /// # Parameters
+    ///
+    /// * `coordinate_aggregation` - the way the coordinates are combined
+    /// * `peak_weights` - the raw peak weights used for weighted coordinate aggregation, if any
+    pub fn with_coordinate_aggregation(
+        mut self,
+        coordinate_aggregation: gipfelkreuzer::CoordinateAggregation,
+        peak_weights: Option<std::sync::Arc<gipfelkreuzer::PeakWeights>>,
+    ) -> Self {
+        self.coordinate_aggregation = coordinate_aggregation;
+        self.peak_weights = peak_weights;
+        self
+    }
+
+    /// Sets the implementation used by the Gipfelkreuzer algorithm to aggregate the peaks of a bin.
+    ///
+    /// # Parameters
+    ///
+    /// * `aggregation_backend` - the aggregation implementation
+    pub fn with_aggregation_backend(
+        mut self,
+        aggregation_backend: gipfelkreuzer::AggregationBackend,
+    ) -> Self {
+        self.aggregation_backend = aggregation_backend;
+        self
+    }
+
+    /// Sets the maximum number of bases between peaks merged by the simple algorithm.
+    ///
+    /// # Parameters
+    ///
+    /// * `max_gap` - the maximum gap in base pairs
+    pub fn with_max_gap(mut self, max_gap: u64) -> Self {
+        self.max_gap = max_gap;
+        self
+    }
+
+    /// Sets the minimum overlap parameters of the overlap algorithm.
+    ///
+    /// # Parameters
+    ///
+    /// * `min_overlap` - the minimum fraction of a peak that must be overlapped to merge peaks
+    /// * `reciprocal` - if the minimum overlap fraction must be reached for both peaks
+    pub fn with_min_overlap(mut self, min_overlap: f64, reciprocal: bool) -> Self {
+        self.min_overlap = min_overlap;
+        self.reciprocal_overlap = reciprocal;
+        self
+    }
+
+    /// Sets the parameters of the MSPC algorithm.
+    ///
+    /// # Parameters
+    ///
+    /// * `combined_stringency` - the maximum combined p-value of confirmed peaks
+    /// * `weak_threshold` - the maximum p-value of peaks that are not considered background
+    /// * `min_replicates` - the minimum number of samples supporting a confirmed peak
+    /// * `evidence` - the sample and p-value of the raw peaks
+    pub fn with_mspc(
+        mut self,
+        combined_stringency: f64,
+        weak_threshold: f64,
+        min_replicates: usize,
+        evidence: Option<std::sync::Arc<mspc::PeakEvidence>>,
+    ) -> Self {
+        self.combined_stringency = combined_stringency;
+        self.weak_threshold = weak_threshold;
+        self.min_replicates = min_replicates;
+        self.evidence = evidence;
+        self
+    }
+}
+
+impl Default for ConsensusParameters {
+    fn default() -> Self {
+        Self::new(20, 0, 250)
+    }
+}
+
 impl std::fmt::Display for ConsensusPeakAlgorithm {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let name = match self {
             ConsensusPeakAlgorithm::Gipfelkreuzer => "gipfelkreuzer",
             ConsensusPeakAlgorithm::Simple => "simple",
             ConsensusPeakAlgorithm::Harmonised => "harmonised",
+            ConsensusPeakAlgorithm::Overlap => "overlap",
+            ConsensusPeakAlgorithm::Mspc => "mspc",
         };
         write!(f, "{}", name)
     }
 }
 
+impl std::str::FromStr for ConsensusPeakAlgorithm {
+    type Err = ApplicationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "gipfelkreuzer" => Ok(ConsensusPeakAlgorithm::Gipfelkreuzer),
+            "simple" => Ok(ConsensusPeakAlgorithm::Simple),
+            "harmonised" => Ok(ConsensusPeakAlgorithm::Harmonised),
+            "overlap" => Ok(ConsensusPeakAlgorithm::Overlap),
+            "mspc" => Ok(ConsensusPeakAlgorithm::Mspc),
+            _ => Err(ApplicationError::new(
+                ApplicationErrorType::ConfigError,
+                format!("The algorithm \"{}\" is not known.", s),
+            )),
+        }
+    }
+}
+
+/// Sorts peaks by their start and end coordinates.
+/// Packed coordinate keys are sorted together with the peak indices instead of
+/// moving the full [`PeakData`] around during sorting, which keeps the sort cache friendly
+/// for large numbers of peaks. The peaks are only moved once into their final order.
+/// Ties are broken by the remaining peak properties, so the resulting order
+/// does not depend on the order the peaks were passed in.
+///
+/// # Parameters
+///
+/// * `peaks` - the peaks to sort
+pub(crate) fn sort_peaks_by_position(peaks: Vec<PeakData>) -> Vec<PeakData> {
+    let mut sort_keys: Vec<(u128, usize)> = peaks
+        .iter()
+        .enumerate()
+        .map(|(index, peak)| (((peak.start() as u128) << 64) | peak.end() as u128, index))
+        .collect();
+    sort_keys.sort_unstable_by(|(key_a, index_a), (key_b, index_b)| {
+        key_a.cmp(key_b).then_with(|| {
+            let peak_a = &peaks[*index_a];
+            let peak_b = &peaks[*index_b];
+            (peak_a.summit(), peak_a.id(), index_a).cmp(&(peak_b.summit(), peak_b.id(), index_b))
+        })
+    });
+    sort_keys
+        .into_iter()
+        .map(|(_, index)| peaks[index])
+        .collect()
+}
+
 /// Returns true if both ranges are either overlapping or directly adjacent.
 ///
 /// # Panics
@@ -255,6 +691,8 @@ fn is_continuous_range(a_start: u64, a_end: u64, b_start: u64, b_end: u64) -> bo
 
 pub mod gipfelkreuzer;
 pub mod harmoniser;
+pub mod mspc;
+pub mod overlap;
 pub mod simple;
 
 #[cfg(test)]
@@ -1,6 +1,72 @@
 //! This module contains the specifics of the Gipfelkreuzer consensus peak generation algorithm.
 
-use crate::peaks::{PeakBin, PeakData};
+use std::{collections::HashMap, fmt::Display};
+
+use crate::peaks::{PeakBin, PeakData, TrackedConsensusPeak};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "peaks::gipfelkreuzer";
+
+/// The weights of raw peaks by their identifiers.
+pub type PeakWeights = HashMap<usize, f64>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+/// The way the coordinates of aggregated raw peaks are combined into consensus peak coordinates.
+pub enum CoordinateAggregation {
+    /// The median of the raw peak coordinates.
+    #[default]
+    Median,
+    /// The mean of the raw peak coordinates.
+    Mean,
+    /// The median of the raw peak coordinates weighted by the raw peak scores.
+    WeightedMedian,
+}
+
+impl Display for CoordinateAggregation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CoordinateAggregation::Median => write!(f, "median"),
+            CoordinateAggregation::Mean => write!(f, "mean"),
+            CoordinateAggregation::WeightedMedian => write!(f, "weighted-median"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+/// The implementation used to aggregate the peaks of a peak bin.
+/// Both implementations create identical consensus peaks.
+pub enum AggregationBackend {
+    /// Scans all remaining peaks of the bin for each consensus peak.
+    #[default]
+    Linear,
+    /// Queries the remaining peaks by summit position, which improves the worst-case
+    /// performance on super-dense peak bins yielding many consensus peaks.
+    Indexed,
+}
+
+impl Display for AggregationBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AggregationBackend::Linear => write!(f, "linear"),
+            AggregationBackend::Indexed => write!(f, "indexed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+/// The criteria used for merging raw peaks into consensus peaks.
+struct MergeCriteria<'a> {
+    /// The maximum distance between the summits of merged peaks, if any.
+    summit_tolerance: Option<u64>,
+    /// The way coordinates of merged peaks are combined.
+    coordinate_aggregation: CoordinateAggregation,
+    /// The raw peak weights used for weighted coordinate aggregation, if any.
+    weights: Option<&'a PeakWeights>,
+    /// The implementation used to aggregate the peaks.
+    backend: AggregationBackend,
+}
 
 /// Converts a [`PeakBin`] into its respective consensus peaks.
 ///
@@ -9,22 +75,25 @@ use crate::peaks::{PeakBin, PeakData};
 /// * `peak_bin` - the bin of peaks to generate consensus peaks from
 /// * `max_iterations` - the maximum number of peak merging iterations to be performed
 /// * `min_peaks_per_consensus` - the minimum number of raw peak that are required for the generation of a consensus peak
+/// * `criteria` - the criteria used for merging peaks
 fn bin_to_consensus_peaks(
     peak_bin: PeakBin,
     max_iterations: usize,
     min_peaks_per_consensus: usize,
-) -> Vec<PeakData> {
+    criteria: &MergeCriteria,
+) -> Vec<TrackedConsensusPeak> {
     let mut consensus = bin_to_consensus_peaks_internal(
         Vec::<PeakData>::from(peak_bin)
             .into_iter()
             .map(ConsensusPeakAggregator::from)
             .collect(),
+        criteria,
     );
     // Iterativesly merges peaks until the maximum number of iterations is reached
     // or the peaks do not change anymore.
     let previous_consensus_length = consensus.len();
     for _ in 0..max_iterations {
-        consensus = bin_to_consensus_peaks_internal(consensus);
+        consensus = bin_to_consensus_peaks_internal(consensus, criteria);
         if consensus.len() == previous_consensus_length {
             break;
         }
@@ -32,7 +101,7 @@ fn bin_to_consensus_peaks(
     consensus
         .into_iter()
         .filter(|peak| peak.number_aggregated_peaks() >= min_peaks_per_consensus)
-        .map(PeakData::from)
+        .map(TrackedConsensusPeak::from)
         .collect()
 }
 
@@ -42,11 +111,17 @@ fn bin_to_consensus_peaks(
 /// # Parameters
 ///
 /// * `peaks` - the input peaks
+/// * `criteria` - the criteria used for merging peaks
 fn bin_to_consensus_peaks_internal(
     mut peaks: Vec<ConsensusPeakAggregator>,
+    criteria: &MergeCriteria,
 ) -> Vec<ConsensusPeakAggregator> {
+    if criteria.backend == AggregationBackend::Indexed {
+        return indexed_bin_to_consensus_peaks(peaks, criteria);
+    }
     let mut consensus_peaks = Vec::new();
-    peaks.sort_by(|a, b| a.length().cmp(&b.length()));
+    // The sort is stable, so peaks of equal length keep their deterministic bin order.
+    peaks.sort_by_key(|a| a.length());
     let mut remaining_peaks = peaks;
     while !remaining_peaks.is_empty() {
         let mut consensus_peak_aggregator: Option<ConsensusPeakAggregator> = None;
@@ -55,7 +130,7 @@ fn bin_to_consensus_peaks_internal(
         for peak in remaining_peaks {
             if let Some(aggregator) = &mut consensus_peak_aggregator {
                 // If the peak matches the consensus defining one, adds it to the aggregator.
-                if let Some(unsuitable_peak) = aggregator.try_aggregate(peak) {
+                if let Some(unsuitable_peak) = aggregator.try_aggregate(peak, criteria) {
                     // Otherwise retains it as an additional peak.
                     retained_peaks.push(unsuitable_peak);
                 }
@@ -74,6 +149,145 @@ fn bin_to_consensus_peaks_internal(
     consensus_peaks
 }
 
+/// Converts the peak bin into its respective consensus peaks like
+/// [`bin_to_consensus_peaks_internal`], but queries the remaining peaks by summit position
+/// instead of scanning all of them for each consensus peak.
+///
+/// The peaks are visited in the same order as by the linear scan. A peak whose summit lies
+/// within the current consensus peak, but that was already passed by the scan, is set aside
+/// until the next consensus peak is created, as the linear scan would not have aggregated it.
+///
+/// # Parameters
+///
+/// * `peaks` - the input peaks
+/// * `criteria` - the criteria used for merging peaks
+fn indexed_bin_to_consensus_peaks(
+    mut peaks: Vec<ConsensusPeakAggregator>,
+    criteria: &MergeCriteria,
+) -> Vec<ConsensusPeakAggregator> {
+    // The sort is stable, so the ranks are identical to the visiting order of the linear scan.
+    peaks.sort_by_key(|a| a.length());
+    let mut ranks_by_summit: Vec<usize> = (0..peaks.len()).collect();
+    ranks_by_summit.sort_by_key(|rank| (peaks[*rank].summit(), *rank));
+    let summits: Vec<u64> = ranks_by_summit
+        .iter()
+        .map(|rank| peaks[*rank].summit())
+        .collect();
+    let mut summit_positions = vec![0; peaks.len()];
+    for (position, rank) in ranks_by_summit.iter().enumerate() {
+        summit_positions[*rank] = position;
+    }
+    let mut remaining = MinRankTree::new(&ranks_by_summit);
+    let mut peaks: Vec<Option<ConsensusPeakAggregator>> = peaks.into_iter().map(Some).collect();
+    let mut consensus_peaks = Vec::new();
+    // Uses the shortest remaining peak as consensus characteristic defining peak.
+    while let Some(first_rank) = remaining.min(0, summits.len()) {
+        remaining.remove(summit_positions[first_rank]);
+        let mut aggregator = peaks[first_rank]
+            .take()
+            .expect("Each peak must only be visited once.");
+        let mut visited_rank = first_rank;
+        let mut passed_ranks = Vec::new();
+        while let Some((low, high)) = aggregator.summit_range(criteria) {
+            let from = summits.partition_point(|summit| *summit < low);
+            let to = summits.partition_point(|summit| *summit <= high);
+            let Some(rank) = remaining.min(from, to) else {
+                break;
+            };
+            remaining.remove(summit_positions[rank]);
+            if rank < visited_rank {
+                passed_ranks.push(rank);
+            } else {
+                visited_rank = rank;
+                let peak = peaks[rank]
+                    .take()
+                    .expect("Each peak must only be visited once.");
+                if aggregator.try_aggregate(peak, criteria).is_some() {
+                    panic!("A peak with its summit within the consensus peak must be aggregated.");
+                }
+            }
+        }
+        for rank in passed_ranks {
+            remaining.insert(summit_positions[rank], rank);
+        }
+        consensus_peaks.push(aggregator);
+    }
+    consensus_peaks
+}
+
+/// A segment tree storing the minimum rank of the remaining peaks ordered by summit position.
+struct MinRankTree {
+    /// The number of leaves.
+    leaves: usize,
+    /// The minimum rank of each node, where leaves start at index `leaves`.
+    nodes: Vec<usize>,
+}
+
+impl MinRankTree {
+    /// Creates a tree containing the ranks in the specified order.
+    ///
+    /// # Parameters
+    ///
+    /// * `ranks` - the ranks ordered by summit position
+    fn new(ranks: &[usize]) -> Self {
+        let leaves = ranks.len();
+        let mut nodes = vec![usize::MAX; 2 * leaves];
+        nodes[leaves..].copy_from_slice(ranks);
+        for node in (1..leaves).rev() {
+            nodes[node] = nodes[2 * node].min(nodes[2 * node + 1]);
+        }
+        Self { leaves, nodes }
+    }
+
+    /// Sets the rank at the position and updates the minima of the parent nodes.
+    ///
+    /// # Parameters
+    ///
+    /// * `position` - the summit position
+    /// * `rank` - the rank or [`usize::MAX`] if the position is empty
+    fn insert(&mut self, position: usize, rank: usize) {
+        let mut node = position + self.leaves;
+        self.nodes[node] = rank;
+        while node > 1 {
+            node /= 2;
+            self.nodes[node] = self.nodes[2 * node].min(self.nodes[2 * node + 1]);
+        }
+    }
+
+    /// Removes the rank at the position.
+    ///
+    /// # Parameters
+    ///
+    /// * `position` - the summit position
+    fn remove(&mut self, position: usize) {
+        self.insert(position, usize::MAX);
+    }
+
+    /// Returns the minimum remaining rank within the positions `[from, to)` if any.
+    ///
+    /// # Parameters
+    ///
+    /// * `from` - the first position
+    /// * `to` - the position after the last position
+    fn min(&self, from: usize, to: usize) -> Option<usize> {
+        let mut minimum = usize::MAX;
+        let (mut from, mut to) = (from + self.leaves, to + self.leaves);
+        while from < to {
+            if from % 2 == 1 {
+                minimum = minimum.min(self.nodes[from]);
+                from += 1;
+            }
+            if to % 2 == 1 {
+                to -= 1;
+                minimum = minimum.min(self.nodes[to]);
+            }
+            from /= 2;
+            to /= 2;
+        }
+        (minimum != usize::MAX).then_some(minimum)
+    }
+}
+
 /// Creates consensus peaks from raw peaks based on summit proximity.
 ///
 /// # Parameters
@@ -81,20 +295,87 @@ fn bin_to_consensus_peaks_internal(
 /// * `peaks` - the raw input peaks
 /// * `max_iterations` - the maximum number of merging iterations before the process is aborted and the respective peak returned
 /// * `min_peaks_per_consensus` - the minimum number of raw peaks required to form a consensus peak
+/// * `summit_tolerance` - the maximum distance between the summits of merged peaks, if any
+/// * `coordinate_aggregation` - the way the coordinates of merged peaks are combined
+/// * `weights` - the raw peak weights used for weighted coordinate aggregation, if any
+/// * `backend` - the implementation used to aggregate the peaks
 pub fn consensus_peaks(
     peaks: Vec<PeakData>,
     max_iterations: usize,
     min_peaks_per_consensus: usize,
+    summit_tolerance: Option<u64>,
+    coordinate_aggregation: CoordinateAggregation,
+    weights: Option<&PeakWeights>,
+    backend: AggregationBackend,
 ) -> Vec<PeakData> {
-    let mut consensus_peaks = Vec::new();
-    for bin in PeakBin::bin_peaks(peaks) {
-        consensus_peaks.extend(bin_to_consensus_peaks(
-            bin,
-            max_iterations,
-            min_peaks_per_consensus,
-        ));
+    tracked_consensus_peaks(
+        peaks,
+        max_iterations,
+        min_peaks_per_consensus,
+        summit_tolerance,
+        coordinate_aggregation,
+        weights,
+        backend,
+    )
+    .into_iter()
+    .map(PeakData::from)
+    .collect()
+}
+
+/// Creates consensus peaks like [`consensus_peaks`], but keeps track of the raw peaks
+/// aggregated into each consensus peak.
+///
+/// # Parameters
+///
+/// * `peaks` - the raw input peaks
+/// * `max_iterations` - the maximum number of merging iterations before the process is aborted and the respective peak returned
+/// * `min_peaks_per_consensus` - the minimum number of raw peaks required to form a consensus peak
+/// * `summit_tolerance` - the maximum distance between the summits of merged peaks, if any
+/// * `coordinate_aggregation` - the way the coordinates of merged peaks are combined
+/// * `weights` - the raw peak weights used for weighted coordinate aggregation, if any
+/// * `backend` - the implementation used to aggregate the peaks
+pub fn tracked_consensus_peaks(
+    peaks: Vec<PeakData>,
+    max_iterations: usize,
+    min_peaks_per_consensus: usize,
+    summit_tolerance: Option<u64>,
+    coordinate_aggregation: CoordinateAggregation,
+    weights: Option<&PeakWeights>,
+    backend: AggregationBackend,
+) -> Vec<TrackedConsensusPeak> {
+    let criteria = MergeCriteria {
+        summit_tolerance,
+        coordinate_aggregation,
+        weights,
+        backend,
+    };
+    let bins = PeakBin::bin_peaks(peaks);
+    log::debug!(target: LOG_TARGET, "Merging the peaks of {} peak bins.", bins.len());
+    // The bins are independent of each other, so they can be merged in parallel.
+    // Collecting keeps the order of the bins, so the result does not depend on scheduling.
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+
+        bins.into_par_iter()
+            .flat_map_iter(|bin| {
+                bin_to_consensus_peaks(bin, max_iterations, min_peaks_per_consensus, &criteria)
+            })
+            .collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        let mut consensus_peaks = Vec::new();
+        for bin in bins {
+            consensus_peaks.extend(bin_to_consensus_peaks(
+                bin,
+                max_iterations,
+                min_peaks_per_consensus,
+                &criteria,
+            ));
+        }
+        consensus_peaks
     }
-    consensus_peaks
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -117,21 +398,41 @@ impl ConsensusPeakAggregator {
     /// # Parameters
     ///
     /// * `peak` - the consensus peak to merge
+    /// * `criteria` - the criteria used for merging the peaks
     pub fn try_aggregate(
         &mut self,
         peak: ConsensusPeakAggregator,
+        criteria: &MergeCriteria,
     ) -> Option<ConsensusPeakAggregator> {
         if peak.summit() <= self.consensus_peak.end()
             && peak.summit() >= self.consensus_peak.start()
+            && criteria
+                .summit_tolerance
+                .is_none_or(|tolerance| peak.summit().abs_diff(self.summit()) <= tolerance)
         {
             self.peaks.extend(peak.peaks);
-            self.update_consensus_peak();
+            self.update_consensus_peak(criteria);
             None
         } else {
             Some(peak)
         }
     }
 
+    /// Returns the inclusive range of summit positions of peaks that can be aggregated
+    /// or `None` if no peak can be aggregated.
+    ///
+    /// # Parameters
+    ///
+    /// * `criteria` - the criteria used for merging the peaks
+    fn summit_range(&self, criteria: &MergeCriteria) -> Option<(u64, u64)> {
+        let (mut low, mut high) = (self.consensus_peak.start(), self.consensus_peak.end());
+        if let Some(tolerance) = criteria.summit_tolerance {
+            low = low.max(self.summit().saturating_sub(tolerance));
+            high = high.min(self.summit().saturating_add(tolerance));
+        }
+        (low <= high).then_some((low, high))
+    }
+
     /// The summit of the currently aggregated consenus peak.
     fn summit(&self) -> u64 {
         self.consensus_peak.summit()
@@ -147,19 +448,44 @@ impl ConsensusPeakAggregator {
         self.peaks.len()
     }
 
+    /// Returns the identifiers of the raw peaks aggregated into this consensus peak.
+    fn raw_peak_ids(&self) -> Vec<usize> {
+        self.peaks.iter().map(PeakData::id).collect()
+    }
+
     /// Updates the current consenus peak.
     /// Internal function that should be called after updating the raw peaks of the aggregator.
-    fn update_consensus_peak(&mut self) {
-        let starts: Vec<u64> = self.peaks.iter().map(PeakData::start).collect();
-        let ends: Vec<u64> = self.peaks.iter().map(PeakData::end).collect();
-        let summits: Vec<u64> = self.peaks.iter().map(PeakData::summit).collect();
-        self.consensus_peak = PeakData::new(
-            self.id(),
-            u64_median(starts),
-            u64_median(ends),
-            u64_median(summits),
-        )
-        .expect(
+    ///
+    /// # Parameters
+    ///
+    /// * `criteria` - the criteria defining how the coordinates are combined
+    fn update_consensus_peak(&mut self, criteria: &MergeCriteria) {
+        let aggregate = |coordinate: fn(&PeakData) -> u64| -> u64 {
+            let values: Vec<u64> = self.peaks.iter().map(coordinate).collect();
+            match criteria.coordinate_aggregation {
+                CoordinateAggregation::Median => u64_median(values),
+                CoordinateAggregation::Mean => u64_mean(&values),
+                CoordinateAggregation::WeightedMedian => {
+                    let weights: Vec<f64> = self
+                        .peaks
+                        .iter()
+                        .map(|peak| {
+                            criteria
+                                .weights
+                                .and_then(|weights| weights.get(&peak.id()))
+                                .copied()
+                                .unwrap_or(0.0)
+                        })
+                        .collect();
+                    u64_weighted_median(values, &weights)
+                },
+            }
+        };
+        let start = aggregate(PeakData::start);
+        let end = aggregate(PeakData::end);
+        // Means and weighted medians are not guaranteed to place the summit within the peak.
+        let summit = aggregate(PeakData::summit).clamp(start, end);
+        self.consensus_peak = PeakData::new(self.id(), start, end, summit).expect(
             "The consensus peak parameters must be valid as they were derived from valid peaks.",
         );
     }
@@ -180,6 +506,12 @@ impl From<ConsensusPeakAggregator> for PeakData {
     }
 }
 
+impl From<ConsensusPeakAggregator> for TrackedConsensusPeak {
+    fn from(value: ConsensusPeakAggregator) -> Self {
+        TrackedConsensusPeak::new(value.consensus_peak, value.raw_peak_ids())
+    }
+}
+
 /// Returns the median of the specified values.
 ///
 /// # Parameters
@@ -195,17 +527,90 @@ fn u64_median(mut values: Vec<u64>) -> u64 {
     }
     values.sort();
     let midpoint = values.len().div_ceil(2) - 1;
-    if values.len() % 2 == 0 {
+    if values.len().is_multiple_of(2) {
         (values[midpoint] + values[midpoint + 1]) / 2
     } else {
         values[midpoint]
     }
 }
 
+/// Returns the rounded mean of the specified values.
+///
+/// # Parameters
+///
+/// * `values` - the values to calculate the mean of
+///
+/// # Panics
+///
+/// If the slice of values is empty.
+fn u64_mean(values: &[u64]) -> u64 {
+    if values.is_empty() {
+        panic!("The mean of an empty collection cannot be calculated.");
+    }
+    let sum: u128 = values.iter().map(|&value| u128::from(value)).sum();
+    let count = values.len() as u128;
+    ((sum + count / 2) / count) as u64
+}
+
+/// Returns the weighted median of the specified values.
+/// Negative and non-finite weights are treated as zero. If all weights are zero the
+/// unweighted median is returned.
+///
+/// # Parameters
+///
+/// * `values` - the values to calculate the weighted median of
+/// * `weights` - the weights of the respective values
+///
+/// # Panics
+///
+/// If the vector of values is empty or the number of weights does not match the number of values.
+fn u64_weighted_median(values: Vec<u64>, weights: &[f64]) -> u64 {
+    if values.len() != weights.len() {
+        panic!("Each value must be assigned exactly one weight.");
+    }
+    let mut weighted_values: Vec<(u64, f64)> = values
+        .iter()
+        .copied()
+        .zip(weights.iter().copied())
+        .filter(|(_, weight)| weight.is_finite() && *weight > 0.0)
+        .collect();
+    if weighted_values.is_empty() {
+        return u64_median(values);
+    }
+    let total_weight: f64 = weighted_values.iter().map(|(_, weight)| weight).sum();
+    weighted_values.sort_by_key(|(value, _)| *value);
+    let half_weight = total_weight / 2.0;
+    let mut cumulative_weight = 0.0;
+    for (index, (value, weight)) in weighted_values.iter().enumerate() {
+        cumulative_weight += weight;
+        if cumulative_weight > half_weight {
+            return *value;
+        }
+        if cumulative_weight == half_weight {
+            // The weight is split evenly, so the value is averaged with the next one.
+            return weighted_values
+                .get(index + 1)
+                .map_or(*value, |(next_value, _)| (value + next_value) / 2);
+        }
+    }
+    weighted_values
+        .last()
+        .map(|(value, _)| *value)
+        .expect("The weighted values must not be empty at this point.")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Returns merge criteria with the specified summit tolerance.
+    fn tolerance(summit_tolerance: u64) -> MergeCriteria<'static> {
+        MergeCriteria {
+            summit_tolerance: Some(summit_tolerance),
+            ..Default::default()
+        }
+    }
+
     #[test]
     fn test_u64_median() {
         // Central value.
@@ -222,6 +627,146 @@ mod tests {
         u64_median(Vec::new());
     }
 
+    #[test]
+    fn test_u64_mean() {
+        assert_eq!(22, u64_mean(&[1, 8, 56]));
+        // Rounding of the mean.
+        assert_eq!(5, u64_mean(&[4, 5]));
+        assert_eq!(u64::MAX, u64_mean(&[u64::MAX, u64::MAX]));
+    }
+
+    #[test]
+    fn test_u64_weighted_median() {
+        // The heavy value dominates.
+        assert_eq!(56, u64_weighted_median(vec![1, 8, 56], &[1.0, 1.0, 5.0]));
+        // Evenly split weights average the central values.
+        assert_eq!(32, u64_weighted_median(vec![1, 8, 56, 353631], &[1.0, 1.0, 1.0, 1.0]));
+        // Invalid weights are ignored.
+        assert_eq!(1, u64_weighted_median(vec![1, 8, 56], &[1.0, f64::NAN, -3.0]));
+        // Falls back to the unweighted median without any weights.
+        assert_eq!(8, u64_weighted_median(vec![1, 8, 56], &[0.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_u64_weighted_median_mismatched_weights() {
+        u64_weighted_median(vec![1, 8], &[1.0]);
+    }
+
+    #[test]
+    fn test_consensus_peaks_coordinate_aggregation() {
+        let peaks = vec![
+            PeakData::new(0, 100u64, 200u64, 150u64).unwrap(),
+            PeakData::new(1, 110u64, 210u64, 160u64).unwrap(),
+            PeakData::new(2, 90u64, 260u64, 180u64).unwrap(),
+        ];
+        let weights: PeakWeights = [(0, 1.0), (1, 1.0), (2, 10.0)].into_iter().collect();
+        assert_eq!(
+            consensus_peaks(
+                peaks.clone(),
+                20,
+                0,
+                None,
+                CoordinateAggregation::Median,
+                None,
+                AggregationBackend::Linear
+            ),
+            vec![PeakData::new(0, 100u64, 210u64, 160u64).unwrap()]
+        );
+        assert_eq!(
+            consensus_peaks(
+                peaks.clone(),
+                20,
+                0,
+                None,
+                CoordinateAggregation::Mean,
+                None,
+                AggregationBackend::Linear
+            ),
+            vec![PeakData::new(0, 100u64, 223u64, 163u64).unwrap()]
+        );
+        assert_eq!(
+            consensus_peaks(
+                peaks.clone(),
+                20,
+                0,
+                None,
+                CoordinateAggregation::WeightedMedian,
+                Some(&weights),
+                AggregationBackend::Linear
+            ),
+            vec![PeakData::new(0, 90u64, 260u64, 180u64).unwrap()]
+        );
+        // Without weights the weighted median equals the median.
+        assert_eq!(
+            consensus_peaks(
+                peaks,
+                20,
+                0,
+                None,
+                CoordinateAggregation::WeightedMedian,
+                None,
+                AggregationBackend::Linear
+            ),
+            vec![PeakData::new(0, 100u64, 210u64, 160u64).unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_min_rank_tree() {
+        let mut tree = MinRankTree::new(&[3, 0, 4, 1, 2]);
+        assert_eq!(tree.min(0, 5), Some(0));
+        assert_eq!(tree.min(2, 5), Some(1));
+        assert_eq!(tree.min(2, 3), Some(4));
+        assert_eq!(tree.min(2, 2), None);
+        tree.remove(1);
+        tree.remove(3);
+        assert_eq!(tree.min(0, 5), Some(2));
+        assert_eq!(tree.min(1, 2), None);
+        tree.insert(1, 0);
+        assert_eq!(tree.min(0, 5), Some(0));
+        assert_eq!(MinRankTree::new(&[]).min(0, 0), None);
+    }
+
+    #[test]
+    fn test_indexed_aggregation_backend() {
+        // Pseudo-random but deterministic dense and overlapping peaks.
+        let mut state: u64 = 42;
+        let mut random = |bound: u64| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) % bound
+        };
+        let peaks: Vec<PeakData> = (0..1000)
+            .map(|id| {
+                let summit = 1000 + random(2500);
+                let start = summit - random(400);
+                let end = summit + random(400);
+                PeakData::new(id, start, end, summit).unwrap()
+            })
+            .collect();
+        for summit_tolerance in [None, Some(0), Some(25)] {
+            for max_iterations in [0, 1, 20] {
+                let consensus = |backend| {
+                    tracked_consensus_peaks(
+                        peaks.clone(),
+                        max_iterations,
+                        0,
+                        summit_tolerance,
+                        CoordinateAggregation::Median,
+                        None,
+                        backend,
+                    )
+                };
+                assert_eq!(
+                    consensus(AggregationBackend::Indexed),
+                    consensus(AggregationBackend::Linear)
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_consensus_peak_aggregator_from_peak_data() {
         let peak = PeakData::new(42, 42u64, 84u64, 63u64).unwrap();
@@ -243,7 +788,11 @@ mod tests {
         let expected_consensus_peak = PeakData::new(42, 43u64, 84u64, 62u64).unwrap();
         let mut aggregator = ConsensusPeakAggregator::from(start_peak);
         for peak in peaks {
-            assert!(aggregator.try_aggregate(peak).is_none());
+            assert!(
+                aggregator
+                    .try_aggregate(peak, &MergeCriteria::default())
+                    .is_none()
+            );
         }
         let consensus: PeakData = aggregator.into();
         assert_eq!(consensus, expected_consensus_peak);
@@ -260,15 +809,26 @@ mod tests {
         let expected_consensus_peak = PeakData::new(42, 43u64, 84u64, 63u64).unwrap();
         let mut aggregator = ConsensusPeakAggregator::from(start_peak);
         assert_eq!(aggregator.number_aggregated_peaks(), 1);
-        assert!(aggregator.try_aggregate(peaks[0].clone()).is_none());
+        assert!(
+            aggregator
+                .try_aggregate(peaks[0].clone(), &MergeCriteria::default())
+                .is_none()
+        );
         assert_eq!(aggregator.summit(), 62u64);
         assert_eq!(aggregator.length(), 42);
         assert_eq!(aggregator.number_aggregated_peaks(), 2);
-        assert!(aggregator.try_aggregate(peaks[1].clone()).is_none());
+        assert!(
+            aggregator
+                .try_aggregate(peaks[1].clone(), &MergeCriteria::default())
+                .is_none()
+        );
         assert_eq!(aggregator.summit(), 63u64);
         assert_eq!(aggregator.length(), 42);
         assert_eq!(aggregator.number_aggregated_peaks(), 3);
-        assert_eq!(aggregator.try_aggregate(peaks[2].clone()), Some(peaks[2].clone()));
+        assert_eq!(
+            aggregator.try_aggregate(peaks[2].clone(), &MergeCriteria::default()),
+            Some(peaks[2].clone())
+        );
         assert_eq!(aggregator.number_aggregated_peaks(), 3);
         assert_eq!(aggregator.summit(), 63u64);
         assert_eq!(aggregator.length(), 42);
@@ -286,7 +846,11 @@ mod tests {
         ];
         let mut aggregator = ConsensusPeakAggregator::from(start_peak);
         for peak in peaks {
-            assert!(aggregator.try_aggregate(peak).is_none());
+            assert!(
+                aggregator
+                    .try_aggregate(peak, &MergeCriteria::default())
+                    .is_none()
+            );
         }
 
         // Creates a consensus peak that should merge
@@ -297,7 +861,11 @@ mod tests {
         ];
         let mut aggregator_merge = ConsensusPeakAggregator::from(start_peak_merge);
         for peak in peaks_merge {
-            assert!(aggregator_merge.try_aggregate(peak).is_none());
+            assert!(
+                aggregator_merge
+                    .try_aggregate(peak, &MergeCriteria::default())
+                    .is_none()
+            );
         }
 
         // Creates a consensus peak that should not merge.
@@ -308,7 +876,11 @@ mod tests {
         ];
         let mut aggregator_no_merge = ConsensusPeakAggregator::from(start_peak_no_merge);
         for peak in peaks_no_merge {
-            assert!(aggregator_no_merge.try_aggregate(peak).is_none());
+            assert!(
+                aggregator_no_merge
+                    .try_aggregate(peak, &MergeCriteria::default())
+                    .is_none()
+            );
         }
 
         assert_eq!(aggregator.number_aggregated_peaks(), 3);
@@ -316,14 +888,18 @@ mod tests {
         assert_eq!(aggregator.length(), 42);
 
         // Adds a consensus peak that consists of multiple raw peaks.
-        assert!(aggregator.try_aggregate(aggregator_merge).is_none());
+        assert!(
+            aggregator
+                .try_aggregate(aggregator_merge, &MergeCriteria::default())
+                .is_none()
+        );
         assert_eq!(aggregator.number_aggregated_peaks(), 6);
         assert_eq!(aggregator.summit(), 64u64);
         assert_eq!(aggregator.length(), 44);
 
         // Fails to add another peak.
         assert_eq!(
-            aggregator.try_aggregate(aggregator_no_merge.clone()),
+            aggregator.try_aggregate(aggregator_no_merge.clone(), &MergeCriteria::default()),
             Some(aggregator_no_merge)
         );
         assert_eq!(aggregator.number_aggregated_peaks(), 6);
@@ -335,6 +911,74 @@ mod tests {
         assert_eq!(consensus, expected_consensus_peak);
     }
 
+    #[test]
+    fn test_consensus_peak_aggregator_try_aggregate_summit_tolerance() {
+        let start_peak = PeakData::new(42, 42u64, 84u64, 63u64).unwrap();
+        let close_peak: ConsensusPeakAggregator =
+            PeakData::new(43, 44u64, 85u64, 61u64).unwrap().into();
+        let distant_peak: ConsensusPeakAggregator =
+            PeakData::new(44, 43u64, 83u64, 72u64).unwrap().into();
+        let mut aggregator = ConsensusPeakAggregator::from(start_peak);
+        // The summit lies within the consensus peak, but too far from its summit.
+        assert_eq!(
+            aggregator.try_aggregate(distant_peak.clone(), &tolerance(8)),
+            Some(distant_peak.clone())
+        );
+        assert_eq!(aggregator.number_aggregated_peaks(), 1);
+        assert!(
+            aggregator
+                .try_aggregate(close_peak, &tolerance(2))
+                .is_none()
+        );
+        assert_eq!(aggregator.number_aggregated_peaks(), 2);
+        assert_eq!(aggregator.summit(), 62u64);
+        // The tolerance is inclusive.
+        assert!(
+            aggregator
+                .try_aggregate(distant_peak, &tolerance(10))
+                .is_none()
+        );
+        assert_eq!(aggregator.number_aggregated_peaks(), 3);
+    }
+
+    #[test]
+    fn test_consensus_peaks_summit_tolerance() {
+        let peaks = vec![
+            PeakData::new(0, 100u64, 200u64, 150u64).unwrap(),
+            PeakData::new(1, 100u64, 201u64, 155u64).unwrap(),
+            PeakData::new(2, 100u64, 202u64, 190u64).unwrap(),
+        ];
+        assert_eq!(
+            consensus_peaks(
+                peaks.clone(),
+                20,
+                0,
+                None,
+                CoordinateAggregation::Median,
+                None,
+                AggregationBackend::Linear
+            )
+            .len(),
+            1
+        );
+        let consensus = consensus_peaks(
+            peaks,
+            20,
+            0,
+            Some(10),
+            CoordinateAggregation::Median,
+            None,
+            AggregationBackend::Linear,
+        );
+        assert_eq!(
+            consensus,
+            vec![
+                PeakData::new(0, 100u64, 200u64, 152u64).unwrap(),
+                PeakData::new(2, 100u64, 202u64, 190u64).unwrap(),
+            ]
+        );
+    }
+
     #[test]
     fn test_consensus_peaks() {
         let peaks = vec![
@@ -347,7 +991,15 @@ mod tests {
             PeakData::new(5, 271u64, 291u64, 276u64).unwrap(),
             PeakData::new(6, 2700u64, 2900u64, 2770u64).unwrap(),
         ];
-        let consensus = consensus_peaks(peaks, 20, 0);
+        let consensus = consensus_peaks(
+            peaks,
+            20,
+            0,
+            None,
+            CoordinateAggregation::Median,
+            None,
+            AggregationBackend::Linear,
+        );
 
         let expected_consensus_peaks = vec![
             PeakData::new(3, 27u64, 29u64, 27u64).unwrap(),
@@ -359,6 +1011,34 @@ mod tests {
         assert_eq!(consensus, expected_consensus_peaks);
     }
 
+    #[test]
+    fn test_tracked_consensus_peaks() {
+        let peaks = vec![
+            PeakData::new(0, 12u64, 22u64, 18u64).unwrap(),
+            PeakData::new(1, 11u64, 21u64, 17u64).unwrap(),
+            PeakData::new(7, 13u64, 22u64, 16u64).unwrap(),
+            PeakData::new(4, 270u64, 290u64, 277u64).unwrap(),
+        ];
+        let mut consensus: Vec<(usize, Vec<usize>)> = tracked_consensus_peaks(
+            peaks,
+            20,
+            0,
+            None,
+            CoordinateAggregation::Median,
+            None,
+            AggregationBackend::Linear,
+        )
+        .into_iter()
+        .map(|peak| {
+            let mut raw_peak_ids = peak.raw_peak_ids().clone();
+            raw_peak_ids.sort_unstable();
+            (peak.peak().id(), raw_peak_ids)
+        })
+        .collect();
+        consensus.sort_unstable();
+        assert_eq!(consensus, vec![(4, vec![4]), (7, vec![0, 1, 7])]);
+    }
+
     #[test]
     fn test_consensus_peaks_filter() {
         let peaks = vec![
@@ -372,7 +1052,15 @@ mod tests {
             PeakData::new(6, 2700u64, 2900u64, 2770u64).unwrap(),
         ];
         {
-            let consensus = consensus_peaks(peaks.clone(), 20, 0);
+            let consensus = consensus_peaks(
+                peaks.clone(),
+                20,
+                0,
+                None,
+                CoordinateAggregation::Median,
+                None,
+                AggregationBackend::Linear,
+            );
 
             let expected_consensus_peaks = vec![
                 PeakData::new(3, 27u64, 29u64, 27u64).unwrap(),
@@ -384,7 +1072,15 @@ mod tests {
             assert_eq!(consensus, expected_consensus_peaks);
         }
         {
-            let consensus = consensus_peaks(peaks.clone(), 20, 1);
+            let consensus = consensus_peaks(
+                peaks.clone(),
+                20,
+                1,
+                None,
+                CoordinateAggregation::Median,
+                None,
+                AggregationBackend::Linear,
+            );
 
             let expected_consensus_peaks = vec![
                 PeakData::new(3, 27u64, 29u64, 27u64).unwrap(),
@@ -396,7 +1092,15 @@ mod tests {
             assert_eq!(consensus, expected_consensus_peaks);
         }
         {
-            let consensus = consensus_peaks(peaks.clone(), 20, 2);
+            let consensus = consensus_peaks(
+                peaks.clone(),
+                20,
+                2,
+                None,
+                CoordinateAggregation::Median,
+                None,
+                AggregationBackend::Linear,
+            );
 
             let expected_consensus_peaks = vec![
                 PeakData::new(7, 12u64, 22u64, 17u64).unwrap(),
@@ -405,7 +1109,15 @@ mod tests {
             assert_eq!(consensus, expected_consensus_peaks);
         }
         {
-            let consensus = consensus_peaks(peaks, 20, 3);
+            let consensus = consensus_peaks(
+                peaks,
+                20,
+                3,
+                None,
+                CoordinateAggregation::Median,
+                None,
+                AggregationBackend::Linear,
+            );
 
             let expected_consensus_peaks = vec![PeakData::new(7, 12u64, 22u64, 17u64).unwrap()];
             assert_eq!(consensus, expected_consensus_peaks);
@@ -422,7 +1134,15 @@ mod tests {
         ];
 
         {
-            let consensus = consensus_peaks(peaks.clone(), 0, 0);
+            let consensus = consensus_peaks(
+                peaks.clone(),
+                0,
+                0,
+                None,
+                CoordinateAggregation::Median,
+                None,
+                AggregationBackend::Linear,
+            );
 
             let expected_consensus_peaks = vec![
                 PeakData::new(0, 650u64, 751u64, 700u64).unwrap(),
@@ -432,7 +1152,15 @@ mod tests {
         }
 
         {
-            let consensus = consensus_peaks(peaks.clone(), 20, 0);
+            let consensus = consensus_peaks(
+                peaks.clone(),
+                20,
+                0,
+                None,
+                CoordinateAggregation::Median,
+                None,
+                AggregationBackend::Linear,
+            );
 
             let expected_consensus_peaks = vec![PeakData::new(1, 655u64, 731u64, 699u64).unwrap()];
             assert_eq!(consensus, expected_consensus_peaks);
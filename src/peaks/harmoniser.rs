@@ -1,13 +1,14 @@
 //! This module contains the specifics of the consensus peak generation algorithm
 //! supposed by [Cherchame 2025](https://www.protocols.io/view/atac-seq-methods-for-consensus-peak-generation-to-36wgq326olk5/v1).
 
-use std::u64;
-
 use crate::{
-    error::ApplicationError,
-    peaks::{PeakBin, PeakData},
+    error::{ApplicationError, ApplicationErrorType},
+    peaks::{PeakBin, PeakData, TrackedConsensusPeak},
 };
 
+/// The log target of this module.
+const LOG_TARGET: &str = "peaks::harmoniser";
+
 /// Creates harmonised consensus peaks from raw peaks based on summit proximity.
 ///
 /// # Parameters
@@ -24,7 +25,36 @@ pub fn harmonised_consensus_peaks(
     harmonising_distance: u64,
     min_peaks_per_consensus: usize,
 ) -> Result<Vec<PeakData>, ApplicationError> {
+    Ok(tracked_harmonised_consensus_peaks(peaks, harmonising_distance, min_peaks_per_consensus)?
+        .into_iter()
+        .map(PeakData::from)
+        .collect())
+}
+
+/// Creates harmonised consensus peaks like [`harmonised_consensus_peaks`], but keeps track
+/// of the raw peaks merged into each consensus peak.
+///
+/// # Parameters
+///
+/// * `peaks` - the raw input peaks
+/// * `harmonising_distance` - the distance from the summit that is considered a harmonised peak region
+/// * `min_peaks_per_consensus` - the minimum number of raw peaks required to form a consensus peak
+///
+/// # Error
+///
+/// Returns an error if the creation of a consensus peaks fails.
+pub fn tracked_harmonised_consensus_peaks(
+    peaks: Vec<PeakData>,
+    harmonising_distance: u64,
+    min_peaks_per_consensus: usize,
+) -> Result<Vec<TrackedConsensusPeak>, ApplicationError> {
     let mut consensus_peaks = Vec::new();
+    log::debug!(
+        target: LOG_TARGET,
+        "Harmonising {} peaks to a distance of {} from their summits.",
+        peaks.len(),
+        harmonising_distance
+    );
     let peaks = peaks
         .into_iter()
         .map(|peak| harmonise_peak(peak, harmonising_distance))
@@ -35,15 +65,17 @@ pub fn harmonised_consensus_peaks(
         .filter(|bin| bin.peaks().len() >= min_peaks_per_consensus)
         .enumerate()
     {
-        consensus_peaks.push(
+        let peak =
             PeakData::new(bin_index, bin.start(), bin.end(), bin.start().midpoint(bin.end()))
                 .map_err(|err| {
-                    err.chain(format!(
-                        "Failed to create a harmonised consensus peak from peak bin {}: {:?}",
-                        bin_index, bin
-                    ))
-                })?,
-        );
+                    err.with_error_type(ApplicationErrorType::AlgorithmError)
+                        .chain(format!(
+                            "Failed to create a harmonised consensus peak from peak bin {}: {:?}",
+                            bin_index, bin
+                        ))
+                })?;
+        consensus_peaks
+            .push(TrackedConsensusPeak::new(peak, bin.peaks().iter().map(PeakData::id).collect()));
     }
     Ok(consensus_peaks)
 }
@@ -57,8 +89,8 @@ pub fn harmonised_consensus_peaks(
 /// * `distance` - the fixed distance of start and end coordinate from the peak summit
 fn harmonise_peak(peak: PeakData, distance: u64) -> PeakData {
     let summit = peak.summit();
-    let start = summit.checked_sub(distance).unwrap_or(0);
-    let end = summit.checked_add(distance).unwrap_or(u64::MAX);
+    let start = summit.saturating_sub(distance);
+    let end = summit.saturating_add(distance);
     PeakData {
         id: peak.id(),
         start,
@@ -69,8 +101,6 @@ fn harmonise_peak(peak: PeakData, distance: u64) -> PeakData {
 
 #[cfg(test)]
 mod tests {
-    use std::u64;
-
     use super::*;
 
     #[test]
@@ -0,0 +1,364 @@
+//! This module contains the specifics of the multiple sample peak calling (MSPC) like algorithm
+//! proposed by [Jalili et al. in 2015](https://doi.org/10.1093/bioinformatics/btv293), which
+//! combines the p-values of overlapping peaks of different samples with Fisher's method.
+//!
+//! Peaks less significant than the weak threshold are discarded as background. Each remaining
+//! peak is supported by the most significant overlapping peak of every other sample. Peaks
+//! supported by at least the minimum number of samples whose combined p-value reaches the
+//! combined stringency are confirmed, all other peaks are discarded. Overlapping confirmed
+//! peaks are merged into consensus peaks.
+
+use std::collections::HashMap;
+
+use crate::{
+    error::{ApplicationError, ApplicationErrorType},
+    peaks::{PeakBin, PeakData, TrackedConsensusPeak},
+    provenance::SampleIndex,
+    scores::PeakScores,
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "peaks::mspc";
+
+/// The default maximum combined p-value of confirmed peaks.
+pub const DEFAULT_COMBINED_STRINGENCY: f64 = 1e-8;
+/// The default maximum p-value of peaks that are not considered background.
+pub const DEFAULT_WEAK_THRESHOLD: f64 = 1e-4;
+
+/// The sample and the -log10 p-value of raw peaks by raw peak ID.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct PeakEvidence {
+    /// The sample index and the -log10 p-value by raw peak ID.
+    evidence: HashMap<usize, (usize, f64)>,
+}
+
+impl PeakEvidence {
+    /// Creates empty evidence.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Collects the evidence of the raw peaks of all samples from their statistical values.
+    /// Raw peaks without a p-value are skipped.
+    ///
+    /// # Parameters
+    ///
+    /// * `raw_peak_scores` - the statistical values of the raw peaks by raw peak ID
+    /// * `sample_index` - the mapping of raw peaks to samples
+    pub fn from_scores(
+        raw_peak_scores: &HashMap<usize, PeakScores>,
+        sample_index: &SampleIndex,
+    ) -> Self {
+        let mut evidence = Self::new();
+        for (id, scores) in raw_peak_scores {
+            if let Some(p_value) = scores.p_value() {
+                evidence.insert(*id, sample_index.sample(*id), p_value);
+            }
+        }
+        evidence
+    }
+
+    /// Adds the evidence of a raw peak.
+    ///
+    /// # Parameters
+    ///
+    /// * `raw_peak_id` - the ID of the raw peak
+    /// * `sample` - the index of the sample the raw peak was called on
+    /// * `minus_log10_p_value` - the -log10 p-value of the raw peak
+    pub fn insert(&mut self, raw_peak_id: usize, sample: usize, minus_log10_p_value: f64) {
+        self.evidence
+            .insert(raw_peak_id, (sample, minus_log10_p_value));
+    }
+
+    /// Returns the number of raw peaks with evidence.
+    pub fn len(&self) -> usize {
+        self.evidence.len()
+    }
+
+    /// Returns `true` if there is no evidence for any raw peak.
+    pub fn is_empty(&self) -> bool {
+        self.evidence.is_empty()
+    }
+}
+
+/// Combines independent p-values with Fisher's method.
+/// Returns the -log10 combined p-value.
+///
+/// # Parameters
+///
+/// * `minus_log10_p_values` - the -log10 p-values to combine
+pub fn fisher_combined_p_value(minus_log10_p_values: &[f64]) -> f64 {
+    // The test statistic follows a chi-squared distribution with 2k degrees of freedom,
+    // whose survival function is exp(-x/2) * sum_{i<k} (x/2)^i / i!.
+    let half_statistic = std::f64::consts::LN_10 * minus_log10_p_values.iter().sum::<f64>();
+    if half_statistic <= 0.0 {
+        return 0.0;
+    }
+    let log_terms: Vec<f64> = (0..minus_log10_p_values.len())
+        .scan(0.0, |log_term, index| {
+            if index > 0 {
+                *log_term += half_statistic.ln() - (index as f64).ln();
+            }
+            Some(*log_term)
+        })
+        .collect();
+    let max_log_term = log_terms.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let log_sum = max_log_term
+        + log_terms
+            .iter()
+            .map(|log_term| (log_term - max_log_term).exp())
+            .sum::<f64>()
+            .ln();
+    ((half_statistic - log_sum) / std::f64::consts::LN_10).max(0.0)
+}
+
+/// Validates that a p-value threshold is within (0, 1].
+///
+/// # Parameters
+///
+/// * `name` - the name of the threshold
+/// * `threshold` - the threshold
+fn validate_threshold(name: &str, threshold: f64) -> Result<(), ApplicationError> {
+    if threshold > 0.0 && threshold <= 1.0 {
+        Ok(())
+    } else {
+        Err(ApplicationError::new(
+            ApplicationErrorType::ConfigError,
+            format!("The {} {} is not within (0, 1].", name, threshold),
+        ))
+    }
+}
+
+/// Creates consensus peaks by merging the overlapping peaks confirmed by the combined evidence
+/// of the peaks of different samples. The summit of a consensus peak is the summit of its most
+/// significant confirmed peak.
+///
+/// # Parameters
+///
+/// * `peaks` - the raw input peaks
+/// * `evidence` - the sample and p-value of the raw peaks
+/// * `combined_stringency` - the maximum combined p-value of confirmed peaks
+/// * `weak_threshold` - the maximum p-value of peaks that are not considered background
+/// * `min_replicates` - the minimum number of samples supporting a confirmed peak
+/// * `min_peaks_per_consensus` - the minimum number of confirmed peaks required to form a consensus peak
+///
+/// # Errors
+///
+/// Returns an error if a threshold is not within (0, 1] or no evidence is specified.
+pub fn tracked_mspc_consensus_peaks(
+    peaks: Vec<PeakData>,
+    evidence: &PeakEvidence,
+    combined_stringency: f64,
+    weak_threshold: f64,
+    min_replicates: usize,
+    min_peaks_per_consensus: usize,
+) -> Result<Vec<TrackedConsensusPeak>, ApplicationError> {
+    validate_threshold("combined stringency", combined_stringency)?;
+    validate_threshold("weak threshold", weak_threshold)?;
+    if evidence.is_empty() && !peaks.is_empty() {
+        return Err(ApplicationError::new(
+            ApplicationErrorType::ConfigError,
+            "The mspc algorithm requires the p-values of the raw peaks.",
+        )
+        .with_hint("Use narrowPeak input files containing p-values in column 8."));
+    }
+    let min_stringency = -combined_stringency.log10();
+    let min_significance = -weak_threshold.log10();
+    let bins = PeakBin::bin_peaks(peaks);
+    log::debug!(target: LOG_TARGET, "Combining the evidence of {} peak bins.", bins.len());
+    let (mut background, mut discarded) = (0, 0);
+    let mut clusters: Vec<Vec<(PeakData, f64)>> = Vec::new();
+    for bin in bins {
+        let mut bin_peaks: Vec<(PeakData, usize, f64)> = Vec::<PeakData>::from(bin)
+            .into_iter()
+            .filter_map(|peak| {
+                evidence
+                    .evidence
+                    .get(&peak.id())
+                    .map(|(sample, significance)| (peak, *sample, *significance))
+            })
+            .collect();
+        let peaks_before = bin_peaks.len();
+        bin_peaks.retain(|(_, _, significance)| *significance >= min_significance);
+        background += peaks_before - bin_peaks.len();
+        bin_peaks.sort_unstable_by_key(|(peak, _, _)| (peak.start(), peak.end(), peak.id()));
+        let mut confirmed: Vec<(PeakData, f64)> = Vec::new();
+        for (peak, sample, significance) in &bin_peaks {
+            let mut supporting: HashMap<usize, f64> = HashMap::new();
+            for (other, other_sample, other_significance) in &bin_peaks {
+                if other_sample != sample
+                    && other.start() <= peak.end()
+                    && other.end() >= peak.start()
+                {
+                    let best = supporting.entry(*other_sample).or_insert(0.0);
+                    *best = best.max(*other_significance);
+                }
+            }
+            let mut significances: Vec<f64> = supporting.into_values().collect();
+            significances.push(*significance);
+            if significances.len() >= min_replicates
+                && fisher_combined_p_value(&significances) >= min_stringency
+            {
+                confirmed.push((*peak, *significance));
+            } else {
+                discarded += 1;
+            }
+        }
+        // The confirmed peaks are sorted by start, so overlapping peaks are consecutive.
+        let mut bin_clusters: Vec<Vec<(PeakData, f64)>> = Vec::new();
+        let mut cluster_end = 0;
+        for (peak, significance) in confirmed {
+            match bin_clusters.last_mut() {
+                Some(cluster) if peak.start() <= cluster_end => {
+                    cluster.push((peak, significance));
+                    cluster_end = cluster_end.max(peak.end());
+                },
+                _ => {
+                    bin_clusters.push(vec![(peak, significance)]);
+                    cluster_end = peak.end();
+                },
+            }
+        }
+        clusters.extend(bin_clusters);
+    }
+    log::info!(
+        target: LOG_TARGET,
+        "Discarded {} background peaks and {} peaks with insufficient combined evidence.",
+        background,
+        discarded
+    );
+    let mut consensus_peaks = Vec::new();
+    for (index, cluster) in clusters
+        .into_iter()
+        .filter(|cluster| cluster.len() >= min_peaks_per_consensus)
+        .enumerate()
+    {
+        let start = cluster
+            .iter()
+            .map(|(peak, _)| peak.start())
+            .min()
+            .unwrap_or_default();
+        let end = cluster
+            .iter()
+            .map(|(peak, _)| peak.end())
+            .max()
+            .unwrap_or_default();
+        let summit = cluster
+            .iter()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(peak, _)| peak.summit())
+            .unwrap_or(start);
+        let peak = PeakData::new(index, start, end, summit).map_err(|err| {
+            err.with_error_type(ApplicationErrorType::AlgorithmError)
+                .chain(format!(
+                    "Failed to create an MSPC consensus peak from the peaks {:?}.",
+                    cluster
+                ))
+        })?;
+        consensus_peaks.push(TrackedConsensusPeak::new(
+            peak,
+            cluster.iter().map(|(peak, _)| peak.id()).collect(),
+        ));
+    }
+    Ok(consensus_peaks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fisher_combined_p_value() {
+        // A single p-value is unchanged.
+        assert!((fisher_combined_p_value(&[3.0]) - 3.0).abs() < 1e-9);
+        // Two p-values of 0.05 combine to 0.0025 * (1 - ln(0.0025)) = 0.01748.
+        let combined = fisher_combined_p_value(&[-(0.05f64.log10()), -(0.05f64.log10())]);
+        assert!((10f64.powf(-combined) - 0.017479).abs() < 1e-5, "{}", combined);
+        assert_eq!(fisher_combined_p_value(&[0.0, 0.0]), 0.0);
+        // Extremely small p-values do not underflow.
+        assert!(fisher_combined_p_value(&[400.0, 400.0]) > 795.0);
+    }
+
+    #[test]
+    fn test_peak_evidence_from_scores() {
+        let sample_index = SampleIndex::new(&[2, 2]);
+        assert!(PeakEvidence::from_scores(&HashMap::new(), &sample_index).is_empty());
+        let raw_peak_scores = HashMap::from([
+            (1, PeakScores::new(Some(10.0), None, Some(5.0), None)),
+            (2, PeakScores::new(Some(10.0), None, None, None)),
+            (3, PeakScores::new(None, None, Some(7.0), None)),
+        ]);
+        let evidence = PeakEvidence::from_scores(&raw_peak_scores, &sample_index);
+        let mut expected = PeakEvidence::new();
+        expected.insert(1, 0, 5.0);
+        expected.insert(3, 1, 7.0);
+        assert_eq!(evidence, expected);
+    }
+
+    #[test]
+    fn test_tracked_mspc_consensus_peaks() {
+        let peaks = vec![
+            // Two strong peaks of different samples confirming each other.
+            PeakData::new(0, 100u64, 199u64, 150u64).unwrap(),
+            PeakData::new(1, 150u64, 249u64, 180u64).unwrap(),
+            // A weak peak supported by a strong peak of another sample.
+            PeakData::new(2, 1000u64, 1099u64, 1050u64).unwrap(),
+            PeakData::new(3, 1050u64, 1149u64, 1100u64).unwrap(),
+            // A strong peak without support.
+            PeakData::new(4, 5000u64, 5099u64, 5050u64).unwrap(),
+            // Overlapping peaks of the same sample do not support each other.
+            PeakData::new(5, 9000u64, 9099u64, 9050u64).unwrap(),
+            PeakData::new(6, 9050u64, 9149u64, 9100u64).unwrap(),
+            // A background peak.
+            PeakData::new(7, 9120u64, 9199u64, 9150u64).unwrap(),
+        ];
+        let mut evidence = PeakEvidence::new();
+        for (id, sample, significance) in [
+            (0, 0, 6.0),
+            (1, 1, 10.0),
+            (2, 0, 4.5),
+            (3, 1, 12.0),
+            (4, 0, 20.0),
+            (5, 0, 6.0),
+            (6, 0, 6.0),
+            (7, 1, 2.0),
+        ] {
+            evidence.insert(id, sample, significance);
+        }
+        let consensus = tracked_mspc_consensus_peaks(
+            peaks.clone(),
+            &evidence,
+            DEFAULT_COMBINED_STRINGENCY,
+            DEFAULT_WEAK_THRESHOLD,
+            2,
+            0,
+        )
+        .unwrap();
+        let regions: Vec<(u64, u64, u64, Vec<usize>)> = consensus
+            .iter()
+            .map(|peak| {
+                (
+                    peak.peak().start(),
+                    peak.peak().end(),
+                    peak.peak().summit(),
+                    peak.raw_peak_ids().clone(),
+                )
+            })
+            .collect();
+        assert_eq!(regions, vec![(100, 249, 180, vec![0, 1]), (1000, 1149, 1100, vec![2, 3])]);
+        // Without the replicate requirement the unsupported strong peak is confirmed.
+        let consensus = tracked_mspc_consensus_peaks(peaks, &evidence, 1e-8, 1e-4, 1, 0).unwrap();
+        assert_eq!(consensus.len(), 3);
+        assert_eq!(consensus[2].peak().start(), 5000);
+    }
+
+    #[test]
+    fn test_tracked_mspc_consensus_peaks_invalid() {
+        let peaks = vec![PeakData::new(0, 100u64, 199u64, 150u64).unwrap()];
+        let mut evidence = PeakEvidence::new();
+        assert!(tracked_mspc_consensus_peaks(peaks.clone(), &evidence, 1e-8, 1e-4, 1, 0).is_err());
+        evidence.insert(0, 0, 10.0);
+        assert!(tracked_mspc_consensus_peaks(peaks.clone(), &evidence, 0.0, 1e-4, 1, 0).is_err());
+        assert!(tracked_mspc_consensus_peaks(peaks, &evidence, 1e-8, 2.0, 1, 0).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+//! This module contains the specifics of the minimum overlap fraction merging algorithm,
+//! which is analogous to `bedtools intersect -f` and `-r`.
+
+use std::collections::HashMap;
+
+use crate::{
+    error::{ApplicationError, ApplicationErrorType},
+    peaks::{PeakBin, PeakData, TrackedConsensusPeak},
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "peaks::overlap";
+
+/// The default minimum fraction of a peak that must be overlapped to merge two peaks.
+pub const DEFAULT_MIN_OVERLAP: f64 = 0.5;
+
+/// Returns `true` if the peaks overlap by at least the specified fraction of the shorter peak
+/// or, if reciprocal, by at least the specified fraction of both peaks.
+///
+/// # Parameters
+///
+/// * `first` - the first peak
+/// * `second` - the second peak
+/// * `min_overlap` - the minimum overlap fraction
+/// * `reciprocal` - if the minimum overlap fraction must be reached for both peaks
+fn sufficiently_overlapping(
+    first: &PeakData,
+    second: &PeakData,
+    min_overlap: f64,
+    reciprocal: bool,
+) -> bool {
+    let overlap_start = first.start().max(second.start());
+    let overlap_end = first.end().min(second.end());
+    if overlap_start > overlap_end {
+        return false;
+    }
+    let overlap = (overlap_end - overlap_start + 1) as f64;
+    let reference_length = if reciprocal {
+        first.length().max(second.length())
+    } else {
+        first.length().min(second.length())
+    };
+    overlap >= min_overlap * reference_length as f64
+}
+
+/// Creates consensus peaks by merging all peaks that are connected by pairwise overlaps
+/// of at least the specified fraction. Each consensus peak spans all of its raw peaks and
+/// its summit is located at its centre.
+///
+/// # Parameters
+///
+/// * `peaks` - the raw input peaks
+/// * `min_overlap` - the minimum fraction of the shorter peak that must be overlapped
+///   to merge two peaks
+/// * `reciprocal` - if the minimum overlap fraction must be reached for both peaks
+/// * `min_peaks_per_consensus` - the minimum number of raw peaks required to form a consensus peak
+///
+/// # Errors
+///
+/// Returns an error if the minimum overlap fraction is not within (0, 1].
+pub fn tracked_overlap_consensus_peaks(
+    peaks: Vec<PeakData>,
+    min_overlap: f64,
+    reciprocal: bool,
+    min_peaks_per_consensus: usize,
+) -> Result<Vec<TrackedConsensusPeak>, ApplicationError> {
+    if !(min_overlap > 0.0 && min_overlap <= 1.0) {
+        return Err(ApplicationError::new(
+            ApplicationErrorType::ConfigError,
+            format!("The minimum overlap fraction {} is not within (0, 1].", min_overlap),
+        ));
+    }
+    let bins = PeakBin::bin_peaks(peaks);
+    log::debug!(target: LOG_TARGET, "Merging the peaks of {} peak bins.", bins.len());
+    let mut clusters: Vec<Vec<PeakData>> = Vec::new();
+    for bin in bins {
+        // Peaks of different bins never overlap, so only peaks within a bin are compared.
+        let mut bin_peaks = Vec::<PeakData>::from(bin);
+        bin_peaks.sort_unstable_by_key(|peak| (peak.start(), peak.end(), peak.id()));
+        let mut parents: Vec<usize> = (0..bin_peaks.len()).collect();
+        let root = |parents: &Vec<usize>, mut index: usize| {
+            while parents[index] != index {
+                index = parents[index];
+            }
+            index
+        };
+        for first in 0..bin_peaks.len() {
+            for second in (first + 1..bin_peaks.len())
+                .take_while(|second| bin_peaks[*second].start() <= bin_peaks[first].end())
+            {
+                if sufficiently_overlapping(
+                    &bin_peaks[first],
+                    &bin_peaks[second],
+                    min_overlap,
+                    reciprocal,
+                ) {
+                    let first_root = root(&parents, first);
+                    let second_root = root(&parents, second);
+                    parents[first_root.max(second_root)] = first_root.min(second_root);
+                }
+            }
+        }
+        let mut bin_clusters: HashMap<usize, Vec<PeakData>> = HashMap::new();
+        for (index, peak) in bin_peaks.into_iter().enumerate() {
+            bin_clusters
+                .entry(root(&parents, index))
+                .or_default()
+                .push(peak);
+        }
+        let mut bin_clusters: Vec<(usize, Vec<PeakData>)> = bin_clusters.into_iter().collect();
+        bin_clusters.sort_unstable_by_key(|(root, _)| *root);
+        clusters.extend(bin_clusters.into_iter().map(|(_, cluster)| cluster));
+    }
+    let mut consensus_peaks = Vec::new();
+    for (index, cluster) in clusters
+        .into_iter()
+        .filter(|cluster| cluster.len() >= min_peaks_per_consensus)
+        .enumerate()
+    {
+        let start = cluster
+            .iter()
+            .map(PeakData::start)
+            .min()
+            .unwrap_or_default();
+        let end = cluster.iter().map(PeakData::end).max().unwrap_or_default();
+        let peak = PeakData::new(index, start, end, start.midpoint(end)).map_err(|err| {
+            err.with_error_type(ApplicationErrorType::AlgorithmError)
+                .chain(format!(
+                    "Failed to create an overlap consensus peak from the peaks {:?}.",
+                    cluster
+                ))
+        })?;
+        consensus_peaks
+            .push(TrackedConsensusPeak::new(peak, cluster.iter().map(PeakData::id).collect()));
+    }
+    Ok(consensus_peaks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn consensus_regions(
+        peaks: &[(u64, u64)],
+        min_overlap: f64,
+        reciprocal: bool,
+    ) -> Vec<(u64, u64, usize)> {
+        let peaks = peaks
+            .iter()
+            .enumerate()
+            .map(|(id, (start, end))| PeakData::new(id, *start, *end, *start).unwrap())
+            .collect();
+        tracked_overlap_consensus_peaks(peaks, min_overlap, reciprocal, 0)
+            .unwrap()
+            .into_iter()
+            .map(|peak| (peak.peak().start(), peak.peak().end(), peak.raw_peak_ids().len()))
+            .collect()
+    }
+
+    #[test]
+    fn test_sufficiently_overlapping() {
+        let long = PeakData::new(0, 0u64, 99u64, 50u64).unwrap();
+        let short = PeakData::new(1, 80u64, 109u64, 90u64).unwrap();
+        // 20 of 30 bases of the short peak and 20 of 100 bases of the long peak overlap.
+        assert!(sufficiently_overlapping(&long, &short, 0.5, false));
+        assert!(!sufficiently_overlapping(&long, &short, 0.7, false));
+        assert!(sufficiently_overlapping(&long, &short, 0.2, true));
+        assert!(!sufficiently_overlapping(&long, &short, 0.5, true));
+        let adjacent = PeakData::new(2, 100u64, 120u64, 110u64).unwrap();
+        assert!(!sufficiently_overlapping(&long, &adjacent, 0.01, false));
+    }
+
+    #[test]
+    fn test_tracked_overlap_consensus_peaks() {
+        let peaks = [(0, 99), (80, 109), (100, 199), (1000, 1099)];
+        // Adjacent peaks are not merged in contrast to the simple algorithm.
+        assert_eq!(
+            consensus_regions(&peaks, 0.5, false),
+            vec![(0, 109, 2), (100, 199, 1), (1000, 1099, 1)]
+        );
+        assert_eq!(
+            consensus_regions(&peaks, 0.5, true),
+            vec![(0, 99, 1), (80, 109, 1), (100, 199, 1), (1000, 1099, 1)]
+        );
+        // Overlaps are transitive, so chains of overlapping peaks are merged.
+        assert_eq!(consensus_regions(&peaks, 0.1, false), vec![(0, 199, 3), (1000, 1099, 1)]);
+    }
+
+    #[test]
+    fn test_tracked_overlap_consensus_peaks_min_peaks() {
+        let peaks = vec![
+            PeakData::new(0, 0u64, 99u64, 50u64).unwrap(),
+            PeakData::new(1, 10u64, 99u64, 50u64).unwrap(),
+            PeakData::new(2, 500u64, 599u64, 550u64).unwrap(),
+        ];
+        let consensus = tracked_overlap_consensus_peaks(peaks, 0.5, true, 2).unwrap();
+        assert_eq!(consensus.len(), 1);
+        assert_eq!(consensus[0].peak(), PeakData::new(0, 0u64, 99u64, 49u64).unwrap());
+    }
+
+    #[test]
+    fn test_tracked_overlap_consensus_peaks_invalid_fraction() {
+        assert!(tracked_overlap_consensus_peaks(Vec::new(), 0.0, false, 0).is_err());
+        assert!(tracked_overlap_consensus_peaks(Vec::new(), 1.5, false, 0).is_err());
+    }
+}
@@ -1,33 +1,76 @@
 //! This module contains the specifics of the simple peak merging algorithm.
 
-use crate::{error::ApplicationError, peaks::{PeakBin, PeakData}};
+use crate::{
+    error::{ApplicationError, ApplicationErrorType},
+    peaks::{PeakBin, PeakData, TrackedConsensusPeak},
+};
 
-/// Merges overlapping and adjacent peaks.
+/// The log target of this module.
+const LOG_TARGET: &str = "peaks::simple";
+
+/// Merges overlapping and adjacent peaks as well as peaks separated by at most `max_gap` bases.
 /// Returns an error if the merging process fails.
 ///
 /// # Parameters
 /// * `peaks` - the peaks to merge
+/// * `min_peaks_per_bin` - the minimum number of peaks merged into a consensus peak
+/// * `max_gap` - the maximum number of bases between merged peaks
 pub fn merge_peaks(
     peaks: Vec<PeakData>,
     min_peaks_per_bin: usize,
+    max_gap: u64,
 ) -> Result<Vec<PeakData>, ApplicationError> {
+    Ok(tracked_merge_peaks(peaks, min_peaks_per_bin, max_gap)?
+        .into_iter()
+        .map(PeakData::from)
+        .collect())
+}
+
+/// Merges overlapping and adjacent peaks like [`merge_peaks`], but keeps track of the
+/// raw peaks merged into each consensus peak.
+/// Returns an error if the merging process fails.
+///
+/// # Parameters
+/// * `peaks` - the peaks to merge
+/// * `min_peaks_per_bin` - the minimum number of peaks merged into a consensus peak
+/// * `max_gap` - the maximum number of bases between merged peaks
+pub fn tracked_merge_peaks(
+    peaks: Vec<PeakData>,
+    min_peaks_per_bin: usize,
+    max_gap: u64,
+) -> Result<Vec<TrackedConsensusPeak>, ApplicationError> {
     let bins = PeakBin::bin_peaks(peaks);
-    let mut merged_peaks = Vec::with_capacity(bins.len());
+    log::debug!(target: LOG_TARGET, "Merging the peaks of {} peak bins.", bins.len());
+    // Bins are sorted by position and neither overlap nor are adjacent,
+    // so only consecutive bins can be separated by a small gap.
+    let mut groups: Vec<(u64, u64, Vec<usize>)> = Vec::with_capacity(bins.len());
+    for bin in bins {
+        let raw_peak_ids = bin.peaks().iter().map(PeakData::id);
+        match groups.last_mut() {
+            Some((_, end, group_peak_ids))
+                if bin.start().saturating_sub(*end) <= max_gap.saturating_add(1) =>
+            {
+                *end = bin.end().max(*end);
+                group_peak_ids.extend(raw_peak_ids);
+            },
+            _ => groups.push((bin.start(), bin.end(), raw_peak_ids.collect())),
+        }
+    }
+    let mut merged_peaks = Vec::with_capacity(groups.len());
 
-    for (bin_index, bin) in bins
+    for (bin_index, (start, end, raw_peak_ids)) in groups
         .into_iter()
-        .filter(|bin| bin.peaks().len() >= min_peaks_per_bin)
+        .filter(|(_, _, raw_peak_ids)| raw_peak_ids.len() >= min_peaks_per_bin)
         .enumerate()
     {
-        merged_peaks.push(
-            PeakData::new(bin_index, bin.start(), bin.end(), bin.start().midpoint(bin.end()))
-                .map_err(|err| {
-                    err.chain(format!(
-                        "Failed to create a simple merge consensus peak from peak bin {}: {:?}",
-                        bin_index, bin
-                    ))
-                })?,
-        );
+        let peak = PeakData::new(bin_index, start, end, start.midpoint(end)).map_err(|err| {
+            err.with_error_type(ApplicationErrorType::AlgorithmError)
+                .chain(format!(
+                    "Failed to create a simple merge consensus peak from the peaks [{}, {}].",
+                    start, end
+                ))
+        })?;
+        merged_peaks.push(TrackedConsensusPeak::new(peak, raw_peak_ids));
     }
 
     Ok(merged_peaks)
@@ -51,7 +94,7 @@ mod tests {
             PeakData::new(0, 11u64, 29u64, 20u64).unwrap(),
             PeakData::new(1, 259u64, 290u64, 274u64).unwrap(),
         ];
-        let consensus_peaks = merge_peaks(peaks, 0).unwrap();
+        let consensus_peaks = merge_peaks(peaks, 0, 0).unwrap();
         assert_eq!(consensus_peaks.len(), expected_consensus_peaks.len());
         for consensus_peak in consensus_peaks {
             assert!(
@@ -77,7 +120,7 @@ mod tests {
             PeakData::new(0, 11u64, 29u64, 20u64).unwrap(),
             PeakData::new(1, 259u64, 290u64, 274u64).unwrap(),
         ];
-        let consensus_peaks_2 = merge_peaks(peaks.clone(), 2).unwrap();
+        let consensus_peaks_2 = merge_peaks(peaks.clone(), 2, 0).unwrap();
         assert_eq!(consensus_peaks_2.len(), expected_consensus_peaks.len());
         for consensus_peak in consensus_peaks_2 {
             assert!(
@@ -88,7 +131,31 @@ mod tests {
             )
         }
 
-        let consensus_peaks_2 = merge_peaks(peaks.clone(), 3).unwrap();
+        let consensus_peaks_2 = merge_peaks(peaks.clone(), 3, 0).unwrap();
         assert_eq!(consensus_peaks_2, vec![expected_consensus_peaks[0]]);
     }
+
+    #[test]
+    fn test_merge_peaks_max_gap() {
+        let peaks = vec![
+            PeakData::new(0, 10u64, 20u64, 15u64).unwrap(),
+            PeakData::new(1, 26u64, 30u64, 28u64).unwrap(),
+            PeakData::new(2, 41u64, 50u64, 45u64).unwrap(),
+        ];
+        // Peaks separated by 5 bases are merged, while peaks separated by 10 bases are not.
+        let consensus_peaks = tracked_merge_peaks(peaks.clone(), 0, 5).unwrap();
+        assert_eq!(
+            consensus_peaks,
+            vec![
+                TrackedConsensusPeak::new(
+                    PeakData::new(0, 10u64, 30u64, 20u64).unwrap(),
+                    vec![0, 1]
+                ),
+                TrackedConsensusPeak::new(PeakData::new(1, 41u64, 50u64, 45u64).unwrap(), vec![2]),
+            ]
+        );
+        assert_eq!(merge_peaks(peaks.clone(), 0, 4).unwrap().len(), 3);
+        assert_eq!(merge_peaks(peaks.clone(), 0, 10).unwrap().len(), 1);
+        assert_eq!(merge_peaks(peaks, 2, 5).unwrap().len(), 1);
+    }
 }
@@ -245,7 +245,7 @@ fn test_peak_bin_insert() {
 
 #[test]
 fn test_peak_bin_try_insert() {
-    let peaks = vec![
+    let peaks = [
         PeakData::new(0, 12u64, 22u64, 18u64).unwrap(),
         PeakData::new(1, 11u64, 21u64, 17u64).unwrap(),
         PeakData::new(2, 23u64, 26u64, 24u64).unwrap(),
@@ -324,3 +324,71 @@ fn test_peak_bin_bin_peaks() {
         assert!(&peaks[6..].contains(peak));
     }
 }
+
+#[test]
+fn test_sort_peaks_by_position() {
+    let peaks = vec![
+        PeakData::new(0, 12u64, 24u64, 18u64).unwrap(),
+        PeakData::new(1, 11u64, 21u64, 17u64).unwrap(),
+        PeakData::new(2, 11u64, 21u64, 16u64).unwrap(),
+        PeakData::new(3, 11u64, 20u64, 17u64).unwrap(),
+        PeakData::new(4, u64::MAX - 10, u64::MAX, u64::MAX - 5).unwrap(),
+        PeakData::new(5, 0u64, u64::MAX, 17u64).unwrap(),
+        PeakData::new(6, 11u64, 21u64, 16u64).unwrap(),
+    ];
+    let expected_order = vec![
+        peaks[5], peaks[3], peaks[2], peaks[6], peaks[1], peaks[0], peaks[4],
+    ];
+    assert_eq!(sort_peaks_by_position(peaks.clone()), expected_order);
+    let mut reversed_peaks = peaks;
+    reversed_peaks.reverse();
+    assert_eq!(sort_peaks_by_position(reversed_peaks), expected_order);
+}
+
+#[test]
+fn test_consensus_peak_algorithm_from_str() {
+    for algorithm in [
+        ConsensusPeakAlgorithm::Gipfelkreuzer,
+        ConsensusPeakAlgorithm::Simple,
+        ConsensusPeakAlgorithm::Harmonised,
+        ConsensusPeakAlgorithm::Overlap,
+        ConsensusPeakAlgorithm::Mspc,
+    ] {
+        assert_eq!(
+            algorithm
+                .to_string()
+                .parse::<ConsensusPeakAlgorithm>()
+                .unwrap(),
+            algorithm
+        );
+    }
+    assert_eq!("Simple".parse::<ConsensusPeakAlgorithm>().unwrap(), ConsensusPeakAlgorithm::Simple);
+    assert!("unknown".parse::<ConsensusPeakAlgorithm>().is_err());
+}
+
+#[test]
+fn test_peak_data_32_bit_coordinates() {
+    let peak = PeakData32::from_coordinates(3, 100u32, 199u32, 150u32).unwrap();
+    assert_eq!(peak.start(), 100u32);
+    assert_eq!(peak.length(), 100);
+    assert!(PeakData32::from_coordinates(3, 200u32, 199u32, 199u32).is_err());
+    let wide_peak = PeakData64::from(peak);
+    assert_eq!(wide_peak, PeakData::new(3, 100u64, 199u64, 150u64).unwrap());
+    assert_eq!(PeakData32::try_from(wide_peak).unwrap(), peak);
+    let huge_peak = PeakData::new(4, 0u64, u64::from(u32::MAX) + 1, 10u64).unwrap();
+    assert!(huge_peak.try_into_coordinates::<u32>().is_err());
+}
+
+#[test]
+fn test_peak_data_32_bit_serde() {
+    let peak = PeakData32::from_coordinates(3, 100u32, 199u32, 150u32).unwrap();
+    let json = serde_json::to_string(&peak).unwrap();
+    assert_eq!(json, "{\"id\":3,\"start\":100,\"end\":199,\"summit\":150}");
+    assert_eq!(serde_json::from_str::<PeakData32>(&json).unwrap(), peak);
+    assert!(
+        serde_json::from_str::<PeakData32>(
+            "{\"id\":3,\"start\":100,\"end\":5000000000,\"summit\":150}"
+        )
+        .is_err()
+    );
+}
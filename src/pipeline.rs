@@ -0,0 +1,1829 @@
+//! This module runs consensus peak generation from peak files to the consensus peak output
+//! file, which is the default command of the `Gipfelkreuzer` application.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
+
+use crate::{
+    annotation::{
+        DEFAULT_CATEGORY_COLUMN, PeakAnnotation, RegionAnnotation, write_annotation_summary,
+    },
+    assembly::{AssemblyCheck, AssemblyProfile, assembly_inconsistencies},
+    blacklist::{BlacklistMode, remove_blacklisted_peaks, trim_blacklisted_peaks},
+    chromosome::Chromosome,
+    concordance::{SummitConcordance, write_summit_concordance},
+    consensus::{ConsensusBuilder, TrackedConsensus, untrack_consensus},
+    coverage::{GenomeCoverage, chromosome_sizes_from_file, write_coverage_table},
+    density::{
+        DEFAULT_DENSITY_WINDOW, DensityCorrelations, peak_density_track,
+        write_density_correlations, write_density_track,
+    },
+    duplicates::{
+        DEFAULT_DUPLICATE_OVERLAP, NearDuplicate, merge_near_duplicates, near_duplicates,
+        write_duplicate_table,
+    },
+    error::{ApplicationError, ApplicationErrorType},
+    genes::{
+        DEFAULT_PROMOTER_WINDOW, GeneAnnotation, PeakClass, PeakClassCounts, write_class_summary,
+    },
+    input::{
+        InputFormat, bed_to_peak_names, bed_to_peak_stream, bed_to_peaks, bed_to_peaks_with_format,
+    },
+    manifest::{RunManifest, file_entries, run_identifier, with_run_identifier, write_manifest},
+    metadata::{
+        ProcessTrace, RunMetrics, TOOL_NAME, write_multiqc_metrics, write_nf_core_metadata,
+    },
+    outliers::{OutlierThresholds, outlier_peaks, write_outlier_table},
+    output::{
+        OutputFormat, PeakContribution, RecordFields, stable_peak_names, write_peaks_to_bed,
+        write_peaks_to_bed_with_columns,
+    },
+    peak_set::PeakSet,
+    peaks::PeakData,
+    provenance::{
+        ConsensusProvenance, SampleIndex, consensus_provenance, filter_min_samples,
+        merge_duplicate_provenance, peak_contributions, provenance_column, write_occupancy_matrix,
+        write_peak_name_mapping, write_provenance_table,
+    },
+    samples::{SampleSheet, condition_output_file},
+    scores::{PeakScores, ScoreAggregation, ScoreSource, consensus_peak_scores, support_scores},
+    stats::{
+        ConsensusStatistics, ConsensusSummary, InputPeakCount, SampleIntersections,
+        write_consensus_statistics, write_intersection_table, write_summary_table,
+    },
+    strand::{Strand, StrandMode},
+    windows::{DEFAULT_SUMMIT_FLANK, summit_windows, write_window_sequences},
+};
+
+#[cfg(feature = "bam")]
+use crate::{
+    frip::{fraction_of_reads_in_peaks, write_frip_table},
+    metadata::multiqc_sample_name,
+};
+
+#[cfg(feature = "bigwig")]
+use crate::signal::{SignalTrack, peak_signals, refine_summits, write_peaks_to_bed_with_signal};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "pipeline";
+
+/// The number of peaks per chromosome.
+pub type PeakCounts = HashMap<Chromosome, usize>;
+
+/// Returns the number of consensus peaks per chromosome.
+///
+/// # Parameters
+///
+/// * `consensus` - the consensus peaks grouped by chromosome
+fn consensus_peak_counts(consensus: &TrackedConsensus) -> PeakCounts {
+    consensus
+        .iter()
+        .map(|(chromosome, peaks)| (*chromosome, peaks.len()))
+        .collect()
+}
+
+/// The layout and content of the consensus peak output file.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConsensusOutput {
+    fields: usize,
+    format: Option<OutputFormat>,
+    stable_ids: bool,
+    score_aggregation: ScoreAggregation,
+    score_source: ScoreSource,
+    #[cfg(feature = "bigwig")]
+    bigwig: Vec<PathBuf>,
+    #[cfg(feature = "bigwig")]
+    refine_summits: bool,
+}
+
+impl ConsensusOutput {
+    /// Creates output options writing BED3 records.
+    pub fn new() -> Self {
+        Self {
+            fields: 3,
+            format: None,
+            stable_ids: false,
+            score_aggregation: ScoreAggregation::default(),
+            score_source: ScoreSource::default(),
+            #[cfg(feature = "bigwig")]
+            bigwig: Vec::new(),
+            #[cfg(feature = "bigwig")]
+            refine_summits: false,
+        }
+    }
+
+    /// Sets the number of BED fields / columns, which is ignored if an output format preset
+    /// is specified.
+    ///
+    /// # Parameters
+    ///
+    /// * `fields` - the number of standard BED fields / columns
+    pub fn fields(mut self, fields: usize) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    /// Sets the output format preset replacing the default record layout.
+    ///
+    /// # Parameters
+    ///
+    /// * `format` - the output format preset or `None` to write BED records
+    pub fn format(mut self, format: Option<OutputFormat>) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Names the consensus peaks by their coordinates instead of numbering them.
+    ///
+    /// # Parameters
+    ///
+    /// * `stable_ids` - if the peak names are derived from the coordinates
+    pub fn stable_ids(mut self, stable_ids: bool) -> Self {
+        self.stable_ids = stable_ids;
+        self
+    }
+
+    /// Sets the function aggregating the statistical values of the raw peaks
+    /// of each consensus peak.
+    ///
+    /// # Parameters
+    ///
+    /// * `score_aggregation` - the aggregation function
+    pub fn score_aggregation(mut self, score_aggregation: ScoreAggregation) -> Self {
+        self.score_aggregation = score_aggregation;
+        self
+    }
+
+    /// Sets the source of the score field of the consensus peaks.
+    ///
+    /// # Parameters
+    ///
+    /// * `score_source` - the source of the scores
+    pub fn score_source(mut self, score_source: ScoreSource) -> Self {
+        self.score_source = score_source;
+        self
+    }
+
+    /// Annotates the consensus peaks with the signal of the specified BigWig tracks.
+    ///
+    /// # Parameters
+    ///
+    /// * `bigwig` - the BigWig coverage tracks
+    /// * `refine_summits` - if the summits are moved to the position of the highest signal
+    #[cfg(feature = "bigwig")]
+    pub fn bigwig(mut self, bigwig: Vec<PathBuf>, refine_summits: bool) -> Self {
+        self.bigwig = bigwig;
+        self.refine_summits = refine_summits;
+        self
+    }
+
+    /// Returns the number of fields / columns of the output,
+    /// which is defined by the output format preset if specified.
+    pub fn columns(&self) -> usize {
+        self.format.map_or(self.fields, |format| format.fields())
+    }
+
+    /// Returns if the output contains the score column and thereby the statistical values
+    /// of the consensus peaks.
+    pub fn requires_scores(&self) -> bool {
+        self.columns() > 4
+    }
+
+    /// Returns if the records include their contributing raw peaks and samples,
+    /// which is the case for JSON Lines and GFF3 output.
+    pub fn contribution_output(&self) -> bool {
+        matches!(self.format, Some(OutputFormat::Json | OutputFormat::Gff3))
+    }
+
+    /// Returns if the contributing raw peaks and samples of the consensus peaks are required
+    /// for the output records or their scores.
+    pub fn contributions_required(&self) -> bool {
+        self.contribution_output() || self.score_source != ScoreSource::Aggregated
+    }
+
+    /// Returns if the provenance of the consensus peaks is required for the output.
+    pub fn requires_provenance(&self) -> bool {
+        self.contributions_required() || self.requires_scores()
+    }
+
+    /// Writes the consensus peaks of a run to the output file.
+    /// If BigWig tracks are specified, the peaks are annotated with their signal and the
+    /// consensus peaks of the run are replaced by the peaks with refined summits if requested.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - the output file
+    /// * `run` - the consensus peaks and the information about their raw peaks
+    /// * `sample_names` - the names of the input files in input order
+    /// * `columns` - the additional output columns by peak ID
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the signal cannot be extracted or the output file cannot be written.
+    pub fn write<T: AsRef<Path>>(
+        &self,
+        path: T,
+        run: &mut ConsensusRun,
+        sample_names: &[String],
+        columns: &HashMap<usize, Vec<String>>,
+    ) -> Result<(), ApplicationError> {
+        let contributions = match &run.provenance {
+            Some(provenance) if self.contributions_required() => {
+                peak_contributions(&run.consensus, provenance, sample_names)
+            },
+            _ => HashMap::new(),
+        };
+        let mut record_fields = RecordFields::new().strands(run.strands.clone());
+        if let Some(format) = self.format {
+            record_fields = record_fields.format(format);
+        }
+        if self.stable_ids {
+            record_fields = record_fields.names(stable_peak_names(&run.consensus));
+        }
+        // The statistical columns are only written if the output contains the score column.
+        if self.requires_scores() {
+            record_fields = record_fields.scores(self.consensus_scores(
+                run,
+                &contributions,
+                sample_names.len(),
+            ));
+        }
+        let record_fields = record_fields.contributions(contributions);
+        #[cfg(feature = "bigwig")]
+        if !self.bigwig.is_empty() {
+            if self.contribution_output() {
+                return Err(ApplicationError::new(
+                    ApplicationErrorType::ValidationError,
+                    "Signal columns cannot be added to JSON or GFF3 output.",
+                )
+                .with_hint("Use a BED based output format together with \"--bigwig\"."));
+            }
+            let mut tracks = self
+                .bigwig
+                .iter()
+                .map(SignalTrack::open)
+                .collect::<Result<Vec<SignalTrack>, ApplicationError>>()?;
+            if self.refine_summits {
+                run.consensus = refine_summits(&mut tracks, std::mem::take(&mut run.consensus))
+                    .map_err(|err| err.chain("Failed to refine the consensus peak summits."))?;
+            }
+            let signals = peak_signals(&mut tracks, &run.consensus)
+                .map_err(|err| err.chain("Failed to extract the consensus peak signal."))?;
+            return write_peaks_to_bed_with_signal(
+                path,
+                &run.consensus,
+                self.columns(),
+                &signals,
+                columns,
+                &record_fields,
+            );
+        }
+        write_peaks_to_bed_with_columns(
+            path,
+            &run.consensus,
+            self.columns(),
+            columns,
+            &record_fields,
+        )
+    }
+
+    /// Returns the statistical values of the consensus peaks by peak ID.
+    /// The values of the raw peaks each consensus peak was created from are aggregated and
+    /// the score is replaced by the support of the consensus peak if requested.
+    ///
+    /// # Parameters
+    ///
+    /// * `run` - the consensus peaks and the information about their raw peaks
+    /// * `contributions` - the contributing raw peaks and samples by chromosome and peak ID
+    /// * `samples` - the number of samples
+    fn consensus_scores(
+        &self,
+        run: &ConsensusRun,
+        contributions: &HashMap<(Chromosome, usize), PeakContribution>,
+        samples: usize,
+    ) -> HashMap<usize, PeakScores> {
+        let Some(provenance) = &run.provenance else {
+            return HashMap::new();
+        };
+        let mut scores =
+            consensus_peak_scores(provenance, &run.raw_peak_scores, self.score_aggregation);
+        for (peak_id, score) in support_scores(contributions, self.score_source, samples) {
+            let peak_scores = scores.entry(peak_id).or_default();
+            *peak_scores = peak_scores.with_score(Some(score));
+        }
+        scores
+    }
+}
+
+impl Default for ConsensusOutput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The consensus peaks generated from peak files together with the information about the
+/// raw peaks required for the output.
+#[derive(Clone, Debug, Default)]
+pub struct ConsensusRun {
+    /// The consensus peaks grouped by chromosome.
+    pub consensus: HashMap<Chromosome, Vec<PeakData>>,
+    /// The strands of the consensus peaks by peak ID if generated per strand.
+    pub strands: HashMap<usize, Strand>,
+    /// The provenance of the consensus peaks if required.
+    pub provenance: Option<ConsensusProvenance>,
+    /// The consensus peaks keeping track of their raw peaks before near-duplicates are merged
+    /// and blacklisted regions are trimmed if retained.
+    pub tracked_consensus: Option<TrackedConsensus>,
+    /// The number of raw peaks per chromosome.
+    pub input_peaks: PeakCounts,
+    /// The number of consensus peaks per chromosome discarded by the minimum number of raw
+    /// peaks per consensus peak if counted.
+    pub filtered_consensus_peaks: PeakCounts,
+    /// The number of raw peaks of each input file in input order.
+    pub peaks_per_file: Vec<usize>,
+    /// The statistical values of the raw peaks by raw peak ID if parsed.
+    pub raw_peak_scores: HashMap<usize, PeakScores>,
+    /// The peaks of each input file in input order if retained.
+    pub sample_peaks: Vec<PeakSet>,
+    /// The raw peaks remaining after blacklisted peaks are removed if retained.
+    pub raw_peaks: Option<HashMap<Chromosome, Vec<PeakData>>>,
+    /// The near-duplicate consensus peaks if detected.
+    pub duplicates: Vec<NearDuplicate>,
+    /// The number of consensus peaks merged into other consensus peaks as near-duplicates.
+    pub merged_duplicates: usize,
+}
+
+/// The checks and additional output of a consensus peak generation run by
+/// [`ConsensusPipeline::run_and_write`] besides the consensus peak output file.
+/// Additional output is only written if its path is specified.
+/// The options mirror the command line arguments of the `Gipfelkreuzer` application.
+#[derive(Clone, Debug)]
+pub struct ConsensusRunOptions {
+    /// The parameters affecting the content of the consensus peak output by their argument
+    /// names, which identify the run and are recorded in the run manifest.
+    pub parameters: BTreeMap<String, String>,
+    /// The additional input files besides the peak files recorded in the run manifest,
+    /// such as annotations, chromosome sizes, genome sequences and BAM or BigWig files.
+    pub auxiliary_input_files: Vec<PathBuf>,
+    /// A run manifest whose input files must match the current input files.
+    pub verify_inputs: Option<PathBuf>,
+    /// The output path of the run manifest.
+    pub manifest: Option<PathBuf>,
+    /// Appends the run identifier derived from the parameters and input files to the name of
+    /// the consensus peak output file.
+    pub run_id_suffix: bool,
+    /// The chromosome sizes of the genome.
+    pub chromosome_sizes: Option<PathBuf>,
+    /// The handling of input files appearing to originate from different genome assemblies
+    /// or `None` to not check the assemblies.
+    pub assembly_check: Option<AssemblyCheck>,
+    /// The output path of the near-duplicate report.
+    pub duplicate_report: Option<PathBuf>,
+    /// Merges near-duplicate consensus peaks.
+    pub merge_duplicates: bool,
+    /// The minimum reciprocal overlap fraction of near-duplicate consensus peaks.
+    pub duplicate_overlap: f64,
+    /// The reference annotation to label the consensus peaks with.
+    pub annotate_with: Option<PathBuf>,
+    /// The 1-based column of the reference annotation containing the labels.
+    pub annotation_column: usize,
+    /// The output path of the annotation summary
+    /// [default: next to the consensus peak output file].
+    pub annotation_summary: Option<PathBuf>,
+    /// The gene annotation to assign the nearest gene to each consensus peak with.
+    pub gtf: Option<PathBuf>,
+    /// The maximum distance of a promoter-proximal peak summit to the TSS.
+    pub promoter_window: u64,
+    /// The output path of the promoter / distal class summary
+    /// [default: next to the consensus peak output file].
+    pub promoter_summary: Option<PathBuf>,
+    /// Appends the names of the samples supporting each consensus peak as output column.
+    pub provenance_column: bool,
+    /// The output path of the provenance table.
+    pub provenance_table: Option<PathBuf>,
+    /// The output path of the mapping of consensus peak names to raw peak names.
+    pub peak_name_mapping: Option<PathBuf>,
+    /// The output path of the occupancy matrix.
+    pub occupancy_matrix: Option<PathBuf>,
+    /// The output path of the summary table.
+    pub stats_file: Option<PathBuf>,
+    /// Writes the summary table to stderr.
+    pub summary: bool,
+    /// Generates and writes the consensus peaks of each condition of the sample sheet.
+    pub per_condition: bool,
+    /// The output path of the summit windows.
+    pub summit_windows: Option<PathBuf>,
+    /// The output path of the summit window sequences.
+    pub summit_sequences: Option<PathBuf>,
+    /// The number of bases flanking the summit in the summit windows.
+    pub summit_flank: u64,
+    /// The genome sequence to extract the summit window sequences from.
+    pub genome: Option<PathBuf>,
+    /// The output path of the genome coverage table.
+    pub genome_coverage: Option<PathBuf>,
+    /// The output path of the outlier report.
+    pub outlier_report: Option<PathBuf>,
+    /// The thresholds of the outlier report.
+    pub outlier_thresholds: OutlierThresholds,
+    /// The output path of the peak density track.
+    pub density_track: Option<PathBuf>,
+    /// The output path of the peak density correlations of the samples.
+    pub density_correlation: Option<PathBuf>,
+    /// The window size of the peak density track and correlations.
+    pub density_window: u64,
+    /// The BAM files to calculate the fraction of reads in consensus peaks for.
+    #[cfg(feature = "bam")]
+    pub frip_bam: Vec<PathBuf>,
+    /// The output path of the FRiP QC table [default: next to the consensus peak output file].
+    #[cfg(feature = "bam")]
+    pub frip_output: Option<PathBuf>,
+    /// The output path of the sample intersection summary.
+    pub intersection_summary: Option<PathBuf>,
+    /// The output directory of the summit concordance.
+    pub summit_concordance: Option<PathBuf>,
+    /// The output path of the consensus statistics.
+    pub stats_output: Option<PathBuf>,
+    /// The output path of the MultiQC metrics.
+    pub multiqc: Option<PathBuf>,
+    /// The output directory of the nf-core metadata.
+    pub nf_core_metadata: Option<PathBuf>,
+    /// The process name recorded in the nf-core metadata.
+    pub nf_core_process_name: String,
+}
+
+impl ConsensusRunOptions {
+    /// Returns all files and directories written by a consensus peak generation run
+    /// except for the manifest itself and the output of the individual conditions.
+    ///
+    /// # Parameters
+    ///
+    /// * `output_file` - the resolved consensus peak output file
+    pub fn output_paths(&self, output_file: &Path) -> Vec<PathBuf> {
+        let mut paths = vec![output_file.to_path_buf()];
+        paths.extend(
+            [
+                &self.nf_core_metadata,
+                &self.multiqc,
+                &self.stats_output,
+                &self.stats_file,
+                &self.intersection_summary,
+                &self.density_correlation,
+                &self.density_track,
+                &self.summit_concordance,
+                &self.genome_coverage,
+                &self.summit_windows,
+                &self.summit_sequences,
+                &self.outlier_report,
+                &self.duplicate_report,
+                &self.provenance_table,
+                &self.peak_name_mapping,
+                &self.occupancy_matrix,
+            ]
+            .into_iter()
+            .flatten()
+            .cloned(),
+        );
+        if self.annotate_with.is_some() {
+            paths.push(self.annotation_summary_path(output_file));
+        }
+        if self.gtf.is_some() {
+            paths.push(self.promoter_summary_path(output_file));
+        }
+        #[cfg(feature = "bam")]
+        if !self.frip_bam.is_empty() {
+            paths.push(self.frip_output_path(output_file));
+        }
+        paths
+    }
+
+    /// Returns the annotation summary table path.
+    /// If no path has been specified the table is written next to the specified output file.
+    ///
+    /// # Parameters
+    ///
+    /// * `output_file` - the resolved consensus peak output file
+    fn annotation_summary_path(&self, output_file: &Path) -> PathBuf {
+        self.annotation_summary
+            .clone()
+            .unwrap_or_else(|| sibling_output_file(output_file, "_annotation.tsv"))
+    }
+
+    /// Returns the promoter / distal class summary table path.
+    /// If no path has been specified the table is written next to the specified output file.
+    ///
+    /// # Parameters
+    ///
+    /// * `output_file` - the resolved consensus peak output file
+    fn promoter_summary_path(&self, output_file: &Path) -> PathBuf {
+        self.promoter_summary
+            .clone()
+            .unwrap_or_else(|| sibling_output_file(output_file, "_promoter.tsv"))
+    }
+
+    /// Returns the FRiP QC table path.
+    /// If no path has been specified the table is written next to the specified output file.
+    ///
+    /// # Parameters
+    ///
+    /// * `output_file` - the resolved consensus peak output file
+    #[cfg(feature = "bam")]
+    fn frip_output_path(&self, output_file: &Path) -> PathBuf {
+        self.frip_output
+            .clone()
+            .unwrap_or_else(|| sibling_output_file(output_file, "_frip.tsv"))
+    }
+}
+
+impl Default for ConsensusRunOptions {
+    fn default() -> Self {
+        Self {
+            parameters: BTreeMap::new(),
+            auxiliary_input_files: Vec::new(),
+            verify_inputs: None,
+            manifest: None,
+            run_id_suffix: false,
+            chromosome_sizes: None,
+            assembly_check: None,
+            duplicate_report: None,
+            merge_duplicates: false,
+            duplicate_overlap: DEFAULT_DUPLICATE_OVERLAP,
+            annotate_with: None,
+            annotation_column: DEFAULT_CATEGORY_COLUMN,
+            annotation_summary: None,
+            gtf: None,
+            promoter_window: DEFAULT_PROMOTER_WINDOW,
+            promoter_summary: None,
+            provenance_column: false,
+            provenance_table: None,
+            peak_name_mapping: None,
+            occupancy_matrix: None,
+            stats_file: None,
+            summary: false,
+            per_condition: false,
+            summit_windows: None,
+            summit_sequences: None,
+            summit_flank: DEFAULT_SUMMIT_FLANK,
+            genome: None,
+            genome_coverage: None,
+            outlier_report: None,
+            outlier_thresholds: OutlierThresholds::default(),
+            density_track: None,
+            density_correlation: None,
+            density_window: DEFAULT_DENSITY_WINDOW,
+            #[cfg(feature = "bam")]
+            frip_bam: Vec::new(),
+            #[cfg(feature = "bam")]
+            frip_output: None,
+            intersection_summary: None,
+            summit_concordance: None,
+            stats_output: None,
+            multiqc: None,
+            nf_core_metadata: None,
+            nf_core_process_name: "GIPFELKREUZER".to_string(),
+        }
+    }
+}
+
+/// Returns the path of an additional output file next to the consensus peak output file,
+/// whose name is the file stem of the output file followed by the specified suffix.
+///
+/// # Parameters
+///
+/// * `output_file` - the resolved consensus peak output file
+/// * `suffix` - the suffix of the additional output file name
+fn sibling_output_file(output_file: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = output_file.file_stem().unwrap_or_default().to_os_string();
+    file_name.push(suffix);
+    output_file.with_file_name(file_name)
+}
+
+/// The consensus peaks of all chromosomes together with the information about the raw peaks
+/// collected while parsing or streaming the input files.
+struct GeneratedConsensus {
+    consensus: TrackedConsensus,
+    strands: HashMap<usize, Strand>,
+    input_peaks: PeakCounts,
+    unfiltered_consensus_peaks: PeakCounts,
+    peaks_per_file: Vec<usize>,
+    raw_peak_scores: HashMap<usize, PeakScores>,
+    sample_peaks: Vec<PeakSet>,
+    raw_peaks: Option<HashMap<Chromosome, Vec<PeakData>>>,
+}
+
+/// A pipeline generating consensus peaks from peak files, which parses the input files,
+/// removes or trims blacklisted regions, creates the consensus peaks, filters them by the
+/// number of supporting samples and merges near-duplicates.
+/// The available options mirror the command line arguments of the `Gipfelkreuzer` application.
+///
+/// # Example
+///
+/// ```no_run
+/// use gipfelkreuzer::{
+///     consensus::ConsensusBuilder,
+///     pipeline::{ConsensusOutput, ConsensusPipeline},
+/// };
+///
+/// let output = ConsensusOutput::new().fields(6);
+/// let pipeline = ConsensusPipeline::new(ConsensusBuilder::new())
+///     .min_samples(2)
+///     .output(output.clone());
+/// let mut run = pipeline.run_files(&["sample_1.narrowPeak", "sample_2.narrowPeak"]).unwrap();
+/// let sample_names = vec!["sample_1".to_string(), "sample_2".to_string()];
+/// output
+///     .write("consensus.bed", &mut run, &sample_names, &Default::default())
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct ConsensusPipeline {
+    builder: ConsensusBuilder,
+    output: ConsensusOutput,
+    input_format: InputFormat,
+    max_errors: Option<usize>,
+    strand_mode: StrandMode,
+    streaming: bool,
+    blacklist: Option<Arc<PeakSet>>,
+    blacklist_mode: BlacklistMode,
+    min_samples: usize,
+    duplicate_overlap: Option<f64>,
+    merge_duplicates: bool,
+    provenance: bool,
+    count_filtered: bool,
+    retain_tracked_consensus: bool,
+    retain_sample_peaks: bool,
+    retain_raw_peaks: bool,
+}
+
+impl ConsensusPipeline {
+    /// Creates a pipeline generating consensus peaks with the specified builder
+    /// and writing BED3 records.
+    ///
+    /// # Parameters
+    ///
+    /// * `builder` - the builder configuring consensus peak generation
+    pub fn new(builder: ConsensusBuilder) -> Self {
+        Self {
+            builder,
+            output: ConsensusOutput::new(),
+            input_format: InputFormat::Auto,
+            max_errors: None,
+            strand_mode: StrandMode::default(),
+            streaming: false,
+            blacklist: None,
+            blacklist_mode: BlacklistMode::default(),
+            min_samples: 0,
+            duplicate_overlap: None,
+            merge_duplicates: false,
+            provenance: false,
+            count_filtered: false,
+            retain_tracked_consensus: false,
+            retain_sample_peaks: false,
+            retain_raw_peaks: false,
+        }
+    }
+
+    /// Sets the output the consensus peaks are generated for, which defines the statistical
+    /// values and the provenance collected for the raw peaks.
+    ///
+    /// # Parameters
+    ///
+    /// * `output` - the output options
+    pub fn output(mut self, output: ConsensusOutput) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Sets the format defining the interpretation of the columns of the input files.
+    ///
+    /// # Parameters
+    ///
+    /// * `input_format` - the input file format
+    pub fn input_format(mut self, input_format: InputFormat) -> Self {
+        self.input_format = input_format;
+        self
+    }
+
+    /// Collects up to the specified number of problems of the input files before failing
+    /// instead of failing on the first problem.
+    ///
+    /// # Parameters
+    ///
+    /// * `max_errors` - the maximum number of problems to collect or `None` to fail immediately
+    pub fn max_errors(mut self, max_errors: Option<usize>) -> Self {
+        self.max_errors = max_errors;
+        self
+    }
+
+    /// Sets if consensus peaks are generated separately per strand.
+    ///
+    /// # Parameters
+    ///
+    /// * `strand_mode` - the handling of the strand of the raw peaks
+    pub fn strand_mode(mut self, strand_mode: StrandMode) -> Self {
+        self.strand_mode = strand_mode;
+        self
+    }
+
+    /// Streams the input files chromosome by chromosome, so only the raw peaks of a single
+    /// chromosome are kept in memory unless the consensus peak algorithm requires the
+    /// statistical values of all raw peaks. Peaks are not separated by strand when streaming.
+    ///
+    /// # Parameters
+    ///
+    /// * `streaming` - if the input files are streamed
+    pub fn streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    /// Sets the blacklisted regions and how they are handled.
+    ///
+    /// # Parameters
+    ///
+    /// * `blacklist` - the blacklisted regions or `None` to keep all peaks
+    /// * `blacklist_mode` - if overlapping raw peaks are removed or consensus peaks are trimmed
+    pub fn blacklist(mut self, blacklist: Option<PeakSet>, blacklist_mode: BlacklistMode) -> Self {
+        self.blacklist = blacklist.map(Arc::new);
+        self.blacklist_mode = blacklist_mode;
+        self
+    }
+
+    /// Sets the blacklisted regions like [`blacklist`](Self::blacklist), but parses them from
+    /// a BED file.
+    ///
+    /// # Parameters
+    ///
+    /// * `blacklist` - the BED file of the blacklisted regions or `None` to keep all peaks
+    /// * `blacklist_mode` - if overlapping raw peaks are removed or consensus peaks are trimmed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the blacklist cannot be parsed.
+    pub fn blacklist_file<T: AsRef<Path>>(
+        self,
+        blacklist: Option<T>,
+        blacklist_mode: BlacklistMode,
+    ) -> Result<Self, ApplicationError> {
+        let blacklist = blacklist
+            .map(|blacklist_path| {
+                bed_to_peaks(&[blacklist_path.as_ref()])
+                    .map(PeakSet::from)
+                    .map_err(|err| {
+                        err.chain(format!(
+                            "Failed to parse the blacklist \"{}\".",
+                            blacklist_path.as_ref().display()
+                        ))
+                    })
+            })
+            .transpose()?;
+        Ok(self.blacklist(blacklist, blacklist_mode))
+    }
+
+    /// Sets the minimum number of samples contributing raw peaks to a consensus peak.
+    ///
+    /// # Parameters
+    ///
+    /// * `min_samples` - the minimum number of samples per consensus peak
+    pub fn min_samples(mut self, min_samples: usize) -> Self {
+        self.min_samples = min_samples;
+        self
+    }
+
+    /// Detects near-duplicate consensus peaks and merges them if requested.
+    ///
+    /// # Parameters
+    ///
+    /// * `duplicate_overlap` - the minimum fraction of the shorter peak covered by the longer
+    ///   peak or `None` to skip the detection
+    /// * `merge_duplicates` - if near-duplicates are merged into a single consensus peak
+    pub fn near_duplicates(
+        mut self,
+        duplicate_overlap: Option<f64>,
+        merge_duplicates: bool,
+    ) -> Self {
+        self.duplicate_overlap = duplicate_overlap;
+        self.merge_duplicates = merge_duplicates;
+        self
+    }
+
+    /// Determines the provenance of the consensus peaks even if not required by the output.
+    ///
+    /// # Parameters
+    ///
+    /// * `provenance` - if the provenance is determined
+    pub fn provenance(mut self, provenance: bool) -> Self {
+        self.provenance = provenance;
+        self
+    }
+
+    /// Counts the consensus peaks discarded by the minimum number of raw peaks
+    /// per consensus peak.
+    ///
+    /// # Parameters
+    ///
+    /// * `count_filtered` - if the discarded consensus peaks are counted
+    pub fn count_filtered(mut self, count_filtered: bool) -> Self {
+        self.count_filtered = count_filtered;
+        self
+    }
+
+    /// Retains the consensus peaks keeping track of their raw peaks in the result.
+    ///
+    /// # Parameters
+    ///
+    /// * `retain` - if the tracked consensus peaks are retained
+    pub fn retain_tracked_consensus(mut self, retain: bool) -> Self {
+        self.retain_tracked_consensus = retain;
+        self
+    }
+
+    /// Retains the peaks of each input file in the result, for example to compare the samples.
+    /// When streaming, the input files are loaded completely after consensus peak generation.
+    ///
+    /// # Parameters
+    ///
+    /// * `retain` - if the peaks of each input file are retained
+    pub fn retain_sample_peaks(mut self, retain: bool) -> Self {
+        self.retain_sample_peaks = retain;
+        self
+    }
+
+    /// Retains the raw peaks in the result, which are otherwise consumed by consensus peak
+    /// generation. The raw peaks are not retained when streaming.
+    ///
+    /// # Parameters
+    ///
+    /// * `retain` - if the raw peaks are retained
+    pub fn retain_raw_peaks(mut self, retain: bool) -> Self {
+        self.retain_raw_peaks = retain;
+        self
+    }
+
+    /// Generates the consensus peaks of the specified peak files.
+    ///
+    /// # Parameters
+    ///
+    /// * `paths` - the paths to the BED3+ input peak files
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if parsing the input files or creating the consensus peaks fails.
+    pub fn run_files<T: AsRef<Path>>(&self, paths: &[T]) -> Result<ConsensusRun, ApplicationError> {
+        let paths: Vec<PathBuf> = paths
+            .iter()
+            .map(|path| path.as_ref().to_path_buf())
+            .collect();
+        let generated = if self.streaming {
+            self.stream(&paths).map_err(|err| {
+                err.chain(format!("Failed to stream input files \"{:?}\".", paths))
+            })?
+        } else {
+            self.generate(&paths)?
+        };
+        let generated_consensus_peaks = consensus_peak_counts(&generated.consensus);
+        let filtered_consensus_peaks: PeakCounts = generated
+            .unfiltered_consensus_peaks
+            .iter()
+            .map(|(chromosome, unfiltered)| {
+                (
+                    *chromosome,
+                    unfiltered.saturating_sub(
+                        generated_consensus_peaks
+                            .get(chromosome)
+                            .copied()
+                            .unwrap_or_default(),
+                    ),
+                )
+            })
+            .collect();
+        let sample_index = SampleIndex::new(&generated.peaks_per_file);
+        let tracked_consensus = if self.min_samples > 1 {
+            filter_min_samples(generated.consensus, &sample_index, self.min_samples)
+        } else {
+            generated.consensus
+        };
+        let mut provenance = (self.provenance || self.output.requires_provenance())
+            .then(|| consensus_provenance(&tracked_consensus, &sample_index));
+        let retained_tracked_consensus = self
+            .retain_tracked_consensus
+            .then(|| tracked_consensus.clone());
+        let consensus = untrack_consensus(tracked_consensus);
+        let unmerged_consensus_peaks: usize = consensus.values().map(Vec::len).sum();
+        let duplicates = match self.duplicate_overlap {
+            Some(duplicate_overlap) => {
+                let duplicates = near_duplicates(&consensus, duplicate_overlap);
+                log::info!(
+                    target: LOG_TARGET,
+                    "Detected {} pairs of near-duplicate consensus peaks.",
+                    duplicates.len()
+                );
+                duplicates
+            },
+            None => Vec::new(),
+        };
+        let consensus = if self.merge_duplicates {
+            provenance =
+                provenance.map(|provenance| merge_duplicate_provenance(provenance, &duplicates));
+            merge_near_duplicates(consensus, &duplicates)
+        } else {
+            consensus
+        };
+        let merged_duplicates =
+            unmerged_consensus_peaks - consensus.values().map(Vec::len).sum::<usize>();
+        let consensus = match &self.blacklist {
+            Some(blacklist) if self.blacklist_mode == BlacklistMode::Trim => {
+                trim_blacklisted_peaks(consensus, blacklist)
+            },
+            _ => consensus,
+        };
+        Ok(ConsensusRun {
+            consensus,
+            strands: generated.strands,
+            provenance,
+            tracked_consensus: retained_tracked_consensus,
+            input_peaks: generated.input_peaks,
+            filtered_consensus_peaks,
+            peaks_per_file: generated.peaks_per_file,
+            raw_peak_scores: generated.raw_peak_scores,
+            sample_peaks: generated.sample_peaks,
+            raw_peaks: generated.raw_peaks,
+            duplicates,
+            merged_duplicates,
+        })
+    }
+
+    /// Returns if the statistical values of the raw peaks are required for consensus peak
+    /// generation or the output.
+    fn requires_scores(&self) -> bool {
+        self.builder.requires_scores() || self.output.requires_scores()
+    }
+
+    /// Removes the raw peaks overlapping blacklisted regions if the blacklist mode is remove.
+    ///
+    /// # Parameters
+    ///
+    /// * `peaks` - the raw peaks grouped by chromosome
+    fn remove_blacklisted(
+        &self,
+        peaks: HashMap<Chromosome, Vec<PeakData>>,
+    ) -> HashMap<Chromosome, Vec<PeakData>> {
+        match &self.blacklist {
+            Some(blacklist) if self.blacklist_mode == BlacklistMode::Remove => {
+                remove_blacklisted_peaks(peaks, blacklist)
+            },
+            _ => peaks,
+        }
+    }
+
+    /// Parses the input files completely and creates the consensus peaks,
+    /// separately per strand if requested.
+    ///
+    /// # Parameters
+    ///
+    /// * `paths` - the peak files
+    fn generate(&self, paths: &[PathBuf]) -> Result<GeneratedConsensus, ApplicationError> {
+        let parsed = bed_to_peaks_with_format(
+            paths,
+            self.input_format,
+            self.max_errors,
+            self.strand_mode == StrandMode::Separate,
+            self.requires_scores(),
+        )
+        .map_err(|err| err.chain(format!("Failed to parse input files \"{:?}\".", paths)))?;
+        let sample_peaks: Vec<PeakSet> = if self.retain_sample_peaks {
+            parsed
+                .peaks_by_file()
+                .into_iter()
+                .map(PeakSet::from)
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let builder = self
+            .builder
+            .clone()
+            .raw_peak_scores(&parsed.scores, &parsed.peaks_per_file);
+        let input_peaks: PeakCounts = parsed
+            .peaks
+            .iter()
+            .map(|(chromosome, peaks)| (*chromosome, peaks.len()))
+            .collect();
+        let peaks = self.remove_blacklisted(parsed.peaks);
+        // The raw peaks are consumed by consensus peak generation, so they are only
+        // retained if requested.
+        let raw_peaks = self.retain_raw_peaks.then(|| peaks.clone());
+        let run = |builder: &ConsensusBuilder, peaks| match self.strand_mode {
+            StrandMode::Ignore => Ok((builder.run_tracked(peaks)?, HashMap::new())),
+            StrandMode::Separate => builder.run_stranded(peaks, &parsed.strands),
+        };
+        let unfiltered_consensus_peaks = if self.count_filtered {
+            consensus_peak_counts(
+                &run(&builder.clone().min_peaks_per_consensus(0), peaks.clone())?.0,
+            )
+        } else {
+            HashMap::new()
+        };
+        let (consensus, strands) = run(&builder, peaks)?;
+        Ok(GeneratedConsensus {
+            consensus,
+            strands,
+            input_peaks,
+            unfiltered_consensus_peaks,
+            peaks_per_file: parsed.peaks_per_file,
+            raw_peak_scores: parsed.scores,
+            sample_peaks,
+            raw_peaks,
+        })
+    }
+
+    /// Streams the raw peaks of the input files chromosome by chromosome and creates their
+    /// consensus peaks, so only the raw peaks of a single chromosome are kept in memory at a time
+    /// unless the consensus peak algorithm requires the statistical values of all raw peaks.
+    ///
+    /// # Parameters
+    ///
+    /// * `paths` - the peak files, each sorted by chromosome
+    fn stream(&self, paths: &[PathBuf]) -> Result<GeneratedConsensus, ApplicationError> {
+        // The statistical values used by the consensus peak algorithm are required before
+        // the first chromosome is processed.
+        let (mut raw_peak_scores, peaks_per_file) = if self.builder.requires_scores() {
+            log::warn!(
+                target: LOG_TARGET,
+                "The consensus peak algorithm requires the statistical values of all raw peaks, \
+                so all input files are loaded into memory before streaming."
+            );
+            let parsed = bed_to_peaks_with_format(paths, self.input_format, None, false, true)?;
+            (parsed.scores, parsed.peaks_per_file)
+        } else {
+            (HashMap::new(), Vec::new())
+        };
+        let builder = self
+            .builder
+            .clone()
+            .raw_peak_scores(&raw_peak_scores, &peaks_per_file);
+        let unfiltered_builder = self
+            .count_filtered
+            .then(|| builder.clone().min_peaks_per_consensus(0));
+        let mut input_peaks = HashMap::new();
+        let mut unfiltered_consensus_peaks = HashMap::new();
+        let mut stream = bed_to_peak_stream(paths).input_format(self.input_format);
+        if self.output.requires_scores() && !self.builder.requires_scores() {
+            stream = stream.parse_scores();
+        }
+        let chunks = stream.by_ref().map(|chunk| {
+            let (chromosome, peaks) = chunk?;
+            input_peaks.insert(chromosome, peaks.len());
+            let peaks = self
+                .remove_blacklisted(HashMap::from([(chromosome, peaks)]))
+                .remove(&chromosome)
+                .unwrap_or_default();
+            if let Some(unfiltered_builder) = &unfiltered_builder {
+                let unfiltered =
+                    unfiltered_builder.run_tracked_chunks([Ok((chromosome, peaks.clone()))])?;
+                unfiltered_consensus_peaks.extend(consensus_peak_counts(&unfiltered));
+            }
+            Ok((chromosome, peaks))
+        });
+        let consensus = builder.run_tracked_chunks(chunks)?;
+        raw_peak_scores.extend(stream.take_scores());
+        let sample_peaks = if self.retain_sample_peaks {
+            log::warn!(
+                target: LOG_TARGET,
+                "The requested sample comparisons require the peaks of all input files, \
+                so all input files are loaded into memory after streaming."
+            );
+            paths
+                .iter()
+                .map(|path| {
+                    bed_to_peaks_with_format(&[path], self.input_format, None, false, false)
+                        .map(|parsed| PeakSet::from(parsed.peaks))
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            Vec::new()
+        };
+        Ok(GeneratedConsensus {
+            consensus,
+            strands: HashMap::new(),
+            input_peaks,
+            unfiltered_consensus_peaks,
+            peaks_per_file: stream.peaks_per_file().to_vec(),
+            raw_peak_scores,
+            sample_peaks,
+            raw_peaks: None,
+        })
+    }
+
+    /// Generates consensus peaks from the input files and writes them and all additional
+    /// output requested by the options.
+    /// Returns the consensus peak output file, which contains the run identifier if requested.
+    ///
+    /// # Parameters
+    ///
+    /// * `input_files` - the peak files to generate consensus peaks from
+    /// * `sample_sheet` - the sample sheet naming the input files and assigning their conditions
+    /// * `output_file` - the consensus peak output file
+    /// * `options` - the checks and additional output of the run
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a check fails, generating the consensus peaks fails or any output
+    /// cannot be written.
+    pub fn run_and_write(
+        &self,
+        input_files: &[PathBuf],
+        sample_sheet: &SampleSheet,
+        output_file: PathBuf,
+        options: &ConsensusRunOptions,
+    ) -> Result<PathBuf, ApplicationError> {
+        let start_time = Instant::now();
+        let inputs: Vec<PathBuf> = input_files
+            .iter()
+            .chain(&options.auxiliary_input_files)
+            .cloned()
+            .collect();
+        let input_entries = if options.verify_inputs.is_some()
+            || options.manifest.is_some()
+            || options.run_id_suffix
+        {
+            file_entries(&inputs)?
+        } else {
+            Vec::new()
+        };
+        if let Some(manifest_path) = &options.verify_inputs {
+            RunManifest::from_file(manifest_path)?
+                .verify_inputs(&input_entries)
+                .map_err(|err| err.chain("Failed to verify the input files."))?;
+        }
+        let output_file = if options.run_id_suffix {
+            let run_id = run_identifier(&options.parameters, &input_entries);
+            log::info!(target: LOG_TARGET, "Run identifier: {}", run_id);
+            with_run_identifier(output_file, &run_id)
+        } else {
+            output_file
+        };
+        let chromosome_sizes = if options.genome_coverage.is_some()
+            || options.assembly_check.is_some()
+            || options.density_track.is_some()
+        {
+            options
+                .chromosome_sizes
+                .as_ref()
+                .map(chromosome_sizes_from_file)
+                .transpose()?
+                .unwrap_or_default()
+        } else {
+            BTreeMap::new()
+        };
+        let mut run = self
+            .clone()
+            .near_duplicates(
+                (options.duplicate_report.is_some() || options.merge_duplicates)
+                    .then_some(options.duplicate_overlap),
+                options.merge_duplicates,
+            )
+            .provenance(
+                options.provenance_table.is_some()
+                    || options.peak_name_mapping.is_some()
+                    || options.occupancy_matrix.is_some()
+                    || options.provenance_column,
+            )
+            .count_filtered(
+                (options.stats_file.is_some() || options.summary)
+                    && self.builder.consensus_parameters().min_peaks_per_consensus() > 1,
+            )
+            .retain_tracked_consensus(options.stats_file.is_some() || options.summary)
+            // The peaks of each input file are only required by outputs comparing the samples.
+            .retain_sample_peaks(
+                options.intersection_summary.is_some()
+                    || options.summit_concordance.is_some()
+                    || options.density_correlation.is_some()
+                    || options.assembly_check.is_some(),
+            )
+            .retain_raw_peaks(
+                options.genome_coverage.is_some()
+                    || options.outlier_report.is_some()
+                    || options.stats_output.is_some(),
+            )
+            .run_files(input_files)?;
+        if let Some(assembly_check) = options.assembly_check {
+            check_assembly_consistency(
+                assembly_check,
+                input_files,
+                &run.sample_peaks,
+                options
+                    .chromosome_sizes
+                    .is_some()
+                    .then_some(&chromosome_sizes),
+            )?;
+        }
+        if let Some(report_path) = &options.duplicate_report {
+            let file = File::create(report_path).map_err(|err| {
+                ApplicationError::from(err).chain(format!(
+                    "The near-duplicate report \"{}\" could not be created.",
+                    report_path.display()
+                ))
+            })?;
+            write_duplicate_table(BufWriter::new(file), &run.duplicates)?;
+        }
+        let peak_annotation = match &options.annotate_with {
+            Some(annotation_path) => {
+                let annotation =
+                    RegionAnnotation::from_bed_file(annotation_path, options.annotation_column)
+                        .map_err(|err| err.chain("Failed to parse the reference annotation."))?;
+                Some(PeakAnnotation::new(&annotation, &run.consensus))
+            },
+            None => None,
+        };
+        let mut columns: HashMap<usize, Vec<String>> = HashMap::new();
+        for (peak_id, label) in peak_annotation.iter().flat_map(PeakAnnotation::labels) {
+            columns.entry(*peak_id).or_default().push(label.clone());
+        }
+        let class_counts = match &options.gtf {
+            Some(gtf_path) => {
+                let genes = GeneAnnotation::from_gtf_file(gtf_path)
+                    .map_err(|err| err.chain("Failed to parse the gene annotation."))?;
+                Some(append_nearest_gene_columns(
+                    &genes,
+                    options.promoter_window,
+                    &run.consensus,
+                    &mut columns,
+                ))
+            },
+            None => None,
+        };
+        let sample_names = sample_sheet.sample_names(input_files);
+        if let Some(provenance) = &run.provenance
+            && options.provenance_column
+        {
+            for (peak_id, samples) in provenance_column(&run.consensus, provenance, &sample_names) {
+                columns.entry(peak_id).or_default().push(samples);
+            }
+        }
+        let names = if self.output.stable_ids
+            && (options.provenance_table.is_some()
+                || options.peak_name_mapping.is_some()
+                || options.occupancy_matrix.is_some())
+        {
+            stable_peak_names(&run.consensus)
+        } else {
+            HashMap::new()
+        };
+        if let (Some(provenance), Some(provenance_path)) =
+            (&run.provenance, &options.provenance_table)
+        {
+            let file = File::create(provenance_path).map_err(|err| {
+                ApplicationError::from(err).chain(format!(
+                    "The provenance table \"{}\" could not be created.",
+                    provenance_path.display()
+                ))
+            })?;
+            write_provenance_table(
+                BufWriter::new(file),
+                &run.consensus,
+                provenance,
+                &names,
+                &sample_names,
+            )?;
+        }
+        if let (Some(provenance), Some(mapping_path)) =
+            (&run.provenance, &options.peak_name_mapping)
+        {
+            let raw_peak_names = bed_to_peak_names(input_files, self.input_format)
+                .map_err(|err| err.chain("Failed to parse the names of the raw peaks."))?;
+            let file = File::create(mapping_path).map_err(|err| {
+                ApplicationError::from(err).chain(format!(
+                    "The peak name mapping \"{}\" could not be created.",
+                    mapping_path.display()
+                ))
+            })?;
+            write_peak_name_mapping(
+                BufWriter::new(file),
+                &run.consensus,
+                provenance,
+                &names,
+                &raw_peak_names,
+            )?;
+        }
+        if let (Some(provenance), Some(matrix_path)) = (&run.provenance, &options.occupancy_matrix)
+        {
+            let file = File::create(matrix_path).map_err(|err| {
+                ApplicationError::from(err).chain(format!(
+                    "The occupancy matrix \"{}\" could not be created.",
+                    matrix_path.display()
+                ))
+            })?;
+            write_occupancy_matrix(
+                BufWriter::new(file),
+                &run.consensus,
+                provenance,
+                &names,
+                &sample_names,
+            )?;
+        }
+        self.output
+            .write(&output_file, &mut run, &sample_names, &columns)
+            .map_err(|err| {
+                err.chain(format!(
+                    "Failed to write the consensus peaks to output file \"{}\".",
+                    output_file.display(),
+                ))
+            })?;
+        let ConsensusRun {
+            consensus,
+            tracked_consensus: summary_consensus,
+            input_peaks,
+            filtered_consensus_peaks,
+            peaks_per_file,
+            sample_peaks: samples,
+            raw_peaks: retained_raw_peaks,
+            merged_duplicates,
+            ..
+        } = run;
+        let raw_peaks: usize = input_peaks.values().sum();
+        if let Some(summary_consensus) = summary_consensus {
+            let summary = ConsensusSummary::new(
+                &input_peaks,
+                &summary_consensus,
+                &filtered_consensus_peaks,
+                &consensus,
+            );
+            if let Some(stats_path) = &options.stats_file {
+                let file = File::create(stats_path).map_err(|err| {
+                    ApplicationError::from(err).chain(format!(
+                        "The summary file \"{}\" could not be created.",
+                        stats_path.display()
+                    ))
+                })?;
+                write_summary_table(BufWriter::new(file), &summary)?;
+            }
+            if options.summary {
+                write_summary_table(std::io::stderr().lock(), &summary)?;
+            }
+        }
+        let mut condition_output_files = Vec::new();
+        if options.per_condition {
+            for (condition, condition_input_files) in sample_sheet.conditions() {
+                let condition_output_file = condition_output_file(&output_file, &condition);
+                self.write_condition_consensus_peaks(
+                    &condition_input_files,
+                    &sample_sheet.sample_names(&condition_input_files),
+                    &condition_output_file,
+                )
+                .map_err(|err| {
+                    err.chain(format!(
+                        "Failed to generate the consensus peaks of condition \"{}\".",
+                        condition
+                    ))
+                })?;
+                condition_output_files.push(condition_output_file);
+            }
+        }
+        if options.summit_windows.is_some() || options.summit_sequences.is_some() {
+            let windows = summit_windows(&consensus, options.summit_flank);
+            if let Some(windows_path) = &options.summit_windows {
+                write_peaks_to_bed(windows_path, &windows, self.output.fields)
+                    .map_err(|err| err.chain("Failed to write the summit windows."))?;
+            }
+            if let (Some(sequences_path), Some(genome)) =
+                (&options.summit_sequences, &options.genome)
+            {
+                write_window_sequences(genome, &windows, sequences_path)
+                    .map_err(|err| err.chain("Failed to write the summit window sequences."))?;
+            }
+        }
+        if let Some(peak_annotation) = peak_annotation {
+            let summary_path = options.annotation_summary_path(&output_file);
+            let file = File::create(&summary_path).map_err(|err| {
+                ApplicationError::from(err).chain(format!(
+                    "The annotation summary file \"{}\" could not be created.",
+                    summary_path.display()
+                ))
+            })?;
+            write_annotation_summary(BufWriter::new(file), &peak_annotation)?;
+        }
+        if let Some(class_counts) = class_counts {
+            log::info!(
+                target: LOG_TARGET,
+                "Classified {} consensus peaks as promoter-proximal and {} as distal.",
+                class_counts.promoter(),
+                class_counts.distal()
+            );
+            let summary_path = options.promoter_summary_path(&output_file);
+            let file = File::create(&summary_path).map_err(|err| {
+                ApplicationError::from(err).chain(format!(
+                    "The promoter summary file \"{}\" could not be created.",
+                    summary_path.display()
+                ))
+            })?;
+            write_class_summary(BufWriter::new(file), &class_counts)?;
+        }
+        if let (Some(coverage_path), Some(raw_peaks_by_chromosome)) =
+            (&options.genome_coverage, &retained_raw_peaks)
+        {
+            let coverage =
+                GenomeCoverage::new(&chromosome_sizes, raw_peaks_by_chromosome, &consensus);
+            let file = File::create(coverage_path).map_err(|err| {
+                ApplicationError::from(err).chain(format!(
+                    "The genome coverage file \"{}\" could not be created.",
+                    coverage_path.display()
+                ))
+            })?;
+            write_coverage_table(BufWriter::new(file), &coverage)?;
+        }
+        if let (Some(outlier_path), Some(raw_peaks_by_chromosome)) =
+            (&options.outlier_report, &retained_raw_peaks)
+        {
+            let concordance = SummitConcordance::new(
+                &PeakSet::from(consensus.clone()),
+                &[PeakSet::from(raw_peaks_by_chromosome.clone())],
+            );
+            let outliers = outlier_peaks(&concordance, &options.outlier_thresholds);
+            let file = File::create(outlier_path).map_err(|err| {
+                ApplicationError::from(err).chain(format!(
+                    "The outlier report \"{}\" could not be created.",
+                    outlier_path.display()
+                ))
+            })?;
+            write_outlier_table(BufWriter::new(file), &outliers)?;
+        }
+        if let Some(track_path) = &options.density_track {
+            let track = peak_density_track(
+                &PeakSet::from(consensus.clone()),
+                options.density_window,
+                &chromosome_sizes,
+            );
+            let file = File::create(track_path).map_err(|err| {
+                ApplicationError::from(err).chain(format!(
+                    "The peak density track \"{}\" could not be created.",
+                    track_path.display()
+                ))
+            })?;
+            write_density_track(BufWriter::new(file), &track)?;
+        }
+        #[cfg(feature = "bam")]
+        let frip = if !options.frip_bam.is_empty() {
+            let consensus_peak_set = PeakSet::from(consensus.clone());
+            let statistics = options
+                .frip_bam
+                .iter()
+                .map(|bam| fraction_of_reads_in_peaks(bam, &consensus_peak_set))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| err.chain("Failed to calculate the fraction of reads in peaks."))?;
+            write_frip_table(options.frip_output_path(&output_file), &statistics)?;
+            statistics
+                .iter()
+                .map(|sample| (multiqc_sample_name(sample.sample()), sample.frip()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        #[cfg(not(feature = "bam"))]
+        let frip = Vec::new();
+        if options.intersection_summary.is_some()
+            || options.summit_concordance.is_some()
+            || options.density_correlation.is_some()
+        {
+            let sample_names: Vec<String> = input_files
+                .iter()
+                .map(|input_file| input_file.display().to_string())
+                .collect();
+            let consensus_peak_set = PeakSet::from(consensus.clone());
+            if let Some(summary_path) = &options.intersection_summary {
+                let intersections = SampleIntersections::new(&consensus_peak_set, &samples);
+                write_intersection_table(summary_path, &sample_names, &intersections)
+                    .map_err(|err| err.chain("Failed to write the sample intersection summary."))?;
+            }
+            if let Some(concordance_directory) = &options.summit_concordance {
+                let concordance = SummitConcordance::new(&consensus_peak_set, &samples);
+                write_summit_concordance(concordance_directory, &sample_names, &concordance)
+                    .map_err(|err| err.chain("Failed to write the summit concordance."))?;
+            }
+            if let Some(correlation_path) = &options.density_correlation {
+                let correlations = DensityCorrelations::new(&samples, options.density_window);
+                let file = File::create(correlation_path).map_err(|err| {
+                    ApplicationError::from(err).chain(format!(
+                        "The peak density correlation file \"{}\" could not be created.",
+                        correlation_path.display()
+                    ))
+                })?;
+                write_density_correlations(BufWriter::new(file), &sample_names, &correlations)?;
+            }
+        }
+        if let (Some(statistics_path), Some(raw_peaks_by_chromosome)) =
+            (&options.stats_output, &retained_raw_peaks)
+        {
+            let inputs = input_files
+                .iter()
+                .zip(&peaks_per_file)
+                .map(|(input_file, peaks)| InputPeakCount::new(input_file, *peaks))
+                .collect();
+            let statistics = ConsensusStatistics::new(inputs, raw_peaks_by_chromosome, &consensus);
+            write_consensus_statistics(statistics_path, &statistics)
+                .map_err(|err| err.chain("Failed to write the consensus statistics."))?;
+        }
+        if let Some(multiqc_path) = &options.multiqc {
+            let metrics = RunMetrics {
+                inputs: sample_names
+                    .iter()
+                    .zip(&peaks_per_file)
+                    .map(|(sample_name, peaks)| (sample_name.clone(), *peaks))
+                    .collect(),
+                consensus_peaks: consensus.values().map(Vec::len).sum(),
+                min_peaks_per_consensus: self
+                    .builder
+                    .consensus_parameters()
+                    .min_peaks_per_consensus(),
+                merged_duplicates,
+                frip,
+            };
+            write_multiqc_metrics(multiqc_path, &metrics)
+                .map_err(|err| err.chain("Failed to write the MultiQC metrics."))?;
+        }
+        if let Some(metadata_directory) = &options.nf_core_metadata {
+            let trace = ProcessTrace {
+                process: options.nf_core_process_name.clone(),
+                tool: TOOL_NAME.to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                algorithm: self.builder.consensus_algorithm().to_string(),
+                input_files: input_files.to_vec(),
+                output_file: output_file.clone(),
+                raw_peaks,
+                consensus_peaks: consensus.values().map(Vec::len).sum(),
+                duration_seconds: start_time.elapsed().as_secs_f64(),
+            };
+            write_nf_core_metadata(metadata_directory, &trace)
+                .map_err(|err| err.chain("Failed to write the nf-core metadata."))?;
+        }
+        if let Some(manifest_path) = &options.manifest {
+            let mut output_paths = options.output_paths(&output_file);
+            output_paths.extend(condition_output_files);
+            let manifest =
+                RunManifest::new(options.parameters.clone(), input_entries, &output_paths)
+                    .map_err(|err| err.chain("Failed to create the run manifest."))?;
+            write_manifest(manifest_path, &manifest)?;
+        }
+        Ok(output_file)
+    }
+
+    /// Generates the consensus peaks of the peak files of a single condition and writes them
+    /// without additional columns.
+    ///
+    /// # Parameters
+    ///
+    /// * `input_files` - the peak files of the condition
+    /// * `sample_names` - the names of the peak files of the condition
+    /// * `output_file` - the consensus peak output file of the condition
+    fn write_condition_consensus_peaks(
+        &self,
+        input_files: &[PathBuf],
+        sample_names: &[String],
+        output_file: &Path,
+    ) -> Result<(), ApplicationError> {
+        log::info!(
+            target: LOG_TARGET,
+            "Generating consensus peaks from {} input files for {}.",
+            input_files.len(),
+            output_file.display()
+        );
+        let mut run = self.run_files(input_files)?;
+        self.output
+            .write(output_file, &mut run, sample_names, &HashMap::new())
+    }
+}
+
+/// Checks whether the input files appear to originate from different genome assemblies.
+///
+/// # Parameters
+///
+/// * `assembly_check` - the handling of detected inconsistencies
+/// * `input_files` - the input files
+/// * `samples` - the peaks of each input file
+/// * `chromosome_sizes` - the optional chromosome sizes of the expected assembly
+///
+/// # Errors
+///
+/// Returns an error if inconsistencies are detected and should abort the run.
+fn check_assembly_consistency(
+    assembly_check: AssemblyCheck,
+    input_files: &[PathBuf],
+    samples: &[PeakSet],
+    chromosome_sizes: Option<&BTreeMap<Chromosome, u64>>,
+) -> Result<(), ApplicationError> {
+    let file_names: Vec<String> = input_files
+        .iter()
+        .map(|input_file| input_file.display().to_string())
+        .collect();
+    let profiles: Vec<AssemblyProfile> = samples.iter().map(AssemblyProfile::new).collect();
+    let inconsistencies = assembly_inconsistencies(&file_names, &profiles, chromosome_sizes);
+    if inconsistencies.is_empty() {
+        return Ok(());
+    }
+    match assembly_check {
+        AssemblyCheck::Warn => {
+            for inconsistency in &inconsistencies {
+                log::warn!(
+                    target: LOG_TARGET,
+                    "Possible genome assembly mismatch: {}",
+                    inconsistency
+                );
+            }
+            Ok(())
+        },
+        AssemblyCheck::Error => Err(ApplicationError::new(
+            ApplicationErrorType::ValidationError,
+            "The input files appear to originate from different genome assemblies.",
+        )
+        .with_related(
+            inconsistencies
+                .iter()
+                .map(|inconsistency| {
+                    ApplicationError::new(ApplicationErrorType::ValidationError, inconsistency)
+                })
+                .collect(),
+        )
+        .with_hint(
+            "Lift all input files to the same assembly with the \"liftover\" command or use \
+            \"--assembly-check warn\" to continue regardless.",
+        )),
+    }
+}
+
+/// Appends the name and strand of the nearest gene, the distance to its TSS and the
+/// promoter / distal class to the additional output columns of each peak
+/// and returns the number of peaks per class.
+/// Peaks on chromosomes without genes are labelled with `.` and classified as distal.
+///
+/// # Parameters
+///
+/// * `genes` - the gene annotation
+/// * `promoter_window` - the maximum distance of a promoter-proximal peak summit to the TSS
+/// * `consensus` - the consensus peaks grouped by chromosome
+/// * `columns` - the additional output columns by peak ID
+fn append_nearest_gene_columns(
+    genes: &GeneAnnotation,
+    promoter_window: u64,
+    consensus: &HashMap<Chromosome, Vec<PeakData>>,
+    columns: &mut HashMap<usize, Vec<String>>,
+) -> PeakClassCounts {
+    let mut class_counts = PeakClassCounts::default();
+    for (chromosome, peaks) in consensus {
+        for peak in peaks {
+            let peak_columns = columns.entry(peak.id()).or_default();
+            let nearest_gene = genes.nearest_gene(*chromosome, peak);
+            match &nearest_gene {
+                Some(nearest_gene) => peak_columns.extend([
+                    nearest_gene.gene().to_string(),
+                    nearest_gene.strand().to_string(),
+                    nearest_gene.distance().to_string(),
+                ]),
+                None => peak_columns.extend(vec![".".to_string(); 3]),
+            }
+            let class = PeakClass::new(nearest_gene.as_ref(), promoter_window);
+            peak_columns.push(class.to_string());
+            class_counts.add(class);
+        }
+    }
+    class_counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        input::bed_to_peaks,
+        peaks::ConsensusPeakAlgorithm,
+        test_utils::{test_output, test_resources},
+    };
+
+    fn input_files() -> Vec<PathBuf> {
+        [
+            "input_test_valid_with_summit_multiple_01.narrowPeak",
+            "input_test_valid_with_summit_multiple_02.narrowPeak",
+            "input_test_valid_with_summit_multiple_03.narrowPeak",
+        ]
+        .iter()
+        .map(|file| test_resources().join(file))
+        .collect()
+    }
+
+    #[test]
+    fn test_consensus_pipeline_run_files() {
+        let builder = ConsensusBuilder::new().algorithm(ConsensusPeakAlgorithm::Simple);
+        let run = ConsensusPipeline::new(builder.clone())
+            .provenance(true)
+            .retain_sample_peaks(true)
+            .retain_raw_peaks(true)
+            .run_files(&input_files())
+            .unwrap();
+        let raw_peaks = bed_to_peaks(&input_files()).unwrap();
+        assert_eq!(run.consensus, builder.run(raw_peaks.clone()).unwrap());
+        assert_eq!(run.raw_peaks, Some(raw_peaks));
+        assert_eq!(run.peaks_per_file, vec![2, 2, 2]);
+        assert_eq!(run.sample_peaks.len(), 3);
+        assert_eq!(run.input_peaks.values().sum::<usize>(), 6);
+        let provenance = run.provenance.unwrap();
+        assert_eq!(provenance.len(), run.consensus.values().map(Vec::len).sum::<usize>());
+    }
+
+    #[test]
+    fn test_consensus_pipeline_streaming() {
+        let builder = ConsensusBuilder::new().algorithm(ConsensusPeakAlgorithm::Simple);
+        let pipeline = ConsensusPipeline::new(builder).min_samples(2);
+        let parsed = pipeline.run_files(&input_files()).unwrap();
+        let streamed = pipeline.streaming(true).run_files(&input_files()).unwrap();
+        assert_eq!(streamed.consensus, parsed.consensus);
+        assert_eq!(streamed.input_peaks, parsed.input_peaks);
+        assert_eq!(streamed.peaks_per_file, parsed.peaks_per_file);
+    }
+
+    #[test]
+    fn test_consensus_output_write() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_file = test_output().join("test_consensus_output_write.bed");
+        let output = ConsensusOutput::new()
+            .fields(6)
+            .score_source(ScoreSource::Samples);
+        assert_eq!(output.columns(), 6);
+        assert!(output.requires_scores());
+        assert!(output.contributions_required());
+        let mut run = ConsensusPipeline::new(
+            ConsensusBuilder::new().algorithm(ConsensusPeakAlgorithm::Simple),
+        )
+        .output(output.clone())
+        .run_files(&input_files())
+        .unwrap();
+        assert!(run.provenance.is_some());
+        let sample_names = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        output
+            .write(&output_file, &mut run, &sample_names, &HashMap::new())
+            .unwrap();
+        let written = bed_to_peaks(&[&output_file]).unwrap();
+        std::fs::remove_file(&output_file).unwrap();
+        let peaks = |consensus: &HashMap<Chromosome, Vec<PeakData>>| {
+            let mut peaks: Vec<(Chromosome, u64, u64)> = consensus
+                .iter()
+                .flat_map(|(chromosome, peaks)| {
+                    peaks
+                        .iter()
+                        .map(|peak| (*chromosome, peak.start(), peak.end()))
+                })
+                .collect();
+            peaks.sort_unstable();
+            peaks
+        };
+        assert_eq!(peaks(&written), peaks(&run.consensus));
+    }
+
+    #[test]
+    fn test_consensus_pipeline_run_and_write() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_file = test_output().join("test_consensus_pipeline_run_and_write.bed");
+        let stats_file = test_output().join("test_consensus_pipeline_run_and_write_stats.tsv");
+        let options = ConsensusRunOptions {
+            stats_file: Some(stats_file.clone()),
+            gtf: Some(test_output().join("unused.gtf")),
+            ..Default::default()
+        };
+        assert_eq!(
+            options.output_paths(&output_file),
+            vec![
+                output_file.clone(),
+                stats_file.clone(),
+                test_output().join("test_consensus_pipeline_run_and_write_promoter.tsv"),
+            ]
+        );
+        let pipeline = ConsensusPipeline::new(
+            ConsensusBuilder::new().algorithm(ConsensusPeakAlgorithm::Simple),
+        );
+        let options = ConsensusRunOptions {
+            gtf: None,
+            ..options
+        };
+        let written = pipeline
+            .run_and_write(&input_files(), &SampleSheet::default(), output_file.clone(), &options)
+            .unwrap();
+        assert_eq!(written, output_file);
+        let consensus = bed_to_peaks(&[&output_file]).unwrap();
+        let stats = std::fs::read_to_string(&stats_file).unwrap();
+        std::fs::remove_file(&output_file).unwrap();
+        std::fs::remove_file(&stats_file).unwrap();
+        assert_eq!(
+            consensus.values().map(Vec::len).sum::<usize>(),
+            pipeline
+                .run_files(&input_files())
+                .unwrap()
+                .consensus
+                .values()
+                .map(Vec::len)
+                .sum::<usize>()
+        );
+        assert!(!stats.is_empty());
+    }
+}
@@ -0,0 +1,203 @@
+//! This module converts peak sets to and from [polars](https://pola.rs/) DataFrames.
+//!
+//! Each row of the DataFrame represents a single peak with the columns `chromosome`, `id`,
+//! `start`, `end` and `summit`. Coordinates are 0-based and the end coordinate is inclusive
+//! as for [`PeakData`].
+
+use polars::prelude::{Column, DataFrame, DataType, PolarsError};
+
+use crate::{
+    chromosome::Chromosome,
+    error::{ApplicationError, ApplicationErrorType},
+    peak_set::{CHROMOSOME_COLUMN, END_COLUMN, ID_COLUMN, PeakSet, START_COLUMN, SUMMIT_COLUMN},
+    peaks::PeakData,
+};
+
+impl PeakSet {
+    /// Returns a DataFrame containing all peaks sorted by chromosome and position.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the DataFrame cannot be created.
+    pub fn to_polars(&self) -> Result<DataFrame, ApplicationError> {
+        let mut chromosomes = Vec::with_capacity(self.len());
+        let mut ids = Vec::with_capacity(self.len());
+        let mut starts = Vec::with_capacity(self.len());
+        let mut ends = Vec::with_capacity(self.len());
+        let mut summits = Vec::with_capacity(self.len());
+        for chromosome in self.chromosomes() {
+            for peak in self.peaks(chromosome) {
+                chromosomes.push(chromosome.name());
+                ids.push(peak.id() as u64);
+                starts.push(peak.start());
+                ends.push(peak.end());
+                summits.push(peak.summit());
+            }
+        }
+        DataFrame::new(vec![
+            Column::new(CHROMOSOME_COLUMN.into(), chromosomes),
+            Column::new(ID_COLUMN.into(), ids),
+            Column::new(START_COLUMN.into(), starts),
+            Column::new(END_COLUMN.into(), ends),
+            Column::new(SUMMIT_COLUMN.into(), summits),
+        ])
+        .map_err(|err| {
+            ApplicationError::from(err).chain("The peak DataFrame could not be created.")
+        })
+    }
+
+    /// Creates a peak set from a DataFrame containing the peak columns.
+    /// Additional columns are ignored and integer columns are cast to unsigned 64 bit integers.
+    ///
+    /// # Parameters
+    ///
+    /// * `data_frame` - the DataFrame containing the peaks
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a column is missing, contains missing values or invalid peaks.
+    pub fn from_polars(data_frame: &DataFrame) -> Result<Self, ApplicationError> {
+        let chromosome_column = data_frame
+            .column(CHROMOSOME_COLUMN)
+            .and_then(|column| column.cast(&DataType::String))
+            .map_err(|err| polars_column_error(err, CHROMOSOME_COLUMN))?;
+        let chromosomes = chromosome_column
+            .str()
+            .map_err(|err| polars_column_error(err, CHROMOSOME_COLUMN))?;
+        let ids = u64_column(data_frame, ID_COLUMN)?;
+        let starts = u64_column(data_frame, START_COLUMN)?;
+        let ends = u64_column(data_frame, END_COLUMN)?;
+        let summits = u64_column(data_frame, SUMMIT_COLUMN)?;
+        (0..data_frame.height())
+            .map(|row| {
+                let (Some(chromosome), Some(id), Some(start), Some(end), Some(summit)) =
+                    (chromosomes.get(row), ids[row], starts[row], ends[row], summits[row])
+                else {
+                    return Err(ApplicationError::new(
+                        ApplicationErrorType::ValidationError,
+                        format!("Row {} of the peak DataFrame contains missing values.", row),
+                    ));
+                };
+                Ok((
+                    Chromosome::intern(chromosome),
+                    PeakData::new(id as usize, start, end, summit)?,
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Returns the values of the specified column as unsigned 64 bit integers.
+///
+/// # Parameters
+///
+/// * `data_frame` - the DataFrame containing the column
+/// * `name` - the name of the column
+fn u64_column(data_frame: &DataFrame, name: &str) -> Result<Vec<Option<u64>>, ApplicationError> {
+    let column = data_frame
+        .column(name)
+        .and_then(|column| column.strict_cast(&DataType::UInt64))
+        .map_err(|err| polars_column_error(err, name))?;
+    Ok(column
+        .u64()
+        .map_err(|err| polars_column_error(err, name))?
+        .into_iter()
+        .collect())
+}
+
+/// Converts an error concerning a specific DataFrame column into an [`ApplicationError`].
+///
+/// # Parameters
+///
+/// * `err` - the polars error
+/// * `name` - the name of the column
+fn polars_column_error(err: PolarsError, name: &str) -> ApplicationError {
+    ApplicationError::from(err).chain(format!("The peak DataFrame column \"{}\" is invalid.", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peak_set_polars() {
+        let peak_set: PeakSet = [
+            (Chromosome::from("chr2"), PeakData::new(2, 50u64, 99u64, 70u64).unwrap()),
+            (Chromosome::from("chr1"), PeakData::new(1, 300u64, 399u64, 350u64).unwrap()),
+            (Chromosome::from("chr1"), PeakData::new(0, 100u64, 199u64, 150u64).unwrap()),
+        ]
+        .into_iter()
+        .collect();
+        let data_frame = peak_set.to_polars().unwrap();
+        assert_eq!(data_frame.shape(), (3, 5));
+        let starts: Vec<Option<u64>> = data_frame
+            .column(START_COLUMN)
+            .unwrap()
+            .u64()
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(starts, vec![Some(100), Some(300), Some(50)]);
+        let converted = PeakSet::from_polars(&data_frame).unwrap();
+        let peaks: Vec<(Chromosome, PeakData)> = converted
+            .iter()
+            .map(|(chromosome, peak)| (chromosome, *peak))
+            .collect();
+        let expected: Vec<(Chromosome, PeakData)> = peak_set
+            .iter()
+            .map(|(chromosome, peak)| (chromosome, *peak))
+            .collect();
+        assert_eq!(peaks, expected);
+    }
+
+    #[test]
+    fn test_peak_set_from_polars_cast() {
+        let data_frame = DataFrame::new(vec![
+            Column::new(CHROMOSOME_COLUMN.into(), ["chr1"]),
+            Column::new(ID_COLUMN.into(), [7i32]),
+            Column::new(START_COLUMN.into(), [10i64]),
+            Column::new(END_COLUMN.into(), [20i64]),
+            Column::new(SUMMIT_COLUMN.into(), [15i64]),
+            Column::new("score".into(), [1.5f64]),
+        ])
+        .unwrap();
+        let peak_set = PeakSet::from_polars(&data_frame).unwrap();
+        assert_eq!(
+            peak_set.peaks(Chromosome::from("chr1")),
+            &[PeakData::new(7, 10u64, 20u64, 15u64).unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_peak_set_from_polars_invalid() {
+        let missing_column = DataFrame::new(vec![
+            Column::new(CHROMOSOME_COLUMN.into(), ["chr1"]),
+            Column::new(ID_COLUMN.into(), [0u64]),
+        ])
+        .unwrap();
+        assert!(matches!(
+            PeakSet::from_polars(&missing_column)
+                .unwrap_err()
+                .error_type(),
+            ApplicationErrorType::ValidationError
+        ));
+        let negative_start = DataFrame::new(vec![
+            Column::new(CHROMOSOME_COLUMN.into(), ["chr1"]),
+            Column::new(ID_COLUMN.into(), [0i64]),
+            Column::new(START_COLUMN.into(), [-10i64]),
+            Column::new(END_COLUMN.into(), [20i64]),
+            Column::new(SUMMIT_COLUMN.into(), [15i64]),
+        ])
+        .unwrap();
+        assert!(PeakSet::from_polars(&negative_start).is_err());
+        let missing_value = DataFrame::new(vec![
+            Column::new(CHROMOSOME_COLUMN.into(), [Some("chr1"), None]),
+            Column::new(ID_COLUMN.into(), [0u64, 1u64]),
+            Column::new(START_COLUMN.into(), [10u64, 10u64]),
+            Column::new(END_COLUMN.into(), [20u64, 20u64]),
+            Column::new(SUMMIT_COLUMN.into(), [15u64, 15u64]),
+        ])
+        .unwrap();
+        assert!(PeakSet::from_polars(&missing_value).is_err());
+    }
+}
@@ -0,0 +1,625 @@
+//! This module records the provenance of consensus peaks, which are the input files (samples)
+//! and the raw peaks each consensus peak was created from.
+//!
+//! Raw peak IDs are assigned in the order of the records of all input files starting at 0,
+//! so the sample of a raw peak is derived from the number of peaks of each input file.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::Write,
+};
+
+use getset::Getters;
+
+use crate::{
+    chromosome::Chromosome, consensus::TrackedConsensus, duplicates::NearDuplicate,
+    error::ApplicationError, output::PeakContribution, peaks::PeakData,
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "provenance";
+
+/// The provenance of a single consensus peak.
+#[derive(Getters, Clone, Default, PartialEq, Eq, Debug)]
+#[getset(get = "pub")]
+pub struct PeakProvenance {
+    /// The indices of the contributing samples in ascending order.
+    samples: Vec<usize>,
+    /// The IDs of the contributing raw peaks in ascending order.
+    raw_peak_ids: Vec<usize>,
+}
+
+impl PeakProvenance {
+    /// Adds the samples and raw peaks of another provenance.
+    ///
+    /// # Parameters
+    ///
+    /// * `other` - the provenance to add
+    fn extend(&mut self, other: &PeakProvenance) {
+        let samples: BTreeSet<usize> = self.samples.iter().chain(&other.samples).copied().collect();
+        let raw_peak_ids: BTreeSet<usize> = self
+            .raw_peak_ids
+            .iter()
+            .chain(&other.raw_peak_ids)
+            .copied()
+            .collect();
+        self.samples = samples.into_iter().collect();
+        self.raw_peak_ids = raw_peak_ids.into_iter().collect();
+    }
+}
+
+/// The provenance of consensus peaks by chromosome and consensus peak ID.
+pub type ConsensusProvenance = HashMap<(Chromosome, usize), PeakProvenance>;
+
+/// Maps raw peak IDs to the index of the sample they were parsed from.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SampleIndex {
+    /// The first raw peak ID of each sample.
+    first_ids: Vec<usize>,
+}
+
+impl SampleIndex {
+    /// Creates the mapping from the number of raw peaks of each sample in input order.
+    ///
+    /// # Parameters
+    ///
+    /// * `peaks_per_sample` - the number of raw peaks of each sample
+    pub fn new(peaks_per_sample: &[usize]) -> Self {
+        let first_ids = peaks_per_sample
+            .iter()
+            .scan(0, |first_id, peaks| {
+                let sample_first_id = *first_id;
+                *first_id += peaks;
+                Some(sample_first_id)
+            })
+            .collect();
+        Self { first_ids }
+    }
+
+    /// Returns the index of the sample the raw peak was parsed from.
+    ///
+    /// # Parameters
+    ///
+    /// * `raw_peak_id` - the ID of the raw peak
+    pub fn sample(&self, raw_peak_id: usize) -> usize {
+        self.first_ids
+            .partition_point(|first_id| *first_id <= raw_peak_id)
+            .saturating_sub(1)
+    }
+}
+
+/// Determines the contributing samples and raw peaks of all consensus peaks.
+///
+/// # Parameters
+///
+/// * `consensus` - the consensus peaks keeping track of their raw peaks
+/// * `sample_index` - the mapping of raw peaks to samples
+pub fn consensus_provenance(
+    consensus: &TrackedConsensus,
+    sample_index: &SampleIndex,
+) -> ConsensusProvenance {
+    let mut provenance = ConsensusProvenance::new();
+    for (chromosome, peaks) in consensus {
+        for peak in peaks {
+            let raw_peak_ids: BTreeSet<usize> = peak.raw_peak_ids().iter().copied().collect();
+            let samples: BTreeSet<usize> = raw_peak_ids
+                .iter()
+                .map(|raw_peak_id| sample_index.sample(*raw_peak_id))
+                .collect();
+            provenance.insert(
+                (*chromosome, peak.peak().id()),
+                PeakProvenance {
+                    samples: samples.into_iter().collect(),
+                    raw_peak_ids: raw_peak_ids.into_iter().collect(),
+                },
+            );
+        }
+    }
+    log::debug!(
+        target: LOG_TARGET,
+        "Determined the provenance of {} consensus peaks.",
+        provenance.len()
+    );
+    provenance
+}
+
+/// Removes all consensus peaks with raw peaks from less than the specified number of
+/// distinct samples. Returns the retained consensus peaks.
+///
+/// # Parameters
+///
+/// * `consensus` - the consensus peaks keeping track of their raw peaks
+/// * `sample_index` - the mapping of raw peaks to samples
+/// * `min_samples` - the minimum number of contributing samples
+pub fn filter_min_samples(
+    consensus: TrackedConsensus,
+    sample_index: &SampleIndex,
+    min_samples: usize,
+) -> TrackedConsensus {
+    let mut removed = 0;
+    let retained = consensus
+        .into_iter()
+        .map(|(chromosome, peaks)| {
+            let peaks_before = peaks.len();
+            let peaks: Vec<_> = peaks
+                .into_iter()
+                .filter(|peak| {
+                    let samples: BTreeSet<usize> = peak
+                        .raw_peak_ids()
+                        .iter()
+                        .map(|raw_peak_id| sample_index.sample(*raw_peak_id))
+                        .collect();
+                    samples.len() >= min_samples
+                })
+                .collect();
+            removed += peaks_before - peaks.len();
+            (chromosome, peaks)
+        })
+        .filter(|(_, peaks)| !peaks.is_empty())
+        .collect();
+    log::info!(
+        target: LOG_TARGET,
+        "Removed {} consensus peaks supported by less than {} samples.",
+        removed,
+        min_samples
+    );
+    retained
+}
+
+/// Combines the provenance of near-duplicate consensus peaks that were merged.
+/// As merged groups of near-duplicates are represented by their smallest ID,
+/// the provenance of each group is stored under this ID.
+///
+/// # Parameters
+///
+/// * `provenance` - the provenance of the consensus peaks before merging
+/// * `duplicates` - the merged near-duplicate pairs
+pub fn merge_duplicate_provenance(
+    mut provenance: ConsensusProvenance,
+    duplicates: &[NearDuplicate],
+) -> ConsensusProvenance {
+    let mut representatives: HashMap<(Chromosome, usize), usize> = HashMap::new();
+    let representative = |representatives: &HashMap<(Chromosome, usize), usize>,
+                          chromosome: Chromosome,
+                          mut id: usize| {
+        while let Some(parent) = representatives.get(&(chromosome, id)) {
+            id = *parent;
+        }
+        id
+    };
+    for duplicate in duplicates {
+        let chromosome = duplicate.chromosome();
+        let first = representative(&representatives, chromosome, duplicate.first().id());
+        let second = representative(&representatives, chromosome, duplicate.second().id());
+        if first != second {
+            representatives.insert((chromosome, first.max(second)), first.min(second));
+        }
+    }
+    let mut members: Vec<(Chromosome, usize)> = representatives.keys().copied().collect();
+    members.sort_unstable();
+    for (chromosome, id) in members {
+        let group_id = representative(&representatives, chromosome, id);
+        if let Some(member_provenance) = provenance.remove(&(chromosome, id)) {
+            provenance
+                .entry((chromosome, group_id))
+                .or_default()
+                .extend(&member_provenance);
+        }
+    }
+    provenance
+}
+
+/// Formats the contributing samples of a consensus peak as comma separated list of
+/// sample names or "." if there are none.
+///
+/// # Parameters
+///
+/// * `provenance` - the provenance of the consensus peak
+/// * `sample_names` - the names of all samples in input order
+fn format_samples(provenance: Option<&PeakProvenance>, sample_names: &[String]) -> String {
+    let samples: Vec<&str> = provenance
+        .iter()
+        .flat_map(|provenance| provenance.samples())
+        .filter_map(|sample| sample_names.get(*sample).map(String::as_str))
+        .collect();
+    if samples.is_empty() {
+        ".".to_string()
+    } else {
+        samples.join(",")
+    }
+}
+
+/// Returns the comma separated names of the contributing samples of each consensus peak
+/// by peak ID, so they can be appended as additional output column.
+///
+/// # Parameters
+///
+/// * `consensus` - the consensus peaks grouped by chromosome
+/// * `provenance` - the provenance of the consensus peaks
+/// * `sample_names` - the names of all samples in input order
+pub fn provenance_column(
+    consensus: &HashMap<Chromosome, Vec<PeakData>>,
+    provenance: &ConsensusProvenance,
+    sample_names: &[String],
+) -> HashMap<usize, String> {
+    consensus
+        .iter()
+        .flat_map(|(chromosome, peaks)| {
+            peaks.iter().map(|peak| {
+                (peak.id(), format_samples(provenance.get(&(*chromosome, peak.id())), sample_names))
+            })
+        })
+        .collect()
+}
+
+/// Returns the number of contributing raw peaks and the names of the contributing samples
+/// of each consensus peak by chromosome and peak ID, so they can be added to JSON output records.
+///
+/// # Parameters
+///
+/// * `consensus` - the consensus peaks grouped by chromosome
+/// * `provenance` - the provenance of the consensus peaks
+/// * `sample_names` - the names of all samples in input order
+pub fn peak_contributions(
+    consensus: &HashMap<Chromosome, Vec<PeakData>>,
+    provenance: &ConsensusProvenance,
+    sample_names: &[String],
+) -> HashMap<(Chromosome, usize), PeakContribution> {
+    consensus
+        .iter()
+        .flat_map(|(chromosome, peaks)| {
+            peaks.iter().map(|peak| {
+                let peak_provenance = provenance
+                    .get(&(*chromosome, peak.id()))
+                    .cloned()
+                    .unwrap_or_default();
+                let samples = peak_provenance
+                    .samples()
+                    .iter()
+                    .filter_map(|sample| sample_names.get(*sample).cloned())
+                    .collect();
+                (
+                    (*chromosome, peak.id()),
+                    PeakContribution::new(peak_provenance.raw_peak_ids().len(), samples),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Writes the provenance of the consensus peaks as tab separated table with the columns
+/// name, chromosome, start, end, the comma separated contributing samples and the comma
+/// separated contributing raw peak IDs. The consensus peaks are sorted by chromosome and
+/// coordinates.
+///
+/// # Parameters
+///
+/// * `writer` - the writer to write the table to
+/// * `consensus` - the consensus peaks grouped by chromosome
+/// * `provenance` - the provenance of the consensus peaks
+/// * `names` - the names of the consensus peaks by peak ID if they are not numbered
+/// * `sample_names` - the names of all samples in input order
+///
+/// # Errors
+///
+/// Returns an error if the table could not be written.
+pub fn write_provenance_table<W: Write>(
+    mut writer: W,
+    consensus: &HashMap<Chromosome, Vec<PeakData>>,
+    provenance: &ConsensusProvenance,
+    names: &HashMap<usize, String>,
+    sample_names: &[String],
+) -> Result<(), ApplicationError> {
+    let mut peaks: Vec<(Chromosome, &PeakData)> = consensus
+        .iter()
+        .flat_map(|(chromosome, peaks)| peaks.iter().map(move |peak| (*chromosome, peak)))
+        .collect();
+    peaks.sort_unstable_by_key(|(chromosome, peak)| (*chromosome, peak.start(), peak.end()));
+    let mut table = String::from("name\tchromosome\tstart\tend\tsamples\traw_peak_ids\n");
+    for (chromosome, peak) in peaks {
+        let peak_provenance = provenance.get(&(chromosome, peak.id()));
+        let raw_peak_ids: Vec<String> = peak_provenance
+            .iter()
+            .flat_map(|provenance| provenance.raw_peak_ids())
+            .map(ToString::to_string)
+            .collect();
+        table.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\n",
+            names
+                .get(&peak.id())
+                .cloned()
+                .unwrap_or_else(|| format!("consensus_{}", peak.id())),
+            chromosome,
+            peak.start(),
+            peak.end(),
+            format_samples(peak_provenance, sample_names),
+            if raw_peak_ids.is_empty() {
+                ".".to_string()
+            } else {
+                raw_peak_ids.join(",")
+            }
+        ));
+    }
+    writer.write_all(table.as_bytes()).map_err(|err| {
+        ApplicationError::from(err).chain("The consensus peak provenance could not be written.")
+    })
+}
+
+/// Writes the mapping of raw peaks to the consensus peaks they were merged into as tab
+/// separated table with the columns raw_peak_name and consensus_name. Raw peaks without a name
+/// are listed by their raw peak ID. The consensus peaks are sorted by chromosome and
+/// coordinates and their raw peaks by raw peak ID.
+///
+/// # Parameters
+///
+/// * `writer` - the writer to write the table to
+/// * `consensus` - the consensus peaks grouped by chromosome
+/// * `provenance` - the provenance of the consensus peaks
+/// * `names` - the names of the consensus peaks by peak ID if they are not numbered
+/// * `raw_peak_names` - the names of the raw peaks by raw peak ID
+///
+/// # Errors
+///
+/// Returns an error if the table could not be written.
+pub fn write_peak_name_mapping<W: Write>(
+    mut writer: W,
+    consensus: &HashMap<Chromosome, Vec<PeakData>>,
+    provenance: &ConsensusProvenance,
+    names: &HashMap<usize, String>,
+    raw_peak_names: &HashMap<usize, String>,
+) -> Result<(), ApplicationError> {
+    let mut peaks: Vec<(Chromosome, &PeakData)> = consensus
+        .iter()
+        .flat_map(|(chromosome, peaks)| peaks.iter().map(move |peak| (*chromosome, peak)))
+        .collect();
+    peaks.sort_unstable_by_key(|(chromosome, peak)| (*chromosome, peak.start(), peak.end()));
+    let mut table = String::from("raw_peak_name\tconsensus_name\n");
+    for (chromosome, peak) in peaks {
+        let name = names
+            .get(&peak.id())
+            .cloned()
+            .unwrap_or_else(|| format!("consensus_{}", peak.id()));
+        for raw_peak_id in provenance
+            .get(&(chromosome, peak.id()))
+            .iter()
+            .flat_map(|provenance| provenance.raw_peak_ids())
+        {
+            let raw_peak_name = raw_peak_names
+                .get(raw_peak_id)
+                .cloned()
+                .unwrap_or_else(|| raw_peak_id.to_string());
+            table.push_str(&format!("{}\t{}\n", raw_peak_name, name));
+        }
+    }
+    writer.write_all(table.as_bytes()).map_err(|err| {
+        ApplicationError::from(err).chain("The raw peak name mapping could not be written.")
+    })
+}
+
+/// Writes the binary occupancy matrix of the consensus peaks as tab separated table with the
+/// consensus peaks as rows and the samples as columns. A cell is 1 if the sample contributed a
+/// raw peak to the consensus peak and 0 otherwise. This is the input format of UpSet plots and
+/// differential occupancy analyses. The consensus peaks are sorted by chromosome and coordinates.
+///
+/// # Parameters
+///
+/// * `writer` - the writer to write the matrix to
+/// * `consensus` - the consensus peaks grouped by chromosome
+/// * `provenance` - the provenance of the consensus peaks
+/// * `names` - the names of the consensus peaks by peak ID if they are not numbered
+/// * `sample_names` - the names of all samples in input order
+///
+/// # Errors
+///
+/// Returns an error if the matrix could not be written.
+pub fn write_occupancy_matrix<W: Write>(
+    mut writer: W,
+    consensus: &HashMap<Chromosome, Vec<PeakData>>,
+    provenance: &ConsensusProvenance,
+    names: &HashMap<usize, String>,
+    sample_names: &[String],
+) -> Result<(), ApplicationError> {
+    let mut peaks: Vec<(Chromosome, &PeakData)> = consensus
+        .iter()
+        .flat_map(|(chromosome, peaks)| peaks.iter().map(move |peak| (*chromosome, peak)))
+        .collect();
+    peaks.sort_unstable_by_key(|(chromosome, peak)| (*chromosome, peak.start(), peak.end()));
+    let mut matrix = std::iter::once("name")
+        .chain(sample_names.iter().map(String::as_str))
+        .collect::<Vec<&str>>()
+        .join("\t");
+    matrix.push('\n');
+    for (chromosome, peak) in peaks {
+        let samples = provenance
+            .get(&(chromosome, peak.id()))
+            .map(PeakProvenance::samples);
+        matrix.push_str(
+            &names
+                .get(&peak.id())
+                .cloned()
+                .unwrap_or_else(|| format!("consensus_{}", peak.id())),
+        );
+        for sample in 0..sample_names.len() {
+            let occupied = samples.is_some_and(|samples| samples.binary_search(&sample).is_ok());
+            matrix.push_str(if occupied { "\t1" } else { "\t0" });
+        }
+        matrix.push('\n');
+    }
+    writer.write_all(matrix.as_bytes()).map_err(|err| {
+        ApplicationError::from(err)
+            .chain("The consensus peak occupancy matrix could not be written.")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        duplicates::near_duplicates,
+        peaks::{PeakData, TrackedConsensusPeak},
+    };
+
+    use super::*;
+
+    fn tracked_consensus() -> TrackedConsensus {
+        HashMap::from([(
+            Chromosome::from("chr1"),
+            vec![
+                TrackedConsensusPeak::new(
+                    PeakData::new(0, 100u64, 199u64, 150u64).unwrap(),
+                    vec![4, 0, 1],
+                ),
+                TrackedConsensusPeak::new(
+                    PeakData::new(5, 110u64, 199u64, 150u64).unwrap(),
+                    vec![5],
+                ),
+                TrackedConsensusPeak::new(
+                    PeakData::new(2, 500u64, 599u64, 550u64).unwrap(),
+                    vec![2],
+                ),
+            ],
+        )])
+    }
+
+    #[test]
+    fn test_sample_index() {
+        let sample_index = SampleIndex::new(&[2, 0, 3]);
+        assert_eq!(sample_index.sample(0), 0);
+        assert_eq!(sample_index.sample(1), 0);
+        assert_eq!(sample_index.sample(2), 2);
+        assert_eq!(sample_index.sample(4), 2);
+    }
+
+    #[test]
+    fn test_consensus_provenance() {
+        let provenance = consensus_provenance(&tracked_consensus(), &SampleIndex::new(&[2, 2, 2]));
+        let chr1 = Chromosome::from("chr1");
+        assert_eq!(provenance[&(chr1, 0)].samples(), &vec![0, 2]);
+        assert_eq!(provenance[&(chr1, 0)].raw_peak_ids(), &vec![0, 1, 4]);
+        assert_eq!(provenance[&(chr1, 2)].samples(), &vec![1]);
+        let consensus: HashMap<Chromosome, Vec<PeakData>> = tracked_consensus()
+            .into_iter()
+            .map(|(chromosome, peaks)| {
+                (chromosome, peaks.into_iter().map(PeakData::from).collect())
+            })
+            .collect();
+        let merged = merge_duplicate_provenance(provenance, &near_duplicates(&consensus, 0.5));
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[&(chr1, 0)].raw_peak_ids(), &vec![0, 1, 4, 5]);
+    }
+
+    #[test]
+    fn test_filter_min_samples() {
+        let sample_index = SampleIndex::new(&[2, 2, 2]);
+        let chr1 = Chromosome::from("chr1");
+        let retained = filter_min_samples(tracked_consensus(), &sample_index, 2);
+        // The raw peaks 0 and 1 of the same sample only count once.
+        let ids: Vec<usize> = retained[&chr1]
+            .iter()
+            .map(|peak| peak.peak().id())
+            .collect();
+        assert_eq!(ids, vec![0]);
+        assert_eq!(filter_min_samples(tracked_consensus(), &sample_index, 1)[&chr1].len(), 3);
+        assert!(filter_min_samples(tracked_consensus(), &sample_index, 3).is_empty());
+    }
+
+    #[test]
+    fn test_write_provenance() {
+        let provenance = consensus_provenance(&tracked_consensus(), &SampleIndex::new(&[2, 2, 2]));
+        let consensus = HashMap::from([(
+            Chromosome::from("chr1"),
+            vec![
+                PeakData::new(2, 500u64, 599u64, 550u64).unwrap(),
+                PeakData::new(0, 100u64, 199u64, 150u64).unwrap(),
+                PeakData::new(9, 900u64, 999u64, 950u64).unwrap(),
+            ],
+        )]);
+        let sample_names = ["a".to_string(), "b".to_string(), "c".to_string()];
+        let column = provenance_column(&consensus, &provenance, &sample_names);
+        assert_eq!(column[&0], "a,c");
+        assert_eq!(column[&9], ".");
+        let contributions = peak_contributions(&consensus, &provenance, &sample_names);
+        let chr1 = Chromosome::from("chr1");
+        assert_eq!(
+            contributions[&(chr1, 0)],
+            PeakContribution::new(3, vec!["a".into(), "c".into()])
+        );
+        assert_eq!(contributions[&(chr1, 9)], PeakContribution::default());
+        let mut table = Vec::new();
+        write_provenance_table(
+            &mut table,
+            &consensus,
+            &provenance,
+            &HashMap::from([(2, "peak_b".to_string())]),
+            &sample_names,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            "name\tchromosome\tstart\tend\tsamples\traw_peak_ids\n\
+            consensus_0\tchr1\t100\t199\ta,c\t0,1,4\n\
+            peak_b\tchr1\t500\t599\tb\t2\n\
+            consensus_9\tchr1\t900\t999\t.\t.\n"
+        );
+    }
+
+    #[test]
+    fn test_write_peak_name_mapping() {
+        let provenance = consensus_provenance(&tracked_consensus(), &SampleIndex::new(&[2, 2, 2]));
+        let consensus = HashMap::from([(
+            Chromosome::from("chr1"),
+            vec![
+                PeakData::new(2, 500u64, 599u64, 550u64).unwrap(),
+                PeakData::new(0, 100u64, 199u64, 150u64).unwrap(),
+            ],
+        )]);
+        let raw_peak_names = HashMap::from([
+            (0, "raw_a".to_string()),
+            (2, "raw_b".to_string()),
+            (4, "raw_c".to_string()),
+        ]);
+        let mut table = Vec::new();
+        write_peak_name_mapping(
+            &mut table,
+            &consensus,
+            &provenance,
+            &HashMap::from([(2, "peak_b".to_string())]),
+            &raw_peak_names,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            "raw_peak_name\tconsensus_name\n\
+            raw_a\tconsensus_0\n\
+            1\tconsensus_0\n\
+            raw_c\tconsensus_0\n\
+            raw_b\tpeak_b\n"
+        );
+    }
+
+    #[test]
+    fn test_write_occupancy_matrix() {
+        let provenance = consensus_provenance(&tracked_consensus(), &SampleIndex::new(&[2, 2, 2]));
+        let consensus = HashMap::from([(
+            Chromosome::from("chr1"),
+            vec![
+                PeakData::new(2, 500u64, 599u64, 550u64).unwrap(),
+                PeakData::new(0, 100u64, 199u64, 150u64).unwrap(),
+            ],
+        )]);
+        let mut matrix = Vec::new();
+        write_occupancy_matrix(
+            &mut matrix,
+            &consensus,
+            &provenance,
+            &HashMap::from([(2, "peak_b".to_string())]),
+            &["a".to_string(), "b".to_string(), "c".to_string()],
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(matrix).unwrap(),
+            "name\ta\tb\tc\n\
+            consensus_0\t1\t0\t1\n\
+            peak_b\t0\t1\t0\n"
+        );
+    }
+}
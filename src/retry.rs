@@ -0,0 +1,317 @@
+//! This module retries I/O operations that fail with transient errors.
+//!
+//! Network and FUSE based cluster file systems occasionally fail single operations,
+//! for example with timeouts or stale file handles. Instead of aborting a long running
+//! consensus peak generation, such operations are retried with an exponential backoff.
+//! The policy used by the file operations of this library is configured process wide
+//! via [`RetryPolicy::set_global`] and does not retry by default.
+
+use std::{
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
+    sync::RwLock,
+    time::Duration,
+};
+
+use getset::CopyGetters;
+
+/// The log target of this module.
+const LOG_TARGET: &str = "retry";
+
+/// The maximum delay between two attempts.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// The policy used by the file operations of this library.
+static GLOBAL_POLICY: RwLock<RetryPolicy> = RwLock::new(RetryPolicy::none());
+
+/// A policy to retry operations failing with transient I/O errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CopyGetters)]
+pub struct RetryPolicy {
+    /// The maximum number of retries after the initial attempt.
+    #[getset(get_copy = "pub")]
+    max_retries: u32,
+    /// The delay before the first retry, which doubles for each further retry.
+    #[getset(get_copy = "pub")]
+    initial_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Creates a new retry policy.
+    ///
+    /// # Parameters
+    ///
+    /// * `max_retries` - the maximum number of retries after the initial attempt
+    /// * `initial_delay` - the delay before the first retry, which doubles for each further retry
+    pub fn new(max_retries: u32, initial_delay: Duration) -> Self {
+        Self {
+            max_retries,
+            initial_delay,
+        }
+    }
+
+    /// Creates a policy that never retries.
+    pub const fn none() -> Self {
+        Self {
+            max_retries: 0,
+            initial_delay: Duration::ZERO,
+        }
+    }
+
+    /// Returns the policy used by the file operations of this library.
+    pub fn global() -> Self {
+        *GLOBAL_POLICY
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Sets this policy as the policy used by the file operations of this library.
+    pub fn set_global(self) {
+        *GLOBAL_POLICY
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = self;
+    }
+
+    /// Returns the delay before the specified retry.
+    ///
+    /// # Parameters
+    ///
+    /// * `retry` - the number of the retry starting at 1
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.initial_delay
+            .checked_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY))
+    }
+
+    /// Runs the operation and retries it as long as it fails with a transient error
+    /// and the maximum number of retries has not been reached.
+    ///
+    /// # Parameters
+    ///
+    /// * `description` - a description of the operation used for logging
+    /// * `operation` - the operation to run
+    pub fn run<T, F: FnMut() -> std::io::Result<T>>(
+        &self,
+        description: &str,
+        mut operation: F,
+    ) -> std::io::Result<T> {
+        let mut retry = 0;
+        loop {
+            match operation() {
+                Err(err) if retry < self.max_retries && is_transient(&err) => {
+                    retry += 1;
+                    let delay = self.delay(retry);
+                    log::warn!(
+                        target: LOG_TARGET,
+                        "{} failed with a transient error: {} Retrying in {} ms \
+                        (retry {} of {}).",
+                        description,
+                        err,
+                        delay.as_millis(),
+                        retry,
+                        self.max_retries
+                    );
+                    std::thread::sleep(delay);
+                },
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// Runs the operation with the [global policy](RetryPolicy::global).
+///
+/// # Parameters
+///
+/// * `description` - a description of the operation used for logging
+/// * `operation` - the operation to run
+pub fn retry<T, F: FnMut() -> std::io::Result<T>>(
+    description: &str,
+    operation: F,
+) -> std::io::Result<T> {
+    RetryPolicy::global().run(description, operation)
+}
+
+/// Returns `true` if the error is likely to be transient, so the failed operation
+/// might succeed if it is retried.
+///
+/// # Parameters
+///
+/// * `err` - the error to check
+pub fn is_transient(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::TimedOut
+            | ErrorKind::ResourceBusy
+            | ErrorKind::StaleNetworkFileHandle
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NetworkDown
+    )
+}
+
+/// A reader or writer retrying single read and write calls that fail with transient errors.
+/// As failed calls do not consume any data, retrying them is safe.
+#[derive(Debug)]
+pub struct Retrying<T> {
+    inner: T,
+    policy: RetryPolicy,
+    description: String,
+}
+
+impl<T> Retrying<T> {
+    /// Wraps the reader or writer using the [global policy](RetryPolicy::global).
+    ///
+    /// # Parameters
+    ///
+    /// * `inner` - the reader or writer
+    /// * `description` - a description of the data source or destination used for logging
+    pub fn new<D: ToString>(inner: T, description: D) -> Self {
+        Self::with_policy(inner, description, RetryPolicy::global())
+    }
+
+    /// Wraps the reader or writer using the specified policy.
+    ///
+    /// # Parameters
+    ///
+    /// * `inner` - the reader or writer
+    /// * `description` - a description of the data source or destination used for logging
+    /// * `policy` - the retry policy
+    pub fn with_policy<D: ToString>(inner: T, description: D, policy: RetryPolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            description: description.to_string(),
+        }
+    }
+
+    /// Returns the wrapped reader or writer.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Read> Read for Retrying<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let inner = &mut self.inner;
+        self.policy.run(&self.description, || inner.read(buf))
+    }
+}
+
+impl<T: Seek> Seek for Retrying<T> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let inner = &mut self.inner;
+        self.policy.run(&self.description, || inner.seek(pos))
+    }
+}
+
+impl<T: Write> Write for Retrying<T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let inner = &mut self.inner;
+        self.policy.run(&self.description, || inner.write(buf))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let inner = &mut self.inner;
+        self.policy.run(&self.description, || inner.flush())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+
+    use super::*;
+
+    /// A reader failing with the specified error before every chunk of data.
+    struct FlakyReader {
+        chunks: Vec<&'static [u8]>,
+        kind: ErrorKind,
+        failed: bool,
+    }
+
+    impl Read for FlakyReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.chunks.is_empty() {
+                return Ok(0);
+            }
+            if !self.failed {
+                self.failed = true;
+                return Err(std::io::Error::from(self.kind));
+            }
+            self.failed = false;
+            let chunk = self.chunks.remove(0);
+            buf[..chunk.len()].copy_from_slice(chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    #[test]
+    fn test_retry_policy_delay() {
+        let policy = RetryPolicy::new(5, Duration::from_millis(100));
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(4), Duration::from_millis(800));
+        assert_eq!(policy.delay(40), MAX_RETRY_DELAY);
+        assert_eq!(RetryPolicy::default(), RetryPolicy::none());
+    }
+
+    #[test]
+    fn test_retry_policy_run() {
+        let policy = RetryPolicy::new(2, Duration::ZERO);
+        let mut attempts = 0;
+        let result = policy.run("Test", || {
+            attempts += 1;
+            if attempts < 3 {
+                Err(std::io::Error::from(ErrorKind::TimedOut))
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        attempts = 0;
+        let result: std::io::Result<()> = policy.run("Test", || {
+            attempts += 1;
+            Err(std::io::Error::from(ErrorKind::StaleNetworkFileHandle))
+        });
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::StaleNetworkFileHandle);
+        assert_eq!(attempts, 3);
+
+        attempts = 0;
+        let result: std::io::Result<()> = policy.run("Test", || {
+            attempts += 1;
+            Err(std::io::Error::from(ErrorKind::NotFound))
+        });
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn test_retrying_reader() {
+        let flaky = || FlakyReader {
+            chunks: vec![b"chr1\t1", b"0\t20\nchr2\t5\t9\n"],
+            kind: ErrorKind::TimedOut,
+            failed: false,
+        };
+        let reader = BufReader::new(Retrying::with_policy(
+            flaky(),
+            "Test",
+            RetryPolicy::new(1, Duration::ZERO),
+        ));
+        let lines: Vec<String> = reader.lines().map(|line| line.unwrap()).collect();
+        assert_eq!(lines, vec!["chr1\t10\t20", "chr2\t5\t9"]);
+        let mut content = String::new();
+        assert!(
+            Retrying::with_policy(flaky(), "Test", RetryPolicy::none())
+                .read_to_string(&mut content)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,318 @@
+//! This module parses sample sheets mapping peak files to sample names, conditions and
+//! replicate groups, so large cohorts do not have to be listed on the command line.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use getset::Getters;
+
+use crate::{
+    error::{ApplicationError, ApplicationErrorType, ErrorContext},
+    input::read_decompressed_to_string,
+    metadata::multiqc_sample_name,
+    retry::retry,
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "samples";
+
+/// The header of the sample sheet column containing the peak file paths.
+pub const PATH_COLUMN: &str = "path";
+/// The header of the sample sheet column containing the sample names.
+pub const SAMPLE_COLUMN: &str = "sample";
+/// The header of the optional sample sheet column containing the conditions.
+pub const CONDITION_COLUMN: &str = "condition";
+/// The header of the optional sample sheet column containing the replicate groups.
+pub const REPLICATE_COLUMN: &str = "replicate";
+
+/// The hint shown if the sample sheet header is invalid.
+const HEADER_HINT: &str = "The first line of the sample sheet must be a header containing the \
+                           columns \"path\" and \"sample\" and optionally \"condition\" and \
+                           \"replicate\".";
+
+/// A single peak file of a sample sheet.
+#[derive(Getters, Debug, Clone, PartialEq, Eq)]
+#[getset(get = "pub")]
+pub struct SampleSheetEntry {
+    /// The path of the peak file.
+    path: PathBuf,
+    /// The name of the sample.
+    sample: String,
+    /// The condition of the sample if specified.
+    condition: Option<String>,
+    /// The replicate group of the sample if specified.
+    replicate: Option<String>,
+}
+
+/// A sample sheet mapping peak files to sample names, conditions and replicate groups.
+#[derive(Getters, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SampleSheet {
+    /// The peak files in the order of the sample sheet.
+    #[getset(get = "pub")]
+    entries: Vec<SampleSheetEntry>,
+}
+
+impl SampleSheet {
+    /// Parses a tab separated sample sheet or a comma separated one if the file has the
+    /// extension `.csv`. The first line must be a header naming the [`PATH_COLUMN`] and
+    /// [`SAMPLE_COLUMN`] and optionally the [`CONDITION_COLUMN`] and [`REPLICATE_COLUMN`].
+    /// Further columns are ignored. Relative paths are resolved relative to the directory
+    /// of the sample sheet. Blank lines and lines starting with `#` are skipped.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - the path of the sample sheet
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, the header is invalid,
+    /// a record is incomplete or a peak file is listed multiple times.
+    pub fn from_file<T: AsRef<Path>>(path: T) -> Result<Self, ApplicationError> {
+        let path = path.as_ref();
+        log::info!(target: LOG_TARGET, "Parsing sample sheet {}.", path.display());
+        let content =
+            retry(&format!("Reading {}", path.display()), || read_decompressed_to_string(path))
+                .map_err(|err| {
+                    ApplicationError::from(err).chain(format!(
+                        "The sample sheet \"{}\" could not be read.",
+                        path.display()
+                    ))
+                })?;
+        let delimiter = match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => ',',
+            _ => '\t',
+        };
+        let base_directory = path.parent().unwrap_or(Path::new(""));
+        let mut lines = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'));
+        let context = |line_index: usize| {
+            ErrorContext::new()
+                .with_path(path.display())
+                .with_line(line_index as u64 + 1)
+        };
+        let (header_index, header) = lines.next().ok_or_else(|| {
+            ApplicationError::new(
+                ApplicationErrorType::ParseError,
+                format!("The sample sheet \"{}\" is empty.", path.display()),
+            )
+            .with_hint(HEADER_HINT)
+        })?;
+        let header: Vec<String> = header
+            .split(delimiter)
+            .map(|column| column.trim().to_ascii_lowercase())
+            .collect();
+        let column = |name: &str| header.iter().position(|column| column == name);
+        let (Some(path_column), Some(sample_column)) = (column(PATH_COLUMN), column(SAMPLE_COLUMN))
+        else {
+            return Err(ApplicationError::new(
+                ApplicationErrorType::ParseError,
+                "The sample sheet header does not contain a path and a sample column.",
+            )
+            .with_context(context(header_index))
+            .with_hint(HEADER_HINT));
+        };
+        let condition_column = column(CONDITION_COLUMN);
+        let replicate_column = column(REPLICATE_COLUMN);
+        let mut entries: Vec<SampleSheetEntry> = Vec::new();
+        for (line_index, line) in lines {
+            let fields: Vec<&str> = line.split(delimiter).map(str::trim).collect();
+            let field = |column: Option<usize>| {
+                column
+                    .and_then(|column| fields.get(column))
+                    .filter(|field| !field.is_empty())
+                    .map(|field| field.to_string())
+            };
+            let (Some(peak_file), Some(sample)) =
+                (field(Some(path_column)), field(Some(sample_column)))
+            else {
+                return Err(ApplicationError::new(
+                    ApplicationErrorType::ParseError,
+                    "The sample sheet record does not contain a path and a sample name.",
+                )
+                .with_context(context(line_index)));
+            };
+            let peak_file = base_directory.join(peak_file);
+            if entries.iter().any(|entry| entry.path == peak_file) {
+                return Err(ApplicationError::new(
+                    ApplicationErrorType::ValidationError,
+                    format!("The peak file \"{}\" is listed multiple times.", peak_file.display()),
+                )
+                .with_context(context(line_index)));
+            }
+            entries.push(SampleSheetEntry {
+                path: peak_file,
+                sample,
+                condition: field(condition_column),
+                replicate: field(replicate_column),
+            });
+        }
+        log::info!(target: LOG_TARGET, "Parsed {} peak files from the sample sheet.", entries.len());
+        Ok(Self { entries })
+    }
+
+    /// Returns the paths of all peak files in the order of the sample sheet.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.entries
+            .iter()
+            .map(|entry| entry.path.clone())
+            .collect()
+    }
+
+    /// Returns the sample names of the specified peak files. Peak files not listed in the
+    /// sample sheet are named by their [file stem](multiqc_sample_name).
+    ///
+    /// # Parameters
+    ///
+    /// * `peak_files` - the peak files to name
+    pub fn sample_names<T: AsRef<Path>>(&self, peak_files: &[T]) -> Vec<String> {
+        peak_files
+            .iter()
+            .map(|peak_file| {
+                self.entries
+                    .iter()
+                    .find(|entry| entry.path == peak_file.as_ref())
+                    .map_or_else(|| multiqc_sample_name(peak_file), |entry| entry.sample.clone())
+            })
+            .collect()
+    }
+
+    /// Returns the paths of the peak files of each condition sorted by condition.
+    /// Peak files without a condition are not included.
+    pub fn conditions(&self) -> BTreeMap<String, Vec<PathBuf>> {
+        let mut conditions: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+        for entry in &self.entries {
+            if let Some(condition) = &entry.condition {
+                conditions
+                    .entry(condition.clone())
+                    .or_default()
+                    .push(entry.path.clone());
+            }
+        }
+        conditions
+    }
+}
+
+/// Returns the output path of the consensus peaks of a single condition by inserting
+/// the condition before the extension of the output file name.
+///
+/// # Parameters
+///
+/// * `output_file` - the consensus peak output file of all samples
+/// * `condition` - the condition
+pub fn condition_output_file<T: AsRef<Path>>(output_file: T, condition: &str) -> PathBuf {
+    let output_file = output_file.as_ref();
+    let mut file_name = output_file
+        .file_stem()
+        .map(|stem| stem.to_os_string())
+        .unwrap_or_default();
+    file_name.push("_");
+    // Replaces characters that are not allowed or problematic in file names.
+    file_name.push(
+        condition
+            .chars()
+            .map(|character| {
+                if character.is_ascii_alphanumeric() || character == '-' || character == '.' {
+                    character
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>(),
+    );
+    if let Some(extension) = output_file.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+    output_file.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::{test_output, test_resources};
+
+    use super::*;
+
+    #[test]
+    fn test_sample_sheet_from_file() {
+        let sheet =
+            SampleSheet::from_file(test_resources().join("input_test_sample_sheet.tsv")).unwrap();
+        assert_eq!(
+            sheet.paths(),
+            vec![
+                test_resources().join("input_test_main_internal_input_01.narrowPeak"),
+                test_resources().join("input_test_main_internal_input_02.narrowPeak"),
+                test_resources().join("input_test_main_internal_input_iter_01.narrowPeak"),
+            ]
+        );
+        assert_eq!(sheet.entries()[0].sample(), "treated_a");
+        assert_eq!(sheet.entries()[0].condition().as_deref(), Some("treated"));
+        assert_eq!(sheet.entries()[0].replicate().as_deref(), Some("1"));
+        assert_eq!(sheet.entries()[2].condition(), &None);
+        assert_eq!(
+            sheet.conditions(),
+            BTreeMap::from([
+                ("control".to_string(), vec![sheet.paths()[1].clone()]),
+                ("treated".to_string(), vec![sheet.paths()[0].clone()]),
+            ])
+        );
+        assert_eq!(
+            sheet.sample_names(&[
+                sheet.paths()[1].clone(),
+                PathBuf::from("other/sample_x.bed")
+            ]),
+            vec!["control_a".to_string(), "sample_x".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_sample_sheet_from_file_csv() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let sheet_path = test_output().join("test_sample_sheet_from_file_csv.csv");
+        std::fs::write(&sheet_path, "sample,path\nsample_a,peaks_a.bed\n\nsample_b,peaks_b.bed\n")
+            .unwrap();
+        let sheet = SampleSheet::from_file(&sheet_path);
+        std::fs::remove_file(&sheet_path).unwrap();
+        let sheet = sheet.unwrap();
+        assert_eq!(
+            sheet.paths(),
+            vec![
+                test_output().join("peaks_a.bed"),
+                test_output().join("peaks_b.bed")
+            ]
+        );
+        assert_eq!(sheet.entries()[1].sample(), "sample_b");
+        assert!(sheet.conditions().is_empty());
+    }
+
+    #[test]
+    fn test_sample_sheet_from_file_invalid() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let sheet_path = test_output().join("test_sample_sheet_from_file_invalid.tsv");
+        for content in [
+            "",
+            "file\tsample\npeaks_a.bed\tsample_a\n",
+            "path\tsample\npeaks_a.bed\n",
+            "path\tsample\npeaks_a.bed\tsample_a\npeaks_a.bed\tsample_b\n",
+        ] {
+            std::fs::write(&sheet_path, content).unwrap();
+            assert!(SampleSheet::from_file(&sheet_path).is_err(), "{:?}", content);
+        }
+        std::fs::remove_file(&sheet_path).unwrap();
+    }
+
+    #[test]
+    fn test_condition_output_file() {
+        assert_eq!(
+            condition_output_file("out/consensus.bed", "treated"),
+            PathBuf::from("out/consensus_treated.bed")
+        );
+        assert_eq!(
+            condition_output_file("consensus", "day 1/2"),
+            PathBuf::from("consensus_day_1_2")
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! This module analyses how the number of consensus peaks grows with the number of samples,
+//! so users can judge whether additional replicates would still add consensus peaks.
+
+use std::{collections::HashMap, io::Write};
+
+use getset::CopyGetters;
+
+use crate::{
+    chromosome::Chromosome, consensus::ConsensusBuilder, error::ApplicationError, peaks::PeakData,
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "saturation";
+
+/// The number of consensus peaks generated from a random subset of samples.
+#[derive(CopyGetters, Clone, Copy, PartialEq, Eq, Debug)]
+#[getset(get_copy = "pub")]
+pub struct SaturationPoint {
+    /// The number of samples in the subset.
+    samples: usize,
+    /// The repetition of the subsampling starting at 1.
+    repetition: usize,
+    /// The number of consensus peaks generated from the subset.
+    consensus_peaks: usize,
+}
+
+/// A deterministic [SplitMix64](https://prng.di.unimi.it/splitmix64.c) pseudo random number
+/// generator, so subsampling can be reproduced with the same seed.
+pub(crate) struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    /// Creates a new generator.
+    ///
+    /// # Parameters
+    ///
+    /// * `seed` - the seed of the generator
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Returns the next pseudo random number.
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut value = self.state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d049bb133111eb);
+        value ^ (value >> 31)
+    }
+
+    /// Returns a pseudo random index below the specified bound.
+    ///
+    /// # Parameters
+    ///
+    /// * `bound` - the exclusive upper bound
+    pub(crate) fn next_index(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/// Generates consensus peaks from random subsets of 1 to all samples and records the number of
+/// consensus peaks for each subset.
+///
+/// # Parameters
+///
+/// * `builder` - the builder to generate consensus peaks with
+/// * `samples` - the raw peaks of each sample
+/// * `repetitions` - the number of random subsets per subset size
+/// * `seed` - the seed of the random subsampling
+///
+/// # Errors
+///
+/// Returns an error if consensus peak generation fails.
+pub fn saturation_curve(
+    builder: &ConsensusBuilder,
+    samples: &[HashMap<Chromosome, Vec<PeakData>>],
+    repetitions: usize,
+    seed: u64,
+) -> Result<Vec<SaturationPoint>, ApplicationError> {
+    let mut random = SplitMix64::new(seed);
+    let mut points = Vec::with_capacity(samples.len() * repetitions);
+    for subset_size in 1..=samples.len() {
+        for repetition in 1..=repetitions {
+            // Draws the subset with a partial Fisher-Yates shuffle.
+            let mut indices: Vec<usize> = (0..samples.len()).collect();
+            for position in 0..subset_size {
+                let swap = position + random.next_index(samples.len() - position);
+                indices.swap(position, swap);
+            }
+            log::debug!(
+                target: LOG_TARGET,
+                "Generating consensus peaks for the samples {:?}.",
+                &indices[..subset_size]
+            );
+            // Reassigns the peak identifiers, as they are only unique within each sample.
+            let mut peaks: HashMap<Chromosome, Vec<PeakData>> = HashMap::new();
+            let mut peak_id = 0;
+            for sample in indices[..subset_size].iter().map(|index| &samples[*index]) {
+                for (chromosome, sample_peaks) in sample {
+                    let chromosome_peaks = peaks.entry(*chromosome).or_default();
+                    for peak in sample_peaks {
+                        chromosome_peaks.push(PeakData::new(
+                            peak_id,
+                            peak.start(),
+                            peak.end(),
+                            peak.summit(),
+                        )?);
+                        peak_id += 1;
+                    }
+                }
+            }
+            let consensus_peaks = builder.run(peaks)?.values().map(Vec::len).sum();
+            points.push(SaturationPoint {
+                samples: subset_size,
+                repetition,
+                consensus_peaks,
+            });
+        }
+    }
+    Ok(points)
+}
+
+/// Writes the saturation curve as tab separated table.
+///
+/// # Parameters
+///
+/// * `writer` - the writer to write the table to
+/// * `points` - the points of the saturation curve
+///
+/// # Errors
+///
+/// Returns an error if the table cannot be written.
+pub fn write_saturation_table<W: Write>(
+    mut writer: W,
+    points: &[SaturationPoint],
+) -> Result<(), ApplicationError> {
+    let mut table = String::from("samples\trepetition\tconsensus_peaks\n");
+    for point in points {
+        table.push_str(&format!(
+            "{}\t{}\t{}\n",
+            point.samples(),
+            point.repetition(),
+            point.consensus_peaks()
+        ));
+    }
+    writer.write_all(table.as_bytes()).map_err(|err| {
+        ApplicationError::from(err).chain("The saturation curve could not be written.")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saturation_curve() {
+        let chromosome = Chromosome::from("chr1");
+        // Each sample contains a shared peak and a peak unique to the sample.
+        let samples: Vec<HashMap<Chromosome, Vec<PeakData>>> = (0..3u64)
+            .map(|sample| {
+                let unique_start = 10000 * (sample + 1);
+                HashMap::from([(
+                    chromosome,
+                    vec![
+                        PeakData::new(0, 100u64, 199u64, 150u64).unwrap(),
+                        PeakData::new(1, unique_start, unique_start + 99, unique_start + 50)
+                            .unwrap(),
+                    ],
+                )])
+            })
+            .collect();
+        let builder = ConsensusBuilder::new();
+        let points = saturation_curve(&builder, &samples, 2, 42).unwrap();
+        assert_eq!(points.len(), 6);
+        for point in &points {
+            assert_eq!(point.consensus_peaks(), point.samples() + 1);
+        }
+        assert_eq!(points, saturation_curve(&builder, &samples, 2, 42).unwrap());
+        assert!(saturation_curve(&builder, &[], 2, 42).unwrap().is_empty());
+
+        let mut table = Vec::new();
+        write_saturation_table(&mut table, &points[..2]).unwrap();
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            "samples\trepetition\tconsensus_peaks\n1\t1\t2\n1\t2\t2\n"
+        );
+    }
+
+    #[test]
+    fn test_split_mix_64() {
+        let mut random = SplitMix64 { state: 7 };
+        let first: Vec<u64> = (0..3).map(|_| random.next_u64()).collect();
+        let mut random = SplitMix64 { state: 7 };
+        assert_eq!(first, (0..3).map(|_| random.next_u64()).collect::<Vec<u64>>());
+        assert!((0..100).all(|_| random.next_index(3) < 3));
+    }
+}
@@ -0,0 +1,472 @@
+//! This module preserves the statistical columns of narrowPeak input files, namely the score
+//! (column 5), the signal value (column 7), the p-value (column 8) and the q-value (column 9),
+//! and aggregates them over the raw peaks contributing to each consensus peak.
+//!
+//! The raw peaks contributing to a consensus peak are all raw peaks the consensus peak was
+//! created from.
+
+use std::{collections::HashMap, path::Path};
+
+use getset::{CopyGetters, Getters};
+
+use crate::{
+    chromosome::Chromosome,
+    error::ApplicationError,
+    input::{InputFormat, bed_to_peaks_with_format},
+    output::PeakContribution,
+    peak_set::PeakSet,
+    peaks::gipfelkreuzer::PeakWeights,
+    provenance::ConsensusProvenance,
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "scores";
+
+/// The maximum score of BED records.
+pub const MAX_BED_SCORE: f64 = 1000.0;
+
+/// The statistical values of a peak as defined by the
+/// [narrowPeak](https://genome.ucsc.edu/FAQ/FAQformat.html#format12) format.
+/// Values that are not present are `None`.
+#[derive(CopyGetters, Clone, Copy, Default, PartialEq, Debug)]
+#[getset(get_copy = "pub")]
+pub struct PeakScores {
+    /// The score of column 5.
+    score: Option<f64>,
+    /// The signal value of column 7.
+    signal_value: Option<f64>,
+    /// The -log10 p-value of column 8.
+    p_value: Option<f64>,
+    /// The -log10 q-value of column 9.
+    q_value: Option<f64>,
+}
+
+impl PeakScores {
+    /// Creates the statistical values of a peak.
+    ///
+    /// # Parameters
+    ///
+    /// * `score` - the score
+    /// * `signal_value` - the signal value
+    /// * `p_value` - the -log10 p-value
+    /// * `q_value` - the -log10 q-value
+    pub fn new(
+        score: Option<f64>,
+        signal_value: Option<f64>,
+        p_value: Option<f64>,
+        q_value: Option<f64>,
+    ) -> Self {
+        Self {
+            score,
+            signal_value,
+            p_value,
+            q_value,
+        }
+    }
+
+    /// Parses the statistical values from the fields of a narrowPeak record.
+    /// Values that are missing, not numeric or negative, which narrowPeak uses to mark
+    /// unavailable values, are `None`.
+    ///
+    /// # Parameters
+    ///
+    /// * `fields` - the fields of the record
+    pub(crate) fn from_fields(fields: &[&str]) -> Self {
+        let value = |index: usize| {
+            fields
+                .get(index)
+                .and_then(|field| field.parse::<f64>().ok())
+                .filter(|value| value.is_finite() && *value >= 0.0)
+        };
+        Self::new(value(4), value(6), value(7), value(8))
+    }
+
+    /// Returns the values with the score replaced.
+    ///
+    /// # Parameters
+    ///
+    /// * `score` - the new score
+    pub fn with_score(mut self, score: Option<f64>) -> Self {
+        self.score = score;
+        self
+    }
+
+    /// Returns `true` if none of the values is present.
+    pub fn is_empty(&self) -> bool {
+        self.score.is_none()
+            && self.signal_value.is_none()
+            && self.p_value.is_none()
+            && self.q_value.is_none()
+    }
+
+    /// Replaces the score, signal value, p-value and q-value fields of a BED record line
+    /// with the present values. Fields that are not part of the record are not added.
+    ///
+    /// # Parameters
+    ///
+    /// * `record_line` - the BED record line
+    pub fn apply_to_record_line(&self, record_line: &str) -> String {
+        if record_line.is_empty() {
+            return record_line.to_string();
+        }
+        let mut fields: Vec<String> = record_line
+            .trim_end_matches('\n')
+            .split('\t')
+            .map(str::to_string)
+            .collect();
+        for (index, value) in [
+            (4, self.score.map(|score| format!("{:.0}", score))),
+            (6, self.signal_value.map(format_value)),
+            (7, self.p_value.map(format_value)),
+            (8, self.q_value.map(format_value)),
+        ] {
+            if let (Some(field), Some(value)) = (fields.get_mut(index), value) {
+                *field = value;
+            }
+        }
+        format!("{}\n", fields.join("\t"))
+    }
+}
+
+/// Formats a statistical value with up to 5 decimal places.
+///
+/// # Parameters
+///
+/// * `value` - the value
+fn format_value(value: f64) -> String {
+    let formatted = format!("{:.5}", value);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/// The function aggregating the statistical values of the raw peaks contributing to a
+/// consensus peak.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ScoreAggregation {
+    /// The arithmetic mean of the values.
+    Mean,
+    /// The median of the values.
+    Median,
+    /// The maximum of the values.
+    #[default]
+    Max,
+}
+
+impl ScoreAggregation {
+    /// Aggregates the values or returns `None` if there are no values.
+    ///
+    /// # Parameters
+    ///
+    /// * `values` - the values to aggregate
+    pub fn aggregate(&self, mut values: Vec<f64>) -> Option<f64> {
+        if values.is_empty() {
+            return None;
+        }
+        match self {
+            ScoreAggregation::Mean => Some(values.iter().sum::<f64>() / values.len() as f64),
+            ScoreAggregation::Median => {
+                values.sort_by(f64::total_cmp);
+                let middle = values.len() / 2;
+                if values.len().is_multiple_of(2) {
+                    Some((values[middle - 1] + values[middle]) / 2.0)
+                } else {
+                    Some(values[middle])
+                }
+            },
+            ScoreAggregation::Max => values.into_iter().reduce(f64::max),
+        }
+    }
+}
+
+impl std::fmt::Display for ScoreAggregation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScoreAggregation::Mean => write!(f, "mean"),
+            ScoreAggregation::Median => write!(f, "median"),
+            ScoreAggregation::Max => write!(f, "max"),
+        }
+    }
+}
+
+/// The source of the score (column 5) of the consensus peaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ScoreSource {
+    /// The aggregated scores of the contributing raw peaks.
+    #[default]
+    Aggregated,
+    /// The number of aggregated raw peaks scaled to the maximum number of any consensus peak.
+    Peaks,
+    /// The number of supporting samples scaled to the number of samples.
+    Samples,
+}
+
+impl std::fmt::Display for ScoreSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScoreSource::Aggregated => write!(f, "aggregated"),
+            ScoreSource::Peaks => write!(f, "peaks"),
+            ScoreSource::Samples => write!(f, "samples"),
+        }
+    }
+}
+
+/// Scores the support of the consensus peaks between 0 and [`MAX_BED_SCORE`] by peak ID,
+/// so the confidence of the consensus peaks is visible in genome browsers.
+/// No scores are returned for aggregated scores.
+///
+/// # Parameters
+///
+/// * `contributions` - the contributing raw peaks and samples by chromosome and peak ID
+/// * `source` - the source of the scores
+/// * `samples` - the number of samples
+pub fn support_scores(
+    contributions: &HashMap<(Chromosome, usize), PeakContribution>,
+    source: ScoreSource,
+    samples: usize,
+) -> HashMap<usize, f64> {
+    let (support, maximum): (fn(&PeakContribution) -> usize, usize) = match source {
+        ScoreSource::Aggregated => return HashMap::new(),
+        ScoreSource::Peaks => (
+            PeakContribution::contributing_peaks,
+            contributions
+                .values()
+                .map(PeakContribution::contributing_peaks)
+                .max()
+                .unwrap_or_default(),
+        ),
+        ScoreSource::Samples => (|contribution| contribution.samples().len(), samples),
+    };
+    log::debug!(
+        target: LOG_TARGET,
+        "Scoring {} consensus peaks by their {} relative to {}.",
+        contributions.len(),
+        source,
+        maximum
+    );
+    contributions
+        .iter()
+        .map(|((_, id), contribution)| {
+            let score = if maximum == 0 {
+                0.0
+            } else {
+                (support(contribution) as f64 * MAX_BED_SCORE / maximum as f64).round()
+            };
+            (*id, score.min(MAX_BED_SCORE))
+        })
+        .collect()
+}
+
+/// The peaks of a single peak file together with their statistical values by peak ID.
+#[derive(Getters, Debug, Clone, Default, PartialEq)]
+#[getset(get = "pub")]
+pub struct ScoredPeaks {
+    /// The peaks.
+    peaks: PeakSet,
+    /// The statistical values by peak ID.
+    scores: HashMap<usize, PeakScores>,
+}
+
+impl ScoredPeaks {
+    /// Parses the peaks and their statistical values from a BED3+ / narrowPeak file.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - the path of the peak file
+    /// * `input_format` - the format of the peak file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or contains invalid records.
+    pub fn from_file<T: AsRef<Path>>(
+        path: T,
+        input_format: InputFormat,
+    ) -> Result<Self, ApplicationError> {
+        let path = path.as_ref();
+        let parsed = bed_to_peaks_with_format(&[path], input_format, None, false, true)?;
+        log::debug!(target: LOG_TARGET, "Parsed the peak scores of {}.", path.display());
+        Ok(Self {
+            peaks: PeakSet::from(parsed.peaks),
+            scores: parsed.scores,
+        })
+    }
+}
+
+/// Returns the scores (column 5) of the raw peaks by raw peak ID as peak weights.
+/// Raw peaks without a score are omitted.
+///
+/// # Parameters
+///
+/// * `raw_peak_scores` - the statistical values of the raw peaks by raw peak ID
+pub fn raw_peak_weights(raw_peak_scores: &HashMap<usize, PeakScores>) -> PeakWeights {
+    raw_peak_scores
+        .iter()
+        .filter_map(|(id, scores)| scores.score().map(|score| (*id, score)))
+        .collect()
+}
+
+/// Aggregates the statistical values of the raw peaks each consensus peak was created from
+/// by consensus peak ID.
+/// Consensus peaks without any contributing raw peak with statistical values are omitted.
+///
+/// # Parameters
+///
+/// * `provenance` - the provenance of the consensus peaks listing their raw peak IDs
+/// * `raw_peak_scores` - the statistical values of the raw peaks by raw peak ID
+/// * `aggregation` - the function aggregating the values
+pub fn consensus_peak_scores(
+    provenance: &ConsensusProvenance,
+    raw_peak_scores: &HashMap<usize, PeakScores>,
+    aggregation: ScoreAggregation,
+) -> HashMap<usize, PeakScores> {
+    let mut consensus_scores = HashMap::new();
+    for ((_, consensus_peak_id), peak_provenance) in provenance {
+        let contributing: Vec<PeakScores> = peak_provenance
+            .raw_peak_ids()
+            .iter()
+            .filter_map(|raw_peak_id| raw_peak_scores.get(raw_peak_id).copied())
+            .collect();
+        let aggregate = |value: fn(&PeakScores) -> Option<f64>| {
+            aggregation.aggregate(contributing.iter().filter_map(value).collect())
+        };
+        let scores = PeakScores::new(
+            aggregate(PeakScores::score),
+            aggregate(PeakScores::signal_value),
+            aggregate(PeakScores::p_value),
+            aggregate(PeakScores::q_value),
+        );
+        if !scores.is_empty() {
+            consensus_scores.insert(*consensus_peak_id, scores);
+        }
+    }
+    consensus_scores
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        peaks::{PeakData, TrackedConsensusPeak},
+        provenance::{SampleIndex, consensus_provenance},
+        test_utils::test_resources,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_support_scores() {
+        let chr1 = Chromosome::from("chr1");
+        let contributions = HashMap::from([
+            ((chr1, 0), PeakContribution::new(4, vec!["a".into(), "b".into()])),
+            ((chr1, 1), PeakContribution::new(1, vec!["b".into()])),
+            ((chr1, 2), PeakContribution::new(3, vec!["a".into(), "b".into(), "c".into()])),
+        ]);
+        assert!(support_scores(&contributions, ScoreSource::Aggregated, 3).is_empty());
+        assert_eq!(
+            support_scores(&contributions, ScoreSource::Peaks, 3),
+            HashMap::from([(0, 1000.0), (1, 250.0), (2, 750.0)])
+        );
+        assert_eq!(
+            support_scores(&contributions, ScoreSource::Samples, 3),
+            HashMap::from([(0, 667.0), (1, 333.0), (2, 1000.0)])
+        );
+        assert_eq!(
+            support_scores(&contributions, ScoreSource::Samples, 0),
+            HashMap::from([(0, 0.0), (1, 0.0), (2, 0.0)])
+        );
+        assert_eq!(
+            PeakScores::new(Some(1.0), Some(2.0), None, None).with_score(Some(5.0)),
+            PeakScores::new(Some(5.0), Some(2.0), None, None)
+        );
+    }
+
+    #[test]
+    fn test_peak_scores_from_fields() {
+        let fields = [
+            "chr1", "0", "10", "peak", "358", ".", "16.7", "-1", "35.8", "5",
+        ];
+        assert_eq!(
+            PeakScores::from_fields(&fields),
+            PeakScores::new(Some(358.0), Some(16.7), None, Some(35.8))
+        );
+        assert!(PeakScores::from_fields(&["chr1", "0", "10"]).is_empty());
+    }
+
+    #[test]
+    fn test_apply_to_record_line() {
+        let scores = PeakScores::new(Some(357.6), Some(16.74711), None, Some(2.0));
+        assert_eq!(
+            scores.apply_to_record_line("chr1\t0\t10\tconsensus_0\t0\t.\t0\t0\t0\t5\n"),
+            "chr1\t0\t10\tconsensus_0\t358\t.\t16.74711\t0\t2\t5\n"
+        );
+        assert_eq!(
+            scores.apply_to_record_line("chr1\t0\t10\tconsensus_0\t0\n"),
+            "chr1\t0\t10\tconsensus_0\t358\n"
+        );
+        assert_eq!(scores.apply_to_record_line(""), "");
+    }
+
+    #[test]
+    fn test_score_aggregation() {
+        let values = vec![4.0, 1.0, 3.0, 2.0];
+        assert_eq!(ScoreAggregation::Mean.aggregate(values.clone()), Some(2.5));
+        assert_eq!(ScoreAggregation::Median.aggregate(values.clone()), Some(2.5));
+        assert_eq!(ScoreAggregation::Median.aggregate(vec![3.0, 1.0, 2.0]), Some(2.0));
+        assert_eq!(ScoreAggregation::Max.aggregate(values), Some(4.0));
+        assert_eq!(ScoreAggregation::Max.aggregate(Vec::new()), None);
+    }
+
+    #[test]
+    fn test_raw_peak_weights() {
+        let paths = [
+            test_resources().join("input_test_main_internal_input_01.narrowPeak"),
+            test_resources().join("input_test_main_internal_input_02.narrowPeak"),
+        ];
+        let parsed =
+            bed_to_peaks_with_format(&paths, InputFormat::Auto, None, false, true).unwrap();
+        let second_sample = ScoredPeaks::from_file(&paths[1], InputFormat::Auto).unwrap();
+        let weights = raw_peak_weights(&parsed.scores);
+        assert_eq!(weights.len(), parsed.scores.len());
+        assert_eq!(weights.get(&0), parsed.scores[&0].score().as_ref());
+        // The raw peak IDs of the second file follow the peaks of the first file.
+        assert_eq!(
+            weights.get(&parsed.peaks_per_file[0]),
+            second_sample.scores()[&0].score().as_ref()
+        );
+    }
+
+    #[test]
+    fn test_consensus_peak_scores() {
+        let chr1 = Chromosome::from("chr1");
+        let raw_peak_scores = HashMap::from([
+            (0, PeakScores::new(Some(358.0), Some(16.7471), Some(38.4652), Some(35.8377))),
+            (1, PeakScores::new(Some(100.0), None, Some(50.0), None)),
+            (2, PeakScores::new(None, None, None, None)),
+        ]);
+        let consensus = HashMap::from([(
+            chr1,
+            vec![
+                // The summit of raw peak 1 does not need to be located within the consensus peak.
+                TrackedConsensusPeak::new(
+                    PeakData::new(0, 650u64, 750u64, 700u64).unwrap(),
+                    vec![0, 1],
+                ),
+                TrackedConsensusPeak::new(
+                    PeakData::new(1, 5000u64, 5100u64, 5050u64).unwrap(),
+                    vec![2],
+                ),
+            ],
+        )]);
+        let provenance = consensus_provenance(&consensus, &SampleIndex::new(&[3]));
+        let scores = consensus_peak_scores(&provenance, &raw_peak_scores, ScoreAggregation::Max);
+        assert_eq!(scores.len(), 1);
+        assert_eq!(
+            scores[&0],
+            PeakScores::new(Some(358.0), Some(16.7471), Some(50.0), Some(35.8377))
+        );
+        let scores = consensus_peak_scores(&provenance, &raw_peak_scores, ScoreAggregation::Mean);
+        assert_eq!(scores[&0].score(), Some(229.0));
+    }
+}
@@ -0,0 +1,480 @@
+//! This module provides a minimal HTTP server for querying a set of consensus peaks,
+//! for example from genome browser plugins or dashboards.
+//!
+//! The server answers `GET` requests with JSON encoded peaks:
+//!
+//! * `/overlap?chrom=<chromosome>&start=<start>&end=<end>` returns all peaks overlapping the
+//!   region, where the end coordinate is inclusive as for [`PeakData`]
+//! * `/peak/<id>` returns the peak with the specified identifier
+//!
+//! Each peak is encoded as object with the fields `chromosome`, `id`, `start`, `end` and `summit`.
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::{
+    chromosome::Chromosome,
+    error::{ApplicationError, ApplicationErrorType},
+    peak_set::PeakSet,
+    peaks::PeakData,
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "server";
+
+/// The maximum length of a request line or header line in bytes.
+const MAX_LINE_LENGTH: u64 = 8192;
+
+/// The maximum number of header lines of a request.
+const MAX_HEADER_LINES: usize = 100;
+
+/// The maximum number of connections handled concurrently.
+/// Further connections wait until a connection has been handled.
+const MAX_CONNECTIONS: usize = 64;
+
+/// The time after which reading a request or writing a response is aborted,
+/// so idle clients cannot occupy a connection indefinitely.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A peak including its chromosome as returned by the server.
+#[derive(Serialize)]
+struct PeakRecord<'a> {
+    chromosome: Chromosome,
+    #[serde(flatten)]
+    peak: &'a PeakData,
+}
+
+/// The response to a single request.
+#[derive(PartialEq, Eq, Debug)]
+pub struct Response {
+    /// The HTTP status code.
+    pub status: u16,
+    /// The JSON encoded response body.
+    pub body: String,
+}
+
+impl Response {
+    /// Creates a successful response containing the specified value.
+    ///
+    /// # Parameters
+    ///
+    /// * `value` - the value to serialise as response body
+    fn ok<T: Serialize>(value: &T) -> Self {
+        match serde_json::to_string(value) {
+            Ok(body) => Self { status: 200, body },
+            Err(err) => Self::error(500, err),
+        }
+    }
+
+    /// Creates an error response.
+    ///
+    /// # Parameters
+    ///
+    /// * `status` - the HTTP status code
+    /// * `message` - the error message
+    fn error<T: ToString>(status: u16, message: T) -> Self {
+        Self {
+            status,
+            body: serde_json::json!({ "error": message.to_string() }).to_string(),
+        }
+    }
+
+    /// Returns the reason phrase of the status code.
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            431 => "Request Header Fields Too Large",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+/// A server answering queries about a set of peaks.
+pub struct PeakServer {
+    /// The peaks to query.
+    peaks: PeakSet,
+    /// The peaks indexed by their identifier.
+    peaks_by_id: HashMap<usize, (Chromosome, PeakData)>,
+}
+
+impl PeakServer {
+    /// Creates a new server for the specified peaks.
+    ///
+    /// # Parameters
+    ///
+    /// * `peaks` - the peaks to query
+    pub fn new(peaks: PeakSet) -> Self {
+        let peaks_by_id = peaks
+            .iter()
+            .map(|(chromosome, peak)| (peak.id(), (chromosome, *peak)))
+            .collect();
+        Self { peaks, peaks_by_id }
+    }
+
+    /// Binds the server to the specified address and answers requests until the process is stopped.
+    /// Up to 64 connections are handled concurrently on separate threads.
+    ///
+    /// # Parameters
+    ///
+    /// * `address` - the address to listen on
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the address cannot be bound.
+    pub fn serve<A: ToSocketAddrs>(&self, address: A) -> Result<(), ApplicationError> {
+        let listener = TcpListener::bind(address).map_err(|err| {
+            ApplicationError::from(err).chain("The server address could not be bound.")
+        })?;
+        self.serve_listener(listener)
+    }
+
+    /// Answers requests received by the specified listener until the process is stopped.
+    ///
+    /// # Parameters
+    ///
+    /// * `listener` - the bound listener
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the local address of the listener cannot be determined.
+    pub fn serve_listener(&self, listener: TcpListener) -> Result<(), ApplicationError> {
+        log::info!(
+            target: LOG_TARGET,
+            "Serving {} peaks on http://{}.",
+            self.peaks.len(),
+            listener.local_addr()?
+        );
+        // A fixed number of workers accepts connections, so a flood of connections cannot
+        // exhaust the threads of the process.
+        std::thread::scope(|scope| {
+            for _ in 0..MAX_CONNECTIONS {
+                scope.spawn(|| {
+                    for stream in listener.incoming() {
+                        match stream {
+                            Ok(stream) => {
+                                if let Err(err) = self.handle_connection(stream) {
+                                    log::warn!(
+                                        target: LOG_TARGET,
+                                        "A connection could not be handled: {}",
+                                        err
+                                    );
+                                }
+                            },
+                            Err(err) => {
+                                log::warn!(
+                                    target: LOG_TARGET,
+                                    "A connection could not be accepted: {}",
+                                    err
+                                )
+                            },
+                        }
+                    }
+                });
+            }
+        });
+        Ok(())
+    }
+
+    /// Reads a single request from the connection and writes the response.
+    ///
+    /// # Parameters
+    ///
+    /// * `stream` - the connection to the client
+    fn handle_connection(&self, stream: TcpStream) -> Result<(), ApplicationError> {
+        stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+        stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+        let mut reader = BufReader::new(&stream);
+        let mut request_line = String::new();
+        (&mut reader)
+            .take(MAX_LINE_LENGTH)
+            .read_line(&mut request_line)?;
+        // Skips the headers as no request body is expected.
+        let mut header_line = String::new();
+        let mut header_lines = 0;
+        while header_lines <= MAX_HEADER_LINES {
+            header_line.clear();
+            let read = (&mut reader)
+                .take(MAX_LINE_LENGTH)
+                .read_line(&mut header_line)?;
+            if read == 0 || header_line.trim_end().is_empty() {
+                break;
+            }
+            header_lines += 1;
+        }
+        let mut fields = request_line.split_whitespace();
+        let response = match (fields.next(), fields.next()) {
+            _ if header_lines > MAX_HEADER_LINES => {
+                Response::error(431, "The request contains too many header lines.")
+            },
+            (Some(method), Some(target)) => self.handle_request(method, target),
+            _ => Response::error(400, "The request line is malformed."),
+        };
+        log::debug!(target: LOG_TARGET, "{} -> {}", request_line.trim_end(), response.status);
+        let mut stream = &stream;
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+             Access-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}",
+            response.status,
+            response.reason(),
+            response.body.len(),
+            response.body
+        )?;
+        stream.flush()?;
+        Ok(())
+    }
+
+    /// Answers a single request.
+    ///
+    /// # Parameters
+    ///
+    /// * `method` - the HTTP method of the request
+    /// * `target` - the request target consisting of path and query
+    pub fn handle_request(&self, method: &str, target: &str) -> Response {
+        if method != "GET" {
+            return Response::error(405, format!("The method {} is not supported.", method));
+        }
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        if path == "/overlap" {
+            self.overlap(query)
+        } else if let Some(id) = path.strip_prefix("/peak/") {
+            self.peak(id)
+        } else {
+            Response::error(404, format!("The path {} does not exist.", path))
+        }
+    }
+
+    /// Answers an overlap query.
+    ///
+    /// # Parameters
+    ///
+    /// * `query` - the query string of the request
+    fn overlap(&self, query: &str) -> Response {
+        let parameters = match parse_query(query) {
+            Ok(parameters) => parameters,
+            Err(err) => return Response::error(400, err),
+        };
+        let (Some(chromosome), Some(start), Some(end)) =
+            (parameters.get("chrom"), parameters.get("start"), parameters.get("end"))
+        else {
+            return Response::error(400, "The parameters chrom, start and end are required.");
+        };
+        let (Ok(start), Ok(end)) = (start.parse::<u64>(), end.parse::<u64>()) else {
+            return Response::error(400, "The parameters start and end must be positive integers.");
+        };
+        if start > end {
+            return Response::error(400, "The start must not be greater than the end.");
+        }
+        // Unknown chromosomes cannot contain peaks and are not interned to avoid growing
+        // the chromosome arena with arbitrary query input.
+        let records: Vec<PeakRecord> = Chromosome::lookup(chromosome)
+            .map(|chromosome| {
+                self.peaks
+                    .overlapping(chromosome, start, end)
+                    .into_iter()
+                    .map(|peak| PeakRecord { chromosome, peak })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Response::ok(&records)
+    }
+
+    /// Answers a peak query.
+    ///
+    /// # Parameters
+    ///
+    /// * `id` - the requested peak identifier
+    fn peak(&self, id: &str) -> Response {
+        let Ok(id) = id.parse::<usize>() else {
+            return Response::error(400, format!("The peak identifier {} is invalid.", id));
+        };
+        match self.peaks_by_id.get(&id) {
+            Some((chromosome, peak)) => Response::ok(&PeakRecord {
+                chromosome: *chromosome,
+                peak,
+            }),
+            None => Response::error(404, format!("The peak {} does not exist.", id)),
+        }
+    }
+}
+
+/// Parses the parameters of a query string.
+///
+/// # Parameters
+///
+/// * `query` - the query string without the leading `?`
+fn parse_query(query: &str) -> Result<HashMap<String, String>, ApplicationError> {
+    query
+        .split('&')
+        .filter(|parameter| !parameter.is_empty())
+        .map(|parameter| {
+            let (key, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+            Ok((percent_decode(key)?, percent_decode(value)?))
+        })
+        .collect()
+}
+
+/// Decodes a percent encoded query component.
+///
+/// # Parameters
+///
+/// * `component` - the encoded component
+fn percent_decode(component: &str) -> Result<String, ApplicationError> {
+    let invalid_encoding = || {
+        ApplicationError::new(
+            ApplicationErrorType::ParseError,
+            format!("The query component \"{}\" is not correctly percent encoded.", component),
+        )
+    };
+    let mut decoded = Vec::with_capacity(component.len());
+    let mut bytes = component.bytes();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'%' => {
+                let hex = [bytes.next(), bytes.next()];
+                let [Some(high), Some(low)] = hex else {
+                    return Err(invalid_encoding());
+                };
+                let hex = std::str::from_utf8(&[high, low])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(invalid_encoding)?;
+                decoded.push(hex);
+            },
+            b'+' => decoded.push(b' '),
+            _ => decoded.push(byte),
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| invalid_encoding())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a server for test peaks on a chromosome unique to the test.
+    fn test_server(chromosome: &str) -> PeakServer {
+        let chromosome = Chromosome::from(chromosome);
+        PeakServer::new(
+            [
+                (chromosome, PeakData::new(0, 100u64, 199u64, 150u64).unwrap()),
+                (chromosome, PeakData::new(1, 300u64, 399u64, 350u64).unwrap()),
+                (chromosome, PeakData::new(2, 350u64, 449u64, 400u64).unwrap()),
+            ]
+            .into_iter()
+            .collect(),
+        )
+    }
+
+    #[test]
+    fn test_handle_request_overlap() {
+        let server = test_server("test_server_overlap");
+        let response =
+            server.handle_request("GET", "/overlap?chrom=test_server_overlap&start=199&end=300");
+        assert_eq!(response.status, 200);
+        let peaks: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(peaks.as_array().unwrap().len(), 2);
+        assert_eq!(peaks[0]["chromosome"], "test_server_overlap");
+        assert_eq!(peaks[0]["id"], 0);
+        assert_eq!(peaks[1]["start"], 300);
+        assert_eq!(
+            server.handle_request("GET", "/overlap?chrom=test_server_unknown&start=0&end=1000"),
+            Response {
+                status: 200,
+                body: "[]".to_string()
+            }
+        );
+        assert_eq!(Chromosome::lookup("test_server_unknown"), None);
+    }
+
+    #[test]
+    fn test_handle_request_peak() {
+        let server = test_server("test_server_peak");
+        let response = server.handle_request("GET", "/peak/2");
+        assert_eq!(response.status, 200);
+        let peak: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(peak["chromosome"], "test_server_peak");
+        assert_eq!(peak["end"], 449);
+        assert_eq!(peak["summit"], 400);
+        assert_eq!(server.handle_request("GET", "/peak/3").status, 404);
+        assert_eq!(server.handle_request("GET", "/peak/abc").status, 400);
+    }
+
+    #[test]
+    fn test_handle_request_invalid() {
+        let server = test_server("test_server_invalid");
+        assert_eq!(server.handle_request("POST", "/peak/0").status, 405);
+        assert_eq!(server.handle_request("GET", "/unknown").status, 404);
+        assert_eq!(
+            server
+                .handle_request("GET", "/overlap?chrom=chr1&start=10")
+                .status,
+            400
+        );
+        assert_eq!(
+            server
+                .handle_request("GET", "/overlap?chrom=chr1&start=-1&end=10")
+                .status,
+            400
+        );
+        assert_eq!(
+            server
+                .handle_request("GET", "/overlap?chrom=chr1&start=20&end=10")
+                .status,
+            400
+        );
+        assert_eq!(
+            server
+                .handle_request("GET", "/overlap?chrom=%zz&start=1&end=10")
+                .status,
+            400
+        );
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("chr1").unwrap(), "chr1");
+        assert_eq!(percent_decode("chrUn%5Fgl000220v1").unwrap(), "chrUn_gl000220v1");
+        assert_eq!(percent_decode("a+b%20c").unwrap(), "a b c");
+        assert!(percent_decode("%2").is_err());
+        assert!(percent_decode("%ff").is_err());
+    }
+
+    #[test]
+    fn test_serve_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || test_server("test_server_listener").serve_listener(listener));
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .write_all(b"GET /peak/1 HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("Access-Control-Allow-Origin: *\r\n"));
+        assert!(response.ends_with(
+            "{\"chromosome\":\"test_server_listener\",\"id\":1,\"start\":300,\"end\":399,\"summit\":350}"
+        ));
+        // Requests with excessive headers are rejected.
+        let mut stream = TcpStream::connect(address).unwrap();
+        let mut request = String::from("GET /peak/1 HTTP/1.1\r\n");
+        for header in 0..=MAX_HEADER_LINES {
+            request.push_str(&format!("X-Header-{}: value\r\n", header));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"));
+    }
+}
@@ -0,0 +1,416 @@
+//! This module extracts the signal of BigWig coverage tracks for consensus peaks.
+
+use std::{collections::HashMap, path::Path};
+
+use bigtools::{BigWigRead, utils::reopen::ReopenableFile};
+use getset::CopyGetters;
+
+use crate::{
+    chromosome::Chromosome,
+    error::{ApplicationError, ApplicationErrorType},
+    output::{OutputFormat, RecordFields, write_bed_file},
+    peaks::PeakData,
+};
+
+/// The BED field index of the score.
+const SCORE_FIELD_INDEX: usize = 4;
+/// The narrowPeak field index of the signal value.
+const SIGNAL_VALUE_FIELD_INDEX: usize = 6;
+
+/// The signal of a coverage track within a single peak region.
+#[derive(CopyGetters, Clone, Copy, PartialEq, Debug)]
+pub struct PeakSignal {
+    /// The mean signal over all bases of the peak region.
+    #[getset(get_copy = "pub")]
+    mean: f64,
+    /// The maximum signal within the peak region.
+    #[getset(get_copy = "pub")]
+    max: f64,
+}
+
+impl PeakSignal {
+    /// Summarises per-base signal values.
+    ///
+    /// # Parameters
+    ///
+    /// * `values` - the per-base signal values
+    fn from_values(values: &[f64]) -> Self {
+        if values.is_empty() {
+            return Self {
+                mean: 0.0,
+                max: 0.0,
+            };
+        }
+        Self {
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            max: values.iter().copied().fold(f64::MIN, f64::max),
+        }
+    }
+}
+
+/// A BigWig coverage track.
+pub struct SignalTrack {
+    reader: BigWigRead<ReopenableFile>,
+    /// The length of each chromosome contained in the track.
+    chromosome_lengths: HashMap<String, u32>,
+}
+
+impl SignalTrack {
+    /// Opens the BigWig file at the specified path.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - the path to the BigWig file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or is not a valid BigWig file.
+    pub fn open<T: AsRef<Path>>(path: T) -> Result<Self, ApplicationError> {
+        let reader = BigWigRead::open_file(path.as_ref()).map_err(|err| {
+            ApplicationError::from_source(ApplicationErrorType::IOError, err).chain(format!(
+                "The BigWig file \"{}\" could not be opened.",
+                path.as_ref().display()
+            ))
+        })?;
+        let chromosome_lengths = reader
+            .chroms()
+            .iter()
+            .map(|chromosome| (chromosome.name.clone(), chromosome.length))
+            .collect();
+        Ok(Self {
+            reader,
+            chromosome_lengths,
+        })
+    }
+
+    /// Returns the per-base signal of the specified peak region.
+    /// Bases without data and chromosomes not contained in the track are
+    /// considered to have a signal of `0`.
+    ///
+    /// # Parameters
+    ///
+    /// * `chromosome` - the chromosome of the peak
+    /// * `peak` - the peak region
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the signal cannot be read.
+    pub fn values(
+        &mut self,
+        chromosome: &Chromosome,
+        peak: &PeakData,
+    ) -> Result<Vec<f64>, ApplicationError> {
+        let mut values = vec![0.0; peak.length() as usize];
+        let Some(chromosome_length) = self.chromosome_lengths.get(chromosome.name()) else {
+            return Ok(values);
+        };
+        let chromosome_length = u64::from(*chromosome_length);
+        if peak.start() >= chromosome_length {
+            return Ok(values);
+        }
+        // Converts the inclusive peak end to the exclusive BigWig end.
+        let end = (peak.end() + 1).min(chromosome_length);
+        let track_values = self
+            .reader
+            .values(chromosome.name(), peak.start() as u32, end as u32)
+            .map_err(|err| {
+                ApplicationError::from_source(ApplicationErrorType::IOError, err).chain(format!(
+                    "The signal of peak {} on chromosome {} could not be read.",
+                    peak.id(),
+                    chromosome
+                ))
+            })?;
+        for (value, track_value) in values.iter_mut().zip(track_values) {
+            if !track_value.is_nan() {
+                *value = f64::from(track_value);
+            }
+        }
+        Ok(values)
+    }
+
+    /// Returns the signal summary of the specified peak region.
+    ///
+    /// # Parameters
+    ///
+    /// * `chromosome` - the chromosome of the peak
+    /// * `peak` - the peak region
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the signal cannot be read.
+    pub fn peak_signal(
+        &mut self,
+        chromosome: &Chromosome,
+        peak: &PeakData,
+    ) -> Result<PeakSignal, ApplicationError> {
+        Ok(PeakSignal::from_values(&self.values(chromosome, peak)?))
+    }
+}
+
+/// Moves the summit of each peak to the position with the highest signal summed over all tracks.
+/// If multiple positions share the highest signal the first one is used.
+/// Peaks without any signal keep their summit.
+///
+/// # Parameters
+///
+/// * `tracks` - the coverage tracks
+/// * `peaks` - the peaks grouped by chromosome
+///
+/// # Errors
+///
+/// Returns an error if the signal cannot be read.
+pub fn refine_summits(
+    tracks: &mut [SignalTrack],
+    peaks: HashMap<Chromosome, Vec<PeakData>>,
+) -> Result<HashMap<Chromosome, Vec<PeakData>>, ApplicationError> {
+    let mut refined_peaks = HashMap::with_capacity(peaks.len());
+    for (chromosome, chromosome_peaks) in peaks {
+        let mut refined_chromosome_peaks = Vec::with_capacity(chromosome_peaks.len());
+        for peak in chromosome_peaks {
+            let mut summed_values = vec![0.0; peak.length() as usize];
+            for track in tracks.iter_mut() {
+                for (sum, value) in summed_values
+                    .iter_mut()
+                    .zip(track.values(&chromosome, &peak)?)
+                {
+                    *sum += value;
+                }
+            }
+            let mut max_offset = None;
+            let mut max_value = 0.0;
+            for (offset, value) in summed_values.into_iter().enumerate() {
+                if value > max_value {
+                    max_offset = Some(offset);
+                    max_value = value;
+                }
+            }
+            refined_chromosome_peaks.push(match max_offset {
+                Some(offset) => PeakData::new(
+                    peak.id(),
+                    peak.start(),
+                    peak.end(),
+                    peak.start() + offset as u64,
+                )?,
+                None => peak,
+            });
+        }
+        refined_peaks.insert(chromosome, refined_chromosome_peaks);
+    }
+    Ok(refined_peaks)
+}
+
+/// Returns the signal of each peak in each track by peak ID.
+///
+/// # Parameters
+///
+/// * `tracks` - the coverage tracks
+/// * `peaks` - the peaks grouped by chromosome
+///
+/// # Errors
+///
+/// Returns an error if the signal cannot be read.
+pub fn peak_signals(
+    tracks: &mut [SignalTrack],
+    peaks: &HashMap<Chromosome, Vec<PeakData>>,
+) -> Result<HashMap<usize, Vec<PeakSignal>>, ApplicationError> {
+    let mut signals = HashMap::new();
+    for (chromosome, chromosome_peaks) in peaks {
+        for peak in chromosome_peaks {
+            let peak_signals = tracks
+                .iter_mut()
+                .map(|track| track.peak_signal(chromosome, peak))
+                .collect::<Result<Vec<PeakSignal>, ApplicationError>>()?;
+            signals.insert(peak.id(), peak_signals);
+        }
+    }
+    Ok(signals)
+}
+
+/// Writes all peaks annotated with their signal to the specified BED file.
+/// If present, the score field (5) is set to the mean signal averaged over all tracks scaled
+/// to a range of 0 to 1000 and the signal value field (7) is set to the unscaled mean signal.
+/// The mean and maximum signal of each track are appended as additional columns
+/// followed by the further additional columns of each peak.
+///
+/// # Parameters
+///
+/// * `path`- the path of the output file
+/// * `peaks` - all peaks sorted by chromosome
+/// * `fields`- the number of standard fields / columns to generate
+/// * `signals` - the signal of each peak in each track by peak ID
+/// * `columns` - the further additional columns by peak ID
+/// * `record_fields` - the values replacing the default standard fields
+///
+/// # Errors
+///
+/// Returns an error if the output file could not be written.
+pub fn write_peaks_to_bed_with_signal<T: AsRef<Path>>(
+    path: T,
+    peaks: &HashMap<Chromosome, Vec<PeakData>>,
+    fields: usize,
+    signals: &HashMap<usize, Vec<PeakSignal>>,
+    columns: &HashMap<usize, Vec<String>>,
+    record_fields: &RecordFields,
+) -> Result<(), ApplicationError> {
+    let mean_signal = |peak_id: usize| -> f64 {
+        signals
+            .get(&peak_id)
+            .filter(|peak_signals| !peak_signals.is_empty())
+            .map(|peak_signals| {
+                peak_signals.iter().map(PeakSignal::mean).sum::<f64>() / peak_signals.len() as f64
+            })
+            .unwrap_or(0.0)
+    };
+    let highest_mean_signal = peaks
+        .values()
+        .flatten()
+        .map(|peak| mean_signal(peak.id()))
+        .fold(0.0, f64::max);
+    // The signal value field is not part of BED12 records.
+    let is_bed12 = record_fields.output_format() == Some(OutputFormat::Bed12);
+    write_bed_file(path, peaks, |peak, chromosome| {
+        let record_line = record_fields.record_line(peak, chromosome.name(), fields);
+        if record_line.is_empty() {
+            return record_line;
+        }
+        let mut record_fields: Vec<String> = record_line
+            .trim_end_matches('\n')
+            .split('\t')
+            .map(str::to_string)
+            .collect();
+        let peak_mean_signal = mean_signal(peak.id());
+        if let Some(score) = record_fields.get_mut(SCORE_FIELD_INDEX) {
+            *score = if highest_mean_signal > 0.0 {
+                ((peak_mean_signal / highest_mean_signal) * 1000.0)
+                    .round()
+                    .to_string()
+            } else {
+                "0".to_string()
+            };
+        }
+        if let Some(signal_value) = record_fields
+            .get_mut(SIGNAL_VALUE_FIELD_INDEX)
+            .filter(|_| !is_bed12)
+        {
+            *signal_value = format!("{:.6}", peak_mean_signal);
+        }
+        for peak_signal in signals.get(&peak.id()).into_iter().flatten() {
+            record_fields.push(format!("{:.6}", peak_signal.mean()));
+            record_fields.push(format!("{:.6}", peak_signal.max()));
+        }
+        record_fields.extend(columns.get(&peak.id()).into_iter().flatten().cloned());
+        let mut record = record_fields.join("\t");
+        record.push('\n');
+        record
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::{test_output, test_resources};
+
+    use super::*;
+
+    fn signal_track() -> SignalTrack {
+        SignalTrack::open(test_resources().join("input_test_signal.bw")).unwrap()
+    }
+
+    #[test]
+    fn test_signal_track_peak_signal() {
+        let mut track = signal_track();
+        let chr1 = Chromosome::from("chr1");
+        // Signal: [100, 150) = 1, [150, 160) = 5, [160, 200) = 2
+        let peak = PeakData::new(0, 140u64, 169u64, 145u64).unwrap();
+        let signal = track.peak_signal(&chr1, &peak).unwrap();
+        assert_eq!(signal.max(), 5.0);
+        assert_eq!(signal.mean(), (10.0 * 1.0 + 10.0 * 5.0 + 10.0 * 2.0) / 30.0);
+        // Bases without data count as zero signal.
+        let peak = PeakData::new(1, 90u64, 109u64, 100u64).unwrap();
+        assert_eq!(track.peak_signal(&chr1, &peak).unwrap().mean(), 0.5);
+        // Unknown chromosomes and regions beyond the chromosome end do not have any signal.
+        let peak = PeakData::new(2, 100u64, 109u64, 100u64).unwrap();
+        let no_signal = PeakSignal {
+            mean: 0.0,
+            max: 0.0,
+        };
+        assert_eq!(
+            track
+                .peak_signal(&Chromosome::from("test_signal_unknown"), &peak)
+                .unwrap(),
+            no_signal
+        );
+        let peak = PeakData::new(3, 20000u64, 20010u64, 20005u64).unwrap();
+        assert_eq!(track.peak_signal(&chr1, &peak).unwrap(), no_signal);
+    }
+
+    #[test]
+    fn test_signal_track_open_invalid() {
+        assert!(
+            SignalTrack::open(test_resources().join("input_test_valid_minimal.narrowPeak"))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_refine_summits() {
+        let peaks = HashMap::from([
+            (
+                Chromosome::from("chr1"),
+                vec![
+                    PeakData::new(0, 100u64, 199u64, 110u64).unwrap(),
+                    PeakData::new(1, 5000u64, 5100u64, 5010u64).unwrap(),
+                ],
+            ),
+            (Chromosome::from("chr2"), vec![PeakData::new(2, 0u64, 49u64, 40u64).unwrap()]),
+        ]);
+        let refined = refine_summits(&mut [signal_track(), signal_track()], peaks).unwrap();
+        assert_eq!(refined[&Chromosome::from("chr1")][0].summit(), 150);
+        // Peaks without signal keep their summit.
+        assert_eq!(refined[&Chromosome::from("chr1")][1].summit(), 5010);
+        // Ties are resolved by using the first position.
+        assert_eq!(refined[&Chromosome::from("chr2")][0].summit(), 0);
+    }
+
+    #[test]
+    fn test_write_peaks_to_bed_with_signal() {
+        let output_path = test_output().join("test_write_peaks_to_bed_with_signal.bed");
+        let peaks = HashMap::from([(
+            Chromosome::from("chr1"),
+            vec![
+                PeakData::new(0, 150u64, 159u64, 155u64).unwrap(),
+                PeakData::new(1, 1000u64, 1099u64, 1050u64).unwrap(),
+            ],
+        )]);
+        let mut tracks = [signal_track()];
+        let signals = peak_signals(&mut tracks, &peaks).unwrap();
+        write_peaks_to_bed_with_signal(
+            &output_path,
+            &peaks,
+            10,
+            &signals,
+            &HashMap::new(),
+            &RecordFields::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output_path).unwrap(),
+            "chr1\t150\t159\tconsensus_0\t1000\t.\t5.000000\t0\t0\t5\t5.000000\t5.000000\n\
+            chr1\t1000\t1099\tconsensus_1\t600\t.\t3.000000\t0\t0\t50\t3.000000\t3.000000\n"
+        );
+        let columns = HashMap::from([(1, vec!["PLS".to_string()])]);
+        write_peaks_to_bed_with_signal(
+            &output_path,
+            &peaks,
+            3,
+            &signals,
+            &columns,
+            &RecordFields::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output_path).unwrap(),
+            "chr1\t150\t159\t5.000000\t5.000000\nchr1\t1000\t1099\t3.000000\t3.000000\tPLS\n"
+        );
+        std::fs::remove_file(output_path).unwrap();
+    }
+}
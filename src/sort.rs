@@ -0,0 +1,338 @@
+//! This module coordinate-sorts existing peak files, so they can be compressed with `bgzip` and
+//! indexed with `tabix`. Chromosome names can be normalised to the names of a chromosome sizes
+//! file or a [UCSC chromAlias](https://genome.ucsc.edu/goldenPath/help/chromAlias.html) table
+//! and duplicate records can be removed.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::Path,
+};
+
+use getset::{CopyGetters, Getters};
+
+use crate::{
+    chromosome::Chromosome,
+    error::{ApplicationError, ApplicationErrorType, ErrorContext},
+    input::{bed_to_peaks, read_decompressed_to_string},
+    peaks::PeakData,
+    retry::retry,
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "sort";
+
+/// Maps alternative chromosome names, such as Ensembl names, to canonical chromosome names.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChromosomeNormaliser {
+    /// The canonical name by alias.
+    aliases: HashMap<String, String>,
+    /// The canonical chromosome names.
+    canonical: HashSet<String>,
+}
+
+impl ChromosomeNormaliser {
+    /// Creates a normaliser retaining all chromosome names.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses the chromosomes of a chromosome sizes file as canonical names.
+    /// Names only differing by a `chr` prefix, such as `1` and `chr1` or `MT` and `chrM`,
+    /// are mapped to the canonical name.
+    ///
+    /// # Parameters
+    ///
+    /// * `chromosome_sizes` - the chromosome sizes
+    pub fn chromosome_sizes(mut self, chromosome_sizes: &BTreeMap<Chromosome, u64>) -> Self {
+        self.canonical.extend(
+            chromosome_sizes
+                .keys()
+                .map(|chromosome| chromosome.name().to_string()),
+        );
+        self
+    }
+
+    /// Maps the specified aliases to their canonical names.
+    ///
+    /// # Parameters
+    ///
+    /// * `aliases` - the canonical name by alias
+    pub fn aliases(mut self, aliases: HashMap<String, String>) -> Self {
+        self.canonical.extend(aliases.values().cloned());
+        self.aliases.extend(aliases);
+        self
+    }
+
+    /// Returns the canonical name of a chromosome or `None` if the name is unknown.
+    /// If no canonical names have been specified, all names are returned unchanged.
+    ///
+    /// # Parameters
+    ///
+    /// * `name` - the chromosome name to normalise
+    pub fn normalise<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+        if self.canonical.is_empty() || self.canonical.contains(name) {
+            return Some(name);
+        }
+        if let Some(canonical) = self.aliases.get(name) {
+            return Some(canonical);
+        }
+        let candidates = match name.strip_prefix("chr") {
+            Some("M") => vec!["MT".to_string()],
+            Some(unprefixed) => vec![unprefixed.to_string()],
+            None if name == "MT" => vec!["chrM".to_string(), "chrMT".to_string()],
+            None => vec![format!("chr{}", name)],
+        };
+        candidates
+            .into_iter()
+            .find_map(|candidate| self.canonical.get(&candidate))
+            .map(String::as_str)
+    }
+}
+
+/// Parses a [UCSC chromAlias](https://genome.ucsc.edu/goldenPath/help/chromAlias.html) table
+/// containing the canonical chromosome name in the first column and
+/// any number of alternative names in the following tab separated columns.
+/// Blank lines and lines starting with `#` are skipped.
+///
+/// # Parameters
+///
+/// * `path` - the path of the alias table
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or an alias is mapped to different names.
+pub fn chromosome_aliases_from_file<T: AsRef<Path>>(
+    path: T,
+) -> Result<HashMap<String, String>, ApplicationError> {
+    let path = path.as_ref();
+    log::info!(target: LOG_TARGET, "Parsing chromosome aliases from {}.", path.display());
+    let content = read_to_string(path, "chromosome alias file")?;
+    let mut aliases = HashMap::new();
+    for (line_index, line) in content.lines().enumerate() {
+        let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        for alias in fields.iter().filter(|alias| !alias.is_empty()) {
+            if let Some(previous) = aliases.insert(alias.to_string(), fields[0].to_string())
+                && previous != fields[0]
+            {
+                return Err(ApplicationError::new(
+                    ApplicationErrorType::ValidationError,
+                    format!(
+                        "The alias \"{}\" is mapped to the chromosomes \"{}\" and \"{}\".",
+                        alias, previous, fields[0]
+                    ),
+                )
+                .with_context(
+                    ErrorContext::new()
+                        .with_path(path.display())
+                        .with_line(line_index as u64 + 1),
+                ));
+            }
+        }
+    }
+    Ok(aliases)
+}
+
+/// The records of a sorted peak file.
+#[derive(Getters, CopyGetters, Debug, Clone, PartialEq, Eq)]
+pub struct SortedPeakFile {
+    /// The comment lines followed by the sorted records.
+    #[getset(get = "pub")]
+    content: String,
+    /// The number of written records.
+    #[getset(get_copy = "pub")]
+    records: usize,
+    /// The number of removed duplicate records.
+    #[getset(get_copy = "pub")]
+    duplicates: usize,
+    /// The chromosome names that could not be normalised and were written unchanged.
+    #[getset(get = "pub")]
+    unknown_chromosomes: Vec<String>,
+}
+
+/// Sorts the records of a BED3+ / narrowPeak file by chromosome name, start and end coordinate.
+/// Records are parsed and validated like input files of the consensus peak generation and
+/// written unchanged apart from the normalised chromosome name. Comment lines are moved to the
+/// start of the file and blank lines are removed.
+///
+/// # Parameters
+///
+/// * `path` - the path of the peak file
+/// * `normaliser` - the normaliser of the chromosome names
+/// * `deduplicate` - removes all but the first record with identical chromosome, start and end
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or contains invalid records.
+pub fn sort_peak_file<T: AsRef<Path>>(
+    path: T,
+    normaliser: &ChromosomeNormaliser,
+    deduplicate: bool,
+) -> Result<SortedPeakFile, ApplicationError> {
+    let path = path.as_ref();
+    let mut peaks: Vec<PeakData> = bed_to_peaks(&[path])?.into_values().flatten().collect();
+    // Peak identifiers are assigned in the order of the records.
+    peaks.sort_unstable_by_key(PeakData::id);
+    let content = read_to_string(path, "peak file")?;
+    let mut comments = String::new();
+    let mut records = Vec::new();
+    let mut unknown_chromosomes = Vec::new();
+    let mut peaks = peaks.iter();
+    for line in content.lines() {
+        let record = line.trim_start();
+        let Some(name) = record
+            .split([' ', '\t'])
+            .next()
+            .filter(|name| !name.is_empty())
+        else {
+            continue;
+        };
+        if name.starts_with('#') {
+            comments.push_str(line);
+            comments.push('\n');
+            continue;
+        }
+        let peak = peaks
+            .next()
+            .expect("Each record line must have been parsed as peak.");
+        let chromosome = normaliser.normalise(name).unwrap_or_else(|| {
+            if !unknown_chromosomes.iter().any(|unknown| unknown == name) {
+                unknown_chromosomes.push(name.to_string());
+            }
+            name
+        });
+        records.push((chromosome, peak.start(), peak.end(), &record[name.len()..]));
+    }
+    // The stable sort keeps the original order of identical coordinates.
+    records.sort_by_key(|(chromosome, start, end, _)| (*chromosome, *start, *end));
+    let record_count = records.len();
+    if deduplicate {
+        records.dedup_by_key(|(chromosome, start, end, _)| (*chromosome, *start, *end));
+    }
+    for unknown in &unknown_chromosomes {
+        log::warn!(
+            target: LOG_TARGET,
+            "The chromosome \"{}\" of {} could not be normalised and is written unchanged.",
+            unknown,
+            path.display()
+        );
+    }
+    let mut sorted = SortedPeakFile {
+        content: comments,
+        records: records.len(),
+        duplicates: record_count - records.len(),
+        unknown_chromosomes,
+    };
+    for (chromosome, _, _, remainder) in records {
+        sorted.content.push_str(chromosome);
+        sorted.content.push_str(remainder);
+        sorted.content.push('\n');
+    }
+    Ok(sorted)
+}
+
+/// Reads the content of a text file.
+///
+/// # Parameters
+///
+/// * `path` - the path of the file
+/// * `description` - the description of the file used in error messages
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read.
+fn read_to_string(path: &Path, description: &str) -> Result<String, ApplicationError> {
+    retry(&format!("Reading {}", path.display()), || read_decompressed_to_string(path)).map_err(
+        |err| {
+            ApplicationError::from(err).chain(format!(
+                "The {} \"{}\" could not be read.",
+                description,
+                path.display()
+            ))
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::{test_output, test_resources};
+
+    use super::*;
+
+    #[test]
+    fn test_chromosome_normaliser() {
+        let sizes = BTreeMap::from([
+            (Chromosome::from("chr1"), 1000),
+            (Chromosome::from("chrM"), 100),
+            (Chromosome::from("2"), 500),
+        ]);
+        let normaliser = ChromosomeNormaliser::new()
+            .chromosome_sizes(&sizes)
+            .aliases(HashMap::from([("NC_000001.11".to_string(), "chr1".to_string())]));
+        assert_eq!(normaliser.normalise("chr1"), Some("chr1"));
+        assert_eq!(normaliser.normalise("1"), Some("chr1"));
+        assert_eq!(normaliser.normalise("NC_000001.11"), Some("chr1"));
+        assert_eq!(normaliser.normalise("MT"), Some("chrM"));
+        assert_eq!(normaliser.normalise("chr2"), Some("2"));
+        assert_eq!(normaliser.normalise("chrX"), None);
+        assert_eq!(ChromosomeNormaliser::new().normalise("chrX"), Some("chrX"));
+    }
+
+    #[test]
+    fn test_chromosome_aliases_from_file() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let path = test_output().join("test_chromosome_aliases_from_file.txt");
+        std::fs::write(&path, "# ucsc\tensembl\tgenbank\nchr1\t1\tCM000663.2\nchrM\tMT\t\n")
+            .unwrap();
+        let aliases = chromosome_aliases_from_file(&path).unwrap();
+        std::fs::write(&path, "chr1\t1\nchr2\t1\n").unwrap();
+        let error = chromosome_aliases_from_file(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(aliases.len(), 5);
+        assert_eq!(aliases["CM000663.2"], "chr1");
+        assert_eq!(aliases["MT"], "chrM");
+        assert_eq!(error.error_type(), ApplicationErrorType::ValidationError);
+        assert_eq!(error.context().unwrap().line(), Some(2));
+    }
+
+    #[test]
+    fn test_sort_peak_file() {
+        let path = test_resources().join("input_test_main_internal_input_01.narrowPeak");
+        let sorted = sort_peak_file(&path, &ChromosomeNormaliser::new(), false).unwrap();
+        assert_eq!(sorted.records(), 4);
+        assert_eq!(sorted.duplicates(), 0);
+        assert_eq!(
+            sorted.content(),
+            "# Broad fragment covering both peaks and slightly shifted summit.\n\
+            # Peak at approximately coordinate 700.\n\
+            chr1\t500\t1000\tnarrow_peak_01\t358\t.\t16.7471\t38.4652\t35.8377 230\n\
+            chr1\t600\t800\tnarrow_peak_01\t358\t.\t16.7471\t38.4652\t35.8377 101\n\
+            chr1\t658\t739\tnarrow_peak_01\t358\t.\t16.7471\t38.4652\t35.8377 37\n\
+            chr1\t678\t719\tnarrow_peak_01\t358\t.\t16.7471\t38.4652\t35.8377 15\n"
+        );
+    }
+
+    #[test]
+    fn test_sort_peak_file_normalised() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let path = test_output().join("test_sort_peak_file_normalised.bed");
+        std::fs::write(
+            &path,
+            "2\t10\t20\ta\nX\t5\t8\n1\t10\t20\tb\nchr1\t10\t20\tc\n\nchr1\t1\t5\n",
+        )
+        .unwrap();
+        let sizes = BTreeMap::from([
+            (Chromosome::from("chr1"), 1000),
+            (Chromosome::from("chr2"), 100),
+        ]);
+        let normaliser = ChromosomeNormaliser::new().chromosome_sizes(&sizes);
+        let sorted = sort_peak_file(&path, &normaliser, true).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(sorted.content(), "X\t5\t8\nchr1\t1\t5\nchr1\t10\t20\tb\nchr2\t10\t20\ta\n");
+        assert_eq!(sorted.records(), 4);
+        assert_eq!(sorted.duplicates(), 1);
+        assert_eq!(sorted.unknown_chromosomes(), &vec!["X".to_string()]);
+    }
+}
@@ -0,0 +1,304 @@
+//! This module spills intermediate peak data to compressed temporary files.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    chromosome::Chromosome,
+    error::ApplicationError,
+    peaks::PeakData,
+    retry::{Retrying, retry},
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "spill";
+
+/// The number of bytes a single encoded [`PeakData`] record occupies before compression.
+const RECORD_SIZE: usize = 4 * std::mem::size_of::<u64>();
+
+/// The default zstd compression level used for spill files.
+pub const DEFAULT_COMPRESSION_LEVEL: i32 = 3;
+
+/// A writer that encodes [`PeakData`] into a zstd-compressed spill file.
+pub struct SpillWriter {
+    path: PathBuf,
+    encoder: zstd::Encoder<'static, BufWriter<Retrying<File>>>,
+    records: usize,
+}
+
+impl SpillWriter {
+    /// Creates a new spill file at the specified path.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - the path of the spill file
+    /// * `compression_level` - the zstd compression level to use
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be created or the encoder cannot be initialised.
+    pub fn create<T: AsRef<Path>>(
+        path: T,
+        compression_level: i32,
+    ) -> Result<Self, ApplicationError> {
+        let description = format!("Writing {}", path.as_ref().display());
+        let file = retry(&description, || File::create(&path)).map_err(|err| {
+            ApplicationError::from(err).chain(format!(
+                "The spill file \"{}\" could not be created.",
+                path.as_ref().display()
+            ))
+        })?;
+        let encoder =
+            zstd::Encoder::new(BufWriter::new(Retrying::new(file, description)), compression_level)
+                .map_err(|err| {
+                    ApplicationError::from(err).chain(format!(
+                        "The compression of spill file \"{}\" could not be initialised.",
+                        path.as_ref().display()
+                    ))
+                })?;
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            encoder,
+            records: 0,
+        })
+    }
+
+    /// Appends a peak to the spill file.
+    ///
+    /// # Parameters
+    ///
+    /// * `peak` - the peak to write
+    pub fn write_peak(&mut self, peak: &PeakData) -> Result<(), ApplicationError> {
+        self.encoder.write_all(&encode_peak(peak)).map_err(|err| {
+            ApplicationError::from(err).chain(format!(
+                "Writing peak {:?} to spill file \"{}\" failed.",
+                peak,
+                self.path.display()
+            ))
+        })?;
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Finalises the compressed frame and returns a handle to the written spill file.
+    pub fn finish(self) -> Result<SpillFile, ApplicationError> {
+        let path = self.path;
+        self.encoder
+            .finish()
+            .and_then(|mut writer| writer.flush())
+            .map_err(|err| {
+                ApplicationError::from(err)
+                    .chain(format!("The spill file \"{}\" could not be finalised.", path.display()))
+            })?;
+        Ok(SpillFile {
+            path,
+            records: self.records,
+        })
+    }
+}
+
+/// A finished spill file. The file is removed from disk once the handle is dropped.
+#[derive(Debug)]
+pub struct SpillFile {
+    path: PathBuf,
+    records: usize,
+}
+
+impl SpillFile {
+    /// Decompresses and returns all peaks stored in the spill file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or contains invalid peak data.
+    pub fn read_peaks(&self) -> Result<Vec<PeakData>, ApplicationError> {
+        let read_error = |err: std::io::Error| {
+            ApplicationError::from(err)
+                .chain(format!("The spill file \"{}\" could not be read.", self.path.display()))
+        };
+        let description = format!("Reading {}", self.path.display());
+        let file = retry(&description, || File::open(&self.path)).map_err(read_error)?;
+        let mut decoder = zstd::Decoder::new(BufReader::new(Retrying::new(file, description)))
+            .map_err(read_error)?;
+        let mut peaks = Vec::with_capacity(self.records);
+        let mut buffer = [0u8; RECORD_SIZE];
+        loop {
+            match decoder.read_exact(&mut buffer) {
+                Ok(()) => peaks.push(decode_peak(&buffer).map_err(|err| {
+                    err.chain(format!(
+                        "The spill file \"{}\" contains invalid data.",
+                        self.path.display()
+                    ))
+                })?),
+                Err(err) if err.kind() == ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(read_error(err)),
+            }
+        }
+        Ok(peaks)
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            log::warn!(
+                target: LOG_TARGET,
+                "The spill file \"{}\" could not be removed: {}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+}
+
+/// Spills all peaks to one compressed file per chromosome inside the specified directory
+/// and releases the in-memory peak data.
+///
+/// # Parameters
+///
+/// * `directory` - the directory to create the spill files in
+/// * `peaks_by_chromosome` - the peaks to spill
+/// * `compression_level` - the zstd compression level to use
+///
+/// # Errors
+///
+/// Returns an error if the directory or any of the spill files cannot be written.
+pub fn spill_peaks_by_chromosome<T: AsRef<Path>>(
+    directory: T,
+    peaks_by_chromosome: HashMap<Chromosome, Vec<PeakData>>,
+    compression_level: i32,
+) -> Result<Vec<(Chromosome, SpillFile)>, ApplicationError> {
+    std::fs::create_dir_all(&directory).map_err(|err| {
+        ApplicationError::from(err).chain(format!(
+            "The spill directory \"{}\" could not be created.",
+            directory.as_ref().display()
+        ))
+    })?;
+    let mut spill_files = Vec::with_capacity(peaks_by_chromosome.len());
+    for (chromosome, peaks) in peaks_by_chromosome {
+        let path = directory.as_ref().join(format!(
+            "gipfelkreuzer_{}_{}.peaks.zst",
+            std::process::id(),
+            chromosome.id()
+        ));
+        log::debug!(
+            target: LOG_TARGET,
+            "Spilling {} peaks of chromosome {} to {}.",
+            peaks.len(),
+            chromosome,
+            path.display()
+        );
+        let mut writer = SpillWriter::create(&path, compression_level)?;
+        for peak in &peaks {
+            writer.write_peak(peak)?;
+        }
+        spill_files.push((chromosome, writer.finish()?));
+    }
+    Ok(spill_files)
+}
+
+/// Encodes a peak as fixed size little endian record.
+///
+/// # Parameters
+///
+/// * `peak` - the peak to encode
+fn encode_peak(peak: &PeakData) -> [u8; RECORD_SIZE] {
+    let mut record = [0u8; RECORD_SIZE];
+    let values = [peak.id() as u64, peak.start(), peak.end(), peak.summit()];
+    for (chunk, value) in record
+        .chunks_exact_mut(std::mem::size_of::<u64>())
+        .zip(values)
+    {
+        chunk.copy_from_slice(&value.to_le_bytes());
+    }
+    record
+}
+
+/// Decodes a peak from a fixed size little endian record.
+///
+/// # Parameters
+///
+/// * `record` - the record to decode
+///
+/// # Errors
+///
+/// Returns an error if the decoded values do not form a valid peak.
+fn decode_peak(record: &[u8; RECORD_SIZE]) -> Result<PeakData, ApplicationError> {
+    let mut values = record
+        .chunks_exact(std::mem::size_of::<u64>())
+        .map(|chunk| {
+            u64::from_le_bytes(
+                chunk
+                    .try_into()
+                    .expect("The chunk size must match the value size."),
+            )
+        });
+    let mut next_value = || values.next().expect("The record must contain all values.");
+    let id = next_value() as usize;
+    PeakData::new(id, next_value(), next_value(), next_value())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::test_output;
+
+    use super::*;
+
+    #[test]
+    fn test_encode_decode_peak() {
+        let peak = PeakData::new(42, 4470246u64, 4470509u64, 4470353u64).unwrap();
+        assert_eq!(decode_peak(&encode_peak(&peak)).unwrap(), peak);
+    }
+
+    #[test]
+    fn test_spill_file_round_trip() {
+        let path = test_output().join("test_spill_file_round_trip.peaks.zst");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let peaks: Vec<PeakData> = (0..1000u64)
+            .map(|index| {
+                PeakData::new(index as usize, index * 1000, index * 1000 + 250, index * 1000 + 125)
+                    .unwrap()
+            })
+            .collect();
+        let mut writer = SpillWriter::create(&path, DEFAULT_COMPRESSION_LEVEL).unwrap();
+        for peak in &peaks {
+            writer.write_peak(peak).unwrap();
+        }
+        let spill_file = writer.finish().unwrap();
+        assert_eq!(spill_file.records, peaks.len());
+        let compressed_size = std::fs::metadata(&spill_file.path).unwrap().len() as usize;
+        assert!(
+            compressed_size < peaks.len() * RECORD_SIZE / 2,
+            "The spill file of size {} should be considerably smaller than the raw data.",
+            compressed_size
+        );
+        assert_eq!(spill_file.read_peaks().unwrap(), peaks);
+        drop(spill_file);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_spill_peaks_by_chromosome() {
+        let directory = test_output().join("test_spill_peaks_by_chromosome");
+        let mut peaks = HashMap::new();
+        peaks
+            .insert(Chromosome::from("chr1"), vec![PeakData::new(0, 45u64, 98u64, 55u64).unwrap()]);
+        peaks.insert(
+            Chromosome::from("chr4"),
+            vec![
+                PeakData::new(1, 4568u64, 9786u64, 5573u64).unwrap(),
+                PeakData::new(2, 4569u64, 9787u64, 5574u64).unwrap(),
+            ],
+        );
+        let spilled = spill_peaks_by_chromosome(&directory, peaks.clone(), 1).unwrap();
+        assert_eq!(spilled.len(), 2);
+        for (chromosome, spill_file) in &spilled {
+            assert_eq!(&spill_file.read_peaks().unwrap(), &peaks[chromosome]);
+        }
+        drop(spilled);
+        std::fs::remove_dir(directory).unwrap();
+    }
+}
@@ -0,0 +1,908 @@
+//! This module summarises peak sets, for example to inspect raw peak files before
+//! consensus peak generation or to compare them with the generated consensus peaks.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use getset::{CopyGetters, Getters};
+use serde::Serialize;
+
+use crate::{
+    chromosome::Chromosome,
+    concordance::SummitConcordance,
+    consensus::TrackedConsensus,
+    error::ApplicationError,
+    input::bed_to_peaks_with_approximated_summits,
+    peak_set::PeakSet,
+    peaks::{PeakBin, PeakData},
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "stats";
+
+/// The quantiles of the peak widths.
+#[derive(CopyGetters, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[getset(get_copy = "pub")]
+pub struct WidthQuantiles {
+    /// The minimum peak width.
+    min: u64,
+    /// The first quartile of the peak widths.
+    q25: u64,
+    /// The median peak width.
+    median: u64,
+    /// The third quartile of the peak widths.
+    q75: u64,
+    /// The maximum peak width.
+    max: u64,
+}
+
+impl WidthQuantiles {
+    /// Calculates the quantiles of the specified peak widths using the nearest rank method.
+    /// All quantiles are `0` if no widths are specified.
+    ///
+    /// # Parameters
+    ///
+    /// * `widths` - the peak widths
+    fn new(mut widths: Vec<u64>) -> Self {
+        if widths.is_empty() {
+            return Self::default();
+        }
+        widths.sort_unstable();
+        Self {
+            min: widths[0],
+            q25: nearest_rank(&widths, 0.25),
+            median: nearest_rank(&widths, 0.5),
+            q75: nearest_rank(&widths, 0.75),
+            max: widths[widths.len() - 1],
+        }
+    }
+}
+
+/// Returns the quantile of the sorted values using the nearest rank method.
+///
+/// # Parameters
+///
+/// * `sorted_values` - the sorted values, which must not be empty
+/// * `fraction` - the fraction of values below the quantile
+pub(crate) fn nearest_rank<T: Copy>(sorted_values: &[T], fraction: f64) -> T {
+    let rank = (fraction * sorted_values.len() as f64).ceil() as usize;
+    sorted_values[rank.clamp(1, sorted_values.len()) - 1]
+}
+
+/// Returns the number of bases covered by at least one of the peaks.
+///
+/// # Parameters
+///
+/// * `peaks` - the peaks located on the same chromosome
+pub(crate) fn covered_bases(peaks: &[PeakData]) -> u64 {
+    let mut sorted_peaks: Vec<&PeakData> = peaks.iter().collect();
+    sorted_peaks.sort_by_key(|peak| (peak.start(), peak.end()));
+    let mut covered_bases = 0;
+    let mut covered_region: Option<(u64, u64)> = None;
+    for peak in sorted_peaks {
+        covered_region = match covered_region {
+            Some((start, end)) if peak.start() <= end => Some((start, end.max(peak.end()))),
+            Some((start, end)) => {
+                covered_bases += end - start + 1;
+                Some((peak.start(), peak.end()))
+            },
+            None => Some((peak.start(), peak.end())),
+        };
+    }
+    if let Some((start, end)) = covered_region {
+        covered_bases += end - start + 1;
+    }
+    covered_bases
+}
+
+/// The summary of a set of peaks.
+#[derive(CopyGetters, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[getset(get_copy = "pub")]
+pub struct PeakStatistics {
+    /// The number of peaks.
+    peaks: usize,
+    /// The number of bases covered by at least one peak.
+    covered_bases: u64,
+    /// The number of peaks with summit information.
+    summits: usize,
+    /// The quantiles of the peak widths.
+    widths: WidthQuantiles,
+}
+
+impl PeakStatistics {
+    /// Summarises the peaks of a single chromosome.
+    ///
+    /// # Parameters
+    ///
+    /// * `peaks` - the peaks located on the same chromosome
+    /// * `approximated_summit_ids` - the identifiers of the peaks without summit information
+    pub fn new(peaks: &[PeakData], approximated_summit_ids: &HashSet<usize>) -> Self {
+        Self {
+            peaks: peaks.len(),
+            covered_bases: covered_bases(peaks),
+            summits: peaks
+                .iter()
+                .filter(|peak| !approximated_summit_ids.contains(&peak.id()))
+                .count(),
+            widths: WidthQuantiles::new(
+                peaks
+                    .iter()
+                    .map(|peak| peak.end() - peak.start() + 1)
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Returns the fraction of peaks with summit information or `0.0` if there are no peaks.
+    pub fn summit_presence_rate(&self) -> f64 {
+        if self.peaks == 0 {
+            0.0
+        } else {
+            self.summits as f64 / self.peaks as f64
+        }
+    }
+}
+
+/// The summary of a peak file.
+#[derive(CopyGetters, Getters, Clone, PartialEq, Eq, Debug)]
+pub struct FileStatistics {
+    /// The peak file.
+    #[getset(get = "pub")]
+    file: PathBuf,
+    /// The summary of all peaks of the file.
+    #[getset(get_copy = "pub")]
+    total: PeakStatistics,
+    /// The summaries of the peaks per chromosome.
+    #[getset(get = "pub")]
+    chromosomes: BTreeMap<Chromosome, PeakStatistics>,
+}
+
+impl FileStatistics {
+    /// Summarises the peaks of a BED3+ file.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - the peak file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be parsed.
+    pub fn from_bed_file<T: AsRef<Path>>(path: T) -> Result<Self, ApplicationError> {
+        log::info!(target: LOG_TARGET, "Summarising {}.", path.as_ref().display());
+        let (peaks, approximated_summit_ids) = bed_to_peaks_with_approximated_summits(&path)?;
+        let chromosomes: BTreeMap<Chromosome, PeakStatistics> = peaks
+            .iter()
+            .map(|(chromosome, chromosome_peaks)| {
+                (*chromosome, PeakStatistics::new(chromosome_peaks, &approximated_summit_ids))
+            })
+            .collect();
+        let all_peaks: Vec<PeakData> = peaks.into_values().flatten().collect();
+        let total = PeakStatistics {
+            covered_bases: chromosomes
+                .values()
+                .map(PeakStatistics::covered_bases)
+                .sum(),
+            ..PeakStatistics::new(&all_peaks, &approximated_summit_ids)
+        };
+        Ok(Self {
+            file: path.as_ref().to_path_buf(),
+            total,
+            chromosomes,
+        })
+    }
+}
+
+/// The number of consensus peaks supported by each combination of samples,
+/// which is the data behind an UpSet plot.
+/// A sample supports a consensus peak if any of its peaks overlaps the consensus peak.
+#[derive(Getters, Clone, Default, PartialEq, Eq, Debug)]
+pub struct SampleIntersections {
+    /// The number of consensus peaks per combination of supporting sample indices.
+    #[getset(get = "pub")]
+    counts: BTreeMap<Vec<usize>, usize>,
+}
+
+impl SampleIntersections {
+    /// Counts the consensus peaks supported by each combination of samples.
+    ///
+    /// # Parameters
+    ///
+    /// * `consensus_peaks` - the consensus peaks
+    /// * `samples` - the peaks of each sample
+    pub fn new(consensus_peaks: &PeakSet, samples: &[PeakSet]) -> Self {
+        let mut counts = BTreeMap::new();
+        for (chromosome, peak) in consensus_peaks.iter() {
+            let supporting_samples: Vec<usize> = samples
+                .iter()
+                .enumerate()
+                .filter(|(_, sample)| {
+                    !sample
+                        .overlapping(chromosome, peak.start(), peak.end())
+                        .is_empty()
+                })
+                .map(|(index, _)| index)
+                .collect();
+            *counts.entry(supporting_samples).or_default() += 1;
+        }
+        Self { counts }
+    }
+}
+
+/// Writes the number of consensus peaks supported by each combination of samples as tab
+/// separated table sorted by the number of consensus peaks in descending order.
+/// The supporting samples of a combination are joined by `&`.
+///
+/// # Parameters
+///
+/// * `path` - the path of the table
+/// * `sample_names` - the names of the samples in the order used to create the intersections
+/// * `intersections` - the consensus peaks per combination of samples
+///
+/// # Errors
+///
+/// Returns an error if the table cannot be written.
+pub fn write_intersection_table<T: AsRef<Path>, S: AsRef<str>>(
+    path: T,
+    sample_names: &[S],
+    intersections: &SampleIntersections,
+) -> Result<(), ApplicationError> {
+    log::info!(
+        target: LOG_TARGET,
+        "Writing the sample intersections to {}.",
+        path.as_ref().display()
+    );
+    let mut combinations: Vec<(&Vec<usize>, &usize)> = intersections.counts().iter().collect();
+    combinations.sort_by(|(samples_a, count_a), (samples_b, count_b)| {
+        count_b
+            .cmp(count_a)
+            .then_with(|| samples_a.len().cmp(&samples_b.len()))
+            .then_with(|| samples_a.cmp(samples_b))
+    });
+    let mut table = String::from("samples\tsample_count\tconsensus_peaks\n");
+    for (samples, count) in combinations {
+        let names: Vec<&str> = samples
+            .iter()
+            .map(|index| sample_names[*index].as_ref())
+            .collect();
+        table.push_str(&format!("{}\t{}\t{}\n", names.join("&"), samples.len(), count));
+    }
+    File::create(&path)
+        .and_then(|mut file| file.write_all(table.as_bytes()))
+        .map_err(|err| {
+            ApplicationError::from(err).chain(format!(
+                "The sample intersection table \"{}\" could not be written.",
+                path.as_ref().display()
+            ))
+        })
+}
+
+/// Writes the summaries as tab separated table with one row per file and chromosome.
+/// The summary of all peaks of a file is written with the chromosome `*`.
+///
+/// # Parameters
+///
+/// * `writer` - the writer to write the table to
+/// * `statistics` - the summaries of the peak files
+///
+/// # Errors
+///
+/// Returns an error if the table cannot be written.
+pub fn write_statistics_table<W: Write>(
+    mut writer: W,
+    statistics: &[FileStatistics],
+) -> Result<(), ApplicationError> {
+    let mut table = String::from(
+        "file\tchromosome\tpeaks\tcovered_bases\twidth_min\twidth_q25\twidth_median\t\
+        width_q75\twidth_max\tsummit_presence_rate\n",
+    );
+    for file_statistics in statistics {
+        let rows = std::iter::once(("*".to_string(), file_statistics.total())).chain(
+            file_statistics
+                .chromosomes()
+                .iter()
+                .map(|(chromosome, chromosome_statistics)| {
+                    (chromosome.name().to_string(), *chromosome_statistics)
+                }),
+        );
+        for (chromosome, row) in rows {
+            table.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.6}\n",
+                file_statistics.file().display(),
+                chromosome,
+                row.peaks(),
+                row.covered_bases(),
+                row.widths().min(),
+                row.widths().q25(),
+                row.widths().median(),
+                row.widths().q75(),
+                row.widths().max(),
+                row.summit_presence_rate()
+            ));
+        }
+    }
+    writer
+        .write_all(table.as_bytes())
+        .map_err(|err| ApplicationError::from(err).chain("The statistics could not be written."))
+}
+
+/// The number of raw peaks parsed from an input file.
+#[derive(Serialize, CopyGetters, Getters, Clone, PartialEq, Eq, Debug)]
+pub struct InputPeakCount {
+    /// The input file.
+    #[getset(get = "pub")]
+    file: PathBuf,
+    /// The number of raw peaks.
+    #[getset(get_copy = "pub")]
+    raw_peaks: usize,
+}
+
+impl InputPeakCount {
+    /// Creates a new raw peak count of an input file.
+    ///
+    /// # Parameters
+    ///
+    /// * `file` - the input file
+    /// * `raw_peaks` - the number of raw peaks parsed from the file
+    pub fn new<T: Into<PathBuf>>(file: T, raw_peaks: usize) -> Self {
+        Self {
+            file: file.into(),
+            raw_peaks,
+        }
+    }
+}
+
+/// The statistics of the consensus peaks of a single chromosome or the whole genome.
+#[derive(Serialize, CopyGetters, Clone, Copy, Default, PartialEq, Debug)]
+#[getset(get_copy = "pub")]
+pub struct ConsensusCounts {
+    /// The number of raw peaks.
+    raw_peaks: usize,
+    /// The number of bins of overlapping or adjacent raw peaks.
+    bins: usize,
+    /// The number of consensus peaks.
+    consensus_peaks: usize,
+    /// The mean consensus peak width or `0.0` if there are no consensus peaks.
+    mean_width: f64,
+    /// The median consensus peak width or `0` if there are no consensus peaks.
+    median_width: u64,
+}
+
+impl ConsensusCounts {
+    /// Counts the raw peaks, bins and consensus peaks.
+    ///
+    /// # Parameters
+    ///
+    /// * `raw_peaks` - the raw peaks
+    /// * `bins` - the number of bins of overlapping or adjacent raw peaks
+    /// * `consensus_peaks` - the consensus peaks
+    fn new(raw_peaks: usize, bins: usize, consensus_peaks: &[&PeakData]) -> Self {
+        let (mean_width, median_width) = mean_and_median_width(consensus_peaks);
+        Self {
+            raw_peaks,
+            bins,
+            consensus_peaks: consensus_peaks.len(),
+            mean_width,
+            median_width,
+        }
+    }
+}
+
+/// Returns the mean and median width of the peaks or `0.0` and `0` if there are no peaks.
+///
+/// # Parameters
+///
+/// * `peaks` - the peaks
+fn mean_and_median_width(peaks: &[&PeakData]) -> (f64, u64) {
+    let mut widths: Vec<u64> = peaks.iter().map(|peak| peak.length()).collect();
+    widths.sort_unstable();
+    if widths.is_empty() {
+        (0.0, 0)
+    } else {
+        (widths.iter().sum::<u64>() as f64 / widths.len() as f64, nearest_rank(&widths, 0.5))
+    }
+}
+
+/// The number of consensus peaks supported by at least a minimum number of raw peaks.
+#[derive(Serialize, CopyGetters, Clone, Copy, PartialEq, Eq, Debug)]
+#[getset(get_copy = "pub")]
+pub struct SupportThreshold {
+    /// The minimum number of raw peak summits within a consensus peak.
+    min_support: usize,
+    /// The number of consensus peaks reaching the minimum support.
+    consensus_peaks: usize,
+}
+
+/// The machine-readable statistics of a consensus peak generation run, which allow pipelines
+/// to assert on the outcome of the run.
+#[derive(Serialize, CopyGetters, Getters, Clone, PartialEq, Debug)]
+pub struct ConsensusStatistics {
+    /// The number of raw peaks per input file.
+    #[getset(get = "pub")]
+    inputs: Vec<InputPeakCount>,
+    /// The statistics of the whole genome.
+    #[getset(get_copy = "pub")]
+    total: ConsensusCounts,
+    /// The statistics per chromosome.
+    #[getset(get = "pub")]
+    chromosomes: BTreeMap<Chromosome, ConsensusCounts>,
+    /// The number of consensus peaks remaining for each minimum number of supporting raw peaks
+    /// from `1` up to the number of input files.
+    /// A raw peak supports a consensus peak if its summit is located within the consensus peak.
+    #[getset(get = "pub")]
+    support_thresholds: Vec<SupportThreshold>,
+}
+
+impl ConsensusStatistics {
+    /// Summarises a consensus peak generation run.
+    ///
+    /// # Parameters
+    ///
+    /// * `inputs` - the number of raw peaks per input file
+    /// * `raw_peaks` - the raw peaks of all input files grouped by chromosome
+    /// * `consensus_peaks` - the consensus peaks grouped by chromosome
+    pub fn new(
+        inputs: Vec<InputPeakCount>,
+        raw_peaks: &HashMap<Chromosome, Vec<PeakData>>,
+        consensus_peaks: &HashMap<Chromosome, Vec<PeakData>>,
+    ) -> Self {
+        let chromosome_names: BTreeSet<Chromosome> = raw_peaks
+            .keys()
+            .chain(consensus_peaks.keys())
+            .copied()
+            .collect();
+        let mut total_bins = 0;
+        let chromosomes: BTreeMap<Chromosome, ConsensusCounts> = chromosome_names
+            .into_iter()
+            .map(|chromosome| {
+                let chromosome_raw_peaks = raw_peaks.get(&chromosome).cloned().unwrap_or_default();
+                let raw_peak_count = chromosome_raw_peaks.len();
+                let bins = PeakBin::bin_peaks(chromosome_raw_peaks).len();
+                total_bins += bins;
+                let chromosome_consensus_peaks: Vec<&PeakData> = consensus_peaks
+                    .get(&chromosome)
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                (
+                    chromosome,
+                    ConsensusCounts::new(raw_peak_count, bins, &chromosome_consensus_peaks),
+                )
+            })
+            .collect();
+        let all_consensus_peaks: Vec<&PeakData> = consensus_peaks.values().flatten().collect();
+        let total = ConsensusCounts::new(
+            raw_peaks.values().map(Vec::len).sum(),
+            total_bins,
+            &all_consensus_peaks,
+        );
+        let concordance = SummitConcordance::new(
+            &PeakSet::from(consensus_peaks.clone()),
+            &[PeakSet::from(raw_peaks.clone())],
+        );
+        let support_thresholds = (1..=inputs.len().max(1))
+            .map(|min_support| SupportThreshold {
+                min_support,
+                consensus_peaks: concordance
+                    .peaks()
+                    .iter()
+                    .filter(|peak| peak.summits() >= min_support)
+                    .count(),
+            })
+            .collect();
+        Self {
+            inputs,
+            total,
+            chromosomes,
+            support_thresholds,
+        }
+    }
+}
+
+/// Writes the consensus statistics as JSON file.
+///
+/// # Parameters
+///
+/// * `path` - the path of the JSON file
+/// * `statistics` - the consensus statistics
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be written.
+pub fn write_consensus_statistics<T: AsRef<Path>>(
+    path: T,
+    statistics: &ConsensusStatistics,
+) -> Result<(), ApplicationError> {
+    log::info!(
+        target: LOG_TARGET,
+        "Writing the consensus statistics to {}.",
+        path.as_ref().display()
+    );
+    let mut statistics_json = serde_json::to_vec_pretty(statistics)?;
+    statistics_json.push(b'\n');
+    File::create(&path)
+        .and_then(|mut file| file.write_all(&statistics_json))
+        .map_err(|err| {
+            ApplicationError::from(err).chain(format!(
+                "The consensus statistics \"{}\" could not be written.",
+                path.as_ref().display()
+            ))
+        })
+}
+
+/// The quality control summary of the consensus peaks of a single chromosome or the whole genome.
+#[derive(CopyGetters, Clone, Copy, Default, PartialEq, Debug)]
+#[getset(get_copy = "pub")]
+pub struct SummaryCounts {
+    /// The number of input peaks.
+    input_peaks: usize,
+    /// The number of input peaks contributing to any consensus peak.
+    retained_input_peaks: usize,
+    /// The number of consensus peaks discarded, as they were created from less than the minimum
+    /// number of raw peaks per consensus peak.
+    filtered_consensus_peaks: usize,
+    /// The number of consensus peaks.
+    consensus_peaks: usize,
+    /// The mean consensus peak width or `0.0` if there are no consensus peaks.
+    mean_width: f64,
+    /// The median consensus peak width or `0` if there are no consensus peaks.
+    median_width: u64,
+}
+
+impl SummaryCounts {
+    /// Returns the fraction of input peaks contributing to any consensus peak
+    /// or `0.0` if there are no input peaks.
+    pub fn retained_fraction(&self) -> f64 {
+        if self.input_peaks == 0 {
+            0.0
+        } else {
+            self.retained_input_peaks as f64 / self.input_peaks as f64
+        }
+    }
+}
+
+/// The quality control summary of a consensus peak generation run per chromosome.
+#[derive(CopyGetters, Getters, Clone, PartialEq, Debug)]
+pub struct ConsensusSummary {
+    /// The summary of the whole genome.
+    #[getset(get_copy = "pub")]
+    total: SummaryCounts,
+    /// The summaries per chromosome.
+    #[getset(get = "pub")]
+    chromosomes: BTreeMap<Chromosome, SummaryCounts>,
+}
+
+impl ConsensusSummary {
+    /// Summarises a consensus peak generation run.
+    ///
+    /// # Parameters
+    ///
+    /// * `input_peaks` - the number of input peaks per chromosome
+    /// * `tracked_consensus` - the consensus peaks keeping track of the raw peaks they were
+    ///   created from
+    /// * `filtered_consensus_peaks` - the number of consensus peaks per chromosome discarded by
+    ///   the minimum number of raw peaks per consensus peak
+    /// * `consensus_peaks` - the written consensus peaks grouped by chromosome
+    pub fn new(
+        input_peaks: &HashMap<Chromosome, usize>,
+        tracked_consensus: &TrackedConsensus,
+        filtered_consensus_peaks: &HashMap<Chromosome, usize>,
+        consensus_peaks: &HashMap<Chromosome, Vec<PeakData>>,
+    ) -> Self {
+        let chromosome_names: BTreeSet<Chromosome> = input_peaks
+            .keys()
+            .chain(consensus_peaks.keys())
+            .copied()
+            .collect();
+        let chromosomes: BTreeMap<Chromosome, SummaryCounts> = chromosome_names
+            .into_iter()
+            .map(|chromosome| {
+                // A raw peak may contribute to multiple consensus peaks, but is only counted once.
+                let retained_input_peaks = tracked_consensus
+                    .get(&chromosome)
+                    .into_iter()
+                    .flatten()
+                    .flat_map(|peak| peak.raw_peak_ids())
+                    .collect::<HashSet<&usize>>()
+                    .len();
+                let chromosome_consensus_peaks: Vec<&PeakData> = consensus_peaks
+                    .get(&chromosome)
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                let (mean_width, median_width) = mean_and_median_width(&chromosome_consensus_peaks);
+                (
+                    chromosome,
+                    SummaryCounts {
+                        input_peaks: input_peaks.get(&chromosome).copied().unwrap_or_default(),
+                        retained_input_peaks,
+                        filtered_consensus_peaks: filtered_consensus_peaks
+                            .get(&chromosome)
+                            .copied()
+                            .unwrap_or_default(),
+                        consensus_peaks: chromosome_consensus_peaks.len(),
+                        mean_width,
+                        median_width,
+                    },
+                )
+            })
+            .collect();
+        let (mean_width, median_width) =
+            mean_and_median_width(&consensus_peaks.values().flatten().collect::<Vec<_>>());
+        let total = SummaryCounts {
+            input_peaks: chromosomes.values().map(SummaryCounts::input_peaks).sum(),
+            retained_input_peaks: chromosomes
+                .values()
+                .map(SummaryCounts::retained_input_peaks)
+                .sum(),
+            filtered_consensus_peaks: chromosomes
+                .values()
+                .map(SummaryCounts::filtered_consensus_peaks)
+                .sum(),
+            consensus_peaks: chromosomes
+                .values()
+                .map(SummaryCounts::consensus_peaks)
+                .sum(),
+            mean_width,
+            median_width,
+        };
+        Self { total, chromosomes }
+    }
+}
+
+/// Writes the consensus summary as tab separated table with one row per chromosome.
+/// The summary of the whole genome is written first with the chromosome `*`.
+///
+/// # Parameters
+///
+/// * `writer` - the writer to write the table to
+/// * `summary` - the consensus summary
+///
+/// # Errors
+///
+/// Returns an error if the table cannot be written.
+pub fn write_summary_table<W: Write>(
+    mut writer: W,
+    summary: &ConsensusSummary,
+) -> Result<(), ApplicationError> {
+    let mut table = String::from(
+        "chromosome\tinput_peaks\tconsensus_peaks\tmean_width\tmedian_width\t\
+        retained_input_fraction\tfiltered_by_min_peaks\n",
+    );
+    let rows = std::iter::once(("*".to_string(), summary.total())).chain(
+        summary
+            .chromosomes()
+            .iter()
+            .map(|(chromosome, counts)| (chromosome.name().to_string(), *counts)),
+    );
+    for (chromosome, row) in rows {
+        table.push_str(&format!(
+            "{}\t{}\t{}\t{:.1}\t{}\t{:.6}\t{}\n",
+            chromosome,
+            row.input_peaks(),
+            row.consensus_peaks(),
+            row.mean_width(),
+            row.median_width(),
+            row.retained_fraction(),
+            row.filtered_consensus_peaks()
+        ));
+    }
+    writer
+        .write_all(table.as_bytes())
+        .map_err(|err| ApplicationError::from(err).chain("The summary could not be written."))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        consensus::untrack_consensus,
+        peaks::TrackedConsensusPeak,
+        test_utils::{test_output, test_resources},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_width_quantiles() {
+        assert_eq!(WidthQuantiles::new(Vec::new()), WidthQuantiles::default());
+        let quantiles = WidthQuantiles::new(vec![40, 10, 30, 20]);
+        assert_eq!(quantiles.min(), 10);
+        assert_eq!(quantiles.q25(), 10);
+        assert_eq!(quantiles.median(), 20);
+        assert_eq!(quantiles.q75(), 30);
+        assert_eq!(quantiles.max(), 40);
+    }
+
+    #[test]
+    fn test_peak_statistics() {
+        let peaks = vec![
+            PeakData::new(0, 100u64, 199u64, 150u64).unwrap(),
+            PeakData::new(1, 150u64, 249u64, 200u64).unwrap(),
+            PeakData::new(2, 500u64, 509u64, 505u64).unwrap(),
+        ];
+        let statistics = PeakStatistics::new(&peaks, &HashSet::from([2]));
+        assert_eq!(statistics.peaks(), 3);
+        assert_eq!(statistics.covered_bases(), 160);
+        assert_eq!(statistics.summits(), 2);
+        assert_eq!(statistics.widths().median(), 100);
+        assert!((statistics.summit_presence_rate() - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(PeakStatistics::new(&[], &HashSet::new()).summit_presence_rate(), 0.0);
+    }
+
+    #[test]
+    fn test_sample_intersections() {
+        let chromosome = Chromosome::from("chr1");
+        let sample = |coordinates: &[(u64, u64)]| -> PeakSet {
+            coordinates
+                .iter()
+                .enumerate()
+                .map(|(id, (start, end))| {
+                    (chromosome, PeakData::new(id, *start, *end, start.midpoint(*end)).unwrap())
+                })
+                .collect()
+        };
+        let consensus_peaks = sample(&[(100, 199), (300, 399), (500, 599), (700, 799)]);
+        let samples = [
+            sample(&[(90, 150), (310, 320), (700, 710)]),
+            sample(&[(150, 250), (390, 450)]),
+            sample(&[(580, 620), (790, 800)]),
+        ];
+        let intersections = SampleIntersections::new(&consensus_peaks, &samples);
+        assert_eq!(
+            intersections.counts(),
+            &BTreeMap::from([(vec![0, 1], 2), (vec![0, 2], 1), (vec![2], 1)])
+        );
+
+        std::fs::create_dir_all(test_output()).unwrap();
+        let table_path = test_output().join("test_sample_intersections.tsv");
+        write_intersection_table(&table_path, &["a", "b", "c"], &intersections).unwrap();
+        let table = std::fs::read_to_string(&table_path).unwrap();
+        std::fs::remove_file(&table_path).unwrap();
+        assert_eq!(
+            table,
+            "samples\tsample_count\tconsensus_peaks\na&b\t2\t2\nc\t1\t1\na&c\t2\t1\n"
+        );
+    }
+
+    #[test]
+    fn test_file_statistics() {
+        let with_summits = FileStatistics::from_bed_file(
+            test_resources().join("input_test_valid_with_summit.narrowPeak"),
+        )
+        .unwrap();
+        let minimal = FileStatistics::from_bed_file(
+            test_resources().join("input_test_valid_minimal.narrowPeak"),
+        )
+        .unwrap();
+        assert_eq!(with_summits.total().peaks(), 6);
+        assert_eq!(with_summits.total().summit_presence_rate(), 1.0);
+        assert_eq!(minimal.total().summit_presence_rate(), 0.0);
+        assert_eq!(with_summits.chromosomes().len(), 2);
+        assert_eq!(with_summits.chromosomes()[&Chromosome::from("chr2")].peaks(), 2);
+        assert_eq!(
+            with_summits.total().covered_bases(),
+            with_summits
+                .chromosomes()
+                .values()
+                .map(PeakStatistics::covered_bases)
+                .sum::<u64>()
+        );
+
+        let mut table = Vec::new();
+        write_statistics_table(&mut table, &[with_summits, minimal]).unwrap();
+        let table = String::from_utf8(table).unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 7);
+        assert!(lines[0].starts_with("file\tchromosome\tpeaks"));
+        assert!(lines[1].ends_with("\t*\t6\t1654\t160\t194\t221\t363\t452\t1.000000"));
+        assert!(lines[3].ends_with("\tchr2\t2\t523\t160\t160\t160\t363\t363\t1.000000"));
+        assert!(lines[4].ends_with("\t*\t6\t1654\t160\t194\t221\t363\t452\t0.000000"));
+    }
+
+    #[test]
+    fn test_consensus_statistics() {
+        let chr1 = Chromosome::from("chr1");
+        let chr2 = Chromosome::from("chr2");
+        let raw_peaks = HashMap::from([
+            (
+                chr1,
+                vec![
+                    PeakData::new(0, 100u64, 199u64, 150u64).unwrap(),
+                    PeakData::new(1, 120u64, 219u64, 160u64).unwrap(),
+                    PeakData::new(2, 500u64, 599u64, 550u64).unwrap(),
+                ],
+            ),
+            (chr2, vec![PeakData::new(3, 0u64, 99u64, 50u64).unwrap()]),
+        ]);
+        let consensus_peaks = HashMap::from([(
+            chr1,
+            vec![
+                PeakData::new(0, 100u64, 219u64, 155u64).unwrap(),
+                PeakData::new(1, 500u64, 599u64, 550u64).unwrap(),
+            ],
+        )]);
+        let statistics = ConsensusStatistics::new(
+            vec![
+                InputPeakCount::new("a.bed", 2),
+                InputPeakCount::new("b.bed", 2),
+            ],
+            &raw_peaks,
+            &consensus_peaks,
+        );
+        assert_eq!(statistics.total().raw_peaks(), 4);
+        assert_eq!(statistics.total().bins(), 3);
+        assert_eq!(statistics.total().consensus_peaks(), 2);
+        assert!((statistics.total().mean_width() - 110.0).abs() < 1e-9);
+        assert_eq!(statistics.total().median_width(), 100);
+        assert_eq!(statistics.chromosomes()[&chr1].bins(), 2);
+        assert_eq!(statistics.chromosomes()[&chr2].consensus_peaks(), 0);
+        assert_eq!(statistics.chromosomes()[&chr2].mean_width(), 0.0);
+        assert_eq!(
+            statistics
+                .support_thresholds()
+                .iter()
+                .map(SupportThreshold::consensus_peaks)
+                .collect::<Vec<usize>>(),
+            vec![2, 1]
+        );
+
+        std::fs::create_dir_all(test_output()).unwrap();
+        let statistics_path = test_output().join("test_consensus_statistics.json");
+        write_consensus_statistics(&statistics_path, &statistics).unwrap();
+        let statistics_json: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&statistics_path).unwrap()).unwrap();
+        std::fs::remove_file(&statistics_path).unwrap();
+        assert_eq!(statistics_json["inputs"][1]["file"], "b.bed");
+        assert_eq!(statistics_json["total"]["bins"], 3);
+        assert_eq!(statistics_json["chromosomes"]["chr1"]["consensus_peaks"], 2);
+        assert_eq!(statistics_json["support_thresholds"][1]["min_support"], 2);
+    }
+
+    #[test]
+    fn test_consensus_summary() {
+        let chr1 = Chromosome::from("chr1");
+        let chr2 = Chromosome::from("chr2");
+        let input_peaks = HashMap::from([(chr1, 4), (chr2, 1)]);
+        let tracked_consensus = HashMap::from([(
+            chr1,
+            vec![
+                TrackedConsensusPeak::new(
+                    PeakData::new(0, 100u64, 219u64, 155u64).unwrap(),
+                    vec![0, 1],
+                ),
+                TrackedConsensusPeak::new(
+                    PeakData::new(1, 200u64, 299u64, 250u64).unwrap(),
+                    vec![1, 2],
+                ),
+            ],
+        )]);
+        let consensus_peaks = untrack_consensus(tracked_consensus.clone());
+        let summary = ConsensusSummary::new(
+            &input_peaks,
+            &tracked_consensus,
+            &HashMap::from([(chr2, 1)]),
+            &consensus_peaks,
+        );
+        assert_eq!(summary.chromosomes()[&chr1].retained_input_peaks(), 3);
+        assert!((summary.chromosomes()[&chr1].retained_fraction() - 0.75).abs() < 1e-9);
+        assert_eq!(summary.chromosomes()[&chr2].consensus_peaks(), 0);
+        assert_eq!(summary.total().input_peaks(), 5);
+        assert_eq!(summary.total().filtered_consensus_peaks(), 1);
+        assert!((summary.total().mean_width() - 110.0).abs() < 1e-9);
+        assert_eq!(SummaryCounts::default().retained_fraction(), 0.0);
+
+        let mut table = Vec::new();
+        write_summary_table(&mut table, &summary).unwrap();
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            "chromosome\tinput_peaks\tconsensus_peaks\tmean_width\tmedian_width\t\
+            retained_input_fraction\tfiltered_by_min_peaks\n\
+            *\t5\t2\t110.0\t100\t0.600000\t1\n\
+            chr1\t4\t2\t110.0\t100\t0.750000\t0\n\
+            chr2\t1\t0\t0.0\t0\t0.000000\t1\n"
+        );
+    }
+}
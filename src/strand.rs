@@ -0,0 +1,136 @@
+//! This module handles the strand of stranded peaks as produced by CUT&RUN and other
+//! strand-specific protocols, so peaks located on opposite strands can be kept apart
+//! during consensus peak generation.
+
+use std::{collections::HashMap, str::FromStr};
+
+use crate::{
+    chromosome::Chromosome,
+    error::{ApplicationError, ApplicationErrorType},
+    peaks::PeakData,
+};
+
+/// The strand of a peak as defined by column 6 of the
+/// [GA4GH BED v1.0](https://github.com/samtools/hts-specs/blob/master/BEDv1.pdf) definition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Strand {
+    /// The forward strand (`+`).
+    Forward,
+    /// The reverse strand (`-`).
+    Reverse,
+    /// The strand is unknown or the peak is not stranded (`.`).
+    #[default]
+    Unknown,
+}
+
+impl FromStr for Strand {
+    type Err = ApplicationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "+" => Ok(Strand::Forward),
+            "-" => Ok(Strand::Reverse),
+            "." => Ok(Strand::Unknown),
+            _ => Err(ApplicationError::new(
+                ApplicationErrorType::ParseError,
+                format!("\"{}\" is not a valid strand.", s),
+            )
+            .with_hint("The strand must be \"+\", \"-\" or \".\" if the strand is unknown.")),
+        }
+    }
+}
+
+impl std::fmt::Display for Strand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Strand::Forward => write!(f, "+"),
+            Strand::Reverse => write!(f, "-"),
+            Strand::Unknown => write!(f, "."),
+        }
+    }
+}
+
+/// The handling of the strand of raw peaks during consensus peak generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum StrandMode {
+    /// The strand is ignored and peaks of all strands are merged.
+    #[default]
+    Ignore,
+    /// Consensus peaks are generated separately per strand, so peaks on different strands
+    /// are never merged.
+    Separate,
+}
+
+impl std::fmt::Display for StrandMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StrandMode::Ignore => write!(f, "ignore"),
+            StrandMode::Separate => write!(f, "separate"),
+        }
+    }
+}
+
+/// Splits the peaks by strand. Peaks without a known strand are grouped as
+/// [`Strand::Unknown`].
+///
+/// # Parameters
+///
+/// * `peaks` - the peaks grouped by chromosome
+/// * `strands` - the strands of the peaks by peak ID
+pub fn split_by_strand(
+    peaks: HashMap<Chromosome, Vec<PeakData>>,
+    strands: &HashMap<usize, Strand>,
+) -> HashMap<Strand, HashMap<Chromosome, Vec<PeakData>>> {
+    let mut split: HashMap<Strand, HashMap<Chromosome, Vec<PeakData>>> = HashMap::new();
+    for (chromosome, peaks) in peaks {
+        for peak in peaks {
+            let strand = strands.get(&peak.id()).copied().unwrap_or_default();
+            split
+                .entry(strand)
+                .or_default()
+                .entry(chromosome)
+                .or_default()
+                .push(peak);
+        }
+    }
+    split
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strand_from_str() {
+        assert_eq!("+".parse::<Strand>().unwrap(), Strand::Forward);
+        assert_eq!("-".parse::<Strand>().unwrap(), Strand::Reverse);
+        assert_eq!(".".parse::<Strand>().unwrap(), Strand::Unknown);
+        assert!("x".parse::<Strand>().is_err());
+        assert_eq!(Strand::Reverse.to_string(), "-");
+    }
+
+    #[test]
+    fn test_split_by_strand() {
+        let peaks = HashMap::from([(
+            Chromosome::from("chr1"),
+            vec![
+                PeakData::new(0, 0u64, 10u64, 5u64).unwrap(),
+                PeakData::new(1, 5u64, 15u64, 10u64).unwrap(),
+                PeakData::new(2, 20u64, 30u64, 25u64).unwrap(),
+            ],
+        )]);
+        let strands = HashMap::from([(0, Strand::Forward), (1, Strand::Reverse)]);
+        let split = split_by_strand(peaks, &strands);
+        assert_eq!(split.len(), 3);
+        let ids = |strand: Strand| -> Vec<usize> {
+            split[&strand][&Chromosome::from("chr1")]
+                .iter()
+                .map(PeakData::id)
+                .collect()
+        };
+        assert_eq!(ids(Strand::Forward), vec![0]);
+        assert_eq!(ids(Strand::Reverse), vec![1]);
+        assert_eq!(ids(Strand::Unknown), vec![2]);
+    }
+}
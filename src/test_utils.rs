@@ -2,6 +2,8 @@
 
 use std::path::PathBuf;
 
+use crate::{chromosome::Chromosome, peak_set::PeakSet, peaks::PeakData};
+
 /// Returns the path to the test resources.
 pub fn test_resources() -> PathBuf {
     "./test_resources".into()
@@ -12,4 +14,20 @@ pub fn test_output() -> PathBuf {
     let mut path = test_resources();
     path.push("tmp");
     path
-}
\ No newline at end of file
+}
+
+/// Creates a peak set from the specified peaks.
+/// The peaks are numbered in the order they are specified.
+///
+/// # Parameters
+///
+/// * `peaks` - the chromosome, start, (inclusive) end and summit coordinates of the peaks
+pub fn peak_set(peaks: &[(&str, u64, u64, u64)]) -> PeakSet {
+    peaks
+        .iter()
+        .enumerate()
+        .map(|(id, (chromosome, start, end, summit))| {
+            (Chromosome::from(*chromosome), PeakData::new(id, *start, *end, *summit).unwrap())
+        })
+        .collect()
+}
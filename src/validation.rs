@@ -0,0 +1,446 @@
+//! This module validates peak files for compliance with the BED and narrowPeak specifications,
+//! for example to check the output of peak callers before consensus peak generation.
+
+use std::{
+    collections::{HashMap, HashSet, hash_map::Entry},
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use getset::{CopyGetters, Getters};
+
+use crate::{
+    chromosome::Chromosome,
+    error::{ApplicationError, ErrorContext},
+    input::{BedLineParser, InputFormat, open_bed_file},
+    strand::Strand,
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "validation";
+
+/// The severity of a problem found in a peak file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// A violation of the specification, which prevents using the file.
+    Error,
+    /// A deviation from common conventions, which does not prevent using the file,
+    /// such as unsorted or duplicate records.
+    Warning,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem found in a peak file.
+#[derive(CopyGetters, Getters, Debug, Clone, PartialEq, Eq)]
+pub struct ValidationProblem {
+    /// The severity of the problem.
+    #[getset(get_copy = "pub")]
+    severity: Severity,
+    /// The line of the offending record, if the problem refers to a specific record.
+    #[getset(get_copy = "pub")]
+    line: Option<u64>,
+    /// The description of the problem.
+    #[getset(get = "pub")]
+    message: String,
+}
+
+impl ValidationProblem {
+    /// Creates a problem from an error reported while parsing the peak file.
+    ///
+    /// # Parameters
+    ///
+    /// * `error` - the parsing error
+    fn from_error(error: &ApplicationError) -> Self {
+        let mut message = error
+            .internal_messages()
+            .iter()
+            .rev()
+            .cloned()
+            .collect::<Vec<String>>()
+            .join(" ");
+        if let Some(context) = error.context() {
+            match (context.field(), context.value()) {
+                (Some(field), Some(value)) => {
+                    message.push_str(&format!(" (field \"{}\", value \"{}\")", field, value))
+                },
+                (None, Some(value)) => message.push_str(&format!(" (value \"{}\")", value)),
+                _ => {},
+            }
+        }
+        Self {
+            severity: Severity::Error,
+            line: error.context().and_then(|context| context.line()),
+            message,
+        }
+    }
+}
+
+/// The validation result of a peak file.
+#[derive(CopyGetters, Getters, Debug, Clone, PartialEq, Eq)]
+pub struct FileValidation {
+    /// The peak file.
+    #[getset(get = "pub")]
+    file: PathBuf,
+    /// The number of records excluding blank and comment lines.
+    #[getset(get_copy = "pub")]
+    records: usize,
+    /// If the records are grouped by chromosome and sorted by start coordinate per chromosome.
+    #[getset(get_copy = "pub")]
+    sorted: bool,
+    /// The number of records duplicating the chromosome, coordinates and strand of a
+    /// previous record.
+    #[getset(get_copy = "pub")]
+    duplicates: usize,
+    /// The problems found ordered by line.
+    #[getset(get = "pub")]
+    problems: Vec<ValidationProblem>,
+}
+
+impl FileValidation {
+    /// Validates a BED3+ file. Malformed records, invalid coordinates, summits outside of
+    /// the peak and invalid strand values are reported as errors, unsorted and duplicate
+    /// records as warnings. Gzip- and BGZF-compressed files are decompressed transparently.
+    /// The file is read once, so its size is only limited by the number of distinct records.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - the peak file
+    /// * `input_format` - the format defining the interpretation of the columns
+    /// * `max_problems` - the maximum number of problems of each severity to report
+    pub fn from_bed_file<T: AsRef<Path>>(
+        path: T,
+        input_format: InputFormat,
+        max_problems: usize,
+    ) -> Self {
+        let path = path.as_ref();
+        log::info!(target: LOG_TARGET, "Validating {}.", path.display());
+        let mut validation = Self {
+            file: path.to_path_buf(),
+            records: 0,
+            sorted: true,
+            duplicates: 0,
+            problems: Vec::new(),
+        };
+        match open_bed_file(path) {
+            Ok(reader) => validation.check_records(
+                reader,
+                BedLineParser::new(path.display(), input_format),
+                max_problems.max(1),
+            ),
+            Err(err) => validation
+                .problems
+                .push(ValidationProblem::from_error(&err)),
+        }
+        validation
+            .problems
+            .sort_by_key(|problem| (problem.line().unwrap_or(0), problem.severity()));
+        validation
+    }
+
+    /// Parses the records and checks their order and uniqueness.
+    ///
+    /// # Parameters
+    ///
+    /// * `reader` - the reader of the decompressed file content
+    /// * `parser` - the parser of the records
+    /// * `max_problems` - the maximum number of problems of each severity to report
+    fn check_records<R: BufRead>(
+        &mut self,
+        mut reader: R,
+        mut parser: BedLineParser,
+        max_problems: usize,
+    ) {
+        let mut reported: HashMap<Severity, usize> = HashMap::new();
+        let problems = &mut self.problems;
+        let mut add_problem = |problem: ValidationProblem| {
+            let count = reported.entry(problem.severity()).or_default();
+            if *count < max_problems {
+                *count += 1;
+                problems.push(problem);
+            }
+        };
+        let warning = |line: u64, message: String| ValidationProblem {
+            severity: Severity::Warning,
+            line: Some(line),
+            message,
+        };
+        // Records are identified by their interned chromosome, coordinates and strand,
+        // so no names are copied per record.
+        let mut finished_chromosomes: HashSet<Chromosome> = HashSet::new();
+        let mut previous: Option<(Chromosome, u64)> = None;
+        let mut records: HashMap<(Chromosome, u64, u64, Strand), u64> = HashMap::new();
+        let mut line = String::new();
+        let mut line_number = 0;
+        loop {
+            line_number += 1;
+            line.clear();
+            match reader.read_line(&mut line) {
+                Ok(0) => break,
+                Ok(_) => {},
+                Err(err) => {
+                    add_problem(ValidationProblem::from_error(
+                        &ApplicationError::from(err)
+                            .chain("Failed to parse line.")
+                            .with_context(
+                                ErrorContext::new()
+                                    .with_path(self.file.display())
+                                    .with_line(line_number),
+                            ),
+                    ));
+                    break;
+                },
+            }
+            if let Err(err) = parser.parse_line(&line) {
+                add_problem(ValidationProblem::from_error(&err));
+            }
+            let fields: Vec<&str> = line
+                .trim_end_matches(['\n', '\r'])
+                .split(&[' ', '\t'])
+                .filter(|split| !split.is_empty())
+                .collect();
+            if fields.is_empty() || fields[0].starts_with('#') {
+                continue;
+            }
+            self.records += 1;
+            // Malformed records are already reported by the parser.
+            let (Some(start), Some(end)) = (
+                fields.get(1).and_then(|start| start.parse::<u64>().ok()),
+                fields.get(2).and_then(|end| end.parse::<u64>().ok()),
+            ) else {
+                continue;
+            };
+            let chromosome = Chromosome::intern(fields[0]);
+            if self.sorted {
+                let unsorted = match previous {
+                    Some((previous_chromosome, previous_start))
+                        if previous_chromosome == chromosome =>
+                    {
+                        start < previous_start
+                    },
+                    Some((previous_chromosome, _)) => {
+                        finished_chromosomes.insert(previous_chromosome);
+                        finished_chromosomes.contains(&chromosome)
+                    },
+                    None => false,
+                };
+                if unsorted {
+                    self.sorted = false;
+                    add_problem(warning(
+                        line_number,
+                        "The records are not grouped by chromosome and sorted by start \
+                        coordinate, which is required for indexing, starting with this record."
+                            .to_string(),
+                    ));
+                }
+                previous = Some((chromosome, start));
+            }
+            // Invalid strands are already reported by the parser.
+            let Ok(strand) = fields
+                .get(5)
+                .map_or(Ok(Strand::Unknown), |strand| strand.parse())
+            else {
+                continue;
+            };
+            match records.entry((chromosome, start, end, strand)) {
+                Entry::Occupied(first_line) => {
+                    self.duplicates += 1;
+                    add_problem(warning(
+                        line_number,
+                        format!(
+                            "The record duplicates the chromosome, coordinates and strand of line {}.",
+                            first_line.get()
+                        ),
+                    ));
+                },
+                Entry::Vacant(entry) => {
+                    entry.insert(line_number);
+                },
+            }
+        }
+    }
+
+    /// Returns the number of problems of the specified severity.
+    ///
+    /// # Parameters
+    ///
+    /// * `severity` - the severity of the problems to count
+    pub fn count(&self, severity: Severity) -> usize {
+        self.problems
+            .iter()
+            .filter(|problem| problem.severity() == severity)
+            .count()
+    }
+
+    /// Returns `true` if no errors were found.
+    ///
+    /// # Parameters
+    ///
+    /// * `strict` - treats warnings as errors
+    pub fn is_valid(&self, strict: bool) -> bool {
+        self.count(Severity::Error) == 0 && (!strict || self.count(Severity::Warning) == 0)
+    }
+}
+
+/// Writes a human readable report with a summary line per file followed by the problems
+/// found in the file.
+///
+/// # Parameters
+///
+/// * `writer` - the writer to write the report to
+/// * `validations` - the validation results of the peak files
+/// * `strict` - treats warnings as errors
+///
+/// # Errors
+///
+/// Returns an error if the report cannot be written.
+pub fn write_validation_report<W: Write>(
+    mut writer: W,
+    validations: &[FileValidation],
+    strict: bool,
+) -> Result<(), ApplicationError> {
+    let mut report = String::new();
+    for validation in validations {
+        report.push_str(&format!(
+            "{}: {}, {} records, {} errors, {} warnings, {}\n",
+            validation.file().display(),
+            if validation.is_valid(strict) {
+                "valid"
+            } else {
+                "invalid"
+            },
+            validation.records(),
+            validation.count(Severity::Error),
+            validation.count(Severity::Warning),
+            if validation.sorted() {
+                "sorted"
+            } else {
+                "not sorted"
+            }
+        ));
+        for problem in validation.problems() {
+            match problem.line() {
+                Some(line) => report.push_str(&format!(
+                    "  line {}: {}: {}\n",
+                    line,
+                    problem.severity(),
+                    problem.message()
+                )),
+                None => {
+                    report.push_str(&format!("  {}: {}\n", problem.severity(), problem.message()))
+                },
+            }
+        }
+    }
+    writer.write_all(report.as_bytes()).map_err(|err| {
+        ApplicationError::from(err).chain("The validation report could not be written.")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::test_output;
+
+    #[test]
+    fn test_file_validation() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let path = test_output().join("test_file_validation.bed");
+        std::fs::write(
+            &path,
+            "# comment\n\
+            chr1\t100\t200\tpeak_0\t0\t+\n\
+            chr1\t50\t80\tpeak_1\t0\t*\n\
+            chr2\t10\t5\n\
+            chr2\t10\t20\tpeak_3\t0\t-\n\
+            chr1\t100\t200\tpeak_4\t0\t+\n",
+        )
+        .unwrap();
+        let validation = FileValidation::from_bed_file(&path, InputFormat::Auto, 10);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(validation.records(), 5);
+        assert!(!validation.sorted());
+        assert_eq!(validation.duplicates(), 1);
+        assert_eq!(validation.count(Severity::Error), 2);
+        assert_eq!(validation.count(Severity::Warning), 2);
+        assert!(!validation.is_valid(false));
+        let lines: Vec<(Option<u64>, Severity)> = validation
+            .problems()
+            .iter()
+            .map(|problem| (problem.line(), problem.severity()))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (Some(3), Severity::Error),
+                (Some(3), Severity::Warning),
+                (Some(4), Severity::Error),
+                (Some(6), Severity::Warning),
+            ]
+        );
+        assert!(validation.problems()[0].message().contains("value \"*\""));
+    }
+
+    #[test]
+    fn test_file_validation_valid() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let path = test_output().join("test_file_validation_valid.bed");
+        std::fs::write(&path, "chr1\t100\t200\nchr1\t150\t250\nchr2\t10\t20\n").unwrap();
+        let validation = FileValidation::from_bed_file(&path, InputFormat::Bed, 10);
+        std::fs::remove_file(&path).unwrap();
+        assert!(validation.sorted());
+        assert!(validation.problems().is_empty());
+        assert!(validation.is_valid(true));
+        let mut report = Vec::new();
+        write_validation_report(&mut report, &[validation], true).unwrap();
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            format!("{}: valid, 3 records, 0 errors, 0 warnings, sorted\n", path.display())
+        );
+    }
+
+    #[test]
+    fn test_file_validation_max_problems() {
+        std::fs::create_dir_all(test_output()).unwrap();
+        let path = test_output().join("test_file_validation_max_problems.narrowPeak");
+        std::fs::write(
+            &path,
+            "chr1\t18446744073709551610\t18446744073709551615\t.\t0\t.\t0\t0\t0\t10\n\
+            chr1\t10\n\
+            chr1\t10\t20\n",
+        )
+        .unwrap();
+        let validation = FileValidation::from_bed_file(&path, InputFormat::Auto, 1);
+        std::fs::remove_file(&path).unwrap();
+        // All records are checked, but only the first problem is reported.
+        assert_eq!(validation.records(), 3);
+        assert!(!validation.sorted());
+        assert_eq!(validation.count(Severity::Error), 1);
+        assert_eq!(validation.problems()[0].line(), Some(1));
+        assert!(
+            validation.problems()[0]
+                .message()
+                .contains("maximum genomic coordinate")
+        );
+    }
+
+    #[test]
+    fn test_file_validation_missing_file() {
+        let validation = FileValidation::from_bed_file(
+            test_output().join("test_file_validation_missing_file.bed"),
+            InputFormat::Auto,
+            10,
+        );
+        assert_eq!(validation.records(), 0);
+        assert_eq!(validation.count(Severity::Error), 1);
+        assert_eq!(validation.problems()[0].line(), None);
+    }
+}
@@ -0,0 +1,126 @@
+//! This module counts the warnings logged during a run per category,
+//! so degraded input can be reported and optionally treated as failure.
+
+use std::{collections::BTreeMap, sync::Mutex};
+
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+/// The number of logged warnings per category.
+static WARNING_COUNTS: Mutex<BTreeMap<String, usize>> = Mutex::new(BTreeMap::new());
+
+/// A logger counting all warnings per log target before passing
+/// the records to the wrapped logger.
+pub struct WarningCountingLogger {
+    inner: env_logger::Logger,
+}
+
+impl WarningCountingLogger {
+    /// Creates a new logger wrapping the specified logger.
+    ///
+    /// # Parameters
+    ///
+    /// * `inner` - the logger to pass the records to
+    pub fn new(inner: env_logger::Logger) -> Self {
+        Self { inner }
+    }
+
+    /// Installs the logger as global logger.
+    /// Warnings are counted even if the wrapped logger does not output them.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        let max_level = self.inner.filter().max(LevelFilter::Warn);
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+}
+
+impl Log for WarningCountingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() == Level::Warn
+            && let Ok(mut counts) = WARNING_COUNTS.lock()
+        {
+            *counts.entry(record.target().to_string()).or_default() += 1;
+        }
+        if self.inner.matches(record) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Returns the number of logged warnings per category.
+pub fn warning_counts() -> BTreeMap<String, usize> {
+    WARNING_COUNTS
+        .lock()
+        .map(|counts| counts.clone())
+        .unwrap_or_default()
+}
+
+/// Returns a summary of the warning counts, such as `3 warnings (input: 2, retry: 1)`.
+///
+/// # Parameters
+///
+/// * `counts` - the number of warnings per category
+pub fn format_warning_counts(counts: &BTreeMap<String, usize>) -> String {
+    let total: usize = counts.values().sum();
+    let categories: Vec<String> = counts
+        .iter()
+        .map(|(category, count)| format!("{}: {}", category, count))
+        .collect();
+    if categories.is_empty() {
+        "0 warnings".to_string()
+    } else {
+        format!(
+            "{} warning{} ({})",
+            total,
+            if total == 1 { "" } else { "s" },
+            categories.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warning_counting_logger() {
+        let logger = WarningCountingLogger::new(
+            env_logger::Builder::new()
+                .filter_level(LevelFilter::Off)
+                .build(),
+        );
+        assert!(logger.enabled(&Metadata::builder().level(Level::Warn).build()));
+        assert!(!logger.enabled(&Metadata::builder().level(Level::Info).build()));
+        let before = warning_counts()
+            .get("test_warning_counting_logger")
+            .copied()
+            .unwrap_or_default();
+        for level in [Level::Warn, Level::Error, Level::Warn, Level::Info] {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target("test_warning_counting_logger")
+                    .args(format_args!("Test"))
+                    .build(),
+            );
+        }
+        assert_eq!(warning_counts()["test_warning_counting_logger"], before + 2);
+    }
+
+    #[test]
+    fn test_format_warning_counts() {
+        assert_eq!(format_warning_counts(&BTreeMap::new()), "0 warnings");
+        let counts = BTreeMap::from([("retry".to_string(), 1), ("input".to_string(), 2)]);
+        assert_eq!(format_warning_counts(&counts), "3 warnings (input: 2, retry: 1)");
+        let counts = BTreeMap::from([("input".to_string(), 1)]);
+        assert_eq!(format_warning_counts(&counts), "1 warning (input: 1)");
+    }
+}
@@ -0,0 +1,81 @@
+//! This module provides JavaScript bindings for WebAssembly builds.
+//! All data is passed as byte buffers, so no file system access is required.
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::{
+    consensus::ConsensusBuilder, error::ApplicationError, input::bed_buffers_to_peaks,
+    output::peaks_to_bed_buffer, peaks::ConsensusPeakAlgorithm,
+};
+
+/// Creates consensus peaks from BED3+ data and returns them as BED data.
+/// Multiple input files can be passed by concatenating their content.
+///
+/// # Parameters
+///
+/// * `bed` - the content of the BED3+ input files
+/// * `algorithm` - the name of the consensus peak algorithm ("gipfelkreuzer", "simple", "harmonised" or "overlap")
+/// * `max_merge_iterations` - the maximum number of merging iterations of the Gipfelkreuzer algorithm
+/// * `min_peaks_per_consensus` - the minimum number of raw peaks required to form a consensus peak
+/// * `harmonising_distance` - the distance from the summit that is considered a harmonised peak region
+/// * `bed_output_columns` - the number of fields / columns to generate
+///
+/// # Errors
+///
+/// Returns an error message if the algorithm is unknown, the input data is invalid
+/// or the consensus peak generation fails.
+#[wasm_bindgen(js_name = consensusPeaksBed)]
+pub fn consensus_peaks_bed(
+    bed: &[u8],
+    algorithm: &str,
+    max_merge_iterations: usize,
+    min_peaks_per_consensus: usize,
+    harmonising_distance: u32,
+    bed_output_columns: usize,
+) -> Result<Vec<u8>, String> {
+    consensus_peaks_bed_internal(
+        bed,
+        algorithm,
+        max_merge_iterations,
+        min_peaks_per_consensus,
+        harmonising_distance,
+        bed_output_columns,
+    )
+    .map_err(|err| err.to_string())
+}
+
+/// An internal helper function to allow testing without a JavaScript runtime.
+fn consensus_peaks_bed_internal(
+    bed: &[u8],
+    algorithm: &str,
+    max_merge_iterations: usize,
+    min_peaks_per_consensus: usize,
+    harmonising_distance: u32,
+    bed_output_columns: usize,
+) -> Result<Vec<u8>, ApplicationError> {
+    let algorithm: ConsensusPeakAlgorithm = algorithm.parse()?;
+    let peaks = bed_buffers_to_peaks(&[bed])?;
+    let consensus = ConsensusBuilder::new()
+        .algorithm(algorithm)
+        .max_merge_iterations(max_merge_iterations)
+        .min_peaks_per_consensus(min_peaks_per_consensus)
+        .harmonising_distance(harmonising_distance.into())
+        .run(peaks)?;
+    Ok(peaks_to_bed_buffer(&consensus, bed_output_columns))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consensus_peaks_bed() {
+        let bed = b"chr1\t100\t200\tpeak_1\t0\t.\t0\t0\t0\t50\n\
+            chr1\t110\t190\tpeak_2\t0\t.\t0\t0\t0\t38\n\
+            chr2\t5000\t5100\tpeak_3\t0\t.\t0\t0\t0\t50\n";
+        let output = consensus_peaks_bed_internal(bed, "gipfelkreuzer", 20, 2, 250, 3).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "chr1\t105\t195\n");
+        assert!(consensus_peaks_bed_internal(bed, "unknown", 20, 2, 250, 3).is_err());
+        assert!(consensus_peaks_bed_internal(b"chr1\t100\n", "simple", 20, 2, 250, 3).is_err());
+    }
+}
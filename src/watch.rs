@@ -0,0 +1,190 @@
+//! This module detects changes of peak files within a watched input directory.
+
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use gipfelkreuzer::error::ApplicationError;
+
+/// The file extensions of peak files picked up from a watched directory.
+const PEAK_FILE_EXTENSIONS: [&str; 3] = ["bed", "narrowPeak", "broadPeak"];
+
+/// The state of a single peak file used to detect changes.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct FileState {
+    path: PathBuf,
+    length: u64,
+    modified: Option<SystemTime>,
+}
+
+/// Polls a directory for new or changed peak files.
+/// Changes are only reported once the files have not changed between two consecutive polls,
+/// so files that are still being written are not processed prematurely.
+#[derive(Debug)]
+pub struct DirectoryWatcher {
+    /// The watched directory.
+    directory: PathBuf,
+    /// The files to ignore, such as the output file.
+    ignored_files: Vec<PathBuf>,
+    /// The file states observed by the previous poll.
+    observed: Option<Vec<FileState>>,
+    /// The file states of the last reported change.
+    reported: Option<Vec<FileState>>,
+}
+
+impl DirectoryWatcher {
+    /// Creates a new watcher for the specified directory.
+    ///
+    /// # Parameters
+    ///
+    /// * `directory` - the directory to watch
+    /// * `ignored_files` - files within the directory that should not be reported, such as the output file
+    pub fn new<T: Into<PathBuf>>(directory: T, ignored_files: Vec<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            ignored_files: ignored_files
+                .iter()
+                .map(|path| std::path::absolute(path).unwrap_or_else(|_| path.clone()))
+                .collect(),
+            observed: None,
+            reported: None,
+        }
+    }
+
+    /// Checks the directory for changes and returns all peak files sorted by path
+    /// if they changed since the last reported change and are stable since the last poll.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be read.
+    pub fn poll(&mut self) -> Result<Option<Vec<PathBuf>>, ApplicationError> {
+        let current = self.file_states()?;
+        let is_stable = self.observed.as_ref() == Some(&current);
+        self.observed = Some(current.clone());
+        if !is_stable || current.is_empty() || self.reported.as_ref() == Some(&current) {
+            return Ok(None);
+        }
+        let files = current.iter().map(|state| state.path.clone()).collect();
+        self.reported = Some(current);
+        Ok(Some(files))
+    }
+
+    /// Returns the states of all peak files in the directory sorted by path.
+    fn file_states(&self) -> Result<Vec<FileState>, ApplicationError> {
+        let read_error = |err: std::io::Error| {
+            ApplicationError::from(err).chain(format!(
+                "The watched directory \"{}\" could not be read.",
+                self.directory.display()
+            ))
+        };
+        let mut states = Vec::new();
+        for entry in std::fs::read_dir(&self.directory).map_err(read_error)? {
+            let entry = entry.map_err(read_error)?;
+            let path = entry.path();
+            if !is_peak_file(&path)
+                || self
+                    .ignored_files
+                    .iter()
+                    .any(|ignored| std::path::absolute(&path).is_ok_and(|path| &path == ignored))
+            {
+                continue;
+            }
+            // Files might be removed between listing and reading the metadata.
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_file() {
+                states.push(FileState {
+                    path,
+                    length: metadata.len(),
+                    modified: metadata.modified().ok(),
+                });
+            }
+        }
+        states.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(states)
+    }
+}
+
+/// Returns `true` if the path is a visible file with a peak file extension.
+///
+/// # Parameters
+///
+/// * `path` - the path to check
+fn is_peak_file(path: &Path) -> bool {
+    let is_hidden = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_none_or(|name| name.starts_with('.'));
+    !is_hidden
+        && path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| {
+                PEAK_FILE_EXTENSIONS
+                    .iter()
+                    .any(|peak_extension| peak_extension.eq_ignore_ascii_case(extension))
+            })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::test_output;
+
+    use super::*;
+
+    #[test]
+    fn test_directory_watcher_poll() {
+        let directory = test_output().join("test_directory_watcher_poll");
+        if directory.exists() {
+            std::fs::remove_dir_all(&directory).unwrap();
+        }
+        std::fs::create_dir_all(&directory).unwrap();
+        let output = directory.join("consensus.bed");
+        let mut watcher = DirectoryWatcher::new(&directory, vec![output.clone()]);
+        assert_eq!(watcher.poll().unwrap(), None);
+        assert_eq!(watcher.poll().unwrap(), None);
+
+        std::fs::write(directory.join("sample_2.narrowPeak"), "chr1\t10\t20\n").unwrap();
+        std::fs::write(directory.join("sample_1.narrowPeak"), "chr1\t10\t20\n").unwrap();
+        std::fs::write(directory.join("notes.txt"), "irrelevant").unwrap();
+        std::fs::write(directory.join(".sample_3.narrowPeak"), "").unwrap();
+        std::fs::write(&output, "chr1\t10\t20\n").unwrap();
+        // New files are only reported once they are stable.
+        assert_eq!(watcher.poll().unwrap(), None);
+        assert_eq!(
+            watcher.poll().unwrap(),
+            Some(vec![
+                directory.join("sample_1.narrowPeak"),
+                directory.join("sample_2.narrowPeak")
+            ])
+        );
+        assert_eq!(watcher.poll().unwrap(), None);
+
+        std::fs::write(directory.join("sample_1.narrowPeak"), "chr1\t10\t20\nchr1\t30\t40\n")
+            .unwrap();
+        assert_eq!(watcher.poll().unwrap(), None);
+        assert_eq!(watcher.poll().unwrap().map(|files| files.len()), Some(2));
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_directory_watcher_missing_directory() {
+        let mut watcher = DirectoryWatcher::new(
+            test_output().join("test_directory_watcher_missing_directory"),
+            Vec::new(),
+        );
+        assert!(watcher.poll().is_err());
+    }
+
+    #[test]
+    fn test_is_peak_file() {
+        assert!(is_peak_file(Path::new("dir/sample.narrowPeak")));
+        assert!(is_peak_file(Path::new("sample.BED")));
+        assert!(is_peak_file(Path::new("sample.broadPeak")));
+        assert!(!is_peak_file(Path::new("sample.txt")));
+        assert!(!is_peak_file(Path::new(".sample.bed")));
+        assert!(!is_peak_file(Path::new("sample")));
+    }
+}
@@ -0,0 +1,234 @@
+//! This module exports fixed-size windows around peak summits, which are the canonical input
+//! of de-novo motif discovery and footprinting tools.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    chromosome::Chromosome,
+    error::ApplicationError,
+    peaks::PeakData,
+    retry::{Retrying, retry},
+};
+
+/// The log target of this module.
+const LOG_TARGET: &str = "windows";
+
+/// The default number of bases flanking the summit on each side of a summit window.
+pub const DEFAULT_SUMMIT_FLANK: u64 = 100;
+
+/// The number of sequence characters per FASTA line.
+const FASTA_LINE_WIDTH: usize = 60;
+
+/// Returns the windows of the specified number of bases on each side of the peak summits.
+/// The windows keep the identifiers and summits of their peaks.
+/// Windows are clipped at the start of the chromosome.
+///
+/// # Parameters
+///
+/// * `peaks` - the peaks grouped by chromosome
+/// * `flank` - the number of bases on each side of the summit
+pub fn summit_windows(
+    peaks: &HashMap<Chromosome, Vec<PeakData>>,
+    flank: u64,
+) -> HashMap<Chromosome, Vec<PeakData>> {
+    peaks
+        .iter()
+        .map(|(chromosome, chromosome_peaks)| {
+            let windows = chromosome_peaks
+                .iter()
+                .map(|peak| {
+                    PeakData::new(
+                        peak.id(),
+                        peak.summit().saturating_sub(flank),
+                        peak.summit().saturating_add(flank),
+                        peak.summit(),
+                    )
+                    .expect("The window must be valid as it contains the summit.")
+                })
+                .collect();
+            (*chromosome, windows)
+        })
+        .collect()
+}
+
+/// Writes the sequences of the windows as FASTA file.
+/// The genome is read one chromosome at a time, so only the sequence of a single chromosome
+/// is kept in memory. Windows are clipped at the end of the chromosome and written in the
+/// order of the chromosomes in the genome and by coordinates.
+/// The records are named after the window, such as `consensus_0::chr1:100-300`.
+/// Windows on chromosomes missing from the genome are skipped with a warning.
+///
+/// # Parameters
+///
+/// * `genome` - the path of the genome FASTA file
+/// * `windows` - the windows grouped by chromosome
+/// * `path` - the path of the output FASTA file
+///
+/// # Errors
+///
+/// Returns an error if the genome cannot be read or the output cannot be written.
+pub fn write_window_sequences<T: AsRef<Path>, U: AsRef<Path>>(
+    genome: T,
+    windows: &HashMap<Chromosome, Vec<PeakData>>,
+    path: U,
+) -> Result<(), ApplicationError> {
+    let genome = genome.as_ref();
+    let path = path.as_ref();
+    log::info!(
+        target: LOG_TARGET,
+        "Writing the sequences of {} summit windows to {}.",
+        windows.values().map(Vec::len).sum::<usize>(),
+        path.display()
+    );
+    let description = format!("Reading {}", genome.display());
+    let read_error = |err: std::io::Error| {
+        ApplicationError::from(err)
+            .chain(format!("The genome \"{}\" could not be read.", genome.display()))
+    };
+    let write_error = |err: std::io::Error| {
+        ApplicationError::from(err).chain(format!(
+            "The summit window sequences \"{}\" could not be written.",
+            path.display()
+        ))
+    };
+    let genome_file = retry(&description, || File::open(genome)).map_err(read_error)?;
+    let reader = BufReader::new(Retrying::new(genome_file, &description));
+    let output_file = File::create(path).map_err(write_error)?;
+    let mut writer = BufWriter::new(output_file);
+    let mut missing_chromosomes: Vec<Chromosome> = windows.keys().copied().collect();
+    let mut chromosome: Option<Chromosome> = None;
+    let mut sequence: Vec<u8> = Vec::new();
+    for line in reader.lines() {
+        let line = line.map_err(read_error)?;
+        if let Some(header) = line.strip_prefix('>') {
+            if let Some(chromosome) = chromosome {
+                write_chromosome_windows(&mut writer, chromosome, &sequence, windows)
+                    .map_err(write_error)?;
+            }
+            let name = header.split_whitespace().next().unwrap_or_default();
+            // Only the sequences of chromosomes with windows are retained.
+            chromosome = Chromosome::lookup(name).filter(|name| windows.contains_key(name));
+            missing_chromosomes.retain(|missing| Some(*missing) != chromosome);
+            sequence.clear();
+        } else if chromosome.is_some() {
+            sequence.extend_from_slice(line.trim_end().as_bytes());
+        }
+    }
+    if let Some(chromosome) = chromosome {
+        write_chromosome_windows(&mut writer, chromosome, &sequence, windows)
+            .map_err(write_error)?;
+    }
+    writer.flush().map_err(write_error)?;
+    if !missing_chromosomes.is_empty() {
+        missing_chromosomes.sort_unstable();
+        log::warn!(
+            target: LOG_TARGET,
+            "The summit windows on the chromosomes {:?} were skipped, \
+            as the chromosomes are missing from the genome {}.",
+            missing_chromosomes,
+            genome.display()
+        );
+    }
+    Ok(())
+}
+
+/// Writes the sequences of the windows of a single chromosome as FASTA records.
+///
+/// # Parameters
+///
+/// * `writer` - the writer to write the records to
+/// * `chromosome` - the chromosome
+/// * `sequence` - the sequence of the chromosome
+/// * `windows` - the windows grouped by chromosome
+fn write_chromosome_windows<W: Write>(
+    writer: &mut W,
+    chromosome: Chromosome,
+    sequence: &[u8],
+    windows: &HashMap<Chromosome, Vec<PeakData>>,
+) -> std::io::Result<()> {
+    let mut chromosome_windows: Vec<&PeakData> =
+        windows.get(&chromosome).into_iter().flatten().collect();
+    chromosome_windows.sort_by_key(|window| (window.start(), window.end(), window.id()));
+    for window in chromosome_windows {
+        let start = (window.start() as usize).min(sequence.len());
+        let end = (window.end() as usize + 1).min(sequence.len());
+        writeln!(
+            writer,
+            ">consensus_{}::{}:{}-{}",
+            window.id(),
+            chromosome,
+            window.start(),
+            window.end()
+        )?;
+        for line in sequence[start..end].chunks(FASTA_LINE_WIDTH) {
+            writer.write_all(line)?;
+            writer.write_all(b"\n")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::{test_output, test_resources};
+
+    use super::*;
+
+    #[test]
+    fn test_summit_windows() {
+        let chromosome = Chromosome::from("chr1");
+        let peaks = HashMap::from([(
+            chromosome,
+            vec![
+                PeakData::new(0, 100u64, 399u64, 250u64).unwrap(),
+                PeakData::new(1, 0u64, 99u64, 5u64).unwrap(),
+            ],
+        )]);
+        let windows = summit_windows(&peaks, 10);
+        assert_eq!(
+            windows[&chromosome],
+            vec![
+                PeakData::new(0, 240u64, 260u64, 250u64).unwrap(),
+                PeakData::new(1, 0u64, 15u64, 5u64).unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_window_sequences() {
+        let windows = HashMap::from([
+            (
+                Chromosome::from("chr1"),
+                vec![
+                    PeakData::new(1, 8u64, 12u64, 10u64).unwrap(),
+                    PeakData::new(0, 0u64, 2u64, 1u64).unwrap(),
+                ],
+            ),
+            (Chromosome::from("chr2"), vec![PeakData::new(2, 60u64, 80u64, 70u64).unwrap()]),
+            (
+                Chromosome::from("chrUn_test_write_window_sequences"),
+                vec![PeakData::new(3, 0u64, 2u64, 1u64).unwrap()],
+            ),
+        ]);
+        std::fs::create_dir_all(test_output()).unwrap();
+        let output_path = test_output().join("test_write_window_sequences.fa");
+        write_window_sequences(
+            test_resources().join("input_test_genome.fa"),
+            &windows,
+            &output_path,
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output_path).unwrap(),
+            ">consensus_0::chr1:0-2\nACG\n\
+            >consensus_1::chr1:8-12\nACGTA\n\
+            >consensus_2::chr2:60-80\nGGGG\n"
+        );
+        std::fs::remove_file(output_path).unwrap();
+    }
+}